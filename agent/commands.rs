@@ -0,0 +1,421 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use eth_agent_core::{
+    cast_script, forge_script, render_portfolio, render_report, rig::providers::anthropic, search_provider, unsigned_bundle, EthAgent, EtherscanClient,
+    ReportFormat, BundleExecution, DuneClient, PlanStatus, RunRecord, SafeBundle, SearchProvider, SearchProviderKind, StepStatus, TenderlyClient,
+};
+use alloy_primitives::{utils::format_units, U256};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::secrets::{self, SecretSource, KNOWN_SECRETS};
+
+// Blocks sampled for `/portfolio`, the oldest one `days` back
+const PORTFOLIO_SAMPLES: u32 = 12;
+
+// Commands handled by the REPL itself instead of being sent to the agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    Quit,
+    Help,
+    Clear,
+    KeysList,
+    KeysSet(String),
+    Runs,
+    RunsList { since: Option<String> },
+    Export { run_id: String, path: PathBuf },
+    Bundle { run_id: String, path: PathBuf },
+    ExportScript { run_id: String, path: PathBuf },
+    ExportCast { run_id: String, path: PathBuf },
+    RunBundle { path: PathBuf, execution: BundleExecution },
+    // Holdings over time and P&L of an account, with extra tokens to include
+    Portfolio { address: String, days: u32, path: PathBuf, tokens: Vec<String> },
+    // A pasted EIP-681 `ethereum:` URI, optionally followed by `from <sender>`
+    Pay { uri: String, from: Option<String> },
+    Undo,
+    WhatIf(String),
+    BlocklistRefresh,
+    // Tools of the MCP server, optionally only those whose name contains a filter
+    Tools(Option<String>),
+    Plan,
+    Accounts,
+    // Run the last run again from where it failed, or only one step of its plan
+    Retry(Option<u32>),
+    Unknown(String),
+}
+
+fn is_payment_uri(word: &str) -> bool {
+    word.get(..9).is_some_and(|scheme| scheme.eq_ignore_ascii_case("ethereum:"))
+}
+
+// Returns None for input that should be run as a prompt
+pub fn parse(input: &str) -> Option<ReplCommand> {
+    match input.to_lowercase().as_str() {
+        "quit" | "exit" | "q" => return Some(ReplCommand::Quit),
+        "help" | "h" => return Some(ReplCommand::Help),
+        "clear" | "cls" => return Some(ReplCommand::Clear),
+        _ => {}
+    }
+
+    let words: Vec<&str> = input.split_whitespace().collect();
+    match words.as_slice() {
+        [uri] if is_payment_uri(uri) => {
+            return Some(ReplCommand::Pay {
+                uri: uri.to_string(),
+                from: None,
+            })
+        }
+        [uri, "from", from] if is_payment_uri(uri) => {
+            return Some(ReplCommand::Pay {
+                uri: uri.to_string(),
+                from: Some(from.to_string()),
+            })
+        }
+        _ => {}
+    }
+
+    let command = input.strip_prefix('/')?;
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let command = match words.as_slice() {
+        ["quit"] | ["exit"] => ReplCommand::Quit,
+        ["help"] => ReplCommand::Help,
+        ["clear"] => ReplCommand::Clear,
+        ["keys"] => ReplCommand::KeysList,
+        ["keys", "set", name] => ReplCommand::KeysSet(name.to_lowercase()),
+        ["runs"] => ReplCommand::Runs,
+        ["runs", "list"] => ReplCommand::RunsList { since: None },
+        ["runs", "list", since] => ReplCommand::RunsList {
+            since: Some(since.to_string()),
+        },
+        ["undo"] => ReplCommand::Undo,
+        ["blocklist", "refresh"] => ReplCommand::BlocklistRefresh,
+        ["tools"] => ReplCommand::Tools(None),
+        ["tools", filter] => ReplCommand::Tools(Some(filter.to_lowercase())),
+        ["plan"] => ReplCommand::Plan,
+        ["accounts"] => ReplCommand::Accounts,
+        ["retry"] => ReplCommand::Retry(None),
+        ["retry", step] if step.parse::<u32>().is_ok() => ReplCommand::Retry(step.parse().ok()),
+        ["whatif", ..] if words.len() > 1 => ReplCommand::WhatIf(command["whatif".len()..].trim().to_string()),
+        ["export", run_id, path] => ReplCommand::Export {
+            run_id: run_id.to_string(),
+            path: PathBuf::from(path),
+        },
+        ["bundle", run_id, path] => ReplCommand::Bundle {
+            run_id: run_id.to_string(),
+            path: PathBuf::from(path),
+        },
+        ["export-script", run_id, path] => ReplCommand::ExportScript {
+            run_id: run_id.to_string(),
+            path: PathBuf::from(path),
+        },
+        ["export-cast", run_id, path] => ReplCommand::ExportCast {
+            run_id: run_id.to_string(),
+            path: PathBuf::from(path),
+        },
+        ["portfolio", address, days, path, tokens @ ..] if days.parse::<u32>().is_ok() => ReplCommand::Portfolio {
+            address: address.to_string(),
+            days: days.parse().unwrap_or_default(),
+            path: PathBuf::from(path),
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+        },
+        ["run-bundle", path] => ReplCommand::RunBundle {
+            path: PathBuf::from(path),
+            execution: BundleExecution::Propose { proposer: None },
+        },
+        ["run-bundle", path, "propose", proposer] => ReplCommand::RunBundle {
+            path: PathBuf::from(path),
+            execution: BundleExecution::Propose {
+                proposer: Some(proposer.to_string()),
+            },
+        },
+        ["run-bundle", path, "send", from] => ReplCommand::RunBundle {
+            path: PathBuf::from(path),
+            execution: BundleExecution::Send { from: from.to_string() },
+        },
+        _ => ReplCommand::Unknown(input.to_string()),
+    };
+    Some(command)
+}
+
+pub fn list_keys() {
+    println!("🔑 Keys:");
+    for (name, env_var) in KNOWN_SECRETS {
+        let source = match secrets::get(name) {
+            Some((_, SecretSource::Keyring)) => "OS keyring".to_string(),
+            Some((_, SecretSource::Env)) => format!("env {}", env_var),
+            None => "not set".to_string(),
+        };
+        println!("  {:<10} - {}", name, source);
+    }
+}
+
+// Store a key in the OS keyring and hand it to the running agent
+pub async fn set_key(name: &str, agent: &mut EthAgent<anthropic::Client>) -> Result<()> {
+    if !secrets::is_known(name) {
+        let names: Vec<&str> = KNOWN_SECRETS.iter().map(|(name, _)| *name).collect();
+        return Err(anyhow::anyhow!("Unknown key {}, expected one of: {}", name, names.join(", ")));
+    }
+
+    let secret = secrets::read_hidden(&format!("Enter {} key: ", name)).await?;
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("No key entered, nothing changed"));
+    }
+
+    secrets::set(name, &secret)?;
+
+    let search_kind = agent.config().search.provider;
+    if name == "anthropic" {
+        agent.set_provider_client(anthropic::Client::new(&secret));
+    } else if name == "etherscan" {
+        let etherscan = EtherscanClient::new(secret, &agent.config().etherscan);
+        agent.set_etherscan(Some(etherscan));
+    } else if name == "dune" {
+        let dune = DuneClient::new(secret, &agent.config().dune);
+        agent.set_dune(Some(dune));
+    } else if name == "tenderly" {
+        let tenderly = TenderlyClient::new(secret, &agent.config().tenderly);
+        agent.set_tenderly(Some(tenderly));
+    } else if search_kind.key_name() == Some(name) {
+        agent.set_search_provider(search_provider(search_kind, Some(secret)).ok());
+    }
+
+    println!("🔑 Stored {} key in the OS keyring", name);
+    Ok(())
+}
+
+// The configured search provider with its key, web search is disabled when the key is missing
+pub fn load_search_provider(kind: SearchProviderKind) -> Option<Arc<dyn SearchProvider>> {
+    let api_key = kind.key_name().and_then(secrets::get).map(|(key, _)| key);
+    match search_provider(kind, api_key) {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            let name = kind.key_name().unwrap_or_default();
+            println!("⚠️  {}, web search is disabled until you run `/keys set {}`", e, name);
+            None
+        }
+    }
+}
+
+pub fn list_runs(agent: &EthAgent<anthropic::Client>) {
+    let runs = agent.runs();
+    if runs.is_empty() {
+        println!("No runs yet");
+        return;
+    }
+
+    println!("📜 Runs:");
+    for run in runs {
+        print_run(&run, false);
+    }
+}
+
+// Output shown per step by `/plan`, the full output is in the run report
+const PLAN_OUTPUT_CHARS: usize = 300;
+
+// Steps of the last run's plan with how far each got and what it returned
+pub fn show_plan(agent: &EthAgent<anthropic::Client>) {
+    let Some(run) = agent.last_run() else {
+        println!("No runs yet");
+        return;
+    };
+    if run.steps.is_empty() {
+        println!("Run {} has no plan: {:?}", run.id, run.status);
+        return;
+    }
+
+    println!("🗺️  Plan of run {} ({:?}): {}", run.id, run.status, run.prompt.natural_language);
+    for step in &run.steps {
+        let status = match &step.status {
+            StepStatus::Planned => "⏳ planned".to_string(),
+            StepStatus::Executing => "▶️  executing".to_string(),
+            StepStatus::Completed => "✅ completed".to_string(),
+            StepStatus::Failed(reason) => format!("❌ failed: {}", reason),
+            StepStatus::Skipped => "⏭️  skipped".to_string(),
+        };
+        println!("  {}. [{}] {} - {}", step.step_number, step.agent_name, step.agent_prompt, status);
+        if let Some(output) = &step.output {
+            let mut shown: String = output.chars().take(PLAN_OUTPUT_CHARS).collect();
+            if shown.len() < output.len() {
+                shown.push('…');
+            }
+            println!("     → {}", shown);
+        }
+    }
+    if matches!(run.status, PlanStatus::Failed(_) | PlanStatus::MaxStepsReached) {
+        println!("💡 /retry resumes at the first unfinished step, /retry <step> runs one step again");
+    }
+}
+
+// Saved runs, including earlier sessions', optionally from a date ("2024-05-01") or time (RFC 3339) on
+pub fn list_saved_runs(agent: &EthAgent<anthropic::Client>, since: Option<&str>) -> Result<()> {
+    let since = since.map(parse_since).transpose()?;
+    let runs = agent.saved_runs(since)?;
+    if runs.is_empty() {
+        println!("No saved runs");
+        return Ok(());
+    }
+
+    println!("📜 Saved runs:");
+    for run in runs {
+        print_run(&run, true);
+    }
+    Ok(())
+}
+
+fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid date {}, expected e.g. 2024-05-01", since))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+fn print_run(run: &RunRecord, with_date: bool) {
+    let started = if with_date { format!("{}  ", run.started_at.format("%Y-%m-%d %H:%M")) } else { String::new() };
+    println!(
+        "  {}  {}{:<12} {}",
+        &run.id[..8.min(run.id.len())],
+        started,
+        format!("{:?}", run.status).split('(').next().unwrap_or_default(),
+        run.prompt.natural_language
+    );
+}
+
+fn find_run(agent: &EthAgent<anthropic::Client>, run_id: &str) -> Result<RunRecord> {
+    match run_id {
+        "last" => agent.last_run(),
+        run_id => agent.find_run(run_id),
+    }
+    .ok_or_else(|| anyhow::anyhow!("No run matches {}, see /runs", run_id))
+}
+
+// Write a Markdown (or HTML, for .html paths) report of a run, `last` picks the latest run
+pub fn export_run(agent: &EthAgent<anthropic::Client>, run_id: &str, path: &PathBuf) -> Result<()> {
+    let run = find_run(agent, run_id)?;
+
+    let report = render_report(&run, &agent.config().explorer_url, agent.labels(), ReportFormat::from_path(path));
+    std::fs::write(path, report)?;
+
+    println!("📄 Exported run {} to {}", run.id, path.display());
+    Ok(())
+}
+
+// Write the unsigned transactions of a run as JSON for an external wallet, `last` picks the latest run
+pub fn export_bundle(agent: &EthAgent<anthropic::Client>, run_id: &str, path: &PathBuf) -> Result<()> {
+    let run = find_run(agent, run_id)?;
+    let bundle = unsigned_bundle(&run);
+    let count = bundle["transactions"].as_array().map(Vec::len).unwrap_or_default();
+    if count == 0 {
+        return Err(anyhow::anyhow!("Run {} has no unsigned transactions, set unsigned_transactions = true", run.id));
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&bundle)?)?;
+
+    println!("🖊️  Exported {} unsigned transaction(s) of run {} to {}", count, run.id, path.display());
+    Ok(())
+}
+
+// Write the transactions a run sent as a `forge script`, `last` picks the latest run
+pub fn export_script(agent: &EthAgent<anthropic::Client>, run_id: &str, path: &PathBuf) -> Result<()> {
+    let run = find_run(agent, run_id)?;
+    std::fs::write(path, forge_script(&run)?)?;
+
+    println!("📜 Exported the transactions of run {} as a forge script to {}", run.id, path.display());
+    Ok(())
+}
+
+// Write what a run did on chain as a shell script of cast commands, `last` picks the latest run
+pub fn export_cast(agent: &EthAgent<anthropic::Client>, run_id: &str, path: &PathBuf) -> Result<()> {
+    let run = find_run(agent, run_id)?;
+    std::fs::write(path, cast_script(&run)?)?;
+
+    println!("📜 Exported run {} as cast commands to {}, run it with `bash {}`", run.id, path.display(), path.display());
+    Ok(())
+}
+
+// Write a holdings-over-time and P&L report of an account (Markdown, or HTML for .html paths)
+pub async fn export_portfolio(agent: &EthAgent<anthropic::Client>, address: &str, days: u32, tokens: &[String], path: &PathBuf) -> Result<()> {
+    println!("📈 Sampling the holdings of {} over the last {} days...", address, days);
+    let history = agent.portfolio_history(address, tokens, days, PORTFOLIO_SAMPLES).await?;
+    std::fs::write(path, render_portfolio(&history, agent.labels(), ReportFormat::from_path(path)))?;
+
+    println!("📄 Exported the portfolio of {} to {} (change {:+.2} USD)", address, path.display(), history.change_usd());
+    Ok(())
+}
+
+// Ask foundry-mcp which tools it has now and print each with its parameters
+pub async fn list_tools(agent: &EthAgent<anthropic::Client>, filter: Option<&str>) -> Result<()> {
+    let mut tools = agent.mcp_tools().await?;
+    tools.retain(|tool| filter.is_none_or(|filter| tool.name.to_lowercase().contains(filter)));
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    if tools.is_empty() {
+        println!("No tools match");
+        return Ok(());
+    }
+
+    println!("🧰 foundry-mcp tools ({}):", tools.len());
+    for tool in &tools {
+        println!("\n  {}", tool.name);
+        if let Some(description) = &tool.description {
+            println!("    {}", description);
+        }
+        let required: Vec<&str> = tool
+            .input_schema
+            .get("required")
+            .and_then(|required| required.as_array())
+            .map(|required| required.iter().filter_map(|name| name.as_str()).collect())
+            .unwrap_or_default();
+        let Some(properties) = tool.input_schema.get("properties").and_then(|properties| properties.as_object()) else {
+            continue;
+        };
+        for (name, schema) in properties {
+            let kind = schema_type(schema);
+            let optional = if required.contains(&name.as_str()) { "" } else { ", optional" };
+            let description = schema.get("description").and_then(|description| description.as_str()).unwrap_or_default();
+            println!("    - {} ({}{}) {}", name, kind, optional, description);
+        }
+    }
+    Ok(())
+}
+
+// A parameter's JSON schema type, e.g. "string", "integer | null" or "array of object"
+fn schema_type(schema: &serde_json::Value) -> String {
+    let kind = match schema.get("type") {
+        Some(serde_json::Value::String(kind)) => kind.clone(),
+        Some(serde_json::Value::Array(kinds)) => kinds.iter().filter_map(|kind| kind.as_str()).collect::<Vec<_>>().join(" | "),
+        _ if schema.get("$ref").is_some() => "object".to_string(),
+        _ => "any".to_string(),
+    };
+    match schema.get("items") {
+        Some(items) if kind.starts_with("array") => format!("{} of {}", kind, schema_type(items)),
+        _ => kind,
+    }
+}
+
+// Accounts the node can send from and the labeled addresses, with balances and nonces read now
+pub async fn list_accounts(agent: &EthAgent<anthropic::Client>) -> Result<()> {
+    let accounts = agent.accounts().await?;
+    if accounts.is_empty() {
+        println!("No accounts: the node manages none and no addresses are labeled in [labels]");
+        return Ok(());
+    }
+
+    println!("👛 Accounts:");
+    for account in accounts {
+        let balance = U256::from_str(&account.balance)
+            .ok()
+            .and_then(|balance| format_units(balance, "ether").ok())
+            .map(|balance| format!("{} ETH", balance))
+            .unwrap_or_else(|| format!("{} wei", account.balance));
+        let label = account.label.map(|label| format!("  {}", label)).unwrap_or_default();
+        println!("  {}  {:>28}  nonce {:<6} {}{}", account.address, balance, account.nonce, account.source, label);
+    }
+    Ok(())
+}
+
+// Read a Safe Transaction Builder export
+pub fn load_bundle(path: &PathBuf) -> Result<SafeBundle> {
+    let json = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Could not read {}: {}", path.display(), e))?;
+    SafeBundle::from_json(&json)
+}
@@ -0,0 +1,34 @@
+use std::io::{self, Write};
+
+use async_trait::async_trait;
+use eth_agent_core::Interaction;
+
+// Asks on the terminal, the REPL isn't reading stdin while a run is in progress
+pub struct TerminalInteraction;
+
+#[async_trait]
+impl Interaction for TerminalInteraction {
+    async fn confirm(&self, message: &str) -> bool {
+        print!("\n⚠️  {} [y/N] ", message);
+        let answer = read_answer().await.unwrap_or_default();
+
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    }
+
+    async fn ask(&self, question: &str) -> Option<String> {
+        print!("\n❓ {}\n> ", question);
+        read_answer().await
+    }
+}
+
+async fn read_answer() -> Option<String> {
+    let _ = io::stdout().flush();
+
+    tokio::task::spawn_blocking(|| {
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).map(|_| answer)
+    })
+    .await
+    .ok()?
+    .ok()
+}
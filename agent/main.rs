@@ -1,40 +1,121 @@
-use std::env;
 use std::collections::HashMap;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use anyhow::Result;
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::filter::EnvFilter;
 
 // Import modules
-mod types;
-mod mcp_client;
-mod agent;
-mod tools;
+mod secrets;
+mod commands;
+mod interaction;
 
 
-use types::*;
-use agent::EthAgent;
-use rig::providers::anthropic;
+use eth_agent_core::{
+    rig::providers::anthropic, AgentConfig, AgentError, AgentEvent, AgentResult, Artifact, DuneClient, EthAgent, EtherscanClient,
+    EventSink, Interaction, Notifier, PaymentRequest, RedactingWriter, RunRecord, TenderlyClient, UserPrompt, WebhookNotifier,
+    watch_config,
+};
+use commands::ReplCommand;
+use interaction::TerminalInteraction;
 
-const ANTHROPIC_MODEL: &str = "claude-3-5-haiku-20241022";
-const EVALUATION_THRESHOLD: u32 = 70;
+const DEFAULT_RUNS_DATABASE: &str = "eth-agent-runs.db";
+// ScamSniffer's community-maintained list of drainer and phishing addresses
+const DEFAULT_BLOCKLIST: &str = "https://raw.githubusercontent.com/scamsniffer/scam-database/main/blacklist/address.json";
+const DEFAULT_BLOCKLIST_CACHE: &str = "eth-agent-blocklists";
+// How often the config file is checked for edits
+const CONFIG_POLL_SECS: u64 = 2;
+
+// The CLI keeps its run history between sessions unless another database is configured. Likewise
+// it checks transactions against ScamSniffer's blocklist unless other sources are set.
+fn with_cli_defaults(mut config: AgentConfig) -> AgentConfig {
+    config.runs.database.get_or_insert_with(|| PathBuf::from(DEFAULT_RUNS_DATABASE));
+    config.blocklist.sources.get_or_insert_with(|| vec![DEFAULT_BLOCKLIST.to_string()]);
+    config.blocklist.cache_dir.get_or_insert_with(|| PathBuf::from(DEFAULT_BLOCKLIST_CACHE));
+    config
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
     let filter = EnvFilter::new("info,rig=warn,serve_inner=warn,rmcp::service=warn");
 
+    // Secrets pasted into prompts or returned by tools never reach the log
     tracing_subscriber::fmt()
         .with_max_level(Level::INFO)
         .with_env_filter(filter)
+        .with_writer(|| RedactingWriter::new(io::stdout()))
         .init();
     info!("Starting ETH Agent with MCP-based Foundry integration");
 
-    let brave_search_api_key = env::var("BRAVE_SEARCH_API_KEY").expect("BRAVE_SEARCH_API_KEY must be set");
+    // Keys come from the OS keyring, falling back to env vars
+    let anthropic_api_key = match secrets::get("anthropic") {
+        Some((key, _)) => key,
+        None => {
+            println!("No Anthropic API key found in the OS keyring or ANTHROPIC_API_KEY");
+            let key = secrets::read_hidden("Enter Anthropic API key: ").await?;
+            if key.is_empty() {
+                return Err(anyhow::anyhow!("An Anthropic API key is required"));
+            }
+            if let Err(e) = secrets::set("anthropic", &key) {
+                warn!("Could not store the Anthropic API key in the OS keyring: {}", e);
+            }
+            key
+        }
+    };
+
+    let config = with_cli_defaults(AgentConfig::load()?);
+    info!("Loaded config: {:?}", config);
+
+    let search_provider = commands::load_search_provider(config.search.provider);
+    let etherscan = secrets::get("etherscan").map(|(key, _)| EtherscanClient::new(key, &config.etherscan));
+    if etherscan.is_none() {
+        println!("⚠️  No Etherscan key set, account history is disabled until you run `/keys set etherscan`");
+    }
+    // Tenderly is optional and only used when configured, so no warning without it
+    let tenderly = secrets::get("tenderly").map(|(key, _)| TenderlyClient::new(key, &config.tenderly));
+    let dune = secrets::get("dune").map(|(key, _)| DuneClient::new(key, &config.dune));
+    // Without a secret the agent still notifies the configured webhooks, just unsigned
+    let webhooks = secrets::get("webhook")
+        .filter(|_| !config.webhooks.urls.is_empty())
+        .map(|(secret, _)| WebhookNotifier::new(Some(secret), &config.webhooks));
+    let notifier = Notifier::from_config(
+        &config.notifications,
+        secrets::get("slack").map(|(url, _)| url),
+        secrets::get("smtp").map(|(password, _)| password),
+    )?;
 
     // Create ETH Agent
     //let mut agent = EthAgent::<openai::Client>::new(api_key, Some(10))?;
-    let mut agent = EthAgent::<anthropic::Client>::new(&brave_search_api_key, ANTHROPIC_MODEL, ANTHROPIC_MODEL, ANTHROPIC_MODEL, EVALUATION_THRESHOLD)?;
+    let (events, receiver) = EventSink::channel();
+    tokio::spawn(render_events(receiver));
+
+    let mut agent = EthAgent::new(anthropic::Client::new(&anthropic_api_key), search_provider, config)?
+        .with_event_sink(events)
+        .with_interaction(Arc::new(TerminalInteraction));
+    if let Some(etherscan) = etherscan {
+        agent = agent.with_etherscan(etherscan);
+    }
+    if let Some(tenderly) = tenderly {
+        agent = agent.with_tenderly(tenderly);
+    }
+    if let Some(dune) = dune {
+        agent = agent.with_dune(dune);
+    }
+    if let Some(webhooks) = webhooks {
+        agent = agent.with_webhooks(webhooks);
+    }
+    if !notifier.is_empty() {
+        agent = agent.with_notifier(notifier);
+    }
+
+    // Edits to the config file apply from the next prompt on, the watcher logs what changed
+    let mut config_changes = AgentConfig::path().map(|path| {
+        let load = |path: &Path| AgentConfig::load_from(path).map(with_cli_defaults);
+        watch_config(path, Duration::from_secs(CONFIG_POLL_SECS), agent.config().clone(), load)
+    });
 
     // Initialize context
     let mut context = HashMap::new();
@@ -51,58 +132,451 @@ async fn main() -> Result<()> {
         print!("agent> ");
         io::stdout().flush()?;
 
-        // Read user input
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim();
-
-        // Handle special prompts
-        match input.to_lowercase().as_str() {
-            "quit" | "exit" | "q" => {
+        // Read user input, Ctrl+C at the prompt exits the REPL
+        let input = tokio::select! {
+            input = read_line() => input?,
+            _ = tokio::signal::ctrl_c() => {
+                println!();
                 println!("Goodbye! 👋");
                 break;
             }
-            "help" | "h" => {
-                print_help();
-                continue;
-            }
-            "clear" | "cls" => {
-                print!("\x1B[2J\x1B[1;1H"); // Clear screen
-                continue;
+        };
+        let input = input.trim();
+
+        while let Some(config) = config_changes.as_mut().and_then(|changes| changes.try_recv().ok()) {
+            agent.reload_config(config);
+        }
+
+        if input.is_empty() {
+            continue;
+        }
+
+        // Handle REPL commands
+        if let Some(command) = commands::parse(input) {
+            match command {
+                ReplCommand::Quit => {
+                    println!("Goodbye! 👋");
+                    break;
+                }
+                ReplCommand::Help => print_help(),
+                ReplCommand::Clear => print!("\x1B[2J\x1B[1;1H"), // Clear screen
+                ReplCommand::KeysList => commands::list_keys(),
+                ReplCommand::KeysSet(name) => {
+                    if let Err(e) = commands::set_key(&name, &mut agent).await {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Runs => commands::list_runs(&agent),
+                ReplCommand::RunsList { since } => {
+                    if let Err(e) = commands::list_saved_runs(&agent, since.as_deref()) {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Export { run_id, path } => {
+                    if let Err(e) = commands::export_run(&agent, &run_id, &path) {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Bundle { run_id, path } => {
+                    if let Err(e) = commands::export_bundle(&agent, &run_id, &path) {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::ExportScript { run_id, path } => {
+                    if let Err(e) = commands::export_script(&agent, &run_id, &path) {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::ExportCast { run_id, path } => {
+                    if let Err(e) = commands::export_cast(&agent, &run_id, &path) {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Tools(filter) => {
+                    if let Err(e) = commands::list_tools(&agent, filter.as_deref()).await {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Plan => commands::show_plan(&agent),
+                ReplCommand::Accounts => {
+                    if let Err(e) = commands::list_accounts(&agent).await {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::Retry(step) => {
+                    let outcome = tokio::select! {
+                        result = agent.retry(step) => result,
+                        _ = tokio::signal::ctrl_c() => {
+                            println!();
+                            print_cancelled_run(agent.cancel_run());
+                            println!();
+                            continue;
+                        }
+                    };
+                    print_outcome(outcome);
+                    print_usage(&agent);
+                }
+                ReplCommand::Portfolio { address, days, path, tokens } => {
+                    if let Err(e) = commands::export_portfolio(&agent, &address, days, &tokens, &path).await {
+                        println!("❌ {}", e);
+                    }
+                }
+                ReplCommand::RunBundle { path, execution } => {
+                    let bundle = match commands::load_bundle(&path) {
+                        Ok(bundle) => bundle,
+                        Err(e) => {
+                            println!("❌ {}", e);
+                            continue;
+                        }
+                    };
+                    println!("📦 Running {} transaction(s) from {}", bundle.transactions.len(), path.display());
+                    let outcome = tokio::select! {
+                        result = agent.run_safe_bundle(&bundle, execution) => result,
+                        _ = tokio::signal::ctrl_c() => {
+                            println!();
+                            print_cancelled_run(agent.cancel_run());
+                            println!();
+                            continue;
+                        }
+                    };
+                    print_outcome(outcome);
+                    print_usage(&agent);
+                }
+                ReplCommand::Pay { uri, from } => {
+                    let request = match PaymentRequest::parse(&uri) {
+                        Ok(request) => request,
+                        Err(e) => {
+                            println!("❌ {}", e);
+                            continue;
+                        }
+                    };
+                    let outcome = tokio::select! {
+                        result = agent.pay(&request, from) => result,
+                        _ = tokio::signal::ctrl_c() => {
+                            println!();
+                            print_cancelled_run(agent.cancel_run());
+                            println!();
+                            continue;
+                        }
+                    };
+                    print_outcome(outcome);
+                    print_usage(&agent);
+                }
+                ReplCommand::Undo => match agent.undo().await {
+                    Ok(Some(run_id)) => println!("↩️  Reverted the chain to before run {}", run_id),
+                    Ok(None) => println!("Nothing to undo, only runs on a local anvil node are snapshotted"),
+                    Err(e) => println!("❌ Undo failed: {}", e),
+                },
+                ReplCommand::BlocklistRefresh => match agent.blocklist().refresh().await {
+                    Ok(count) => println!("🛡️  Blocklist refreshed, {} addresses listed", count),
+                    Err(e) => println!("❌ Blocklist refresh failed: {}", e),
+                },
+                ReplCommand::WhatIf(text) => {
+                    let prompt = UserPrompt {
+                        context: context.clone(),
+                        ..UserPrompt::new(text)
+                    };
+                    run_what_if(&mut agent, prompt).await;
+                    print_usage(&agent);
+                }
+                ReplCommand::Unknown(command) => {
+                    println!("Unknown command: {} (type 'help' for available commands)", command);
+                }
             }
-            "" => continue, // Empty input, continue to next iteration
-            _ => {}
+            continue;
         }
 
         // Process the prompt
         let prompt = UserPrompt {
-            id: uuid::Uuid::new_v4().to_string(),
-            natural_language: input.to_string(),
-            timestamp: chrono::Utc::now(),
             context: context.clone(),
+            ..UserPrompt::new(input)
         };
 
         info!("Processing prompt: {}", prompt.natural_language);
 
-        match agent.run(prompt).await {
-            Ok(result) => {
-                println!("✅ Prompt executed successfully!");
-                println!("🎯 Result: {}", result.result);
+        // Ctrl+C while a plan is running cancels it and returns to the prompt
+        let outcome = tokio::select! {
+            result = agent.run(prompt) => result,
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                print_cancelled_run(agent.cancel_run());
+                println!();
+                continue;
+            }
+        };
+
+        print_outcome(outcome);
+        print_usage(&agent);
+    }
+
+    info!("Shutting down ETH Agent");
+    Ok(())
+}
+
+fn print_outcome(outcome: Result<AgentResult, AgentError>) {
+    match outcome {
+        Ok(result) => {
+            println!("✅ Prompt executed successfully!");
+            println!("🎯 Result: {}", result.result);
+            print_transcript(&result);
 
-                if let Some(error) = result.error_message {
-                    println!("⚠️  Execution completed with error: {}", error);
+            if let Some(error) = result.error_message {
+                println!("⚠️  Execution completed with error: {}", error);
+            }
+        }
+        Err(e) => {
+            println!("❌ Failed to process prompt: {}", e);
+            error!("Failed to process prompt: {}", e);
+        }
+    }
+}
+
+fn print_usage(agent: &EthAgent<anthropic::Client>) {
+    let usage = agent.budget_usage();
+    println!(
+        "💰 ~{} tokens (≈ ${:.4}), {} wei gas",
+        usage.tokens(),
+        usage.cost_usd(&agent.config().budget),
+        usage.gas_wei
+    );
+    if let Some(run) = agent.last_run() {
+        println!("📜 Run {} (export it with `/export last report.md`)", run.id);
+    }
+    println!(); // Add spacing between prompts
+}
+
+// Run a prompt on forks, show what it changed and replay it for real once the user agrees
+async fn run_what_if(agent: &mut EthAgent<anthropic::Client>, prompt: UserPrompt) {
+    println!("🧪 Running on a fork first, nothing is sent to the real network");
+    let outcome = tokio::select! {
+        result = agent.what_if(prompt) => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!();
+            print_cancelled_run(agent.cancel_run());
+            return;
+        }
+    };
+    let report = match outcome {
+        Ok(report) => report,
+        Err(e) => {
+            println!("❌ The plan failed on the fork, nothing was sent: {}", e);
+            return;
+        }
+    };
+
+    println!("🧪 Fork result: {}", report.result.result);
+    print_transcript(&report.result);
+    if report.balance_changes.is_empty() {
+        println!("  No ETH balance changed");
+    }
+    for change in &report.balance_changes {
+        let network = change.network.as_deref().map(|network| format!(" on {}", network)).unwrap_or_default();
+        println!("  {}{}: {} → {} wei", change.address, network, change.before, change.after);
+    }
+    if let Some(error) = &report.result.error_message {
+        println!("⚠️  The plan did not complete on the fork, nothing was sent: {}", error);
+        return;
+    }
+
+    if !TerminalInteraction.confirm("Run this for real?").await {
+        println!("Nothing was sent to the real network");
+        return;
+    }
+    let outcome = tokio::select! {
+        result = agent.replay(report) => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!();
+            print_cancelled_run(agent.cancel_run());
+            return;
+        }
+    };
+    print_outcome(outcome);
+}
+
+// Print streamed agent output as it arrives
+async fn render_events(mut receiver: tokio::sync::mpsc::UnboundedReceiver<AgentEvent>) {
+    while let Some(event) = receiver.recv().await {
+        match event {
+            AgentEvent::StepStarted { step_number, agent_name } => {
+                println!("\n▶️  Step {} ({})", step_number, agent_name);
+            }
+            AgentEvent::Token(token) => {
+                print!("{}", token);
+            }
+            AgentEvent::ToolCall { name, arguments } => {
+                println!("\n🔧 {}({})", name, arguments);
+            }
+            AgentEvent::ToolResult { name, result } => {
+                // Tool results can be large JSON blobs, only show the start
+                let preview: String = result.chars().take(200).collect();
+                let ellipsis = if result.chars().count() > 200 { "…" } else { "" };
+                println!("   ↳ {}: {}{}", name, preview, ellipsis);
+            }
+            AgentEvent::EnsResolved { name, address } => {
+                println!("🔗 {} → {}", name, address);
+            }
+            AgentEvent::FiatAmountPinned { phrase, value } => {
+                println!("💵 {} → {}", phrase, value);
+            }
+            AgentEvent::ReferenceResolved { phrase, value } => {
+                println!("↩️  \"{}\" → {}", phrase, value);
+            }
+            AgentEvent::TransactionPreview { preview } => {
+                print_transaction_preview(&preview);
+            }
+            AgentEvent::PlanCostEstimate { estimate } => {
+                println!("\n⛽ Plan cost: {}", estimate.describe());
+                for step in &estimate.steps {
+                    let source = if step.simulated { "simulated" } else { "typical" };
+                    println!("   Step {} ({}): ~{} gas, {}", step.step_number, step.action, step.gas, source);
                 }
             }
-            Err(e) => {
-                println!("❌ Failed to process prompt: {}", e);
-                error!("Failed to process prompt: {}", e);
+            AgentEvent::StepFinished { .. } => {
+                println!();
+            }
+            AgentEvent::PlanChosen { chosen, scores } => {
+                println!("\n🗳️  Picked plan {} of {} (score {})", chosen + 1, scores.len(), scores[chosen]);
+            }
+            AgentEvent::StepRetry { step_number, attempt, reason } => {
+                println!("\n🔁 Retrying step {} (attempt {}): {}", step_number, attempt, reason);
+            }
+            AgentEvent::Progress { progress, total, message } => {
+                // Redrawn in place, the next line of output starts after it
+                let amount = match total {
+                    Some(total) => format!("{}/{}", progress, total),
+                    None => progress.to_string(),
+                };
+                print!("\r   ⏳ {} {}", message.unwrap_or_default(), amount);
+            }
+            AgentEvent::Warning { message } => {
+                println!("\n⚠️  {}", message);
             }
         }
-        println!(); // Add spacing between prompts
+        let _ = io::stdout().flush();
     }
+}
 
-    info!("Shutting down ETH Agent");
-    Ok(())
+fn print_transaction_preview(preview: &serde_json::Value) {
+    let field = |name: &str| preview.get(name).map(|value| value.to_string().trim_matches('"').to_string()).unwrap_or_default();
+    // Show labels next to their addresses, e.g. "vitalik.eth (0xd8dA...)" or "Uniswap V3 Router 2 (0x68b3...)"
+    let label = |address: &str| match preview.get("address_labels").and_then(|names| names.get(address)).and_then(|name| name.as_str()) {
+        Some(name) => format!("{} ({})", name, address),
+        None => address.to_string(),
+    };
+
+    println!("\n📝 Transaction preview");
+    println!("   {} → {} ({} wei)", label(&field("from")), label(&field("to")), field("value"));
+    if let Some(fiat) = preview.get("fiat_amount").and_then(|fiat| fiat.as_str()) {
+        println!("   Amount: {}", fiat);
+    }
+    if let Some(call) = preview.get("decoded_call") {
+        println!("   Call: {}", call);
+    }
+    println!("   Estimated gas: {} (≈ {} wei)", field("estimated_gas"), field("estimated_gas_cost"));
+    if let Some(fee) = preview.get("fee").filter(|fee| !fee.is_null()) {
+        let fee_field = |name: &str| fee.get(name).map(|value| value.to_string().trim_matches('"').to_string()).unwrap_or_default();
+        println!(
+            "   Fees: {} from {} (max {} wei/gas, ~{}s to inclusion)",
+            fee_field("tier"),
+            fee_field("source"),
+            fee_field("max_fee_per_gas"),
+            fee_field("expected_inclusion_seconds")
+        );
+    }
+    if let Some(sponsorship) = preview.get("sponsorship").filter(|sponsorship| !sponsorship.is_null()) {
+        let terms = |name: &str| sponsorship.get(name).filter(|value| !value.is_null()).map(|value| value.to_string().trim_matches('"').to_string());
+        let policy = terms("policy").map(|policy| format!(", policy {}", policy)).unwrap_or_default();
+        let valid_until = terms("valid_until").map(|until| format!(", offer valid until {}", until)).unwrap_or_default();
+        println!(
+            "   Gas sponsored by paymaster {}{}: up to {} wei, the account pays no gas{}",
+            terms("paymaster").unwrap_or_default(),
+            policy,
+            terms("max_sponsored_cost").unwrap_or_default(),
+            valid_until
+        );
+    }
+    if let Some(exposure) = preview.get("mev_exposure").filter(|exposure| exposure.get("kind").and_then(|kind| kind.as_str()) == Some("swap")) {
+        let exposure_field = |name: &str| exposure.get(name).filter(|value| !value.is_null()).map(|value| value.to_string().trim_matches('"').to_string()).unwrap_or_else(|| "?".to_string());
+        println!(
+            "   MEV exposure: slippage tolerance {} bps, price impact {} bps, up to {} wei extractable",
+            exposure_field("slippage_bps"),
+            exposure_field("price_impact_bps"),
+            exposure_field("max_extractable_wei")
+        );
+    }
+    if let Some(l2_fees) = preview.get("l2_fees").filter(|fees| !fees.is_null()) {
+        let l2_field = |name: &str| l2_fees.get(name).map(|value| value.to_string().trim_matches('"').to_string()).unwrap_or_default();
+        println!("   {} L1 data fee: {} wei (included in the gas cost)", l2_field("rollup"), l2_field("l1_data_fee"));
+    }
+    for change in preview.get("balance_changes").and_then(|changes| changes.as_array()).into_iter().flatten() {
+        println!(
+            "   {} {}: {}",
+            label(change.get("address").and_then(|value| value.as_str()).unwrap_or_default()),
+            change.get("asset").and_then(|value| value.as_str()).unwrap_or_default(),
+            change.get("change").and_then(|value| value.as_str()).unwrap_or_default(),
+        );
+    }
+}
+
+// Read a line from stdin without blocking the runtime, so Ctrl+C can still be handled
+async fn read_line() -> Result<String> {
+    let input = tokio::task::spawn_blocking(|| {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).map(|_| input)
+    })
+    .await??;
+
+    Ok(input)
+}
+
+// One line per step with its score, tool calls and time, then the transactions the run sent
+fn print_transcript(result: &AgentResult) {
+    for step in &result.steps {
+        let score = step.evaluation.as_ref().map(|evaluation| format!(", score {}", evaluation.score)).unwrap_or_default();
+        let duration = step.duration().map(|duration| format!(", {:.1}s", duration.num_milliseconds() as f64 / 1000.0)).unwrap_or_default();
+        println!(
+            "  Step {} ({}): {} tool call(s){}{}",
+            step.step_number,
+            step.agent_name,
+            step.tool_calls.len(),
+            score,
+            duration
+        );
+    }
+    for hash in &result.transaction_hashes {
+        println!("  🧾 {}", hash);
+    }
+    let mut unsigned = 0;
+    for artifact in &result.artifacts {
+        if let Artifact::UnsignedTransaction { step_number, eip681, .. } = artifact {
+            unsigned += 1;
+            println!("  🖊️  Step {}: {}", step_number, eip681.as_deref().unwrap_or("unsigned transaction (JSON only)"));
+        }
+    }
+    if unsigned > 0 {
+        println!("  Nothing was sent, export the transactions to sign with `/bundle last bundle.json`");
+    }
+}
+
+fn print_cancelled_run(run: Option<RunRecord>) {
+    println!("🛑 Run cancelled");
+
+    let Some(run) = run else {
+        return;
+    };
+
+    for step in &run.steps {
+        println!("  Step {} ({}): {:?}", step.step_number, step.agent_name, step.status);
+    }
+
+    // Transactions already submitted keep going on-chain even though the run stopped
+    let hashes = run.transaction_hashes();
+    if !hashes.is_empty() {
+        println!("⚠️  Transactions submitted before cancellation:");
+        for hash in hashes {
+            println!("  {}", hash);
+        }
+    }
 }
 
 fn print_help() {
@@ -110,6 +584,24 @@ fn print_help() {
     println!("  help, h          - Show this help message");
     println!("  quit, exit, q    - Exit the REPL");
     println!("  clear, cls       - Clear the screen");
+    println!("  /keys            - Show where each API key is loaded from");
+    println!("  /keys set <name> - Store an API key (anthropic, brave, tavily, serpapi, etherscan, tenderly, dune) in the OS keyring");
+    println!("  /runs            - List the runs of this session");
+    println!("  /runs list [since] - List saved runs of every session, optionally from a date (2024-05-01) on");
+    println!("  /export <run-id> <file> - Export a run report (Markdown, or HTML for .html files), `last` for the latest run");
+    println!("  /bundle <run-id> <file> - Export a run's unsigned transactions as JSON for an external wallet (unsigned_transactions = true)");
+    println!("  /export-script <run-id> <file> - Export the transactions a run sent as a Solidity forge script (e.g. AgentRun.s.sol)");
+    println!("  /export-cast <run-id> <file> - Export what a run did on chain as a shell script of cast send/call commands");
+    println!("  /portfolio <address> <days> <file> [token ...] - Export holdings over time and P&L of an account (Markdown, or HTML for .html files)");
+    println!("  /run-bundle <file> [propose <proposer> | send <from>] - Run a Safe Transaction Builder export, proposed to its Safe by default");
+    println!("  ethereum:<address>?value=<wei> [from <sender>] - Pay an EIP-681 payment request (ETH or ERC20 transfer) after confirming");
+    println!("  /undo            - Revert a local anvil chain to before the last run (repeat to go further back)");
+    println!("  /whatif <prompt> - Run a prompt on a throwaway anvil fork, show what it changes, then run it for real if you agree");
+    println!("  /blocklist refresh - Download the scam address blocklists again");
+    println!("  /accounts        - List the node's accounts and labeled addresses with ETH balances and nonces, no LLM involved");
+    println!("  /plan            - Show the last run's plan with each step's status and output");
+    println!("  /retry [step]    - Run the last failed run again from its first unfinished step, or only the given step");
+    println!("  /tools [filter]  - List the tools foundry-mcp offers now with their parameters, optionally only names containing filter");
     println!("\n💡 Example Prompts:");
     println!("  Transfer 0.1 ETH to 0x742d35Cc6634C0532925a3b8D4C9db96C4b4d8b6");
     println!("  Deploy a simple contract");
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use eth_agent_core::register_secret;
+use keyring::Entry;
+use tracing::debug;
+
+// Keyring service all secrets are stored under
+const SERVICE: &str = "eth-agent";
+
+// Secrets the agent knows about, as (name used with `/keys set`, env var fallback)
+pub const KNOWN_SECRETS: &[(&str, &str)] = &[
+    ("anthropic", "ANTHROPIC_API_KEY"),
+    ("brave", "BRAVE_SEARCH_API_KEY"),
+    ("tavily", "TAVILY_API_KEY"),
+    ("serpapi", "SERPAPI_API_KEY"),
+    ("etherscan", "ETHERSCAN_API_KEY"),
+    ("tenderly", "TENDERLY_ACCESS_KEY"),
+    ("dune", "DUNE_API_KEY"),
+    ("webhook", "ETH_AGENT_WEBHOOK_SECRET"),
+    ("slack", "SLACK_WEBHOOK_URL"),
+    ("smtp", "SMTP_PASSWORD"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretSource {
+    Keyring,
+    Env,
+}
+
+fn env_var(name: &str) -> Option<&'static str> {
+    KNOWN_SECRETS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, env_var)| *env_var)
+}
+
+pub fn is_known(name: &str) -> bool {
+    env_var(name).is_some()
+}
+
+// Look a secret up in the OS keyring first, then fall back to its env var.
+// Every secret found is masked in logs and prompts from then on.
+pub fn get(name: &str) -> Option<(String, SecretSource)> {
+    let found = lookup(name);
+    if let Some((secret, _)) = &found {
+        register_secret(secret);
+    }
+    found
+}
+
+fn lookup(name: &str) -> Option<(String, SecretSource)> {
+    match Entry::new(SERVICE, name).and_then(|entry| entry.get_password()) {
+        Ok(secret) if !secret.is_empty() => return Some((secret, SecretSource::Keyring)),
+        Ok(_) | Err(keyring::Error::NoEntry) => {}
+        // No keyring backend (e.g. a headless box), the env var is all we have
+        Err(e) => debug!("Keyring lookup for {} failed: {}", name, e),
+    }
+
+    let env_var = env_var(name)?;
+    std::env::var(env_var)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .map(|secret| (secret, SecretSource::Env))
+}
+
+pub fn set(name: &str, secret: &str) -> Result<()> {
+    if !is_known(name) {
+        return Err(anyhow::anyhow!("Unknown key {}", name));
+    }
+
+    register_secret(secret);
+    Entry::new(SERVICE, name)?.set_password(secret)?;
+    Ok(())
+}
+
+// Read a secret from the terminal without echoing it, so it never ends up in shell history
+pub async fn read_hidden(prompt: &str) -> Result<String> {
+    let prompt = prompt.to_string();
+    let secret = tokio::task::spawn_blocking(move || rpassword::prompt_password(prompt)).await??;
+    Ok(secret.trim().to_string())
+}
@@ -0,0 +1,2158 @@
+use crate::{
+    blocklist::Blocklist,
+    budget::{BudgetUsage, RunBudget},
+    cache::SearchCache,
+    config::{AgentConfig, BudgetAction},
+    dune::DuneClient,
+    error::AgentError,
+    etherscan::EtherscanClient,
+    events::{AgentEvent, EventSink},
+    fork::{anvil_launcher, ForkLauncher, Forks},
+    guard::{classify_step, ActionGuard},
+    interaction::{Interaction, NoInteraction},
+    journal::RunJournal,
+    labels::AddressLabels,
+    redact::redact,
+    safe_bundle::{BundleExecution, SafeBundle},
+    search::SearchProvider,
+    store::RunStore,
+    tenderly::TenderlyClient,
+    webhook::WebhookNotifier,
+    references::RecentEntities,
+    notifications::{Notification, Notifier, Severity},
+    payment_uri::PaymentRequest,
+    plugins::{check_pack_name, namespaced, plugin_preamble, plugin_tools, McpToolPack, PluginTool, ToolPack, PLUGIN_AGENTS},
+    portfolio::PortfolioHistory,
+    tool_results::AccountInfo,
+    mcp_client::{default_connector, FoundryMcpClient, McpConnector},
+    amounts::{annotate_amounts, annotate_fiat, convert_fiat, find_fiat_amounts, token_units, FiatConversion, TokenDecimals},
+    ens::{annotate_ens, find_ens_names},
+    retry::{classify, with_backoff},
+    plan_cost,
+    plan_validation,
+    grounding,
+    postconditions,
+    sampling::ModelSampler,
+    sub_agent::SubAgentBuilder,
+    tools::*,
+    types::*,
+};
+use alloy_primitives::U256;
+use anyhow::Result;
+use futures::future::join_all;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
+
+use rig::{client::{CompletionClient, ProviderClient}, completion::Prompt, tool::Tool};
+
+/// The agent: plans a prompt into steps, runs each step on a sub-agent with Ethereum or search
+/// tools, and evaluates every result, replanning when a step falls short.
+pub struct EthAgent<T: CompletionClient + ProviderClient + Send + Sync> {
+    provider_client: T,
+    search_provider: Option<Arc<dyn SearchProvider>>,
+    search_cache: Arc<SearchCache>,
+    etherscan: Option<Arc<EtherscanClient>>,
+    tenderly: Option<Arc<TenderlyClient>>,
+    dune: Option<Arc<DuneClient>>,
+    webhooks: Option<Arc<WebhookNotifier>>,
+    notifier: Option<Arc<Notifier>>,
+    config: AgentConfig,
+    journal: RunJournal,
+    events: EventSink,
+    mcp_connector: Option<McpConnector>,
+    budget: RunBudget,
+    interaction: Arc<dyn Interaction>,
+    // What earlier runs of this agent dealt with, for prompts referring back to it
+    recent_entities: RecentEntities,
+    // (run id, snapshot id) of every run on a local chain that can still be undone, latest last
+    undo_points: Mutex<Vec<(String, String)>>,
+    fork_launcher: Option<ForkLauncher>,
+    // Set for the duration of a what-if run, every ethereum_agent step then runs on a fork
+    forks: Option<Arc<Forks>>,
+    // Names of addresses, kept across runs so names resolved once stay known
+    labels: AddressLabels,
+    // Loaded once and refreshed in place, so runs don't download the lists again
+    blocklist: Blocklist,
+    // `[[plugins]]` servers and native packs, whose tools are added to the sub-agents
+    tool_packs: Vec<Arc<dyn ToolPack>>,
+}
+
+impl<T: CompletionClient + ProviderClient + Send + Sync> EthAgent<T>
+where
+    T::CompletionModel: 'static,
+{
+    /// Create an agent on top of a rig provider client. Web search is disabled without a
+    /// search provider, see [`search_provider`](crate::search_provider).
+    pub fn new(provider_client: T, search_provider: Option<Arc<dyn SearchProvider>>, config: AgentConfig) -> Result<Self> {
+        let journal = match &config.runs.database {
+            Some(path) => RunJournal::with_store(Arc::new(RunStore::open(path)?)),
+            None => RunJournal::new(),
+        };
+        let mut tool_packs: Vec<Arc<dyn ToolPack>> = vec![];
+        for plugin in &config.plugins {
+            if let Some(agent) = plugin.agents.iter().find(|agent| !PLUGIN_AGENTS.contains(&agent.as_str())) {
+                return Err(anyhow::anyhow!("Plugin {} names unknown agent {}, use {}", plugin.name, agent, PLUGIN_AGENTS.join(", ")));
+            }
+            if tool_packs.iter().any(|pack| pack.name() == plugin.name) {
+                return Err(anyhow::anyhow!("Plugin {} is declared twice", plugin.name));
+            }
+            let timeout = Duration::from_secs(config.timeouts.tool_secs);
+            tool_packs.push(Arc::new(McpToolPack::new(plugin.clone(), timeout)?));
+        }
+        Ok(Self {
+            provider_client,
+            search_provider,
+            search_cache: Arc::new(SearchCache::new(Duration::from_secs(config.search.cache_ttl_secs))),
+            etherscan: None,
+            tenderly: None,
+            dune: None,
+            // Unsigned until a secret is given with `with_webhooks`
+            webhooks: (!config.webhooks.urls.is_empty()).then(|| Arc::new(WebhookNotifier::new(None, &config.webhooks))),
+            notifier: None,
+            budget: RunBudget::new(config.budget.clone()),
+            labels: AddressLabels::new(&config.labels),
+            blocklist: Blocklist::new(&config.blocklist),
+            config,
+            journal,
+            events: EventSink::default(),
+            mcp_connector: None,
+            interaction: Arc::new(NoInteraction),
+            recent_entities: RecentEntities::default(),
+            undo_points: Mutex::new(vec![]),
+            fork_launcher: None,
+            forks: None,
+            tool_packs,
+        })
+    }
+
+    /// Add a native tool pack. Its tools reach the sub-agents it names as `<pack>__<tool>` from the
+    /// next run on; a pack whose name is invalid or already taken is skipped with a warning.
+    pub fn with_tool_pack(mut self, pack: Arc<dyn ToolPack>) -> Self {
+        self.tool_packs.push(pack);
+        self
+    }
+
+    /// Apply a reloaded config, e.g. from [`watch_config`](crate::watch_config), to the runs that
+    /// start from now on; a run in progress keeps the settings it started with. Returns the
+    /// settings that changed. [`RESTART_ONLY_SETTINGS`](crate::RESTART_ONLY_SETTINGS) are taken but
+    /// don't take effect until the agent is built again.
+    pub fn reload_config(&mut self, config: AgentConfig) -> Vec<&'static str> {
+        let changed = config.changed_settings(&self.config);
+        if changed.contains(&"budget") {
+            self.budget = RunBudget::new(config.budget.clone());
+        }
+        if config.search.cache_ttl_secs != self.config.search.cache_ttl_secs {
+            self.search_cache = Arc::new(SearchCache::new(Duration::from_secs(config.search.cache_ttl_secs)));
+        }
+        if changed.contains(&"labels") {
+            self.labels.replace_user_labels(&self.config.labels, &config.labels);
+        }
+        if changed.contains(&"blocklist") {
+            self.blocklist = Blocklist::new(&config.blocklist);
+        }
+        self.config = config;
+        changed
+    }
+
+    /// Swap the provider client, e.g. after its API key was changed
+    pub fn set_provider_client(&mut self, provider_client: T) {
+        self.provider_client = provider_client;
+    }
+
+    /// Swap the search provider, e.g. after its API key was set. Web search is unavailable without one.
+    pub fn set_search_provider(&mut self, search_provider: Option<Arc<dyn SearchProvider>>) {
+        self.search_provider = search_provider;
+    }
+
+    /// Notify webhooks of finished runs through `notifier`, e.g. one signing with a secret
+    pub fn with_webhooks(mut self, notifier: WebhookNotifier) -> Self {
+        self.webhooks = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Send Slack or email notifications when runs finish, per `[notifications]`
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Answer account history questions through Etherscan
+    pub fn with_etherscan(mut self, etherscan: EtherscanClient) -> Self {
+        self.etherscan = Some(Arc::new(etherscan));
+        self
+    }
+
+    /// Swap the Etherscan client, e.g. after its API key was set
+    pub fn set_etherscan(&mut self, etherscan: Option<EtherscanClient>) {
+        self.etherscan = etherscan.map(Arc::new);
+    }
+
+    /// Let the Ethereum agent simulate transactions on Tenderly
+    pub fn with_tenderly(mut self, tenderly: TenderlyClient) -> Self {
+        self.tenderly = Some(Arc::new(tenderly));
+        self
+    }
+
+    /// Swap the Tenderly client, e.g. after its access key was set
+    pub fn set_tenderly(&mut self, tenderly: Option<TenderlyClient>) {
+        self.tenderly = tenderly.map(Arc::new);
+    }
+
+    /// Let the analysis agent run saved Dune queries
+    pub fn with_dune(mut self, dune: DuneClient) -> Self {
+        self.dune = Some(Arc::new(dune));
+        self
+    }
+
+    /// Swap the Dune client, e.g. after its API key was set
+    pub fn set_dune(&mut self, dune: Option<DuneClient>) {
+        self.dune = dune.map(Arc::new);
+    }
+
+    /// Stream step progress, tokens and tool calls to the given sink
+    pub fn with_event_sink(mut self, events: EventSink) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Connect to the MCP server with `connector` instead of spawning foundry-mcp for every run
+    pub fn with_mcp_connector(mut self, connector: McpConnector) -> Self {
+        self.mcp_connector = Some(connector);
+        self
+    }
+
+    /// Start the forks of what-if runs with `launcher` instead of spawning anvil
+    pub fn with_fork_launcher(mut self, launcher: ForkLauncher) -> Self {
+        self.fork_launcher = Some(launcher);
+        self
+    }
+
+    /// Ask the user through `interaction` when a run goes over budget with `on_exceeded = "confirm"`
+    /// and when the plan has a clarification question. Without one confirmations are declined and
+    /// questions end the run with [`AgentError::ClarificationNeeded`].
+    pub fn with_interaction(mut self, interaction: Arc<dyn Interaction>) -> Self {
+        self.interaction = interaction;
+        self
+    }
+
+    /// Estimated tokens and gas spent by the current (or last) run
+    pub fn budget_usage(&self) -> BudgetUsage {
+        self.budget.usage()
+    }
+
+    /// Latest checkpoint of the current (or last) run
+    pub fn last_run(&self) -> Option<RunRecord> {
+        self.journal.snapshot()
+    }
+
+    /// Every run of this session, oldest first
+    pub fn runs(&self) -> Vec<RunRecord> {
+        self.journal.runs()
+    }
+
+    /// Runs started at or after `since`, oldest first, read from the `[runs] database` when one is
+    /// configured and from this session otherwise
+    pub fn saved_runs(&self, since: Option<chrono::DateTime<chrono::Utc>>) -> Result<Vec<RunRecord>> {
+        self.journal.saved_runs(since)
+    }
+
+    /// Find a run by id or unique id prefix, in this session or the `[runs] database`
+    pub fn find_run(&self, id: &str) -> Option<RunRecord> {
+        self.journal.find(id)
+    }
+
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// Names of addresses: the configured `[labels]`, well-known contracts and what runs resolved
+    pub fn labels(&self) -> &AddressLabels {
+        &self.labels
+    }
+
+    /// Scam addresses no transaction may touch, from the `[blocklist]` sources
+    pub fn blocklist(&self) -> &Blocklist {
+        &self.blocklist
+    }
+
+    /// Revert a local anvil chain to right before the latest run that wasn't undone yet, see
+    /// `snapshot_local_chains`. Returns the id of the undone run, `None` when there is none.
+    pub async fn undo(&self) -> Result<Option<String>> {
+        // Only dropped once reverted, so a failed undo can be tried again
+        let undo_point = self.undo_points.lock().unwrap().last().cloned();
+        let Some((run_id, snapshot_id)) = undo_point else {
+            return Ok(None);
+        };
+
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = FoundryMcpClient::from_service(connector().await?, &self.config, RunJournal::new(), self.events.clone());
+        client.revert_chain(&snapshot_id).await?;
+        self.undo_points.lock().unwrap().pop();
+        info!("Reverted the chain to snapshot {} taken before run {}", snapshot_id, run_id);
+        Ok(Some(run_id))
+    }
+
+    /// Mark the current run as cancelled, call this after dropping the future returned by [`EthAgent::run`]
+    pub fn cancel_run(&self) -> Option<RunRecord> {
+        warn!("Run cancelled by user");
+        self.journal.cancel()
+    }
+
+    /// Plan and execute a prompt. Replans up to three times on bad plans or rejected step results.
+    pub async fn run(&mut self, prompt: UserPrompt) -> Result<AgentResult, AgentError> {
+        self.execute(prompt, None).await
+    }
+
+    /// Run the last run again without retyping its prompt. Without a step, a failed run resumes
+    /// at its first unfinished step: completed steps (and the transactions they sent) are kept,
+    /// the rest of the plan runs again and replans as usual when it falls short. With a step, only
+    /// that step of the last run's plan runs again, as a new one-step run.
+    pub async fn retry(&mut self, step: Option<u32>) -> Result<AgentResult, AgentError> {
+        let run = self.last_run().ok_or_else(|| AgentError::Planning {
+            message: "There is no run to retry".to_string(),
+        })?;
+        let prompt = UserPrompt {
+            context: run.prompt.context.clone(),
+            ..UserPrompt::new(run.prompt.natural_language.clone())
+        };
+        let retried_plan = |steps: Vec<AgentStep>, plan: &AgentPlan| AgentPlan {
+            id: Uuid::new_v4().to_string(),
+            prompt: prompt.clone(),
+            steps,
+            max_steps: self.config.max_steps,
+            current_step: 0,
+            ens_resolutions: plan.ens_resolutions.clone(),
+        };
+
+        if let Some(number) = step {
+            let plan = run.plan.as_ref().ok_or_else(|| AgentError::Planning {
+                message: format!("Run {} has no plan to retry a step of", run.id),
+            })?;
+            let step = plan.steps.iter().find(|step| step.step_number == number).ok_or_else(|| AgentError::Planning {
+                message: format!("Run {} has no step {}", run.id, number),
+            })?;
+            let plan = retried_plan(vec![AgentStep { step_number: 1, ..step.clone() }], plan);
+            info!("Retrying step {} of run {}", number, run.id);
+            return self.execute(prompt, Some(plan)).await;
+        }
+
+        if matches!(run.status, PlanStatus::Completed) {
+            return Err(AgentError::Planning {
+                message: format!("Run {} completed, name one of its steps to run it again", run.id),
+            });
+        }
+        let completed: Vec<StepRecord> = run.steps.into_iter().filter(|step| matches!(step.status, StepStatus::Completed)).collect();
+        let plan = run.plan.as_ref().map(|plan| {
+            let remaining = plan.steps.iter().filter(|step| !completed.iter().any(|done| done.step_number == step.step_number));
+            retried_plan(remaining.cloned().collect(), plan)
+        });
+        // Without a plan (or anything left of it) the run failed planning or answering, plan again
+        let plan = plan.filter(|plan| !plan.steps.is_empty());
+        info!("Retrying run {} after {} completed steps", run.id, completed.len());
+        self.execute_from(prompt, plan, completed).await
+    }
+
+    /// Execute a prompt on throwaway anvil forks of the networks its steps use, without sending
+    /// anything for real. The report shows what the run would change; pass it to
+    /// [`EthAgent::replay`] to run the same plan on the real networks.
+    pub async fn what_if(&mut self, prompt: UserPrompt) -> Result<WhatIfReport, AgentError> {
+        let launcher = self.fork_launcher.clone().unwrap_or_else(|| anvil_launcher(&self.config.what_if));
+        let forks = Arc::new(Forks::new(launcher));
+        self.forks = Some(forks.clone());
+        let result = self.execute(prompt.clone(), None).await;
+        self.forks = None;
+        let result = result?;
+
+        let run = self.journal.snapshot();
+        let balance_changes = match &run {
+            Some(run) => self.balance_changes(&forks, run).await,
+            None => vec![],
+        };
+        // A partial replan only planned the rest of the run, replaying it would skip the steps before
+        let plan = run.and_then(|run| run.plan.filter(|plan| plan.steps.len() == run.steps.len()));
+
+        Ok(WhatIfReport {
+            prompt,
+            result,
+            balance_changes,
+            plan,
+        })
+    }
+
+    /// Run what a [`EthAgent::what_if`] report previewed on the real networks, starting with the
+    /// plan that ran on the forks. It replans like any run if a step falls short.
+    pub async fn replay(&mut self, report: WhatIfReport) -> Result<AgentResult, AgentError> {
+        self.execute(report.prompt, report.plan).await
+    }
+
+    /// Run a Safe Transaction Builder bundle as exported, one step per transaction, proposing each
+    /// to the bundle's Safe or sending it from an account. Nothing is planned, a step that falls
+    /// short replans the rest like any run.
+    pub async fn run_safe_bundle(&mut self, bundle: &SafeBundle, execution: BundleExecution) -> Result<AgentResult, AgentError> {
+        let plan = bundle
+            .plan(&execution, self.config.max_steps)
+            .map_err(|e| AgentError::Planning { message: e.to_string() })?;
+
+        // A bundle built for another chain must not land on this one
+        if let Some(chain_id) = bundle.chain_id() {
+            self.check_chain_id(chain_id, "bundle").await?;
+        }
+
+        self.execute(plan.prompt.clone(), Some(plan)).await
+    }
+
+    /// Sample the balances of `address` (ETH and `tokens`, mainnet's major tokens when empty) at
+    /// `samples` blocks over the last `days`, priced at each block, for a holdings-over-time and
+    /// P&L report with [`render_portfolio`](crate::render_portfolio). Nothing is planned.
+    pub async fn portfolio_history(&self, address: &str, tokens: &[String], days: u32, samples: u32) -> Result<PortfolioHistory> {
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = FoundryMcpClient::from_service(connector().await?, &self.config, RunJournal::new(), self.events.clone())
+            .with_labels(self.labels.clone());
+        client.portfolio_history(address, tokens, days, samples).await
+    }
+
+    /// Definitions of the tools foundry-mcp offers right now, as the agent discovers them at the
+    /// start of a run. Cheatcodes are only listed when the node is anvil or Hardhat.
+    pub async fn mcp_tools(&self) -> Result<Vec<rmcp::model::Tool>> {
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = FoundryMcpClient::from_service(connector().await?, &self.config, RunJournal::new(), self.events.clone());
+        client.list_tools().await
+    }
+
+    /// The accounts foundry-mcp's node can send from and the addresses of `[labels]`, with their
+    /// ETH balance, nonce and label. Read straight from the node, nothing is planned.
+    pub async fn accounts(&self) -> Result<Vec<AccountInfo>> {
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = FoundryMcpClient::from_service(connector().await?, &self.config, RunJournal::new(), self.events.clone());
+        let addresses: Vec<String> = self.config.labels.keys().cloned().collect();
+        let mut accounts = client.accounts(&addresses).await?;
+        for account in &mut accounts {
+            account.label = self.labels.get(&account.address);
+        }
+        Ok(accounts)
+    }
+
+    /// Make the payment an EIP-681 `ethereum:` URI asks for, from `from` or the default account.
+    /// The request is already structured, so nothing is planned; it runs only once the
+    /// [`Interaction`] confirms it, whatever the guard policy.
+    pub async fn pay(&mut self, request: &PaymentRequest, from: Option<String>) -> Result<AgentResult, AgentError> {
+        if let Some(chain_id) = request.chain_id {
+            self.check_chain_id(chain_id, "payment request").await?;
+        }
+        if !self.interaction.confirm(&format!("Pay {}?", request.describe())).await {
+            return Err(AgentError::PolicyViolation {
+                message: format!("Payment of {} declined", request.describe()),
+            });
+        }
+
+        let plan = request.plan(from.as_deref(), self.config.max_steps);
+        self.execute(plan.prompt.clone(), Some(plan)).await
+    }
+
+    // Refuse a request made for another chain than the one foundry-mcp is on
+    async fn check_chain_id(&self, chain_id: u64, what: &str) -> Result<(), AgentError> {
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let service = connector().await.map_err(|e| AgentError::Tool {
+            tool: None,
+            message: format!("MCP client initialization failed: {}", e),
+        })?;
+        let client = FoundryMcpClient::from_service(service, &self.config, RunJournal::new(), self.events.clone());
+        let network_chain_id = client.use_network(None).await.map_err(|e| AgentError::Tool {
+            tool: Some("use_network".to_string()),
+            message: e.to_string(),
+        })?;
+        if network_chain_id != chain_id {
+            return Err(AgentError::PolicyViolation {
+                message: format!("The {} is for chain {} but foundry-mcp is on chain {}", what, chain_id, network_chain_id),
+            });
+        }
+        Ok(())
+    }
+
+    // ETH balances of every account a what-if run sent from, sent to or deployed, read on the real
+    // networks and on their forks. Accounts whose balance didn't move are left out.
+    async fn balance_changes(&self, forks: &Forks, run: &RunRecord) -> Vec<BalanceChange> {
+        let networks: HashMap<u32, Option<String>> = run
+            .plan
+            .iter()
+            .flat_map(|plan| plan.steps.iter())
+            .map(|step| (step.step_number, step.network.clone()))
+            .collect();
+        let mut accounts: Vec<(Option<String>, String)> = vec![];
+        for step in &run.steps {
+            let network = networks.get(&step.step_number).cloned().flatten();
+            for call in step.tool_calls.iter().filter(|call| call.tool == "send_transaction") {
+                let Some(payload) = call.payload() else {
+                    continue;
+                };
+                if payload.get("success").and_then(Value::as_bool) == Some(false) {
+                    continue;
+                }
+                let addresses = [call.arguments.get("from"), call.arguments.get("to"), payload.get("contract_address")];
+                for address in addresses.into_iter().flatten().filter_map(Value::as_str).filter(|address| !address.is_empty()) {
+                    let account = (network.clone(), address.to_string());
+                    if !accounts.contains(&account) {
+                        accounts.push(account);
+                    }
+                }
+            }
+        }
+        if accounts.is_empty() {
+            return vec![];
+        }
+
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = match connector().await {
+            Ok(service) => FoundryMcpClient::from_service(service, &self.config, RunJournal::new(), self.events.clone()),
+            Err(e) => {
+                warn!("Could not connect to foundry-mcp to compare balances: {}", e);
+                return vec![];
+            }
+        };
+
+        let mut changes = vec![];
+        for (network, address) in accounts {
+            let Some(fork_url) = (match self.fork_source(network.as_deref()) {
+                Ok(source) => forks.started(&source).await,
+                Err(_) => None,
+            }) else {
+                continue;
+            };
+            let real_url = self.network_url(network.as_deref()).ok().flatten();
+
+            let mut balances = vec![];
+            for rpc_url in [real_url, Some(fork_url.as_str())] {
+                let balance = match client.use_network(rpc_url).await {
+                    Ok(_) => client.balance(&address, true).await.map(|balance| balance.balance),
+                    Err(e) => Err(e),
+                };
+                balances.push(balance);
+            }
+            match (balances.remove(0), balances.remove(0)) {
+                (Ok(before), Ok(after)) if before != after => changes.push(BalanceChange {
+                    network,
+                    address,
+                    before,
+                    after,
+                }),
+                (Ok(_), Ok(_)) => {}
+                (Err(e), _) | (_, Err(e)) => warn!("Could not compare the balance of {}: {}", address, e),
+            }
+        }
+        changes
+    }
+
+    async fn execute(&mut self, prompt: UserPrompt, plan: Option<AgentPlan>) -> Result<AgentResult, AgentError> {
+        self.execute_from(prompt, plan, vec![]).await
+    }
+
+    // Execute after `completed` steps that already ran, as a replan would
+    async fn execute_from(&mut self, prompt: UserPrompt, plan: Option<AgentPlan>, completed: Vec<StepRecord>) -> Result<AgentResult, AgentError> {
+        // Keys pasted into the prompt are masked before they are logged, journaled or planned with
+        let prompt = UserPrompt {
+            natural_language: redact(&prompt.natural_language),
+            ..prompt
+        };
+        info!("Starting agent execution for prompt: {}", prompt.natural_language);
+
+        self.journal.begin(&prompt);
+        self.journal.resume(&completed);
+        self.budget.reset();
+        let packs = self.start_tool_packs(&prompt).await;
+        let result = self.run_plans(&prompt, plan, completed, &packs).await;
+        self.journal.set_gas_spent(&self.budget.usage().gas_wei.to_string());
+
+        match &result {
+            Ok(res) => self.journal.finish(res.status.clone(), Some(res.result.clone())),
+            Err(e) => self.journal.finish(PlanStatus::Failed(e.to_string()), None),
+        }
+
+        // Failed runs have no result to carry it, their transcript is in `last_run`
+        let Some(run) = self.journal.snapshot() else {
+            return result;
+        };
+        for pack in &packs {
+            pack.run_finished(&run).await;
+        }
+        let result = result.map(|result| result.with_transcript(&run));
+
+        // Nothing of a what-if run happened for real, its replay is what gets reported and
+        // remembered for later references
+        if self.forks.is_some() {
+            return result;
+        }
+        self.recent_entities.record(&run);
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.notify(&WebhookNotifier::payload(&run, &result)).await;
+        }
+        if let Some(notifier) = &self.notifier {
+            let notification = Notification::for_run(&run, &result);
+            // Short successful runs were just watched finishing, there's nothing to catch up on
+            if notification.severity > Severity::Info || run.steps.len() >= self.config.notifications.min_steps {
+                notifier.send(&notification).await;
+            }
+        }
+
+        result
+    }
+
+    // Let every tool pack prepare for the run. Packs that fail to, or whose name is invalid or taken
+    // by an earlier pack, sit the run out with a warning.
+    async fn start_tool_packs(&self, prompt: &UserPrompt) -> Vec<Arc<dyn ToolPack>> {
+        let mut started: Vec<Arc<dyn ToolPack>> = vec![];
+        for pack in &self.tool_packs {
+            let problem = if let Err(e) = check_pack_name(pack.name()) {
+                Some(e.to_string())
+            } else if started.iter().any(|other| other.name() == pack.name()) {
+                Some(format!("Plugin {} is registered twice", pack.name()))
+            } else if let Err(e) = pack.run_started(prompt).await {
+                Some(format!("Plugin {} could not start, its tools are unavailable this run: {}", pack.name(), e))
+            } else {
+                None
+            };
+            match problem {
+                Some(message) => {
+                    warn!("{}", message);
+                    self.events.emit(AgentEvent::Warning { message });
+                }
+                None => started.push(pack.clone()),
+            }
+        }
+        started
+    }
+
+    // `completed` holds the steps finished by earlier plan attempts (or by the run being retried),
+    // a replan only plans what comes after them
+    async fn run_plans(
+        &self,
+        prompt: &UserPrompt,
+        mut approved_plan: Option<AgentPlan>,
+        mut completed: Vec<StepRecord>,
+        packs: &[Arc<dyn ToolPack>],
+    ) -> Result<AgentResult, AgentError> {
+        let mut prompt = prompt.clone();
+        let guard = ActionGuard::new(self.config.guard.clone(), self.interaction.clone());
+
+        // One MCP client for the whole run, so every plan attempt sees the same pinned ENS names
+        info!("Initializing MCP client...");
+        let connector = self.mcp_connector.clone().unwrap_or_else(|| default_connector(&self.config.mcp));
+        let client = connector().await.map(|service| {
+            FoundryMcpClient::from_service(service, &self.config, self.journal.clone(), self.events.clone())
+                .with_reconnect(connector.clone())
+                .with_budget(self.budget.clone(), self.interaction.clone())
+                .with_guard(guard.clone())
+                .with_labels(self.labels.clone())
+                .with_blocklist(self.blocklist.clone())
+                .with_sampler(Arc::new(ModelSampler::new(
+                    self.provider_client.completion_model(&self.config.execution_model),
+                    &self.config.execution_model,
+                    self.budget.clone(),
+                )))
+        });
+        let client = match client {
+            Ok(client) => {
+                info!("MCP client initialized successfully");
+                Arc::new(client)
+            }
+            Err(e) => {
+                error!("Failed to initialize MCP client: {}", e);
+                return Err(AgentError::Tool {
+                    tool: None,
+                    message: format!("MCP client initialization failed: {}", e),
+                });
+            }
+        };
+
+        // Local experiments can be undone, the snapshot is taken before anything of the run happens.
+        // What-if runs leave the real chain alone and their forks are thrown away.
+        if self.config.snapshot_local_chains && self.forks.is_none() {
+            match client.snapshot_chain().await {
+                Ok(Some(snapshot_id)) => {
+                    let run_id = self.journal.snapshot().map(|run| run.id).unwrap_or_default();
+                    info!("Took chain snapshot {} before run {}", snapshot_id, run_id);
+                    self.undo_points.lock().unwrap().push((run_id, snapshot_id));
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Could not snapshot the chain, this run can't be undone: {}", e),
+            }
+        }
+
+        // Pin exact wei/raw values for every amount so no model has to convert units itself
+        let token_decimals = self.token_decimals(&prompt.natural_language, &client).await;
+        let (annotated, amounts) = annotate_amounts(&prompt.natural_language, &token_decimals);
+        if !amounts.is_empty() {
+            info!("Annotated amounts in prompt: {}", annotated);
+            prompt.natural_language = annotated;
+        }
+
+        // "Send him the same amount" means the recipient and amount of an earlier run, pin them
+        // here rather than hoping the planner remembers
+        let (resolved, references) = self.recent_entities.resolve(&prompt.natural_language);
+        if !references.is_empty() {
+            info!("Resolved references in prompt: {}", resolved);
+            for reference in references {
+                self.events.emit(AgentEvent::ReferenceResolved {
+                    phrase: reference.phrase,
+                    value: reference.value,
+                });
+            }
+            prompt.natural_language = resolved;
+        }
+
+        // Price fiat amounts once up front, so every step sends the same amount whatever the price does
+        let conversions = self.resolve_fiat_amounts(&prompt, &client, &token_decimals).await?;
+        if !conversions.is_empty() {
+            prompt.natural_language = annotate_fiat(&prompt.natural_language, &conversions, &token_decimals);
+            info!("Annotated fiat amounts in prompt: {}", prompt.natural_language);
+        }
+
+        // Resolve every ENS name once up front, so steps can't see different addresses for the same name
+        let ens_resolutions = self.resolve_ens_names(&prompt, &client).await?;
+        if !ens_resolutions.is_empty() {
+            prompt.natural_language = annotate_ens(&prompt.natural_language, &ens_resolutions);
+            info!("Annotated ENS names in prompt: {}", prompt.natural_language);
+        }
+
+        let mut plan_counter = 0;
+
+        const MAX_PLAN_RETRIES: u32 = 3;
+
+        let mut replan_reason: Option<String> = None;
+        let mut last_error: Option<AgentError> = None;
+
+        // Overall wall-clock deadline for this run, shared by every plan attempt
+        let deadline = Instant::now() + self.config.timeouts.run_deadline();
+
+        while plan_counter < MAX_PLAN_RETRIES {
+
+
+            // Step 1: Plan
+            plan_counter += 1;
+            self.enforce_budget().await?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let planned = match approved_plan.take() {
+                Some(plan) => Ok(Ok(plan)),
+                None => timeout(remaining, self.plan(&prompt, &replan_reason, &completed, &ens_resolutions, packs)).await,
+            };
+            let plan = match planned {
+                Ok(Ok(plan)) => plan,
+                Ok(Err(e)) if e.should_replan() => {
+                    error!("Plan creation failed, replanning: {}", e);
+                    replan_reason = Some(e.to_string());
+                    last_error = Some(e);
+                    continue;
+                }
+                Ok(Err(e)) => {
+                    error!("Plan creation failed: {}", e);
+                    return Err(e);
+                }
+                Err(_) => {
+                    error!("Run deadline exceeded while planning");
+                    return Err(AgentError::DeadlineExceeded {
+                        seconds: self.config.timeouts.run_deadline_secs,
+                        during: "planning".to_string(),
+                    });
+                }
+            };
+            // Steps that only name a chain run on the configured network on it
+            let plan = match self.resolve_chains(plan, &client).await {
+                Ok(plan) => plan,
+                Err(e) if e.should_replan() => {
+                    error!("Plan names a chain no network is on, replanning: {}", e);
+                    replan_reason = Some(e.to_string());
+                    last_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            info!("Plan created: {:?}", plan);
+            self.journal.set_plan(&plan);
+
+            // Step 2: Agent loop
+            let res = match self.agent_loop(&prompt, &plan, &completed, client.clone(), &guard, deadline, packs).await {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Agent loop failed: {}", e);
+                    self.journal.fail_current_step(&e.to_string());
+                    if e.should_replan() {
+                        completed = self.journal.completed_steps();
+                        replan_reason = Some(e.to_string());
+                        last_error = Some(e);
+                        continue;
+                    }
+                    return Err(e);
+                }
+            };
+
+            info!("Agent loop completed with result: {:?}", res);
+            return Ok(res);
+        }
+
+        Err(AgentError::RetriesExhausted {
+            attempts: MAX_PLAN_RETRIES,
+            last_error: Box::new(last_error.unwrap_or(AgentError::Planning {
+                message: "No plan attempt succeeded".to_string(),
+            })),
+        })
+    }
+
+    // Decimals of the tokens the prompt gives amounts in, as the run's network has them. Steps on
+    // other `[networks]` may see other decimals, so with those configured token amounts aren't pinned.
+    async fn token_decimals(&self, text: &str, client: &FoundryMcpClient) -> TokenDecimals {
+        let mut decimals = TokenDecimals::new();
+        if !self.config.networks.is_empty() {
+            return decimals;
+        }
+        for symbol in token_units(text) {
+            match client.resolve_token(&symbol.to_uppercase()).await {
+                Ok(payload) => match payload.pointer("/token/decimals").and_then(Value::as_u64) {
+                    Some(token_decimals) => {
+                        decimals.insert(symbol, token_decimals as u8);
+                    }
+                    None => warn!("{} did not resolve to one token, its amounts are not pinned", symbol),
+                },
+                Err(e) => warn!("Could not resolve {}, its amounts are not pinned: {}", symbol, e),
+            }
+        }
+        decimals
+    }
+
+    async fn resolve_fiat_amounts(
+        &self,
+        prompt: &UserPrompt,
+        client: &Arc<FoundryMcpClient>,
+        token_decimals: &TokenDecimals,
+    ) -> Result<Vec<FiatConversion>, AgentError> {
+        let amounts = find_fiat_amounts(&prompt.natural_language, token_decimals);
+        if amounts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut conversions = vec![];
+        for amount in amounts {
+            let price_error = |message: String| AgentError::Tool {
+                tool: Some("get_price".to_string()),
+                message: format!("Could not price {}: {}", amount.phrase, message),
+            };
+            let price = client.get_price(&amount.unit, None).await.map_err(|e| price_error(e.to_string()))?;
+            let price_usd = price.get("price_usd").and_then(|price| price.as_str()).ok_or_else(|| {
+                price_error(price.get("error").and_then(|error| error.as_str()).unwrap_or("no price_usd").to_string())
+            })?;
+            let conversion = convert_fiat(&amount, price_usd).ok_or_else(|| price_error(format!("unusable price {}", price_usd)))?;
+            info!("Pinned {} to {}", amount.phrase, conversion.describe());
+            self.events.emit(AgentEvent::FiatAmountPinned {
+                phrase: amount.phrase.clone(),
+                value: conversion.describe(),
+            });
+            conversions.push(conversion);
+        }
+        client.pin_fiat_amounts(&conversions);
+
+        Ok(conversions)
+    }
+
+    async fn resolve_ens_names(&self, prompt: &UserPrompt, client: &Arc<FoundryMcpClient>) -> Result<Vec<EnsResolution>, AgentError> {
+        let names = find_ens_names(&prompt.natural_language);
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut resolutions = vec![];
+        for name in names {
+            let resolution = client.resolve_ens(&name).await.map_err(|e| AgentError::Tool {
+                tool: Some("resolve_ens".to_string()),
+                message: e.to_string(),
+            })?;
+            info!("Resolved {}", resolution.display());
+            self.events.emit(AgentEvent::EnsResolved {
+                name: resolution.name.clone(),
+                address: resolution.address.clone(),
+            });
+            resolutions.push(resolution);
+        }
+        client.pin_ens_names(&resolutions);
+
+        Ok(resolutions)
+    }
+
+    // Stop the run once it is over budget, unless the user agrees to go on
+    async fn enforce_budget(&self) -> Result<(), AgentError> {
+        // A transaction was already refused for exceeding the gas budget
+        if let Some(reason) = self.budget.refused() {
+            return Err(AgentError::BudgetExceeded { message: reason });
+        }
+
+        let Some(overrun) = self.budget.token_overrun() else {
+            return Ok(());
+        };
+
+        if self.config.budget.on_exceeded == BudgetAction::Confirm
+            && self.interaction.confirm(&format!("{}. Continue the run?", overrun)).await
+        {
+            info!("Continuing over budget: {}", overrun);
+            self.budget.waive_tokens();
+            return Ok(());
+        }
+
+        warn!("Aborting run: {}", overrun);
+        Err(AgentError::BudgetExceeded { message: overrun })
+    }
+
+    async fn plan(
+        &self,
+        prompt: &UserPrompt,
+        replan_reason: &Option<String>,
+        completed: &[StepRecord],
+        ens_resolutions: &[EnsResolution],
+        packs: &[Arc<dyn ToolPack>],
+    ) -> Result<AgentPlan, AgentError> {
+        info!("Creating execution plan for prompt: {}", prompt.natural_language);
+
+        const PREAMBLE: &str = r#"
+        You are a helpful assistant that creates execution plans for Ethereum transactions.
+
+        The output MUST be a valid JSON object in the following format:
+        {
+            "number_of_steps": 1-10,
+            "steps": [
+                {
+                    "step_number": 1-10,
+                    "agent_name": "ethereum_agent",
+                    "agent_prompt": "Prompt for the agent to execute"
+                }
+            ]
+        }
+
+        DO NOT output anything else than the JSON object.
+
+        Amounts in the user prompt are annotated with their exact value in the smallest unit,
+        e.g. "0.1 ETH (= 100000000000000000 wei)". Always copy these exact values into agent prompts,
+        never convert units yourself.
+
+        ENS names are annotated with the address they were resolved to for this run,
+        e.g. "vitalik.eth (= 0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045)". Always put both the name
+        and that exact address in agent prompts.
+
+        Fiat amounts are annotated with the amount they were converted to at the current price,
+        e.g. "$50 worth of ETH (= 0.0125 ETH = 12500000000000000 wei at $4000 per ETH)". Always copy
+        that exact wei or raw value into agent prompts, never price the amount again.
+
+        References to earlier runs are annotated with what they referred to,
+        e.g. "him (= 0x70997970C51812dc3A010C7d01b50e0d17dc79C8)" or "the same amount (= 1000 wei)".
+        Always use these exact values in agent prompts.
+
+        Steps that change chain state should carry "postconditions" the agent checks with tool calls
+        after the step, with addresses in full and amounts in the smallest unit:
+            "postconditions": [
+                {"kind": "balance_increased", "address": "0x...", "amount": "100000000000000000"},
+                {"kind": "balance_decreased", "address": "0x...", "amount": "100000000000000000"},
+                {"kind": "token_balance_increased", "address": "0x...", "token": "0x...", "amount": "1000000"},
+                {"kind": "has_code", "address": "0x..."}
+            ]
+        Balance changes are measured from right before the step and are minimums, so leave gas out
+        of them. Omit the field for steps that only read.
+
+
+        Sub-agents:
+        - ethereum_agent: An agent that can send transactions to the Ethereum network, with the following tools:
+            - send_transaction: Send a transaction to the Ethereum network
+            - validate_address: Validate an Ethereum address
+            - balance: Get the balance of an Ethereum address
+            - get_contract_code: Get the contract code of an Ethereum address
+            - erc20_balance: Get the balance of an ERC20 token for an address
+            - resolve_token: Resolve a token symbol to its address and decimals
+            - get_price: Get the current USD price of an asset (ETH, BTC, stablecoins, ...)
+            - gas_oracle: Slow, standard and fast fee recommendations with expected inclusion times
+            - suggest_priority_fee: Priority fee for inclusion in the next block, within 1 or 5 minutes
+            - safe_info: Owners, threshold and nonce of a Safe multisig
+            - propose_safe_transaction: Propose a transaction from a Safe for its owners to confirm
+            - sign_permit: Sign an EIP-2612 permit, a gasless token approval
+            - sign_in_with_ethereum: Sign a Sign-In-With-Ethereum (EIP-4361) message to log a site in
+            - send_user_operation: Make a call from an ERC-4337 smart account through a bundler
+            - smart_account_nonce: EntryPoint nonce of an ERC-4337 smart account
+            - account_history: Recent transactions, internal transactions and ERC20/ERC721 transfers of an address
+            - tenderly_simulate: Simulate a transaction on Tenderly with a decoded trace and asset changes
+            - explain_transaction: What a mined transaction did (action, assets moved, counterparties) by hash
+            - scan_events: Every event of a kind over a block range (e.g. all mints of an NFT collection)
+            - set_code: Replace a contract's bytecode on a local fork, e.g. to mock an oracle
+            - set_storage_at: Overwrite a contract storage slot on a local fork, e.g. to fund an account with tokens
+            - set_nonce: Set an account's nonce on a local fork
+            - reset_fork: Reset a local fork to its starting state or onto a new fork
+            - detect_standards: Which standards an address implements (ERC-20/721/1155/4626, Safe, proxy, plain account)
+            - verify_bytecode: Whether a contract's deployed bytecode matches its verified source
+            - risk_score: How risky interacting with an address is (0-100) and why
+            - infer_interface: A best-effort ABI for a contract without verified source
+            - encode_call: Encode a function call into transaction data, or call a view and decode it
+            - defi_positions: Aave V3 and Compound V3 lending and Uniswap V3 LP positions of an address
+            - batch_calls: Batch independent calls from one sender (e.g. ETH payouts to many recipients) into one Multicall3 transaction
+            - staking_transaction: Build a Lido or Rocket Pool stake, unstake or withdrawal claim transaction
+            - staking_withdrawals: Status of an address's Lido withdrawal requests
+            - bridge_quote: Routes, fees and time to move assets to another chain, with the transaction of the cheapest
+            - nft_metadata: Name, description, traits and image of an NFT from its token URI
+        - search_agent: An agent that can search the web for information
+            - search: Search the web for information
+        - analysis_agent: An agent for aggregate on-chain analytics (volumes, holders, flows over time)
+          that single RPC calls can't answer, with the following tools:
+            - dune_query: Run a saved Dune query by ID with parameters
+            - account_history: Recent transactions and transfers of an address
+            - get_price: Get the current USD price of an asset
+        - defi_agent: An agent that reads DeFi positions of an address, with the following tools:
+            - defi_positions: Aave V3 and Compound V3 supplied and borrowed assets with the health factor
+              and liquidation risk, and Uniswap V3 LP positions with their amounts and range
+            - get_price: Get the current USD price of an asset
+            - resolve_token: Resolve a token symbol to its address and decimals
+          Use it for questions about positions (e.g. "am I close to liquidation?"), and add a defi_agent
+          step reading the account's positions before swap, repay, borrow or withdraw steps for an
+          account that has lending positions, so those steps know the health factor they change.
+        - ask_user: Not an agent, the agent_prompt is a question put to the user. Use it when the
+          prompt is ambiguous (e.g. a missing amount or an unclear recipient) instead of guessing;
+          the answer is passed on to every following step.
+        
+
+        Example prompt:
+        - Send 0.001 ETH from Alice to Bob
+        - What is the balance of Alice?
+        - What is the current price of ETH?
+        - What is the USDC balance of Vitalik?
+
+        Known addresses:
+        Alice: 0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266
+        Bob: 0x70997970C51812dc3A010C7d01b50e0d17dc79C8
+        Vitalik: 0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045
+
+        Refer to ERC20 tokens by symbol (e.g. USDC) in the steps, the ethereum_agent resolves
+        them to addresses. Never write token addresses from memory.
+        "#;
+
+        // Let the planner route ethereum_agent steps to the configured networks
+        let mut preamble = if self.config.networks.is_empty() {
+            PREAMBLE.to_string()
+        } else {
+            let names: Vec<&str> = self.config.networks.keys().map(String::as_str).collect();
+            format!(
+                "{}\n        Networks: {}. Add a \"network\" field with one of these names to every ethereum_agent step\n        that must run on a specific network, steps without one run on the default network. A step can\n        give a \"chain\" id instead (e.g. 8453 for Base), it then runs on the network on that chain.\n        Move funds between chains with a bridge_quote step on the source chain, then send its transaction.\n",
+                PREAMBLE,
+                names.join(", ")
+            )
+        };
+
+        // Plugin tools join the sub-agents' own, the planner routes steps needing them there
+        for pack in packs {
+            let tools: Vec<String> = match pack.tools().await {
+                Ok(tools) => tools.iter().map(|tool| format!("{}: {}", namespaced(pack.name(), &tool.name), tool.description)).collect(),
+                Err(_) => continue,
+            };
+            preamble += &format!(
+                "\n        Plugin {} adds tools to {}:\n            - {}\n",
+                pack.name(),
+                pack.agents().join(", "),
+                tools.join("\n            - ")
+            );
+            if let Some(instructions) = pack.instructions() {
+                preamble += &format!("          {}\n", instructions);
+            }
+        }
+
+        let planner_client = self.provider_client.agent(&self.config.planning_model)
+        .preamble(&preamble)
+        .build();
+
+        info!("Planner client initialized");
+
+        // If replan reason is provided, add it to the user prompt
+        let user_prompt = if let Some(replan_reason_str) = replan_reason {
+            format!("This a replan request. The user prompt is: {} and the replan reason is: {}", prompt.natural_language.clone(), replan_reason_str)
+        } else {
+            prompt.natural_language.clone()
+        };
+
+        // Completed steps may have sent transactions already, only the rest of the plan is redone
+        let max_steps = self.config.max_steps.saturating_sub(completed.len() as u32);
+        let user_prompt = if completed.is_empty() {
+            user_prompt
+        } else {
+            let done: Vec<String> = completed
+                .iter()
+                .map(|step| {
+                    format!(
+                        "Step {} ({}): {}\nOutput: {}",
+                        step.step_number,
+                        step.agent_name,
+                        step.agent_prompt,
+                        step.output.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect();
+            format!(
+                "{}\n\nThese steps are already completed, their transactions are sent and must not be repeated:\n{}\n\nPlan only the remaining steps, numbered from {}.",
+                user_prompt,
+                done.join("\n"),
+                completed.len() + 1
+            )
+        };
+
+        let user_prompt = format!("{}\n\nUse at most {} steps.", user_prompt, max_steps);
+
+        info!("User prompt: {}", user_prompt);
+
+        let request_plan = {
+            let planner_client = &planner_client;
+            let preamble = &preamble;
+            let user_prompt = &user_prompt;
+            move || async move {
+                let plan_response = with_backoff(&self.config.retry, "planning", || {
+                    let user_prompt = user_prompt.clone();
+                    async move { planner_client.prompt(user_prompt).await }
+                }).await.map_err(|e| AgentError::Provider {
+                    agent: "planner".to_string(),
+                    message: e.to_string(),
+                })?;
+                self.budget.record_completion(preamble.len() + user_prompt.len(), plan_response.len());
+
+                debug!("Plan response: {}", plan_response.clone());
+
+                let actual_plan = if plan_response.contains("```json") {
+                    // Keep the content between ```json and ``` from the plan response
+                    // Reason: Claude models output some extra text before and after the json object, so we need to remove it
+                    // TODO: This is a hack, structured output should be implemented in the future
+                    plan_response.split("```json").nth(1).and_then(|plan| plan.split("```").nth(0)).unwrap_or_default().to_string()
+
+                } else {
+                    plan_response
+                };
+
+                info!("Actual plan: {}", actual_plan);
+
+                let plan: AgentPlanResponse = serde_json::from_str(&actual_plan).map_err(|e| AgentError::Planning {
+                    message: format!("Plan is not valid JSON: {}", e),
+                })?;
+                plan_validation::validate(&plan).map_err(|violation| AgentError::Planning {
+                    message: format!("Plan is invalid: {}", violation),
+                })?;
+                Ok::<_, AgentError>(plan)
+            }
+        };
+
+        let agent_plan = if self.config.plan_candidates > 1 {
+            let candidates = join_all((0..self.config.plan_candidates).map(|_| request_plan())).await;
+            self.best_plan(prompt, max_steps, candidates).await?
+        } else {
+            request_plan().await?
+        };
+        let agent_plan = if self.config.critique_plans {
+            self.critique_plan(prompt, max_steps, agent_plan).await?
+        } else {
+            agent_plan
+        };
+
+        // Number the remaining steps after the completed ones, whatever the planner numbered them
+        let mut steps = agent_plan.steps;
+        for (index, step) in steps.iter_mut().enumerate() {
+            step.step_number = (completed.len() + index + 1) as u32;
+        }
+
+        Ok(AgentPlan {
+            id: Uuid::new_v4().to_string(),
+            prompt: prompt.clone(),
+            steps,
+            max_steps,
+            current_step: 0,
+            ens_resolutions: ens_resolutions.to_vec(),
+        })
+    }
+
+    // Have the evaluation model look for issues that would make the plan fail or do the wrong thing.
+    // Blocking issues are patched when the critique can fix them, and otherwise replanned. A critique
+    // that fails itself lets the plan through, it only ever saves a wasted run.
+    async fn critique_plan(&self, prompt: &UserPrompt, max_steps: u32, plan: AgentPlanResponse) -> Result<AgentPlanResponse, AgentError> {
+        const CRITIQUE_PREAMBLE: &str = r#"
+        You are a reviewer of execution plans for an Ethereum agent, checking a plan before it runs.
+        You will be given a user prompt, the constraints of the run and the plan.
+
+        Blocking issues are ones that make the plan fail or do something the user didn't ask for:
+        - a step for an agent that doesn't exist
+        - amounts that don't match the exact wei or raw values annotated in the user prompt, or wrong units
+        - addresses, ENS names or token symbols that differ from the prompt
+        - sending funds to an address or token that no step validates or resolves first
+        - actions that break the constraints, or state-changing actions the user didn't ask for
+        - missing steps the prompt needs, or steps in an order that can't work
+
+        Style, wording and harmless extra checks are warnings, not blocking issues.
+        When you can fix every blocking issue yourself, put the fixed plan in patched_plan.
+
+        You should only output a valid JSON object in the following format:
+        {
+            "blocking_issues": ["Issue that must be fixed"],
+            "warnings": ["Issue that can be ignored"],
+            "patched_plan": null or {"number_of_steps": 1-10, "steps": [{"step_number": 1, "agent_name": "...", "agent_prompt": "..."}]}
+        }
+
+        DO NOT output anything else than the JSON object.
+        "#;
+
+        let mut constraints = vec![
+            format!("at most {} steps", max_steps),
+            "agents: ethereum_agent, search_agent, analysis_agent, defi_agent and ask_user".to_string(),
+        ];
+        if !self.config.networks.is_empty() {
+            let names: Vec<&str> = self.config.networks.keys().map(String::as_str).collect();
+            constraints.push(format!("networks: {}", names.join(", ")));
+        }
+        // A plan that sends what the policy refuses gets stopped halfway through
+        let guard = &self.config.guard;
+        constraints.push(format!(
+            "guard policies (Allow, Confirm or Phrase): value transfers {:?}, approvals {:?}, unlimited approvals {:?}, deployments {:?}, selfdestruct {:?}",
+            guard.value_transfer, guard.approval, guard.unlimited_approval, guard.contract_deployment, guard.selfdestruct
+        ));
+
+        let critique_client = self.provider_client.agent(&self.config.evaluation_model)
+        .preamble(CRITIQUE_PREAMBLE)
+        .build();
+
+        let critique_prompt = format!(
+            "User prompt: {}\n\nConstraints: {}\n\nPlan: {}",
+            prompt.natural_language,
+            constraints.join("; "),
+            serde_json::to_string(&plan).unwrap_or_default()
+        );
+        let critique_response = with_backoff(&self.config.retry, "plan critique", || {
+            let critique_prompt = critique_prompt.clone();
+            let critique_client = &critique_client;
+            async move { critique_client.prompt(critique_prompt).await }
+        }).await;
+        let critique_response = match critique_response {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Plan critique failed, running the plan unchecked: {}", e);
+                return Ok(plan);
+            }
+        };
+        self.budget.record_completion(CRITIQUE_PREAMBLE.len() + critique_prompt.len(), critique_response.len());
+
+        let critique_response = critique_response.replace("```json", "").replace("```", "");
+        let critique: PlanCritiqueResponse = match serde_json::from_str(&critique_response) {
+            Ok(critique) => critique,
+            Err(e) => {
+                warn!("Plan critique is not valid JSON, running the plan unchecked: {}", e);
+                return Ok(plan);
+            }
+        };
+        for warning in &critique.warnings {
+            info!("Plan critique warning: {}", warning);
+        }
+        if critique.blocking_issues.is_empty() {
+            return Ok(plan);
+        }
+
+        let issues = critique.blocking_issues.join("; ");
+        match critique.patched_plan {
+            Some(patched) if plan_validation::validate(&patched).is_ok() => {
+                warn!("Plan critique patched blocking issues: {}", issues);
+                self.events.emit(AgentEvent::Warning {
+                    message: format!("Plan patched before running: {}", issues),
+                });
+                Ok(patched)
+            }
+            _ => Err(AgentError::Planning {
+                message: format!("Plan critique found blocking issues: {}", issues),
+            }),
+        }
+    }
+
+    // The candidate plan the evaluator scores highest. Candidates that failed are dropped, the run
+    // only fails when all of them did.
+    async fn best_plan(
+        &self,
+        prompt: &UserPrompt,
+        max_steps: u32,
+        candidates: Vec<Result<AgentPlanResponse, AgentError>>,
+    ) -> Result<AgentPlanResponse, AgentError> {
+        let mut plans = vec![];
+        let mut first_error = None;
+        for candidate in candidates {
+            match candidate {
+                Ok(plan) => plans.push(plan),
+                Err(e) => {
+                    warn!("Dropping candidate plan: {}", e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if plans.len() <= 1 {
+            return plans.pop().ok_or_else(|| {
+                first_error.unwrap_or(AgentError::Planning {
+                    message: "No candidate plan was generated".to_string(),
+                })
+            });
+        }
+
+        let scores = join_all(plans.iter().map(|plan| self.score_plan(prompt, max_steps, plan))).await;
+        let mut chosen = 0;
+        for (index, score) in scores.iter().enumerate() {
+            if *score > scores[chosen] {
+                chosen = index;
+            }
+        }
+        info!("Picked candidate plan {} of {}, scores {:?}", chosen + 1, plans.len(), scores);
+        self.events.emit(AgentEvent::PlanChosen { chosen, scores });
+
+        Ok(plans.swap_remove(chosen))
+    }
+
+    // Score a candidate plan against the user prompt and the run's constraints, 0 when the
+    // evaluator can't be reached or answers with something other than a score
+    async fn score_plan(&self, prompt: &UserPrompt, max_steps: u32, plan: &AgentPlanResponse) -> u32 {
+        const PLAN_EVALUATION_PREAMBLE: &str = r#"
+        You are an evaluator of execution plans for an Ethereum agent.
+        You will be given a user prompt and a candidate plan of steps for sub-agents.
+        Score how well the plan answers the prompt: every part of the request is covered, steps are
+        in a workable order, amounts, addresses and token symbols are copied exactly, and nothing is
+        done that the user didn't ask for. Prefer fewer steps when plans are otherwise equal.
+
+        You should only output a valid JSON object in the following format:
+        {
+            "score": 0-100,
+            "reasoning": "Reasoning for the score"
+        }
+
+        DO NOT output anything else than the JSON object.
+        "#;
+
+        // Plans that break these are stopped or refused later, so they should lose here already
+        let mut constraints = vec![
+            format!("at most {} steps", max_steps),
+            "only the sub-agents ethereum_agent, search_agent, analysis_agent, defi_agent and ask_user".to_string(),
+            "transfers, approvals and deployments need the user's confirmation, so only send what was asked for".to_string(),
+        ];
+        if !self.config.networks.is_empty() {
+            let names: Vec<&str> = self.config.networks.keys().map(String::as_str).collect();
+            constraints.push(format!("networks must be one of {}", names.join(", ")));
+        }
+
+        let evaluation_client = self.provider_client.agent(&self.config.evaluation_model)
+        .preamble(PLAN_EVALUATION_PREAMBLE)
+        .build();
+
+        let evaluation_prompt = format!(
+            "User prompt: {}\n\nConstraints: {}\n\nCandidate plan: {}",
+            prompt.natural_language,
+            constraints.join("; "),
+            serde_json::to_string(plan).unwrap_or_default()
+        );
+        let evaluation_response = with_backoff(&self.config.retry, "plan evaluation", || {
+            let evaluation_prompt = evaluation_prompt.clone();
+            let evaluation_client = &evaluation_client;
+            async move { evaluation_client.prompt(evaluation_prompt).await }
+        }).await;
+        let evaluation_response = match evaluation_response {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to score candidate plan: {}", e);
+                return 0;
+            }
+        };
+        self.budget.record_completion(PLAN_EVALUATION_PREAMBLE.len() + evaluation_prompt.len(), evaluation_response.len());
+
+        let evaluation_response = evaluation_response.replace("```json", "").replace("```", "");
+        match serde_json::from_str::<EvaluationScoreResponse>(&evaluation_response) {
+            Ok(evaluation) => {
+                info!("Candidate plan score: {} and reasoning: {}", evaluation.score, evaluation.reasoning);
+                evaluation.score
+            }
+            Err(e) => {
+                warn!("Plan evaluator output is not valid JSON: {}", e);
+                0
+            }
+        }
+    }
+
+    async fn agent_loop(
+        &self,
+        prompt: &UserPrompt,
+        agent_plan: &AgentPlan,
+        completed: &[StepRecord],
+        client: Arc<FoundryMcpClient>,
+        guard: &ActionGuard,
+        deadline: Instant,
+        packs: &[Arc<dyn ToolPack>],
+    ) -> Result<AgentResult, AgentError> {
+        info!("Creating execution plan for prompt: {}", prompt.natural_language);
+
+        const ETHEREUM_PREAMBLE: &str = "
+        You are a helpful assistant that creates execution plans for Ethereum transactions.
+
+        The output should be succinct and to the point. 
+
+        Transaction values are in wei. When an amount is annotated with its exact value,
+        e.g. \"0.1 ETH (= 100000000000000000 wei)\", use that value as is.
+
+        ENS names were resolved once at the start of the run, use the pinned address given
+        next to the name and never look names up elsewhere.
+
+
+        Tools:
+        - send_transaction: Send a transaction to the Ethereum network. Every transaction is simulated first
+          and blocked with the revert reason if it would fail; report the reason instead of retrying blindly.
+          Pass private when the user wants protection from front-running or sandwiching (e.g. swaps or
+          large transfers on mainnet); it goes through a private RPC and is refused where none is configured.
+        - balance: Get the balance of an Ethereum address
+        - validate_address: Validate an Ethereum address
+        - get_contract_code: Get the contract code of an Ethereum address
+        - erc20_balance: Get the balance of an ERC20 token for an address
+        - resolve_token: Resolve a token symbol (e.g. USDC) to its checksummed address and decimals
+        - get_price: Get the current USD price of an asset with its update time. Use it for prices
+          instead of guessing, and mention how old the price is.
+          On local forks prefer get_twap, which reads the price from the fork's own Uniswap pools.
+        - gas_oracle: Slow, standard and fast fees with expected inclusion times. On public networks
+          send_transaction uses these fees already, pass fee_tier when the user asks for a faster or
+          cheaper transaction instead of setting gas_price: \"fast\" targets the next block and \"slow\"
+          (for cheap) about 5 minutes, both priced from the mempool.
+        - suggest_priority_fee: Priority fee for a target inclusion time (next_block, 1m, 5m) sampled
+          from recent blocks and pending transactions, for questions about current fees.
+        - safe_info: Owners, confirmation threshold and nonce of a Safe multisig.
+        - propose_safe_transaction: Propose a transaction from a Safe. Use it instead of send_transaction
+          whenever funds come from a Safe; the proposal is signed by the proposer and only executed once
+          enough owners confirm it, so say so in the answer.
+        - sign_permit: Sign an EIP-2612 permit (a gasless approval) and get v, r, s and the deadline.
+          Use it instead of an approve transaction when a following call accepts a permit; it is
+          checked like an approval.
+        - sign_in_with_ethereum: Sign an EIP-4361 sign-in message for a site. Pass the site's own
+          nonce when it gave one; the user confirms the domain before anything is signed. Return the
+          message and signature verbatim, the site needs both.
+        - send_user_operation: Make a call from an ERC-4337 smart account. Use it instead of
+          send_transaction whenever funds come from a smart account; the owner signs and the
+          bundler's transaction carries the call, so report both the user_op_hash and transaction_hash.
+          Pass sponsored when the user asks for a gasless or sponsored transaction; the paymaster pays
+          the gas, and if it declines, report that instead of retrying without sponsorship.
+        - account_history: Transactions, internal transactions, ERC20 or ERC721 transfers of an address,
+          newest first and paginated. Summarize what the address has been doing, fetch further pages
+          only when needed.
+        - tenderly_simulate: Simulate a transaction on Tenderly for a decoded call trace, asset changes
+          and the revert reason. Use it to explain what a contract call would do before sending it.
+
+        Before using token, NFT, vault or Safe tools on a contract you don't know, call detect_standards
+        and pick tools from its standards and suggested_tools; for proxies they describe the implementation.
+
+        Before trusting a contract's verified source (e.g. to review it or to interact with an unknown
+        contract), call verify_bytecode; if it reports a mismatch, warn the user prominently. For proxies
+        check the implementation address as well.
+
+        When the user asks whether a contract or token is safe to use, call risk_score and report its
+        level and reasons.
+
+        To move assets to another chain, call bridge_quote on the source chain. Report the quoted route
+        with the amount received, fees and duration, and the other routes when they trade cost against
+        time; send its transaction only when the step asks to bridge, not just to compare.
+
+        To make several independent calls from one account, such as paying ETH to many recipients,
+        call batch_calls and send its transaction with send_transaction instead of sending each call:
+        one confirmation and less gas. The calls run as Multicall3, not as the sender, so token
+        transfers and approvals can't be batched and are sent one by one.
+
+        To stake or unstake ETH, call staking_transaction (Lido by default, Rocket Pool when asked) and
+        send its transaction with send_transaction; it is confirmed like any other transaction. A Lido
+        unstake needs the stETH approved to its approval_address first, and pays out later: check
+        staking_withdrawals and claim the request once it is claimable.
+
+        Before repaying, borrowing, withdrawing collateral or swapping assets of an account with lending
+        positions, call defi_positions unless an earlier step already reported them, and mention the
+        health factor the step changes.
+
+        For contracts without verified source, call infer_interface and pass its abi to encode_call to
+        read views or build the data for send_transaction. Its names and output types are guesses from
+        public signature databases, say so when relying on them.
+
+        When asked about an NFT, call nft_metadata and describe it by its name, description and traits,
+        giving the image URL rather than the raw token URI.
+
+        For questions about every event of some kind over many blocks, such as all mints of a collection
+        (Transfer with from = the zero address), call scan_events with the event signature including
+        indexed markers and parameter names; it handles the block range itself.
+
+        On a local anvil fork, set_code swaps a contract's bytecode for testing (e.g. a mock oracle) and
+        set_storage_at writes a storage slot, with mapping_key for mapping entries such as a token
+        balance. set_nonce unsticks an account and reset_fork drops every local change (or re-forks
+        at another block) when experiments left the fork in a broken state. These are only offered on
+        anvil, and changes last until the fork is reset or restarted.
+
+        When asked what a transaction did, call explain_transaction and describe its summary in plain
+        English: the action, who sent what to whom (with token symbols and decimals applied), and
+        whether it reverted.
+
+        Any other tools come straight from the foundry server, follow their descriptions.
+
+        Known addresses:
+        Alice: 0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266
+        Bob: 0x70997970C51812dc3A010C7d01b50e0d17dc79C8
+        Vitalik: 0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045
+
+        ERC20 tokens:
+        Resolve every token symbol with resolve_token and use the address and decimals it returns,
+        never an address from memory. If the symbol is unknown or ambiguous, say so instead of guessing.
+        ";
+
+        // Signing-free mode, send_transaction only assembles what the user signs later
+        const UNSIGNED_PREAMBLE: &str = "
+        Transactions are never signed or sent here. send_transaction returns the unsigned transaction
+        (nonce, gas, fees, chain id, calldata) for the user to sign in their own wallet, nothing
+        changes on chain. Say that the transactions are ready to sign, not that they were sent, and
+        don't check balances for their effect. Transactions that depend on an earlier unsigned one
+        can't be simulated until it is signed and mined.
+        ";
+
+        const SEARCH_PREAMBLE: &str = "
+        You are a helpful assistant that can search the web for information.
+
+        Tools:
+        - web_search: Search the web for information. Set country and search_lang for local
+          information (e.g. prices in a local currency), and a small count when one answer is enough.
+          Set fetch_top only when the snippets don't answer the question, it reads the top page.
+        ";
+
+        const ANALYSIS_PREAMBLE: &str = "
+        You are an on-chain data analyst.
+
+        Tools:
+        - dune_query: Run a saved Dune Analytics query by its ID, with parameters by name. Results
+          are limited to a number of rows, say so when they are truncated. Only use query IDs given in
+          the prompt, never guess one.
+        - account_history: Transactions and ERC20/ERC721 transfers of an address, newest first.
+        - get_price: Current USD price of an asset.
+
+        Summarize the numbers that answer the question instead of repeating every row.
+        ";
+
+        const DEFI_PREAMBLE: &str = "
+        You are a DeFi position analyst.
+
+        Tools:
+        - defi_positions: Lending positions (Aave V3, Compound V3) with supplied and borrowed assets,
+          health factor and liquidation_risk, and Uniswap V3 LP positions with their amounts and
+          whether the pool price is in their range.
+        - get_price: Current USD price of an asset.
+        - resolve_token: Token address and decimals of a symbol.
+
+        Always read the positions with defi_positions, never guess them. Lead with the health
+        factor and liquidation risk of every lending position (below 1.0 is liquidated), then what
+        is supplied and borrowed, then the LP positions. When the prompt mentions a planned swap,
+        repay, borrow or withdrawal, say how it would move the health factor.
+        ";
+
+        info!("Looping through steps...");
+
+        // Server tools without a wrapper are bridged from their MCP definitions
+        let listed_tools = match client.list_tools().await {
+            Ok(tools) => tools,
+            Err(e) => {
+                warn!("Could not list foundry-mcp tools, only wrapped tools are available: {}", e);
+                vec![]
+            }
+        };
+        let bridged_tools = bridge_tools(client.clone(), &listed_tools);
+        let defi_tools: Vec<McpBridgeTool> =
+            bridged_tools.iter().filter(|tool| DEFI_TOOLS.contains(&tool.name().as_str())).cloned().collect();
+        debug!("Bridged foundry-mcp tools: {:?}", bridged_tools.iter().map(|tool| tool.name()).collect::<Vec<_>>());
+
+        let plugin_tools = plugin_tools(packs, guard, &self.journal).await;
+        let tools_for = |agent: &str| -> Vec<PluginTool> {
+            plugin_tools.iter().filter(|(name, _)| name == agent).map(|(_, tool)| tool.clone()).collect()
+        };
+
+        let ethereum_preamble = if self.config.unsigned_transactions {
+            format!("{}{}", ETHEREUM_PREAMBLE, UNSIGNED_PREAMBLE)
+        } else {
+            ETHEREUM_PREAMBLE.to_string()
+        };
+        let ethereum_preamble = format!("{}{}", ethereum_preamble, plugin_preamble(packs, "ethereum_agent"));
+        let mut ethereum_agent = SubAgentBuilder::new(
+            self.provider_client.agent(&self.config.execution_model)
+            .preamble(&ethereum_preamble)
+            .temperature(0.7),
+            2,
+        )
+        .retry(self.config.retry.clone())
+        .budget(self.budget.clone())
+        .labels(self.labels.clone())
+        .tool(SendTransactionTool::new(client.clone()))
+        .tool(BalanceTool::new(client.clone()))
+        .tool(GetContractCodeTool::new(client.clone()))
+        .tool(ValidateAddressTool::new(client.clone()))
+        .tool(Erc20BalanceTool::new(client.clone()))
+        .tool(ResolveTokenTool::new(client.clone()))
+        .tool(GetPriceTool::new(client.clone()))
+        .tool(GasOracleTool::new(client.clone()))
+        .tool(SafeInfoTool::new(client.clone()))
+        .tool(ProposeSafeTransactionTool::new(client.clone()))
+        .tool(SignPermitTool::new(client.clone()))
+        .tool(SignInWithEthereumTool::new(client.clone()))
+        .tool(SendUserOperationTool::new(client.clone()))
+        .tool(AccountHistoryTool::new(self.etherscan.clone()))
+        .tool(TenderlySimulateTool::new(self.tenderly.clone()));
+        for tool in bridged_tools {
+            ethereum_agent = ethereum_agent.tool(tool);
+        }
+        for tool in cheatcode_tools(client.clone(), &listed_tools) {
+            ethereum_agent = ethereum_agent.tool(tool);
+        }
+        for tool in tools_for("ethereum_agent") {
+            ethereum_agent = ethereum_agent.tool(tool);
+        }
+        let ethereum_agent = ethereum_agent.build();
+
+        let search_preamble = format!("{}{}", SEARCH_PREAMBLE, plugin_preamble(packs, "search_agent"));
+        let mut search_agent = SubAgentBuilder::new(
+            self.provider_client.agent(&self.config.execution_model)
+            .preamble(&search_preamble)
+            .temperature(0.7),
+            3,
+        )
+        .retry(self.config.retry.clone())
+        .budget(self.budget.clone())
+        .tool(WebSearchTool::new(self.search_provider.clone())
+            .with_config(&self.config.search)
+            .with_cache(self.search_cache.clone()));
+        for tool in tools_for("search_agent") {
+            search_agent = search_agent.tool(tool);
+        }
+        let search_agent = search_agent.build();
+
+        let analysis_preamble = format!("{}{}", ANALYSIS_PREAMBLE, plugin_preamble(packs, "analysis_agent"));
+        let mut analysis_agent = SubAgentBuilder::new(
+            self.provider_client.agent(&self.config.execution_model)
+            .preamble(&analysis_preamble)
+            .temperature(0.3),
+            3,
+        )
+        .retry(self.config.retry.clone())
+        .budget(self.budget.clone())
+        .tool(DuneQueryTool::new(self.dune.clone()))
+        .tool(AccountHistoryTool::new(self.etherscan.clone()))
+        .tool(GetPriceTool::new(client.clone()));
+        for tool in tools_for("analysis_agent") {
+            analysis_agent = analysis_agent.tool(tool);
+        }
+        let analysis_agent = analysis_agent.build();
+
+        let defi_preamble = format!("{}{}", DEFI_PREAMBLE, plugin_preamble(packs, "defi_agent"));
+        let mut defi_agent = SubAgentBuilder::new(
+            self.provider_client.agent(&self.config.execution_model)
+            .preamble(&defi_preamble)
+            .temperature(0.3),
+            3,
+        )
+        .retry(self.config.retry.clone())
+        .budget(self.budget.clone())
+        .labels(self.labels.clone())
+        .tool(GetPriceTool::new(client.clone()))
+        .tool(ResolveTokenTool::new(client.clone()));
+        for tool in defi_tools {
+            defi_agent = defi_agent.tool(tool);
+        }
+        for tool in tools_for("defi_agent") {
+            defi_agent = defi_agent.tool(tool);
+        }
+        let defi_agent = defi_agent.build();
+
+        // Implement memory, starting from the outputs of steps completed before a replan
+        let mut memory: Vec<String> = completed.iter().map(|step| step.output.clone().unwrap_or_default()).collect();
+        let total_steps = completed.len() + agent_plan.steps.len();
+
+        // Repeat the pinned ENS names in every step, in case the planner dropped them from a step prompt
+        let pinned_names = if agent_plan.ens_resolutions.is_empty() {
+            String::new()
+        } else {
+            let names: Vec<String> = agent_plan.ens_resolutions.iter().map(|resolution| resolution.display()).collect();
+            format!("\nPinned ENS names: {}\n", names.join(", "))
+        };
+
+        // Reject unknown networks before any step runs, not halfway through the plan
+        for step in agent_plan.steps.iter().filter(|step| step.agent_name == "ethereum_agent") {
+            self.network_url(step.network.as_deref())?;
+        }
+
+        // Price the whole plan up front, so its gas cost is approved once before anything is sent
+        if self.config.budget.estimate_plan_cost {
+            if let Some(estimate) = plan_cost::estimate(agent_plan, &client).await {
+                info!("Estimated plan cost: {}", estimate.describe());
+                self.journal.set_cost_estimate(&estimate);
+                self.events.emit(AgentEvent::PlanCostEstimate { estimate: estimate.clone() });
+                let description = format!("The plan sends {}", estimate.describe());
+                guard.check_plan_cost(&description).await.map_err(|message| AgentError::PolicyViolation { message })?;
+            }
+        }
+
+        for (index, step) in agent_plan.steps.iter().enumerate() {
+            info!("Step: {}", step.step_number);
+
+            // Never run more steps than configured, however long the plan is
+            if index as u32 >= agent_plan.max_steps {
+                return Ok(self.max_steps_reached_result(agent_plan, &memory));
+            }
+
+            // Stop with partial results once the run deadline has passed
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(self.deadline_exceeded_result(step.step_number, total_steps, &memory));
+            }
+            self.enforce_budget().await?;
+            self.journal.step_started(step.step_number);
+
+            let sub_agent = match step.agent_name.as_str() {
+                "ask_user" => {
+                    memory.push(self.ask_user(step).await?);
+                    self.journal.step_completed(step.step_number, memory.last().unwrap());
+                    continue;
+                }
+                "ethereum_agent" => &ethereum_agent,
+                "search_agent" => &search_agent,
+                "analysis_agent" => &analysis_agent,
+                "defi_agent" => &defi_agent,
+                _ => {
+                    error!("Unknown agent name: {}", step.agent_name);
+                    return Err(AgentError::Planning {
+                        message: format!("Unknown agent name: {}", step.agent_name),
+                    });
+                }
+            };
+
+            // Later steps build on earlier transactions, wait for them to be mined (on their own
+            // network, before switching away from it)
+            client.confirm_sent_transactions().await.map_err(|e| AgentError::Tool {
+                tool: Some("transaction_status".to_string()),
+                message: e.to_string(),
+            })?;
+
+            // Point foundry-mcp at the step's network before the agent calls any tool
+            let network_note = if step.agent_name == "ethereum_agent" {
+                self.switch_network(&client, step.network.as_deref()).await?;
+                if let Some(chain) = step.chain {
+                    let chain_id = self.current_chain_id(&client).await?;
+                    if chain_id != chain {
+                        return Err(AgentError::Planning {
+                            message: format!(
+                                "Step {} is for chain {} but network {} is chain {}",
+                                step.step_number,
+                                chain,
+                                step.network.as_deref().unwrap_or("default"),
+                                chain_id
+                            ),
+                        });
+                    }
+                }
+                step.network.as_ref().map(|network| format!("\nNetwork: {}\n", network)).unwrap_or_default()
+            } else {
+                String::new()
+            };
+
+            // Ask before dangerous steps, transactions are checked again when they are sent
+            let description = format!("Step {} \"{}\"", step.step_number, step.agent_prompt);
+            guard.check_step(classify_step(step), &description).await.map_err(|message| AgentError::PolicyViolation { message })?;
+
+            // A failed step is run again on its own before the whole plan is replanned, so the
+            // steps before it (which may have sent transactions) are kept
+            let mut attempt = 0;
+            let mut feedback = String::new();
+            let response = loop {
+                attempt += 1;
+                let retries_left = attempt <= self.config.step_retries;
+                let calls_before = self.journal.tool_calls(step.step_number).len();
+                let balances_before = postconditions::baseline(&client, &step.postconditions).await;
+
+                self.events.emit(AgentEvent::StepStarted {
+                    step_number: step.step_number,
+                    agent_name: step.agent_name.clone(),
+                });
+
+                let request = sub_agent.run(step.agent_prompt.clone() + &network_note + &pinned_names + &feedback + "Previous steps: " + &memory.join("\n"), &self.events);
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let step_timeout = self.config.timeouts.step().min(remaining);
+                let response = match timeout(step_timeout, request).await {
+                    Ok(Ok(response)) => {
+                        info!("Response: {}", response);
+                        response
+                    }
+                    Ok(Err(e)) if retries_left && classify(&e.to_string()).is_transient() => {
+                        self.retry_step(step.step_number, attempt, &format!("{} failed: {}", step.agent_name, e)).await?;
+                        feedback = self.retry_feedback(step.step_number, None);
+                        continue;
+                    }
+                    Ok(Err(e)) => {
+                        error!("Failed to get response from {}: {}", step.agent_name, e);
+                        return Err(AgentError::Provider {
+                            agent: step.agent_name.clone(),
+                            message: e.to_string(),
+                        });
+                    }
+                    Err(_) if Instant::now() >= deadline => {
+                        return Ok(self.deadline_exceeded_result(step.step_number, total_steps, &memory));
+                    }
+                    Err(_) if retries_left => {
+                        let reason = format!("{} timed out after {}s", step.agent_name, step_timeout.as_secs());
+                        self.retry_step(step.step_number, attempt, &reason).await?;
+                        feedback = self.retry_feedback(step.step_number, None);
+                        continue;
+                    }
+                    Err(_) => {
+                        error!("{} timed out after {}s", step.agent_name, step_timeout.as_secs());
+                        return Err(AgentError::Provider {
+                            agent: step.agent_name.clone(),
+                            message: format!("Timed out after {}s", step_timeout.as_secs()),
+                        });
+                    }
+                };
+
+                self.events.emit(AgentEvent::StepFinished {
+                    step_number: step.step_number,
+                });
+
+                // The user refused a transaction of this step, don't let a retry or replan try again
+                if let Some(message) = guard.refused() {
+                    return Err(AgentError::PolicyViolation { message });
+                }
+
+                // Read back what the step's transactions did, so a transfer that never landed is
+                // rejected whatever the agent claims
+                let checks = if self.config.ground_evaluations && step.agent_name == "ethereum_agent" {
+                    let calls: Vec<ToolCallRecord> = self.journal.tool_calls(step.step_number).into_iter().skip(calls_before).collect();
+                    grounding::verify_step(&client, step, &calls).await
+                } else {
+                    None
+                };
+                let mut failed = self.check_postconditions(&client, step, &balances_before).await;
+                failed.extend(checks.iter().flatten().filter(|check| !check.passed).map(|check| check.description.clone()));
+                let evaluation = if !failed.is_empty() {
+                    warn!("Step {} failed its on-chain checks: {}", step.step_number, failed.join("; "));
+                    EvaluationResult {
+                        plan_id: Uuid::new_v4().to_string(),
+                        original_prompt: prompt.natural_language.clone(),
+                        score: 0,
+                        reasoning: format!("On-chain checks failed: {}", failed.join("; ")),
+                    }
+                } else {
+                    let verified: Vec<&str> = checks.iter().flatten().map(|check| check.description.as_str()).collect();
+                    let evaluation = self.evaluate_result(&prompt, step.step_number, &step.agent_prompt, &response, &verified);
+                    let evaluator_timeout = self.config.timeouts.step().min(deadline.saturating_duration_since(Instant::now()));
+                    match timeout(evaluator_timeout, evaluation).await {
+                        Ok(evaluation) => evaluation?,
+                        Err(_) if Instant::now() >= deadline => {
+                            return Ok(self.deadline_exceeded_result(step.step_number, total_steps, &memory));
+                        }
+                        Err(_) => {
+                            error!("evaluator timed out after {}s", evaluator_timeout.as_secs());
+                            return Err(AgentError::Provider {
+                                agent: "evaluator".to_string(),
+                                message: format!("Timed out after {}s", evaluator_timeout.as_secs()),
+                            });
+                        }
+                    }
+                };
+
+                self.journal.step_evaluated(step.step_number, evaluation.score, &evaluation.reasoning);
+                self.enforce_budget().await?;
+
+                if evaluation.score >= self.config.evaluation_threshold {
+                    break response;
+                }
+
+                let reason = format!(
+                    "Score {} is below threshold {}: {}",
+                    evaluation.score, self.config.evaluation_threshold, evaluation.reasoning
+                );
+                if !retries_left {
+                    error!("Evaluation score is below threshold: {}, returning error", evaluation.score);
+                    return Err(AgentError::Evaluation {
+                        step_number: step.step_number,
+                        score: Some(evaluation.score),
+                        reason,
+                    });
+                }
+                self.retry_step(step.step_number, attempt, &reason).await?;
+                let rejection = self.config.step_retry_feedback.then_some((response.as_str(), evaluation.reasoning.as_str()));
+                feedback = self.retry_feedback(step.step_number, rejection);
+            };
+
+            memory.push(response);
+            self.journal.step_completed(step.step_number, memory.last().unwrap());
+        }
+
+        let result = memory.last().unwrap_or(&"Failed to get response from agent".to_string()).clone();
+
+        info!("Agent loop response: {:?}", result);
+
+        Ok(AgentResult::new(PlanStatus::Completed, result, None))
+    }
+
+    // Post-conditions the planner attached to a step that don't hold, once its transactions are mined
+    async fn check_postconditions(&self, client: &FoundryMcpClient, step: &AgentStep, balances_before: &[Option<U256>]) -> Vec<String> {
+        // Unsigned transactions change nothing until the user signs them
+        if step.postconditions.is_empty() || self.config.unsigned_transactions {
+            return vec![];
+        }
+        if let Err(e) = client.confirm_sent_transactions().await {
+            return vec![format!("post-conditions could not be checked: {}", e)];
+        }
+        let failures = postconditions::check(client, &step.postconditions, balances_before).await;
+        failures.into_iter().map(|failure| format!("post-condition {}", failure)).collect()
+    }
+
+    async fn retry_step(&self, step_number: u32, attempt: u32, reason: &str) -> Result<(), AgentError> {
+        warn!("Step {} attempt {} failed, running it again: {}", step_number, attempt, reason);
+        self.events.emit(AgentEvent::StepRetry {
+            step_number,
+            attempt: attempt + 1,
+            reason: reason.to_string(),
+        });
+        self.enforce_budget().await
+    }
+
+    // Note for the next attempt of a step: why the last answer was rejected, if it was, and the
+    // transactions earlier attempts already sent, which must not be sent twice
+    fn retry_feedback(&self, step_number: u32, rejection: Option<(&str, &str)>) -> String {
+        let sent: Vec<String> = self
+            .journal
+            .snapshot()
+            .into_iter()
+            .flat_map(|run| run.steps)
+            .filter(|step| step.step_number == step_number)
+            .flat_map(|step| step.tool_calls)
+            .filter_map(|call| call.transaction_hash())
+            .collect();
+
+        let mut feedback = String::new();
+        if let Some((response, reasoning)) = rejection {
+            feedback += &format!("\nA previous attempt of this step answered \"{}\" and was rejected: {}\n", response, reasoning);
+        }
+        if !sent.is_empty() {
+            feedback += &format!("\nEarlier attempts of this step already sent transactions {}, do not send them again.\n", sent.join(", "));
+        }
+        feedback
+    }
+
+    // Put a planned clarification question to the user, the answer is remembered like a step result
+    async fn ask_user(&self, step: &AgentStep) -> Result<String, AgentError> {
+        info!("Asking user: {}", step.agent_prompt);
+        self.events.emit(AgentEvent::StepStarted {
+            step_number: step.step_number,
+            agent_name: step.agent_name.clone(),
+        });
+
+        let answer = self.interaction.ask(&step.agent_prompt).await.filter(|answer| !answer.trim().is_empty());
+        let Some(answer) = answer else {
+            warn!("No answer to clarification question: {}", step.agent_prompt);
+            return Err(AgentError::ClarificationNeeded {
+                question: step.agent_prompt.clone(),
+            });
+        };
+
+        self.events.emit(AgentEvent::StepFinished {
+            step_number: step.step_number,
+        });
+
+        Ok(format!("The user was asked \"{}\" and answered: {}", step.agent_prompt, redact(answer.trim())))
+    }
+
+    // RPC URL of a configured network, `None` stays on foundry-mcp's own network
+    fn network_url(&self, network: Option<&str>) -> Result<Option<&str>, AgentError> {
+        let Some(name) = network else {
+            return Ok(None);
+        };
+        match self.config.networks.get(name) {
+            Some(rpc_url) => Ok(Some(rpc_url.as_str())),
+            None => {
+                let names: Vec<&str> = self.config.networks.keys().map(String::as_str).collect();
+                Err(AgentError::Planning {
+                    message: format!("Unknown network: {} (configured networks: {})", name, if names.is_empty() { "none".to_string() } else { names.join(", ") }),
+                })
+            }
+        }
+    }
+
+    // RPC URL of the network to fork for a what-if step, foundry-mcp's own network has to be given
+    fn fork_source(&self, network: Option<&str>) -> Result<String, AgentError> {
+        if let Some(rpc_url) = self.network_url(network)? {
+            return Ok(rpc_url.to_string());
+        }
+        self.config.what_if.fork_url.clone().or_else(|| std::env::var("ETH_RPC_URL").ok()).ok_or_else(|| AgentError::Tool {
+            tool: None,
+            message: "What-if runs need the RPC URL of the default network to fork it, set [what_if] fork_url or ETH_RPC_URL".to_string(),
+        })
+    }
+
+    async fn switch_network(&self, client: &FoundryMcpClient, network: Option<&str>) -> Result<(), AgentError> {
+        let rpc_url = match &self.forks {
+            Some(forks) => Some(forks.rpc_url(&self.fork_source(network)?).await.map_err(|e| AgentError::Tool {
+                tool: None,
+                message: format!("Could not fork network {}: {}", network.unwrap_or("default"), e),
+            })?),
+            None => self.network_url(network)?.map(str::to_string),
+        };
+        if client.network() == rpc_url {
+            return Ok(());
+        }
+
+        let chain_id = client.use_network(rpc_url.as_deref()).await.map_err(|e| AgentError::Tool {
+            tool: Some("use_network".to_string()),
+            message: e.to_string(),
+        })?;
+        info!("Switched to network {} (chain id {})", network.unwrap_or("default"), chain_id);
+        Ok(())
+    }
+
+    // Chain id of the network foundry-mcp is on, asked for once per network
+    async fn current_chain_id(&self, client: &FoundryMcpClient) -> Result<u64, AgentError> {
+        if let Some(chain_id) = client.chain_id() {
+            return Ok(chain_id);
+        }
+        client.use_network(client.network().as_deref()).await.map_err(|e| AgentError::Tool {
+            tool: Some("use_network".to_string()),
+            message: e.to_string(),
+        })
+    }
+
+    // Give ethereum_agent steps with a chain but no network the first network on that chain,
+    // foundry-mcp's own network before the configured ones
+    async fn resolve_chains(&self, mut plan: AgentPlan, client: &FoundryMcpClient) -> Result<AgentPlan, AgentError> {
+        let mut wanted: Vec<u64> = plan
+            .steps
+            .iter()
+            .filter(|step| step.agent_name == "ethereum_agent" && step.network.is_none())
+            .filter_map(|step| step.chain)
+            .collect();
+        if wanted.is_empty() {
+            return Ok(plan);
+        }
+
+        // Transactions of an earlier attempt are confirmed on their own network before switching away
+        client.confirm_sent_transactions().await.map_err(|e| AgentError::Tool {
+            tool: Some("transaction_status".to_string()),
+            message: e.to_string(),
+        })?;
+
+        let candidates = std::iter::once(None).chain(self.config.networks.keys().cloned().map(Some));
+        let mut chains: Vec<(Option<String>, u64)> = vec![];
+        for network in candidates {
+            if wanted.is_empty() {
+                break;
+            }
+            self.switch_network(client, network.as_deref()).await?;
+            let chain_id = self.current_chain_id(client).await?;
+            wanted.retain(|chain| *chain != chain_id);
+            chains.push((network, chain_id));
+        }
+
+        for step in plan.steps.iter_mut().filter(|step| step.agent_name == "ethereum_agent" && step.network.is_none()) {
+            let Some(chain) = step.chain else {
+                continue;
+            };
+            match chains.iter().find(|(_, chain_id)| *chain_id == chain) {
+                Some((network, _)) => step.network = network.clone(),
+                None => {
+                    let known: Vec<String> = chains
+                        .iter()
+                        .map(|(network, chain_id)| format!("{} is chain {}", network.as_deref().unwrap_or("default"), chain_id))
+                        .collect();
+                    return Err(AgentError::Planning {
+                        message: format!("No configured network is on chain {} ({})", chain, known.join(", ")),
+                    });
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    fn max_steps_reached_result(&self, agent_plan: &AgentPlan, memory: &[String]) -> AgentResult {
+        warn!("Plan has {} steps, stopping after {}", agent_plan.steps.len(), agent_plan.max_steps);
+        self.journal.skip_planned_steps();
+
+        let error_message = format!(
+            "Plan has {} steps but at most {} are executed, returning partial results",
+            agent_plan.steps.len(),
+            agent_plan.max_steps
+        );
+        AgentResult::new(PlanStatus::MaxStepsReached, memory.join("\n"), Some(error_message))
+    }
+
+    fn deadline_exceeded_result(&self, step_number: u32, total_steps: usize, memory: &[String]) -> AgentResult {
+        warn!("Run deadline exceeded before step {}, returning partial results", step_number);
+        self.journal.fail_current_step("Run deadline exceeded");
+
+        let error_message = format!(
+            "Run deadline of {}s exceeded, completed {} of {} steps",
+            self.config.timeouts.run_deadline_secs,
+            memory.len(),
+            total_steps
+        );
+
+        AgentResult::new(PlanStatus::Failed(error_message.clone()), memory.join("\n"), Some(error_message))
+    }
+
+    async fn evaluate_result(
+        &self,
+        original_prompt: &UserPrompt,
+        step_number: u32,
+        agent_prompt: &str,
+        result: &str,
+        verified: &[&str],
+    ) -> Result<EvaluationResult, AgentError> {
+        info!("Evaluating execution result against original prompt");
+
+        const EVALUATION_PREAMBLE: &str = r#"
+        You are an evaluator of agent execution results.
+        You will be given a result from an agent and an agent prompt that the agent was given, and a user prompt that the agent was given.
+        You will need to evaluate the result and determine if it is aligned with the agent prompt and user prompt.
+        You will need to return a score between 0 and 100.
+        Facts verified on chain, when given, were read back after the step and are authoritative:
+        judge whether they match what the agent prompt asked for (recipient, amount, contract), and
+        never score a result highly when they contradict it.
+
+        You should only output a valid JSON object in the following format:
+        {{
+            "score": 0-100,
+            "reasoning": "Reasoning for the score"
+        }}
+
+        DO NOT output anything else than the JSON object.
+
+        "#;
+        let evaluation_client = self.provider_client.agent(&self.config.evaluation_model)
+        .preamble(EVALUATION_PREAMBLE)
+        .build();
+
+        let mut evaluation_prompt = format!("Evaluate the following result: {} against the current agent prompt: {} and user prompt: {}", result, agent_prompt, original_prompt.natural_language);
+        if !verified.is_empty() {
+            evaluation_prompt += &format!("\n\nVerified on chain: {}", verified.join("; "));
+        }
+        let evaluation_response = with_backoff(&self.config.retry, "evaluation", || {
+            let evaluation_prompt = evaluation_prompt.clone();
+            let evaluation_client = &evaluation_client;
+            async move { evaluation_client.prompt(evaluation_prompt).await }
+        }).await.map_err(|e| AgentError::Provider {
+            agent: "evaluator".to_string(),
+            message: e.to_string(),
+        })?;
+        self.budget.record_completion(EVALUATION_PREAMBLE.len() + evaluation_prompt.len(), evaluation_response.len());
+
+        // Remove ```json and ``` from the evaluation response if they exist
+        let evaluation_response = evaluation_response.replace("```json", "").replace("```", "");
+
+        let evaluation_response: EvaluationScoreResponse = serde_json::from_str(&evaluation_response).map_err(|e| AgentError::Evaluation {
+            step_number,
+            score: None,
+            reason: format!("Evaluator output is not valid JSON: {}", e),
+        })?;
+
+        info!("Evaluation score: {} and reasoning: {}", evaluation_response.score, evaluation_response.reasoning);
+
+        Ok(EvaluationResult {
+            plan_id: Uuid::new_v4().to_string(),
+            original_prompt: original_prompt.natural_language.clone(),
+            score: evaluation_response.score,
+            reasoning: evaluation_response.reasoning,
+        })
+    }
+} 
@@ -0,0 +1,304 @@
+use alloy_primitives::{utils::{format_units, parse_units}, U256};
+use std::collections::HashMap;
+
+// Units we can convert without asking the chain, with their decimals
+const NATIVE_UNITS: &[(&str, u8)] = &[("eth", 18), ("ether", 18), ("gwei", 9), ("wei", 0)];
+
+// ERC20 symbols read as amounts in prompts. Their decimals differ between chains (USDC and USDT
+// have 18 on BNB Chain), so they are resolved on the run's network, see `TokenDecimals`.
+pub const AMOUNT_TOKENS: &[&str] = &["usdc", "usdt", "dai"];
+
+// Most decimals any chain gives an `AMOUNT_TOKENS` token
+const MAX_TOKEN_DECIMALS: u8 = 18;
+
+// Decimals of the `AMOUNT_TOKENS` on the run's network, by lowercase symbol. Tokens missing from it
+// are left for the agents to resolve, their amounts aren't pinned.
+pub type TokenDecimals = HashMap<String, u8>;
+
+// An amount found in a prompt, e.g. "0.1 ETH"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedAmount {
+    pub amount: String,
+    pub unit: String,
+    pub decimals: u8,
+    // Value in the smallest unit (wei for ETH, raw token units for ERC20s)
+    pub raw: U256,
+    pub is_token: bool,
+}
+
+impl ParsedAmount {
+    pub fn annotation(&self) -> String {
+        if self.is_token {
+            format!("(= {} raw {} units, {} decimals)", self.raw, self.unit, self.decimals)
+        } else {
+            format!("(= {} wei)", self.raw)
+        }
+    }
+}
+
+fn lookup_unit(unit: &str, tokens: &TokenDecimals) -> Option<(u8, bool)> {
+    let unit = unit.to_lowercase();
+    if let Some((_, decimals)) = NATIVE_UNITS.iter().find(|(name, _)| *name == unit) {
+        return Some((*decimals, false));
+    }
+    tokens.get(&unit).map(|decimals| (*decimals, true))
+}
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_end_matches(|c: char| matches!(c, '.' | ',' | '?' | '!' | ';' | ':' | ')'))
+}
+
+// Parse a number and unit into an amount, e.g. ("1,000", "USDC")
+fn parse_amount(number: &str, unit: &str, tokens: &TokenDecimals) -> Option<ParsedAmount> {
+    let number = number.trim_start_matches('(').replace(',', "");
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+
+    let unit = trim_punctuation(unit);
+    let (decimals, is_token) = lookup_unit(unit, tokens)?;
+    let raw = parse_units(&number, decimals).ok()?.get_absolute();
+
+    Some(ParsedAmount {
+        amount: number,
+        unit: unit.to_string(),
+        decimals,
+        raw,
+        is_token,
+    })
+}
+
+// Split a word like "0.1ETH" into its number and unit
+fn split_glued(word: &str) -> Option<(&str, &str)> {
+    let index = word.find(|c: char| c.is_ascii_alphabetic())?;
+    if index == 0 {
+        return None;
+    }
+    Some(word.split_at(index))
+}
+
+// The words of `text` with the byte offset each one ends at, so annotations can be inserted
+// without touching the whitespace around them, line breaks included
+fn words_with_ends(text: &str) -> Vec<(&str, usize)> {
+    text.split_whitespace()
+        .map(|word| (word, word.as_ptr() as usize - text.as_ptr() as usize + word.len()))
+        .collect()
+}
+
+// `text` with each annotation inserted after the byte offset it belongs to, in order
+fn insert_annotations(text: &str, annotations: &[(usize, String)]) -> String {
+    let mut output = String::with_capacity(text.len() + annotations.iter().map(|(_, annotation)| annotation.len() + 1).sum::<usize>());
+    let mut copied = 0;
+    for (end, annotation) in annotations {
+        output.push_str(&text[copied..*end]);
+        output.push(' ');
+        output.push_str(annotation);
+        copied = *end;
+    }
+    output.push_str(&text[copied..]);
+    output
+}
+
+// The `AMOUNT_TOKENS` that `text` gives amounts in, lowercase, to resolve before annotating it
+pub fn token_units(text: &str) -> Vec<String> {
+    let mut units: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        let unit = split_glued(word).map_or(word, |(_, unit)| unit);
+        let unit = trim_punctuation(unit).to_lowercase();
+        if AMOUNT_TOKENS.contains(&unit.as_str()) && !units.contains(&unit) {
+            units.push(unit);
+        }
+    }
+    units
+}
+
+// Numbers in front of a known unit that don't parse as an amount of it, e.g. "1.2.3 ETH" or
+// "0.0000000000000000001 ETH" (more decimals than ETH has). Words like "all ETH" or an address
+// followed by a symbol are not amounts at all. The chain isn't known here, so token amounts are
+// only invalid when no chain's decimals fit them.
+pub fn invalid_amounts(text: &str) -> Vec<String> {
+    let tokens: TokenDecimals = AMOUNT_TOKENS.iter().map(|symbol| (symbol.to_string(), MAX_TOKEN_DECIMALS)).collect();
+    let looks_numeric = |word: &str| {
+        let word = word.trim_start_matches('(');
+        word.starts_with(|c: char| c.is_ascii_digit() || c == '.') && !word.starts_with("0x")
+    };
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words
+        .windows(2)
+        .filter(|pair| looks_numeric(pair[0]) && lookup_unit(trim_punctuation(pair[1]), &tokens).is_some())
+        .filter(|pair| parse_amount(pair[0], pair[1], &tokens).is_none())
+        .map(|pair| format!("{} {}", pair[0], trim_punctuation(pair[1])))
+        .collect()
+}
+
+// Rewrite the prompt so every recognised amount carries its exact value in the smallest unit,
+// e.g. "Send 0.1 ETH to Bob" -> "Send 0.1 ETH (= 100000000000000000 wei) to Bob"
+pub fn annotate_amounts(text: &str, tokens: &TokenDecimals) -> (String, Vec<ParsedAmount>) {
+    let words = words_with_ends(text);
+    let mut annotations = vec![];
+    let mut amounts = vec![];
+
+    let mut i = 0;
+    while i < words.len() {
+        let (word, end) = words[i];
+
+        // "0.1 ETH"
+        if let Some((next, next_end)) = words.get(i + 1) {
+            if let Some(amount) = parse_amount(word, next, tokens) {
+                annotations.push((*next_end, amount.annotation()));
+                amounts.push(amount);
+                i += 2;
+                continue;
+            }
+        }
+
+        // "0.1ETH"
+        if let Some(amount) = split_glued(word).and_then(|(number, unit)| parse_amount(number, unit, tokens)) {
+            annotations.push((end, amount.annotation()));
+            amounts.push(amount);
+        }
+        i += 1;
+    }
+
+    (insert_annotations(text, &annotations), amounts)
+}
+
+// Precision of fiat amounts and prices while converting, more than any feed reports
+const FIAT_DECIMALS: u8 = 18;
+
+// A fiat-denominated amount of an asset, e.g. "$50 worth of ETH" or "100 USD of DAI"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiatAmount {
+    pub phrase: String,
+    pub usd: String,
+    pub unit: String,
+    pub decimals: u8,
+    pub is_token: bool,
+}
+
+// A fiat amount converted at the price the run started with, pinned so every step sends the same
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiatConversion {
+    pub fiat: FiatAmount,
+    pub price_usd: String,
+    // The converted amount in whole units, e.g. "0.0125"
+    pub amount: String,
+    pub raw: U256,
+}
+
+impl FiatConversion {
+    pub fn annotation(&self) -> String {
+        let raw_unit = if self.fiat.is_token { format!("raw {} units", self.fiat.unit) } else { "wei".to_string() };
+        format!(
+            "(= {} {} = {} {} at ${} per {})",
+            self.amount, self.fiat.unit, self.raw, raw_unit, self.price_usd, self.fiat.unit
+        )
+    }
+
+    // Both denominations, e.g. "0.0125 ETH ≈ $50 at $4000 per ETH"
+    pub fn describe(&self) -> String {
+        format!("{} {} ≈ ${} at ${} per {}", self.amount, self.fiat.unit, self.fiat.usd, self.price_usd, self.fiat.unit)
+    }
+}
+
+fn fixed(number: &str) -> Option<U256> {
+    // Feeds and prompts may carry more decimals than the conversion keeps
+    let number = match number.split_once('.') {
+        Some((whole, fraction)) if fraction.len() > FIAT_DECIMALS as usize => format!("{}.{}", whole, &fraction[..FIAT_DECIMALS as usize]),
+        _ => number.to_string(),
+    };
+    parse_units(&number, FIAT_DECIMALS).ok().map(|value| value.get_absolute())
+}
+
+fn fiat_number(word: &str) -> Option<String> {
+    let number = word.replace(',', "");
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit() || c == '.') && fixed(&number).is_some()).then_some(number)
+}
+
+// A fiat amount starting at `words[i]`, with the number of words it spans: "$50 worth of ETH",
+// "$50 of ETH", "50 USD worth of ETH" or "50 dollars of ETH"
+fn fiat_phrase(words: &[&str], i: usize, tokens: &TokenDecimals) -> Option<(FiatAmount, usize)> {
+    let (usd, mut next) = match words[i].strip_prefix('$') {
+        Some(number) => (fiat_number(number)?, i + 1),
+        None => {
+            let currency = words.get(i + 1)?.to_lowercase();
+            if !matches!(currency.as_str(), "usd" | "dollars" | "dollar") {
+                return None;
+            }
+            (fiat_number(words[i])?, i + 2)
+        }
+    };
+    if words.get(next).is_some_and(|word| word.eq_ignore_ascii_case("worth")) {
+        next += 1;
+    }
+    if !words.get(next)?.eq_ignore_ascii_case("of") {
+        return None;
+    }
+    let unit = trim_punctuation(words.get(next + 1)?);
+    // Dollars' worth of gwei or wei is not something anyone asks for
+    if matches!(unit.to_lowercase().as_str(), "gwei" | "wei") {
+        return None;
+    }
+    let (decimals, is_token) = lookup_unit(unit, tokens)?;
+    let end = next + 2;
+    let fiat = FiatAmount {
+        phrase: format!("{} {}", words[i..end - 1].join(" "), unit),
+        usd,
+        unit: unit.to_string(),
+        decimals,
+        is_token,
+    };
+    Some((fiat, end - i))
+}
+
+// Every fiat amount in the text, in order of appearance
+pub fn find_fiat_amounts(text: &str, tokens: &TokenDecimals) -> Vec<FiatAmount> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut amounts = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        match fiat_phrase(&words, i, tokens) {
+            Some((amount, length)) => {
+                amounts.push(amount);
+                i += length;
+            }
+            None => i += 1,
+        }
+    }
+    amounts
+}
+
+// The amount of `fiat` worth its USD value at `price_usd`, rounded down to the asset's smallest unit
+pub fn convert_fiat(fiat: &FiatAmount, price_usd: &str) -> Option<FiatConversion> {
+    let price = fixed(price_usd).filter(|price| !price.is_zero())?;
+    let raw = fixed(&fiat.usd)?.checked_mul(U256::from(10).pow(U256::from(fiat.decimals)))? / price;
+    let amount = format_units(raw, fiat.decimals).ok()?;
+    let amount = if amount.contains('.') { amount.trim_end_matches('0').trim_end_matches('.').to_string() } else { amount };
+    Some(FiatConversion {
+        fiat: fiat.clone(),
+        price_usd: price_usd.to_string(),
+        amount,
+        raw,
+    })
+}
+
+// Rewrite the text so every converted fiat amount carries the amount it was pinned to,
+// e.g. "Send $50 worth of ETH to Bob" -> "Send $50 worth of ETH (= 0.0125 ETH = 12500000000000000 wei at $4000 per ETH) to Bob"
+pub fn annotate_fiat(text: &str, conversions: &[FiatConversion], tokens: &TokenDecimals) -> String {
+    let words_and_ends = words_with_ends(text);
+    let words: Vec<&str> = words_and_ends.iter().map(|(word, _)| *word).collect();
+    let mut annotations = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let found = fiat_phrase(&words, i, tokens)
+            .and_then(|(amount, length)| Some((conversions.iter().find(|conversion| conversion.fiat == amount)?, length)));
+        match found {
+            Some((conversion, length)) => {
+                annotations.push((words_and_ends[i + length - 1].1, conversion.annotation()));
+                i += length;
+            }
+            None => i += 1,
+        }
+    }
+    insert_annotations(text, &annotations)
+}
@@ -0,0 +1,128 @@
+use serde_json::Value;
+
+use crate::types::{Artifact, StepRecord, StepStatus};
+
+fn text(payload: &Value, name: &str) -> Option<String> {
+    payload.get(name)?.as_str().map(|value| value.to_string())
+}
+
+// Artifacts of one successful tool call
+fn from_call(step_number: u32, tool: &str, payload: &Value) -> Vec<Artifact> {
+    match tool {
+        "send_transaction" => {
+            let hash = text(payload, "transaction_hash");
+            let mut artifacts: Vec<Artifact> = hash
+                .iter()
+                .map(|hash| Artifact::TransactionHash {
+                    step_number,
+                    hash: hash.clone(),
+                })
+                .collect();
+            if let Some(address) = text(payload, "contract_address") {
+                artifacts.push(Artifact::DeployedContract {
+                    step_number,
+                    address,
+                    transaction_hash: hash,
+                    abi: None,
+                });
+            }
+            artifacts
+        }
+        "balance" => match (text(payload, "address"), text(payload, "balance")) {
+            (Some(address), Some(balance)) => vec![Artifact::Balance {
+                step_number,
+                address,
+                token: None,
+                balance,
+                decimals: 18,
+            }],
+            _ => vec![],
+        },
+        "erc20_balance" => match (text(payload, "account_address"), text(payload, "balance")) {
+            (Some(address), Some(balance)) => vec![Artifact::Balance {
+                step_number,
+                address,
+                token: text(payload, "symbol").or_else(|| text(payload, "token_address")),
+                balance,
+                decimals: payload.get("decimals").and_then(Value::as_u64).unwrap_or_default() as u8,
+            }],
+            _ => vec![],
+        },
+        "sign_permit" => match (text(payload, "owner"), text(payload, "signature")) {
+            (Some(signer), Some(signature)) => vec![Artifact::Signature {
+                step_number,
+                signer,
+                signature,
+                digest: text(payload, "digest"),
+                deadline: payload.get("deadline").and_then(Value::as_u64),
+            }],
+            _ => vec![],
+        },
+        "sign_in_with_ethereum" => match (text(payload, "address"), text(payload, "signature")) {
+            (Some(signer), Some(signature)) => vec![Artifact::Signature {
+                step_number,
+                signer,
+                signature,
+                digest: None,
+                deadline: None,
+            }],
+            _ => vec![],
+        },
+        "build_unsigned_transaction" => match payload.get("unsigned_transaction") {
+            Some(transaction) => vec![Artifact::UnsignedTransaction {
+                step_number,
+                transaction: transaction.clone(),
+                eip681: text(payload, "eip681"),
+            }],
+            None => vec![],
+        },
+        "explain_transaction" => vec![Artifact::Report {
+            step_number,
+            title: format!("Transaction {}", text(payload, "hash").unwrap_or_default()),
+            content: payload.clone(),
+        }],
+        _ => vec![],
+    }
+}
+
+// Typed values from the tool outputs of a run's steps, in the order they were produced. Failed
+// calls and blocked transactions produce nothing.
+pub fn extract(steps: &[StepRecord]) -> Vec<Artifact> {
+    let mut artifacts = vec![];
+    for step in steps {
+        for call in &step.tool_calls {
+            let Some(payload) = call.payload() else {
+                continue;
+            };
+            if payload.get("success").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+
+            // An interface inferred for a contract the run deployed is that contract's ABI
+            if call.tool == "infer_interface" {
+                let address = text(&payload, "address").unwrap_or_default();
+                for artifact in artifacts.iter_mut() {
+                    if let Artifact::DeployedContract { address: deployed, abi, .. } = artifact {
+                        if deployed.eq_ignore_ascii_case(&address) {
+                            *abi = payload.get("abi").cloned();
+                        }
+                    }
+                }
+                continue;
+            }
+
+            artifacts.extend(from_call(step.step_number, &call.tool, &payload));
+        }
+
+        if step.agent_name == "analysis_agent" && matches!(step.status, StepStatus::Completed) {
+            if let Some(output) = &step.output {
+                artifacts.push(Artifact::Report {
+                    step_number: step.step_number,
+                    title: step.agent_prompt.clone(),
+                    content: Value::String(output.clone()),
+                });
+            }
+        }
+    }
+    artifacts
+}
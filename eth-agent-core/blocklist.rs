@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use alloy_primitives::hex;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::BlocklistConfig;
+
+// transfer, approve, increaseAllowance and setApprovalForAll name the recipient or spender first
+const FIRST_ARGUMENT_SELECTORS: &[&str] = &["a9059cbb", "095ea7b3", "39509351", "a22cb465"];
+// transferFrom and both safeTransferFrom name it second
+const SECOND_ARGUMENT_SELECTORS: &[&str] = &["23b872dd", "42842e0e", "f242432a"];
+
+#[derive(Debug, Default)]
+struct Listed {
+    // Lowercased address -> the source listing it
+    addresses: HashMap<String, String>,
+    loaded_at: Option<Instant>,
+}
+
+/// Known scam and phishing addresses from the `[blocklist]` sources. Transactions, UserOperations,
+/// Safe proposals and permits touching one are refused whatever the guard policy; only the
+/// addresses in `allow` get past it. Downloaded lists are cached in `cache_dir` and fetched again
+/// once older than `refresh_hours`. Cloning shares the loaded lists.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    config: BlocklistConfig,
+    allowed: Arc<HashSet<String>>,
+    listed: Arc<Mutex<Listed>>,
+}
+
+// The 20-byte address in the 32-byte argument word at `index`
+fn argument_address(data: &str, index: usize) -> Option<String> {
+    let start = 8 + index * 64 + 24;
+    data.get(start..start + 40).map(|address| format!("0x{}", address))
+}
+
+// The address a transaction goes to and, for token transfers and approvals, the recipient or
+// spender in its calldata
+fn targets(to: &str, data: Option<&str>) -> Vec<String> {
+    let mut targets = vec![to.to_lowercase()];
+    let data = data.unwrap_or_default().trim_start_matches("0x").to_lowercase();
+    let argument = match data.get(..8) {
+        Some(selector) if FIRST_ARGUMENT_SELECTORS.contains(&selector) => argument_address(&data, 0),
+        Some(selector) if SECOND_ARGUMENT_SELECTORS.contains(&selector) => argument_address(&data, 1),
+        _ => None,
+    };
+    targets.extend(argument);
+    targets
+}
+
+// A JSON array of addresses, or one address per line with `#` comments
+fn parse(content: &str) -> Vec<String> {
+    let entries: Vec<String> = match serde_json::from_str(content) {
+        Ok(entries) => entries,
+        Err(_) => content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim().to_string())
+            .collect(),
+    };
+    entries
+        .into_iter()
+        .map(|entry| entry.trim().to_lowercase())
+        .filter(|entry| entry.len() == 42 && entry.starts_with("0x") && entry[2..].bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .collect()
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+impl Blocklist {
+    pub fn new(config: &BlocklistConfig) -> Self {
+        Self {
+            config: config.clone(),
+            allowed: Arc::new(config.allow.iter().map(|address| address.to_lowercase()).collect()),
+            listed: Default::default(),
+        }
+    }
+
+    fn refresh_after(&self) -> Duration {
+        Duration::from_secs(self.config.refresh_hours * 3600)
+    }
+
+    fn cache_file(&self, source: &str) -> Option<PathBuf> {
+        let digest = hex::encode(Sha256::digest(source.as_bytes()));
+        Some(self.config.cache_dir.as_ref()?.join(format!("{}.txt", &digest[..16])))
+    }
+
+    // A cached copy of a downloaded list, when it is younger than `refresh_hours` or `stale` is set
+    fn cached(&self, source: &str, stale: bool) -> Option<String> {
+        let path = self.cache_file(source)?;
+        let age = std::fs::metadata(&path).ok()?.modified().ok()?.elapsed().unwrap_or_default();
+        if !stale && age >= self.refresh_after() {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()
+    }
+
+    async fn download(&self, source: &str) -> Result<String> {
+        let content = reqwest::get(source).await?.error_for_status()?.text().await?;
+        if let Some(path) = self.cache_file(source) {
+            let written = std::fs::create_dir_all(path.parent().unwrap_or(&path)).and_then(|_| std::fs::write(&path, &content));
+            if let Err(e) = written {
+                warn!("Could not cache blocklist {} in {}: {}", source, path.display(), e);
+            }
+        }
+        Ok(content)
+    }
+
+    // Read every source, downloading lists whose cache is missing or old (all of them with
+    // `force`). A list that can't be downloaded falls back to its cache however old it is.
+    async fn load(&self, listed: &mut Listed, force: bool) -> Result<usize> {
+        let sources = self.config.sources.as_deref().unwrap_or_default();
+        let mut addresses = HashMap::new();
+        let mut failures = vec![];
+        for source in sources {
+            let content = if !is_url(source) {
+                std::fs::read_to_string(source).map_err(anyhow::Error::from)
+            } else if let Some(content) = self.cached(source, false).filter(|_| !force) {
+                Ok(content)
+            } else {
+                match self.download(source).await {
+                    Ok(content) => Ok(content),
+                    Err(e) => self.cached(source, true).ok_or(e),
+                }
+            };
+            match content {
+                Ok(content) => addresses.extend(parse(&content).into_iter().map(|address| (address, source.clone()))),
+                Err(e) => {
+                    warn!("Skipping blocklist {}: {}", source, e);
+                    failures.push(source.as_str());
+                }
+            }
+        }
+
+        listed.loaded_at = Some(Instant::now());
+        if !sources.is_empty() && failures.len() == sources.len() {
+            return Err(anyhow::anyhow!("No blocklist could be loaded from {}", failures.join(", ")));
+        }
+        info!("Loaded {} blocklisted addresses from {} source(s)", addresses.len(), sources.len() - failures.len());
+        listed.addresses = addresses;
+        Ok(listed.addresses.len())
+    }
+
+    /// Download every list again, ignoring the cache. Returns how many addresses are listed.
+    pub async fn refresh(&self) -> Result<usize> {
+        let mut listed = self.listed.lock().await;
+        self.load(&mut listed, true).await
+    }
+
+    /// The listed address among `to` and the recipient or spender of its calldata, with the source
+    /// listing it. Loads the lists on first use and again once they are older than `refresh_hours`.
+    pub async fn find(&self, to: &str, data: Option<&str>) -> Option<(String, String)> {
+        if self.config.sources.as_deref().unwrap_or_default().is_empty() {
+            return None;
+        }
+        let mut listed = self.listed.lock().await;
+        if listed.loaded_at.is_none_or(|loaded_at| loaded_at.elapsed() >= self.refresh_after()) {
+            if let Err(e) = self.load(&mut listed, false).await {
+                warn!("{}", e);
+            }
+        }
+        targets(to, data)
+            .into_iter()
+            .filter(|address| !self.allowed.contains(address))
+            .find_map(|address| listed.addresses.get(&address).map(|source| (address.clone(), source.clone())))
+    }
+}
@@ -0,0 +1,139 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::U256;
+
+use crate::config::BudgetConfig;
+
+// Rough token estimate for text, providers don't report usage through every rig call we make
+pub fn estimate_tokens(text_len: usize) -> u64 {
+    (text_len as u64).div_ceil(4)
+}
+
+/// Estimated LLM tokens and gas fees spent by a run
+#[derive(Debug, Clone, Default)]
+pub struct BudgetUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub gas_wei: U256,
+}
+
+impl BudgetUsage {
+    /// Input and output tokens together
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// Estimated LLM spend at the configured token prices
+    pub fn cost_usd(&self, config: &BudgetConfig) -> f64 {
+        (self.input_tokens as f64 * config.input_usd_per_million_tokens
+            + self.output_tokens as f64 * config.output_usd_per_million_tokens)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Default)]
+struct BudgetState {
+    usage: BudgetUsage,
+    // The user agreed to go over the limit for the rest of the run
+    tokens_waived: bool,
+    gas_waived: bool,
+    // Set when a send was refused for going over the gas budget, ends the run
+    refused: Option<String>,
+}
+
+// Spending of the current run, shared between the agent, its sub-agents and the MCP client
+#[derive(Clone)]
+pub struct RunBudget {
+    config: BudgetConfig,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl RunBudget {
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = BudgetState::default();
+    }
+
+    pub fn usage(&self) -> BudgetUsage {
+        self.state.lock().unwrap().usage.clone()
+    }
+
+    // Record one completion by the length of the text sent and received
+    pub fn record_completion(&self, input_len: usize, output_len: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.usage.input_tokens += estimate_tokens(input_len);
+        state.usage.output_tokens += estimate_tokens(output_len);
+    }
+
+    pub fn record_gas(&self, gas_wei: U256) {
+        self.state.lock().unwrap().usage.gas_wei += gas_wei;
+    }
+
+    // Why the token or cost limit is exceeded, unless the user already waived it
+    pub fn token_overrun(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.tokens_waived {
+            return None;
+        }
+
+        let tokens = state.usage.tokens();
+        if let Some(max_tokens) = self.config.max_tokens {
+            if tokens > max_tokens {
+                return Some(format!("Token budget exceeded: ~{} of {} tokens used", tokens, max_tokens));
+            }
+        }
+
+        let cost = state.usage.cost_usd(&self.config);
+        if let Some(max_cost) = self.config.max_cost_usd {
+            if cost > max_cost {
+                return Some(format!("Cost budget exceeded: ~${:.4} of ${:.4} spent", cost, max_cost));
+            }
+        }
+
+        None
+    }
+
+    // Why sending a transaction with this gas cost would exceed the gas limit
+    pub fn gas_overrun(&self, estimated_gas_wei: U256) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.gas_waived {
+            return None;
+        }
+
+        let max_gas = U256::from_str(self.config.max_gas_wei.as_deref()?).ok()?;
+        let total = state.usage.gas_wei + estimated_gas_wei;
+        (total > max_gas).then(|| {
+            format!(
+                "Gas budget exceeded: this transaction would bring gas spend to {} of {} wei",
+                total, max_gas
+            )
+        })
+    }
+
+    pub fn waive_tokens(&self) {
+        self.state.lock().unwrap().tokens_waived = true;
+    }
+
+    pub fn waive_gas(&self) {
+        self.state.lock().unwrap().gas_waived = true;
+    }
+
+    pub fn refuse(&self, reason: &str) {
+        self.state.lock().unwrap().refused = Some(reason.to_string());
+    }
+
+    pub fn refused(&self) -> Option<String> {
+        self.state.lock().unwrap().refused.clone()
+    }
+}
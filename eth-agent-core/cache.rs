@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::search::SearchOptions;
+
+// TTL for account balances, short enough to roughly track new blocks
+const BALANCE_TTL: Duration = Duration::from_secs(12);
+// Deployed code rarely changes, so it can be cached for longer
+const CONTRACT_CODE_TTL: Duration = Duration::from_secs(300);
+// Token lists are loaded once when the server starts, only a network switch (which clears the
+// cache) changes what a symbol resolves to
+const TOKEN_TTL: Duration = Duration::from_secs(3600);
+// An inferred interface only changes with the code. The storage it probes can go stale, but any
+// state-changing call clears the cache.
+const INTERFACE_TTL: Duration = Duration::from_secs(3600);
+
+// Simple thread-safe cache where every entry expires after its own TTL
+pub struct TtlCache<V: Clone> {
+    entries: Mutex<HashMap<String, (Instant, V)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((expires_at, value)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                // Drop the expired entry so the map doesn't grow unbounded
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: String, value: V, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, (Instant::now() + ttl, value));
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl<V: Clone> Default for TtlCache<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Cache for read-only MCP tool results, keyed by tool name + arguments. None of the cached tools
+// take a block, they always read "latest".
+pub struct ToolCache {
+    entries: TtlCache<serde_json::Value>,
+}
+
+impl ToolCache {
+    pub fn new() -> Self {
+        Self {
+            entries: TtlCache::new(),
+        }
+    }
+
+    // Only read-only tools have a TTL, everything else is never cached
+    pub fn ttl_for(tool: &str) -> Option<Duration> {
+        match tool {
+            "balance" | "erc20_balance" => Some(BALANCE_TTL),
+            "get_contract_code" => Some(CONTRACT_CODE_TTL),
+            "resolve_token" => Some(TOKEN_TTL),
+            "infer_interface" => Some(INTERFACE_TTL),
+            _ => None,
+        }
+    }
+
+    fn key(tool: &str, arguments: &serde_json::Value) -> String {
+        // serde_json objects are ordered maps, so the serialized arguments are canonical
+        format!("{}:{}", tool, arguments)
+    }
+
+    pub fn get(&self, tool: &str, arguments: &serde_json::Value) -> Option<serde_json::Value> {
+        Self::ttl_for(tool)?;
+        self.entries.get(&Self::key(tool, arguments))
+    }
+
+    pub fn insert(&self, tool: &str, arguments: &serde_json::Value, value: serde_json::Value) {
+        if let Some(ttl) = Self::ttl_for(tool) {
+            self.entries.insert(Self::key(tool, arguments), value, ttl);
+        }
+    }
+
+    // Called after any state-changing call, since cached reads may now be stale
+    pub fn invalidate_all(&self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for ToolCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Web search responses by normalized query, shared by every plan of the agent so replans and
+// repeated steps don't pay for the same search twice. A zero TTL disables it.
+pub struct SearchCache {
+    entries: TtlCache<serde_json::Value>,
+    ttl: Duration,
+}
+
+impl SearchCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: TtlCache::new(),
+            ttl,
+        }
+    }
+
+    // Case and spacing don't change what a search engine returns
+    pub fn key(provider: &str, query: &str, options: &SearchOptions, fetch_top: bool) -> String {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        format!(
+            "{}:{}:{}:{}:{}:{}",
+            provider,
+            options.count,
+            options.country.as_deref().unwrap_or_default().to_lowercase(),
+            options.search_lang.as_deref().unwrap_or_default().to_lowercase(),
+            fetch_top,
+            query
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.entries.get(key)
+    }
+
+    pub fn insert(&self, key: String, value: serde_json::Value) {
+        if !self.ttl.is_zero() {
+            self.entries.insert(key, value, self.ttl);
+        }
+    }
+}
@@ -0,0 +1,779 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::guard::ActionClass;
+use crate::notifications::Severity;
+use crate::search::{SearchOptions, SearchProviderKind};
+
+const DEFAULT_MODEL: &str = "claude-3-5-haiku-20241022";
+const DEFAULT_EVALUATION_THRESHOLD: u32 = 70;
+const DEFAULT_MAX_STEPS: u32 = 10;
+const DEFAULT_STEP_RETRIES: u32 = 2;
+const DEFAULT_CONFIG_FILE: &str = "eth-agent.toml";
+const DEFAULT_EXPLORER_URL: &str = "https://etherscan.io";
+
+// Built-in per-tool timeouts in seconds, tools not listed here use `tool_secs`.
+// Lookups that only hit the node are quick, calls that simulate or wait on receipts are slow.
+const DEFAULT_TOOL_TIMEOUTS: &[(&str, u64)] = &[
+    ("validate_address", 5),
+    ("use_network", 15),
+    ("resolve_token", 10),
+    ("resolve_ens", 15),
+    ("balance", 15),
+    ("get_price", 20),
+    ("gas_oracle", 20),
+    ("simulate_transaction", 60),
+    ("preview_transaction", 60),
+    ("send_transaction", 90),
+    ("propose_safe_transaction", 60),
+    // Waits up to a minute for the bundler to include the operation
+    ("send_user_operation", 120),
+];
+
+// Agent configuration, loaded from a TOML file with every field optional
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub planning_model: String,
+    pub execution_model: String,
+    pub evaluation_model: String,
+    pub evaluation_threshold: u32,
+    // Plans generated in parallel per planning round. With more than one, the evaluator scores them
+    // and the best one runs, at the cost of the extra planner and evaluator tokens
+    pub plan_candidates: u32,
+    // Check every plan for blocking issues (missing validations, wrong units, unknown agents, policy
+    // conflicts) with the evaluation model before running it
+    pub critique_plans: bool,
+    // Steps executed per plan, the rest of a longer plan is skipped
+    pub max_steps: u32,
+    // Check sent transactions on chain (mined without a revert, deployed code present) before a
+    // state-changing step is scored, a step whose transactions never landed is rejected outright
+    pub ground_evaluations: bool,
+    // Snapshot local anvil chains before every run, so `EthAgent::undo` can revert the run
+    pub snapshot_local_chains: bool,
+    // Never sign or send: every transaction is assembled fully populated but unsigned, for the
+    // user to sign in an external wallet (see `/bundle`)
+    pub unsigned_transactions: bool,
+    // Extra attempts of a step that was rejected or failed transiently, before the plan is replanned
+    pub step_retries: u32,
+    // Tell a retried step why its last answer was rejected
+    pub step_retry_feedback: bool,
+    // Block explorer used for transaction links in run reports
+    pub explorer_url: String,
+    pub timeouts: TimeoutConfig,
+    pub retry: RetryConfig,
+    pub budget: BudgetConfig,
+    pub guard: GuardConfig,
+    pub search: SearchConfig,
+    pub etherscan: EtherscanConfig,
+    pub tenderly: TenderlyConfig,
+    pub dune: DuneConfig,
+    pub mcp: McpConfig,
+    pub runs: RunsConfig,
+    pub webhooks: WebhooksConfig,
+    pub notifications: NotificationsConfig,
+    pub what_if: WhatIfConfig,
+    pub private_transactions: PrivateTransactionsConfig,
+    pub blocklist: BlocklistConfig,
+    // Networks plan steps can switch to by name, e.g. mainnet = "https://...". Steps without
+    // one run on foundry-mcp's own network.
+    pub networks: BTreeMap<String, String>,
+    // Names for addresses shown next to them, e.g. "0x7099..." = "Bob's cold wallet"
+    pub labels: BTreeMap<String, String>,
+    // Third-party MCP servers whose tools are added to the sub-agents, see `plugins.rs`
+    pub plugins: Vec<PluginConfig>,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            planning_model: DEFAULT_MODEL.to_string(),
+            execution_model: DEFAULT_MODEL.to_string(),
+            evaluation_model: DEFAULT_MODEL.to_string(),
+            evaluation_threshold: DEFAULT_EVALUATION_THRESHOLD,
+            plan_candidates: 1,
+            critique_plans: true,
+            max_steps: DEFAULT_MAX_STEPS,
+            ground_evaluations: true,
+            snapshot_local_chains: true,
+            unsigned_transactions: false,
+            step_retries: DEFAULT_STEP_RETRIES,
+            step_retry_feedback: true,
+            explorer_url: DEFAULT_EXPLORER_URL.to_string(),
+            timeouts: TimeoutConfig::default(),
+            retry: RetryConfig::default(),
+            budget: BudgetConfig::default(),
+            guard: GuardConfig::default(),
+            search: SearchConfig::default(),
+            etherscan: EtherscanConfig::default(),
+            tenderly: TenderlyConfig::default(),
+            dune: DuneConfig::default(),
+            mcp: McpConfig::default(),
+            runs: RunsConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            notifications: NotificationsConfig::default(),
+            what_if: WhatIfConfig::default(),
+            private_transactions: PrivateTransactionsConfig::default(),
+            blocklist: BlocklistConfig::default(),
+            networks: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            plugins: vec![],
+        }
+    }
+}
+
+// Settings read once when the agent and its clients are built, a reloaded config can't change them
+pub const RESTART_ONLY_SETTINGS: &[&str] = &["runs", "webhooks", "notifications", "etherscan", "tenderly", "dune", "plugins"];
+
+impl AgentConfig {
+    // Load from $ETH_AGENT_CONFIG, or ./eth-agent.toml if it exists, otherwise use defaults
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    // The file `load` reads: $ETH_AGENT_CONFIG, or ./eth-agent.toml if it exists
+    pub fn path() -> Option<PathBuf> {
+        match std::env::var("ETH_AGENT_CONFIG") {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+        let config = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file {}: {}", path.display(), e))?;
+
+        Ok(config)
+    }
+
+    // Top-level settings (keys or tables of the config file) that differ from `other`'s
+    pub fn changed_settings(&self, other: &AgentConfig) -> Vec<&'static str> {
+        macro_rules! changed {
+            ($($setting:ident),* $(,)?) => {
+                [$((stringify!($setting), self.$setting != other.$setting)),*]
+            };
+        }
+        let settings = changed!(
+            planning_model, execution_model, evaluation_model, evaluation_threshold, plan_candidates, critique_plans,
+            max_steps, ground_evaluations, snapshot_local_chains, unsigned_transactions, step_retries, step_retry_feedback,
+            explorer_url, timeouts, retry, budget, guard, search, etherscan, tenderly, dune, mcp, runs, webhooks,
+            notifications, what_if, private_transactions, blocklist, networks, labels, plugins,
+        );
+        settings.into_iter().filter(|(_, changed)| *changed).map(|(setting, _)| setting).collect()
+    }
+}
+
+// Poll the config file at `path` every `interval` and send the config `load` makes of it whenever
+// it changed, after logging which settings did. A file that fails to load is logged and skipped,
+// the last good config stays in use. Stops once the receiver is dropped.
+pub fn watch_config<F>(path: PathBuf, interval: Duration, current: AgentConfig, load: F) -> mpsc::UnboundedReceiver<AgentConfig>
+where
+    F: Fn(&Path) -> Result<AgentConfig> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    tokio::spawn(async move {
+        let mut current = current;
+        let mut last_modified = modified(&path);
+        loop {
+            tokio::time::sleep(interval).await;
+            if sender.is_closed() {
+                break;
+            }
+            let now_modified = modified(&path);
+            if now_modified == last_modified {
+                continue;
+            }
+            last_modified = now_modified;
+
+            let config = match load(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("Config file changed but can't be loaded, keeping the current settings: {}", e);
+                    continue;
+                }
+            };
+            let changed = config.changed_settings(&current);
+            if changed.is_empty() {
+                continue;
+            }
+            info!("Config file {} changed: {}", path.display(), changed.join(", "));
+            let needs_restart: Vec<&str> = changed.iter().copied().filter(|setting| RESTART_ONLY_SETTINGS.contains(setting)).collect();
+            if !needs_restart.is_empty() {
+                warn!("Changes to {} only take effect after a restart", needs_restart.join(", "));
+            }
+            current = config.clone();
+            if sender.send(config).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// Timeouts in seconds
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    // A single sub-agent prompt, including its tool calls
+    pub step_secs: u64,
+    // A single MCP tool call, for tools without their own timeout
+    pub tool_secs: u64,
+    // Wall-clock deadline for a whole run, including replans
+    pub run_deadline_secs: u64,
+    // How long a sent transaction may stay pending before the next send or step gives up on it
+    pub confirmation_secs: u64,
+    // Per-tool overrides by tool name, on top of the built-in defaults
+    pub tools: HashMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            step_secs: 120,
+            tool_secs: 30,
+            run_deadline_secs: 600,
+            confirmation_secs: 180,
+            tools: HashMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn step(&self) -> Duration {
+        Duration::from_secs(self.step_secs)
+    }
+
+    pub fn tool(&self) -> Duration {
+        Duration::from_secs(self.tool_secs)
+    }
+
+    // Timeout for one call of `name`: the configured override, the built-in default, then `tool_secs`
+    pub fn tool_timeout(&self, name: &str) -> Duration {
+        let secs = self.tools.get(name).copied().or_else(|| {
+            DEFAULT_TOOL_TIMEOUTS
+                .iter()
+                .find(|(tool, _)| *tool == name)
+                .map(|(_, secs)| *secs)
+        });
+        Duration::from_secs(secs.unwrap_or(self.tool_secs))
+    }
+
+    pub fn run_deadline(&self) -> Duration {
+        Duration::from_secs(self.run_deadline_secs)
+    }
+
+    pub fn confirmation(&self) -> Duration {
+        Duration::from_secs(self.confirmation_secs)
+    }
+}
+
+// Retry policy for transient provider and tool failures
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    // Total attempts including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8000,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+}
+
+// What to do once a run goes over budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    #[default]
+    Abort,
+    // Ask the user whether to carry on, aborting if they say no
+    Confirm,
+}
+
+// Per-run spending limits, every limit is off unless set
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    // Estimated LLM tokens (input + output) across planning, execution and evaluation
+    pub max_tokens: Option<u64>,
+    // Estimated LLM spend, priced with the rates below
+    pub max_cost_usd: Option<f64>,
+    pub input_usd_per_million_tokens: f64,
+    pub output_usd_per_million_tokens: f64,
+    // Gas fees of sent transactions in wei, as a string since it can exceed u64
+    pub max_gas_wei: Option<String>,
+    pub on_exceeded: BudgetAction,
+    // Price the plan's state-changing steps before running them, approved per `[guard] plan_cost`
+    pub estimate_plan_cost: bool,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        // Claude 3.5 Haiku pricing
+        Self {
+            max_tokens: None,
+            max_cost_usd: None,
+            input_usd_per_million_tokens: 0.8,
+            output_usd_per_million_tokens: 4.0,
+            max_gas_wei: None,
+            on_exceeded: BudgetAction::Abort,
+            estimate_plan_cost: true,
+        }
+    }
+}
+
+// How a class of action is let through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardPolicy {
+    Allow,
+    // Ask the user yes/no
+    Confirm,
+    // The user has to type the confirmation phrase
+    Phrase,
+}
+
+// Policy per class of dangerous action, read-only steps are always allowed
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GuardConfig {
+    pub value_transfer: GuardPolicy,
+    pub approval: GuardPolicy,
+    pub contract_deployment: GuardPolicy,
+    pub unlimited_approval: GuardPolicy,
+    pub selfdestruct: GuardPolicy,
+    // Approval of a plan's estimated gas cost before any of its steps run
+    pub plan_cost: GuardPolicy,
+    // Signing a Sign-In-With-Ethereum message, which logs a site in as the account
+    pub sign_in: GuardPolicy,
+    // Calls of plugin tools that don't declare themselves read-only
+    pub plugins: GuardPolicy,
+    // Anvil cheatcodes rewriting local chain state outside of any transaction
+    pub cheatcodes: GuardPolicy,
+    pub confirmation_phrase: String,
+    pub mev: MevGuardConfig,
+    pub risk: RiskGuardConfig,
+}
+
+impl GuardConfig {
+    // Let everything through, for unattended environments like local test chains
+    pub fn allow_all() -> Self {
+        Self {
+            value_transfer: GuardPolicy::Allow,
+            approval: GuardPolicy::Allow,
+            contract_deployment: GuardPolicy::Allow,
+            unlimited_approval: GuardPolicy::Allow,
+            selfdestruct: GuardPolicy::Allow,
+            plan_cost: GuardPolicy::Allow,
+            sign_in: GuardPolicy::Allow,
+            plugins: GuardPolicy::Allow,
+            cheatcodes: GuardPolicy::Allow,
+            mev: MevGuardConfig {
+                policy: GuardPolicy::Allow,
+                ..MevGuardConfig::default()
+            },
+            risk: RiskGuardConfig {
+                policy: GuardPolicy::Allow,
+                ..RiskGuardConfig::default()
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn policy(&self, class: ActionClass) -> GuardPolicy {
+        match class {
+            ActionClass::ReadOnly => GuardPolicy::Allow,
+            ActionClass::ValueTransfer => self.value_transfer,
+            ActionClass::Approval => self.approval,
+            ActionClass::ContractDeployment => self.contract_deployment,
+            ActionClass::UnlimitedApproval => self.unlimited_approval,
+            ActionClass::SelfDestruct => self.selfdestruct,
+        }
+    }
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            value_transfer: GuardPolicy::Confirm,
+            approval: GuardPolicy::Confirm,
+            contract_deployment: GuardPolicy::Confirm,
+            unlimited_approval: GuardPolicy::Phrase,
+            selfdestruct: GuardPolicy::Phrase,
+            plan_cost: GuardPolicy::Confirm,
+            sign_in: GuardPolicy::Confirm,
+            plugins: GuardPolicy::Confirm,
+            cheatcodes: GuardPolicy::Confirm,
+            confirmation_phrase: "I accept the risk".to_string(),
+            mev: MevGuardConfig::default(),
+            risk: RiskGuardConfig::default(),
+        }
+    }
+}
+
+// Sandwich risk of swaps bound for the public mempool, judged from their preview's `mev_exposure`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MevGuardConfig {
+    // How an exposed swap is let through, "allow" only warns
+    pub policy: GuardPolicy,
+    // Send exposed swaps through the network's private RPC instead, refusing them without one
+    pub require_private: bool,
+    // Slippage tolerance a front-runner may push the price by, in basis points
+    pub max_slippage_bps: u64,
+    // Price impact of the trade, high in shallow pools, in basis points
+    pub max_price_impact_bps: u64,
+    // Swaps worth more than this many wei of ETH are exposed whatever their slippage
+    pub large_value_wei: String,
+}
+
+impl Default for MevGuardConfig {
+    fn default() -> Self {
+        Self {
+            policy: GuardPolicy::Confirm,
+            require_private: false,
+            max_slippage_bps: 100,
+            max_price_impact_bps: 100,
+            large_value_wei: "10000000000000000000".to_string(),
+        }
+    }
+}
+
+// Risk of calling a contract for the first time, scored by the server's risk_score tool
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RiskGuardConfig {
+    // Score every contract before the run first calls it
+    pub require_for_new_contracts: bool,
+    // Contracts scoring this much or more (out of 100) go through `policy`
+    pub max_score: u8,
+    // How a risky contract, or one that couldn't be scored, is let through
+    pub policy: GuardPolicy,
+}
+
+impl Default for RiskGuardConfig {
+    fn default() -> Self {
+        Self {
+            require_for_new_contracts: false,
+            max_score: 70,
+            policy: GuardPolicy::Confirm,
+        }
+    }
+}
+
+// Web search backend, its API key is looked up by `SearchProviderKind::key_name`.
+// The defaults apply to searches that don't set their own count, country or language.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub provider: SearchProviderKind,
+    #[serde(flatten)]
+    pub defaults: SearchOptions,
+    // Snippets are cut to this many characters before they reach the model
+    pub max_snippet_chars: usize,
+    // Length of the top page summary when a search asks for it
+    pub page_summary_chars: usize,
+    // How long a search response is reused for the same query, 0 disables the cache
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            provider: SearchProviderKind::default(),
+            defaults: SearchOptions::default(),
+            max_snippet_chars: 300,
+            page_summary_chars: 1500,
+            cache_ttl_secs: 300,
+        }
+    }
+}
+
+// Etherscan account history, the API key is the "etherscan" secret
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EtherscanConfig {
+    // Etherscan V2 serves every supported chain from one endpoint
+    pub api_url: String,
+    pub chain_id: u64,
+    pub page_size: u32,
+}
+
+impl Default for EtherscanConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.etherscan.io/v2/api".to_string(),
+            chain_id: 1,
+            page_size: 25,
+        }
+    }
+}
+
+// Tenderly simulations, the access key is the "tenderly" secret
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TenderlyConfig {
+    pub account: String,
+    pub project: String,
+    pub network_id: String,
+}
+
+impl Default for TenderlyConfig {
+    fn default() -> Self {
+        Self {
+            account: String::new(),
+            project: String::new(),
+            network_id: "1".to_string(),
+        }
+    }
+}
+
+// Dune Analytics queries, the API key is the "dune" secret
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DuneConfig {
+    // Rows returned to the model per query, results can be huge
+    pub max_rows: u32,
+    // How long to wait for a query execution before giving up
+    pub max_wait_secs: u64,
+}
+
+impl Default for DuneConfig {
+    fn default() -> Self {
+        Self {
+            max_rows: 50,
+            max_wait_secs: 120,
+        }
+    }
+}
+
+// How the agent reaches foundry-mcp
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpTransport {
+    // Spawn a local foundry-mcp child process and talk over its stdio
+    #[default]
+    Stdio,
+    // Streamable HTTP, e.g. a shared server started with `foundry-mcp --http`
+    Http,
+    // Legacy HTTP + server-sent events
+    Sse,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct McpConfig {
+    pub transport: McpTransport,
+    // Server endpoint for the http and sse transports, e.g. "http://127.0.0.1:8545/mcp"
+    pub url: Option<String>,
+    // foundry-mcp binary for the stdio transport, found next to the agent executable by default
+    pub binary: Option<PathBuf>,
+}
+
+// A tool pack served by a third-party MCP server, one `[[plugins]]` table each
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    // Namespace of the pack's tools, which reach the model as `<name>__<tool>`
+    pub name: String,
+    // Server started as a child process speaking MCP over stdio
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    // Or a remote server over streamable HTTP
+    pub url: Option<String>,
+    // Sub-agents given the tools
+    pub agents: Vec<String>,
+    // Only these of the server's tools, all of them when unset
+    pub tools: Option<Vec<String>>,
+    // When to use the tools, told to the planner and the sub-agents
+    pub instructions: Option<String>,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            command: None,
+            args: vec![],
+            url: None,
+            agents: vec!["ethereum_agent".to_string()],
+            tools: None,
+            instructions: None,
+        }
+    }
+}
+
+// Throwaway anvil forks what-if runs execute on before anything is sent for real
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WhatIfConfig {
+    // RPC URL of foundry-mcp's own network to fork, $ETH_RPC_URL when unset. Configured networks
+    // are forked from their own URLs.
+    pub fork_url: Option<String>,
+    // anvil binary, found on the PATH by default
+    pub anvil_binary: Option<PathBuf>,
+    // How long a fork may take to start serving requests
+    pub startup_secs: u64,
+}
+
+impl Default for WhatIfConfig {
+    fn default() -> Self {
+        Self {
+            fork_url: None,
+            anvil_binary: None,
+            startup_secs: 30,
+        }
+    }
+}
+
+// Private RPCs (Flashbots Protect, MEV Blocker) that keep transactions out of the public mempool
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PrivateTransactionsConfig {
+    // Private RPC per network, named as in `networks` or "default" for foundry-mcp's own network
+    pub rpcs: BTreeMap<String, String>,
+    // Send every transaction on those networks privately, not only the ones asked to be
+    pub always: bool,
+}
+
+impl PrivateTransactionsConfig {
+    // Private RPC for the network at `network_url`, `None` being foundry-mcp's own network
+    pub fn rpc_for(&self, networks: &BTreeMap<String, String>, network_url: Option<&str>) -> Option<String> {
+        let name = match network_url {
+            Some(url) => networks.iter().find(|(_, network)| network.as_str() == url).map(|(name, _)| name.as_str())?,
+            None => "default",
+        };
+        self.rpcs.get(name).cloned()
+    }
+}
+
+// Scam and phishing addresses no transaction may touch, see `Blocklist`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BlocklistConfig {
+    // URLs or file paths of lists, each a JSON array of addresses or one address per line. Nothing is
+    // checked when unset, the CLI then uses ScamSniffer's address list.
+    pub sources: Option<Vec<String>>,
+    // Where downloaded lists are kept between sessions, they are downloaded every session without one
+    pub cache_dir: Option<PathBuf>,
+    // Age after which a downloaded list is fetched again
+    pub refresh_hours: u64,
+    // Listed addresses the user lets through anyway, the only way past the blocklist
+    pub allow: Vec<String>,
+}
+
+impl Default for BlocklistConfig {
+    fn default() -> Self {
+        Self {
+            sources: None,
+            cache_dir: None,
+            refresh_hours: 24,
+            allow: vec![],
+        }
+    }
+}
+
+// Where runs are saved, in memory for the session only without a database
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RunsConfig {
+    // SQLite file holding every run's prompt, plan, steps, tool calls and evaluations
+    pub database: Option<PathBuf>,
+}
+
+// URLs notified with a JSON summary of every finished run, signed with the `webhook` secret
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub urls: Vec<String>,
+    pub timeout_secs: u64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            urls: vec![],
+            timeout_secs: 10,
+        }
+    }
+}
+
+// Slack and email messages when a run finishes. Failures and partial results are always sent,
+// successful runs only when their plan had at least `min_steps` steps.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub min_steps: usize,
+    // Enabled by the `slack` secret, an incoming webhook URL
+    pub slack: SlackConfig,
+    pub email: EmailConfig,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            min_steps: 5,
+            slack: SlackConfig::default(),
+            email: EmailConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SlackConfig {
+    pub min_severity: Severity,
+}
+
+// Enabled with an SMTP host and at least one recipient, the password is the `smtp` secret
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub username: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    pub min_severity: Severity,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: None,
+            smtp_port: 587,
+            username: None,
+            from: "eth-agent@localhost".to_string(),
+            to: vec![],
+            min_severity: Severity::Warning,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+
+use crate::config::DuneConfig;
+
+const API_URL: &str = "https://api.dune.com/api/v1";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Dune Analytics API client running saved queries
+pub struct DuneClient {
+    api_key: String,
+    config: DuneConfig,
+    http: reqwest::Client,
+}
+
+impl DuneClient {
+    pub fn new(api_key: String, config: &DuneConfig) -> Self {
+        Self {
+            api_key,
+            config: config.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Rows of saved query `query_id`. Without parameters the latest stored result is used unless
+    /// `fresh` is set, otherwise the query is executed and polled until it finishes.
+    pub async fn run_query(&self, query_id: u64, parameters: Map<String, Value>, fresh: bool, limit: Option<u32>) -> Result<Value> {
+        let limit = limit.unwrap_or(self.config.max_rows).clamp(1, self.config.max_rows);
+
+        let response = if parameters.is_empty() && !fresh {
+            self.get(&format!("{}/query/{}/results", API_URL, query_id), limit).await?
+        } else {
+            let execution_id = self.execute(query_id, parameters).await?;
+            self.wait_for(&execution_id).await?;
+            self.get(&format!("{}/execution/{}/results", API_URL, execution_id), limit).await?
+        };
+
+        let rows = response.pointer("/result/rows").cloned().unwrap_or_else(|| json!([]));
+        let returned = rows.as_array().map(|rows| rows.len()).unwrap_or_default();
+        let total = response.pointer("/result/metadata/total_row_count").and_then(|total| total.as_u64());
+
+        Ok(json!({
+            "query_id": query_id,
+            "columns": response.pointer("/result/metadata/column_names"),
+            "rows": rows,
+            "total_rows": total,
+            "truncated": total.map(|total| total > returned as u64).unwrap_or(false),
+            "executed_at": response.get("execution_ended_at"),
+        }))
+    }
+
+    async fn execute(&self, query_id: u64, parameters: Map<String, Value>) -> Result<String> {
+        let response: Value = self
+            .http
+            .post(format!("{}/query/{}/execute", API_URL, query_id))
+            .header("X-Dune-API-Key", &self.api_key)
+            .json(&json!({ "query_parameters": parameters }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response
+            .get("execution_id")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string())
+            .ok_or_else(|| anyhow::anyhow!("Dune did not start query {}: {}", query_id, response))
+    }
+
+    async fn wait_for(&self, execution_id: &str) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(self.config.max_wait_secs);
+        loop {
+            let status: Value = self
+                .http
+                .get(format!("{}/execution/{}/status", API_URL, execution_id))
+                .header("X-Dune-API-Key", &self.api_key)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            match status.get("state").and_then(|state| state.as_str()).unwrap_or_default() {
+                "QUERY_STATE_COMPLETED" => return Ok(()),
+                "QUERY_STATE_FAILED" | "QUERY_STATE_CANCELLED" | "QUERY_STATE_EXPIRED" => {
+                    let error = status.pointer("/error/message").cloned().unwrap_or(status["state"].clone());
+                    return Err(anyhow::anyhow!("Dune execution {} did not complete: {}", execution_id, error));
+                }
+                _ if Instant::now() >= deadline => {
+                    return Err(anyhow::anyhow!(
+                        "Dune execution {} still running after {}s",
+                        execution_id,
+                        self.config.max_wait_secs
+                    ));
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    async fn get(&self, url: &str, limit: u32) -> Result<Value> {
+        let response = self
+            .http
+            .get(url)
+            .header("X-Dune-API-Key", &self.api_key)
+            .query(&[("limit", limit.to_string())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::types::EnsResolution;
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_start_matches('(')
+        .trim_end_matches(|c: char| matches!(c, '.' | ',' | '?' | '!' | ';' | ':' | ')' | '\'' | '"'))
+}
+
+pub fn is_ens_name(value: &str) -> bool {
+    let value = value.to_lowercase();
+    match value.strip_suffix(".eth") {
+        Some(label) => !label.is_empty() && label.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        }),
+        None => false,
+    }
+}
+
+// Every distinct `*.eth` name in the text, lowercased, in order of appearance
+pub fn find_ens_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for word in text.split_whitespace().map(trim_punctuation) {
+        let name = word.to_lowercase();
+        if is_ens_name(&name) && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Rewrite the text so every resolved name carries its address,
+// e.g. "Send 1 ETH to vitalik.eth" -> "Send 1 ETH to vitalik.eth (= 0xd8dA...6045)"
+pub fn annotate_ens(text: &str, resolutions: &[EnsResolution]) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            let name = trim_punctuation(word).to_lowercase();
+            match resolutions.iter().find(|resolution| resolution.name == name) {
+                Some(resolution) => format!("{} (= {})", word, resolution.address),
+                None => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,52 @@
+use thiserror::Error;
+
+// Errors that can end a plan attempt. `should_replan` decides whether the
+// agent asks the planner for a new plan or aborts the run.
+#[derive(Debug, Clone, Error)]
+pub enum AgentError {
+    // The planner produced something we can't execute
+    #[error("Planning failed: {message}")]
+    Planning { message: String },
+
+    // The MCP server or a tool could not be used
+    #[error("Tool error{}: {message}", tool.as_ref().map(|tool| format!(" in {}", tool)).unwrap_or_default())]
+    Tool { tool: Option<String>, message: String },
+
+    // A step result was rejected by (or could not be scored by) the evaluator
+    #[error("Evaluation of step {step_number} failed: {reason}")]
+    Evaluation {
+        step_number: u32,
+        score: Option<u32>,
+        reason: String,
+    },
+
+    // The plan tried to do something it is not allowed to do
+    #[error("Policy violation: {message}")]
+    PolicyViolation { message: String },
+
+    // The LLM provider failed or timed out
+    #[error("Provider error in {agent}: {message}")]
+    Provider { agent: String, message: String },
+
+    #[error("Run deadline of {seconds}s exceeded while {during}")]
+    DeadlineExceeded { seconds: u64, during: String },
+
+    // The run used up its token, cost or gas budget and nobody approved going on
+    #[error("Budget exceeded: {message}")]
+    BudgetExceeded { message: String },
+
+    // The plan needs an answer from the user but nobody can be asked
+    #[error("Clarification needed: {question}")]
+    ClarificationNeeded { question: String },
+
+    #[error("Gave up after {attempts} plan attempts, last failure: {last_error}")]
+    RetriesExhausted { attempts: u32, last_error: Box<AgentError> },
+}
+
+impl AgentError {
+    // Bad plans and rejected results can be fixed by replanning, everything
+    // else would fail the same way again
+    pub fn should_replan(&self) -> bool {
+        matches!(self, AgentError::Planning { .. } | AgentError::Evaluation { .. })
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::EtherscanConfig;
+
+/// Which history of an address to page through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Transactions,
+    Internal,
+    Erc20,
+    Erc721,
+}
+
+impl HistoryKind {
+    fn action(&self) -> &'static str {
+        match self {
+            HistoryKind::Transactions => "txlist",
+            HistoryKind::Internal => "txlistinternal",
+            HistoryKind::Erc20 => "tokentx",
+            HistoryKind::Erc721 => "tokennfttx",
+        }
+    }
+
+    // Fields worth showing a model, Etherscan returns many more
+    fn fields(&self) -> &'static [&'static str] {
+        match self {
+            HistoryKind::Transactions => &["hash", "blockNumber", "timeStamp", "from", "to", "value", "functionName", "isError"],
+            HistoryKind::Internal => &["hash", "blockNumber", "timeStamp", "from", "to", "value", "type", "isError"],
+            HistoryKind::Erc20 => &[
+                "hash", "blockNumber", "timeStamp", "from", "to", "value", "tokenSymbol", "tokenDecimal", "contractAddress",
+            ],
+            HistoryKind::Erc721 => &["hash", "blockNumber", "timeStamp", "from", "to", "tokenID", "tokenName", "contractAddress"],
+        }
+    }
+}
+
+/// Etherscan API client for account history, newest first
+pub struct EtherscanClient {
+    api_key: String,
+    config: EtherscanConfig,
+    http: reqwest::Client,
+}
+
+impl EtherscanClient {
+    pub fn new(api_key: String, config: &EtherscanConfig) -> Self {
+        Self {
+            api_key,
+            config: config.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// One page of `kind` history for `address`, pages start at 1
+    pub async fn account_history(&self, kind: HistoryKind, address: &str, page: u32, page_size: Option<u32>) -> Result<Value> {
+        // Etherscan caps page * offset at 10000
+        let page_size = page_size.unwrap_or(self.config.page_size).clamp(1, 100);
+        let page = page.max(1);
+
+        let response: Value = self
+            .http
+            .get(&self.config.api_url)
+            .query(&[
+                ("chainid", self.config.chain_id.to_string()),
+                ("module", "account".to_string()),
+                ("action", kind.action().to_string()),
+                ("address", address.to_string()),
+                ("page", page.to_string()),
+                ("offset", page_size.to_string()),
+                ("sort", "desc".to_string()),
+                ("apikey", self.api_key.clone()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Failures come back as status "0" with the reason in `result`, an empty history too
+        let items = match response.get("result") {
+            Some(Value::Array(items)) => items.clone(),
+            _ if response.get("message").and_then(|message| message.as_str()) == Some("No transactions found") => vec![],
+            result => {
+                let reason = result.and_then(|result| result.as_str()).unwrap_or("unknown error");
+                return Err(anyhow::anyhow!("Etherscan request failed: {}", reason));
+            }
+        };
+
+        let items: Vec<Value> = items
+            .iter()
+            .map(|item| {
+                kind.fields()
+                    .iter()
+                    .filter_map(|field| Some((field.to_string(), item.get(*field)?.clone())))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            })
+            .collect();
+
+        Ok(json!({
+            "address": address,
+            "page": page,
+            "page_size": page_size,
+            // A full page means there may be more, ask for the next one
+            "has_more": items.len() as u32 == page_size,
+            "items": items,
+        }))
+    }
+}
@@ -0,0 +1,51 @@
+use tokio::sync::mpsc;
+
+use crate::types::PlanCostEstimate;
+
+// Live events emitted while a plan runs, for frontends like the REPL
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    StepStarted { step_number: u32, agent_name: String },
+    Token(String),
+    ToolCall { name: String, arguments: serde_json::Value },
+    ToolResult { name: String, result: String },
+    // An ENS name pinned to an address for the rest of the run
+    EnsResolved { name: String, address: String },
+    // A fiat amount like "$50 worth of ETH" pinned to the amount it was worth when the run started
+    FiatAmountPinned { phrase: String, value: String },
+    // A phrase like "him" or "the same amount" pinned to what it meant in an earlier run
+    ReferenceResolved { phrase: String, value: String },
+    // Decoded calldata, estimated gas cost and simulated balance changes of a send about to happen
+    TransactionPreview { preview: serde_json::Value },
+    StepFinished { step_number: u32 },
+    // A step failed (rejected by the evaluator or a transient error) and runs again on its own
+    StepRetry { step_number: u32, attempt: u32, reason: String },
+    // The best of several candidate plans was picked, with the evaluator's score of each one
+    PlanChosen { chosen: usize, scores: Vec<u32> },
+    // Gas cost of the plan's state-changing steps, estimated before the first step runs
+    PlanCostEstimate { estimate: PlanCostEstimate },
+    // Progress reported by a long-running tool, `total` when the server knows it
+    Progress { progress: f64, total: Option<f64>, message: Option<String> },
+    // Something went wrong but the run carries on, e.g. foundry-mcp had to be restarted
+    Warning { message: String },
+}
+
+// Cheap to clone handle for emitting events, a no-op when nobody is listening
+#[derive(Clone, Default)]
+pub struct EventSink {
+    sender: Option<mpsc::UnboundedSender<AgentEvent>>,
+}
+
+impl EventSink {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<AgentEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender: Some(sender) }, receiver)
+    }
+
+    pub fn emit(&self, event: AgentEvent) {
+        if let Some(sender) = &self.sender {
+            // The receiver going away just means nobody is watching anymore
+            let _ = sender.send(event);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+use tracing::info;
+
+use crate::config::WhatIfConfig;
+use crate::redact::redact;
+
+/// A throwaway node forking a network, stopped when dropped
+pub struct ForkedNode {
+    rpc_url: String,
+    _process: Option<Child>,
+}
+
+impl ForkedNode {
+    /// A fork someone else runs and cleans up, e.g. in tests
+    pub fn external(rpc_url: impl Into<String>) -> Self {
+        Self {
+            rpc_url: rpc_url.into(),
+            _process: None,
+        }
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+}
+
+/// Starts a fork of the network at the given RPC URL
+pub type ForkLauncher = Arc<dyn Fn(String) -> BoxFuture<'static, Result<ForkedNode>> + Send + Sync>;
+
+/// Launcher running `anvil --fork-url` on a free local port
+pub fn anvil_launcher(config: &WhatIfConfig) -> ForkLauncher {
+    let binary = config.anvil_binary.clone().unwrap_or_else(|| PathBuf::from("anvil"));
+    let startup = Duration::from_secs(config.startup_secs);
+    Arc::new(move |fork_url| spawn_anvil(binary.clone(), fork_url, startup).boxed())
+}
+
+async fn spawn_anvil(binary: PathBuf, fork_url: String, startup: Duration) -> Result<ForkedNode> {
+    // Let the OS pick a free port, anvil binds it right after
+    let port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+    let mut process = Command::new(&binary)
+        .args(["--fork-url", &fork_url, "--port", &port.to_string(), "--silent"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to start {}: {}", binary.display(), e))?;
+
+    // Forking fetches the latest block first, the node only listens once that is done
+    let deadline = Instant::now() + startup;
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if let Some(status) = process.try_wait()? {
+            return Err(anyhow::anyhow!("anvil exited with {} before the fork was ready", status));
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("anvil did not start listening within {}s", startup.as_secs()));
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    info!("Forked {} on port {}", redact(&fork_url), port);
+    Ok(ForkedNode {
+        rpc_url: format!("http://127.0.0.1:{}", port),
+        _process: Some(process),
+    })
+}
+
+// The forks of a what-if run, one per network the plan touches, started when a step first needs one
+pub(crate) struct Forks {
+    launcher: ForkLauncher,
+    nodes: Mutex<HashMap<String, ForkedNode>>,
+}
+
+impl Forks {
+    pub fn new(launcher: ForkLauncher) -> Self {
+        Self {
+            launcher,
+            nodes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // RPC URL of the fork of `source`, forked now if no step used it yet
+    pub async fn rpc_url(&self, source: &str) -> Result<String> {
+        let mut nodes = self.nodes.lock().await;
+        if let Some(node) = nodes.get(source) {
+            return Ok(node.rpc_url().to_string());
+        }
+        let node = (self.launcher)(source.to_string()).await?;
+        let rpc_url = node.rpc_url().to_string();
+        nodes.insert(source.to_string(), node);
+        Ok(rpc_url)
+    }
+
+    // RPC URL of the fork of `source` if one was started
+    pub async fn started(&self, source: &str) -> Option<String> {
+        self.nodes.lock().await.get(source).map(|node| node.rpc_url().to_string())
+    }
+}
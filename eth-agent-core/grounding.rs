@@ -0,0 +1,102 @@
+use serde_json::{json, Value};
+
+use crate::guard::{classify_step, ActionClass};
+use crate::mcp_client::FoundryMcpClient;
+use crate::types::{AgentStep, ToolCallRecord};
+
+// Tools whose payload carries the hash of a transaction that has to be mined, for UserOperations
+// the bundle transaction that included it
+const SENDING_TOOLS: &[&str] = &["send_transaction", "send_user_operation"];
+
+// Tools whose success stands for the step's state change without a transaction of its own
+const OFF_CHAIN_ACTIONS: &[&str] = &["propose_safe_transaction", "sign_permit", "sign_in_with_ethereum", "build_unsigned_transaction"];
+
+fn text(value: &Value, name: &str) -> Option<String> {
+    value.get(name)?.as_str().map(|value| value.to_string())
+}
+
+// One fact read back from the chain after a step, and whether it matches what the step meant to do
+#[derive(Debug, Clone)]
+pub struct ChainCheck {
+    pub description: String,
+    pub passed: bool,
+}
+
+impl ChainCheck {
+    fn passed(description: String) -> Self {
+        Self { description, passed: true }
+    }
+
+    fn failed(description: String) -> Self {
+        Self { description, passed: false }
+    }
+}
+
+// Read back what a step's transactions did: every sent transaction has to be mined without a
+// revert and every deployment has to have left code behind. `None` for informational steps,
+// which are left to the evaluator alone.
+pub async fn verify_step(client: &FoundryMcpClient, step: &AgentStep, calls: &[ToolCallRecord]) -> Option<Vec<ChainCheck>> {
+    let sends: Vec<(&ToolCallRecord, Value)> = calls
+        .iter()
+        .filter(|call| SENDING_TOOLS.contains(&call.tool.as_str()))
+        .filter_map(|call| call.payload().map(|payload| (call, payload)))
+        .filter(|(_, payload)| payload.get("success").and_then(Value::as_bool) != Some(false))
+        .collect();
+    let off_chain = calls
+        .iter()
+        .filter(|call| OFF_CHAIN_ACTIONS.contains(&call.tool.as_str()))
+        .filter_map(|call| call.payload())
+        .any(|payload| payload.get("success").and_then(Value::as_bool) != Some(false));
+
+    let state_changing = classify_step(step) != ActionClass::ReadOnly;
+    if !state_changing && sends.is_empty() {
+        return None;
+    }
+    if sends.is_empty() {
+        if off_chain {
+            return None;
+        }
+        return Some(vec![ChainCheck::failed("The step asks for a state change but sent no transaction".to_string())]);
+    }
+
+    // Wait for the step's transactions to be mined before reading their receipts
+    if let Err(e) = client.confirm_sent_transactions().await {
+        return Some(vec![ChainCheck::failed(e.to_string())]);
+    }
+
+    let mut checks = vec![];
+    for (call, payload) in sends {
+        let Some(hash) = text(&payload, "transaction_hash") else {
+            continue;
+        };
+        let status = client.call("transaction_status", json!({ "hash": hash })).await;
+        checks.push(match status.as_ref().ok().and_then(|status| text(status, "status")).as_deref() {
+            Some("confirmed") if call.tool == "send_user_operation" => {
+                let user_op_hash = text(&payload, "user_op_hash").unwrap_or_default();
+                let account = text(&payload, "account").unwrap_or_default();
+                ChainCheck::passed(format!("UserOperation {} of {} was included by transaction {}", user_op_hash, account, hash))
+            }
+            Some("confirmed") => {
+                let to = text(&call.arguments, "to").unwrap_or_else(|| "a new contract".to_string());
+                let value = match call.arguments.get("value") {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                    None => "0".to_string(),
+                };
+                ChainCheck::passed(format!("Transaction {} to {} with {} wei was mined successfully", hash, to, value))
+            }
+            Some("failed") => ChainCheck::failed(format!("Transaction {} was mined but reverted", hash)),
+            Some("not_found") => ChainCheck::failed(format!("Transaction {} is not known to the node, it never landed", hash)),
+            Some(status) => ChainCheck::failed(format!("Transaction {} is still {}", hash, status)),
+            None => ChainCheck::failed(format!("Could not read the status of transaction {}", hash)),
+        });
+
+        if let Some(address) = text(&payload, "contract_address") {
+            checks.push(match client.get_contract_code(&address, true).await {
+                Ok(code) if code.is_contract() => ChainCheck::passed(format!("Contract {} has code", address)),
+                _ => ChainCheck::failed(format!("No code at the deployed contract address {}", address)),
+            });
+        }
+    }
+    Some(checks)
+}
@@ -0,0 +1,260 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::{hex, U256};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::config::{GuardConfig, GuardPolicy, MevGuardConfig, RiskGuardConfig};
+use crate::interaction::Interaction;
+use crate::types::AgentStep;
+
+// approve(address,uint256) and setApprovalForAll(address,bool)
+const APPROVE_SELECTOR: &str = "095ea7b3";
+const SET_APPROVAL_FOR_ALL_SELECTOR: &str = "a22cb465";
+const SELFDESTRUCT_OPCODE: u8 = 0xff;
+
+/// What a step or transaction does, from harmless to most dangerous. The policy for each class
+/// is set in [`GuardConfig`](crate::GuardConfig).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActionClass {
+    ReadOnly,
+    ValueTransfer,
+    Approval,
+    ContractDeployment,
+    UnlimitedApproval,
+    SelfDestruct,
+}
+
+impl fmt::Display for ActionClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ActionClass::ReadOnly => "read-only",
+            ActionClass::ValueTransfer => "value transfer",
+            ActionClass::Approval => "token approval",
+            ActionClass::ContractDeployment => "contract deployment",
+            ActionClass::UnlimitedApproval => "unlimited token approval",
+            ActionClass::SelfDestruct => "selfdestruct interaction",
+        };
+        f.write_str(name)
+    }
+}
+
+// Keyword pass over a planned step, so the user can be asked before the step runs.
+// Transactions are classified again from their calldata when they are sent.
+pub fn classify_step(step: &AgentStep) -> ActionClass {
+    if step.agent_name != "ethereum_agent" {
+        return ActionClass::ReadOnly;
+    }
+
+    let prompt = step.agent_prompt.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| prompt.contains(word));
+
+    if has(&["selfdestruct", "self-destruct", "self destruct"]) {
+        ActionClass::SelfDestruct
+    } else if has(&["approv", "allowance", "permit"]) && has(&["unlimited", "infinite", "max", "all tokens", "approval for all"]) {
+        ActionClass::UnlimitedApproval
+    } else if has(&["approve", "allowance", "permit"]) {
+        ActionClass::Approval
+    } else if has(&["deploy"]) {
+        ActionClass::ContractDeployment
+    } else if has(&["send", "transfer", "pay", "swap", "wrap", "deposit", "withdraw", "mint", "stake", "claim", "call function"]) {
+        ActionClass::ValueTransfer
+    } else {
+        ActionClass::ReadOnly
+    }
+}
+
+// Classify a transaction from its calldata. `to` is empty for deployments.
+pub fn classify_transaction(to: &str, data: Option<&str>) -> ActionClass {
+    if to.is_empty() {
+        return ActionClass::ContractDeployment;
+    }
+
+    let data = data.unwrap_or_default().trim_start_matches("0x").to_lowercase();
+    // Both approvals take (address, uint256/bool), the second word decides how much is approved
+    let amount = data.get(72..136).and_then(|word| U256::from_str_radix(word, 16).ok());
+
+    match data.get(..8) {
+        Some(APPROVE_SELECTOR) if amount == Some(U256::MAX) => ActionClass::UnlimitedApproval,
+        Some(APPROVE_SELECTOR) => ActionClass::Approval,
+        Some(SET_APPROVAL_FOR_ALL_SELECTOR) if amount.is_some_and(|approved| !approved.is_zero()) => {
+            ActionClass::UnlimitedApproval
+        }
+        Some(SET_APPROVAL_FOR_ALL_SELECTOR) => ActionClass::Approval,
+        _ => ActionClass::ValueTransfer,
+    }
+}
+
+// Whether runtime code contains a SELFDESTRUCT opcode, skipping PUSH data
+pub fn has_selfdestruct(code: &str) -> bool {
+    let Ok(code) = hex::decode(code) else {
+        return false;
+    };
+
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if opcode == SELFDESTRUCT_OPCODE {
+            return true;
+        }
+        // PUSH1..PUSH32 are followed by 1..32 bytes of data
+        if (0x60..=0x7f).contains(&opcode) {
+            pc += (opcode - 0x5f) as usize;
+        }
+        pc += 1;
+    }
+    false
+}
+
+// Why a swap's `mev_exposure` (from preview_transaction) makes it worth sandwiching, empty when it
+// is safe enough for the public mempool. Only swaps are exposed, reordering a transfer gains nothing.
+pub fn mev_risks(exposure: &Value, config: &MevGuardConfig) -> Vec<String> {
+    if exposure.get("kind").and_then(Value::as_str) != Some("swap") {
+        return vec![];
+    }
+    let bps = |name: &str| exposure.get(name).and_then(Value::as_u64);
+    let wei = |value: Option<&Value>| value.and_then(Value::as_str).and_then(|wei| U256::from_str_radix(wei, 10).ok());
+
+    let mut risks = vec![];
+    if let Some(slippage) = bps("slippage_bps").filter(|slippage| *slippage > config.max_slippage_bps) {
+        risks.push(format!("slippage tolerance of {}.{:02}%", slippage / 100, slippage % 100));
+    }
+    if let Some(impact) = bps("price_impact_bps").filter(|impact| *impact > config.max_price_impact_bps) {
+        risks.push(format!("price impact of {}.{:02}% in a shallow pool", impact / 100, impact % 100));
+    }
+    let large = U256::from_str_radix(&config.large_value_wei, 10).ok();
+    if let (Some(value), Some(large)) = (wei(exposure.get("value_wei")), large) {
+        if value > large {
+            risks.push(format!("{} wei of ETH at stake", value));
+        }
+    }
+    if risks.is_empty() {
+        return risks;
+    }
+    if let Some(extractable) = wei(exposure.get("max_extractable_wei")) {
+        risks.push(format!("up to {} wei extractable by a sandwich", extractable));
+    }
+    risks
+}
+
+#[derive(Default)]
+struct GuardState {
+    // Set when the user refused an action, ends the run
+    refused: Option<String>,
+}
+
+// Applies the configured policy to steps and transactions of a run, shared between the agent
+// and the MCP client
+#[derive(Clone)]
+pub struct ActionGuard {
+    config: GuardConfig,
+    interaction: Arc<dyn Interaction>,
+    state: Arc<Mutex<GuardState>>,
+}
+
+impl ActionGuard {
+    pub fn new(config: GuardConfig, interaction: Arc<dyn Interaction>) -> Self {
+        Self {
+            config,
+            interaction,
+            state: Arc::default(),
+        }
+    }
+
+    // Whether `class` is let through without asking
+    pub fn always_allows(&self, class: ActionClass) -> bool {
+        self.config.policy(class) == GuardPolicy::Allow
+    }
+
+    pub fn refused(&self) -> Option<String> {
+        self.state.lock().unwrap().refused.clone()
+    }
+
+    // Whether `policy` lets `subject` through, asking the user if it says to
+    async fn ask(&self, policy: GuardPolicy, subject: &str) -> bool {
+        match policy {
+            GuardPolicy::Allow => true,
+            GuardPolicy::Confirm => self.interaction.confirm(&format!("{}. Allow it?", subject)).await,
+            GuardPolicy::Phrase => {
+                let answer = self
+                    .interaction
+                    .ask(&format!("{}. Type \"{}\" to allow it", subject, self.config.confirmation_phrase))
+                    .await;
+                answer.is_some_and(|answer| answer.trim() == self.config.confirmation_phrase)
+            }
+        }
+    }
+
+    // Let `description` through per `policy`, marking the run refused otherwise
+    async fn check_policy(&self, policy: GuardPolicy, description: &str) -> Result<(), String> {
+        if self.ask(policy, description).await {
+            return Ok(());
+        }
+
+        let reason = format!("{} was not allowed", description);
+        warn!("{}", reason);
+        self.state.lock().unwrap().refused = Some(reason.clone());
+        Err(reason)
+    }
+
+    pub fn mev_config(&self) -> &MevGuardConfig {
+        &self.config.mev
+    }
+
+    // Let a swap exposed to sandwiching into the public mempool, per the `mev` policy
+    pub async fn check_mev_exposure(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.mev.policy, description).await
+    }
+
+    pub fn risk_config(&self) -> &RiskGuardConfig {
+        &self.config.risk
+    }
+
+    // Let the first call to a risky or unscored contract through, per the `risk` policy
+    pub async fn check_risk(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.risk.policy, description).await
+    }
+
+    // Approve the estimated gas cost of a whole plan before its first step
+    pub async fn check_plan_cost(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.plan_cost, description).await
+    }
+
+    // Approve signing a Sign-In-With-Ethereum message for a site
+    pub async fn check_sign_in(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.sign_in, description).await
+    }
+
+    // Approve a call of a plugin tool that may change state, the plugin's own checks are unknown
+    pub async fn check_plugin_call(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.plugins, description).await
+    }
+
+    // Approve an anvil cheatcode, which rewrites chain state without a transaction to classify
+    pub async fn check_cheatcode(&self, description: &str) -> Result<(), String> {
+        self.check_policy(self.config.cheatcodes, description).await
+    }
+
+    // Allow a planned step of `class` before it runs. Only the prompt is known at this point, so
+    // the approval doesn't cover the transactions the step then sends.
+    pub async fn check_step(&self, class: ActionClass, description: &str) -> Result<(), String> {
+        self.check(class, description).await
+    }
+
+    // Allow a concrete transaction of `class` by policy or by asking the user, returns why it was
+    // refused otherwise. `description` names what the user decides on: sender, target, value and
+    // calldata. Every send is asked about on its own, even one identical to a send allowed
+    // before, since a retried step or a repeated tool call would otherwise send it twice.
+    pub async fn check(&self, class: ActionClass, description: &str) -> Result<(), String> {
+        if self.ask(self.config.policy(class), &format!("{} is a {}", description, class)).await {
+            info!("Allowed {}: {}", class, description);
+            return Ok(());
+        }
+
+        let reason = format!("{} ({}) was not allowed", description, class);
+        warn!("{}", reason);
+        self.state.lock().unwrap().refused = Some(reason.clone());
+        Err(reason)
+    }
+}
@@ -0,0 +1,27 @@
+use async_trait::async_trait;
+
+/// How the agent reaches the user in the middle of a run, e.g. to approve going over budget.
+/// The CLI asks on the terminal, services can route this to their own UI.
+#[async_trait]
+pub trait Interaction: Send + Sync {
+    /// Ask the user to approve something, `false` means no
+    async fn confirm(&self, message: &str) -> bool;
+
+    /// Ask the user a clarifying question, `None` when nobody can answer
+    async fn ask(&self, question: &str) -> Option<String>;
+}
+
+/// For unattended use: nobody is there to ask, so every confirmation is declined and
+/// every question goes unanswered
+pub struct NoInteraction;
+
+#[async_trait]
+impl Interaction for NoInteraction {
+    async fn confirm(&self, _message: &str) -> bool {
+        false
+    }
+
+    async fn ask(&self, _question: &str) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,252 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::store::RunStore;
+use crate::types::*;
+
+// Checkpoint of the run currently in progress, shared between the agent and the MCP client.
+// Every update is applied immediately, so the state survives the run future being dropped.
+// Finished runs are kept for the rest of the session, and with a store every checkpoint is
+// also written to disk so runs outlive the process.
+#[derive(Clone, Default)]
+pub struct RunJournal {
+    current: Arc<Mutex<Option<RunRecord>>>,
+    history: Arc<Mutex<Vec<RunRecord>>>,
+    store: Option<Arc<RunStore>>,
+}
+
+impl RunJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_store(store: Arc<RunStore>) -> Self {
+        Self {
+            store: Some(store),
+            ..Self::default()
+        }
+    }
+
+    pub fn begin(&self, prompt: &UserPrompt) {
+        let now = chrono::Utc::now();
+        let previous = self.current.lock().unwrap().replace(RunRecord {
+            id: prompt.id.clone(),
+            prompt: prompt.clone(),
+            plan: None,
+            steps: vec![],
+            status: PlanStatus::Planning,
+            result: None,
+            started_at: now,
+            updated_at: now,
+            cost_estimate: None,
+            gas_spent_wei: None,
+        });
+
+        if let Some(previous) = previous {
+            self.history.lock().unwrap().push(previous);
+        }
+        self.persist();
+    }
+
+    // A new plan replaces the steps of any previous attempt, except the completed steps a partial
+    // replan continues after
+    pub fn set_plan(&self, plan: &AgentPlan) {
+        let first_step = plan.steps.first().map(|step| step.step_number).unwrap_or(u32::MAX);
+        self.update(|run| {
+            run.plan = Some(plan.clone());
+            run.status = PlanStatus::Executing;
+            run.steps.retain(|step| matches!(step.status, StepStatus::Completed) && step.step_number < first_step);
+            run.steps.extend(plan.steps.iter().map(|step| StepRecord {
+                step_number: step.step_number,
+                agent_name: step.agent_name.clone(),
+                agent_prompt: step.agent_prompt.clone(),
+                status: StepStatus::Planned,
+                output: None,
+                tool_calls: vec![],
+                evaluation: None,
+                started_at: None,
+                finished_at: None,
+            }));
+        });
+    }
+
+    // Carry the completed steps of an earlier run into the current one, which resumes after them
+    pub fn resume(&self, completed: &[StepRecord]) {
+        self.update(|run| run.steps = completed.to_vec());
+    }
+
+    // Steps of the current run that finished, in order, for a replan to continue after
+    pub fn completed_steps(&self) -> Vec<StepRecord> {
+        self.snapshot()
+            .map(|run| run.steps.into_iter().filter(|step| matches!(step.status, StepStatus::Completed)).collect())
+            .unwrap_or_default()
+    }
+
+    // Tool calls recorded for a step of the current run so far, across all of its attempts
+    pub fn tool_calls(&self, step_number: u32) -> Vec<ToolCallRecord> {
+        self.snapshot()
+            .and_then(|run| run.steps.into_iter().find(|step| step.step_number == step_number))
+            .map(|step| step.tool_calls)
+            .unwrap_or_default()
+    }
+
+    pub fn set_cost_estimate(&self, estimate: &PlanCostEstimate) {
+        self.update(|run| run.cost_estimate = Some(estimate.clone()));
+    }
+
+    pub fn set_gas_spent(&self, gas_wei: &str) {
+        self.update(|run| run.gas_spent_wei = Some(gas_wei.to_string()));
+    }
+
+    pub fn step_started(&self, step_number: u32) {
+        self.update(|run| {
+            if let Some(plan) = run.plan.as_mut() {
+                plan.current_step = step_number;
+            }
+        });
+        self.update_step(step_number, |step| {
+            step.status = StepStatus::Executing;
+            step.started_at.get_or_insert_with(chrono::Utc::now);
+        });
+    }
+
+    // Steps that will never run, e.g. past the step cap
+    pub fn skip_planned_steps(&self) {
+        self.update(|run| {
+            for step in run.steps.iter_mut().filter(|step| matches!(step.status, StepStatus::Planned)) {
+                step.status = StepStatus::Skipped;
+            }
+        });
+    }
+
+    pub fn step_completed(&self, step_number: u32, output: &str) {
+        self.update_step(step_number, |step| {
+            step.status = StepStatus::Completed;
+            step.output = Some(output.to_string());
+            step.finished_at = Some(chrono::Utc::now());
+        });
+    }
+
+    pub fn step_evaluated(&self, step_number: u32, score: u32, reasoning: &str) {
+        self.update_step(step_number, |step| {
+            step.evaluation = Some(StepEvaluation {
+                score,
+                reasoning: reasoning.to_string(),
+            });
+        });
+    }
+
+    pub fn fail_current_step(&self, reason: &str) {
+        self.update(|run| {
+            if let Some(step) = run
+                .steps
+                .iter_mut()
+                .find(|step| matches!(step.status, StepStatus::Executing))
+            {
+                step.status = StepStatus::Failed(reason.to_string());
+                step.finished_at = Some(chrono::Utc::now());
+            }
+        });
+    }
+
+    // Tool calls are attached to whichever step is currently executing
+    pub fn record_tool_call(&self, record: ToolCallRecord) {
+        self.update(|run| {
+            if let Some(step) = run
+                .steps
+                .iter_mut()
+                .find(|step| matches!(step.status, StepStatus::Executing))
+            {
+                step.tool_calls.push(record);
+            }
+        });
+    }
+
+    pub fn finish(&self, status: PlanStatus, result: Option<String>) {
+        self.update(|run| {
+            run.status = status;
+            run.result = result;
+        });
+    }
+
+    // Mark the run as cancelled and return the final checkpoint
+    pub fn cancel(&self) -> Option<RunRecord> {
+        self.update(|run| {
+            for step in run.steps.iter_mut() {
+                match step.status {
+                    StepStatus::Executing => step.status = StepStatus::Failed("Cancelled by user".to_string()),
+                    StepStatus::Planned => step.status = StepStatus::Skipped,
+                    _ => {}
+                }
+            }
+            run.status = PlanStatus::Failed("Cancelled by user".to_string());
+        });
+        self.snapshot()
+    }
+
+    pub fn snapshot(&self) -> Option<RunRecord> {
+        self.current.lock().unwrap().clone()
+    }
+
+    // Every run of this session, oldest first
+    pub fn runs(&self) -> Vec<RunRecord> {
+        let mut runs = self.history.lock().unwrap().clone();
+        runs.extend(self.snapshot());
+        runs
+    }
+
+    // Saved runs started at or after `since`, oldest first. Without a store only this session's runs are known.
+    pub fn saved_runs(&self, since: Option<DateTime<Utc>>) -> Result<Vec<RunRecord>> {
+        match &self.store {
+            Some(store) => store.list(since),
+            None => Ok(self.runs().into_iter().filter(|run| since.is_none_or(|since| run.started_at >= since)).collect()),
+        }
+    }
+
+    // Find a run by id, or by a unique prefix of it, in this session first and then in the store
+    pub fn find(&self, id: &str) -> Option<RunRecord> {
+        let matches: Vec<RunRecord> = self.runs().into_iter().filter(|run| run.id.starts_with(id)).collect();
+        let found = match matches.as_slice() {
+            [run] => Some(run.clone()),
+            _ => matches.into_iter().find(|run| run.id == id),
+        };
+        found.or_else(|| {
+            let store = self.store.as_ref()?;
+            store.find(id).unwrap_or_else(|e| {
+                warn!("Failed to look up run {}: {}", id, e);
+                None
+            })
+        })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RunRecord)) {
+        if let Some(run) = self.current.lock().unwrap().as_mut() {
+            f(run);
+            run.updated_at = chrono::Utc::now();
+        }
+        self.persist();
+    }
+
+    // A failing disk never fails the run, the checkpoint is still kept in memory
+    fn persist(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if let Some(run) = self.snapshot() {
+            if let Err(e) = store.save(&run) {
+                warn!("Failed to save run {}: {}", run.id, e);
+            }
+        }
+    }
+
+    fn update_step(&self, step_number: u32, f: impl FnOnce(&mut StepRecord)) {
+        self.update(|run| {
+            if let Some(step) = run.steps.iter_mut().find(|step| step.step_number == step_number) {
+                f(step);
+            }
+        });
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+// Contracts worth recognising on sight, by their mainnet address. Most protocols deploy to the
+// same address on the L2s, and local forks of mainnet keep them.
+const WELL_KNOWN: &[(&str, &str)] = &[
+    ("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH"),
+    ("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC"),
+    ("0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT"),
+    ("0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI"),
+    ("0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599", "WBTC"),
+    ("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84", "Lido stETH"),
+    ("0x889edC2eDab5f40e902b864aD4d7AdE8E412F9B1", "Lido Withdrawal Queue"),
+    ("0xae78736Cd615f374D3085123A210448E74Fc6393", "Rocket Pool rETH"),
+    ("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D", "Uniswap V2 Router"),
+    ("0xE592427A0AEce92De3Edee1F18E0157C05861564", "Uniswap V3 Router"),
+    ("0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45", "Uniswap V3 Router 2"),
+    ("0x3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD", "Uniswap Universal Router"),
+    ("0x000000000022D473030F116dDEE9F6B43aC78BA3", "Uniswap Permit2"),
+    ("0x111111125421cA6dc452d289314280a0f8842A65", "1inch Router v6"),
+    ("0xDef1C0ded9bec7F1a1670819833240f027b25EfF", "0x Exchange Proxy"),
+    ("0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2", "Aave V3 Pool"),
+    ("0x0000000071727De22E5E9d8BAf0edAc6f37da032", "ERC-4337 EntryPoint v0.7"),
+    ("0xa6B71E26C5e0845f74c812102Ca7114b6a896AB2", "Safe Proxy Factory"),
+    ("0xcA11bde05977b3631167028862bE2a173976CA11", "Multicall3"),
+    ("0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e", "ENS Registry"),
+    ("0x000000000000000000000000000000000000dEaD", "Burn address"),
+];
+
+// Every 20-byte hex address in `text`, longer hex strings (hashes, calldata) are skipped
+fn addresses(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut found = vec![];
+    let mut i = 0;
+    while let Some(offset) = text[i..].find("0x") {
+        let start = i + offset;
+        let digits = bytes[start + 2..].iter().take_while(|byte| byte.is_ascii_hexdigit()).count();
+        let preceded = start > 0 && bytes[start - 1].is_ascii_alphanumeric();
+        if digits == 40 && !preceded {
+            found.push(&text[start..start + 42]);
+        }
+        i = start + 2 + digits;
+    }
+    found
+}
+
+fn collect_addresses<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) => found.extend(addresses(text)),
+        Value::Array(values) => values.iter().for_each(|value| collect_addresses(value, found)),
+        Value::Object(map) => map.values().for_each(|value| collect_addresses(value, found)),
+        _ => {}
+    }
+}
+
+// e.g. 0x68b3…Fc45
+fn shorten(address: &str) -> String {
+    format!("{}…{}", &address[..6], &address[address.len() - 4..])
+}
+
+/// Names for addresses, shown next to them in tool results, transaction previews and reports.
+/// The user's own labels (`[labels]` in the config) come first, then well-known protocol and
+/// token contracts, then the ENS names and tokens a run resolved. Cloning shares the labels.
+#[derive(Debug, Clone, Default)]
+pub struct AddressLabels {
+    // Keyed by lowercased address
+    labels: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl AddressLabels {
+    pub fn new(user_labels: &BTreeMap<String, String>) -> Self {
+        let mut labels: HashMap<String, String> =
+            WELL_KNOWN.iter().map(|(address, label)| (address.to_lowercase(), label.to_string())).collect();
+        labels.extend(user_labels.iter().map(|(address, label)| (address.to_lowercase(), label.clone())));
+        Self {
+            labels: Arc::new(RwLock::new(labels)),
+        }
+    }
+
+    pub fn get(&self, address: &str) -> Option<String> {
+        self.labels.read().unwrap().get(&address.to_lowercase()).cloned()
+    }
+
+    /// Remember a label found during a run, e.g. a resolved ENS name. Addresses that already have
+    /// one keep it.
+    pub fn learn(&self, address: &str, label: &str) {
+        if addresses(address) != [address] {
+            return;
+        }
+        self.labels.write().unwrap().entry(address.to_lowercase()).or_insert_with(|| label.to_string());
+    }
+
+    /// Swap the user's labels `old` for `new`, e.g. after `[labels]` was edited. Addresses that lost
+    /// their user label fall back to a well-known one, if any; labels learned during runs stay.
+    pub fn replace_user_labels(&self, old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) {
+        let mut labels = self.labels.write().unwrap();
+        for address in old.keys().filter(|address| !new.contains_key(*address)) {
+            let address = address.to_lowercase();
+            match WELL_KNOWN.iter().find(|(known, _)| known.to_lowercase() == address) {
+                Some((_, label)) => labels.insert(address, label.to_string()),
+                None => labels.remove(&address),
+            };
+        }
+        labels.extend(new.iter().map(|(address, label)| (address.to_lowercase(), label.clone())));
+    }
+
+    /// The address with its label in front, e.g. "Uniswap V3 Router 2 (0x68b3…Fc45)", or as it is
+    /// without one
+    pub fn describe(&self, address: &str) -> String {
+        match self.get(address) {
+            Some(label) if address.len() == 42 && address.is_ascii() => format!("{} ({})", label, shorten(address)),
+            _ => address.to_string(),
+        }
+    }
+
+    /// Labels of the addresses appearing in `value`, keyed by the address as it appears there
+    pub fn labels_in(&self, value: &Value) -> serde_json::Map<String, Value> {
+        let mut found = vec![];
+        collect_addresses(value, &mut found);
+        found
+            .into_iter()
+            .filter_map(|address| Some((address.to_string(), Value::String(self.get(address)?))))
+            .collect()
+    }
+
+    /// Add an `address_labels` object to a JSON object naming the labelled addresses in it, so
+    /// the values themselves stay plain addresses. Anything else is left as it is.
+    pub fn annotate_json(&self, output: &str) -> String {
+        let Ok(mut value) = serde_json::from_str::<Value>(output) else {
+            return output.to_string();
+        };
+        let labels = self.labels_in(&value);
+        let Value::Object(map) = &mut value else {
+            return output.to_string();
+        };
+        if labels.is_empty() {
+            return output.to_string();
+        }
+        map.insert("address_labels".to_string(), Value::Object(labels));
+        value.to_string()
+    }
+
+    /// Follow every labelled address in `text` with its label, e.g. "0x68b3...Fc45 (Uniswap V3
+    /// Router 2)"
+    pub fn annotate_text(&self, text: &str) -> String {
+        let mut annotated = String::with_capacity(text.len());
+        let mut rest = text;
+        for address in addresses(text) {
+            let Some(offset) = rest.find(address) else {
+                continue;
+            };
+            let end = offset + address.len();
+            annotated.push_str(&rest[..end]);
+            rest = &rest[end..];
+            if let Some(label) = self.get(address) {
+                if !rest.starts_with(" (") {
+                    annotated.push_str(&format!(" ({})", label));
+                }
+            }
+        }
+        annotated.push_str(rest);
+        annotated
+    }
+}
@@ -0,0 +1,124 @@
+//! Core of the ETH agent: a plan -> execute -> evaluate loop over Ethereum tools served by
+//! the `foundry-mcp` server.
+//!
+//! The `eth-agent` binary is a thin REPL on top of this crate. To embed the agent in your
+//! own service, build an [`EthAgent`] from any rig provider client and call [`EthAgent::run`]:
+//!
+//! ```no_run
+//! use eth_agent_core::{
+//!     rig::providers::anthropic, search_provider, AgentConfig, EthAgent, EventSink, SearchProviderKind, UserPrompt,
+//! };
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let (events, mut receiver) = EventSink::channel();
+//! tokio::spawn(async move {
+//!     while let Some(event) = receiver.recv().await {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//!
+//! // Keyless web search, or `None` to run without it
+//! let search = search_provider(SearchProviderKind::DuckDuckGo, None)?;
+//! let mut agent = EthAgent::new(anthropic::Client::new("sk-..."), Some(search), AgentConfig::load()?)?
+//!     .with_event_sink(events);
+//!
+//! let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await?;
+//! println!("{}", result.result);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every run is checkpointed, see [`EthAgent::last_run`] and [`EthAgent::cancel_run`]. Set
+//! `[runs] database` to keep runs in SQLite across restarts, see [`EthAgent::saved_runs`].
+
+pub mod agent;
+pub mod blocklist;
+pub mod config;
+pub mod error;
+pub mod dune;
+pub mod etherscan;
+pub mod events;
+pub mod fork;
+pub mod interaction;
+pub mod journal;
+pub mod labels;
+pub mod mcp_client;
+pub mod notifications;
+pub mod payment_uri;
+pub mod plugins;
+pub mod portfolio;
+pub mod redact;
+pub mod report;
+pub mod safe_bundle;
+pub mod search;
+pub mod store;
+pub mod tenderly;
+pub mod tool_results;
+pub mod tools;
+pub mod types;
+pub mod webhook;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+mod amounts;
+mod artifacts;
+mod budget;
+mod cache;
+mod ens;
+mod grounding;
+mod guard;
+mod plan_cost;
+mod plan_validation;
+mod postconditions;
+mod references;
+mod retry;
+mod sampling;
+mod schema;
+mod scripts;
+mod sub_agent;
+mod tx_queue;
+
+pub use agent::EthAgent;
+pub use blocklist::Blocklist;
+pub use budget::BudgetUsage;
+pub use config::{
+    AgentConfig, BlocklistConfig, BudgetAction, BudgetConfig, DuneConfig, EmailConfig, EtherscanConfig, GuardConfig,
+    GuardPolicy, McpConfig, McpTransport, MevGuardConfig, NotificationsConfig, PluginConfig, PrivateTransactionsConfig, RetryConfig,
+    RiskGuardConfig, RunsConfig, SearchConfig, SlackConfig, TenderlyConfig, TimeoutConfig, WebhooksConfig, WhatIfConfig,
+    watch_config, RESTART_ONLY_SETTINGS,
+};
+pub use dune::DuneClient;
+pub use error::AgentError;
+pub use etherscan::{EtherscanClient, HistoryKind};
+pub use events::{AgentEvent, EventSink};
+pub use fork::{anvil_launcher, ForkLauncher, ForkedNode};
+pub use guard::{classify_transaction, has_selfdestruct, ActionClass};
+pub use interaction::{Interaction, NoInteraction};
+pub use journal::RunJournal;
+pub use labels::AddressLabels;
+pub use mcp_client::{connect_http, connect_sse, default_connector, spawn_server, FoundryMcpClient, McpConnector};
+pub use notifications::{EmailChannel, Notification, NotificationChannel, Notifier, Severity, SlackChannel};
+pub use payment_uri::PaymentRequest;
+pub use plugins::{McpToolPack, PackTool, ToolPack};
+pub use portfolio::{AssetPnl, Holding, PortfolioHistory, PortfolioSample};
+pub use redact::{redact, register_secret, RedactingWriter};
+pub use retry::{classify, ErrorClass};
+pub use report::{render_portfolio, render_report, unsigned_bundle, ReportFormat};
+pub use safe_bundle::{BundleExecution, SafeBundle};
+pub use scripts::{cast_script, forge_script};
+pub use search::{
+    distill, html_to_text, search_provider, summarize_page, SearchOptions, SearchProvider, SearchProviderKind,
+    SearchResult,
+};
+pub use store::RunStore;
+pub use tenderly::{SimulationRequest, TenderlyClient};
+pub use webhook::{WebhookNotifier, SIGNATURE_HEADER};
+pub use tool_results::{AccountInfo, AddressValidation, BalanceResult, ContractCode, TokenBalance, TxResult};
+pub use types::{
+    AgentPlan, AgentResult, Artifact, BalanceChange, EnsResolution, PlanCostEstimate, PlanStatus, RunRecord, StepCostEstimate,
+    StepEvaluation, StepRecord, StepStatus, UserPrompt, WhatIfReport,
+};
+
+// Re-exported so embedders use the same rig version for provider clients
+pub use rig;
@@ -0,0 +1,1416 @@
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use rmcp::{
+    model::{CallToolRequestParam, CallToolResult, ClientInfo, ServerNotification, ServerRequest, Tool},
+    service::{NotificationContext, RoleClient, RunningService, Service, ServiceExt},
+    transport::{SseClientTransport, StreamableHttpClientTransport, TokioChildProcess},
+};
+use tokio::process::Command;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use alloy_primitives::U256;
+
+use crate::amounts::FiatConversion;
+use crate::blocklist::Blocklist;
+use crate::budget::RunBudget;
+use crate::cache::ToolCache;
+use crate::config::{AgentConfig, BudgetAction, McpConfig, McpTransport, PrivateTransactionsConfig, RetryConfig, TimeoutConfig};
+use crate::ens::is_ens_name;
+use crate::events::{AgentEvent, EventSink};
+use crate::guard::{classify_transaction, has_selfdestruct, mev_risks, ActionClass, ActionGuard};
+use crate::interaction::{Interaction, NoInteraction};
+use crate::journal::RunJournal;
+use crate::labels::AddressLabels;
+use crate::portfolio::PortfolioHistory;
+use crate::retry::{classify, with_backoff_if, ErrorClass};
+use crate::sampling::Sampler;
+use crate::tool_results::{
+    failure, payload, tool_payload, typed, AccountInfo, AddressValidation, BalanceResult, ContractCode, TokenBalance, TxResult,
+};
+use crate::tx_queue::{Fees, SenderQueue, TransactionQueue};
+use crate::types::{EnsResolution, ToolCallRecord};
+
+// How often pending transactions are checked while waiting for them to be mined
+const CONFIRMATION_POLL: std::time::Duration = std::time::Duration::from_secs(2);
+// Replacements refused as underpriced are sent again with higher fees this many times
+const MAX_FEE_BUMPS: u32 = 3;
+// transfer(address,uint256), whose amount is matched against pinned fiat amounts
+const TRANSFER_SELECTOR: &str = "a9059cbb";
+// Selector and two argument words of the calldata shown in confirmations, in hex characters
+const CALLDATA_SHOWN_CHARS: usize = 8 + 2 * 64;
+
+// Client side of an MCP session. Progress notifications from long-running tools are forwarded
+// to the events of the run currently using the session.
+// Sampling requests are answered by the run's sampler, if it has one.
+#[derive(Clone, Default)]
+pub struct SimpleClientService {
+    events: Arc<std::sync::RwLock<EventSink>>,
+    sampler: Arc<std::sync::RwLock<Option<Arc<dyn Sampler>>>>,
+}
+
+impl SimpleClientService {
+    pub fn attach(&self, events: EventSink) {
+        *self.events.write().unwrap() = events;
+    }
+
+    pub(crate) fn attach_sampler(&self, sampler: Option<Arc<dyn Sampler>>) {
+        *self.sampler.write().unwrap() = sampler;
+    }
+}
+
+impl Service<RoleClient> for SimpleClientService {
+    fn handle_request(
+        &self,
+        request: ServerRequest,
+        _context: rmcp::service::RequestContext<RoleClient>,
+    ) -> impl Future<Output = Result<rmcp::model::ClientResult, rmcp::ErrorData>> + Send + '_ {
+        async move {
+            match request {
+                ServerRequest::CreateMessageRequest(request) => {
+                    let sampler = self.sampler.read().unwrap().clone().ok_or_else(|| {
+                        rmcp::ErrorData::invalid_request("Sampling is not available outside of a run", None)
+                    })?;
+                    let result = sampler
+                        .sample(request.params)
+                        .await
+                        .map_err(|e| rmcp::ErrorData::internal_error(e.to_string(), None))?;
+                    Ok(rmcp::model::ClientResult::CreateMessageResult(result))
+                }
+                // Roots and pings are not supported, the client has nothing to offer for them
+                _ => Err(rmcp::ErrorData {
+                    code: rmcp::model::ErrorCode::METHOD_NOT_FOUND,
+                    message: "Client service only handles sampling requests".into(),
+                    data: None,
+                }),
+            }
+        }
+    }
+
+    fn handle_notification(
+        &self,
+        notification: ServerNotification,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl Future<Output = Result<(), rmcp::ErrorData>> + Send + '_ {
+        async move {
+            // Only progress is of interest, log messages and list changes are ignored
+            if let ServerNotification::ProgressNotification(notification) = notification {
+                let params = notification.params;
+                debug!("Tool progress {:?}: {} of {:?}", params.progress_token, params.progress, params.total);
+                self.events.read().unwrap().emit(AgentEvent::Progress {
+                    progress: f64::from(params.progress),
+                    total: params.total.map(f64::from),
+                    message: params.message,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo {
+            protocol_version: rmcp::model::ProtocolVersion::default(),
+            capabilities: rmcp::model::ClientCapabilities::builder().enable_sampling().build(),
+            client_info: rmcp::model::Implementation {
+                name: "eth-agent".to_string(),
+                version: "1.0.0".to_string(),
+            },
+        }
+    }
+}
+
+/// A connected MCP session to a foundry-mcp compatible server
+pub type McpService = RunningService<RoleClient, SimpleClientService>;
+
+/// Connects to the MCP server for a run. Replaces spawning foundry-mcp as a child process,
+/// e.g. to serve tools in-process in tests.
+pub type McpConnector = Arc<dyn Fn() -> BoxFuture<'static, Result<McpService>> + Send + Sync>;
+
+pub struct FoundryMcpClient {
+    // Swapped for a fresh session when the server dies mid-run
+    service: RwLock<McpService>,
+    // How to bring the server back, without one a crash ends the run
+    reconnect: Option<McpConnector>,
+    // Answers the server's sampling requests, attached again to every new session
+    sampler: Option<Arc<dyn Sampler>>,
+    cache: ToolCache,
+    timeouts: TimeoutConfig,
+    retry: RetryConfig,
+    journal: RunJournal,
+    events: EventSink,
+    // ENS names resolved for the current run, keyed by lowercased name
+    ens_names: std::sync::RwLock<HashMap<String, EnsResolution>>,
+    // Fiat amounts of the current run's prompt with the amounts they were converted to
+    fiat_amounts: std::sync::RwLock<Vec<FiatConversion>>,
+    // RPC URL picked with `use_network`, the server's default network when unset
+    network: std::sync::RwLock<Option<String>>,
+    // Chain id of every network switched to so far, keyed like `network`
+    chain_ids: std::sync::RwLock<HashMap<Option<String>, u64>>,
+    budget: RunBudget,
+    interaction: Arc<dyn Interaction>,
+    // Policy for dangerous transactions, everything is allowed without one
+    guard: Option<ActionGuard>,
+    // Nonces and pending hashes of sent transactions, per sender
+    queue: TransactionQueue,
+    // Assemble transactions for an external wallet instead of sending them
+    unsigned: bool,
+    // Next nonce per lowercased sender for unsigned transactions, which never reach the node
+    unsigned_nonces: std::sync::Mutex<HashMap<String, u64>>,
+    private_transactions: PrivateTransactionsConfig,
+    // Network names of the configured networks, to find the private RPC of the current one
+    networks: BTreeMap<String, String>,
+    // Shown next to addresses in previews and confirmations
+    labels: AddressLabels,
+    // Scam addresses refused whatever the guard says, shared across runs
+    blocklist: Blocklist,
+    // Lowercased contracts scored with risk_score this run, only the first call to one is checked
+    assessed_contracts: std::sync::Mutex<HashSet<String>>,
+}
+
+// Tools that change chain state, these must not be repeated unless the failure proves nothing was sent
+fn is_state_changing(tool: &str) -> bool {
+    matches!(tool, "send_transaction" | "send_user_operation")
+}
+
+// Anvil cheatcodes that rewrite chain state outside of transactions
+fn rewrites_state(tool: &str) -> bool {
+    matches!(tool, "set_code" | "set_storage_at" | "set_nonce" | "reset_fork" | "evm_revert")
+}
+
+fn payload_error(tool_result: &CallToolResult) -> Option<String> {
+    failure(&tool_payload(tool_result)?)
+}
+
+// Result of a transaction that was stopped before being sent
+fn blocked_transaction(error: String, from: &str, to: &str, value: &str) -> TxResult {
+    TxResult {
+        success: false,
+        blocked: true,
+        error: Some(error),
+        transaction_hash: None,
+        from: from.to_string(),
+        to: to.to_string(),
+        value: value.to_string(),
+        nonce: None,
+        contract_address: None,
+        actual_balance_changes: vec![],
+        unsigned_transaction: None,
+        eip681: None,
+    }
+}
+
+// Calldata as the user is asked about it: the selector and the first arguments, where token
+// recipients and amounts sit, with the size of whatever follows
+fn calldata_note(data: Option<&str>) -> String {
+    let data = data.unwrap_or_default().trim_start_matches("0x");
+    match data.len() {
+        0 => String::new(),
+        len if len <= CALLDATA_SHOWN_CHARS => format!(" with calldata 0x{}", data),
+        len => format!(" with calldata 0x{}… ({} bytes)", &data[..CALLDATA_SHOWN_CHARS], len / 2),
+    }
+}
+
+fn signed_difference(before: U256, after: U256) -> String {
+    if after >= before {
+        format!("+{}", after - before)
+    } else {
+        format!("-{}", before - after)
+    }
+}
+
+/// Start an MCP server with `command` and connect to it over its stdio
+pub async fn spawn_server(mut command: Command) -> Result<McpService> {
+    // Suppress server output by redirecting stderr to null
+    // (stdout is used for MCP communication, so we keep that)
+    command.stderr(std::process::Stdio::null());
+
+    // Create the service using TokioChildProcess
+    let transport = TokioChildProcess::new(command)
+        .map_err(|e| anyhow::anyhow!("Failed to start foundry-mcp server: {}", e))?;
+
+    let service = SimpleClientService::default().serve(transport).await
+        .map_err(|e| anyhow::anyhow!("Failed to establish MCP connection: {}", e))?;
+
+    Ok(service)
+}
+
+/// Connect to a foundry-mcp compatible server over streamable HTTP
+pub async fn connect_http(url: &str) -> Result<McpService> {
+    let transport = StreamableHttpClientTransport::from_uri(url.to_string());
+
+    let service = SimpleClientService::default().serve(transport).await
+        .map_err(|e| anyhow::anyhow!("Failed to establish MCP connection to {}: {}", url, e))?;
+
+    Ok(service)
+}
+
+/// Connect to a foundry-mcp compatible server over HTTP with server-sent events
+pub async fn connect_sse(url: &str) -> Result<McpService> {
+    let transport = SseClientTransport::start(url.to_string()).await
+        .map_err(|e| anyhow::anyhow!("Failed to open SSE stream to {}: {}", url, e))?;
+
+    let service = SimpleClientService::default().serve(transport).await
+        .map_err(|e| anyhow::anyhow!("Failed to establish MCP connection to {}: {}", url, e))?;
+
+    Ok(service)
+}
+
+// Command starting foundry-mcp for the stdio transport: the configured binary, then the one
+// installed next to the running executable, then `cargo run` from the source tree in development
+fn server_command(config: &McpConfig) -> Result<Command> {
+    let configured = config.binary.clone().or_else(|| std::env::var_os("FOUNDRY_MCP_BINARY").map(PathBuf::from));
+    if let Some(binary) = configured {
+        if !binary.is_file() {
+            return Err(anyhow::anyhow!("foundry-mcp binary {} does not exist", binary.display()));
+        }
+        return Ok(Command::new(binary));
+    }
+
+    let sibling = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(format!("foundry-mcp{}", std::env::consts::EXE_SUFFIX))));
+    if let Some(binary) = sibling.as_ref().filter(|binary| binary.is_file()) {
+        return Ok(Command::new(binary));
+    }
+
+    // Only a build from a checkout still has its workspace around
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("Cargo.toml");
+    if workspace.is_file() {
+        info!("No foundry-mcp binary found, building it with cargo");
+        let mut command = Command::new("cargo");
+        command.args(["run", "--quiet", "--bin", "foundry-mcp", "--manifest-path"]).arg(workspace);
+        return Ok(command);
+    }
+
+    Err(anyhow::anyhow!(
+        "foundry-mcp binary not found{}: install it next to eth-agent, or set [mcp] binary or FOUNDRY_MCP_BINARY",
+        sibling.map(|binary| format!(" at {}", binary.display())).unwrap_or_default()
+    ))
+}
+
+/// Connector for the transport in `config`: a spawned child process, or a remote server over HTTP or SSE
+pub fn default_connector(config: &McpConfig) -> McpConnector {
+    let config = config.clone();
+    Arc::new(move || {
+        let config = config.clone();
+        async move {
+            let remote_url = || {
+                config.url.clone()
+                    .ok_or_else(|| anyhow::anyhow!("[mcp] url is required for the {:?} transport", config.transport))
+            };
+
+            match config.transport {
+                McpTransport::Stdio => {
+                    let command = server_command(&config)?;
+                    info!("Starting foundry-mcp server as child process: {:?}", command.as_std());
+                    spawn_server(command).await
+                }
+                McpTransport::Http => {
+                    let url = remote_url()?;
+                    info!("Connecting to foundry-mcp at {}", url);
+                    connect_http(&url).await
+                }
+                McpTransport::Sse => {
+                    let url = remote_url()?;
+                    info!("Connecting to foundry-mcp at {} over SSE", url);
+                    connect_sse(&url).await
+                }
+            }
+        }
+        .boxed()
+    })
+}
+
+impl FoundryMcpClient {
+    pub async fn new(config: &AgentConfig, journal: RunJournal, events: EventSink) -> Result<Self> {
+        let connector = default_connector(&config.mcp);
+        let service = connector().await?;
+
+        Ok(Self::from_service(service, config, journal, events).with_reconnect(connector))
+    }
+
+    // Wrap an already connected MCP session
+    pub fn from_service(service: McpService, config: &AgentConfig, journal: RunJournal, events: EventSink) -> Self {
+        debug!("Connected to server: {:#?}", service.peer().peer_info());
+        service.service().attach(events.clone());
+
+        Self {
+            service: RwLock::new(service),
+            reconnect: None,
+            sampler: None,
+            cache: ToolCache::new(),
+            timeouts: config.timeouts.clone(),
+            retry: config.retry.clone(),
+            journal,
+            events,
+            ens_names: Default::default(),
+            fiat_amounts: Default::default(),
+            network: Default::default(),
+            chain_ids: Default::default(),
+            budget: RunBudget::new(config.budget.clone()),
+            interaction: Arc::new(NoInteraction),
+            guard: None,
+            queue: TransactionQueue::new(),
+            unsigned: config.unsigned_transactions,
+            unsigned_nonces: Default::default(),
+            private_transactions: config.private_transactions.clone(),
+            networks: config.networks.clone(),
+            labels: AddressLabels::new(&config.labels),
+            blocklist: Blocklist::new(&config.blocklist),
+            assessed_contracts: Default::default(),
+        }
+    }
+
+    // Count gas spent by sent transactions against the run's budget, asking `interaction`
+    // before going over it
+    pub fn with_budget(mut self, budget: RunBudget, interaction: Arc<dyn Interaction>) -> Self {
+        self.budget = budget;
+        self.interaction = interaction;
+        self
+    }
+
+    // Let the server sample completions from `sampler`
+    pub(crate) fn with_sampler(mut self, sampler: Arc<dyn Sampler>) -> Self {
+        self.service.get_mut().service().attach_sampler(Some(sampler.clone()));
+        self.sampler = Some(sampler);
+        self
+    }
+
+    // Check every transaction against `guard` before it is sent
+    pub fn with_guard(mut self, guard: ActionGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    // Share labels with the agent, names learned here show up in its tool results too
+    pub fn with_labels(mut self, labels: AddressLabels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_blocklist(mut self, blocklist: Blocklist) -> Self {
+        self.blocklist = blocklist;
+        self
+    }
+
+    // Reconnect with `connector` when the server goes away mid-run
+    pub fn with_reconnect(mut self, connector: McpConnector) -> Self {
+        self.reconnect = Some(connector);
+        self
+    }
+
+    async fn transport_closed(&self) -> bool {
+        self.service.read().await.peer().is_transport_closed()
+    }
+
+    // Replace the dead session with a fresh one from the connector
+    async fn restart(&self) -> Result<()> {
+        let connector = self.reconnect.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("foundry-mcp went away and there is no way to restart it"))?;
+        // Calls in flight at the same time all see the crash, only the first one reconnects
+        let mut current = self.service.write().await;
+        if !current.peer().is_transport_closed() {
+            return Ok(());
+        }
+
+        let service = connector().await?;
+        service.service().attach(self.events.clone());
+        service.service().attach_sampler(self.sampler.clone());
+
+        // A fresh server starts on its default network, switch it back to the run's
+        let network = self.network.read().unwrap().clone();
+        if let Some(rpc_url) = network {
+            service.peer().call_tool(CallToolRequestParam {
+                name: "use_network".into(),
+                arguments: serde_json::json!({ "rpc_url": rpc_url }).as_object().cloned(),
+            }).await?;
+        }
+
+        let dead = std::mem::replace(&mut *current, service);
+        tokio::spawn(async move {
+            let _ = dead.cancel().await;
+        });
+        Ok(())
+    }
+
+    // Call a tool on the server, giving up after the tool's timeout
+    // and retrying transient failures with backoff
+    async fn call_tool(&self, name: &str, arguments: serde_json::Value) -> Result<CallToolResult> {
+        let should_retry = |class: ErrorClass| {
+            if is_state_changing(name) {
+                class.is_safe_to_resend()
+            } else {
+                class.is_transient()
+            }
+        };
+
+        let mut tool_result = with_backoff_if(&self.retry, name, should_retry, || self.call_tool_once(name, &arguments)).await;
+
+        // A crashed server (panic, OOM) is restarted and the call replayed once. Transactions are
+        // not replayed, the server may have broadcast them before it died.
+        if tool_result.is_err() && self.transport_closed().await {
+            match self.restart().await {
+                Ok(()) if !is_state_changing(name) => {
+                    let message = format!("foundry-mcp stopped during {}, restarted it and retried the call", name);
+                    warn!("{}", message);
+                    self.events.emit(AgentEvent::Warning { message });
+                    tool_result = self.call_tool_once(name, &arguments).await;
+                }
+                Ok(()) => {
+                    let message = format!("foundry-mcp stopped during {} and was restarted", name);
+                    warn!("{}", message);
+                    self.events.emit(AgentEvent::Warning { message });
+                    tool_result = Err(anyhow::anyhow!(
+                        "foundry-mcp stopped while {} was in flight, check the sender's nonce and history before sending again",
+                        name
+                    ));
+                }
+                Err(e) => warn!("Could not restart foundry-mcp: {}", e),
+            }
+        }
+
+        // Record every call so an interrupted run can still report what it did
+        self.journal.record_tool_call(ToolCallRecord {
+            tool: name.to_string(),
+            arguments,
+            result: tool_result.as_ref().ok().and_then(|result| serde_json::to_value(result).ok()),
+            error: tool_result.as_ref().err().map(|e| e.to_string()),
+            timestamp: chrono::Utc::now(),
+        });
+
+        let tool_result = tool_result?;
+        debug!("{} tool result: {tool_result:#?}", name);
+
+        Ok(tool_result)
+    }
+
+    async fn call_tool_once(&self, name: &str, arguments: &serde_json::Value) -> Result<CallToolResult> {
+        let peer = self.service.read().await.peer().clone();
+        let request = peer.call_tool(CallToolRequestParam {
+            name: name.to_string().into(),
+            arguments: arguments.as_object().cloned(),
+        });
+
+        let timeout = self.timeouts.tool_timeout(name);
+        let tool_result = tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| anyhow::anyhow!("Tool call {} timed out after {}s", name, timeout.as_secs()))??;
+
+        // Surface transient failures reported inside the payload (e.g. nonce races) so they get retried
+        if let Some(error) = payload_error(&tool_result) {
+            if classify(&error).is_transient() {
+                return Err(anyhow::anyhow!("{} failed: {}", name, error));
+            }
+        }
+
+        Ok(tool_result)
+    }
+
+    // Call a tool and return its JSON payload
+    async fn call_payload(&self, name: &str, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        let tool_result = self.call_tool(name, arguments).await?;
+        payload(name, &tool_result)
+    }
+
+    // Call a read-only tool, serving repeated reads from the cache unless bypassed
+    async fn call_cached(&self, name: &str, arguments: serde_json::Value, bypass_cache: bool) -> Result<serde_json::Value> {
+        if !bypass_cache {
+            if let Some(cached) = self.cache.get(name, &arguments) {
+                debug!("Cache hit for {} with arguments {}", name, arguments);
+                return Ok(cached);
+            }
+        }
+
+        let tool_result = self.call_tool(name, arguments.clone()).await?;
+        let value = payload(name, &tool_result)?;
+
+        // Never cache failures, the next attempt may succeed
+        if !tool_result.is_error.unwrap_or(false) && failure(&value).is_none() {
+            self.cache.insert(name, &arguments, value.clone());
+        }
+
+        Ok(value)
+    }
+
+    pub async fn resolve_ens(&self, name: &str) -> Result<EnsResolution> {
+        let tool_result = self.call_tool("resolve_ens", serde_json::json!({ "name": name })).await?;
+
+        if let Some(error) = payload_error(&tool_result) {
+            return Err(anyhow::anyhow!("Failed to resolve {}: {}", name, error));
+        }
+
+        let address = tool_payload(&tool_result)
+            .and_then(|payload| payload.get("address")?.as_str().map(|address| address.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("Failed to resolve {}: no address in response", name))?;
+
+        Ok(EnsResolution {
+            name: name.to_lowercase(),
+            address,
+        })
+    }
+
+    // Pin the run's ENS resolutions, names passed to tools afterwards map to these addresses
+    // instead of being resolved again
+    pub fn pin_ens_names(&self, resolutions: &[EnsResolution]) {
+        for resolution in resolutions {
+            self.labels.learn(&resolution.address, &resolution.name);
+        }
+        *self.ens_names.write().unwrap() = resolutions
+            .iter()
+            .map(|resolution| (resolution.name.clone(), resolution.clone()))
+            .collect();
+    }
+
+    // Pin the run's fiat conversions, transactions sending one of these amounts show it in both
+    // denominations
+    pub fn pin_fiat_amounts(&self, conversions: &[FiatConversion]) {
+        *self.fiat_amounts.write().unwrap() = conversions.to_vec();
+    }
+
+    // The pinned fiat amount a transaction sends: its value for ETH, the amount of an ERC20
+    // transfer for tokens
+    fn fiat_amount(&self, value: &str, data: Option<&str>) -> Option<String> {
+        let value = U256::from_str(value).unwrap_or_default();
+        let data = data.unwrap_or_default().trim_start_matches("0x").to_lowercase();
+        let transferred = data
+            .strip_prefix(TRANSFER_SELECTOR)
+            .and_then(|arguments| arguments.get(64..128))
+            .and_then(|word| U256::from_str_radix(word, 16).ok());
+        self.fiat_amounts
+            .read()
+            .unwrap()
+            .iter()
+            .find(|conversion| {
+                if conversion.fiat.is_token {
+                    transferred == Some(conversion.raw)
+                } else {
+                    data.is_empty() && conversion.raw == value
+                }
+            })
+            .map(FiatConversion::describe)
+    }
+
+    // RPC URL of the network tools currently run against, `None` for the server's default
+    pub fn network(&self) -> Option<String> {
+        self.network.read().unwrap().clone()
+    }
+
+    // Switch the server to another network, or back to its default with `None`, returning the
+    // new chain id. Cached reads are from the old network and are dropped.
+    pub async fn use_network(&self, rpc_url: Option<&str>) -> Result<u64> {
+        let payload = self.call_payload("use_network", serde_json::json!({ "rpc_url": rpc_url })).await?;
+        if let Some(error) = failure(&payload) {
+            return Err(anyhow::anyhow!("use_network failed: {}", error));
+        }
+
+        self.cache.invalidate_all();
+        *self.network.write().unwrap() = rpc_url.map(str::to_string);
+        let chain_id = payload.get("chain_id").and_then(|chain_id| chain_id.as_u64()).unwrap_or_default();
+        self.chain_ids.write().unwrap().insert(rpc_url.map(str::to_string), chain_id);
+        Ok(chain_id)
+    }
+
+    // Chain id of the current network, `None` until it was switched to once
+    pub fn chain_id(&self) -> Option<u64> {
+        self.chain_ids.read().unwrap().get(&self.network()).copied()
+    }
+
+    // Replace a pinned ENS name with its address, plain addresses are returned as is
+    fn pinned_address(&self, value: &str) -> Result<String> {
+        if !is_ens_name(value) {
+            return Ok(value.to_string());
+        }
+        self.ens_names
+            .read()
+            .unwrap()
+            .get(&value.to_lowercase())
+            .map(|resolution| resolution.address.clone())
+            .ok_or_else(|| anyhow::anyhow!("ENS name {} was not resolved for this run, use an address instead", value))
+    }
+
+    pub async fn balance(&self, address: &str, bypass_cache: bool) -> Result<BalanceResult> {
+        let address = self.pinned_address(address)?;
+        let payload = self.call_cached("balance", serde_json::json!({ "address": address }), bypass_cache).await?;
+        typed("balance", payload)
+    }
+
+    // The node's accounts and `addresses`, with balances and nonces read now rather than cached
+    pub async fn accounts(&self, addresses: &[String]) -> Result<Vec<AccountInfo>> {
+        let payload = self.call_payload("accounts", serde_json::json!({ "addresses": addresses })).await?;
+        if let Some(error) = failure(&payload) {
+            return Err(anyhow::anyhow!("accounts failed: {}", error));
+        }
+        typed("accounts", payload.get("accounts").cloned().unwrap_or_default())
+    }
+
+    pub async fn validate_address(&self, address: &str) -> Result<AddressValidation> {
+        let address = self.pinned_address(address)?;
+        let payload = self.call_payload("validate_address", serde_json::json!({ "address": address })).await?;
+
+        // Invalid addresses come back as failures, but they are the answer here
+        serde_json::from_value(payload).map_err(|e| anyhow::anyhow!("Unexpected validate_address response: {}", e))
+    }
+
+    pub async fn send_transaction(
+        &self,
+        from: &str,
+        to: &str,
+        value: &str,
+        data: Option<&str>,
+        gas_limit: Option<u64>,
+        gas_price: Option<u128>,
+        fee_tier: Option<&str>,
+        private: bool,
+    ) -> Result<TxResult> {
+        let from = &self.pinned_address(from)?;
+        let to = &self.pinned_address(to)?;
+        if let Some(reason) = self.blocklisted(to, data).await {
+            return Ok(blocked_transaction(format!("Transaction blocked: {}", reason), from, to, value));
+        }
+        let mut arguments = serde_json::json!({
+            "from": from,
+            "to": to,
+            "value": value,
+        });
+
+        if let Some(data) = data {
+            arguments["data"] = serde_json::json!(data);
+        }
+
+        if let Some(gas_limit) = gas_limit {
+            arguments["gas_limit"] = serde_json::json!(gas_limit);
+        }
+
+        if let Some(gas_price) = gas_price {
+            arguments["gas_price"] = serde_json::json!(gas_price);
+        }
+
+        if let Some(fee_tier) = fee_tier {
+            arguments["fee_tier"] = serde_json::json!(fee_tier);
+        }
+
+        // Refuse rather than quietly exposing a transaction meant to stay private
+        let private_rpc = self.private_transactions.rpc_for(&self.networks, self.network().as_deref());
+        let private = private || (self.private_transactions.always && private_rpc.is_some());
+        if private {
+            let Some(private_rpc) = &private_rpc else {
+                return Ok(blocked_transaction(
+                    "No private RPC is configured for this network in [private_transactions.rpcs]".to_string(),
+                    from,
+                    to,
+                    value,
+                ));
+            };
+            arguments["private_rpc"] = serde_json::json!(private_rpc);
+        }
+
+        // One transaction at a time per sender, simulated only once its earlier ones were mined
+        let mut sender = self.queue.lock(from).await;
+        self.confirm_pending(&mut sender).await?;
+
+        // Never send a transaction that would revert, it only burns gas
+        let simulation = self.call_tool("simulate_transaction", arguments.clone()).await?;
+        if let Some(error) = payload_error(&simulation) {
+            warn!("Blocked transaction from {} to {}: {}", from, to, error);
+            return Ok(blocked_transaction(format!("Transaction blocked by pre-send simulation: {}", error), from, to, value));
+        }
+
+        // Show what is about to happen before it happens
+        let mut preview = self.call_tool("preview_transaction", arguments.clone()).await
+            .ok()
+            .and_then(|preview| tool_payload(&preview))
+            .filter(|preview| preview.get("success").and_then(|success| success.as_bool()).unwrap_or(false));
+        if let Some(preview) = &mut preview {
+            // Name the sender, the recipient and every account whose balance changes
+            preview["address_labels"] = serde_json::Value::Object(self.labels.labels_in(preview));
+            if let Some(fiat) = self.fiat_amount(value, data) {
+                preview["fiat_amount"] = serde_json::json!(fiat);
+            }
+            self.events.emit(AgentEvent::TransactionPreview { preview: preview.clone() });
+        }
+
+        if let Some(guard) = &self.guard {
+            let class = self.classify_transaction(to, data, guard).await;
+            let target = if to.is_empty() { "a new contract".to_string() } else { self.labels.describe(to) };
+            let mut description =
+                format!("Transaction from {} to {} ({} wei){}", self.labels.describe(from), target, value, calldata_note(data));
+            if let Some(fiat) = self.fiat_amount(value, data) {
+                description = format!("{}, sending {}", description, fiat);
+            }
+            if let Err(reason) = guard.check(class, &description).await {
+                return Ok(blocked_transaction(format!("Transaction blocked: {}", reason), from, to, value));
+            }
+
+            // Swaps worth sandwiching stay out of the public mempool unless the user accepts the risk
+            let exposure = preview.as_ref().and_then(|preview| preview.get("mev_exposure")).filter(|exposure| !exposure.is_null());
+            let risks = exposure.map(|exposure| mev_risks(exposure, guard.mev_config())).unwrap_or_default();
+            if !private && !self.unsigned && !risks.is_empty() {
+                let description = format!("Public swap from {} to {} exposed to sandwiching ({})", from, target, risks.join(", "));
+                self.events.emit(AgentEvent::Warning { message: description.clone() });
+                if guard.mev_config().require_private {
+                    let Some(private_rpc) = &private_rpc else {
+                        let reason = format!("{}, and no private RPC is configured for this network", description);
+                        return Ok(blocked_transaction(format!("Transaction blocked: {}", reason), from, to, value));
+                    };
+                    info!("Sending through {} instead: {}", private_rpc, description);
+                    arguments["private_rpc"] = serde_json::json!(private_rpc);
+                } else if let Err(reason) = guard.check_mev_exposure(&description).await {
+                    return Ok(blocked_transaction(format!("Transaction blocked: {}", reason), from, to, value));
+                }
+            }
+
+            if let Err(reason) = self.check_contract_risk(to, data, &target, guard).await {
+                return Ok(blocked_transaction(format!("Transaction blocked: {}", reason), from, to, value));
+            }
+        }
+
+        // Gas is only known from the preview, so without one the transaction can't be checked
+        let gas_cost = preview
+            .as_ref()
+            .and_then(|preview| preview.get("estimated_gas_cost")?.as_str())
+            .and_then(|cost| U256::from_str(cost).ok());
+        if let Some(overrun) = gas_cost.and_then(|cost| self.budget.gas_overrun(cost)) {
+            let approved = self.budget.config().on_exceeded == BudgetAction::Confirm
+                && self.interaction.confirm(&format!("{}. Send it anyway?", overrun)).await;
+            if !approved {
+                warn!("Blocked transaction from {} to {}: {}", from, to, overrun);
+                self.budget.refuse(&overrun);
+                return Ok(blocked_transaction(format!("Transaction blocked: {}", overrun), from, to, value));
+            }
+            self.budget.waive_gas();
+        }
+
+        if self.unsigned {
+            drop(sender);
+            return self.build_unsigned(from, arguments).await;
+        }
+
+        let tool_result = self.send_queued(&mut sender, arguments).await;
+        drop(sender);
+
+        // Balances and code may have changed, drop everything we cached so far
+        self.cache.invalidate_all();
+
+        // Failures to send are reported in the result, the payload still names the transaction
+        let mut result: TxResult = serde_json::from_value(payload("send_transaction", &tool_result?)?)
+            .map_err(|e| anyhow::anyhow!("Unexpected send_transaction response: {}", e))?;
+
+        // Record what actually changed, so the step output can be compared against the preview
+        if let (true, Some(gas_cost)) = (result.success, gas_cost) {
+            self.budget.record_gas(gas_cost);
+        }
+        if let (true, Some(preview)) = (result.success, &preview) {
+            result.actual_balance_changes = self.actual_balance_changes(preview).await;
+        }
+
+        Ok(result)
+    }
+
+    // Assemble the transaction for an external wallet instead of sending it. Nothing gets mined,
+    // so a sender's later transactions take the nonces after its earlier unsigned ones.
+    async fn build_unsigned(&self, from: &str, mut arguments: serde_json::Value) -> Result<TxResult> {
+        let next_nonce = self.unsigned_nonces.lock().unwrap().get(&from.to_lowercase()).copied();
+        if let Some(nonce) = next_nonce {
+            arguments["nonce"] = serde_json::json!(nonce);
+        }
+
+        let payload = self.call_payload("build_unsigned_transaction", arguments).await?;
+        if let Some(nonce) = payload.get("nonce").and_then(|nonce| nonce.as_u64()).filter(|_| failure(&payload).is_none()) {
+            self.unsigned_nonces.lock().unwrap().insert(from.to_lowercase(), nonce + 1);
+        }
+        serde_json::from_value(payload).map_err(|e| anyhow::anyhow!("Unexpected build_unsigned_transaction response: {}", e))
+    }
+
+    // Send with the sender's next nonce. A replacement the node refuses as underpriced goes out
+    // again with bumped fees, and a nonce used up outside the queue is read again from the node.
+    async fn send_queued(&self, sender: &mut SenderQueue, mut arguments: serde_json::Value) -> Result<CallToolResult> {
+        let mut fees: Option<Fees> = None;
+        let mut fee_bumps = 0;
+        let mut nonce_refreshed = false;
+        loop {
+            match (sender.next_nonce, arguments.as_object_mut()) {
+                (Some(nonce), Some(arguments)) => {
+                    arguments.insert("nonce".to_string(), serde_json::json!(nonce));
+                }
+                (None, Some(arguments)) => {
+                    arguments.remove("nonce");
+                }
+                _ => {}
+            }
+
+            let tool_result = self.call_tool("send_transaction", arguments.clone()).await;
+            let error = match &tool_result {
+                Ok(result) => payload_error(result),
+                Err(e) => Some(e.to_string()),
+            };
+            let Some(error) = error.map(|error| error.to_lowercase()) else {
+                if let Some(sent) = tool_result.as_ref().ok().and_then(tool_payload) {
+                    let hash = sent.get("transaction_hash").and_then(|hash| hash.as_str());
+                    let nonce = sent.get("nonce").and_then(|nonce| nonce.as_u64());
+                    if let (Some(hash), Some(nonce)) = (hash, nonce) {
+                        sender.sent(hash.to_string(), nonce, Fees::from_json(&sent["fees"]));
+                    }
+                }
+                return tool_result;
+            };
+
+            if error.contains("nonce too low") && !nonce_refreshed {
+                nonce_refreshed = true;
+                sender.next_nonce = None;
+                continue;
+            }
+
+            if error.contains("underpriced") && fee_bumps < MAX_FEE_BUMPS {
+                let current = match fees.take() {
+                    Some(fees) => Some(fees),
+                    None => self.replaced_fees(sender).await,
+                };
+                let Some(current) = current else {
+                    return tool_result;
+                };
+                let bumped = current.bumped();
+                let message = format!("Transaction replacement underpriced, sending again with higher fees {:?}", bumped);
+                warn!("{}", message);
+                self.events.emit(AgentEvent::Warning { message });
+                bumped.apply(&mut arguments);
+                fees = Some(bumped);
+                fee_bumps += 1;
+                continue;
+            }
+
+            return tool_result;
+        }
+    }
+
+    // Fees a replacement has to outbid: the queue's own pending transaction at the nonce, or the
+    // gas oracle's fast tier when the nonce is held by a transaction sent from elsewhere
+    async fn replaced_fees(&self, sender: &SenderQueue) -> Option<Fees> {
+        if let Some(fees) = sender.next_nonce.and_then(|nonce| sender.fees_at(nonce)).filter(Fees::is_known) {
+            return Some(fees);
+        }
+        let oracle = self.gas_oracle(None).await.ok()?;
+        Some(Fees::from_json(oracle.pointer("/tiers/fast")?)).filter(Fees::is_known)
+    }
+
+    // Wait until the sender's earlier transactions are mined, so what comes next sees their effects
+    async fn confirm_pending(&self, sender: &mut SenderQueue) -> Result<()> {
+        let timeout = self.timeouts.confirmation();
+        let deadline = tokio::time::Instant::now() + timeout;
+        while let Some(pending) = sender.pending.first().cloned() {
+            let status = self.call_payload("transaction_status", serde_json::json!({ "hash": pending.hash })).await?;
+            if let Some(error) = failure(&status) {
+                // Without a status there is nothing to wait on, let the server read the nonce from the node
+                warn!("Can't check transaction {}, not waiting for it: {}", pending.hash, error);
+                sender.pending.clear();
+                sender.next_nonce = None;
+                return Ok(());
+            }
+
+            match status.get("status").and_then(|status| status.as_str()) {
+                Some("confirmed") => {
+                    sender.pending.remove(0);
+                }
+                Some("failed") => {
+                    let message = format!("Transaction {} (nonce {}) was mined but reverted", pending.hash, pending.nonce);
+                    warn!("{}", message);
+                    self.events.emit(AgentEvent::Warning { message });
+                    sender.pending.remove(0);
+                }
+                Some("not_found") => {
+                    // Replaced or dropped outside the queue, its nonce may be free again
+                    warn!("Transaction {} is no longer known to the node", pending.hash);
+                    sender.pending.remove(0);
+                    sender.next_nonce = None;
+                }
+                _ if tokio::time::Instant::now() >= deadline => {
+                    return Err(anyhow::anyhow!(
+                        "Transaction {} (nonce {}) is still pending after {}s, nothing is sent from this account until it is mined",
+                        pending.hash,
+                        pending.nonce,
+                        timeout.as_secs()
+                    ));
+                }
+                _ => tokio::time::sleep(CONFIRMATION_POLL).await,
+            }
+        }
+        Ok(())
+    }
+
+    // Wait until every transaction sent so far is mined, before a step that may depend on them
+    pub async fn confirm_sent_transactions(&self) -> Result<()> {
+        for sender in self.queue.senders() {
+            let mut sender = self.queue.lock(&sender).await;
+            self.confirm_pending(&mut sender).await?;
+        }
+        Ok(())
+    }
+
+    // Snapshot of the whole chain, `None` when the node is not a local dev node that can take one
+    pub async fn snapshot_chain(&self) -> Result<Option<String>> {
+        let payload = self.call_payload("evm_snapshot", serde_json::json!({})).await?;
+        if let Some(error) = failure(&payload) {
+            debug!("No chain snapshot: {}", error);
+            return Ok(None);
+        }
+        Ok(payload.get("snapshot_id").and_then(|id| id.as_str()).map(|id| id.to_string()))
+    }
+
+    // Go back to a snapshot, dropping every change made since
+    pub async fn revert_chain(&self, snapshot_id: &str) -> Result<()> {
+        let payload = self.call("evm_revert", serde_json::json!({ "snapshot_id": snapshot_id })).await?;
+        match failure(&payload) {
+            Some(error) => Err(anyhow::anyhow!("evm_revert failed: {}", error)),
+            None => Ok(()),
+        }
+    }
+
+    // Token lists don't change while the server runs
+    pub async fn resolve_token(&self, symbol: &str) -> Result<serde_json::Value> {
+        let payload = self.call_cached("resolve_token", serde_json::json!({ "symbol": symbol }), false).await?;
+        let token = |field: &str| payload.pointer(field).and_then(|value| value.as_str());
+        if let (Some(address), Some(symbol)) = (token("/token/address"), token("/token/symbol")) {
+            self.labels.learn(address, symbol);
+        }
+        Ok(payload)
+    }
+
+    // Old blocks don't change, but the latest sample does, so this is never cached
+    pub async fn portfolio_history(&self, address: &str, tokens: &[String], days: u32, samples: u32) -> Result<PortfolioHistory> {
+        let address = self.pinned_address(address)?;
+        let mut arguments = serde_json::json!({ "address": address, "days": days, "samples": samples });
+        if !tokens.is_empty() {
+            arguments["tokens"] = serde_json::json!(tokens);
+        }
+        let payload = self.call_payload("portfolio_history", arguments).await?;
+        typed("portfolio_history", payload)
+    }
+
+    pub async fn safe_info(&self, safe: &str) -> Result<serde_json::Value> {
+        let safe = self.pinned_address(safe)?;
+        self.call_payload("safe_info", serde_json::json!({ "safe": safe })).await
+    }
+
+    // Proposals only collect the proposer's signature, but they are guarded like a transaction
+    // since the other owners may confirm them without a second look
+    pub async fn propose_safe_transaction(
+        &self,
+        safe: &str,
+        to: &str,
+        value: &str,
+        data: Option<&str>,
+        proposer: &str,
+        nonce: Option<u64>,
+    ) -> Result<serde_json::Value> {
+        let safe = &self.pinned_address(safe)?;
+        let to = &self.pinned_address(to)?;
+        let proposer = &self.pinned_address(proposer)?;
+        if let Some(reason) = self.blocklisted(to, data).await {
+            return Ok(serde_json::to_value(blocked_transaction(format!("Proposal blocked: {}", reason), safe, to, value))?);
+        }
+
+        if let Some(guard) = &self.guard {
+            let class = self.classify_transaction(to, data, guard).await;
+            let description = format!("Safe {} proposal to {} ({} wei){}", safe, to, value, calldata_note(data));
+            if let Err(reason) = guard.check(class, &description).await {
+                return Ok(serde_json::to_value(blocked_transaction(format!("Proposal blocked: {}", reason), safe, to, value))?);
+            }
+        }
+
+        let mut arguments = serde_json::json!({
+            "safe": safe,
+            "to": to,
+            "value": value,
+            "proposer": proposer,
+        });
+        if let Some(data) = data {
+            arguments["data"] = serde_json::json!(data);
+        }
+        if let Some(nonce) = nonce {
+            arguments["nonce"] = serde_json::json!(nonce);
+        }
+
+        self.call_payload("propose_safe_transaction", arguments).await
+    }
+
+    // The smart account makes the call, so it is guarded like a transaction from the account:
+    // build through the bundler (and the paymaster when `sponsored`), show who pays the gas,
+    // then sign as the owner, submit and wait for inclusion
+    pub async fn send_user_operation(
+        &self,
+        account: &str,
+        owner: &str,
+        to: &str,
+        value: &str,
+        data: Option<&str>,
+        sponsored: bool,
+    ) -> Result<serde_json::Value> {
+        let account = &self.pinned_address(account)?;
+        let owner = &self.pinned_address(owner)?;
+        let to = &self.pinned_address(to)?;
+        if let Some(reason) = self.blocklisted(to, data).await {
+            return Ok(serde_json::to_value(blocked_transaction(format!("UserOperation blocked: {}", reason), account, to, value))?);
+        }
+
+        let mut arguments = serde_json::json!({
+            "account": account,
+            "to": to,
+            "value": value,
+        });
+        if let Some(data) = data {
+            arguments["data"] = serde_json::json!(data);
+        }
+        if sponsored {
+            arguments["sponsored"] = serde_json::json!(true);
+        }
+        let built = self.call_payload("build_user_operation", arguments).await?;
+        if failure(&built).is_some() {
+            return Ok(built);
+        }
+
+        // Sponsorship terms are part of what the user approves
+        let sponsorship = built.get("sponsorship").filter(|sponsorship| !sponsorship.is_null());
+        let mut preview = serde_json::json!({
+            "from": account,
+            "to": to,
+            "value": value,
+            "estimated_gas": built["max_gas"],
+            "estimated_gas_cost": built["max_gas_cost"],
+            "sponsorship": sponsorship,
+        });
+        preview["address_labels"] = serde_json::Value::Object(self.labels.labels_in(&preview));
+        self.events.emit(AgentEvent::TransactionPreview { preview });
+        if let Some(guard) = &self.guard {
+            let class = self.classify_transaction(to, data, guard).await;
+            let gas = match sponsorship.and_then(|sponsorship| sponsorship.get("paymaster")?.as_str()) {
+                Some(paymaster) => format!(", gas sponsored by paymaster {}", paymaster),
+                None => String::new(),
+            };
+            let description = format!(
+                "UserOperation from smart account {} to {} ({} wei){}{}",
+                self.labels.describe(account),
+                self.labels.describe(to),
+                value,
+                calldata_note(data),
+                gas
+            );
+            if let Err(reason) = guard.check(class, &description).await {
+                return Ok(serde_json::to_value(blocked_transaction(format!("UserOperation blocked: {}", reason), account, to, value))?);
+            }
+        }
+
+        let signed = self
+            .call_payload("sign_user_operation", serde_json::json!({ "user_operation": built["user_operation"], "owner": owner }))
+            .await?;
+        if failure(&signed).is_some() {
+            return Ok(signed);
+        }
+        let sent = self.call_payload("send_user_operation", serde_json::json!({ "user_operation": signed["user_operation"] })).await;
+        self.cache.invalidate_all();
+        sent
+    }
+
+    // A signed permit is as good as an on-chain approval once anyone submits it, so it is
+    // guarded like one
+    pub async fn sign_permit(
+        &self,
+        token: &str,
+        owner: &str,
+        spender: &str,
+        value: &str,
+        deadline: Option<u64>,
+    ) -> Result<serde_json::Value> {
+        let owner = &self.pinned_address(owner)?;
+        let spender = &self.pinned_address(spender)?;
+        if let Some(reason) = self.blocklisted(spender, None).await {
+            return Ok(serde_json::to_value(blocked_transaction(format!("Permit blocked: {}", reason), owner, spender, value))?);
+        }
+
+        if let Some(guard) = &self.guard {
+            let class = match U256::from_str(value) {
+                Ok(amount) if amount == U256::MAX => ActionClass::UnlimitedApproval,
+                _ => ActionClass::Approval,
+            };
+            let description = format!("Permit for {} to spend {} of token {} from {}", spender, value, token, owner);
+            if let Err(reason) = guard.check(class, &description).await {
+                return Ok(serde_json::to_value(blocked_transaction(format!("Permit blocked: {}", reason), owner, spender, value))?);
+            }
+        }
+
+        let mut arguments = serde_json::json!({
+            "token": token,
+            "owner": owner,
+            "spender": spender,
+            "value": value,
+        });
+        if let Some(deadline) = deadline {
+            arguments["deadline"] = serde_json::json!(deadline);
+        }
+
+        self.call_payload("sign_permit", arguments).await
+    }
+
+    // A sign-in signature lets the site act as the account within its own service, so it is
+    // confirmed per the `sign_in` policy; the domain is part of the question to catch lookalikes
+    pub async fn sign_in_with_ethereum(&self, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        let mut arguments = arguments;
+        let address = self.pinned_address(arguments["address"].as_str().unwrap_or_default())?;
+        arguments["address"] = serde_json::json!(address);
+
+        if let Some(guard) = &self.guard {
+            let domain = arguments["domain"].as_str().unwrap_or_default();
+            let description = format!("Sign in to {} as {}", domain, address);
+            if let Err(reason) = guard.check_sign_in(&description).await {
+                return Ok(serde_json::json!({
+                    "success": false,
+                    "blocked": true,
+                    "error": format!("Sign-in blocked: {}", reason),
+                    "address": address,
+                    "domain": domain
+                }));
+            }
+        }
+
+        self.call_payload("sign_in_with_ethereum", arguments).await
+    }
+
+    // Cheatcodes bypass transactions, and with them the classification, budget and blocklist
+    // checks of a send, so each one is confirmed per the `cheatcodes` policy instead
+    pub async fn cheatcode(&self, name: &str, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        if let Some(guard) = &self.guard {
+            let description = format!("Cheatcode {} with {}", name, arguments);
+            if let Err(reason) = guard.check_cheatcode(&description).await {
+                return Ok(serde_json::json!({
+                    "success": false,
+                    "blocked": true,
+                    "error": format!("Cheatcode blocked: {}", reason)
+                }));
+            }
+        }
+
+        self.call(name, arguments).await
+    }
+
+    // Calls into contracts that can selfdestruct rank above whatever the calldata says
+    async fn classify_transaction(&self, to: &str, data: Option<&str>, guard: &ActionGuard) -> ActionClass {
+        let class = classify_transaction(to, data);
+        let is_call = !to.is_empty() && !data.unwrap_or_default().trim_start_matches("0x").is_empty();
+        // Only look at the code when it could change the outcome
+        if !is_call || guard.always_allows(ActionClass::SelfDestruct) {
+            return class;
+        }
+
+        let code = self
+            .call_tool("get_contract_code", serde_json::json!({ "address": to }))
+            .await
+            .ok()
+            .and_then(|code| tool_payload(&code))
+            .and_then(|payload| payload.get("code")?.as_str().map(|code| code.to_string()));
+        match code {
+            Some(code) if has_selfdestruct(&code) => ActionClass::SelfDestruct,
+            _ => class,
+        }
+    }
+
+    // Why `to` or the recipient or spender in `data` is refused, `None` when neither is blocklisted
+    async fn blocklisted(&self, to: &str, data: Option<&str>) -> Option<String> {
+        let (address, source) = self.blocklist.find(to, data).await?;
+        let reason = format!(
+            "{} is a known scam address (listed by {}), add it to [blocklist] allow to override",
+            self.labels.describe(&address),
+            source
+        );
+        warn!("{}", reason);
+        self.events.emit(AgentEvent::Warning { message: reason.clone() });
+        Some(reason)
+    }
+
+    // Score a contract with risk_score before the run first calls it, when the `risk` policy asks
+    // for it. Contracts scoring `max_score` or more need the user's approval, and so do contracts
+    // that couldn't be scored.
+    async fn check_contract_risk(&self, to: &str, data: Option<&str>, target: &str, guard: &ActionGuard) -> Result<(), String> {
+        let is_call = !to.is_empty() && !data.unwrap_or_default().trim_start_matches("0x").is_empty();
+        if !is_call || !guard.risk_config().require_for_new_contracts {
+            return Ok(());
+        }
+        if !self.assessed_contracts.lock().unwrap().insert(to.to_lowercase()) {
+            return Ok(());
+        }
+
+        let assessment = self
+            .call_tool("risk_score", serde_json::json!({ "address": to }))
+            .await
+            .ok()
+            .and_then(|result| tool_payload(&result))
+            .filter(|assessment| failure(assessment).is_none());
+        let Some(assessment) = assessment else {
+            return guard.check_risk(&format!("First call to {}, whose risk couldn't be scored", target)).await;
+        };
+        if assessment.get("is_contract").and_then(|is_contract| is_contract.as_bool()) == Some(false) {
+            return Ok(());
+        }
+
+        let score = assessment.get("score").and_then(|score| score.as_u64()).unwrap_or_default();
+        let reasons: Vec<&str> = assessment
+            .get("reasons")
+            .and_then(|reasons| reasons.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|reason| reason.as_str())
+            .collect();
+        let description = if reasons.is_empty() {
+            format!("First call to {}, risk score {}", target, score)
+        } else {
+            format!("First call to {}, risk score {} ({})", target, score, reasons.join(", "))
+        };
+        if score < u64::from(guard.risk_config().max_score) {
+            info!("{}", description);
+            return Ok(());
+        }
+        self.events.emit(AgentEvent::Warning { message: description.clone() });
+        guard.check_risk(&description).await
+    }
+
+    // Re-read every balance from the preview and compare it with the balance before sending
+    async fn actual_balance_changes(&self, preview: &serde_json::Value) -> Vec<serde_json::Value> {
+        let mut changes = vec![];
+        let entries = preview.get("balance_changes").and_then(|changes| changes.as_array()).cloned().unwrap_or_default();
+
+        for entry in entries {
+            let (Some(address), Some(asset), Some(before)) = (
+                entry.get("address").and_then(|address| address.as_str()),
+                entry.get("asset").and_then(|asset| asset.as_str()),
+                entry.get("before").and_then(|before| before.as_str()).and_then(|before| U256::from_str(before).ok()),
+            ) else {
+                continue;
+            };
+
+            let arguments = if asset == "ETH" {
+                ("balance", serde_json::json!({ "address": address }))
+            } else {
+                ("erc20_balance", serde_json::json!({ "address": address, "token_address": asset }))
+            };
+
+            let after = self.call_tool(arguments.0, arguments.1).await
+                .ok()
+                .and_then(|result| tool_payload(&result))
+                .and_then(|payload| payload.get("balance")?.as_str().and_then(|balance| U256::from_str(balance).ok()));
+
+            if let Some(after) = after {
+                changes.push(serde_json::json!({
+                    "address": address,
+                    "asset": asset,
+                    "expected_change": entry.get("change"),
+                    "actual_change": signed_difference(before, after),
+                }));
+            }
+        }
+
+        changes
+    }
+
+    pub async fn get_contract_code(&self, address: &str, bypass_cache: bool) -> Result<ContractCode> {
+        let address = self.pinned_address(address)?;
+        let payload = self.call_cached("get_contract_code", serde_json::json!({ "address": address }), bypass_cache).await?;
+
+        // An account without code is reported as a failure, for callers it's just not a contract
+        match failure(&payload) {
+            Some(error) if !error.contains("No contract code") => Err(anyhow::anyhow!("get_contract_code failed: {}", error)),
+            _ => serde_json::from_value(payload).map_err(|e| anyhow::anyhow!("Unexpected get_contract_code response: {}", e)),
+        }
+    }
+
+    // Gas a transaction would use, previewed without showing the preview to the user
+    pub async fn estimate_gas(&self, from: &str, to: &str, value: &str, data: Option<&str>) -> Result<u64> {
+        let mut arguments = serde_json::json!({
+            "from": self.pinned_address(from)?,
+            "to": self.pinned_address(to)?,
+            "value": value,
+        });
+        if let Some(data) = data {
+            arguments["data"] = serde_json::json!(data);
+        }
+
+        let payload = self.call_payload("preview_transaction", arguments).await?;
+        if let Some(error) = failure(&payload) {
+            return Err(anyhow::anyhow!("preview_transaction failed: {}", error));
+        }
+        payload
+            .get("estimated_gas")
+            .and_then(|gas| gas.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("preview_transaction returned no gas estimate"))
+    }
+
+    // Prices move, so they are never served from the cache
+    pub async fn get_price(&self, asset: &str, source: Option<&str>) -> Result<serde_json::Value> {
+        let mut arguments = serde_json::json!({ "asset": asset });
+        if let Some(source) = source {
+            arguments["source"] = serde_json::json!(source);
+        }
+        self.call_payload("get_price", arguments).await
+    }
+
+    // Fees move every block, so they are never served from the cache either
+    pub async fn gas_oracle(&self, source: Option<&str>) -> Result<serde_json::Value> {
+        let mut arguments = serde_json::json!({});
+        if let Some(source) = source {
+            arguments["source"] = serde_json::json!(source);
+        }
+        self.call_payload("gas_oracle", arguments).await
+    }
+
+    pub async fn erc20_balance(&self, address: &str, token_address: &str, bypass_cache: bool) -> Result<TokenBalance> {
+        let address = self.pinned_address(address)?;
+        let payload = self.call_cached(
+            "erc20_balance",
+            serde_json::json!({ "address": address, "token_address": token_address }),
+            bypass_cache,
+        ).await?;
+        typed("erc20_balance", payload)
+    }
+
+    // Definitions of every tool the server offers
+    pub async fn list_tools(&self) -> Result<Vec<Tool>> {
+        let peer = self.service.read().await.peer().clone();
+        Ok(peer.list_all_tools().await?)
+    }
+
+    // Call any server tool by name and return its payload, failures reported in it are left
+    // for the caller. Pinned ENS names in top-level arguments are replaced with their addresses.
+    pub async fn call(&self, name: &str, mut arguments: serde_json::Value) -> Result<serde_json::Value> {
+        if let Some(arguments) = arguments.as_object_mut() {
+            for value in arguments.values_mut() {
+                if let Some(address) = value.as_str().filter(|value| is_ens_name(value)) {
+                    *value = serde_json::json!(self.pinned_address(address)?);
+                }
+            }
+        }
+        // Only tools with a TTL in `ToolCache` are served from the cache
+        let payload = self.call_cached(name, arguments, false).await;
+        if rewrites_state(name) {
+            // Balances, code, storage and nonces read before may no longer be true
+            self.cache.invalidate_all();
+            self.queue.reset();
+        }
+        payload
+    }
+
+    #[allow(dead_code)]
+    pub async fn cancel(self) -> Result<()> {
+        self.service.into_inner().cancel().await?;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub async fn is_alive(&self) -> bool {
+        // Check if the service is still connected by trying to list tools
+        let peer = self.service.read().await.peer().clone();
+        match peer.list_tools(Default::default()).await {
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
+} 
\ No newline at end of file
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::artifacts;
+use crate::config::{EmailConfig, NotificationsConfig};
+use crate::error::AgentError;
+use crate::redact::redact;
+use crate::types::{AgentResult, Artifact, PlanStatus, RunRecord};
+
+// Prompt characters quoted in a notification title
+const TITLE_PROMPT_CHARS: usize = 80;
+// How long one channel may take to deliver, runs wait for their notifications before returning
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How urgent a notification is, channels only get those at or above their `min_severity`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub title: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// Summary of a finished run, e.g. "12-step plan completed: 3 transactions sent, 3 contracts deployed".
+    /// Results and errors can quote keys from tool output, so title and body are redacted.
+    pub fn for_run(run: &RunRecord, outcome: &Result<AgentResult, AgentError>) -> Self {
+        let prompt: String = run.prompt.natural_language.chars().take(TITLE_PROMPT_CHARS).collect();
+        let artifacts = match outcome {
+            Ok(result) => result.artifacts.clone(),
+            Err(_) => artifacts::extract(&run.steps),
+        };
+        let transactions = artifacts.iter().filter(|artifact| matches!(artifact, Artifact::TransactionHash { .. })).count();
+        let contracts = artifacts.iter().filter(|artifact| matches!(artifact, Artifact::DeployedContract { .. })).count();
+
+        let (severity, verb, detail) = match outcome {
+            Ok(result) if matches!(run.status, PlanStatus::Completed) && result.error_message.is_none() => {
+                (Severity::Info, "completed", result.result.clone())
+            }
+            Ok(result) => (
+                Severity::Warning,
+                "finished with problems",
+                result.error_message.clone().unwrap_or_else(|| result.result.clone()),
+            ),
+            Err(e) => (Severity::Critical, "failed", e.to_string()),
+        };
+
+        let mut summary = format!("{}-step plan {}", run.steps.len(), verb);
+        let mut counts = vec![];
+        if transactions > 0 {
+            counts.push(format!("{} transaction(s) sent", transactions));
+        }
+        if contracts > 0 {
+            counts.push(format!("{} contract(s) deployed", contracts));
+        }
+        if !counts.is_empty() {
+            summary += &format!(": {}", counts.join(", "));
+        }
+
+        Self {
+            severity,
+            title: redact(&format!("ETH Agent run {}: {}", verb, prompt)),
+            body: redact(&format!("{}\n\n{}\n\nRun {}", summary, detail, run.id)),
+        }
+    }
+}
+
+/// Somewhere notifications are delivered, e.g. a Slack channel or a mailbox
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Posts to a Slack incoming webhook
+pub struct SlackChannel {
+    webhook_url: String,
+    http: reqwest::Client,
+}
+
+impl SlackChannel {
+    pub fn new(webhook_url: String) -> Self {
+        Self {
+            webhook_url,
+            http: reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).build().unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for SlackChannel {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let text = format!("*{}*\n{}", notification.title, notification.body);
+        self.http
+            .post(&self.webhook_url)
+            .json(&json!({ "text": text }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sends plain text emails through an SMTP relay, over STARTTLS
+pub struct EmailChannel {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailChannel {
+    pub fn new(config: &EmailConfig, password: Option<String>) -> Result<Self> {
+        let host = config.smtp_host.as_deref().ok_or_else(|| anyhow::anyhow!("No SMTP host is configured"))?;
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+            .port(config.smtp_port)
+            .timeout(Some(DELIVERY_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Self {
+            transport: transport.build(),
+            from: config.from.parse()?,
+            to: config.to.iter().map(|to| to.parse()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let mut message = Message::builder().from(self.from.clone()).subject(notification.title.clone());
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        self.transport.send(message.body(notification.body.clone())?).await?;
+        Ok(())
+    }
+}
+
+/// Delivers notifications to every channel whose minimum severity they reach
+#[derive(Default)]
+pub struct Notifier {
+    channels: Vec<(Severity, Arc<dyn NotificationChannel>)>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The channels `config` enables: Slack with a webhook URL, email with an SMTP host and recipients
+    pub fn from_config(config: &NotificationsConfig, slack_webhook_url: Option<String>, smtp_password: Option<String>) -> Result<Self> {
+        let mut notifier = Self::new();
+        if let Some(url) = slack_webhook_url {
+            notifier = notifier.with_channel(Arc::new(SlackChannel::new(url)), config.slack.min_severity);
+        }
+        if config.email.smtp_host.is_some() && !config.email.to.is_empty() {
+            let email = EmailChannel::new(&config.email, smtp_password)?;
+            notifier = notifier.with_channel(Arc::new(email), config.email.min_severity);
+        }
+        Ok(notifier)
+    }
+
+    pub fn with_channel(mut self, channel: Arc<dyn NotificationChannel>, min_severity: Severity) -> Self {
+        self.channels.push((min_severity, channel));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    // A channel that can't be reached or hangs only logs a warning, like a failed webhook
+    pub async fn send(&self, notification: &Notification) {
+        for (min_severity, channel) in &self.channels {
+            if notification.severity < *min_severity {
+                continue;
+            }
+            match tokio::time::timeout(DELIVERY_TIMEOUT, channel.send(notification)).await {
+                Ok(Ok(())) => info!("Sent {:?} notification to {}", notification.severity, channel.name()),
+                Ok(Err(e)) => warn!("Notification to {} failed: {}", channel.name(), e),
+                Err(_) => warn!("Notification to {} timed out after {}s", channel.name(), DELIVERY_TIMEOUT.as_secs()),
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use alloy_primitives::{Address, U256};
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::types::{AgentPlan, AgentStep, Postcondition, UserPrompt};
+
+/// A payment requested with an EIP-681 `ethereum:` URI, e.g. from a wallet QR code:
+/// `ethereum:0x...@1?value=1e15` for ETH or `ethereum:<token>@1/transfer?address=0x...&uint256=5`
+/// for an ERC20 token. Amounts are in wei or raw token units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentRequest {
+    pub recipient: String,
+    pub amount: U256,
+    // The token contract of an ERC20 transfer, unset for ETH payments
+    pub token: Option<String>,
+    pub chain_id: Option<u64>,
+}
+
+// EIP-681 numbers are decimals with an optional exponent, e.g. 2.014e18, and have to be whole
+fn number(text: &str) -> Result<U256> {
+    let invalid = || anyhow::anyhow!("{} is not a whole amount", text);
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<usize>().map_err(|_| invalid())?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > exponent {
+        return Err(invalid());
+    }
+
+    let digits = format!("{}{}{}", whole, fraction, "0".repeat(exponent - fraction.len()));
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    U256::from_str_radix(&digits, 10).map_err(|_| invalid())
+}
+
+impl PaymentRequest {
+    /// Parse an `ethereum:` URI. Only ETH payments and ERC20 `transfer` calls are payments, other
+    /// function calls are rejected.
+    pub fn parse(uri: &str) -> Result<Self> {
+        let rest = uri
+            .get(..9)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("ethereum:"))
+            .map(|_| &uri[9..])
+            .ok_or_else(|| anyhow::anyhow!("{} is not an ethereum: URI", uri))?;
+        let rest = rest.strip_prefix("pay-").unwrap_or(rest);
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (target, function) = match path.split_once('/') {
+            Some((target, function)) => (target, Some(function)),
+            None => (path, None),
+        };
+        let (target, chain_id) = match target.split_once('@') {
+            Some((target, chain_id)) => {
+                let chain_id = chain_id.parse().map_err(|_| anyhow::anyhow!("{} is not a chain id", chain_id))?;
+                (target, Some(chain_id))
+            }
+            None => (target, None),
+        };
+        if target.is_empty() {
+            return Err(anyhow::anyhow!("The URI names no address"));
+        }
+
+        let parameter = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value)
+        };
+        let request = match function {
+            None => PaymentRequest {
+                recipient: target.to_string(),
+                amount: number(parameter("value").ok_or_else(|| anyhow::anyhow!("The URI asks for no value"))?)?,
+                token: None,
+                chain_id,
+            },
+            Some("transfer") => PaymentRequest {
+                recipient: parameter("address")
+                    .ok_or_else(|| anyhow::anyhow!("The transfer names no recipient address"))?
+                    .to_string(),
+                amount: number(parameter("uint256").ok_or_else(|| anyhow::anyhow!("The transfer names no uint256 amount"))?)?,
+                token: Some(target.to_string()),
+                chain_id,
+            },
+            Some(function) => {
+                return Err(anyhow::anyhow!("{} is a contract call, only ETH payments and ERC20 transfers are supported", function))
+            }
+        };
+        if request.amount.is_zero() {
+            return Err(anyhow::anyhow!("The URI asks for an amount of 0"));
+        }
+        Ok(request)
+    }
+
+    // e.g. "1000 wei to 0x7099..." or "5 raw units of token 0xA0b8... to 0x7099..."
+    pub fn describe(&self) -> String {
+        match &self.token {
+            Some(token) => format!("{} raw units of token {} to {}", self.amount, token, self.recipient),
+            None => format!("{} wei to {}", self.amount, self.recipient),
+        }
+    }
+
+    /// A one-step plan making the payment as requested, with a post-condition on the recipient's
+    /// balance when the recipient is an address rather than an ENS name
+    pub fn plan(&self, from: Option<&str>, max_steps: u32) -> AgentPlan {
+        let from = from.map(|from| format!(" from {}", from)).unwrap_or_default();
+        let agent_prompt = match &self.token {
+            Some(token) => format!(
+                "Transfer {} raw units of the ERC20 token {}{} to {}: call transfer({}, {}) on the token, encoded with encode_call",
+                self.amount, token, from, self.recipient, self.recipient, self.amount
+            ),
+            None => format!("Send {} wei{} to {}", self.amount, from, self.recipient),
+        };
+
+        let mut postconditions = vec![];
+        if self.recipient.parse::<Address>().is_ok() {
+            postconditions.push(match &self.token {
+                Some(token) => Postcondition::TokenBalanceIncreased {
+                    address: self.recipient.clone(),
+                    token: token.clone(),
+                    amount: self.amount.to_string(),
+                },
+                None => Postcondition::BalanceIncreased {
+                    address: self.recipient.clone(),
+                    amount: self.amount.to_string(),
+                },
+            });
+        }
+
+        AgentPlan {
+            id: Uuid::new_v4().to_string(),
+            prompt: UserPrompt::new(format!("Pay {}{}", self.describe(), from)),
+            steps: vec![AgentStep {
+                step_number: 1,
+                agent_name: "ethereum_agent".to_string(),
+                agent_prompt,
+                network: None,
+                chain: None,
+                postconditions,
+            }],
+            max_steps,
+            current_step: 0,
+            ens_resolutions: vec![],
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use alloy_primitives::U256;
+use tracing::{debug, warn};
+
+use crate::guard::{classify_step, ActionClass};
+use crate::mcp_client::FoundryMcpClient;
+use crate::types::{AgentPlan, AgentStep, PlanCostEstimate, StepCostEstimate};
+
+// Gas of typical transactions per kind of action, for steps whose transaction isn't known yet
+const ETH_TRANSFER_GAS: u64 = 21_000;
+const TOKEN_TRANSFER_GAS: u64 = 65_000;
+const SWAP_GAS: u64 = 180_000;
+const APPROVAL_GAS: u64 = 46_000;
+const DEPLOYMENT_GAS: u64 = 1_500_000;
+const CONTRACT_CALL_GAS: u64 = 100_000;
+
+fn typical_gas(class: ActionClass, prompt: &str) -> u64 {
+    let prompt = prompt.to_lowercase();
+    match class {
+        ActionClass::ReadOnly => 0,
+        ActionClass::Approval | ActionClass::UnlimitedApproval => APPROVAL_GAS,
+        ActionClass::ContractDeployment => DEPLOYMENT_GAS,
+        ActionClass::SelfDestruct => CONTRACT_CALL_GAS,
+        ActionClass::ValueTransfer if prompt.contains("swap") => SWAP_GAS,
+        ActionClass::ValueTransfer if prompt.contains(" wei") && !prompt.contains("call function") => ETH_TRANSFER_GAS,
+        ActionClass::ValueTransfer if prompt.contains("send") || prompt.contains("transfer") => TOKEN_TRANSFER_GAS,
+        ActionClass::ValueTransfer => CONTRACT_CALL_GAS,
+    }
+}
+
+// Addresses in a step prompt, in order of appearance and without repeats
+fn addresses(prompt: &str) -> Vec<&str> {
+    let mut found: Vec<&str> = vec![];
+    let mut rest = prompt;
+    while let Some(start) = rest.find("0x") {
+        let candidate = &rest[start..];
+        let hex_len = candidate[2..].chars().take_while(|c| c.is_ascii_hexdigit()).count();
+        if hex_len == 40 && !found.iter().any(|address| address.eq_ignore_ascii_case(&candidate[..42])) {
+            found.push(&candidate[..42]);
+        }
+        rest = &candidate[2 + hex_len..];
+    }
+    found
+}
+
+// An ETH transfer the prompt spells out completely: a sender, a recipient and an exact wei
+// amount as annotated by the planner, e.g. "Send 1000 wei (= 1000 wei) from 0x.. to 0x.."
+fn known_transfer(step: &AgentStep) -> Option<(&str, &str, String)> {
+    let prompt = step.agent_prompt.as_str();
+    let [from, to] = addresses(prompt)[..] else {
+        return None;
+    };
+    let (before, _) = prompt.split_once(" wei")?;
+    let value = before.rsplit(|c: char| !c.is_ascii_digit()).next().filter(|value| !value.is_empty())?;
+    Some((from, to, value.to_string()))
+}
+
+async fn step_gas(step: &AgentStep, class: ActionClass, client: &FoundryMcpClient) -> StepCostEstimate {
+    let simulated = match (class, known_transfer(step)) {
+        (ActionClass::ValueTransfer, Some((from, to, value))) => match client.estimate_gas(from, to, &value, None).await {
+            Ok(gas) => Some(gas),
+            Err(e) => {
+                debug!("Could not simulate step {}: {}", step.step_number, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    StepCostEstimate {
+        step_number: step.step_number,
+        action: class.to_string(),
+        gas: simulated.unwrap_or_else(|| typical_gas(class, &step.agent_prompt)),
+        simulated: simulated.is_some(),
+    }
+}
+
+// Gas cost of the plan's state-changing steps, priced at the standard fee and the current ETH
+// price on the network the run starts on. `None` when the plan only reads.
+pub async fn estimate(plan: &AgentPlan, client: &FoundryMcpClient) -> Option<PlanCostEstimate> {
+    let mut steps = vec![];
+    for step in plan.steps.iter().take(plan.max_steps as usize) {
+        let class = classify_step(step);
+        if class != ActionClass::ReadOnly {
+            steps.push(step_gas(step, class, client).await);
+        }
+    }
+    if steps.is_empty() {
+        return None;
+    }
+
+    let gas_price = match client.gas_oracle(None).await {
+        Ok(oracle) => oracle
+            .pointer("/tiers/standard/max_fee_per_gas")
+            .and_then(|fee| fee.as_str())
+            .and_then(|fee| fee.parse::<U256>().ok()),
+        Err(e) => {
+            warn!("Could not read gas prices for the plan estimate: {}", e);
+            None
+        }
+    };
+    let eth_usd = match client.get_price("ETH", None).await {
+        Ok(price) => price.get("price_usd").and_then(|price| price.as_str()).and_then(|price| price.parse::<f64>().ok()),
+        Err(e) => {
+            warn!("Could not read the ETH price for the plan estimate: {}", e);
+            None
+        }
+    };
+
+    let total_gas: u64 = steps.iter().map(|step| step.gas).sum();
+    let total_wei = gas_price.map(|gas_price| gas_price * U256::from(total_gas));
+    let total_usd = match (total_wei, eth_usd) {
+        (Some(total_wei), Some(eth_usd)) => total_wei.to_string().parse::<f64>().ok().map(|wei| wei / 1e18 * eth_usd),
+        _ => None,
+    };
+
+    Some(PlanCostEstimate {
+        steps,
+        gas_price_wei: gas_price.map(|gas_price| gas_price.to_string()),
+        total_wei: total_wei.map(|total_wei| total_wei.to_string()),
+        total_usd,
+    })
+}
@@ -0,0 +1,71 @@
+use alloy_primitives::Address;
+
+use crate::amounts::invalid_amounts;
+use crate::postconditions;
+use crate::types::AgentPlanResponse;
+
+// Sub-agents a step can be routed to, `ask_user` puts the step's prompt to the user instead
+const AGENT_NAMES: &[&str] = &["ethereum_agent", "search_agent", "analysis_agent", "defi_agent", "ask_user"];
+
+// Hex runs of exactly 40 digits after "0x", longer runs are hashes or calldata
+fn find_addresses(text: &str) -> Vec<&str> {
+    let mut addresses = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("0x") {
+        let digits = rest[start + 2..].chars().take_while(|c| c.is_ascii_hexdigit()).count();
+        if digits == 40 {
+            addresses.push(&rest[start..start + 42]);
+        }
+        rest = &rest[start + 2 + digits..];
+    }
+    addresses
+}
+
+// Mixed-case addresses carry an EIP-55 checksum, a wrong one means a mistyped address
+fn has_valid_checksum(address: &str) -> bool {
+    let hex = &address[2..];
+    let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    !mixed_case || Address::parse_checksummed(address, None).is_ok()
+}
+
+// Deterministic checks of a parsed plan, run before anything executes. Returns the first
+// violation, which becomes the replan reason.
+pub fn validate(plan: &AgentPlanResponse) -> Result<(), String> {
+    if plan.steps.is_empty() {
+        return Err("Plan has no steps".to_string());
+    }
+    if plan.number_of_steps as usize != plan.steps.len() {
+        return Err(format!(
+            "number_of_steps is {} but the plan has {} steps",
+            plan.number_of_steps,
+            plan.steps.len()
+        ));
+    }
+
+    // Partial replans continue the numbering of the completed steps, so only gaps are wrong
+    let first = plan.steps[0].step_number;
+    for (index, step) in plan.steps.iter().enumerate() {
+        let expected = first + index as u32;
+        if step.step_number != expected {
+            return Err(format!("Step numbers are not contiguous: expected step {}, got {}", expected, step.step_number));
+        }
+    }
+
+    for step in &plan.steps {
+        if !AGENT_NAMES.contains(&step.agent_name.as_str()) {
+            return Err(format!("Unknown agent name: {} in step {}", step.agent_name, step.step_number));
+        }
+        if let Some(address) = find_addresses(&step.agent_prompt).into_iter().find(|address| !has_valid_checksum(address)) {
+            return Err(format!("Step {} has an address with an invalid checksum: {}", step.step_number, address));
+        }
+        if let Some(amount) = invalid_amounts(&step.agent_prompt).first() {
+            return Err(format!("Step {} has an amount that is not a valid number: {}", step.step_number, amount));
+        }
+        for condition in &step.postconditions {
+            postconditions::validate(condition)
+                .map_err(|e| format!("Step {} has an invalid post-condition {}: {}", step.step_number, postconditions::describe(condition), e))?;
+        }
+    }
+
+    Ok(())
+}
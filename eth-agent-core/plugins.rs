@@ -0,0 +1,298 @@
+//! Third-party tool packs, e.g. ENS management or Snapshot voting, added to the sub-agents
+//! without forking the crate. A pack is either an MCP server declared in `[[plugins]]` or a
+//! native [`ToolPack`] registered with [`EthAgent::with_tool_pack`](crate::EthAgent::with_tool_pack).
+//!
+//! The model sees a pack's tools as `<pack>__<tool>`, so packs can't shadow the built-in tools or
+//! each other. Tools that don't declare themselves read-only only run once the guard's `plugins`
+//! policy allows the call.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rig::{completion::ToolDefinition, tool::Tool};
+use rmcp::model::CallToolRequestParam;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::PluginConfig;
+use crate::guard::ActionGuard;
+use crate::journal::RunJournal;
+use crate::mcp_client::{connect_http, spawn_server, McpService};
+use crate::tool_results::tool_payload;
+use crate::tools::ToolError;
+use crate::types::{RunRecord, ToolCallRecord, UserPrompt};
+
+// Between a pack's name and its tool's in the name the model sees
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+// Sub-agents a pack can add tools to
+pub const PLUGIN_AGENTS: &[&str] = &["ethereum_agent", "search_agent", "analysis_agent", "defi_agent"];
+
+// Longest tool name the model providers accept
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// A tool of a pack, named as the pack knows it
+#[derive(Debug, Clone)]
+pub struct PackTool {
+    pub name: String,
+    pub description: String,
+    // JSON schema of the arguments
+    pub parameters: Value,
+    // Only read-only tools run without asking the guard
+    pub read_only: bool,
+}
+
+/// A set of tools from outside the crate. The hooks run around every run of the agent: tools are
+/// listed after `run_started` succeeded, and `run_finished` runs whatever the run's outcome.
+#[async_trait]
+pub trait ToolPack: Send + Sync {
+    /// Namespace of the pack's tools: lowercase letters, digits and `-`
+    fn name(&self) -> &str;
+
+    /// Sub-agents given the pack's tools, out of [`PLUGIN_AGENTS`]
+    fn agents(&self) -> Vec<String> {
+        vec!["ethereum_agent".to_string()]
+    }
+
+    /// When to use the pack's tools, told to the planner and the sub-agents
+    fn instructions(&self) -> Option<String> {
+        None
+    }
+
+    /// Prepare for a run, e.g. connect to a server. A pack failing here sits the run out.
+    async fn run_started(&self, _prompt: &UserPrompt) -> Result<()> {
+        Ok(())
+    }
+
+    async fn tools(&self) -> Result<Vec<PackTool>>;
+
+    /// Call one of the pack's tools by its own (not namespaced) name
+    async fn call(&self, tool: &str, arguments: Value) -> Result<Value>;
+
+    /// Clean up after a run, e.g. disconnect
+    async fn run_finished(&self, _run: &RunRecord) {}
+}
+
+// Pack names become part of tool names, without `_` so the separator stays unambiguous
+pub fn check_pack_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(anyhow::anyhow!("Invalid plugin name {:?}, use lowercase letters, digits and -", name));
+    }
+    Ok(())
+}
+
+pub fn namespaced(pack: &str, tool: &str) -> String {
+    format!("{}{}{}", pack, NAMESPACE_SEPARATOR, tool)
+}
+
+// Tool pack served by an MCP server from `[[plugins]]`, connected for the duration of each run
+pub struct McpToolPack {
+    config: PluginConfig,
+    timeout: Duration,
+    service: Mutex<Option<McpService>>,
+}
+
+impl McpToolPack {
+    pub fn new(config: PluginConfig, timeout: Duration) -> Result<Self> {
+        check_pack_name(&config.name)?;
+        if config.command.is_some() == config.url.is_some() {
+            return Err(anyhow::anyhow!("Plugin {} needs either a command or a url", config.name));
+        }
+        Ok(Self {
+            config,
+            timeout,
+            service: Mutex::new(None),
+        })
+    }
+
+    async fn connect(&self) -> Result<McpService> {
+        match (&self.config.command, &self.config.url) {
+            (Some(command), _) => {
+                let mut command = Command::new(command);
+                command.args(&self.config.args);
+                info!("Starting plugin {} as child process: {:?}", self.config.name, command.as_std());
+                spawn_server(command).await
+            }
+            (None, Some(url)) => {
+                info!("Connecting to plugin {} at {}", self.config.name, url);
+                connect_http(url).await
+            }
+            (None, None) => Err(anyhow::anyhow!("Plugin {} needs either a command or a url", self.config.name)),
+        }
+    }
+}
+
+#[async_trait]
+impl ToolPack for McpToolPack {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn agents(&self) -> Vec<String> {
+        self.config.agents.clone()
+    }
+
+    fn instructions(&self) -> Option<String> {
+        self.config.instructions.clone()
+    }
+
+    async fn run_started(&self, _prompt: &UserPrompt) -> Result<()> {
+        let service = self.connect().await?;
+        *self.service.lock().await = Some(service);
+        Ok(())
+    }
+
+    async fn tools(&self) -> Result<Vec<PackTool>> {
+        let peer = match self.service.lock().await.as_ref() {
+            Some(service) => service.peer().clone(),
+            None => return Err(anyhow::anyhow!("Plugin {} is not connected", self.config.name)),
+        };
+        let tools = peer.list_all_tools().await?;
+        Ok(tools
+            .into_iter()
+            .filter(|tool| self.config.tools.as_ref().is_none_or(|allowed| allowed.iter().any(|name| name == tool.name.as_ref())))
+            .map(|tool| PackTool {
+                name: tool.name.to_string(),
+                description: tool.description.as_deref().unwrap_or_default().to_string(),
+                parameters: Value::Object(tool.input_schema.as_ref().clone()),
+                // Unannotated tools may change state, a third-party server has to say otherwise
+                read_only: tool.annotations.as_ref().and_then(|annotations| annotations.read_only_hint) == Some(true),
+            })
+            .collect())
+    }
+
+    async fn call(&self, tool: &str, arguments: Value) -> Result<Value> {
+        let peer = match self.service.lock().await.as_ref() {
+            Some(service) => service.peer().clone(),
+            None => return Err(anyhow::anyhow!("Plugin {} is not connected", self.config.name)),
+        };
+        let request = peer.call_tool(CallToolRequestParam {
+            name: tool.to_string().into(),
+            arguments: arguments.as_object().cloned(),
+        });
+        let result = tokio::time::timeout(self.timeout, request)
+            .await
+            .map_err(|_| anyhow::anyhow!("Tool call {} timed out after {}s", tool, self.timeout.as_secs()))??;
+
+        // Third-party tools may answer in plain text rather than JSON
+        let text: Vec<&str> = result.content.iter().filter_map(|content| content.as_text().map(|text| text.text.as_str())).collect();
+        if result.is_error == Some(true) {
+            return Err(anyhow::anyhow!("{} failed: {}", tool, text.join(" ")));
+        }
+        Ok(tool_payload(&result).unwrap_or_else(|| Value::String(text.join("\n"))))
+    }
+
+    async fn run_finished(&self, _run: &RunRecord) {
+        if let Some(service) = self.service.lock().await.take() {
+            if let Err(e) = service.cancel().await {
+                warn!("Could not disconnect plugin {}: {}", self.config.name, e);
+            }
+        }
+    }
+}
+
+// A pack's tool as the sub-agents see it, under its namespaced name. Calls are recorded in the
+// run journal like foundry-mcp's, and state-changing ones go through the guard first.
+#[derive(Clone)]
+pub(crate) struct PluginTool {
+    pack: Arc<dyn ToolPack>,
+    tool: PackTool,
+    definition: ToolDefinition,
+    guard: ActionGuard,
+    journal: RunJournal,
+}
+
+impl PluginTool {
+    pub(crate) fn new(pack: Arc<dyn ToolPack>, tool: PackTool, guard: ActionGuard, journal: RunJournal) -> Self {
+        let definition = ToolDefinition {
+            name: namespaced(pack.name(), &tool.name),
+            description: format!("[{} plugin] {}", pack.name(), tool.description),
+            parameters: tool.parameters.clone(),
+        };
+        Self {
+            pack,
+            tool,
+            definition,
+            guard,
+            journal,
+        }
+    }
+}
+
+impl Tool for PluginTool {
+    // Only a placeholder, every plugin tool goes by its namespaced name
+    const NAME: &'static str = "plugin_tool";
+    type Error = ToolError;
+    type Args = Value;
+    type Output = Value;
+
+    fn name(&self) -> String {
+        self.definition.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        if !self.tool.read_only {
+            let description = format!("Plugin call {} with {}", self.definition.name, args);
+            if let Err(reason) = self.guard.check_plugin_call(&description).await {
+                return Ok(json!({ "success": false, "blocked": true, "error": reason }));
+            }
+        }
+
+        let result = self.pack.call(&self.tool.name, args.clone()).await;
+        self.journal.record_tool_call(ToolCallRecord {
+            tool: self.definition.name.clone(),
+            arguments: args,
+            // Wrapped like an MCP result, so the record's payload reads the same
+            result: result.as_ref().ok().map(|value| json!({ "content": [{ "type": "text", "text": value.to_string() }] })),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            timestamp: chrono::Utc::now(),
+        });
+        Ok(result?)
+    }
+}
+
+// The tools of every started pack, wrapped per sub-agent. Tools whose namespaced name is too long
+// for the model providers are left out.
+pub(crate) async fn plugin_tools(packs: &[Arc<dyn ToolPack>], guard: &ActionGuard, journal: &RunJournal) -> Vec<(String, PluginTool)> {
+    let mut tools = vec![];
+    for pack in packs {
+        let listed = match pack.tools().await {
+            Ok(listed) => listed,
+            Err(e) => {
+                warn!("Could not list the tools of plugin {}: {}", pack.name(), e);
+                continue;
+            }
+        };
+        for tool in listed {
+            if namespaced(pack.name(), &tool.name).len() > MAX_TOOL_NAME_LEN {
+                warn!("Plugin tool {} of {} has too long a name, skipped", tool.name, pack.name());
+                continue;
+            }
+            let tool = PluginTool::new(pack.clone(), tool, guard.clone(), journal.clone());
+            for agent in pack.agents() {
+                tools.push((agent, tool.clone()));
+            }
+        }
+    }
+    tools
+}
+
+// What the packs adding tools to `agent` say about using them, appended to its preamble
+pub(crate) fn plugin_preamble(packs: &[Arc<dyn ToolPack>], agent: &str) -> String {
+    packs
+        .iter()
+        .filter(|pack| pack.agents().iter().any(|name| name == agent))
+        .filter_map(|pack| {
+            let instructions = pack.instructions()?;
+            Some(format!("\n        Tools named {}__* come from the {} plugin: {}\n", pack.name(), pack.name(), instructions))
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// An asset held at a sampled block, its balance in whole units (e.g. "1.5" ETH)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Holding {
+    pub asset: String,
+    // Token contract, `None` for ETH
+    #[serde(default)]
+    pub token: Option<String>,
+    pub balance: String,
+    // Chainlink price at the block, `None` where no feed is known
+    #[serde(default)]
+    pub price_usd: Option<String>,
+}
+
+impl Holding {
+    pub fn amount(&self) -> f64 {
+        self.balance.parse().unwrap_or_default()
+    }
+
+    pub fn price(&self) -> Option<f64> {
+        self.price_usd.as_deref()?.parse().ok()
+    }
+
+    pub fn value_usd(&self) -> Option<f64> {
+        Some(self.amount() * self.price()?)
+    }
+}
+
+/// An account's holdings at one block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioSample {
+    pub block: u64,
+    pub timestamp: u64,
+    pub holdings: Vec<Holding>,
+}
+
+impl PortfolioSample {
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.timestamp as i64, 0).unwrap_or_default()
+    }
+
+    /// USD value of the priced holdings, unpriced ones count for nothing
+    pub fn value_usd(&self) -> f64 {
+        self.holdings.iter().filter_map(Holding::value_usd).sum()
+    }
+
+    fn holding(&self, asset: &str) -> Option<&Holding> {
+        self.holdings.iter().find(|holding| holding.asset == asset)
+    }
+}
+
+/// How one asset's position changed between the first and the last sample. The change in value
+/// splits into the price effect (the starting balance repriced at the end price) and the rest,
+/// which came from the balance moving: transfers in and out, swaps, fees.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetPnl {
+    pub asset: String,
+    pub start_balance: f64,
+    pub end_balance: f64,
+    pub start_value_usd: Option<f64>,
+    pub end_value_usd: Option<f64>,
+    pub change_usd: Option<f64>,
+    pub price_effect_usd: Option<f64>,
+}
+
+/// Balances of an account sampled over a period by the `portfolio_history` tool, oldest first.
+/// See [`EthAgent::portfolio_history`](crate::EthAgent::portfolio_history).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioHistory {
+    pub address: String,
+    pub chain_id: u64,
+    pub days: u32,
+    pub samples: Vec<PortfolioSample>,
+}
+
+impl PortfolioHistory {
+    /// Assets sampled, in the order of the holdings
+    pub fn assets(&self) -> Vec<&str> {
+        let holdings = self.samples.last().map(|sample| sample.holdings.as_slice()).unwrap_or_default();
+        holdings.iter().map(|holding| holding.asset.as_str()).collect()
+    }
+
+    /// Change of every asset over the period, in the order of the last sample
+    pub fn pnl(&self) -> Vec<AssetPnl> {
+        let (Some(first), Some(last)) = (self.samples.first(), self.samples.last()) else {
+            return vec![];
+        };
+        last.holdings
+            .iter()
+            .map(|end| {
+                let start = first.holding(&end.asset);
+                let start_balance = start.map(Holding::amount).unwrap_or_default();
+                let start_value_usd = start.and_then(Holding::value_usd);
+                let end_value_usd = end.value_usd();
+                let price_change = end.price().zip(start.and_then(Holding::price)).map(|(end, start)| end - start);
+                AssetPnl {
+                    asset: end.asset.clone(),
+                    start_balance,
+                    end_balance: end.amount(),
+                    start_value_usd,
+                    end_value_usd,
+                    change_usd: end_value_usd.zip(start_value_usd).map(|(end, start)| end - start),
+                    price_effect_usd: price_change.map(|change| start_balance * change),
+                }
+            })
+            .collect()
+    }
+
+    /// Change in USD value of the priced holdings between the first and the last sample
+    pub fn change_usd(&self) -> f64 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => last.value_usd() - first.value_usd(),
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use alloy_primitives::{Address, U256};
+
+use crate::mcp_client::FoundryMcpClient;
+use crate::types::Postcondition;
+
+fn parse_amount(amount: &str) -> Result<U256, String> {
+    amount.parse().map_err(|_| format!("{} is not an amount in the smallest unit", amount))
+}
+
+fn parse_address(address: &str) -> Result<(), String> {
+    address.parse::<Address>().map(|_| ()).map_err(|_| format!("{} is not an address", address))
+}
+
+// Short form for failure reasons and logs, e.g. "balance(0x7099...) increased by 1000 wei"
+pub fn describe(condition: &Postcondition) -> String {
+    match condition {
+        Postcondition::BalanceIncreased { address, amount } => format!("balance({}) increased by {} wei", address, amount),
+        Postcondition::BalanceDecreased { address, amount } => format!("balance({}) decreased by {} wei", address, amount),
+        Postcondition::TokenBalanceIncreased { address, token, amount } => {
+            format!("balance of token {} for {} increased by {} raw units", token, address, amount)
+        }
+        Postcondition::HasCode { address } => format!("code({}) != 0x", address),
+    }
+}
+
+// Whether a planned post-condition can be checked at all, before anything runs
+pub fn validate(condition: &Postcondition) -> Result<(), String> {
+    match condition {
+        Postcondition::BalanceIncreased { address, amount } | Postcondition::BalanceDecreased { address, amount } => {
+            parse_address(address)?;
+            parse_amount(amount).map(|_| ())
+        }
+        Postcondition::TokenBalanceIncreased { address, token, amount } => {
+            parse_address(address)?;
+            parse_address(token)?;
+            parse_amount(amount).map(|_| ())
+        }
+        Postcondition::HasCode { address } => parse_address(address),
+    }
+}
+
+// The balance a condition is about, `None` for conditions on something else
+async fn measure(client: &FoundryMcpClient, condition: &Postcondition) -> Result<Option<U256>, String> {
+    let balance = match condition {
+        Postcondition::BalanceIncreased { address, .. } | Postcondition::BalanceDecreased { address, .. } => {
+            client.balance(address, true).await.map_err(|e| e.to_string())?.balance
+        }
+        Postcondition::TokenBalanceIncreased { address, token, .. } => {
+            client.erc20_balance(address, token, true).await.map_err(|e| e.to_string())?.balance
+        }
+        Postcondition::HasCode { .. } => return Ok(None),
+    };
+    parse_amount(&balance).map(Some)
+}
+
+// Balances right before a step, which its post-conditions measure changes from. A balance that
+// can't be read is reported when the condition is checked.
+pub async fn baseline(client: &FoundryMcpClient, conditions: &[Postcondition]) -> Vec<Option<U256>> {
+    let mut balances = vec![];
+    for condition in conditions {
+        balances.push(measure(client, condition).await.ok().flatten());
+    }
+    balances
+}
+
+// Every post-condition that doesn't hold after the step, with what was found instead
+pub async fn check(client: &FoundryMcpClient, conditions: &[Postcondition], before: &[Option<U256>]) -> Vec<String> {
+    let mut failures = vec![];
+    for (condition, before) in conditions.iter().zip(before) {
+        let description = describe(condition);
+        if let Postcondition::HasCode { address } = condition {
+            match client.get_contract_code(address, true).await {
+                Ok(code) if code.is_contract() => {}
+                Ok(_) => failures.push(format!("{}: no code at {}", description, address)),
+                Err(e) => failures.push(format!("{}: could not read the code: {}", description, e)),
+            }
+            continue;
+        }
+
+        let Some(before) = before else {
+            failures.push(format!("{}: the balance before the step could not be read", description));
+            continue;
+        };
+        let after = match measure(client, condition).await {
+            Ok(Some(after)) => after,
+            Ok(None) => continue,
+            Err(e) => {
+                failures.push(format!("{}: could not read the balance: {}", description, e));
+                continue;
+            }
+        };
+        let (expected, change) = match condition {
+            Postcondition::BalanceDecreased { amount, .. } => (amount, before.saturating_sub(after)),
+            Postcondition::BalanceIncreased { amount, .. } | Postcondition::TokenBalanceIncreased { amount, .. } => {
+                (amount, after.saturating_sub(*before))
+            }
+            Postcondition::HasCode { .. } => continue,
+        };
+        let expected = parse_amount(expected).unwrap_or_default();
+        if change < expected {
+            failures.push(format!("{}: went from {} to {}, a change of {}", description, before, after, change));
+        }
+    }
+    failures
+}
@@ -0,0 +1,156 @@
+// Masking of secrets before text is logged, stored in the run journal or sent to an LLM
+
+use std::io;
+use std::sync::{LazyLock, Mutex};
+
+// BIP-39 phrases are 12 to 24 words of the BIP-39 wordlist
+const MIN_MNEMONIC_WORDS: usize = 12;
+// How far back to look for "key", "pk" etc. in front of a 64 hex digit key
+const KEY_CONTEXT_CHARS: usize = 40;
+// Words naming a private key, also as JSON field names
+const KEY_WORDS: &[&str] = &["private_key", "private-key", "privatekey", "pk", "secret", "wallet", "mnemonic", "seed"];
+
+// The English BIP-39 wordlist, sorted
+static BIP39_WORDS: LazyLock<Vec<&str>> = LazyLock::new(|| include_str!("bip39_english.txt").lines().collect());
+
+static SECRETS: LazyLock<Mutex<Vec<String>>> = LazyLock::new(Mutex::default);
+
+/// Mask `secret` wherever it shows up from now on, e.g. an API key loaded at startup
+pub fn register_secret(secret: &str) {
+    // Very short values would mask ordinary words
+    if secret.len() < 8 {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Replace registered secrets, private keys, API keys and mnemonic phrases in `text`.
+///
+/// 64 hex digit values, with or without `0x`, are treated as private keys when the words before
+/// them name a key, pk, wallet, seed or secret; other ones are kept since they are almost always
+/// transaction hashes or storage slots. A `"key"` JSON field names a storage or mapping key and
+/// doesn't count. Mnemonics are runs of words that are all in the BIP-39 wordlist.
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        redacted = redacted.replace(secret.as_str(), "[REDACTED secret]");
+    }
+
+    redact_mnemonics(&redact_keys(&redacted))
+}
+
+// Byte ranges of words, where hyphens and underscores are part of a word
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        let in_word = c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        match (in_word, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len()));
+    }
+    words
+}
+
+// Whether `context` (lowercase) names a private key. Words are whole, so "mapping_key" isn't "key".
+fn names_key(context: &str) -> bool {
+    words(context).into_iter().any(|(start, end)| {
+        let word = &context[start..end];
+        KEY_WORDS.contains(&word) || (word == "key" && !context[end..].starts_with('"'))
+    })
+}
+
+fn redact_keys(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, end) in words(text) {
+        redacted.push_str(&text[last..start]);
+        last = end;
+
+        let word = &text[start..end];
+        let hex = word.strip_prefix("0x").unwrap_or(word);
+        let is_hex_key = hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit());
+        // The original text, so an earlier "[REDACTED private key]" doesn't name this one a key
+        let context: Vec<char> = text[..start].chars().rev().take(KEY_CONTEXT_CHARS).collect();
+        let context: String = context.into_iter().rev().collect::<String>().to_lowercase();
+
+        if is_hex_key && names_key(&context) {
+            redacted.push_str("[REDACTED private key]");
+        } else if word.starts_with("sk-") && word.len() > 20 {
+            redacted.push_str("[REDACTED API key]");
+        } else {
+            redacted.push_str(word);
+        }
+    }
+    redacted.push_str(&text[last..]);
+
+    redacted
+}
+
+fn redact_mnemonics(text: &str) -> String {
+    let is_mnemonic_word = |word: &str| BIP39_WORDS.binary_search(&word).is_ok();
+
+    // Runs of wordlist words separated only by whitespace
+    let mut runs: Vec<Vec<(usize, usize)>> = vec![];
+    let mut run: Vec<(usize, usize)> = vec![];
+    for (start, end) in words(text) {
+        let continues = run.last().is_some_and(|&(_, previous_end)| text[previous_end..start].trim().is_empty());
+        if !continues {
+            runs.push(std::mem::take(&mut run));
+        }
+        if is_mnemonic_word(&text[start..end]) {
+            run.push((start, end));
+        } else {
+            runs.push(std::mem::take(&mut run));
+        }
+    }
+    runs.push(run);
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+    for run in runs.into_iter().filter(|run| run.len() >= MIN_MNEMONIC_WORDS) {
+        let (start, end) = (run[0].0, run[run.len() - 1].1);
+        redacted.push_str(&text[last..start]);
+        redacted.push_str("[REDACTED mnemonic]");
+        last = end;
+    }
+    redacted.push_str(&text[last..]);
+
+    redacted
+}
+
+/// Writer redacting everything written through it, e.g. as a `tracing_subscriber` writer:
+/// `.with_writer(|| RedactingWriter::new(std::io::stderr()))`
+pub struct RedactingWriter<W: io::Write> {
+    inner: W,
+}
+
+impl<W: io::Write> RedactingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    // Log lines are written in one piece, so secrets are never split across calls
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,144 @@
+use serde_json::Value;
+
+use crate::types::RunRecord;
+
+// Phrases pointing back at what an earlier run dealt with
+const RECIPIENT_PHRASES: &[&str] = &["the same address", "that address", "the same recipient", "him", "her", "them"];
+const AMOUNT_PHRASES: &[&str] = &["the same amount", "that amount", "the same value"];
+const CONTRACT_PHRASES: &[&str] = &["the same contract", "that contract", "this contract"];
+const TRANSACTION_PHRASES: &[&str] = &[
+    "the same transaction",
+    "that transaction",
+    "the last transaction",
+    "that tx",
+    "the last tx",
+];
+
+fn text(value: &Value, name: &str) -> Option<String> {
+    value.get(name)?.as_str().map(|value| value.to_string())
+}
+
+// Wei amounts come back from tools as strings or numbers
+fn wei(value: &Value) -> Option<String> {
+    match value.get("value")? {
+        Value::String(wei) => Some(wei.clone()),
+        Value::Number(wei) => Some(wei.to_string()),
+        _ => None,
+    }
+    .filter(|wei| wei != "0")
+}
+
+/// A phrase of the prompt pinned to what it referred to in an earlier run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedReference {
+    pub phrase: String,
+    pub value: String,
+}
+
+/// The addresses, amounts, transactions and contracts the latest runs dealt with, so follow-up
+/// prompts like "send him the same amount" don't depend on the model remembering them
+#[derive(Debug, Clone, Default)]
+pub struct RecentEntities {
+    pub recipient: Option<String>,
+    pub amount: Option<String>,
+    pub contract: Option<String>,
+    pub transaction: Option<String>,
+}
+
+impl RecentEntities {
+    // Remember what a finished run's successful tool calls touched, later calls win
+    pub fn record(&mut self, run: &RunRecord) {
+        for call in run.steps.iter().flat_map(|step| step.tool_calls.iter()) {
+            let Some(payload) = call.payload() else {
+                continue;
+            };
+            if payload.get("success").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+            let arguments = &call.arguments;
+            match call.tool.as_str() {
+                "send_transaction" => {
+                    let calls_contract = text(arguments, "data").is_some_and(|data| data != "0x");
+                    match (text(&payload, "contract_address"), text(arguments, "to")) {
+                        (Some(deployed), _) => self.contract = Some(deployed),
+                        (None, Some(to)) if calls_contract => self.contract = Some(to),
+                        (None, Some(to)) => self.recipient = Some(to),
+                        (None, None) => {}
+                    }
+                    if let Some(value) = wei(arguments) {
+                        self.amount = Some(format!("{} wei", value));
+                    }
+                    if let Some(hash) = text(&payload, "transaction_hash") {
+                        self.transaction = Some(hash);
+                    }
+                }
+                "explain_transaction" => {
+                    if let Some(hash) = text(arguments, "hash") {
+                        self.transaction = Some(hash);
+                    }
+                }
+                "get_contract_code" | "infer_interface" | "verify_bytecode" | "detect_standards" | "encode_call" | "risk_score" => {
+                    if let Some(address) = text(arguments, "address") {
+                        self.contract = Some(address);
+                    }
+                }
+                "nft_metadata" => {
+                    if let Some(address) = text(arguments, "contract") {
+                        self.contract = Some(address);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Annotate every phrase referring to a remembered entity with its value, e.g. "Send him the same
+    // amount" -> "Send him (= 0x7099...79C8) the same amount (= 1000 wei)". Phrases without a
+    // remembered entity are left for the planner to ask about.
+    pub fn resolve(&self, text: &str) -> (String, Vec<ResolvedReference>) {
+        let groups = [
+            (RECIPIENT_PHRASES, &self.recipient),
+            (AMOUNT_PHRASES, &self.amount),
+            (CONTRACT_PHRASES, &self.contract),
+            (TRANSACTION_PHRASES, &self.transaction),
+        ];
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let mut output: Vec<String> = Vec::with_capacity(words.len());
+        let mut resolved = vec![];
+
+        let mut i = 0;
+        'words: while i < words.len() {
+            for (phrases, value) in &groups {
+                let Some(value) = value else {
+                    continue;
+                };
+                for phrase in phrases.iter() {
+                    let length = phrase.split_whitespace().count();
+                    let Some(candidate) = words.get(i..i + length) else {
+                        continue;
+                    };
+                    let last = candidate[length - 1];
+                    let trimmed = last.trim_end_matches(|c: char| matches!(c, '.' | ',' | '?' | '!' | ';' | ':'));
+                    let mut spoken = candidate[..length - 1].to_vec();
+                    spoken.push(trimmed);
+                    if !spoken.join(" ").eq_ignore_ascii_case(phrase) {
+                        continue;
+                    }
+
+                    let punctuation = &last[trimmed.len()..];
+                    output.push(format!("{} (= {}){}", spoken.join(" "), value, punctuation));
+                    resolved.push(ResolvedReference {
+                        phrase: phrase.to_string(),
+                        value: value.clone(),
+                    });
+                    i += length;
+                    continue 'words;
+                }
+            }
+            output.push(words[i].to_string());
+            i += 1;
+        }
+
+        (output.join(" "), resolved)
+    }
+}
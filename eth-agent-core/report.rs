@@ -0,0 +1,456 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::labels::AddressLabels;
+use crate::portfolio::PortfolioHistory;
+use crate::types::{Artifact, PlanCostEstimate, PlanStatus, RunRecord, StepRecord, StepStatus, ToolCallRecord};
+
+// Tool results can be large, reports only keep the start
+const MAX_RESULT_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    // HTML for .html/.htm paths, Markdown otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html") | Some("htm") => ReportFormat::Html,
+            _ => ReportFormat::Markdown,
+        }
+    }
+}
+
+/// Render a run as a shareable report: prompt, plan, tool calls per step, transactions with
+/// explorer links, evaluation scores and the final result. Addresses are followed by their
+/// label wherever `labels` has one.
+pub fn render_report(run: &RunRecord, explorer_url: &str, labels: &AddressLabels, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(run, explorer_url, labels),
+        ReportFormat::Html => render_html(run, explorer_url, labels),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_RESULT_CHARS {
+        return text.to_string();
+    }
+    format!("{}…", text.chars().take(MAX_RESULT_CHARS).collect::<String>())
+}
+
+fn transaction_link(explorer_url: &str, hash: &str) -> String {
+    format!("{}/tx/{}", explorer_url.trim_end_matches('/'), hash)
+}
+
+fn run_status(status: &PlanStatus) -> String {
+    match status {
+        PlanStatus::Failed(reason) => format!("Failed: {}", reason),
+        status => format!("{:?}", status),
+    }
+}
+
+fn step_status(status: &StepStatus) -> String {
+    match status {
+        StepStatus::Failed(reason) => format!("Failed: {}", reason),
+        status => format!("{:?}", status),
+    }
+}
+
+// How long a finished step took, e.g. "4.2s"
+fn step_duration(step: &StepRecord) -> Option<String> {
+    step.duration().map(|duration| format!("{:.1}s", duration.num_milliseconds() as f64 / 1000.0))
+}
+
+// Error, or the tool's JSON payload from the first text block of the MCP result
+fn tool_outcome(call: &ToolCallRecord) -> String {
+    if let Some(error) = &call.error {
+        return format!("error: {}", error);
+    }
+
+    let Some(result) = &call.result else {
+        return "no result".to_string();
+    };
+    let payload = result
+        .get("content")
+        .and_then(|content| content.as_array())
+        .and_then(|blocks| blocks.iter().find_map(|block| block.get("text")?.as_str()))
+        .map(|text| text.to_string())
+        .unwrap_or_else(|| result.to_string());
+
+    truncate(&payload)
+}
+
+fn cost_source(simulated: bool) -> &'static str {
+    if simulated { "simulated" } else { "typical" }
+}
+
+fn estimated_wei(estimate: &PlanCostEstimate) -> String {
+    match &estimate.total_wei {
+        Some(total_wei) => format!("{} wei ({})", total_wei, estimate.describe()),
+        None => format!("{} gas, gas price unknown", estimate.total_gas()),
+    }
+}
+
+// Gas fees of the transactions the run sent, as previewed right before sending
+fn spent_wei(run: &RunRecord) -> String {
+    match run.gas_spent_wei.as_deref() {
+        Some(gas_wei) => format!("{} wei", gas_wei),
+        None => "unknown".to_string(),
+    }
+}
+
+fn render_markdown(run: &RunRecord, explorer_url: &str, labels: &AddressLabels) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "# Run report `{}`\n", run.id);
+    let _ = writeln!(report, "- **Status:** {}", run_status(&run.status));
+    let _ = writeln!(report, "- **Started:** {}", run.started_at.to_rfc3339());
+    let _ = writeln!(report, "- **Last update:** {}\n", run.updated_at.to_rfc3339());
+
+    let _ = writeln!(report, "## Prompt\n");
+    let _ = writeln!(report, "> {}\n", labels.annotate_text(&run.prompt.natural_language));
+
+    let _ = writeln!(report, "## Plan\n");
+    match &run.plan {
+        Some(plan) => {
+            let _ = writeln!(report, "| # | Agent | Prompt |");
+            let _ = writeln!(report, "|---|-------|--------|");
+            for step in &plan.steps {
+                let _ = writeln!(report, "| {} | {} | {} |", step.step_number, step.agent_name, labels.annotate_text(&step.agent_prompt).replace('|', "\\|"));
+            }
+            for resolution in &plan.ens_resolutions {
+                let _ = writeln!(report, "\nENS: `{}` pinned to `{}`", resolution.name, resolution.address);
+            }
+            let _ = writeln!(report);
+        }
+        None => {
+            let _ = writeln!(report, "No plan was created.\n");
+        }
+    }
+
+    let _ = writeln!(report, "## Steps\n");
+    for step in &run.steps {
+        let _ = writeln!(report, "### Step {}: {} ({})\n", step.step_number, step.agent_name, step_status(&step.status));
+        let _ = writeln!(report, "**Prompt:** {}\n", labels.annotate_text(&step.agent_prompt));
+        if let Some(duration) = step_duration(step) {
+            let _ = writeln!(report, "**Duration:** {}\n", duration);
+        }
+
+        if !step.tool_calls.is_empty() {
+            let _ = writeln!(report, "**Tool calls:**\n");
+            for call in &step.tool_calls {
+                let _ = writeln!(report, "- `{}` `{}`", call.tool, labels.annotate_text(&call.arguments.to_string()));
+                let _ = writeln!(report, "  - {}", labels.annotate_text(&tool_outcome(call)));
+                if let Some(hash) = call.transaction_hash() {
+                    let _ = writeln!(report, "  - transaction [{}]({})", hash, transaction_link(explorer_url, &hash));
+                }
+            }
+            let _ = writeln!(report);
+        }
+
+        if let Some(output) = &step.output {
+            let _ = writeln!(report, "**Output:**\n\n{}\n", labels.annotate_text(output));
+        }
+
+        if let Some(evaluation) = &step.evaluation {
+            let _ = writeln!(report, "**Evaluation:** {}/100, {}\n", evaluation.score, evaluation.reasoning);
+        }
+    }
+
+    let _ = writeln!(report, "## Transactions\n");
+    let hashes = run.transaction_hashes();
+    if hashes.is_empty() {
+        let _ = writeln!(report, "No transactions were sent.");
+    }
+    for hash in hashes {
+        let _ = writeln!(report, "- [{}]({})", hash, transaction_link(explorer_url, &hash));
+    }
+    let _ = writeln!(report);
+
+    if let Some(estimate) = &run.cost_estimate {
+        let _ = writeln!(report, "## Gas cost\n");
+        let _ = writeln!(report, "| Step | Action | Estimated gas | Source |");
+        let _ = writeln!(report, "|------|--------|---------------|--------|");
+        for step in &estimate.steps {
+            let _ = writeln!(report, "| {} | {} | {} | {} |", step.step_number, step.action, step.gas, cost_source(step.simulated));
+        }
+        let _ = writeln!(report, "\n- **Estimated:** {}", estimated_wei(estimate));
+        let _ = writeln!(report, "- **Actual:** {}\n", spent_wei(run));
+    }
+
+    let _ = writeln!(report, "## Result\n");
+    let _ = writeln!(report, "{}", labels.annotate_text(run.result.as_deref().unwrap_or("No result.")));
+
+    report
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(run: &RunRecord, explorer_url: &str, labels: &AddressLabels) -> String {
+    let mut body = String::new();
+
+    let _ = writeln!(body, "<h1>Run report <code>{}</code></h1>", escape(&run.id));
+    let _ = writeln!(body, "<ul>");
+    let _ = writeln!(body, "<li><b>Status:</b> {}</li>", escape(&run_status(&run.status)));
+    let _ = writeln!(body, "<li><b>Started:</b> {}</li>", run.started_at.to_rfc3339());
+    let _ = writeln!(body, "<li><b>Last update:</b> {}</li>", run.updated_at.to_rfc3339());
+    let _ = writeln!(body, "</ul>");
+
+    let _ = writeln!(body, "<h2>Prompt</h2>\n<blockquote>{}</blockquote>", escape(&labels.annotate_text(&run.prompt.natural_language)));
+
+    let _ = writeln!(body, "<h2>Plan</h2>");
+    match &run.plan {
+        Some(plan) => {
+            let _ = writeln!(body, "<table>\n<tr><th>#</th><th>Agent</th><th>Prompt</th></tr>");
+            for step in &plan.steps {
+                let _ = writeln!(
+                    body,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    step.step_number,
+                    escape(&step.agent_name),
+                    escape(&labels.annotate_text(&step.agent_prompt))
+                );
+            }
+            let _ = writeln!(body, "</table>");
+            for resolution in &plan.ens_resolutions {
+                let _ = writeln!(body, "<p>ENS: <code>{}</code> pinned to <code>{}</code></p>", escape(&resolution.name), escape(&resolution.address));
+            }
+        }
+        None => {
+            let _ = writeln!(body, "<p>No plan was created.</p>");
+        }
+    }
+
+    let _ = writeln!(body, "<h2>Steps</h2>");
+    for step in &run.steps {
+        let _ = writeln!(
+            body,
+            "<h3>Step {}: {} ({})</h3>",
+            step.step_number,
+            escape(&step.agent_name),
+            escape(&step_status(&step.status))
+        );
+        let _ = writeln!(body, "<p><b>Prompt:</b> {}</p>", escape(&labels.annotate_text(&step.agent_prompt)));
+        if let Some(duration) = step_duration(step) {
+            let _ = writeln!(body, "<p><b>Duration:</b> {}</p>", duration);
+        }
+
+        if !step.tool_calls.is_empty() {
+            let _ = writeln!(body, "<p><b>Tool calls:</b></p>\n<ul>");
+            for call in &step.tool_calls {
+                let _ = write!(
+                    body,
+                    "<li><code>{}</code> <code>{}</code><br>{}",
+                    escape(&call.tool),
+                    escape(&labels.annotate_text(&call.arguments.to_string())),
+                    escape(&labels.annotate_text(&tool_outcome(call)))
+                );
+                if let Some(hash) = call.transaction_hash() {
+                    let _ = write!(body, "<br>transaction <a href=\"{}\">{}</a>", escape(&transaction_link(explorer_url, &hash)), escape(&hash));
+                }
+                let _ = writeln!(body, "</li>");
+            }
+            let _ = writeln!(body, "</ul>");
+        }
+
+        if let Some(output) = &step.output {
+            let _ = writeln!(body, "<p><b>Output:</b></p>\n<pre>{}</pre>", escape(&labels.annotate_text(output)));
+        }
+
+        if let Some(evaluation) = &step.evaluation {
+            let _ = writeln!(body, "<p><b>Evaluation:</b> {}/100, {}</p>", evaluation.score, escape(&evaluation.reasoning));
+        }
+    }
+
+    if let Some(estimate) = &run.cost_estimate {
+        let _ = writeln!(body, "<h2>Gas cost</h2>");
+        let _ = writeln!(body, "<table>\n<tr><th>Step</th><th>Action</th><th>Estimated gas</th><th>Source</th></tr>");
+        for step in &estimate.steps {
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                step.step_number,
+                escape(&step.action),
+                step.gas,
+                cost_source(step.simulated)
+            );
+        }
+        let _ = writeln!(body, "</table>");
+        let _ = writeln!(body, "<ul>\n<li><b>Estimated:</b> {}</li>", escape(&estimated_wei(estimate)));
+        let _ = writeln!(body, "<li><b>Actual:</b> {}</li>\n</ul>", escape(&spent_wei(run)));
+    }
+
+    let _ = writeln!(body, "<h2>Transactions</h2>");
+    let hashes = run.transaction_hashes();
+    if hashes.is_empty() {
+        let _ = writeln!(body, "<p>No transactions were sent.</p>");
+    } else {
+        let _ = writeln!(body, "<ul>");
+        for hash in hashes {
+            let _ = writeln!(body, "<li><a href=\"{}\">{}</a></li>", escape(&transaction_link(explorer_url, &hash)), escape(&hash));
+        }
+        let _ = writeln!(body, "</ul>");
+    }
+
+    let _ = writeln!(body, "<h2>Result</h2>\n<pre>{}</pre>", escape(&labels.annotate_text(run.result.as_deref().unwrap_or("No result."))));
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Run report {}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(&run.id),
+        body
+    )
+}
+
+fn usd(value: Option<f64>) -> String {
+    value.map(|value| format!("${:.2}", value)).unwrap_or_else(|| "unpriced".to_string())
+}
+
+fn signed_usd(value: Option<f64>) -> String {
+    match value {
+        Some(value) if value < 0.0 => format!("-${:.2}", -value),
+        Some(value) => format!("+${:.2}", value),
+        None => "-".to_string(),
+    }
+}
+
+/// Render an account's holdings over time and its P&L over the period: the value at every sample,
+/// then each asset's change split into the price effect and balance moves
+pub fn render_portfolio(history: &PortfolioHistory, labels: &AddressLabels, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_portfolio_markdown(history, labels),
+        ReportFormat::Html => render_portfolio_html(history, labels),
+    }
+}
+
+fn render_portfolio_markdown(history: &PortfolioHistory, labels: &AddressLabels) -> String {
+    let mut report = String::new();
+    let assets = history.assets();
+
+    let _ = writeln!(report, "# Portfolio of {}\n", labels.describe(&history.address));
+    let _ = writeln!(report, "- **Chain:** {}", history.chain_id);
+    let _ = writeln!(report, "- **Period:** last {} days, {} samples", history.days, history.samples.len());
+    let _ = writeln!(report, "- **Change:** {}\n", signed_usd(Some(history.change_usd())));
+
+    let _ = writeln!(report, "## Holdings over time\n");
+    let _ = writeln!(report, "| Date | Block | {} | Value |", assets.join(" | "));
+    let _ = writeln!(report, "|------|-------|{}-------|", "------|".repeat(assets.len()));
+    for sample in &history.samples {
+        let balances: Vec<&str> = sample.holdings.iter().map(|holding| holding.balance.as_str()).collect();
+        let _ = writeln!(
+            report,
+            "| {} | {} | {} | {} |",
+            sample.time().format("%Y-%m-%d %H:%M"),
+            sample.block,
+            balances.join(" | "),
+            usd(Some(sample.value_usd()))
+        );
+    }
+
+    let _ = writeln!(report, "\n## P&L\n");
+    let _ = writeln!(report, "| Asset | Start | End | Start value | End value | Change | Price effect |");
+    let _ = writeln!(report, "|-------|-------|-----|-------------|-----------|--------|--------------|");
+    for pnl in history.pnl() {
+        let _ = writeln!(
+            report,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            pnl.asset,
+            pnl.start_balance,
+            pnl.end_balance,
+            usd(pnl.start_value_usd),
+            usd(pnl.end_value_usd),
+            signed_usd(pnl.change_usd),
+            signed_usd(pnl.price_effect_usd)
+        );
+    }
+    let _ = writeln!(report, "\nThe price effect is the starting balance repriced at the end price, the rest of the change came from the balance moving.");
+
+    report
+}
+
+fn render_portfolio_html(history: &PortfolioHistory, labels: &AddressLabels) -> String {
+    let mut body = String::new();
+    let assets = history.assets();
+    let title = format!("Portfolio of {}", labels.describe(&history.address));
+
+    let _ = writeln!(body, "<h1>{}</h1>", escape(&title));
+    let _ = writeln!(body, "<ul>");
+    let _ = writeln!(body, "<li><b>Chain:</b> {}</li>", history.chain_id);
+    let _ = writeln!(body, "<li><b>Period:</b> last {} days, {} samples</li>", history.days, history.samples.len());
+    let _ = writeln!(body, "<li><b>Change:</b> {}</li>", escape(&signed_usd(Some(history.change_usd()))));
+    let _ = writeln!(body, "</ul>");
+
+    let _ = writeln!(body, "<h2>Holdings over time</h2>");
+    let headers: String = assets.iter().map(|asset| format!("<th>{}</th>", escape(asset))).collect();
+    let _ = writeln!(body, "<table>\n<tr><th>Date</th><th>Block</th>{}<th>Value</th></tr>", headers);
+    for sample in &history.samples {
+        let balances: String = sample.holdings.iter().map(|holding| format!("<td>{}</td>", escape(&holding.balance))).collect();
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td>{}<td>{}</td></tr>",
+            sample.time().format("%Y-%m-%d %H:%M"),
+            sample.block,
+            balances,
+            escape(&usd(Some(sample.value_usd())))
+        );
+    }
+    let _ = writeln!(body, "</table>");
+
+    let _ = writeln!(body, "<h2>P&amp;L</h2>");
+    let _ = writeln!(
+        body,
+        "<table>\n<tr><th>Asset</th><th>Start</th><th>End</th><th>Start value</th><th>End value</th><th>Change</th><th>Price effect</th></tr>"
+    );
+    for pnl in history.pnl() {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&pnl.asset),
+            pnl.start_balance,
+            pnl.end_balance,
+            escape(&usd(pnl.start_value_usd)),
+            escape(&usd(pnl.end_value_usd)),
+            escape(&signed_usd(pnl.change_usd)),
+            escape(&signed_usd(pnl.price_effect_usd))
+        );
+    }
+    let _ = writeln!(body, "</table>");
+    let _ = writeln!(body, "<p>The price effect is the starting balance repriced at the end price, the rest of the change came from the balance moving.</p>");
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(&title),
+        body
+    )
+}
+
+/// The unsigned transactions of a run as one JSON bundle for an external wallet, in the order
+/// they have to be signed. Each has its JSON-RPC transaction object and, for plain transfers, an
+/// EIP-681 URI.
+pub fn unsigned_bundle(run: &RunRecord) -> serde_json::Value {
+    let transactions: Vec<serde_json::Value> = crate::artifacts::extract(&run.steps)
+        .into_iter()
+        .filter_map(|artifact| match artifact {
+            Artifact::UnsignedTransaction { step_number, transaction, eip681 } => Some(json!({
+                "step_number": step_number,
+                "transaction": transaction,
+                "eip681": eip681,
+            })),
+            _ => None,
+        })
+        .collect();
+    json!({
+        "run_id": run.id,
+        "prompt": run.prompt.natural_language,
+        "transactions": transactions,
+    })
+}
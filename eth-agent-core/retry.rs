@@ -0,0 +1,129 @@
+use std::fmt::Display;
+use std::future::Future;
+use tracing::warn;
+
+use crate::config::RetryConfig;
+
+// Words that mark the number after them as an HTTP status, e.g. "status 429", "HTTP/1.1 503" or
+// reqwest's "server error (502 Bad Gateway)"
+const STATUS_WORDS: &[&str] = &["status", "http", "code", "error"];
+// How far before a status code its marking word may be
+const STATUS_CONTEXT_WORDS: usize = 2;
+
+// Rough classification of an error from a provider or MCP tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    RateLimited,
+    Timeout,
+    NonceRace,
+    ConnectionReset,
+    // The node already has the transaction, an earlier attempt got through
+    AlreadyKnown,
+    Permanent,
+}
+
+impl ErrorClass {
+    pub fn is_transient(&self) -> bool {
+        !matches!(self, ErrorClass::AlreadyKnown | ErrorClass::Permanent)
+    }
+
+    // Errors where the request was certainly not applied, so even a state-changing call is safe to
+    // repeat. Nonce races are left to the transaction queue, resending as is would fail the same way.
+    pub fn is_safe_to_resend(&self) -> bool {
+        matches!(self, ErrorClass::RateLimited)
+    }
+}
+
+// Whether `message` (lowercase) carries one of the HTTP status `codes`. A bare number doesn't
+// count, amounts, addresses and hashes in error messages are full of them.
+fn has_status(message: &str, codes: &[&str]) -> bool {
+    let words: Vec<&str> = message
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+        .map(|word| word.trim_matches('.'))
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.iter().enumerate().any(|(i, word)| {
+        let before = &words[i.saturating_sub(STATUS_CONTEXT_WORDS)..i];
+        codes.contains(word) && before.iter().any(|before| STATUS_WORDS.contains(before))
+    })
+}
+
+// Providers and RPC nodes don't share error types, so classify on the message
+pub fn classify(message: &str) -> ErrorClass {
+    let message = message.to_lowercase();
+    let contains_any = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+
+    if contains_any(&["rate limit", "rate_limit", "too many requests", "overloaded"]) || has_status(&message, &["429", "529"]) {
+        ErrorClass::RateLimited
+    } else if contains_any(&["already known", "already imported"]) {
+        ErrorClass::AlreadyKnown
+    } else if contains_any(&["nonce too low", "nonce too high", "replacement transaction underpriced"]) {
+        ErrorClass::NonceRace
+    } else if contains_any(&["timed out", "timeout", "deadline has elapsed"]) {
+        ErrorClass::Timeout
+    } else if contains_any(&[
+        "connection reset",
+        "connection refused",
+        "connection closed",
+        "broken pipe",
+        "service unavailable",
+        "bad gateway",
+    ]) || has_status(&message, &["502", "503"])
+    {
+        ErrorClass::ConnectionReset
+    } else {
+        ErrorClass::Permanent
+    }
+}
+
+// Retry a call with exponential backoff while its error is transient
+pub async fn with_backoff<T, E, F, Fut>(config: &RetryConfig, operation: &str, call: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    with_backoff_if(config, operation, |class| class.is_transient(), call).await
+}
+
+// Retry a call with exponential backoff while `should_retry` accepts its error class
+pub async fn with_backoff_if<T, E, F, Fut>(
+    config: &RetryConfig,
+    operation: &str,
+    should_retry: impl Fn(ErrorClass) -> bool,
+    mut call: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let mut attempt = 1;
+    let mut backoff = config.initial_backoff();
+
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                let class = classify(&e.to_string());
+                if attempt >= config.max_attempts || !should_retry(class) {
+                    return Err(e);
+                }
+
+                warn!(
+                    "{} failed with {:?} error (attempt {}/{}), retrying in {}ms: {}",
+                    operation,
+                    class,
+                    attempt,
+                    config.max_attempts,
+                    backoff.as_millis(),
+                    e
+                );
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(config.max_backoff());
+                attempt += 1;
+            }
+        }
+    }
+}
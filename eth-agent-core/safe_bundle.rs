@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::types::{AgentPlan, AgentStep, UserPrompt};
+
+/// A batch exported by the Safe Transaction Builder app ("Download batch" / "Export")
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeBundle {
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    #[serde(default)]
+    pub meta: SafeBundleMeta,
+    pub transactions: Vec<SafeBundleTransaction>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeBundleMeta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub created_from_safe_address: Option<String>,
+    #[serde(default)]
+    pub created_from_owner_address: Option<String>,
+}
+
+/// One call of a bundle. The builder leaves `data` null for calls it encodes from the chosen
+/// method and its input values.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeBundleTransaction {
+    pub to: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub data: Option<String>,
+    #[serde(default)]
+    pub contract_method: Option<ContractMethod>,
+    #[serde(default)]
+    pub contract_inputs_values: Option<HashMap<String, Value>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContractMethod {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Vec<ContractInput>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContractInput {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// How a bundle runs: proposed to its Safe for the owners to sign, or sent directly from an
+/// account, e.g. the Safe itself on an anvil fork with impersonation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleExecution {
+    // The proposer defaults to the owner that created the bundle
+    Propose { proposer: Option<String> },
+    Send { from: String },
+}
+
+impl SafeBundleTransaction {
+    // What the call does, as an ethereum_agent step describes it
+    fn call(&self) -> Result<String> {
+        let value = self.value.as_deref().filter(|value| !value.is_empty()).unwrap_or("0");
+        if let Some(data) = self.data.as_deref().filter(|data| !data.is_empty() && *data != "0x") {
+            return Ok(format!("to {} with value {} wei and data {}", self.to, value, data));
+        }
+        let Some(method) = &self.contract_method else {
+            return Ok(format!("to {} with value {} wei and no data", self.to, value));
+        };
+
+        let values = self.contract_inputs_values.clone().unwrap_or_default();
+        let mut types = vec![];
+        let mut arguments = vec![];
+        for input in &method.inputs {
+            let argument = match values.get(&input.name) {
+                Some(Value::String(argument)) => argument.clone(),
+                Some(argument) => argument.to_string(),
+                None => return Err(anyhow::anyhow!("No value for input {} of {}", input.name, method.name)),
+            };
+            types.push(input.kind.clone());
+            arguments.push(argument);
+        }
+        Ok(format!(
+            "to {} with value {} wei, calling {}({}) with arguments [{}] encoded with encode_call",
+            self.to,
+            value,
+            method.name,
+            types.join(","),
+            arguments.join(", ")
+        ))
+    }
+}
+
+impl SafeBundle {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let bundle: SafeBundle = serde_json::from_str(json).map_err(|e| anyhow::anyhow!("Not a Safe Transaction Builder bundle: {}", e))?;
+        if bundle.transactions.is_empty() {
+            return Err(anyhow::anyhow!("The bundle has no transactions"));
+        }
+        Ok(bundle)
+    }
+
+    pub fn chain_id(&self) -> Option<u64> {
+        self.chain_id.as_deref()?.parse().ok()
+    }
+
+    fn name(&self) -> &str {
+        self.meta.name.as_deref().filter(|name| !name.is_empty()).unwrap_or("Transactions Batch")
+    }
+
+    /// The plan running the bundle as it is, one ethereum_agent step per transaction in order,
+    /// so no planner has to read the bundle
+    pub fn plan(&self, execution: &BundleExecution, max_steps: u32) -> Result<AgentPlan> {
+        if self.transactions.len() > max_steps as usize {
+            return Err(anyhow::anyhow!(
+                "The bundle has {} transactions, more than max_steps ({})",
+                self.transactions.len(),
+                max_steps
+            ));
+        }
+
+        let mut steps = vec![];
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            let call = transaction.call()?;
+            let agent_prompt = match execution {
+                BundleExecution::Send { from } => format!("Send a transaction from {} {}", from, call),
+                BundleExecution::Propose { proposer } => {
+                    let safe = self
+                        .meta
+                        .created_from_safe_address
+                        .as_deref()
+                        .ok_or_else(|| anyhow::anyhow!("The bundle doesn't name its Safe, send it from an account instead"))?;
+                    let proposer = proposer
+                        .as_deref()
+                        .or(self.meta.created_from_owner_address.as_deref())
+                        .filter(|proposer| !proposer.is_empty())
+                        .ok_or_else(|| anyhow::anyhow!("The bundle doesn't name the owner that created it, give a proposer"))?;
+                    // Proposals of one bundle queue up behind each other
+                    let nonce = match index {
+                        0 => String::new(),
+                        index => format!(", with the Safe nonce {} above its current one (see safe_info)", index),
+                    };
+                    format!("Propose a Safe transaction on Safe {} {}, proposed by {}{}", safe, call, proposer, nonce)
+                }
+            };
+            steps.push(AgentStep {
+                step_number: index as u32 + 1,
+                agent_name: "ethereum_agent".to_string(),
+                agent_prompt,
+                network: None,
+                chain: None,
+                postconditions: vec![],
+            });
+        }
+
+        let action = match execution {
+            BundleExecution::Propose { .. } => "Propose",
+            BundleExecution::Send { .. } => "Send",
+        };
+        let mut prompt = format!("{} the {} transaction(s) of Safe Transaction Builder bundle \"{}\"", action, steps.len(), self.name());
+        if let Some(description) = self.meta.description.as_deref().filter(|description| !description.is_empty()) {
+            prompt.push_str(&format!(": {}", description));
+        }
+        let prompt = UserPrompt::new(prompt);
+
+        Ok(AgentPlan {
+            id: Uuid::new_v4().to_string(),
+            prompt,
+            steps,
+            max_steps,
+            current_step: 0,
+            ens_resolutions: vec![],
+        })
+    }
+}
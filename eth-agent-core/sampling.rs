@@ -0,0 +1,99 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use rig::{
+    completion::{CompletionModel, Message},
+    message::AssistantContent,
+};
+use rmcp::model::{Content, CreateMessageRequestParam, CreateMessageResult, Role, SamplingMessage};
+use tracing::info;
+
+use crate::budget::RunBudget;
+use crate::redact::redact;
+
+// Answers sampling requests from the MCP server, so server-side workflows (e.g. summarizing a
+// trace) can use an LLM without the server holding its own API keys
+#[async_trait]
+pub trait Sampler: Send + Sync {
+    async fn sample(&self, request: CreateMessageRequestParam) -> Result<CreateMessageResult>;
+}
+
+// Samples with one of the agent's own models, counted against the run's budget
+pub struct ModelSampler<M: CompletionModel> {
+    model: M,
+    model_name: String,
+    budget: RunBudget,
+}
+
+impl<M: CompletionModel> ModelSampler<M> {
+    pub fn new(model: M, model_name: &str, budget: RunBudget) -> Self {
+        Self {
+            model,
+            model_name: model_name.to_string(),
+            budget,
+        }
+    }
+}
+
+// Text of a sampling message, images and audio are not supported
+fn message_text(message: &SamplingMessage) -> Result<String> {
+    message
+        .content
+        .as_text()
+        .map(|text| redact(&text.text))
+        .ok_or_else(|| anyhow::anyhow!("Only text sampling messages are supported"))
+}
+
+#[async_trait]
+impl<M: CompletionModel + 'static> Sampler for ModelSampler<M> {
+    async fn sample(&self, request: CreateMessageRequestParam) -> Result<CreateMessageResult> {
+        if let Some(overrun) = self.budget.token_overrun() {
+            return Err(anyhow::anyhow!("Sampling refused: {}", overrun));
+        }
+
+        // The last message is the prompt, the ones before it are the conversation so far
+        let mut messages = vec![];
+        let mut input_len = 0;
+        for message in &request.messages {
+            let text = message_text(message)?;
+            input_len += text.len();
+            messages.push(match message.role {
+                Role::User => Message::user(text),
+                Role::Assistant => Message::assistant(text),
+            });
+        }
+        let prompt = messages.pop().ok_or_else(|| anyhow::anyhow!("Sampling request without messages"))?;
+        info!("Sampling {} messages for the MCP server", request.messages.len());
+
+        let mut completion = self.model
+            .completion_request(prompt)
+            .messages(messages)
+            .max_tokens(u64::from(request.max_tokens));
+        if let Some(system) = request.system_prompt.as_deref().map(redact) {
+            input_len += system.len();
+            completion = completion.preamble(system);
+        }
+        if let Some(temperature) = request.temperature {
+            completion = completion.temperature(f64::from(temperature));
+        }
+        let response = completion.send().await?;
+
+        let text: String = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect();
+        self.budget.record_completion(input_len, text.len());
+
+        Ok(CreateMessageResult {
+            model: self.model_name.clone(),
+            stop_reason: Some(CreateMessageResult::STOP_REASON_END_TURN.to_string()),
+            message: SamplingMessage {
+                role: Role::Assistant,
+                content: Content::text(text),
+            },
+        })
+    }
+}
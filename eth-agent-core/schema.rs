@@ -0,0 +1,104 @@
+use serde_json::{json, Value};
+
+// The subset of JSON schema tool definitions use: type, properties, required, items and enum
+fn check(schema: &Value, value: &Value, path: &str, problems: &mut Vec<Value>) {
+    let field = if path.is_empty() { "arguments" } else { path };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(ty) => vec![ty.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|ty| has_type(value, ty)) {
+            problems.push(json!({
+                "field": field,
+                "problem": format!("must be {}, got {}", types.join(" or "), type_name(value))
+            }));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            problems.push(json!({
+                "field": field,
+                "problem": format!("must be one of {}", Value::Array(allowed.clone()))
+            }));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if object.get(required).is_none_or(Value::is_null) {
+                problems.push(json!({ "field": join(path, required), "problem": "missing required field" }));
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, property) in properties {
+                // Optional fields may be left out or null
+                match object.get(name) {
+                    Some(Value::Null) | None => {}
+                    Some(value) => check(property, value, &join(path, name), problems),
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            check(item_schema, item, &format!("{}[{}]", field, index), problems);
+        }
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        // Types we don't know are not ours to reject
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Check a tool call's arguments against the tool's parameter schema. The error is a correction for
+// the model, listing every missing field and wrong type so it can fix them in one go.
+pub fn validate_arguments(tool: &str, schema: &Value, arguments: &Value) -> Result<(), String> {
+    let mut problems = vec![];
+    check(schema, arguments, "", &mut problems);
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    Err(json!({
+        "error": "invalid_arguments",
+        "tool": tool,
+        "problems": problems,
+        "hint": "The tool was not called. Call it again with arguments matching its parameters."
+    })
+    .to_string())
+}
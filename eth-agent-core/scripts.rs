@@ -0,0 +1,333 @@
+use std::fmt::Write;
+
+use alloy_primitives::{hex, Address, U256};
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::types::RunRecord;
+
+// A transaction the run sent, as a script sends it again
+struct Broadcast {
+    from: Address,
+    to: Option<Address>,
+    value: U256,
+    data: Vec<u8>,
+    deployed: Option<Address>,
+}
+
+// What the run did on chain, in the order it did it
+enum Action {
+    Send(Broadcast),
+    Balance { address: String },
+    TokenBalance { address: String, token: String },
+    Code { address: String },
+}
+
+struct StepActions {
+    step_number: u32,
+    step_prompt: String,
+    actions: Vec<Action>,
+}
+
+fn address(arguments: &Value, name: &str) -> Result<Option<Address>> {
+    let Some(address) = arguments.get(name).and_then(Value::as_str).filter(|address| !address.is_empty()) else {
+        return Ok(None);
+    };
+    address
+        .parse()
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("{} {} is not an address, a script can only use resolved addresses", name, address))
+}
+
+fn text(arguments: &Value, name: &str) -> Option<String> {
+    arguments.get(name)?.as_str().map(|value| value.to_string())
+}
+
+fn wei(arguments: &Value) -> Result<U256> {
+    let value = match arguments.get("value") {
+        Some(Value::String(value)) if !value.is_empty() => value.clone(),
+        Some(Value::Number(value)) => value.to_string(),
+        _ => return Ok(U256::ZERO),
+    };
+    value.parse().map_err(|_| anyhow::anyhow!("{} is not an amount in wei", value))
+}
+
+fn calldata(arguments: &Value) -> Result<Vec<u8>> {
+    match arguments.get("data").and_then(Value::as_str) {
+        Some(data) => hex::decode(data).map_err(|_| anyhow::anyhow!("{} is not hex encoded calldata", data)),
+        None => Ok(vec![]),
+    }
+}
+
+// Every transaction the run got mined or pending and every chain read, grouped by step, and the
+// chain id they were made on. Failed and blocked sends never reached the chain and are left out.
+fn actions(run: &RunRecord) -> Result<(Vec<StepActions>, Option<u64>)> {
+    let mut steps = vec![];
+    let mut chain_id = None;
+    let mut chain_ids = vec![];
+    for step in &run.steps {
+        let mut actions = vec![];
+        for call in &step.tool_calls {
+            let Some(payload) = call.payload() else {
+                continue;
+            };
+            if payload.get("success").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+
+            let arguments = &call.arguments;
+            let action = match call.tool.as_str() {
+                "use_network" => {
+                    chain_id = payload.get("chain_id").and_then(Value::as_u64);
+                    continue;
+                }
+                "send_transaction" if call.transaction_hash().is_some() => {
+                    let from = address(arguments, "from")?
+                        .ok_or_else(|| anyhow::anyhow!("A transaction of step {} has no sender", step.step_number))?;
+                    Action::Send(Broadcast {
+                        from,
+                        to: address(arguments, "to")?,
+                        value: wei(arguments)?,
+                        data: calldata(arguments)?,
+                        deployed: text(&payload, "contract_address").and_then(|address| address.parse().ok()),
+                    })
+                }
+                "balance" => match text(arguments, "address") {
+                    Some(address) => Action::Balance { address },
+                    None => continue,
+                },
+                "erc20_balance" => match (text(arguments, "address"), text(arguments, "token_address")) {
+                    (Some(address), Some(token)) => Action::TokenBalance { address, token },
+                    _ => continue,
+                },
+                "get_contract_code" => match text(arguments, "address") {
+                    Some(address) => Action::Code { address },
+                    None => continue,
+                },
+                _ => continue,
+            };
+            actions.push(action);
+            if !chain_ids.contains(&chain_id) {
+                chain_ids.push(chain_id);
+            }
+        }
+        if !actions.is_empty() {
+            steps.push(StepActions {
+                step_number: step.step_number,
+                step_prompt: step.agent_prompt.clone(),
+                actions,
+            });
+        }
+    }
+
+    if chain_ids.len() > 1 {
+        return Err(anyhow::anyhow!("The run used more than one network, a script runs on one"));
+    }
+    Ok((steps, chain_ids.into_iter().flatten().next()))
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn sends(steps: &[StepActions]) -> usize {
+    steps
+        .iter()
+        .flat_map(|step| &step.actions)
+        .filter(|action| matches!(action, Action::Send(_)))
+        .count()
+}
+
+// Deployed contracts get a new address when a script runs again, so calls to them go to the
+// variable holding the new one
+struct Deployments(Vec<(Address, String)>);
+
+impl Deployments {
+    fn target(&self, to: Address) -> Option<&str> {
+        self.0.iter().find(|(address, _)| *address == to).map(|(_, variable)| variable.as_str())
+    }
+
+    // Calldata is copied as is, arguments holding a deployed address still hold the old one
+    fn mentioned_in(&self, data: &[u8]) -> Vec<&(Address, String)> {
+        self.0
+            .iter()
+            .filter(|(address, _)| data.windows(20).any(|window| window == address.as_slice()))
+            .collect()
+    }
+
+    // An address for a cast command, the shell variable holding it for contracts deployed again
+    fn shell_address(&self, address: &str) -> String {
+        match address.parse().ok().and_then(|address| self.target(address)) {
+            Some(variable) => format!("\"${}\"", variable),
+            None => quote(address),
+        }
+    }
+}
+
+/// Convert the transactions a run sent into a Solidity `forge script`, so a reviewed plan can be
+/// committed and executed again without the LLM. Contracts the run deployed are deployed again
+/// and later calls to them go to the new address.
+pub fn forge_script(run: &RunRecord) -> Result<String> {
+    let (steps, chain_id) = actions(run)?;
+    if sends(&steps) == 0 {
+        return Err(anyhow::anyhow!("Run {} sent no transactions, there is nothing to script", run.id));
+    }
+
+    let mut script = String::new();
+    let _ = writeln!(script, "// SPDX-License-Identifier: UNLICENSED");
+    let _ = writeln!(script, "pragma solidity ^0.8.13;\n");
+    let _ = writeln!(script, "import {{Script}} from \"forge-std/Script.sol\";\n");
+    let _ = writeln!(script, "// Exported from eth-agent run {}", run.id);
+    let _ = writeln!(script, "// Prompt: {}", one_line(&run.prompt.natural_language));
+    let _ = writeln!(script, "// forge script <this file> --rpc-url <url> --broadcast, with the key of every sender below");
+    let _ = writeln!(script, "contract AgentRun is Script {{");
+    let _ = writeln!(script, "    function run() external {{");
+    if let Some(chain_id) = chain_id {
+        let _ = writeln!(script, "        require(block.chainid == {}, \"run on chain {}\");", chain_id, chain_id);
+    }
+
+    let mut deployments = Deployments(vec![]);
+    let mut sender = None;
+    let mut index = 0;
+    for step in &steps {
+        let broadcasts: Vec<&Broadcast> = step
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Send(broadcast) => Some(broadcast),
+                _ => None,
+            })
+            .collect();
+        if broadcasts.is_empty() {
+            continue;
+        }
+        let _ = writeln!(script, "\n        // Step {}: {}", step.step_number, one_line(&step.step_prompt));
+
+        for broadcast in broadcasts {
+            index += 1;
+            if sender != Some(broadcast.from) {
+                if sender.is_some() {
+                    let _ = writeln!(script, "        vm.stopBroadcast();");
+                }
+                let _ = writeln!(script, "        vm.startBroadcast({});", broadcast.from);
+                sender = Some(broadcast.from);
+            }
+            for (address, variable) in deployments.mentioned_in(&broadcast.data) {
+                let _ = writeln!(script, "        // The calldata holds {} as deployed by the original run, not {}", address, variable);
+            }
+
+            let data = format!("hex\"{}\"", hex::encode(&broadcast.data));
+            match broadcast.to {
+                Some(to) => {
+                    let target = deployments.target(to).map(str::to_string).unwrap_or_else(|| to.to_string());
+                    let _ = writeln!(script, "        send({}, {}, {});", target, broadcast.value, data);
+                }
+                None => {
+                    let variable = format!("deployed{}", index);
+                    let _ = writeln!(script, "        address {} = deploy({}, {});", variable, data, broadcast.value);
+                    if let Some(deployed) = broadcast.deployed {
+                        deployments.0.push((deployed, variable));
+                    }
+                }
+            }
+        }
+    }
+    let _ = writeln!(script, "        vm.stopBroadcast();");
+    let _ = writeln!(script, "    }}\n");
+
+    let _ = writeln!(script, "    function send(address to, uint256 value, bytes memory data) internal {{");
+    let _ = writeln!(script, "        (bool success, ) = to.call{{value: value}}(data);");
+    let _ = writeln!(script, "        require(success, \"call failed\");");
+    let _ = writeln!(script, "    }}\n");
+    let _ = writeln!(script, "    function deploy(bytes memory initCode, uint256 value) internal returns (address deployed) {{");
+    let _ = writeln!(script, "        assembly {{");
+    let _ = writeln!(script, "            deployed := create(value, add(initCode, 0x20), mload(initCode))");
+    let _ = writeln!(script, "        }}");
+    let _ = writeln!(script, "        require(deployed != address(0), \"deployment failed\");");
+    let _ = writeln!(script, "    }}");
+    let _ = writeln!(script, "}}");
+
+    Ok(script)
+}
+
+// Addresses read by the run may be ENS names as the user typed them, anything but plain words is
+// single-quoted for the shell
+fn quote(text: &str) -> String {
+    if text.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Convert what a run did on chain into a shell script of `cast` commands: `cast send` for every
+/// transaction it sent and `cast balance`, `cast call` or `cast code` for what it read. Handy to
+/// review a run line by line, or to finish a plan by hand when the agent isn't available.
+pub fn cast_script(run: &RunRecord) -> Result<String> {
+    let (steps, chain_id) = actions(run)?;
+    if steps.is_empty() {
+        return Err(anyhow::anyhow!("Run {} neither sent transactions nor read the chain, there is nothing to script", run.id));
+    }
+
+    let mut script = String::new();
+    let _ = writeln!(script, "#!/usr/bin/env bash");
+    let _ = writeln!(script, "# Exported from eth-agent run {}", run.id);
+    let _ = writeln!(script, "# Prompt: {}", one_line(&run.prompt.natural_language));
+    let _ = writeln!(script, "# Runs on $ETH_RPC_URL. Arguments are passed to every cast send, e.g. --unlocked on anvil or");
+    let _ = writeln!(script, "# --account <name>. Deployments need jq to read the new contract address.");
+    let _ = writeln!(script, "set -euo pipefail\n");
+    let _ = writeln!(script, ": \"${{ETH_RPC_URL:?set ETH_RPC_URL to the network to run on}}\"");
+    if let Some(chain_id) = chain_id {
+        let _ = writeln!(script, "[ \"$(cast chain-id)\" = \"{}\" ] || {{ echo \"run on chain {}\" >&2; exit 1; }}", chain_id, chain_id);
+    }
+
+    let mut deployments = Deployments(vec![]);
+    let mut index = 0;
+    for step in &steps {
+        let _ = writeln!(script, "\n# Step {}: {}", step.step_number, one_line(&step.step_prompt));
+        for action in &step.actions {
+            match action {
+                Action::Send(broadcast) => {
+                    index += 1;
+                    for (address, variable) in deployments.mentioned_in(&broadcast.data) {
+                        let _ = writeln!(script, "# The calldata holds {} as deployed by the original run, not ${}", address, variable);
+                    }
+                    let data = if broadcast.data.is_empty() { String::new() } else { format!(" 0x{}", hex::encode(&broadcast.data)) };
+                    let options = format!("--value {} --from {} \"$@\"", broadcast.value, broadcast.from);
+                    match broadcast.to {
+                        Some(to) => {
+                            let _ = writeln!(script, "cast send {}{} {}", deployments.shell_address(&to.to_string()), data, options);
+                        }
+                        None => {
+                            let variable = format!("deployed{}", index);
+                            let _ = writeln!(
+                                script,
+                                "{}=$(cast send --create{} {} --json | jq -r .contractAddress)",
+                                variable, data, options
+                            );
+                            let _ = writeln!(script, "echo \"Deployed ${}\"", variable);
+                            if let Some(deployed) = broadcast.deployed {
+                                deployments.0.push((deployed, variable));
+                            }
+                        }
+                    }
+                }
+                Action::Balance { address } => {
+                    let _ = writeln!(script, "cast balance {}", deployments.shell_address(address));
+                }
+                Action::TokenBalance { address, token } => {
+                    let _ = writeln!(
+                        script,
+                        "cast call {} 'balanceOf(address)(uint256)' {}",
+                        deployments.shell_address(token),
+                        deployments.shell_address(address)
+                    );
+                }
+                Action::Code { address } => {
+                    let _ = writeln!(script, "cast code {}", deployments.shell_address(address));
+                }
+            }
+        }
+    }
+
+    Ok(script)
+}
@@ -0,0 +1,399 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const DEFAULT_COUNT: u32 = 10;
+
+/// Web search backend for the search agent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchProviderKind {
+    #[default]
+    Brave,
+    Tavily,
+    SerpApi,
+    /// Needs no API key, but only returns instant answers and related topics
+    DuckDuckGo,
+}
+
+impl SearchProviderKind {
+    /// Name of the API key this provider needs, `None` if it needs none
+    pub fn key_name(&self) -> Option<&'static str> {
+        match self {
+            SearchProviderKind::Brave => Some("brave"),
+            SearchProviderKind::Tavily => Some("tavily"),
+            SearchProviderKind::SerpApi => Some("serpapi"),
+            SearchProviderKind::DuckDuckGo => None,
+        }
+    }
+}
+
+/// One search hit, the same shape whichever provider found it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// How many results to return and for which market. Providers ignore what they don't support.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub count: u32,
+    /// Two letter country code, e.g. "us"
+    pub country: Option<String>,
+    /// Two letter language code, e.g. "en"
+    pub search_lang: Option<String>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_COUNT,
+            country: None,
+            search_lang: None,
+        }
+    }
+}
+
+/// A web search backend
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>>;
+}
+
+/// Build the provider of `kind`, failing when it needs an API key and none was given
+pub fn search_provider(kind: SearchProviderKind, api_key: Option<String>) -> Result<Arc<dyn SearchProvider>> {
+    let api_key = match (kind.key_name(), api_key) {
+        (Some(name), None) => return Err(anyhow::anyhow!("No {} API key was given", name)),
+        (_, api_key) => api_key.unwrap_or_default(),
+    };
+
+    Ok(match kind {
+        SearchProviderKind::Brave => Arc::new(BraveSearch::new(api_key)),
+        SearchProviderKind::Tavily => Arc::new(TavilySearch::new(api_key)),
+        SearchProviderKind::SerpApi => Arc::new(SerpApiSearch::new(api_key)),
+        SearchProviderKind::DuckDuckGo => Arc::new(DuckDuckGoSearch::new()),
+    })
+}
+
+/// Clean up provider results for the model: markup stripped from titles and snippets, snippets
+/// cut to `max_snippet_chars`, and results without a URL or repeating one dropped
+pub fn distill(results: Vec<SearchResult>, max_snippet_chars: usize) -> Vec<SearchResult> {
+    let mut distilled: Vec<SearchResult> = vec![];
+    for result in results {
+        if result.url.is_empty() || distilled.iter().any(|known| known.url == result.url) {
+            continue;
+        }
+        distilled.push(SearchResult {
+            title: html_to_text(&result.title),
+            snippet: truncate(&html_to_text(&result.snippet), max_snippet_chars),
+            url: result.url,
+        });
+    }
+    distilled
+}
+
+/// Visible text of an HTML document or fragment, without scripts, styles and tags and with
+/// whitespace collapsed
+pub fn html_to_text(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets, so positions found in it index into `html` too
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::with_capacity(html.len());
+    let mut position = 0;
+
+    while let Some(offset) = lower[position..].find('<') {
+        let start = position + offset;
+        text.push_str(&html[position..start]);
+        text.push(' ');
+
+        let skipped = ["script", "style", "noscript"]
+            .iter()
+            .find(|name| lower[start + 1..].starts_with(*name))
+            .and_then(|name| lower[start..].find(&format!("</{}", name)).map(|end| start + end));
+        let tag_start = skipped.unwrap_or(start);
+        position = match lower[tag_start..].find('>') {
+            Some(end) => tag_start + end + 1,
+            None => html.len(),
+        };
+    }
+    text.push_str(&html[position..]);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Cut at a word boundary, marking that something was cut
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > 0 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end_matches([',', '.', ';', ':']))
+}
+
+/// Fetch `url` and return its title and the start of its main text, up to `max_chars`
+pub async fn summarize_page(url: &str, max_chars: usize) -> Result<Value> {
+    let html = reqwest::Client::new()
+        .get(url)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let lower = html.to_ascii_lowercase();
+    let title = match (lower.find("<title"), lower.find("</title>")) {
+        (Some(start), Some(end)) if start < end => html_to_text(&html[start..end]),
+        _ => String::new(),
+    };
+    // Pages with a main or article element keep their navigation and footers outside of it
+    let body = ["<main", "<article"]
+        .iter()
+        .find_map(|tag| lower.find(tag))
+        .map(|start| &html[start..])
+        .unwrap_or(&html);
+
+    Ok(json!({
+        "url": url,
+        "title": title,
+        "summary": truncate(&html_to_text(body), max_chars),
+    }))
+}
+
+fn text(value: &Value, field: &str) -> String {
+    value.get(field).and_then(|value| value.as_str()).unwrap_or_default().to_string()
+}
+
+fn results(items: Option<&Value>, count: u32, title: &str, url: &str, snippet: &str) -> Vec<SearchResult> {
+    items
+        .and_then(|items| items.as_array())
+        .into_iter()
+        .flatten()
+        .take(count as usize)
+        .map(|item| SearchResult {
+            title: text(item, title),
+            url: text(item, url),
+            snippet: text(item, snippet),
+        })
+        .collect()
+}
+
+pub struct BraveSearch {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl BraveSearch {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for BraveSearch {
+    fn name(&self) -> &'static str {
+        "brave"
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        // Brave returns at most 20 results per request
+        let mut params = vec![("q", query.to_string()), ("count", options.count.min(20).to_string())];
+        if let Some(country) = &options.country {
+            params.push(("country", country.clone()));
+        }
+        if let Some(search_lang) = &options.search_lang {
+            params.push(("search_lang", search_lang.clone()));
+        }
+
+        let response: Value = self
+            .client
+            .get("https://api.search.brave.com/res/v1/web/search")
+            .header("X-Subscription-Token", &self.api_key)
+            .query(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(results(response.pointer("/web/results"), options.count, "title", "url", "description"))
+    }
+}
+
+pub struct TavilySearch {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl TavilySearch {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for TavilySearch {
+    fn name(&self) -> &'static str {
+        "tavily"
+    }
+
+    // Tavily has no country or language filter, only the result count applies
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let response: Value = self
+            .client
+            .post("https://api.tavily.com/search")
+            .bearer_auth(&self.api_key)
+            .json(&json!({ "query": query, "max_results": options.count }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(results(response.get("results"), options.count, "title", "url", "content"))
+    }
+}
+
+pub struct SerpApiSearch {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl SerpApiSearch {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchProvider for SerpApiSearch {
+    fn name(&self) -> &'static str {
+        "serpapi"
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let mut params = vec![
+            ("engine", "google".to_string()),
+            ("q", query.to_string()),
+            ("num", options.count.to_string()),
+            ("api_key", self.api_key.clone()),
+        ];
+        if let Some(country) = &options.country {
+            params.push(("gl", country.clone()));
+        }
+        if let Some(search_lang) = &options.search_lang {
+            params.push(("hl", search_lang.clone()));
+        }
+
+        let response: Value = self
+            .client
+            .get("https://serpapi.com/search.json")
+            .query(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(results(response.get("organic_results"), options.count, "title", "link", "snippet"))
+    }
+}
+
+#[derive(Default)]
+pub struct DuckDuckGoSearch {
+    client: reqwest::Client,
+}
+
+impl DuckDuckGoSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SearchProvider for DuckDuckGoSearch {
+    fn name(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    // The Instant Answer API, the only keyless DuckDuckGo API
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let mut params = vec![
+            ("q", query.to_string()),
+            ("format", "json".to_string()),
+            ("no_html", "1".to_string()),
+            ("skip_disambig", "1".to_string()),
+        ];
+        // Regions are country-language pairs, e.g. "us-en"
+        if let Some(country) = &options.country {
+            let language = options.search_lang.as_deref().unwrap_or("en");
+            params.push(("kl", format!("{}-{}", country.to_lowercase(), language.to_lowercase())));
+        }
+
+        let response: Value = self
+            .client
+            .get("https://api.duckduckgo.com/")
+            .query(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut results = vec![];
+        let answer = text(&response, "AbstractText");
+        if !answer.is_empty() {
+            results.push(SearchResult {
+                title: text(&response, "Heading"),
+                url: text(&response, "AbstractURL"),
+                snippet: answer,
+            });
+        }
+
+        // Topic groups nest their topics one level deeper
+        let topics = response.get("RelatedTopics").and_then(|topics| topics.as_array()).cloned().unwrap_or_default();
+        let topics = topics.iter().flat_map(|topic| match topic.get("Topics").and_then(|topics| topics.as_array()) {
+            Some(nested) => nested.clone(),
+            None => vec![topic.clone()],
+        });
+        for topic in topics.take((options.count as usize).saturating_sub(results.len())) {
+            let snippet = text(&topic, "Text");
+            results.push(SearchResult {
+                // Topic texts start with their title, e.g. "Ethereum - A decentralized ..."
+                title: snippet.split(" - ").next().unwrap_or_default().to_string(),
+                url: text(&topic, "FirstURL"),
+                snippet,
+            });
+        }
+
+        Ok(results)
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::types::*;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id TEXT PRIMARY KEY,
+    prompt TEXT NOT NULL,
+    prompt_json TEXT NOT NULL,
+    plan_json TEXT,
+    status TEXT NOT NULL,
+    status_json TEXT NOT NULL,
+    result TEXT,
+    started_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    cost_estimate_json TEXT,
+    gas_spent_wei TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_started_at ON runs (started_at);
+CREATE TABLE IF NOT EXISTS steps (
+    run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+    step_number INTEGER NOT NULL,
+    agent_name TEXT NOT NULL,
+    agent_prompt TEXT NOT NULL,
+    status TEXT NOT NULL,
+    status_json TEXT NOT NULL,
+    output TEXT,
+    evaluation_score INTEGER,
+    evaluation_reasoning TEXT,
+    started_at TEXT,
+    finished_at TEXT,
+    PRIMARY KEY (run_id, step_number)
+);
+CREATE TABLE IF NOT EXISTS tool_calls (
+    run_id TEXT NOT NULL REFERENCES runs (id) ON DELETE CASCADE,
+    step_number INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    tool TEXT NOT NULL,
+    arguments_json TEXT NOT NULL,
+    result_json TEXT,
+    error TEXT,
+    timestamp TEXT NOT NULL,
+    PRIMARY KEY (run_id, step_number, position)
+);
+";
+
+// Columns added to the schema later, created in databases written by older versions
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[("steps", "started_at", "TEXT"), ("steps", "finished_at", "TEXT")];
+
+const RUN_COLUMNS: &str =
+    "id, prompt_json, plan_json, status_json, result, started_at, updated_at, cost_estimate_json, gas_spent_wei";
+
+// Fixed width UTC timestamps, so they compare correctly as text
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(time: &str) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(json).map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+// Status name without its reason, e.g. "Failed"
+fn status_name<T: std::fmt::Debug>(status: &T) -> String {
+    format!("{:?}", status).split('(').next().unwrap_or_default().to_string()
+}
+
+/// Runs saved in an embedded SQLite database, so they outlive the process. Prompts, plans,
+/// step statuses, tool calls and evaluations each get their own rows.
+pub struct RunStore {
+    connection: Mutex<Connection>,
+}
+
+impl RunStore {
+    /// Open (or create) the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open run database {}: {}", path.display(), e))?;
+        Self::init(connection)
+    }
+
+    /// A database that only lives as long as the store
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        for (table, column, ty) in ADDED_COLUMNS {
+            let exists = connection
+                .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+                .exists(params![column])?;
+            if !exists {
+                connection.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, ty))?;
+            }
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Write the latest checkpoint of a run, replacing what was saved for it before
+    pub fn save(&self, run: &RunRecord) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "INSERT OR REPLACE INTO runs (id, prompt, prompt_json, plan_json, status, status_json, result, started_at,
+                updated_at, cost_estimate_json, gas_spent_wei)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                run.id,
+                run.prompt.natural_language,
+                to_json(&run.prompt)?,
+                run.plan.as_ref().map(to_json).transpose()?,
+                status_name(&run.status),
+                to_json(&run.status)?,
+                run.result,
+                timestamp(&run.started_at),
+                timestamp(&run.updated_at),
+                run.cost_estimate.as_ref().map(to_json).transpose()?,
+                run.gas_spent_wei,
+            ],
+        )?;
+
+        // Replanning replaces every step, so the old rows are dropped rather than merged
+        transaction.execute("DELETE FROM tool_calls WHERE run_id = ?1", params![run.id])?;
+        transaction.execute("DELETE FROM steps WHERE run_id = ?1", params![run.id])?;
+        for step in &run.steps {
+            transaction.execute(
+                "INSERT INTO steps (run_id, step_number, agent_name, agent_prompt, status, status_json, output,
+                    evaluation_score, evaluation_reasoning, started_at, finished_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    run.id,
+                    step.step_number,
+                    step.agent_name,
+                    step.agent_prompt,
+                    status_name(&step.status),
+                    to_json(&step.status)?,
+                    step.output,
+                    step.evaluation.as_ref().map(|evaluation| evaluation.score),
+                    step.evaluation.as_ref().map(|evaluation| evaluation.reasoning.as_str()),
+                    step.started_at.as_ref().map(timestamp),
+                    step.finished_at.as_ref().map(timestamp),
+                ],
+            )?;
+            for (position, call) in step.tool_calls.iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO tool_calls (run_id, step_number, position, tool, arguments_json, result_json, error, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        run.id,
+                        step.step_number,
+                        position as i64,
+                        call.tool,
+                        to_json(&call.arguments)?,
+                        call.result.as_ref().map(to_json).transpose()?,
+                        call.error,
+                        timestamp(&call.timestamp),
+                    ],
+                )?;
+            }
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Runs started at or after `since` (every run without it), oldest first
+    pub fn list(&self, since: Option<DateTime<Utc>>) -> Result<Vec<RunRecord>> {
+        let connection = self.connection.lock().unwrap();
+        let since = since.map(|since| timestamp(&since)).unwrap_or_default();
+        let mut statement = connection
+            .prepare(&format!("SELECT {} FROM runs WHERE started_at >= ?1 ORDER BY started_at", RUN_COLUMNS))?;
+        let runs = statement
+            .query_map(params![since], run_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        runs.into_iter().map(|run| with_steps(&connection, run)).collect()
+    }
+
+    /// Find a run by id, or by a unique prefix of it
+    pub fn find(&self, id: &str) -> Result<Option<RunRecord>> {
+        let connection = self.connection.lock().unwrap();
+        if let Some(run) = connection
+            .query_row(&format!("SELECT {} FROM runs WHERE id = ?1", RUN_COLUMNS), params![id], run_from_row)
+            .optional()?
+        {
+            return Ok(Some(with_steps(&connection, run)?));
+        }
+
+        // `_` and `%` don't appear in run ids, so a plain LIKE prefix is exact
+        let mut statement = connection.prepare(&format!("SELECT {} FROM runs WHERE id LIKE ?1 || '%' LIMIT 2", RUN_COLUMNS))?;
+        let mut matches = statement
+            .query_map(params![id], run_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if matches.len() != 1 {
+            return Ok(None);
+        }
+        Ok(Some(with_steps(&connection, matches.remove(0))?))
+    }
+}
+
+// Run columns in RUN_COLUMNS order, steps are loaded separately
+fn run_from_row(row: &Row) -> rusqlite::Result<RunRecord> {
+    Ok(RunRecord {
+        id: row.get(0)?,
+        prompt: from_json(&row.get::<_, String>(1)?)?,
+        plan: row.get::<_, Option<String>>(2)?.as_deref().map(from_json).transpose()?,
+        steps: vec![],
+        status: from_json(&row.get::<_, String>(3)?)?,
+        result: row.get(4)?,
+        started_at: parse_timestamp(&row.get::<_, String>(5)?)?,
+        updated_at: parse_timestamp(&row.get::<_, String>(6)?)?,
+        cost_estimate: row.get::<_, Option<String>>(7)?.as_deref().map(from_json).transpose()?,
+        gas_spent_wei: row.get(8)?,
+    })
+}
+
+fn with_steps(connection: &Connection, mut run: RunRecord) -> Result<RunRecord> {
+    let mut statement = connection.prepare(
+        "SELECT step_number, agent_name, agent_prompt, status_json, output, evaluation_score, evaluation_reasoning,
+            started_at, finished_at
+         FROM steps WHERE run_id = ?1 ORDER BY step_number",
+    )?;
+    run.steps = statement
+        .query_map(params![run.id], |row| {
+            let score: Option<u32> = row.get(5)?;
+            let reasoning: Option<String> = row.get(6)?;
+            Ok(StepRecord {
+                step_number: row.get(0)?,
+                agent_name: row.get(1)?,
+                agent_prompt: row.get(2)?,
+                status: from_json(&row.get::<_, String>(3)?)?,
+                output: row.get(4)?,
+                tool_calls: vec![],
+                evaluation: score.map(|score| StepEvaluation {
+                    score,
+                    reasoning: reasoning.unwrap_or_default(),
+                }),
+                started_at: row.get::<_, Option<String>>(7)?.as_deref().map(parse_timestamp).transpose()?,
+                finished_at: row.get::<_, Option<String>>(8)?.as_deref().map(parse_timestamp).transpose()?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut statement = connection.prepare(
+        "SELECT step_number, tool, arguments_json, result_json, error, timestamp
+         FROM tool_calls WHERE run_id = ?1 ORDER BY step_number, position",
+    )?;
+    let calls = statement.query_map(params![run.id], |row| {
+        Ok((
+            row.get::<_, u32>(0)?,
+            ToolCallRecord {
+                tool: row.get(1)?,
+                arguments: from_json(&row.get::<_, String>(2)?)?,
+                result: row.get::<_, Option<String>>(3)?.as_deref().map(from_json).transpose()?,
+                error: row.get(4)?,
+                timestamp: parse_timestamp(&row.get::<_, String>(5)?)?,
+            },
+        ))
+    })?;
+    for call in calls {
+        let (step_number, call) = call?;
+        if let Some(step) = run.steps.iter_mut().find(|step| step.step_number == step_number) {
+            step.tool_calls.push(call);
+        }
+    }
+
+    Ok(run)
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use futures::StreamExt;
+use rig::{
+    agent::{Agent, AgentBuilder},
+    completion::{CompletionModel, Message},
+    message::{AssistantContent, ToolResultContent, UserContent},
+    streaming::{StreamedAssistantContent, StreamingChat},
+    tool::{Tool, ToolSet, ToolSetBuilder},
+    OneOrMany,
+};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+use crate::budget::RunBudget;
+use crate::config::RetryConfig;
+use crate::events::{AgentEvent, EventSink};
+use crate::labels::AddressLabels;
+use crate::redact::redact;
+use crate::retry::with_backoff;
+use crate::schema::validate_arguments;
+
+// A sub-agent together with its own tool set, which is needed to execute
+// tool calls ourselves when streaming
+pub struct SubAgent<M: CompletionModel> {
+    agent: Agent<M>,
+    tools: ToolSet,
+    max_turns: usize,
+    retry: RetryConfig,
+    budget: Option<RunBudget>,
+    labels: Option<AddressLabels>,
+}
+
+pub struct SubAgentBuilder<M: CompletionModel> {
+    agent: AgentBuilder<M>,
+    tools: ToolSetBuilder,
+    max_turns: usize,
+    retry: RetryConfig,
+    budget: Option<RunBudget>,
+    labels: Option<AddressLabels>,
+}
+
+impl<M: CompletionModel> SubAgentBuilder<M> {
+    pub fn new(agent: AgentBuilder<M>, max_turns: usize) -> Self {
+        Self {
+            agent,
+            tools: ToolSet::builder(),
+            max_turns,
+            retry: RetryConfig::default(),
+            budget: None,
+            labels: None,
+        }
+    }
+
+    pub fn retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // Record the tokens of every turn against the run's budget
+    pub fn budget(mut self, budget: RunBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    // Name the labelled addresses of every tool result for the model
+    pub fn labels(mut self, labels: AddressLabels) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    // Register the tool both with the model (for its definition) and with our tool set (for execution)
+    pub fn tool<X: Tool + Clone + 'static>(self, tool: X) -> Self {
+        Self {
+            agent: self.agent.tool(tool.clone()),
+            tools: self.tools.static_tool(tool),
+            max_turns: self.max_turns,
+            retry: self.retry,
+            budget: self.budget,
+            labels: self.labels,
+        }
+    }
+
+    pub fn build(self) -> SubAgent<M> {
+        SubAgent {
+            agent: self.agent.build(),
+            tools: self.tools.build(),
+            max_turns: self.max_turns,
+            retry: self.retry,
+            budget: self.budget,
+            labels: self.labels,
+        }
+    }
+}
+
+impl<M: CompletionModel> SubAgent<M> {
+    // Run a multi-turn prompt, streaming text and tool calls to the event sink as they arrive.
+    // Returns the text of the final turn.
+    pub async fn run(&self, prompt: String, events: &EventSink) -> Result<String> {
+        let mut history: Vec<Message> = vec![];
+        let mut message = Message::user(prompt);
+
+        // Parameter schemas to check arguments against before a tool runs
+        let schemas: HashMap<String, serde_json::Value> = match self.tools.get_tool_definitions().await {
+            Ok(definitions) => definitions.into_iter().map(|definition| (definition.name, definition.parameters)).collect(),
+            Err(e) => {
+                warn!("Could not read tool definitions, arguments are not validated: {}", e);
+                HashMap::new()
+            }
+        };
+
+        for turn in 0..=self.max_turns {
+            debug!("Sub-agent turn {}", turn);
+
+            // Only opening the stream is retried, a failure mid-stream would duplicate output
+            let mut stream = with_backoff(&self.retry, "sub-agent completion", || {
+                let message = message.clone();
+                let history = history.clone();
+                async move { self.agent.stream_chat(message, history).await }
+            }).await?;
+            let mut text = String::new();
+            // Streamed text not emitted yet. Tokens are redacted a line at a time, so a key and the
+            // words naming it, or a mnemonic, are never split across events.
+            let mut pending = String::new();
+            let mut tool_calls = vec![];
+
+            while let Some(chunk) = stream.next().await {
+                match chunk? {
+                    StreamedAssistantContent::Text(chunk) => {
+                        text.push_str(&chunk.text);
+                        pending.push_str(&chunk.text);
+                        if let Some(end) = pending.rfind('\n') {
+                            events.emit(AgentEvent::Token(redact(&pending[..=end])));
+                            pending.drain(..=end);
+                        }
+                    }
+                    StreamedAssistantContent::ToolCall(tool_call) => {
+                        events.emit(AgentEvent::ToolCall {
+                            name: tool_call.function.name.clone(),
+                            arguments: tool_call.function.arguments.clone(),
+                        });
+                        tool_calls.push(tool_call);
+                    }
+                    _ => {}
+                }
+            }
+            if !pending.is_empty() {
+                events.emit(AgentEvent::Token(redact(&pending)));
+            }
+
+            if let Some(budget) = &self.budget {
+                // The whole history is sent again every turn
+                let input_len = serde_json::to_string(&message).map(|s| s.len()).unwrap_or_default()
+                    + serde_json::to_string(&history).map(|s| s.len()).unwrap_or_default();
+                let output_len = text.len()
+                    + tool_calls.iter().map(|call| call.function.arguments.to_string().len()).sum::<usize>();
+                budget.record_completion(input_len, output_len);
+            }
+
+            // No tool calls means the model is done
+            if tool_calls.is_empty() {
+                return Ok(text);
+            }
+
+            let mut assistant_content = vec![];
+            if !text.is_empty() {
+                assistant_content.push(AssistantContent::text(&text));
+            }
+            assistant_content.extend(tool_calls.iter().cloned().map(AssistantContent::ToolCall));
+
+            history.push(message);
+            history.push(Message::Assistant {
+                id: None,
+                content: OneOrMany::many(assistant_content)?,
+            });
+
+            // Feed every tool result back as the next user message
+            let mut tool_results = vec![];
+            for tool_call in tool_calls {
+                let name = &tool_call.function.name;
+                let arguments = &tool_call.function.arguments;
+                let validation = match schemas.get(name) {
+                    Some(schema) => validate_arguments(name, schema, arguments),
+                    None => Ok(()),
+                };
+                let output = match validation {
+                    Ok(()) => match self.tools.call(name, arguments.to_string()).await {
+                        Ok(output) => output,
+                        // Let the model see the error and decide how to recover
+                        Err(e) => format!("Tool error: {}", e),
+                    },
+                    // Malformed arguments never reach the tool, the model gets told what to fix
+                    Err(correction) => {
+                        debug!("Rejected arguments of {}: {}", name, correction);
+                        correction
+                    }
+                };
+                let output = redact(&output);
+                let output = match &self.labels {
+                    Some(labels) => labels.annotate_json(&output),
+                    None => output,
+                };
+
+                events.emit(AgentEvent::ToolResult {
+                    name: tool_call.function.name.clone(),
+                    result: output.clone(),
+                });
+
+                tool_results.push(UserContent::tool_result(
+                    tool_call.id.clone(),
+                    OneOrMany::one(ToolResultContent::text(output)),
+                ));
+            }
+
+            message = Message::User {
+                content: OneOrMany::many(tool_results)?,
+            };
+        }
+
+        Err(anyhow::anyhow!("Sub-agent exceeded {} turns", self.max_turns))
+    }
+}
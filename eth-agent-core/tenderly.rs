@@ -0,0 +1,128 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::TenderlyConfig;
+
+// Calls deeper than this are left out of the trace, they rarely matter and blow up the context
+const MAX_TRACE_DEPTH: usize = 4;
+
+/// A transaction to simulate, amounts in wei
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulationRequest {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub data: Option<String>,
+    #[serde(default)]
+    pub gas_limit: Option<u64>,
+}
+
+/// Tenderly simulation API client, a richer alternative to eth_call on real networks
+pub struct TenderlyClient {
+    access_key: String,
+    config: TenderlyConfig,
+    http: reqwest::Client,
+}
+
+impl TenderlyClient {
+    pub fn new(access_key: String, config: &TenderlyConfig) -> Self {
+        Self {
+            access_key,
+            config: config.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Simulate `request` on the latest block, returning the decoded trace, asset changes and
+    /// revert reason
+    pub async fn simulate(&self, request: &SimulationRequest) -> Result<Value> {
+        if self.config.account.is_empty() || self.config.project.is_empty() {
+            return Err(anyhow::anyhow!("Tenderly account and project are not configured"));
+        }
+
+        let url = format!(
+            "https://api.tenderly.co/api/v1/account/{}/project/{}/simulate",
+            self.config.account, self.config.project
+        );
+        let body = json!({
+            "network_id": self.config.network_id,
+            "from": request.from,
+            "to": request.to,
+            "input": request.data.clone().unwrap_or_else(|| "0x".to_string()),
+            "value": request.value.clone().unwrap_or_else(|| "0".to_string()),
+            "gas": request.gas_limit.unwrap_or(8_000_000),
+            "save": false,
+            "simulation_type": "full",
+        });
+
+        let response: Value = self
+            .http
+            .post(url)
+            .header("X-Access-Key", &self.access_key)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let transaction = response.get("transaction").cloned().unwrap_or_default();
+        let info = transaction.get("transaction_info").cloned().unwrap_or_default();
+
+        let asset_changes: Vec<Value> = info
+            .get("asset_changes")
+            .and_then(|changes| changes.as_array())
+            .into_iter()
+            .flatten()
+            .map(|change| {
+                json!({
+                    "type": change.get("type"),
+                    "from": change.get("from"),
+                    "to": change.get("to"),
+                    "amount": change.get("amount"),
+                    "raw_amount": change.get("raw_amount"),
+                    "symbol": change.pointer("/token_info/symbol"),
+                    "token": change.pointer("/token_info/contract_address"),
+                })
+            })
+            .collect();
+
+        let mut trace = vec![];
+        if let Some(call_trace) = info.get("call_trace") {
+            flatten_trace(call_trace, 0, &mut trace);
+        }
+
+        Ok(json!({
+            "success": transaction.get("status").and_then(|status| status.as_bool()).unwrap_or(false),
+            "error": transaction.get("error_message"),
+            "gas_used": transaction.get("gas_used"),
+            "asset_changes": asset_changes,
+            "trace": trace,
+            "network_id": self.config.network_id,
+        }))
+    }
+}
+
+// Depth-first list of calls with their decoded function, inputs and errors
+fn flatten_trace(call: &Value, depth: usize, trace: &mut Vec<Value>) {
+    if depth > MAX_TRACE_DEPTH {
+        return;
+    }
+
+    trace.push(json!({
+        "depth": depth,
+        "from": call.get("from"),
+        "to": call.get("to"),
+        "function": call.get("function_name"),
+        "input": call.get("decoded_input"),
+        "output": call.get("decoded_output"),
+        "error": call.get("error"),
+    }));
+
+    for child in call.get("calls").and_then(|calls| calls.as_array()).into_iter().flatten() {
+        flatten_trace(child, depth + 1, trace);
+    }
+}
@@ -0,0 +1,648 @@
+//! Test doubles for the orchestration loop: a scripted completion client standing in for the
+//! LLM provider and an in-process fake of the foundry-mcp server. Enabled with the `testing`
+//! feature.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use futures::{stream, FutureExt};
+use rig::{
+    client::{CompletionClient, ProviderClient, ProviderValue},
+    completion::{self, CompletionError, CompletionRequest, CompletionResponse},
+    message::{AssistantContent, Message, UserContent},
+    streaming::{RawStreamingChoice, StreamingCompletionResponse},
+    OneOrMany,
+};
+use rmcp::{
+    model::{
+        CallToolRequestParam, CallToolResult, Content, CreateMessageRequestParam, ListToolsResult, PaginatedRequestParam,
+        ProgressNotificationParam, Role, SamplingMessage, ServerCapabilities, ServerInfo, Tool,
+    },
+    service::{RequestContext, RoleServer},
+    ErrorData, ServerHandler, ServiceExt,
+};
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+
+use crate::agent::EthAgent;
+use crate::config::{AgentConfig, BudgetConfig, GuardConfig, RetryConfig};
+use crate::mcp_client::{spawn_server, McpConnector, SimpleClientService};
+
+/// Model names used by [`test_config`], scripted replies are queued per model
+pub const PLANNER: &str = "planner";
+pub const EXECUTOR: &str = "executor";
+pub const EVALUATOR: &str = "evaluator";
+
+/// Config routing planning, execution and evaluation to separately scripted models, without retries
+pub fn test_config() -> AgentConfig {
+    AgentConfig {
+        planning_model: PLANNER.to_string(),
+        execution_model: EXECUTOR.to_string(),
+        evaluation_model: EVALUATOR.to_string(),
+        retry: RetryConfig {
+            max_attempts: 1,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 1,
+        },
+        guard: GuardConfig::allow_all(),
+        // Rejected steps go straight to a replan, tests of step retries turn them back on
+        step_retries: 0,
+        // Critiques take an extra evaluator reply per plan, tests that check them turn them back on
+        critique_plans: false,
+        // Most scripted steps answer without calling tools, tests of on-chain checks turn them back on
+        ground_evaluations: false,
+        // Snapshots add a tool call to every run, the undo test turns them back on
+        snapshot_local_chains: false,
+        // Plan estimates call extra tools, tests that check them turn them back on
+        budget: BudgetConfig {
+            estimate_plan_cost: false,
+            ..BudgetConfig::default()
+        },
+        ..AgentConfig::default()
+    }
+}
+
+/// An agent wired to the scripted client and the fake MCP server
+pub fn test_agent(client: ScriptedClient, foundry: &FakeFoundry) -> EthAgent<ScriptedClient> {
+    EthAgent::new(client, None, test_config())
+        .expect("agent")
+        .with_mcp_connector(foundry.connector())
+}
+
+/// Connector spawning a fresh server process from `command` for every run, e.g. the real
+/// foundry-mcp binary pointed at a local anvil
+pub fn process_connector(command: impl Fn() -> tokio::process::Command + Send + Sync + 'static) -> McpConnector {
+    Arc::new(move || spawn_server(command()).boxed())
+}
+
+/// Planner output for the given `(agent_name, agent_prompt)` steps
+pub fn plan_json(steps: &[(&str, &str)]) -> String {
+    let steps: Vec<Value> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, (agent_name, agent_prompt))| {
+            json!({
+                "step_number": index + 1,
+                "agent_name": agent_name,
+                "agent_prompt": agent_prompt,
+            })
+        })
+        .collect();
+
+    json!({ "number_of_steps": steps.len(), "steps": steps }).to_string()
+}
+
+/// Evaluator output with the given score
+pub fn evaluation_json(score: u32, reasoning: &str) -> String {
+    json!({ "score": score, "reasoning": reasoning }).to_string()
+}
+
+#[derive(Debug, Clone)]
+pub enum ScriptedReply {
+    Text(String),
+    ToolCall { name: String, arguments: Value },
+}
+
+impl ScriptedReply {
+    pub fn text(text: impl Into<String>) -> Self {
+        ScriptedReply::Text(text.into())
+    }
+
+    pub fn tool_call(name: &str, arguments: Value) -> Self {
+        ScriptedReply::ToolCall {
+            name: name.to_string(),
+            arguments,
+        }
+    }
+}
+
+/// Completion client answering every request with the next reply queued for its model.
+/// Running out of replies is a provider error.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedClient {
+    scripts: Arc<Mutex<HashMap<String, VecDeque<ScriptedReply>>>>,
+    requests: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl ScriptedClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a reply for `model`
+    pub fn reply(self, model: &str, reply: ScriptedReply) -> Self {
+        self.scripts
+            .lock()
+            .unwrap()
+            .entry(model.to_string())
+            .or_default()
+            .push_back(reply);
+        self
+    }
+
+    /// Text of the latest user message of every request sent to `model`, in order
+    pub fn requests(&self, model: &str) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(requested_model, _)| requested_model == model)
+            .map(|(_, prompt)| prompt.clone())
+            .collect()
+    }
+
+    /// Replies that were queued but never requested
+    pub fn remaining(&self, model: &str) -> usize {
+        self.scripts.lock().unwrap().get(model).map(|replies| replies.len()).unwrap_or_default()
+    }
+}
+
+impl CompletionClient for ScriptedClient {
+    type CompletionModel = ScriptedModel;
+
+    fn completion_model(&self, model: &str) -> ScriptedModel {
+        ScriptedModel {
+            model: model.to_string(),
+            client: self.clone(),
+        }
+    }
+}
+
+impl ProviderClient for ScriptedClient {
+    fn from_env() -> Self {
+        Self::new()
+    }
+
+    fn from_val(_input: ProviderValue) -> Self {
+        Self::new()
+    }
+}
+
+rig::impl_conversion_traits!(AsEmbeddings, AsTranscription, AsImageGeneration, AsAudioGeneration for ScriptedClient);
+
+#[derive(Debug, Clone)]
+pub struct ScriptedModel {
+    model: String,
+    client: ScriptedClient,
+}
+
+fn last_user_text(request: &CompletionRequest) -> String {
+    match request.chat_history.iter().last() {
+        Some(Message::User { content }) => content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::Text(text) => Some(text.text.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+impl ScriptedModel {
+    fn next_reply(&self, request: &CompletionRequest) -> Result<ScriptedReply, CompletionError> {
+        self.client
+            .requests
+            .lock()
+            .unwrap()
+            .push((self.model.clone(), last_user_text(request)));
+
+        self.client
+            .scripts
+            .lock()
+            .unwrap()
+            .get_mut(&self.model)
+            .and_then(|replies| replies.pop_front())
+            .ok_or_else(|| CompletionError::ProviderError(format!("No scripted reply left for {}", self.model)))
+    }
+}
+
+impl completion::CompletionModel for ScriptedModel {
+    type Response = ();
+    type StreamingResponse = ();
+
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse<()>, CompletionError> {
+        let choice = match self.next_reply(&request)? {
+            ScriptedReply::Text(text) => AssistantContent::text(text),
+            ScriptedReply::ToolCall { name, arguments } => {
+                AssistantContent::tool_call(format!("call_{}", name), name, arguments)
+            }
+        };
+
+        Ok(CompletionResponse {
+            choice: OneOrMany::one(choice),
+            raw_response: (),
+        })
+    }
+
+    async fn stream(&self, request: CompletionRequest) -> Result<StreamingCompletionResponse<()>, CompletionError> {
+        let choice = match self.next_reply(&request)? {
+            ScriptedReply::Text(text) => RawStreamingChoice::Message(text),
+            ScriptedReply::ToolCall { name, arguments } => RawStreamingChoice::ToolCall {
+                id: format!("call_{}", name),
+                call_id: None,
+                name,
+                arguments,
+            },
+        };
+
+        let chunks = stream::iter(vec![Ok(choice), Ok(RawStreamingChoice::FinalResponse(()))]);
+        Ok(StreamingCompletionResponse::stream(Box::pin(chunks)))
+    }
+}
+
+/// In-process stand-in for foundry-mcp. Every tool returns a canned success payload unless
+/// overridden with [`FakeFoundry::respond`], and every call is recorded.
+#[derive(Debug, Clone, Default)]
+pub struct FakeFoundry {
+    responses: Arc<Mutex<HashMap<String, Value>>>,
+    // One-off answers used up before `responses`, in order
+    queued: Arc<Mutex<HashMap<String, VecDeque<Value>>>>,
+    calls: Arc<Mutex<Vec<(String, Value)>>>,
+    // Tool whose next call takes the server down, like a panic or OOM in the real one
+    crash_on: Arc<Mutex<Option<String>>>,
+    crashed: Arc<Notify>,
+    // Definitions served by list_tools, the canned tools are callable without one
+    listed: Arc<Mutex<Vec<Tool>>>,
+    // Progress message sent by a tool before it answers
+    progress: Arc<Mutex<HashMap<String, String>>>,
+    // Prompt a tool samples the client's model with, its answer is the tool's `summary`
+    sampling: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl FakeFoundry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always answer `tool` with `payload`
+    pub fn respond(self, tool: &str, payload: Value) -> Self {
+        self.responses.lock().unwrap().insert(tool.to_string(), payload);
+        self
+    }
+
+    /// Answer the next call to `tool` with `payload`, later calls get the usual answer
+    pub fn respond_once(self, tool: &str, payload: Value) -> Self {
+        self.queued.lock().unwrap().entry(tool.to_string()).or_default().push_back(payload);
+        self
+    }
+
+    /// Advertise `tool` in `list_tools`, taking any object as arguments
+    pub fn list_tool(self, tool: &str, description: &str) -> Self {
+        let schema = json!({ "type": "object", "properties": {} });
+        let schema = schema.as_object().cloned().unwrap_or_default();
+        self.listed.lock().unwrap().push(Tool::new(tool.to_string(), description.to_string(), Arc::new(schema)));
+        self
+    }
+
+    /// Report progress with `message` before answering calls to `tool`, like a long-running server tool
+    pub fn progress_on(self, tool: &str, message: &str) -> Self {
+        self.progress.lock().unwrap().insert(tool.to_string(), message.to_string());
+        self
+    }
+
+    /// Answer calls to `tool` with `{"success": true, "summary": ...}` sampled from the client's
+    /// model with `prompt`, like a server-side summary
+    pub fn sample_on(self, tool: &str, prompt: &str) -> Self {
+        self.sampling.lock().unwrap().insert(tool.to_string(), prompt.to_string());
+        self
+    }
+
+    /// Drop the connection instead of answering the next call to `tool`
+    pub fn crash_on(self, tool: &str) -> Self {
+        *self.crash_on.lock().unwrap() = Some(tool.to_string());
+        self
+    }
+
+    /// Every `(tool, arguments)` call received, in order
+    pub fn calls(&self) -> Vec<(String, Value)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn called_tools(&self) -> Vec<String> {
+        self.calls().into_iter().map(|(tool, _)| tool).collect()
+    }
+
+    /// Connector serving this fake over an in-memory duplex pipe, for [`EthAgent::with_mcp_connector`]
+    pub fn connector(&self) -> McpConnector {
+        let server = self.clone();
+        Arc::new(move || {
+            let server = server.clone();
+            async move {
+                let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+                let crashed = server.crashed.clone();
+                tokio::spawn(async move {
+                    if let Ok(running) = server.serve(server_io).await {
+                        // Dropping the running service closes the pipe, the client sees a dead server
+                        tokio::select! {
+                            _ = running.waiting() => {}
+                            _ = crashed.notified() => {}
+                        }
+                    }
+                });
+
+                let service = SimpleClientService::default().serve(client_io).await?;
+                Ok(service)
+            }
+            .boxed()
+        })
+    }
+
+    fn payload(&self, tool: &str, arguments: &Value) -> Value {
+        if let Some(payload) = self.queued.lock().unwrap().get_mut(tool).and_then(|queued| queued.pop_front()) {
+            return payload;
+        }
+        if let Some(payload) = self.responses.lock().unwrap().get(tool) {
+            return payload.clone();
+        }
+
+        let argument = |name: &str| arguments.get(name).cloned().unwrap_or(Value::Null);
+        match tool {
+            "balance" => json!({
+                "success": true,
+                "address": argument("address"),
+                "balance": "1000000000000000000",
+                "unit": "wei"
+            }),
+            "erc20_balance" => json!({
+                "success": true,
+                "token_address": argument("token_address"),
+                "account_address": argument("address"),
+                "balance": "1000000",
+                "decimals": 6,
+                "symbol": "USDC"
+            }),
+            "get_price" => json!({
+                "success": true,
+                "asset": argument("asset"),
+                "price_usd": "3000",
+                "source": "chainlink",
+                "updated_at": 1_700_000_000u64,
+                "age_seconds": 60
+            }),
+            "gas_oracle" => json!({
+                "success": true,
+                "source": "node",
+                "chain_id": 31337,
+                "tiers": {
+                    "slow": { "max_fee_per_gas": "2000000000", "max_priority_fee_per_gas": "0", "expected_inclusion_seconds": 60 },
+                    "standard": { "max_fee_per_gas": "2000000000", "max_priority_fee_per_gas": "0", "expected_inclusion_seconds": 24 },
+                    "fast": { "max_fee_per_gas": "2000000000", "max_priority_fee_per_gas": "0", "expected_inclusion_seconds": 12 }
+                }
+            }),
+            "resolve_token" => json!({
+                "success": true,
+                "chain_id": 1,
+                "token": {
+                    "address": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+                    "symbol": argument("symbol"),
+                    "name": "USD Coin",
+                    "decimals": 6
+                }
+            }),
+            "safe_info" => json!({
+                "success": true,
+                "safe": argument("safe"),
+                "owners": ["0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266", "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"],
+                "threshold": "2",
+                "nonce": "0",
+                "version": "1.4.1"
+            }),
+            "propose_safe_transaction" => json!({
+                "success": true,
+                "safe": argument("safe"),
+                "to": argument("to"),
+                "value": argument("value"),
+                "nonce": "0",
+                "safe_tx_hash": format!("0x{}", "cd".repeat(32)),
+                "proposer": argument("proposer"),
+                "proposer_is_owner": true,
+                "confirmations_required": "2"
+            }),
+            "build_user_operation" => json!({
+                "success": true,
+                "account": argument("account"),
+                "to": argument("to"),
+                "value": argument("value"),
+                "entry_point": "0x0000000071727De22E5E9d8BAf0edAc6f37da032",
+                "user_operation": {
+                    "sender": argument("account"),
+                    "nonce": "0x0",
+                    "callData": "0xb61d27f6",
+                    "callGasLimit": "0x186a0",
+                    "verificationGasLimit": "0x186a0",
+                    "preVerificationGas": "0xc350",
+                    "maxFeePerGas": "0x3b9aca00",
+                    "maxPriorityFeePerGas": "0x3b9aca00",
+                    "signature": "0x"
+                },
+                "user_op_hash": format!("0x{}", "ef".repeat(32)),
+                "max_gas": "350000",
+                "max_gas_cost": "350000000000000",
+                "gas_paid_by": "account",
+                "sponsorship": null
+            }),
+            "sign_user_operation" => {
+                let mut user_operation = argument("user_operation");
+                user_operation["signature"] = json!(format!("0x{}1b", "11".repeat(64)));
+                json!({
+                    "success": true,
+                    "owner": argument("owner"),
+                    "user_operation": user_operation,
+                    "user_op_hash": format!("0x{}", "ef".repeat(32))
+                })
+            }
+            "send_user_operation" => json!({
+                "success": true,
+                "status": "included",
+                "account": argument("user_operation")["sender"].clone(),
+                "user_op_hash": format!("0x{}", "ef".repeat(32)),
+                "transaction_hash": format!("0x{}", "ab".repeat(32))
+            }),
+            "sign_permit" => json!({
+                "success": true,
+                "token": argument("token"),
+                "owner": argument("owner"),
+                "spender": argument("spender"),
+                "value": argument("value"),
+                "nonce": "0",
+                "deadline": 1_900_000_000u64,
+                "v": 27,
+                "r": format!("0x{}", "11".repeat(32)),
+                "s": format!("0x{}", "22".repeat(32))
+            }),
+            "validate_address" => json!({
+                "success": true,
+                "valid": true,
+                "address": argument("address")
+            }),
+            "get_contract_code" => json!({
+                "success": false,
+                "error": "No contract code found at address",
+                "address": argument("address")
+            }),
+            "resolve_ens" => json!({
+                "success": true,
+                "name": argument("name"),
+                "address": "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            }),
+            "simulate_transaction" => json!({
+                "success": true,
+                "would_revert": false,
+                "from": argument("from"),
+                "to": argument("to")
+            }),
+            "preview_transaction" => json!({
+                "success": true,
+                "from": argument("from"),
+                "to": argument("to"),
+                "value": argument("value"),
+                "estimated_gas": 21000,
+                "gas_price": "1000000000",
+                "estimated_gas_cost": "21000000000000",
+                "balance_changes": []
+            }),
+            "send_transaction" => json!({
+                "success": true,
+                "transaction_hash": format!("0x{}", "ab".repeat(32)),
+                "from": argument("from"),
+                "to": argument("to"),
+                "value": argument("value"),
+                "nonce": 0
+            }),
+            "build_unsigned_transaction" => {
+                let nonce = arguments.get("nonce").and_then(Value::as_u64).unwrap_or_default();
+                json!({
+                    "success": true,
+                    "from": argument("from"),
+                    "to": argument("to"),
+                    "value": argument("value"),
+                    "nonce": nonce,
+                    "unsigned_transaction": {
+                        "chainId": "0x7a69",
+                        "from": argument("from"),
+                        "to": argument("to"),
+                        "value": argument("value"),
+                        "data": "0x",
+                        "nonce": format!("{:#x}", nonce),
+                        "gas": "0x5208",
+                        "type": "0x0",
+                        "gasPrice": "0x3b9aca00"
+                    },
+                    "eip681": format!("ethereum:{}@31337?value={}&gasLimit=21000", argument("to").as_str().unwrap_or_default(), argument("value").as_str().unwrap_or_default()),
+                    "message": "Not sent: sign and broadcast it with an external wallet"
+                })
+            }
+            "risk_score" => json!({
+                "success": true,
+                "address": argument("address"),
+                "is_contract": true,
+                "score": 0,
+                "level": "low",
+                "reasons": []
+            }),
+            "transaction_status" => json!({
+                "success": true,
+                "hash": argument("hash"),
+                "status": "confirmed",
+                "block_number": 1
+            }),
+            "use_network" => json!({
+                "success": true,
+                "rpc_url": argument("rpc_url"),
+                "chain_id": 31337
+            }),
+            _ => json!({
+                "success": false,
+                "error": format!("Unknown tool {}", tool)
+            }),
+        }
+    }
+}
+
+impl ServerHandler for FakeFoundry {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            ..Default::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: self.listed.lock().unwrap().clone(),
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let arguments = Value::Object(request.arguments.unwrap_or_default());
+        self.calls.lock().unwrap().push((request.name.to_string(), arguments.clone()));
+
+        let crash = {
+            let mut crash_on = self.crash_on.lock().unwrap();
+            let crash = crash_on.as_deref() == Some(&*request.name);
+            if crash {
+                *crash_on = None;
+            }
+            crash
+        };
+        if crash {
+            self.crashed.notify_one();
+            return futures::future::pending().await;
+        }
+
+        let progress = self.progress.lock().unwrap().get(&*request.name).cloned();
+        if let (Some(message), Some(progress_token)) = (progress, context.meta.get_progress_token()) {
+            let _ = context
+                .peer
+                .notify_progress(ProgressNotificationParam {
+                    progress_token,
+                    progress: 1u8.into(),
+                    total: Some(2u8.into()),
+                    message: Some(message),
+                })
+                .await;
+        }
+
+        let sampling = self.sampling.lock().unwrap().get(&*request.name).cloned();
+        if let Some(prompt) = sampling {
+            let result = context
+                .peer
+                .create_message(CreateMessageRequestParam {
+                    messages: vec![SamplingMessage {
+                        role: Role::User,
+                        content: Content::text(prompt),
+                    }],
+                    model_preferences: None,
+                    system_prompt: Some("Summarize for an Ethereum user".to_string()),
+                    include_context: None,
+                    temperature: None,
+                    max_tokens: 200,
+                    stop_sequences: None,
+                    metadata: None,
+                })
+                .await;
+            let payload = match result {
+                Ok(result) => json!({
+                    "success": true,
+                    "summary": result.message.content.as_text().map(|text| text.text.clone()),
+                }),
+                Err(e) => json!({ "success": false, "error": e.to_string() }),
+            };
+            return Ok(CallToolResult::success(vec![Content::text(payload.to_string())]));
+        }
+
+        let payload = self.payload(&request.name, &arguments);
+        Ok(CallToolResult::success(vec![Content::text(payload.to_string())]))
+    }
+}
@@ -0,0 +1,52 @@
+// Classification of transactions and contract code by the action guard
+
+use eth_agent_core::{classify_transaction, has_selfdestruct, ActionClass};
+
+const TOKEN: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+// The spender or operator argument, an address padded to a word
+const OPERATOR_WORD: &str = "00000000000000000000000070997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+fn calldata(selector: &str, amount_word: &str) -> String {
+    format!("0x{}{}{}", selector, OPERATOR_WORD, amount_word)
+}
+
+#[test]
+fn unlimited_approvals_are_told_apart_from_finite_ones() {
+    let unlimited = calldata("095ea7b3", &"f".repeat(64));
+    assert_eq!(classify_transaction(TOKEN, Some(&unlimited)), ActionClass::UnlimitedApproval);
+
+    let finite = calldata("095ea7b3", &format!("{:064x}", 1_000_000));
+    assert_eq!(classify_transaction(TOKEN, Some(&finite)), ActionClass::Approval);
+}
+
+#[test]
+fn approval_for_all_is_unlimited_only_when_granted() {
+    let granted = calldata("a22cb465", &format!("{:064x}", 1));
+    assert_eq!(classify_transaction(TOKEN, Some(&granted)), ActionClass::UnlimitedApproval);
+
+    let revoked = calldata("a22cb465", &"0".repeat(64));
+    assert_eq!(classify_transaction(TOKEN, Some(&revoked)), ActionClass::Approval);
+}
+
+#[test]
+fn truncated_calldata_is_never_an_unlimited_approval() {
+    // The amount word is cut short, so there is no amount to read
+    let truncated = calldata("095ea7b3", &"f".repeat(40));
+    assert_eq!(classify_transaction(TOKEN, Some(&truncated)), ActionClass::Approval);
+
+    // Not even a whole selector
+    assert_eq!(classify_transaction(TOKEN, Some("0x095e")), ActionClass::ValueTransfer);
+    assert_eq!(classify_transaction(TOKEN, None), ActionClass::ValueTransfer);
+    assert_eq!(classify_transaction("", Some("0x6080")), ActionClass::ContractDeployment);
+}
+
+#[test]
+fn finds_selfdestruct_outside_of_push_data() {
+    // PUSH1 0x00, SELFDESTRUCT
+    assert!(has_selfdestruct("0x6000ff"));
+    // PUSH1 0xff, STOP
+    assert!(!has_selfdestruct("0x60ff00"));
+    // PUSH32 ending in 0xff, then STOP
+    assert!(!has_selfdestruct(&format!("0x7f{}00", "ff".repeat(32))));
+    assert!(!has_selfdestruct("not code"));
+}
@@ -0,0 +1,2649 @@
+// Orchestration tests: plan -> execute -> evaluate against a scripted LLM and a fake foundry-mcp
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::U256;
+use async_trait::async_trait;
+use eth_agent_core::testing::*;
+use eth_agent_core::{
+    cast_script, forge_script, render_portfolio, render_report, unsigned_bundle, AddressLabels, AgentConfig, AgentError,
+    AgentEvent, Artifact, BalanceChange, BlocklistConfig, BudgetConfig, BundleExecution, EthAgent, EventSink,
+    ForkLauncher, ForkedNode, FoundryMcpClient, GuardConfig, GuardPolicy, Interaction, MevGuardConfig, Notification,
+    NotificationChannel, Notifier, PackTool, PaymentRequest, PlanStatus, PrivateTransactionsConfig, ReportFormat,
+    RiskGuardConfig, RunJournal, RunRecord, RunsConfig, SafeBundle, SearchOptions, SearchProvider, SearchResult, Severity,
+    StepStatus, ToolPack, UserPrompt, WebhookNotifier, WebhooksConfig, WhatIfConfig, SIGNATURE_HEADER,
+};
+use futures::FutureExt;
+use serde_json::json;
+
+const ALICE: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+const BOB: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+// Answers every question with `answer`, every confirmation with `confirms`, and records what was asked
+#[derive(Default)]
+struct ScriptedUser {
+    answer: Option<String>,
+    confirms: bool,
+    questions: Mutex<Vec<String>>,
+    confirmations: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl Interaction for ScriptedUser {
+    async fn confirm(&self, message: &str) -> bool {
+        self.confirmations.lock().unwrap().push(message.to_string());
+        self.confirms
+    }
+
+    async fn ask(&self, question: &str) -> Option<String> {
+        self.questions.lock().unwrap().push(question.to_string());
+        self.answer.clone()
+    }
+}
+
+// Search provider counting the searches that reached it
+#[derive(Default)]
+struct CountingSearch {
+    searches: AtomicUsize,
+}
+
+#[async_trait]
+impl SearchProvider for CountingSearch {
+    fn name(&self) -> &'static str {
+        "counting"
+    }
+
+    async fn search(&self, query: &str, _options: &SearchOptions) -> anyhow::Result<Vec<SearchResult>> {
+        self.searches.fetch_add(1, Ordering::SeqCst);
+        Ok(vec![SearchResult {
+            title: query.to_string(),
+            url: "https://ethereum.org".to_string(),
+            snippet: "Ethereum".to_string(),
+        }])
+    }
+}
+
+fn balance_plan() -> ScriptedReply {
+    ScriptedReply::text(plan_json(&[("ethereum_agent", "Get the ETH balance of Alice")]))
+}
+
+#[tokio::test]
+async fn runs_plan_wrapped_in_a_json_fence() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(format!(
+            "Here is the plan:\n```json\n{}\n```\nLet me know if you need anything else.",
+            plan_json(&[("ethereum_agent", "Get the ETH balance of Alice")])
+        )))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(95, "Answers the question")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert!(result.error_message.is_none());
+
+    let run = agent.last_run().unwrap();
+    assert_eq!(run.plan.unwrap().steps.len(), 1);
+    assert!(matches!(run.status, PlanStatus::Completed));
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn replans_after_invalid_plan() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text("Sure! First I will check the balance."))
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    let planner_requests = client.requests(PLANNER);
+    assert_eq!(planner_requests.len(), 2);
+    assert!(planner_requests[1].contains("replan"));
+    assert!(planner_requests[1].contains("not valid JSON"));
+}
+
+#[tokio::test]
+async fn replans_plans_that_fail_static_validation() {
+    let bad_checksum = "0xF39fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &format!("Send 1000 wei from {} to Bob", bad_checksum))])))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1.2.3 ETH from Alice to Bob")])))
+        .reply(PLANNER, ScriptedReply::text(json!({
+            "number_of_steps": 2,
+            "steps": [{ "step_number": 1, "agent_name": "ethereum_agent", "agent_prompt": "Get the ETH balance of Alice" }]
+        }).to_string()));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let error = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::RetriesExhausted { .. }));
+    let planner_requests = client.requests(PLANNER);
+    assert!(planner_requests[1].contains(&format!("Step 1 has an address with an invalid checksum: {}", bad_checksum)));
+    assert!(planner_requests[2].contains("Step 1 has an amount that is not a valid number: 1.2.3 ETH"));
+    assert!(error.to_string().contains("number_of_steps is 2 but the plan has 1 steps"));
+    assert_eq!(client.requests(EXECUTOR).len(), 0);
+}
+
+#[tokio::test]
+async fn replans_unknown_sub_agent() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("wizard_agent", "Do magic")])))
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert!(client.requests(PLANNER)[1].contains("Unknown agent name: wizard_agent"));
+}
+
+#[tokio::test]
+async fn replans_when_evaluation_is_below_threshold() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text("The weather is nice"))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(10, "Off topic")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert!(client.requests(PLANNER)[1].contains("Off topic"));
+    assert_eq!(client.remaining(EVALUATOR), 0);
+}
+
+#[tokio::test]
+async fn replans_only_the_steps_after_completed_ones() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Send 1000 wei from Alice to Bob"),
+            ("ethereum_agent", "Get the ETH balance of Bob"),
+        ])))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Get the ETH balance of Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EXECUTOR, ScriptedReply::text("The weather is nice"))
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(10, "Off topic")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob and check Bob's balance")).await.unwrap();
+
+    assert_eq!(result.result, "Bob has 1 ETH");
+    let replan = &client.requests(PLANNER)[1];
+    assert!(replan.contains("Step 1 (ethereum_agent): Send 1000 wei from Alice to Bob\nOutput: Sent 1000 wei to Bob"));
+    assert!(replan.contains("Plan only the remaining steps, numbered from 2"));
+    assert!(client.requests(EXECUTOR)[3].contains("Sent 1000 wei to Bob"));
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "send_transaction").count(), 1);
+
+    let run = agent.last_run().unwrap();
+    let steps: Vec<(u32, bool)> = run
+        .steps
+        .iter()
+        .map(|step| (step.step_number, matches!(step.status, StepStatus::Completed)))
+        .collect();
+    assert_eq!(steps, vec![(1, true), (2, true)]);
+    assert_eq!(run.transaction_hashes().len(), 1);
+}
+
+#[tokio::test]
+async fn retry_resumes_a_failed_run_without_resending_completed_steps() {
+    // No executor reply for the second step, so the first run fails there at the provider
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Send 1000 wei from Alice to Bob"),
+            ("ethereum_agent", "Get the ETH balance of Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob and check Bob's balance")).await.unwrap_err();
+    let failed = agent.last_run().unwrap();
+    assert!(matches!(failed.status, PlanStatus::Failed(_)));
+
+    let client = client
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let result = agent.retry(None).await.unwrap();
+
+    assert_eq!(result.result, "Bob has 1 ETH");
+    assert_eq!(client.requests(PLANNER).len(), 1);
+    assert!(client.requests(EXECUTOR).last().unwrap().contains("Sent 1000 wei to Bob"));
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "send_transaction").count(), 1);
+
+    let run = agent.last_run().unwrap();
+    assert_ne!(run.id, failed.id);
+    assert_eq!(run.prompt.natural_language, failed.prompt.natural_language);
+    let steps: Vec<(u32, bool)> = run
+        .steps
+        .iter()
+        .map(|step| (step.step_number, matches!(step.status, StepStatus::Completed)))
+        .collect();
+    assert_eq!(steps, vec![(1, true), (2, true)]);
+    assert_eq!(run.transaction_hashes().len(), 1);
+
+    // A completed run only reruns a named step
+    assert!(matches!(agent.retry(None).await.unwrap_err(), AgentError::Planning { .. }));
+}
+
+#[tokio::test]
+async fn runs_the_best_scored_of_several_candidate_plans() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("search_agent", "Search for Alice's balance")])))
+        .reply(PLANNER, ScriptedReply::text("Sure! First I will check the balance."))
+        .reply(PLANNER, balance_plan())
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(40, "Searches instead of reading the chain")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(95, "Reads the balance directly")))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        plan_candidates: 3,
+        ..test_config()
+    };
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_event_sink(events);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert_eq!(client.requests(PLANNER).len(), 3);
+    assert!(client.requests(EVALUATOR)[0].contains("Constraints: at most 10 steps"));
+    assert_eq!(client.remaining(EVALUATOR), 0);
+
+    let mut chosen = None;
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::PlanChosen { chosen: index, scores } = event {
+            chosen = Some((index, scores));
+        }
+    }
+    assert_eq!(chosen, Some((1, vec![40, 95])));
+}
+
+#[tokio::test]
+async fn malformed_tool_arguments_never_reach_the_server() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "value": 1000 })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    let mut results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::ToolResult { result, .. } = event {
+            results.push(result);
+        }
+    }
+    let correction: serde_json::Value = serde_json::from_str(&results[0]).unwrap();
+    assert_eq!(correction["error"], "invalid_arguments");
+    assert_eq!(
+        correction["problems"],
+        json!([
+            { "field": "to", "problem": "missing required field" },
+            { "field": "value", "problem": "must be string, got number" }
+        ])
+    );
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "send_transaction").count(), 1);
+}
+
+#[tokio::test]
+async fn results_carry_the_full_transcript() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    assert_eq!(result.plan.unwrap().steps[0].agent_prompt, "Send 1000 wei from Alice to Bob");
+    assert_eq!(result.transaction_hashes, vec![format!("0x{}", "ab".repeat(32))]);
+    let step = &result.steps[0];
+    assert_eq!(step.output.as_deref(), Some("Sent 1000 wei to Bob"));
+    assert_eq!(step.evaluation.as_ref().unwrap().score, 90);
+    let sends: Vec<_> = step.tool_calls.iter().filter(|call| call.tool == "send_transaction").collect();
+    assert_eq!(sends.len(), 1);
+    assert_eq!(sends[0].arguments["value"], "1000");
+    assert!(step.duration().unwrap() >= chrono::Duration::zero());
+}
+
+#[tokio::test]
+async fn results_carry_typed_artifacts() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob and check Bob's balance")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": BOB })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei, Bob has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob and check Bob's balance")).await.unwrap();
+
+    assert_eq!(
+        result.artifacts,
+        vec![
+            Artifact::TransactionHash {
+                step_number: 1,
+                hash: format!("0x{}", "ab".repeat(32)),
+            },
+            Artifact::Balance {
+                step_number: 1,
+                address: BOB.to_string(),
+                token: None,
+                balance: "1000000000000000000".to_string(),
+                decimals: 18,
+            },
+        ]
+    );
+}
+
+// Accepts one HTTP request, answers 200 and hands back its headers and body
+async fn receive_webhook(listener: tokio::net::TcpListener) -> (String, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut request = vec![];
+    let mut buffer = [0; 4096];
+    loop {
+        let read = stream.read(&mut buffer).await.unwrap();
+        request.extend_from_slice(&buffer[..read]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+                .unwrap_or_default();
+            if body.len() >= length {
+                stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+                return (head.to_string(), body.to_string());
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn notifies_webhooks_with_a_signed_summary() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = WebhooksConfig {
+        urls: vec![format!("http://{}/hook", listener.local_addr().unwrap())],
+        ..WebhooksConfig::default()
+    };
+    let received = tokio::spawn(receive_webhook(listener));
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry).with_webhooks(WebhookNotifier::new(Some("shh".to_string()), &config));
+
+    let result = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+    let (head, raw_body) = received.await.unwrap();
+    let body: serde_json::Value = serde_json::from_str(&raw_body).unwrap();
+
+    let hash = format!("0x{}", "ab".repeat(32));
+    assert_eq!(body["event"], "run.completed");
+    assert_eq!(body["run_id"], agent.last_run().unwrap().id);
+    assert_eq!(body["result"], result.result);
+    assert_eq!(body["transaction_hashes"], json!([hash]));
+    assert_eq!(body["artifacts"][0]["kind"], "transaction_hash");
+    let signature = WebhookNotifier::sign("shh", raw_body.as_bytes());
+    assert!(head.lines().any(|line| line.eq_ignore_ascii_case(&format!("{}: {}", SIGNATURE_HEADER, signature))));
+}
+
+#[tokio::test]
+async fn resolves_references_to_earlier_runs_before_planning() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob again")])))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob again"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+    agent.run(UserPrompt::new("Now send him the same amount.")).await.unwrap();
+
+    let planned = &client.requests(PLANNER)[1];
+    assert!(planned.contains(&format!("send him (= {}) the same amount (= 1000 wei).", BOB)));
+}
+
+#[tokio::test]
+async fn what_if_runs_leave_no_references_behind() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Ask who to send to")])))
+        .reply(EXECUTOR, ScriptedReply::text("Nobody was named"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let launcher: ForkLauncher =
+        Arc::new(|_source: String| async { anyhow::Ok(ForkedNode::external("http://127.0.0.1:9999")) }.boxed());
+    let config = AgentConfig {
+        what_if: WhatIfConfig {
+            fork_url: Some("http://mainnet.test".to_string()),
+            ..WhatIfConfig::default()
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_fork_launcher(launcher);
+
+    agent.what_if(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+    agent.run(UserPrompt::new("Now send him the same amount.")).await.unwrap();
+
+    let planned = &client.requests(PLANNER)[1];
+    assert!(planned.contains("Now send him the same amount."));
+}
+
+#[tokio::test]
+async fn fiat_amounts_are_priced_once_and_pinned_in_wei() {
+    // $30 at the fake's $3000 per ETH
+    const WEI: &str = "10000000000000000";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &format!("Send {} wei from Alice to Bob", WEI))])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": WEI })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent $30 worth of ETH to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("Send $30 worth of ETH from Alice to Bob")).await.unwrap();
+
+    let planned = &client.requests(PLANNER)[0];
+    assert!(planned.contains(&format!("$30 worth of ETH (= 0.01 ETH = {} wei at $3000 per ETH)", WEI)));
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "get_price").count(), 1);
+    let mut previews = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::TransactionPreview { preview } = event {
+            previews.push(preview);
+        }
+    }
+    assert_eq!(previews[0]["fiat_amount"], json!("0.01 ETH ≈ $30 at $3000 per ETH"));
+}
+
+#[tokio::test]
+async fn token_amounts_are_pinned_with_the_networks_decimals() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Check Bob's USDC balance")])))
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 5 USDC"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    // USDC as BNB Chain has it
+    let foundry = FakeFoundry::new().respond(
+        "resolve_token",
+        json!({
+            "success": true,
+            "chain_id": 56,
+            "token": { "address": "0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "symbol": "USDC", "name": "USD Coin", "decimals": 18 }
+        }),
+    );
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 5 USDC from Alice to Bob\nthen check Bob's balance")).await.unwrap();
+
+    let planned = &client.requests(PLANNER)[0];
+    assert!(planned.contains("Send 5 USDC (= 5000000000000000000 raw USDC units, 18 decimals) from Alice to Bob\nthen check"));
+}
+
+// Notification channel recording what it was sent
+#[derive(Default)]
+struct RecordingChannel {
+    sent: Mutex<Vec<Notification>>,
+}
+
+#[async_trait]
+impl NotificationChannel for RecordingChannel {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn send(&self, notification: &Notification) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push(notification.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn notifies_channels_at_or_above_their_severity() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut config = test_config();
+    config.notifications.min_steps = 1;
+    let everything = Arc::new(RecordingChannel::default());
+    let critical = Arc::new(RecordingChannel::default());
+    let notifier = Notifier::new()
+        .with_channel(everything.clone(), Severity::Info)
+        .with_channel(critical.clone(), Severity::Critical);
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_notifier(notifier);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    let sent = everything.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].severity, Severity::Info);
+    assert!(sent[0].body.starts_with("1-step plan completed: 1 transaction(s) sent"));
+    assert!(critical.sent.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn notifications_are_redacted() {
+    const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text(format!("Alice has 1 ETH, her private key is 0x{}", PRIVATE_KEY)))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut config = test_config();
+    config.notifications.min_steps = 1;
+    let channel = Arc::new(RecordingChannel::default());
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_notifier(Notifier::new().with_channel(channel.clone(), Severity::Info));
+
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    let sent = channel.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].body.contains("her private key is [REDACTED private key]"));
+    assert!(!sent[0].body.contains(PRIVATE_KEY));
+}
+
+fn grounded_agent(client: ScriptedClient, foundry: &FakeFoundry) -> EthAgent<ScriptedClient> {
+    let config = AgentConfig {
+        ground_evaluations: true,
+        ..test_config()
+    };
+    EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector())
+}
+
+#[tokio::test]
+async fn rejects_transfers_that_never_landed_without_asking_the_evaluator() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"));
+    let foundry = FakeFoundry::new().respond(
+        "transaction_status",
+        json!({ "success": true, "hash": format!("0x{}", "ab".repeat(32)), "status": "not_found" }),
+    );
+    let mut agent = grounded_agent(client.clone(), &foundry);
+
+    assert!(agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.is_err());
+
+    assert!(client.requests(EVALUATOR).is_empty());
+    let evaluation = agent.last_run().unwrap().steps[0].evaluation.clone().unwrap();
+    assert_eq!(evaluation.score, 0);
+    assert!(evaluation.reasoning.contains("never landed"));
+}
+
+#[tokio::test]
+async fn evaluates_landed_transfers_with_the_verified_facts() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = grounded_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    let evaluated = &client.requests(EVALUATOR)[0];
+    assert!(evaluated.contains(&format!("Verified on chain: Transaction 0x{} to {} with 1000 wei was mined successfully", "ab".repeat(32), BOB)));
+}
+
+#[tokio::test]
+async fn replans_with_the_post_condition_that_failed() {
+    let plan = json!({
+        "number_of_steps": 1,
+        "steps": [{
+            "step_number": 1,
+            "agent_name": "ethereum_agent",
+            "agent_prompt": "Send 1000 wei from Alice to Bob",
+            "postconditions": [{ "kind": "balance_increased", "address": BOB, "amount": "1000" }]
+        }]
+    });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan.to_string()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Check whether Bob received 1000 wei")])))
+        .reply(EXECUTOR, ScriptedReply::text("Bob did not receive it"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    // Bob's balance never moves
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    let replanned = &client.requests(PLANNER)[1];
+    assert!(replanned.contains(&format!(
+        "post-condition balance({}) increased by 1000 wei: went from 1000000000000000000 to 1000000000000000000, a change of 0",
+        BOB
+    )));
+    assert_eq!(client.requests(EVALUATOR).len(), 1);
+}
+
+#[tokio::test]
+async fn undoes_runs_on_local_chains_latest_first() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .respond("evm_snapshot", json!({ "success": true, "snapshot_id": "0x1", "block_number": 1 }))
+        .respond("evm_revert", json!({ "success": true, "snapshot_id": "0x1", "block_number": 1 }));
+    let config = AgentConfig {
+        snapshot_local_chains: true,
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+    let run_id = agent.last_run().unwrap().id;
+
+    assert_eq!(agent.undo().await.unwrap(), Some(run_id));
+    assert!(foundry.calls().contains(&("evm_revert".to_string(), json!({ "snapshot_id": "0x1" }))));
+    assert_eq!(agent.undo().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn failed_undos_keep_their_snapshot() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .respond("evm_snapshot", json!({ "success": true, "snapshot_id": "0x1", "block_number": 1 }))
+        .respond_once("evm_revert", json!({ "success": false, "error": "Failed to revert: unknown snapshot" }))
+        .respond("evm_revert", json!({ "success": true, "snapshot_id": "0x1", "block_number": 1 }));
+    let config = AgentConfig {
+        snapshot_local_chains: true,
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+    let run_id = agent.last_run().unwrap().id;
+
+    assert!(agent.undo().await.is_err());
+    assert_eq!(agent.undo().await.unwrap(), Some(run_id));
+}
+
+#[tokio::test]
+async fn previews_plans_on_a_fork_before_replaying_them_for_real() {
+    let send = ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" }));
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, send.clone())
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(EXECUTOR, send)
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    // Alice and Bob on the real network, then on the fork
+    let foundry = FakeFoundry::new()
+        .respond_once("balance", json!({ "success": true, "address": ALICE, "balance": "1000000000000000000", "unit": "wei" }))
+        .respond_once("balance", json!({ "success": true, "address": ALICE, "balance": "999999999999998000", "unit": "wei" }))
+        .respond_once("balance", json!({ "success": true, "address": BOB, "balance": "0", "unit": "wei" }))
+        .respond_once("balance", json!({ "success": true, "address": BOB, "balance": "1000", "unit": "wei" }));
+    let forked = Arc::new(Mutex::new(vec![]));
+    let launcher: ForkLauncher = {
+        let forked = forked.clone();
+        Arc::new(move |source: String| {
+            forked.lock().unwrap().push(source);
+            async { anyhow::Ok(ForkedNode::external("http://127.0.0.1:9999")) }.boxed()
+        })
+    };
+    let config = AgentConfig {
+        what_if: WhatIfConfig {
+            fork_url: Some("http://mainnet.test".to_string()),
+            ..WhatIfConfig::default()
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_fork_launcher(launcher);
+
+    let report = agent.what_if(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    assert_eq!(*forked.lock().unwrap(), vec!["http://mainnet.test".to_string()]);
+    assert!(foundry.calls().contains(&("use_network".to_string(), json!({ "rpc_url": "http://127.0.0.1:9999" }))));
+    assert_eq!(
+        report.balance_changes,
+        vec![
+            BalanceChange {
+                network: None,
+                address: ALICE.to_string(),
+                before: "1000000000000000000".to_string(),
+                after: "999999999999998000".to_string(),
+            },
+            BalanceChange {
+                network: None,
+                address: BOB.to_string(),
+                before: "0".to_string(),
+                after: "1000".to_string(),
+            },
+        ]
+    );
+
+    // The replay runs the previewed plan on the real network without planning it again
+    let calls_before = foundry.calls().len();
+    agent.replay(report).await.unwrap();
+    assert_eq!(client.requests(PLANNER).len(), 1);
+    let replayed: Vec<String> = foundry.calls().into_iter().skip(calls_before).map(|(tool, _)| tool).collect();
+    assert!(replayed.contains(&"send_transaction".to_string()));
+    assert!(!replayed.contains(&"use_network".to_string()));
+}
+
+#[tokio::test]
+async fn exports_unsigned_transactions_instead_of_sending() {
+    let send = ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" }));
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob twice")])))
+        .reply(EXECUTOR, send.clone())
+        .reply(EXECUTOR, send)
+        .reply(EXECUTOR, ScriptedReply::text("Two transfers of 1000 wei to Bob are ready to sign"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        unsigned_transactions: true,
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    let result = agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob twice")).await.unwrap();
+
+    let tools = foundry.called_tools();
+    assert!(!tools.contains(&"send_transaction".to_string()));
+    assert!(result.transaction_hashes.is_empty());
+    // Nothing is mined in between, so the second transaction takes the next nonce itself
+    let nonces: Vec<Option<u64>> = foundry
+        .calls()
+        .into_iter()
+        .filter(|(tool, _)| tool == "build_unsigned_transaction")
+        .map(|(_, arguments)| arguments.get("nonce").and_then(|nonce| nonce.as_u64()))
+        .collect();
+    assert_eq!(nonces, vec![None, Some(1)]);
+
+    let bundle = unsigned_bundle(&agent.last_run().unwrap());
+    let transactions = bundle["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1]["transaction"]["nonce"], json!("0x1"));
+    assert_eq!(transactions[0]["eip681"], json!(format!("ethereum:{}@31337?value=1000&gasLimit=21000", BOB)));
+}
+
+#[tokio::test]
+async fn exports_sent_transactions_as_a_forge_script() {
+    let deployed = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Deploy the counter from Alice and increment it")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": "", "value": "0", "data": "0x6080" })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": deployed, "value": "0", "data": "0xd09de08a" })))
+        .reply(EXECUTOR, ScriptedReply::text("Deployed the counter and incremented it"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().respond_once(
+        "send_transaction",
+        json!({ "success": true, "transaction_hash": format!("0x{}", "cd".repeat(32)), "contract_address": deployed }),
+    );
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new("Deploy the counter from Alice and increment it")).await.unwrap();
+
+    let script = forge_script(&agent.last_run().unwrap()).unwrap();
+    assert!(script.contains(&format!("vm.startBroadcast({});", ALICE)));
+    assert!(script.contains("address deployed1 = deploy(hex\"6080\", 0);"));
+    // The replayed deployment gets a new address, the call follows it
+    assert!(script.contains("send(deployed1, 0, hex\"d09de08a\");"));
+    assert!(!script.contains(deployed));
+}
+
+#[tokio::test]
+async fn exports_what_a_run_did_on_chain_as_cast_commands() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob and check Bob's balance")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": BOB })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei, Bob has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob and check Bob's balance")).await.unwrap();
+
+    let script = cast_script(&agent.last_run().unwrap()).unwrap();
+    let commands: Vec<String> = script.lines().filter(|line| line.starts_with("cast ")).map(str::to_string).collect();
+    assert_eq!(
+        commands,
+        vec![
+            format!("cast send {} --value 1000 --from {} \"$@\"", BOB, ALICE),
+            format!("cast balance {}", BOB),
+        ]
+    );
+}
+
+fn safe_bundle(chain_id: &str) -> SafeBundle {
+    let bundle = json!({
+        "version": "1.0",
+        "chainId": chain_id,
+        "meta": { "name": "Payroll", "createdFromSafeAddress": BOB, "createdFromOwnerAddress": ALICE },
+        "transactions": [
+            { "to": BOB, "value": "1000", "data": null },
+            {
+                "to": BOB,
+                "value": "0",
+                "data": null,
+                "contractMethod": { "name": "transfer", "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }] },
+                "contractInputsValues": { "to": ALICE, "amount": "5" }
+            }
+        ]
+    });
+    SafeBundle::from_json(&bundle.to_string()).unwrap()
+}
+
+#[tokio::test]
+async fn runs_safe_transaction_builder_bundles_without_planning() {
+    let client = ScriptedClient::new()
+        .reply(EXECUTOR, ScriptedReply::text("Proposed the transfer of 1000 wei"))
+        .reply(EXECUTOR, ScriptedReply::text("Proposed the token transfer"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run_safe_bundle(&safe_bundle("31337"), BundleExecution::Propose { proposer: None }).await.unwrap();
+
+    assert!(matches!(result.status, PlanStatus::Completed));
+    assert!(client.requests(PLANNER).is_empty());
+    let requests = client.requests(EXECUTOR);
+    let proposal = format!("Propose a Safe transaction on Safe {} to {} with value 1000 wei and no data, proposed by {}", BOB, BOB, ALICE);
+    assert!(requests[0].starts_with(&proposal));
+    assert!(requests[1].contains(&format!("calling transfer(address,uint256) with arguments [{}, 5]", ALICE)));
+    assert!(requests[1].contains("Safe nonce 1 above its current one"));
+
+    // The fake node is on chain 31337, a mainnet bundle is refused before anything runs
+    let refused = agent.run_safe_bundle(&safe_bundle("1"), BundleExecution::Send { from: BOB.to_string() }).await;
+    assert!(matches!(refused, Err(AgentError::PolicyViolation { .. })));
+}
+
+#[tokio::test]
+async fn pays_eip681_requests_once_confirmed_without_planning() {
+    let request = PaymentRequest::parse(&format!("ethereum:{}@31337?value=1e3", BOB)).unwrap();
+    assert_eq!(request.amount, U256::from(1000));
+    let client = ScriptedClient::new()
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    // Bob's balance before and after the payment, for its post-condition
+    let foundry = FakeFoundry::new()
+        .respond_once("balance", json!({ "success": true, "address": BOB, "balance": "1000000000000000000" }))
+        .respond_once("balance", json!({ "success": true, "address": BOB, "balance": "1000000000000001000" }));
+    let mut declining = test_agent(client.clone(), &foundry).with_interaction(Arc::new(ScriptedUser::default()));
+
+    let declined = declining.pay(&request, Some(ALICE.to_string())).await;
+    assert!(matches!(declined, Err(AgentError::PolicyViolation { .. })));
+    assert!(client.requests(EXECUTOR).is_empty());
+
+    let confirming = Arc::new(ScriptedUser {
+        confirms: true,
+        ..ScriptedUser::default()
+    });
+    let mut agent = test_agent(client.clone(), &foundry).with_interaction(confirming);
+    let result = agent.pay(&request, Some(ALICE.to_string())).await.unwrap();
+
+    assert!(matches!(result.status, PlanStatus::Completed));
+    assert!(client.requests(PLANNER).is_empty());
+    assert!(client.requests(EXECUTOR)[0].starts_with(&format!("Send 1000 wei from {} to {}", ALICE, BOB)));
+    assert!(foundry.called_tools().contains(&"send_transaction".to_string()));
+}
+
+#[tokio::test]
+async fn staking_steps_are_confirmed_and_send_the_built_transaction() {
+    const STETH: &str = "0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84";
+    let step = format!("Stake 1 ETH (= 1000000000000000000 wei) from {} with Lido", ALICE);
+    let stake = json!({ "action": "stake", "protocol": "lido", "from": ALICE, "amount": "1000000000000000000" });
+    let submit = format!("0xa1903eab{}", "0".repeat(64));
+    let transaction = json!({ "from": ALICE, "to": STETH, "value": "1000000000000000000", "data": submit });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &step)])))
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &step)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("staking_transaction", stake.clone()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", transaction.clone()))
+        .reply(EXECUTOR, ScriptedReply::text("Staked 1 ETH with Lido"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("staking_transaction", "Build the transaction of a staking action")
+        .respond("staking_transaction", json!({ "success": true, "protocol": "lido", "action": "stake", "transaction": transaction }));
+    let config = AgentConfig {
+        guard: GuardConfig::default(),
+        ..test_config()
+    };
+
+    let mut declining = EthAgent::new(client.clone(), None, config.clone())
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(Arc::new(ScriptedUser::default()));
+    let declined = declining.run(UserPrompt::new("Stake 1 ETH for me")).await;
+    assert!(matches!(declined, Err(AgentError::PolicyViolation { .. })));
+    assert!(client.requests(EXECUTOR).is_empty());
+
+    let confirming = Arc::new(ScriptedUser {
+        confirms: true,
+        ..ScriptedUser::default()
+    });
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(confirming);
+    agent.run(UserPrompt::new("Stake 1 ETH for me")).await.unwrap();
+
+    assert_eq!(foundry.calls()[0], ("staking_transaction".to_string(), stake));
+    let (_, sent) = foundry.calls().into_iter().find(|(tool, _)| tool == "send_transaction").unwrap();
+    assert_eq!(sent["to"], json!(STETH));
+}
+
+#[tokio::test]
+async fn bulk_payouts_are_sent_as_one_multicall_transaction() {
+    const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+    const CAROL: &str = "0x90F79bf6EB2c4f870365E785982E1f101E93b906";
+    let step = format!("Send 1000 wei each from {} to {} and {}", ALICE, BOB, CAROL);
+    let batch = json!({
+        "from": ALICE,
+        "calls": [{ "to": BOB, "value": "1000" }, { "to": CAROL, "value": "1000" }]
+    });
+    let transaction = json!({ "from": ALICE, "to": MULTICALL3, "value": "2000", "data": "0x174dea71" });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &step)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("batch_calls", batch.clone()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", transaction.clone()))
+        .reply(EXECUTOR, ScriptedReply::text("Paid Bob and Carol in one transaction"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("batch_calls", "Batch independent calls from one sender into a single Multicall3 transaction")
+        .respond("batch_calls", json!({ "success": true, "total_value": "2000", "base_gas_saved": 21000, "transaction": transaction }));
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new(&step)).await.unwrap();
+
+    assert_eq!(foundry.calls()[0], ("batch_calls".to_string(), batch));
+    let sent: Vec<serde_json::Value> = foundry
+        .calls()
+        .into_iter()
+        .filter(|(tool, _)| tool == "send_transaction")
+        .map(|(_, arguments)| arguments)
+        .collect();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["to"], json!(MULTICALL3));
+    assert_eq!(sent[0]["value"], json!("2000"));
+}
+
+fn critiquing_agent(client: ScriptedClient, foundry: &FakeFoundry) -> EthAgent<ScriptedClient> {
+    let config = AgentConfig {
+        critique_plans: true,
+        ..test_config()
+    };
+    EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector())
+}
+
+#[tokio::test]
+async fn runs_the_plan_patched_by_the_critique() {
+    let patched = json!({
+        "blocking_issues": ["Amount is 1 ETH instead of 1000 wei"],
+        "warnings": [],
+        "patched_plan": serde_json::from_str::<serde_json::Value>(&plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])).unwrap()
+    });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1 ETH from Alice to Bob")])))
+        .reply(EVALUATOR, ScriptedReply::text(patched.to_string()))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = critiquing_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    assert_eq!(client.requests(PLANNER).len(), 1);
+    assert!(client.requests(EVALUATOR)[0].contains("Send 1 ETH from Alice to Bob"));
+    assert!(client.requests(EXECUTOR)[0].starts_with("Send 1000 wei from Alice to Bob"));
+    assert_eq!(agent.last_run().unwrap().steps[0].agent_prompt, "Send 1000 wei from Alice to Bob");
+}
+
+#[tokio::test]
+async fn replans_when_the_critique_finds_blocking_issues() {
+    let blocked = json!({ "blocking_issues": ["Checks Bob instead of Alice"], "patched_plan": null });
+    let passed = json!({ "blocking_issues": [], "warnings": ["Could validate the address first"] });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Get the ETH balance of Bob")])))
+        .reply(PLANNER, balance_plan())
+        .reply(EVALUATOR, ScriptedReply::text(blocked.to_string()))
+        .reply(EVALUATOR, ScriptedReply::text(passed.to_string()))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = critiquing_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert!(client.requests(PLANNER)[1].contains("Plan critique found blocking issues: Checks Bob instead of Alice"));
+    assert_eq!(client.requests(EXECUTOR).len(), 1);
+}
+
+fn retrying_agent(client: ScriptedClient, foundry: &FakeFoundry) -> EthAgent<ScriptedClient> {
+    let config = AgentConfig {
+        step_retries: 2,
+        ..test_config()
+    };
+    EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector())
+}
+
+#[tokio::test]
+async fn retries_a_rejected_step_without_replanning() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Get the ETH balance of Alice"),
+            ("ethereum_agent", "Get the ETH balance of Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EXECUTOR, ScriptedReply::text("The weather is nice"))
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(10, "Off topic")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = retrying_agent(client.clone(), &foundry).with_event_sink(events);
+
+    let result = agent.run(UserPrompt::new("What are the ETH balances of Alice and Bob?")).await.unwrap();
+
+    assert_eq!(result.result, "Bob has 1 ETH");
+    assert_eq!(client.requests(PLANNER).len(), 1);
+    let executed = client.requests(EXECUTOR);
+    assert_eq!(executed.len(), 3);
+    assert!(executed[2].contains("answered \"The weather is nice\" and was rejected: Off topic"));
+
+    let mut retries = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::StepRetry { step_number, attempt, .. } = event {
+            retries.push((step_number, attempt));
+        }
+    }
+    assert_eq!(retries, vec![(2, 2)]);
+}
+
+#[tokio::test]
+async fn retried_steps_are_told_which_transactions_were_sent() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Done"))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(20, "Doesn't say what was sent")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = retrying_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    let retry = client.requests(EXECUTOR).last().unwrap().clone();
+    assert!(retry.contains(&format!("already sent transactions 0x{}", "ab".repeat(32))));
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "send_transaction").count(), 1);
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn retried_steps_confirm_repeated_sends_again() {
+    let send = ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" }));
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, send.clone())
+        .reply(EXECUTOR, ScriptedReply::text("Done"))
+        .reply(EXECUTOR, send)
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(20, "Doesn't say what was sent")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let config = AgentConfig {
+        step_retries: 2,
+        guard: GuardConfig {
+            value_transfer: GuardPolicy::Confirm,
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let foundry = FakeFoundry::new();
+    let user = Arc::new(ScriptedUser {
+        confirms: true,
+        ..ScriptedUser::default()
+    });
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user.clone());
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    // The step once, then the same transaction for each attempt that sent it
+    let confirmations = user.confirmations.lock().unwrap();
+    assert_eq!(confirmations.len(), 3);
+    assert!(confirmations[0].starts_with("Step 1"));
+    assert_eq!(confirmations[1], confirmations[2]);
+    assert_eq!(foundry.called_tools().iter().filter(|tool| *tool == "send_transaction").count(), 2);
+}
+
+#[tokio::test]
+async fn score_at_threshold_passes() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(test_config().evaluation_threshold, "Borderline")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await;
+
+    assert!(result.is_ok());
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_plan_retries() {
+    let mut client = ScriptedClient::new();
+    for _ in 0..3 {
+        client = client
+            .reply(PLANNER, balance_plan())
+            .reply(EXECUTOR, ScriptedReply::text("The weather is nice"))
+            .reply(EVALUATOR, ScriptedReply::text(evaluation_json(5, "Off topic")));
+    }
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let error = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap_err();
+
+    match error {
+        AgentError::RetriesExhausted { attempts, last_error } => {
+            assert_eq!(attempts, 3);
+            assert!(matches!(*last_error, AgentError::Evaluation { score: Some(5), .. }));
+        }
+        other => panic!("expected RetriesExhausted, got {:?}", other),
+    }
+    assert!(matches!(agent.last_run().unwrap().status, PlanStatus::Failed(_)));
+}
+
+#[tokio::test]
+async fn stops_at_max_steps_with_partial_results() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Get the ETH balance of Alice"),
+            ("ethereum_agent", "Get the ETH balance of Bob"),
+            ("ethereum_agent", "Get the ETH balance of Vitalik"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 2 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        max_steps: 2,
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    let result = agent.run(UserPrompt::new("What are the balances of Alice, Bob and Vitalik?")).await.unwrap();
+
+    assert!(matches!(result.status, PlanStatus::MaxStepsReached));
+    assert!(result.error_message.is_some());
+    assert_eq!(result.result, "Alice has 1 ETH\nBob has 2 ETH");
+    assert_eq!(client.requests(EXECUTOR).len(), 2);
+
+    let run = agent.last_run().unwrap();
+    assert!(matches!(run.status, PlanStatus::MaxStepsReached));
+    assert_eq!(run.plan.unwrap().current_step, 2);
+    assert!(matches!(run.steps[2].status, StepStatus::Skipped));
+}
+
+#[tokio::test]
+async fn asks_user_and_passes_answer_to_later_steps() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ask_user", "How much ETH should be sent to Bob?"),
+            ("ethereum_agent", "Send the amount the user asked for from Alice to Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 0.1 ETH to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let user = Arc::new(ScriptedUser {
+        answer: Some("0.1 ETH".to_string()),
+        ..ScriptedUser::default()
+    });
+    let mut agent = test_agent(client.clone(), &foundry).with_interaction(user.clone());
+
+    let result = agent.run(UserPrompt::new("Send some ETH to Bob")).await.unwrap();
+
+    assert_eq!(result.result, "Sent 0.1 ETH to Bob");
+    assert_eq!(*user.questions.lock().unwrap(), vec!["How much ETH should be sent to Bob?"]);
+    assert!(client.requests(EXECUTOR)[0].contains("0.1 ETH"));
+    // The answer itself is not evaluated
+    assert_eq!(client.requests(EVALUATOR).len(), 1);
+}
+
+#[tokio::test]
+async fn answers_are_redacted_before_later_steps_see_them() {
+    const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ask_user", "Which account should send?"),
+            ("ethereum_agent", "Send 0.1 ETH from the account the user named to Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 0.1 ETH to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let user = Arc::new(ScriptedUser {
+        answer: Some(format!("the one with key {}", PRIVATE_KEY)),
+        ..ScriptedUser::default()
+    });
+    let mut agent = test_agent(client.clone(), &foundry).with_interaction(user);
+
+    agent.run(UserPrompt::new("Send 0.1 ETH to Bob")).await.unwrap();
+
+    let executed = &client.requests(EXECUTOR)[0];
+    assert!(executed.contains("[REDACTED private key]"));
+    assert!(!executed.contains(PRIVATE_KEY));
+}
+
+#[tokio::test]
+async fn streamed_tokens_are_redacted() {
+    const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text(format!("Alice has 1 ETH\nHer private key is 0x{}", PRIVATE_KEY)))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    let mut streamed = String::new();
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::Token(token) = event {
+            streamed.push_str(&token);
+        }
+    }
+    assert_eq!(streamed, "Alice has 1 ETH\nHer private key is [REDACTED private key]");
+}
+
+#[tokio::test]
+async fn unanswered_question_ends_the_run() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ask_user", "Which Bob do you mean?")])));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let error = agent.run(UserPrompt::new("Send 1 ETH to Bob")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::ClarificationNeeded { question } if question == "Which Bob do you mean?"));
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn unlimited_approval_needs_the_confirmation_phrase() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Get the ETH balance of Alice"),
+            ("ethereum_agent", "Approve an unlimited USDC allowance for Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        guard: GuardConfig::default(),
+        ..test_config()
+    };
+    let user = Arc::new(ScriptedUser {
+        answer: Some("yes".to_string()),
+        ..ScriptedUser::default()
+    });
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user.clone());
+
+    let error = agent.run(UserPrompt::new("Check Alice's balance, then let Bob spend all her USDC")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    // The read-only step ran without asking, the approval was stopped before reaching the executor
+    let questions = user.questions.lock().unwrap();
+    assert_eq!(questions.len(), 1);
+    assert!(questions[0].contains("unlimited token approval"));
+    assert_eq!(client.requests(EXECUTOR).len(), 1);
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn allowed_steps_still_confirm_the_transactions_they_send() {
+    const EVE: &str = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+    let prompt = format!("Send 1000 wei from {} to {}", ALICE, BOB);
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", &prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": EVE, "value": "10000000000000000000" })),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("Sent"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let config = AgentConfig {
+        guard: GuardConfig {
+            value_transfer: GuardPolicy::Confirm,
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let foundry = FakeFoundry::new();
+    let user = Arc::new(ScriptedUser {
+        confirms: true,
+        ..ScriptedUser::default()
+    });
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user.clone());
+
+    agent.run(UserPrompt::new(prompt.clone())).await.unwrap();
+
+    // The step's approval covered its prompt, the send the model chose was asked about on its own
+    let confirmations = user.confirmations.lock().unwrap();
+    assert_eq!(confirmations.len(), 2);
+    assert!(confirmations[0].starts_with("Step 1"));
+    assert!(confirmations[1].contains("10000000000000000000 wei"));
+}
+
+#[tokio::test]
+async fn unlimited_permits_are_guarded_like_approvals() {
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Sign a USDC permit from Alice for Bob")])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call(
+                "sign_permit",
+                json!({ "token": USDC, "owner": ALICE, "spender": BOB, "value": U256::MAX.to_string() }),
+            ),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("The permit was refused"));
+    let foundry = FakeFoundry::new();
+    // Plain approvals are fine, unlimited ones need a yes the user won't give
+    let config = AgentConfig {
+        guard: GuardConfig {
+            approval: GuardPolicy::Allow,
+            unlimited_approval: GuardPolicy::Confirm,
+            ..GuardConfig::default()
+        },
+        ..test_config()
+    };
+    let user = Arc::new(ScriptedUser::default());
+    let mut agent = EthAgent::new(client.clone(), None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user);
+
+    let error = agent.run(UserPrompt::new("Sign a USDC permit from Alice for Bob")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert!(!foundry.called_tools().contains(&"sign_permit".to_string()));
+}
+
+#[tokio::test]
+async fn sign_ins_need_the_users_approval() {
+    let prompt = "Sign in to app.example.com as Alice";
+    let sign_in = json!({ "address": ALICE, "domain": "app.example.com", "uri": "https://app.example.com/login", "nonce": "k3p9x2m4q7" });
+    let script = || {
+        ScriptedClient::new()
+            .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+            .reply(EXECUTOR, ScriptedReply::tool_call("sign_in_with_ethereum", sign_in.clone()))
+            .reply(EXECUTOR, ScriptedReply::text("Signed the sign-in message"))
+            .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Signed")))
+    };
+    let config = || AgentConfig {
+        guard: GuardConfig {
+            sign_in: GuardPolicy::Confirm,
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+
+    let foundry = FakeFoundry::new().respond(
+        "sign_in_with_ethereum",
+        json!({ "success": true, "address": ALICE, "message": "app.example.com wants you to sign in", "signature": format!("0x{}", "ab".repeat(65)) }),
+    );
+    let user = Arc::new(ScriptedUser {
+        confirms: true,
+        ..ScriptedUser::default()
+    });
+    let mut agent = EthAgent::new(script(), None, config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user);
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+    let calls = foundry.calls();
+    let (_, arguments) = calls.iter().find(|(tool, _)| tool == "sign_in_with_ethereum").unwrap();
+    assert_eq!(arguments["nonce"], "k3p9x2m4q7");
+    assert!(arguments.get("statement").is_none());
+
+    let foundry = FakeFoundry::new();
+    let mut agent = EthAgent::new(script(), None, config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(Arc::new(ScriptedUser::default()));
+    let error = agent.run(UserPrompt::new(prompt)).await.unwrap_err();
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert!(!foundry.called_tools().contains(&"sign_in_with_ethereum".to_string()));
+}
+
+#[tokio::test]
+async fn sends_smart_account_calls_as_signed_user_operations() {
+    const ACCOUNT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    let prompt = "Send 1000 wei from Alice's smart account to Bob";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call("send_user_operation", json!({ "account": ACCOUNT, "owner": ALICE, "to": BOB, "value": "1000" })),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei from the smart account"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+
+    let user_op_tools: Vec<String> =
+        foundry.called_tools().into_iter().filter(|tool| tool.ends_with("_user_operation")).collect();
+    assert_eq!(user_op_tools, vec!["build_user_operation", "sign_user_operation", "send_user_operation"]);
+    // The owner's signature is what reaches the bundler
+    let (_, submitted) = foundry.calls().into_iter().find(|(tool, _)| tool == "send_user_operation").unwrap();
+    assert_ne!(submitted["user_operation"]["signature"], json!("0x"));
+    assert!(!foundry.called_tools().contains(&"send_transaction".to_string()));
+    // The bundle transaction counts as the run's transaction
+    assert_eq!(agent.last_run().unwrap().transaction_hashes(), vec![format!("0x{}", "ab".repeat(32))]);
+}
+
+#[tokio::test]
+async fn lists_node_and_labeled_accounts_without_the_llm() {
+    let foundry = FakeFoundry::new().respond(
+        "accounts",
+        json!({
+            "success": true,
+            "chain_id": 31337,
+            "accounts": [
+                { "address": ALICE, "source": "node", "balance": "10000000000000000000000", "nonce": 3 },
+                { "address": BOB, "source": "requested", "balance": "0", "nonce": 0 }
+            ]
+        }),
+    );
+    let config = AgentConfig {
+        labels: [(BOB.to_lowercase(), "Cold wallet".to_string())].into(),
+        ..test_config()
+    };
+    let client = ScriptedClient::new();
+    let agent = EthAgent::new(client.clone(), None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    let accounts = agent.accounts().await.unwrap();
+
+    let (_, arguments) = foundry.calls().into_iter().find(|(tool, _)| tool == "accounts").unwrap();
+    assert_eq!(arguments, json!({ "addresses": [BOB.to_lowercase()] }));
+    assert_eq!(accounts.len(), 2);
+    assert_eq!((accounts[0].nonce, accounts[0].label.as_deref()), (3, None));
+    assert_eq!((accounts[1].source.as_str(), accounts[1].label.as_deref()), ("requested", Some("Cold wallet")));
+    assert!(client.requests(PLANNER).is_empty());
+}
+
+#[tokio::test]
+async fn reloaded_config_applies_without_rebuilding_the_agent() {
+    const ROUTER: &str = "0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45";
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        labels: [(ROUTER.to_lowercase(), "My router".to_string())].into(),
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(ScriptedClient::new(), None, config.clone())
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+    assert!(agent.reload_config(config.clone()).is_empty());
+
+    let changed = agent.reload_config(AgentConfig {
+        evaluation_threshold: 95,
+        labels: [(ALICE.to_string(), "Hot wallet".to_string())].into(),
+        ..config
+    });
+
+    assert_eq!(changed, vec!["evaluation_threshold", "labels"]);
+    assert_eq!(agent.config().evaluation_threshold, 95);
+    assert_eq!(agent.labels().describe(ALICE), "Hot wallet (0xf39F…2266)");
+    // The dropped user label falls back to the built-in one
+    assert_eq!(agent.labels().describe(ROUTER), "Uniswap V3 Router 2 (0x68b3…Fc45)");
+}
+
+#[tokio::test]
+async fn labels_addresses_in_previews_and_tool_results() {
+    const ROUTER: &str = "0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to the router")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": ROUTER, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to the router"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        labels: [(ALICE.to_lowercase(), "Hot wallet".to_string())].into(),
+        ..test_config()
+    };
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_event_sink(events);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to the Uniswap router")).await.unwrap();
+
+    let mut previews = vec![];
+    let mut results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        match event {
+            AgentEvent::TransactionPreview { preview } => previews.push(preview),
+            AgentEvent::ToolResult { result, .. } => results.push(result),
+            _ => {}
+        }
+    }
+    // The user's own label and a well-known contract, keyed by the address as it appears
+    assert_eq!(previews[0]["address_labels"], json!({ ALICE: "Hot wallet", ROUTER: "Uniswap V3 Router 2" }));
+    let sent: serde_json::Value = serde_json::from_str(&results[0]).unwrap();
+    assert_eq!(sent["to"], json!(ROUTER));
+    assert_eq!(sent["address_labels"][ROUTER], json!("Uniswap V3 Router 2"));
+    assert_eq!(agent.labels().describe(ROUTER), "Uniswap V3 Router 2 (0x68b3…Fc45)");
+}
+
+#[tokio::test]
+async fn previews_paymaster_sponsorship_before_signing() {
+    const ACCOUNT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+    const PAYMASTER: &str = "0x00000000000000fB866DaAA79352cC568a005D96";
+    let prompt = "Send 1000 wei from Alice's smart account to Bob without paying gas";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call(
+                "send_user_operation",
+                json!({ "account": ACCOUNT, "owner": ALICE, "to": BOB, "value": "1000", "sponsored": true }),
+            ),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei, the paymaster paid the gas"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().respond(
+        "build_user_operation",
+        json!({
+            "success": true,
+            "user_operation": { "sender": ACCOUNT, "paymaster": PAYMASTER, "signature": "0x" },
+            "max_gas": "350000",
+            "max_gas_cost": "350000000000000",
+            "gas_paid_by": "paymaster",
+            "sponsorship": { "paymaster": PAYMASTER, "policy": "sp_test", "max_sponsored_cost": "350000000000000" }
+        }),
+    );
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client, &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+
+    let (_, built) = foundry.calls().into_iter().find(|(tool, _)| tool == "build_user_operation").unwrap();
+    assert_eq!(built["sponsored"], json!(true));
+    let mut previews = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::TransactionPreview { preview } = event {
+            previews.push(preview);
+        }
+    }
+    assert_eq!(previews.len(), 1);
+    assert_eq!(previews[0]["sponsorship"]["paymaster"], json!(PAYMASTER));
+    assert_eq!(previews[0]["from"], json!(ACCOUNT));
+    // The paymaster's fields are part of what the owner signs
+    let (_, signed) = foundry.calls().into_iter().find(|(tool, _)| tool == "sign_user_operation").unwrap();
+    assert_eq!(signed["user_operation"]["paymaster"], json!(PAYMASTER));
+}
+
+#[tokio::test]
+async fn private_transactions_go_through_the_network_private_rpc() {
+    const PROTECT: &str = "https://rpc.flashbots.net/fast";
+    let prompt = "Send 1000 wei from Alice to Bob without exposing it to the mempool";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000", "private": true })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei privately"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        private_transactions: PrivateTransactionsConfig {
+            rpcs: [("default".to_string(), PROTECT.to_string())].into(),
+            always: false,
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+
+    let (_, sent) = foundry.calls().into_iter().find(|(tool, _)| tool == "send_transaction").unwrap();
+    assert_eq!(sent["private_rpc"], json!(PROTECT));
+}
+
+#[tokio::test]
+async fn private_transactions_are_refused_without_a_private_rpc() {
+    let prompt = "Send 1000 wei from Alice to Bob without exposing it to the mempool";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000", "private": true })))
+        .reply(EXECUTOR, ScriptedReply::text("No private RPC, nothing was sent"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+
+    assert!(!foundry.called_tools().contains(&"send_transaction".to_string()));
+}
+
+// A preview of a Uniswap V2 swap with 5% slippage tolerance on a public network
+fn exposed_swap_preview() -> serde_json::Value {
+    json!({
+        "success": true,
+        "from": ALICE,
+        "to": "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D",
+        "value": "1000000000000000000",
+        "estimated_gas": 150000,
+        "estimated_gas_cost": "150000000000000",
+        "balance_changes": [],
+        "mev_exposure": {
+            "kind": "swap",
+            "protocol": "uniswap_v2",
+            "slippage_bps": 500,
+            "price_impact_bps": 30,
+            "value_wei": "1000000000000000000",
+            "max_extractable_wei": "50000000000000000"
+        }
+    })
+}
+
+#[tokio::test]
+async fn swaps_exposed_to_sandwiching_need_approval() {
+    const ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+    let prompt = "Swap 1 ETH from Alice for USDC";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call(
+                "send_transaction",
+                json!({ "from": ALICE, "to": ROUTER, "value": "1000000000000000000", "data": "0x7ff36ab5" }),
+            ),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("The swap was refused"));
+    let foundry = FakeFoundry::new().respond("preview_transaction", exposed_swap_preview());
+    let config = AgentConfig {
+        guard: GuardConfig {
+            mev: MevGuardConfig::default(),
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let user = Arc::new(ScriptedUser::default());
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user);
+
+    let error = agent.run(UserPrompt::new(prompt)).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert!(!foundry.called_tools().contains(&"send_transaction".to_string()));
+}
+
+#[tokio::test]
+async fn exposed_swaps_go_private_when_required() {
+    const ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
+    const PROTECT: &str = "https://rpc.flashbots.net/fast";
+    let prompt = "Swap 1 ETH from Alice for USDC";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call(
+                "send_transaction",
+                json!({ "from": ALICE, "to": ROUTER, "value": "1000000000000000000", "data": "0x7ff36ab5" }),
+            ),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("Swapped 1 ETH through Flashbots Protect"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().respond("preview_transaction", exposed_swap_preview());
+    let config = AgentConfig {
+        guard: GuardConfig {
+            mev: MevGuardConfig {
+                require_private: true,
+                ..MevGuardConfig::default()
+            },
+            ..GuardConfig::allow_all()
+        },
+        private_transactions: PrivateTransactionsConfig {
+            rpcs: [("default".to_string(), PROTECT.to_string())].into(),
+            always: false,
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+
+    let (_, sent) = foundry.calls().into_iter().find(|(tool, _)| tool == "send_transaction").unwrap();
+    assert_eq!(sent["private_rpc"], json!(PROTECT));
+}
+
+#[tokio::test]
+async fn risky_contracts_need_approval_before_the_first_call() {
+    const TOKEN: &str = "0x5c69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
+    let prompt = "Buy the new token from Alice";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(
+            EXECUTOR,
+            ScriptedReply::tool_call(
+                "send_transaction",
+                json!({ "from": ALICE, "to": TOKEN, "value": "1000000000000000000", "data": "0xd0e30db0" }),
+            ),
+        )
+        .reply(EXECUTOR, ScriptedReply::text("The purchase was refused"));
+    let foundry = FakeFoundry::new().respond(
+        "risk_score",
+        json!({
+            "success": true,
+            "address": TOKEN,
+            "is_contract": true,
+            "score": 85,
+            "level": "high",
+            "reasons": ["source code is not verified", "deployed 2 day(s) ago", "upgradeable by a single account"]
+        }),
+    );
+    let config = AgentConfig {
+        guard: GuardConfig {
+            risk: RiskGuardConfig {
+                require_for_new_contracts: true,
+                ..RiskGuardConfig::default()
+            },
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let user = Arc::new(ScriptedUser::default());
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(user);
+
+    let error = agent.run(UserPrompt::new(prompt)).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    let (_, scored) = foundry.calls().into_iter().find(|(tool, _)| tool == "risk_score").unwrap();
+    assert_eq!(scored["address"], json!(TOKEN));
+    assert!(!foundry.called_tools().contains(&"send_transaction".to_string()));
+}
+
+#[tokio::test]
+async fn transfers_to_blocklisted_addresses_are_refused() {
+    const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    let list = std::env::temp_dir().join(format!("eth-agent-blocklist-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&list, format!("# drainers\n{}\n", BOB.to_lowercase())).unwrap();
+    let prompt = "Send 5 USDC from Alice to Bob";
+    // transfer(BOB, 5000000), the recipient is only in the calldata
+    let data = format!("0xa9059cbb{:0>64}{:064x}", BOB.trim_start_matches("0x"), 5_000_000);
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": USDC, "value": "0", "data": data })))
+        .reply(EXECUTOR, ScriptedReply::text("Bob is a known scam address, nothing was sent"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let config = AgentConfig {
+        blocklist: BlocklistConfig {
+            sources: Some(vec![list.display().to_string()]),
+            ..BlocklistConfig::default()
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config).unwrap().with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new(prompt)).await.unwrap();
+    std::fs::remove_file(&list).unwrap();
+
+    assert!(!foundry.called_tools().contains(&"simulate_transaction".to_string()));
+    assert!(!foundry.called_tools().contains(&"send_transaction".to_string()));
+}
+
+#[tokio::test]
+async fn provider_failure_aborts_without_replanning() {
+    // No executor replies scripted, so the sub-agent call fails at the provider
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(PLANNER, balance_plan());
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    let error = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::Provider { .. }));
+    assert_eq!(client.requests(PLANNER).len(), 1);
+}
+
+#[tokio::test]
+async fn dispatches_tool_calls_to_mcp() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert_eq!(foundry.calls(), vec![("balance".to_string(), json!({ "address": ALICE }))]);
+
+    let mut tool_events = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        match event {
+            AgentEvent::ToolCall { name, .. } => tool_events.push(format!("call {}", name)),
+            AgentEvent::ToolResult { name, result } => {
+                assert!(result.contains("1000000000000000000"));
+                tool_events.push(format!("result {}", name));
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(tool_events, vec!["call balance", "result balance"]);
+}
+
+#[tokio::test]
+async fn tool_results_reach_the_model_without_the_mcp_envelope() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("erc20_balance", json!({ "address": ALICE, "token_address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().respond("erc20_balance", json!({ "success": false, "error": "execution reverted" }));
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    let mut results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::ToolResult { result, .. } = event {
+            results.push(result);
+        }
+    }
+    assert_eq!(results.len(), 2);
+    let balance: serde_json::Value = serde_json::from_str(&results[0]).unwrap();
+    assert_eq!(balance, json!({ "address": ALICE, "balance": "1000000000000000000", "unit": "wei" }));
+    // Failures reported in the payload become tool errors
+    assert!(results[1].contains("erc20_balance failed: execution reverted"));
+}
+
+#[tokio::test]
+async fn simulates_and_previews_before_sending() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    assert_eq!(
+        foundry.called_tools(),
+        vec!["simulate_transaction", "preview_transaction", "send_transaction"]
+    );
+    let run = agent.last_run().unwrap();
+    assert_eq!(run.transaction_hashes(), vec![format!("0x{}", "ab".repeat(32))]);
+}
+
+#[tokio::test]
+async fn blocks_transactions_that_would_revert() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("The transaction would revert: insufficient funds"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(80, "Reported the revert")));
+    let foundry = FakeFoundry::new().respond(
+        "simulate_transaction",
+        json!({ "success": false, "would_revert": true, "error": "execution reverted: insufficient funds" }),
+    );
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    assert_eq!(foundry.called_tools(), vec!["simulate_transaction"]);
+    assert!(agent.last_run().unwrap().transaction_hashes().is_empty());
+}
+
+#[tokio::test]
+async fn queues_sends_behind_confirmed_transactions() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Send 1000 wei from Alice to Bob"),
+            ("ethereum_agent", "Send 2000 wei from Alice to Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "2000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 2000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei, then 2000 wei from Alice to Bob")).await.unwrap();
+
+    // The second step waits for the first transfer to be mined, then sends with the next nonce
+    let calls = foundry.calls();
+    assert_eq!(
+        foundry.called_tools(),
+        vec![
+            "simulate_transaction",
+            "preview_transaction",
+            "send_transaction",
+            "transaction_status",
+            "simulate_transaction",
+            "preview_transaction",
+            "send_transaction",
+        ]
+    );
+    assert!(calls[2].1.get("nonce").is_none());
+    assert_eq!(calls[3].1, json!({ "hash": format!("0x{}", "ab".repeat(32)) }));
+    assert_eq!(calls[6].1["nonce"], json!(1));
+}
+
+#[tokio::test]
+async fn bumps_fees_of_an_underpriced_replacement() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1000 wei from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({ "from": ALICE, "to": BOB, "value": "1000" })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().respond_once(
+        "send_transaction",
+        json!({ "success": false, "error": "Failed to send transaction: replacement transaction underpriced" }),
+    );
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("Send 1000 wei from Alice to Bob")).await.unwrap();
+
+    // A transaction from elsewhere holds the nonce, so the oracle's fast fees are outbid by 12.5%
+    let calls = foundry.calls();
+    assert_eq!(
+        foundry.called_tools(),
+        vec!["simulate_transaction", "preview_transaction", "send_transaction", "gas_oracle", "send_transaction"]
+    );
+    assert_eq!(calls[4].1["max_fee_per_gas"], json!(2_250_000_000u64));
+    assert_eq!(calls[4].1["max_priority_fee_per_gas"], json!(1));
+    assert_eq!(agent.last_run().unwrap().transaction_hashes(), vec![format!("0x{}", "ab".repeat(32))]);
+}
+
+#[tokio::test]
+async fn pins_ens_names_before_planning() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Get the ETH balance of vitalik.eth")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": "vitalik.eth" })))
+        .reply(EXECUTOR, ScriptedReply::text("vitalik.eth has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = test_agent(client.clone(), &foundry);
+
+    agent.run(UserPrompt::new("What is the ETH balance of vitalik.eth?")).await.unwrap();
+
+    // Resolved once up front, the tool call then uses the pinned address instead of the name
+    assert_eq!(foundry.called_tools(), vec!["resolve_ens", "balance"]);
+    assert_eq!(foundry.calls()[1].1, json!({ "address": "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045" }));
+    assert!(client.requests(PLANNER)[0].contains("vitalik.eth? (= 0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045)"));
+    assert_eq!(agent.last_run().unwrap().plan.unwrap().ens_resolutions.len(), 1);
+}
+
+#[tokio::test]
+async fn routes_analysis_steps_to_the_analysis_agent() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("analysis_agent", "Run Dune query 42 and summarize it")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("dune_query", json!({ "query_id": 42 })))
+        .reply(EXECUTOR, ScriptedReply::text("Dune is not configured, no API key"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(80, "Explains why there is no data")));
+    let foundry = FakeFoundry::new();
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    let result = agent.run(UserPrompt::new("Run Dune query 42")).await.unwrap();
+
+    assert_eq!(result.result, "Dune is not configured, no API key");
+    assert!(foundry.calls().is_empty());
+
+    // The tool error went back to the model instead of failing the step
+    let mut tool_results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::ToolResult { name, result } = event {
+            tool_results.push((name, result));
+        }
+    }
+    assert_eq!(tool_results.len(), 1);
+    assert_eq!(tool_results[0].0, "dune_query");
+    assert!(tool_results[0].1.contains("no Dune API key"));
+}
+
+#[tokio::test]
+async fn routes_position_questions_to_the_defi_agent() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("defi_agent", &format!("Is {} close to liquidation?", ALICE))])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("defi_positions", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice's Aave health factor is 1.05, close to liquidation"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("defi_positions", "Read an account's DeFi positions")
+        .respond(
+            "defi_positions",
+            json!({
+                "success": true,
+                "address": ALICE,
+                "lending": [{ "protocol": "aave_v3", "health_factor": 1.05, "liquidation_risk": "at_risk" }],
+                "liquidity": [],
+                "lowest_health_factor": 1.05,
+                "liquidation_risk": "at_risk"
+            }),
+        );
+    let mut agent = test_agent(client, &foundry);
+
+    let result = agent.run(UserPrompt::new("Am I close to liquidation?")).await.unwrap();
+
+    assert_eq!(foundry.called_tools(), vec!["defi_positions"]);
+    assert_eq!(foundry.calls()[0].1, json!({ "address": ALICE }));
+    assert!(result.result.contains("1.05"));
+}
+
+#[tokio::test]
+async fn repeated_searches_are_served_from_the_cache() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("search_agent", "Search for the Ethereum roadmap"),
+            ("search_agent", "Search the Ethereum roadmap again"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("web_search", json!({ "query": "ethereum roadmap" })))
+        .reply(EXECUTOR, ScriptedReply::text("Found the roadmap"))
+        .reply(EXECUTOR, ScriptedReply::tool_call("web_search", json!({ "query": "  Ethereum   Roadmap " })))
+        .reply(EXECUTOR, ScriptedReply::text("Found it again"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Found")))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Found")));
+    let foundry = FakeFoundry::new();
+    let search = Arc::new(CountingSearch::default());
+    let mut agent = EthAgent::new(client, Some(search.clone() as Arc<dyn SearchProvider>), test_config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    let result = agent.run(UserPrompt::new("What is on the Ethereum roadmap?")).await.unwrap();
+
+    assert_eq!(result.result, "Found it again");
+    assert_eq!(search.searches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn restarts_a_crashed_server_and_replays_the_call() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().crash_on("balance");
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    let result = agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert_eq!(result.result, "Alice has 1 ETH");
+    assert_eq!(foundry.called_tools(), vec!["balance", "balance"]);
+
+    let mut warnings = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::Warning { message } = event {
+            warnings.push(message);
+        }
+    }
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("restarted"));
+}
+
+#[tokio::test]
+async fn bridges_server_tools_without_a_wrapper() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("block_number", json!({})))
+        .reply(EXECUTOR, ScriptedReply::text("The chain is at block 42"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("block_number", "Latest block number")
+        .list_tool("balance", "Get the balance of an account in wei")
+        .respond("block_number", json!({ "success": true, "block_number": 42 }));
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("What is the latest block?")).await.unwrap();
+
+    assert_eq!(foundry.called_tools(), vec!["block_number"]);
+    let mut results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::ToolResult { name, result } = event {
+            results.push((name, result));
+        }
+    }
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, "block_number");
+    assert!(results[0].1.contains("42"));
+}
+
+#[tokio::test]
+async fn inferred_interfaces_are_cached() {
+    let token = json!({ "address": "0x6B175474E89094C44Da98b954EedeAC495271d0F" });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Find the interface of the token")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("infer_interface", token.clone()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("infer_interface", token))
+        .reply(EXECUTOR, ScriptedReply::text("The token has a transfer function"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("infer_interface", "Infer a best-effort ABI for a contract")
+        .respond("infer_interface", json!({ "success": true, "abi": [] }));
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new("What functions does the token have?")).await.unwrap();
+
+    assert_eq!(foundry.called_tools(), vec!["infer_interface"]);
+}
+
+#[tokio::test]
+async fn cheatcodes_drop_cached_reads() {
+    let alice = json!({ "address": "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266" });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", alice.clone()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("reset_fork", json!({})))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", alice))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH after the reset"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("reset_fork", "Reset the local fork")
+        .respond("reset_fork", json!({ "success": true, "chain_id": 31337, "block_number": 0 }));
+    let mut agent = test_agent(client, &foundry);
+
+    agent.run(UserPrompt::new("Reset the fork and check Alice's balance")).await.unwrap();
+
+    assert_eq!(foundry.called_tools(), vec!["balance", "reset_fork", "balance"]);
+}
+
+#[tokio::test]
+async fn cheatcodes_are_confirmed_before_they_run() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Reset the local fork")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("reset_fork", json!({})))
+        .reply(EXECUTOR, ScriptedReply::text("The fork could not be reset"));
+    let foundry = FakeFoundry::new()
+        .list_tool("reset_fork", "Reset the local fork")
+        .respond("reset_fork", json!({ "success": true, "chain_id": 31337, "block_number": 0 }));
+    let config = AgentConfig {
+        guard: GuardConfig {
+            cheatcodes: GuardPolicy::Confirm,
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(Arc::new(ScriptedUser::default()));
+
+    let error = agent.run(UserPrompt::new("Reset the fork")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert!(!foundry.called_tools().contains(&"reset_fork".to_string()));
+}
+
+#[tokio::test]
+async fn forwards_tool_progress_to_the_event_sink() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new().progress_on("balance", "Reading the balance");
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    let mut progress = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::Progress { progress: done, total, message } = event {
+            progress.push((done, total, message));
+        }
+    }
+    assert_eq!(progress, vec![(1.0, Some(2.0), Some("Reading the balance".to_string()))]);
+}
+
+#[tokio::test]
+async fn answers_server_sampling_requests_with_the_execution_model() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("summarize_trace", json!({ "hash": "0xabc" })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice sent 1 ETH to Bob"))
+        .reply(EXECUTOR, ScriptedReply::text("The transaction moved 1 ETH from Alice to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new()
+        .list_tool("summarize_trace", "Summarize a transaction trace")
+        .sample_on("summarize_trace", "CALL alice -> bob value 1e18");
+    let (events, mut receiver) = EventSink::channel();
+    let mut agent = test_agent(client.clone(), &foundry).with_event_sink(events);
+
+    agent.run(UserPrompt::new("What did transaction 0xabc do?")).await.unwrap();
+
+    // The sampling request went to the execution model between the tool call and the final answer
+    assert_eq!(client.requests(EXECUTOR)[1], "CALL alice -> bob value 1e18");
+    let mut results = vec![];
+    while let Ok(event) = receiver.try_recv() {
+        if let AgentEvent::ToolResult { result, .. } = event {
+            results.push(result);
+        }
+    }
+    assert_eq!(results.len(), 1);
+    assert!(results[0].contains("Alice sent 1 ETH to Bob"));
+}
+
+#[tokio::test]
+async fn shares_one_client_between_concurrent_tool_calls() {
+    let foundry = FakeFoundry::new();
+    let service = foundry.connector()().await.unwrap();
+    let client = Arc::new(FoundryMcpClient::from_service(service, &test_config(), RunJournal::new(), EventSink::default()));
+
+    let calls: Vec<_> = [ALICE, BOB]
+        .into_iter()
+        .map(|address| {
+            let client = client.clone();
+            tokio::spawn(async move { client.balance(address, true).await })
+        })
+        .collect();
+    let mut addresses = vec![];
+    for call in calls {
+        addresses.push(call.await.unwrap().unwrap().address);
+    }
+
+    assert_eq!(addresses, vec![ALICE, BOB]);
+    assert_eq!(foundry.called_tools(), vec!["balance", "balance"]);
+}
+
+fn network_config() -> AgentConfig {
+    AgentConfig {
+        networks: [
+            ("mainnet".to_string(), "https://mainnet.example".to_string()),
+            ("fork".to_string(), "http://127.0.0.1:8545".to_string()),
+        ]
+        .into(),
+        ..test_config()
+    }
+}
+
+#[tokio::test]
+async fn switches_networks_between_steps() {
+    let plan = json!({
+        "number_of_steps": 2,
+        "steps": [
+            { "step_number": 1, "agent_name": "ethereum_agent", "agent_prompt": "Get the ETH balance of Alice", "network": "mainnet" },
+            { "step_number": 2, "agent_name": "ethereum_agent", "agent_prompt": "Send 1000 wei from Alice to Bob", "network": "fork" }
+        ]
+    });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan.to_string()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH on mainnet"))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob on the fork"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = EthAgent::new(client.clone(), None, network_config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("Check Alice's mainnet balance, then send 1000 wei to Bob on the fork")).await.unwrap();
+
+    let calls = foundry.calls();
+    assert_eq!(foundry.called_tools(), vec!["use_network", "balance", "use_network"]);
+    assert_eq!(calls[0].1, json!({ "rpc_url": "https://mainnet.example" }));
+    assert_eq!(calls[2].1, json!({ "rpc_url": "http://127.0.0.1:8545" }));
+    assert!(client.requests(EXECUTOR)[2].contains("Network: fork"));
+}
+
+#[tokio::test]
+async fn replans_unknown_networks_before_running_any_step() {
+    let plan = json!({
+        "number_of_steps": 2,
+        "steps": [
+            { "step_number": 1, "agent_name": "ethereum_agent", "agent_prompt": "Get the ETH balance of Alice" },
+            { "step_number": 2, "agent_name": "ethereum_agent", "agent_prompt": "Send 1000 wei from Alice to Bob", "network": "goerli" }
+        ]
+    });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan.to_string()))
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = EthAgent::new(client.clone(), None, network_config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+
+    assert!(client.requests(PLANNER)[1].contains("Unknown network: goerli (configured networks: fork, mainnet)"));
+    // Steps without a network stay on the server's default, nothing was switched
+    assert!(foundry.calls().is_empty());
+    assert_eq!(client.remaining(EXECUTOR), 0);
+}
+
+#[tokio::test]
+async fn runs_chain_steps_on_the_network_on_that_chain() {
+    let plan = json!({
+        "number_of_steps": 1,
+        "steps": [
+            { "step_number": 1, "agent_name": "ethereum_agent", "agent_prompt": "Get the ETH balance of Alice", "chain": 1 }
+        ]
+    });
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan.to_string()))
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH on mainnet"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    // The default network and the fork are local chains, only mainnet is chain 1
+    let foundry = FakeFoundry::new()
+        .respond_once("use_network", json!({ "success": true, "chain_id": 31337 }))
+        .respond_once("use_network", json!({ "success": true, "chain_id": 31337 }))
+        .respond_once("use_network", json!({ "success": true, "chain_id": 1 }));
+    let mut agent = EthAgent::new(client.clone(), None, network_config())
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("Check Alice's balance on chain 1")).await.unwrap();
+
+    let calls = foundry.calls();
+    assert_eq!(foundry.called_tools(), vec!["use_network", "use_network", "use_network", "balance"]);
+    assert_eq!(calls[2].1, json!({ "rpc_url": "https://mainnet.example" }));
+    assert!(client.requests(EXECUTOR)[0].contains("Network: mainnet"));
+}
+
+fn estimating_config(guard: GuardConfig) -> AgentConfig {
+    let config = test_config();
+    AgentConfig {
+        guard,
+        budget: BudgetConfig {
+            estimate_plan_cost: true,
+            ..config.budget.clone()
+        },
+        ..config
+    }
+}
+
+#[tokio::test]
+async fn estimates_the_plan_gas_cost_before_running_it() {
+    let step = format!("Send 1000 wei (= 1000 wei) from {} to {}", ALICE, BOB);
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Get the ETH balance of Alice"), ("ethereum_agent", &step)])))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1000 wei to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = EthAgent::new(client.clone(), None, estimating_config(GuardConfig::allow_all()))
+        .unwrap()
+        .with_mcp_connector(foundry.connector());
+
+    agent.run(UserPrompt::new("Check Alice's balance, then send 1000 wei to Bob")).await.unwrap();
+
+    // Only the transfer is priced, its transaction is fully known so it is simulated
+    assert_eq!(foundry.called_tools(), vec!["preview_transaction", "gas_oracle", "get_price"]);
+    let run = agent.last_run().unwrap();
+    let estimate = run.cost_estimate.as_ref().unwrap();
+    assert_eq!(estimate.steps.len(), 1);
+    assert_eq!(estimate.steps[0].step_number, 2);
+    assert_eq!(estimate.steps[0].gas, 21000);
+    assert!(estimate.steps[0].simulated);
+    assert_eq!(estimate.total_wei.as_deref(), Some("42000000000000"));
+    assert_eq!(run.gas_spent_wei.as_deref(), Some("0"));
+    assert!(render_report(&run, "https://etherscan.io", &AddressLabels::default(), ReportFormat::Markdown).contains("## Gas cost"));
+}
+
+#[tokio::test]
+async fn a_refused_plan_cost_ends_the_run_before_any_step() {
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Swap 1 ETH for USDC")])));
+    let foundry = FakeFoundry::new();
+    let guard = GuardConfig {
+        plan_cost: GuardPolicy::Confirm,
+        ..GuardConfig::allow_all()
+    };
+    let mut agent = EthAgent::new(client.clone(), None, estimating_config(guard))
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(Arc::new(ScriptedUser::default()));
+
+    let error = agent.run(UserPrompt::new("Swap 1 ETH for USDC")).await.unwrap_err();
+
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert!(client.requests(EXECUTOR).is_empty());
+    assert_eq!(agent.last_run().unwrap().cost_estimate.unwrap().steps[0].gas, 180_000);
+}
+
+#[tokio::test]
+async fn saved_runs_outlive_the_agent() {
+    let database = std::env::temp_dir().join(format!("eth-agent-runs-{}.db", uuid::Uuid::new_v4()));
+    let config = AgentConfig {
+        runs: RunsConfig {
+            database: Some(database.clone()),
+        },
+        ..test_config()
+    };
+    let client = ScriptedClient::new()
+        .reply(PLANNER, balance_plan())
+        .reply(EXECUTOR, ScriptedReply::tool_call("balance", json!({ "address": ALICE })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice has 1 ETH"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let foundry = FakeFoundry::new();
+    let mut agent = EthAgent::new(client, None, config.clone()).unwrap().with_mcp_connector(foundry.connector());
+    agent.run(UserPrompt::new("What is the ETH balance of Alice?")).await.unwrap();
+    let id = agent.last_run().unwrap().id;
+    drop(agent);
+
+    // A new agent on the same database, as after a restart
+    let agent = EthAgent::new(ScriptedClient::new(), None, config).unwrap();
+    assert!(agent.runs().is_empty());
+    let runs = agent.saved_runs(None).unwrap();
+    assert_eq!(runs.len(), 1);
+    let run = &runs[0];
+    assert_eq!(run.id, id);
+    assert!(matches!(run.status, PlanStatus::Completed));
+    assert_eq!(run.result.as_deref(), Some("Alice has 1 ETH"));
+    assert!(matches!(run.steps[0].status, StepStatus::Completed));
+    assert_eq!(run.steps[0].tool_calls[0].tool, "balance");
+    assert_eq!(run.steps[0].evaluation.as_ref().unwrap().score, 90);
+
+    assert_eq!(agent.find_run(&id[..8]).unwrap().id, id);
+    assert!(agent.saved_runs(Some(chrono::Utc::now())).unwrap().is_empty());
+
+    std::fs::remove_file(database).unwrap();
+}
+
+#[tokio::test]
+async fn portfolio_history_reports_the_pnl_of_each_asset() {
+    let holdings = |eth: &str, eth_price: &str, usdc: &str| {
+        json!([
+            { "asset": "ETH", "token": null, "balance": eth, "price_usd": eth_price },
+            { "asset": "USDC", "token": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "balance": usdc, "price_usd": "1" }
+        ])
+    };
+    let foundry = FakeFoundry::new().respond(
+        "portfolio_history",
+        json!({
+            "success": true,
+            "address": ALICE,
+            "chain_id": 1,
+            "days": 30,
+            "samples": [
+                { "block": 100, "timestamp": 1_700_000_000, "holdings": holdings("2", "2000", "500") },
+                { "block": 200, "timestamp": 1_702_592_000, "holdings": holdings("1", "3000", "2500") }
+            ]
+        }),
+    );
+    let agent = EthAgent::new(ScriptedClient::new(), None, test_config()).unwrap().with_mcp_connector(foundry.connector());
+
+    let history = agent.portfolio_history(ALICE, &[], 30, 2).await.unwrap();
+
+    assert_eq!(foundry.calls()[0].1, json!({ "address": ALICE, "days": 30, "samples": 2 }));
+    assert_eq!(history.assets(), vec!["ETH", "USDC"]);
+    assert_eq!(history.change_usd(), 1000.0);
+    let pnl = history.pnl();
+    // Sold 1 ETH for 2000 USDC while ETH went up 1000
+    assert_eq!(pnl[0].change_usd, Some(-1000.0));
+    assert_eq!(pnl[0].price_effect_usd, Some(2000.0));
+    assert_eq!(pnl[1].change_usd, Some(2000.0));
+    assert_eq!(pnl[1].price_effect_usd, Some(0.0));
+    let report = render_portfolio(&history, &AddressLabels::default(), ReportFormat::Markdown);
+    assert!(report.contains("## P&L"));
+    assert!(report.contains("| ETH | 2 | 1 | $4000.00 | $3000.00 | -$1000.00 | +$2000.00 |"));
+}
+
+// ENS-like pack with a read-only lookup and a state-changing setter, recording its lifecycle
+#[derive(Default)]
+struct RecordingPack {
+    events: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl ToolPack for RecordingPack {
+    fn name(&self) -> &str {
+        "ens-tools"
+    }
+
+    async fn run_started(&self, _prompt: &UserPrompt) -> anyhow::Result<()> {
+        self.events.lock().unwrap().push("started".to_string());
+        Ok(())
+    }
+
+    async fn tools(&self) -> anyhow::Result<Vec<PackTool>> {
+        let tool = |name: &str, read_only| PackTool {
+            name: name.to_string(),
+            description: format!("{} an ENS text record", name),
+            parameters: json!({ "type": "object", "properties": { "name": { "type": "string" } } }),
+            read_only,
+        };
+        Ok(vec![tool("get_text", true), tool("set_text", false)])
+    }
+
+    async fn call(&self, tool: &str, arguments: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        self.events.lock().unwrap().push(format!("{} {}", tool, arguments["name"]));
+        Ok(json!({ "success": true, "value": "https://example.com" }))
+    }
+
+    async fn run_finished(&self, _run: &RunRecord) {
+        self.events.lock().unwrap().push("finished".to_string());
+    }
+}
+
+#[tokio::test]
+async fn plugin_tools_are_namespaced_and_guarded() {
+    let prompt = "Set the url record of alice.eth to the one of vitalik.eth";
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", prompt)])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("ens-tools__get_text", json!({ "name": "vitalik.eth" })))
+        .reply(EXECUTOR, ScriptedReply::tool_call("ens-tools__set_text", json!({ "name": "alice.eth" })))
+        .reply(EXECUTOR, ScriptedReply::text("The url record could not be set"));
+    let config = AgentConfig {
+        guard: GuardConfig {
+            plugins: GuardPolicy::Confirm,
+            ..GuardConfig::allow_all()
+        },
+        ..test_config()
+    };
+    let foundry = FakeFoundry::new();
+    let pack = Arc::new(RecordingPack::default());
+    let mut agent = EthAgent::new(client, None, config)
+        .unwrap()
+        .with_mcp_connector(foundry.connector())
+        .with_interaction(Arc::new(ScriptedUser::default()))
+        .with_tool_pack(pack.clone());
+
+    let error = agent.run(UserPrompt::new(prompt)).await.unwrap_err();
+
+    // The lookup ran without asking, the user never agreed to the setter
+    assert!(matches!(error, AgentError::PolicyViolation { .. }));
+    assert_eq!(*pack.events.lock().unwrap(), vec!["started", "get_text \"vitalik.eth\"", "finished"]);
+    let run = agent.last_run().unwrap();
+    let calls: Vec<&str> = run.steps[0].tool_calls.iter().map(|call| call.tool.as_str()).collect();
+    assert_eq!(calls, vec!["ens-tools__get_text"]);
+}
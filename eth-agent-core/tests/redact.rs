@@ -0,0 +1,87 @@
+// Secret redaction applied to prompts, tool output and logs
+
+use std::io::Write;
+
+use eth_agent_core::{redact, register_secret, RedactingWriter};
+
+const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const TX_HASH: &str = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060";
+
+#[test]
+fn masks_private_keys_named_in_prose() {
+    let redacted = redact(&format!("my private key is 0x{}", PRIVATE_KEY));
+    assert_eq!(redacted, "my private key is [REDACTED private key]");
+
+    let redacted = redact(&format!("Sign with key {} and send 1 ETH to Bob", PRIVATE_KEY));
+    assert_eq!(redacted, "Sign with key [REDACTED private key] and send 1 ETH to Bob");
+
+    let redacted = redact(&format!("my wallet pk: 0x{}", PRIVATE_KEY));
+    assert_eq!(redacted, "my wallet pk: [REDACTED private key]");
+
+    let redacted = redact(&format!("seed {}", PRIVATE_KEY));
+    assert_eq!(redacted, "seed [REDACTED private key]");
+}
+
+#[test]
+fn keeps_hashes_with_and_without_prefix() {
+    // Transaction hashes look the same as keys, they must survive
+    let text = format!("What happened in transaction {}?", TX_HASH);
+    assert_eq!(redact(&text), text);
+
+    let text = format!("Look up block hash {} and slot {}", PRIVATE_KEY, &TX_HASH[2..]);
+    assert_eq!(redact(&text), text);
+
+    // A key masked earlier doesn't make the hash after it a key
+    let redacted = redact(&format!("private key 0x{} sent {}", PRIVATE_KEY, TX_HASH));
+    assert_eq!(redacted, format!("private key [REDACTED private key] sent {}", TX_HASH));
+}
+
+#[test]
+fn keeps_storage_slots_and_mapping_keys() {
+    let text = format!("set_storage_at slot {} with mapping_key 0x{}", TX_HASH, PRIVATE_KEY);
+    assert_eq!(redact(&text), text);
+
+    let text = format!("{{\"key\": \"{}\", \"value\": \"0x01\"}}", TX_HASH);
+    assert_eq!(redact(&text), text);
+
+    let redacted = redact(&format!("{{\"private_key\": \"0x{}\"}}", PRIVATE_KEY));
+    assert_eq!(redacted, "{\"private_key\": \"[REDACTED private key]\"}");
+}
+
+#[test]
+fn masks_mnemonics() {
+    let redacted = redact("my seed is test test test test test test test test test test test junk, what is my address?");
+
+    assert_eq!(redacted, "my seed is [REDACTED mnemonic], what is my address?");
+}
+
+#[test]
+fn keeps_ordinary_prompts() {
+    let text = "Send 0.1 ETH (= 100000000000000000 wei) from Alice to 0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    assert_eq!(redact(text), text);
+
+    let text = "please check the balance of alice and then send half of it to bob";
+    assert_eq!(redact(text), text);
+
+    // Lowercase prose, but not all of it is in the BIP-39 wordlist
+    let text = "swap some tokens for more stable coins once gas fees drop below normal levels today";
+    assert_eq!(redact(text), text);
+}
+
+#[test]
+fn masks_registered_and_api_keys() {
+    register_secret("BSAregistered-brave-key");
+
+    let redacted = redact("keys: BSAregistered-brave-key and sk-ant-REDACTED");
+
+    assert_eq!(redacted, "keys: [REDACTED secret] and [REDACTED API key]");
+}
+
+#[test]
+fn writer_redacts_log_lines() {
+    let mut output = vec![];
+    let line = format!("INFO prompt: use key {}", PRIVATE_KEY);
+    RedactingWriter::new(&mut output).write_all(line.as_bytes()).unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap(), "INFO prompt: use key [REDACTED private key]");
+}
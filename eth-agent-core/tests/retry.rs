@@ -0,0 +1,39 @@
+// Classification of provider and tool errors into retried and permanent ones
+
+use eth_agent_core::{classify, ErrorClass};
+
+#[test]
+fn named_http_statuses_are_transient() {
+    assert_eq!(classify("HTTP 429 from the provider"), ErrorClass::RateLimited);
+    assert_eq!(classify("request failed with status code 503"), ErrorClass::ConnectionReset);
+    assert_eq!(classify("HTTP/1.1 502"), ErrorClass::ConnectionReset);
+    assert!(classify("error 529: try again later").is_transient());
+}
+
+#[test]
+fn numbers_without_a_status_word_are_permanent() {
+    assert_eq!(classify("transfer of 429 USDC to Bob reverted"), ErrorClass::Permanent);
+    assert_eq!(classify("invalid nonce 503 for sender"), ErrorClass::Permanent);
+    assert_eq!(classify("insufficient funds: have 503 wei, want 429"), ErrorClass::Permanent);
+    assert_eq!(
+        classify("transaction 0x429503c51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b2206042950 not found"),
+        ErrorClass::Permanent
+    );
+}
+
+#[test]
+fn only_rate_limits_are_safe_to_resend() {
+    assert!(classify("429 Too Many Requests").is_safe_to_resend());
+    assert!(!classify("nonce too low").is_safe_to_resend());
+    assert!(!classify("connection reset by peer").is_safe_to_resend());
+    assert!(!classify("Tool call send_transaction timed out after 30s").is_safe_to_resend());
+}
+
+#[test]
+fn already_known_transactions_are_not_resent() {
+    let class = classify("send_transaction failed: already known");
+
+    assert_eq!(class, ErrorClass::AlreadyKnown);
+    assert!(!class.is_transient());
+    assert!(!class.is_safe_to_resend());
+}
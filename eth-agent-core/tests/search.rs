@@ -0,0 +1,37 @@
+// Post-processing of web search results before they reach the model
+
+use eth_agent_core::{distill, html_to_text, SearchResult};
+
+fn result(url: &str, snippet: &str) -> SearchResult {
+    SearchResult {
+        title: "<b>Ethereum</b> gas".to_string(),
+        url: url.to_string(),
+        snippet: snippet.to_string(),
+    }
+}
+
+#[test]
+fn strips_markup_scripts_and_entities() {
+    let html = "<html><head><style>p { color: red }</style><script>var x = '<p>';</script></head>\
+                <body><p>Gas &amp; fees</p>\n\n<p>are   <strong>low</strong></p></body></html>";
+
+    assert_eq!(html_to_text(html), "Gas & fees are low");
+}
+
+#[test]
+fn distills_results_for_the_model() {
+    let results = vec![
+        result("https://a.example", "The <strong>base fee</strong> is burned"),
+        result("https://a.example", "Same page again"),
+        result("", "No URL"),
+        result("https://b.example", &"word ".repeat(100)),
+    ];
+
+    let distilled = distill(results, 40);
+
+    assert_eq!(distilled.len(), 2);
+    assert_eq!(distilled[0].title, "Ethereum gas");
+    assert_eq!(distilled[0].snippet, "The base fee is burned");
+    assert!(distilled[1].snippet.ends_with('…'));
+    assert!(distilled[1].snippet.chars().count() <= 41);
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use rmcp::model::CallToolResult;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// ETH balance of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceResult {
+    pub address: String,
+    // Decimal string, balances can exceed u64
+    pub balance: String,
+    pub unit: String,
+}
+
+/// ERC20 balance of an account, `balance` is in the token's smallest unit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub token_address: String,
+    pub account_address: String,
+    pub balance: String,
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+/// An account `accounts` listed: one the node manages (`source` "node") or one asked for
+/// ("requested"), with its ETH balance in wei and the nonce of its next transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub address: String,
+    pub source: String,
+    pub balance: String,
+    pub nonce: u64,
+    // The address's name from `[labels]`, filled in by the agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Outcome of `validate_address`, an invalid address is an answer rather than a failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressValidation {
+    pub valid: bool,
+    // Checksummed when valid, as given otherwise
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Code deployed at an address, `None` for externally owned accounts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractCode {
+    pub address: String,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+impl ContractCode {
+    pub fn is_contract(&self) -> bool {
+        self.code.as_deref().is_some_and(|code| !code.trim_start_matches("0x").is_empty())
+    }
+}
+
+/// Outcome of `send_transaction`. `blocked` is set when the agent stopped the transaction before
+/// it was sent (failed simulation, guard refusal, gas budget), `error` tells why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxResult {
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blocked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub to: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    // Address of a deployed contract
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_address: Option<String>,
+    // Balances re-read after sending, next to the changes the preview expected
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actual_balance_changes: Vec<serde_json::Value>,
+    // With `unsigned_transactions`, what the user signs instead of a sent transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unsigned_transaction: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eip681: Option<String>,
+}
+
+// Tools return their JSON payload in a text content block
+pub(crate) fn tool_payload(tool_result: &CallToolResult) -> Option<serde_json::Value> {
+    tool_result
+        .content
+        .iter()
+        .find_map(|content| serde_json::from_str(&content.as_text()?.text).ok())
+}
+
+// The payload of a tool result, without the MCP envelope. Results without one are errors
+// raised by the server itself, e.g. unknown tools or invalid arguments.
+pub(crate) fn payload(name: &str, tool_result: &CallToolResult) -> Result<serde_json::Value> {
+    if let Some(payload) = tool_payload(tool_result) {
+        return Ok(payload);
+    }
+
+    let text: Vec<&str> = tool_result
+        .content
+        .iter()
+        .filter_map(|content| content.as_text().map(|text| text.text.as_str()))
+        .collect();
+    Err(anyhow::anyhow!("{} failed: {}", name, if text.is_empty() { "empty response".to_string() } else { text.join(" ") }))
+}
+
+// Tools report failures as `{"success": false, "error": ...}` in their payload
+pub(crate) fn failure(payload: &serde_json::Value) -> Option<String> {
+    if payload.get("success")?.as_bool()? {
+        return None;
+    }
+    Some(payload.get("error").and_then(|error| error.as_str()).unwrap_or("unknown error").to_string())
+}
+
+// Deserialize a tool's payload, a reported failure becomes an error
+pub(crate) fn typed<T: DeserializeOwned>(name: &str, payload: serde_json::Value) -> Result<T> {
+    if let Some(error) = failure(&payload) {
+        return Err(anyhow::anyhow!("{} failed: {}", name, error));
+    }
+    serde_json::from_value(payload).map_err(|e| anyhow::anyhow!("Unexpected {} response: {}", name, e))
+}
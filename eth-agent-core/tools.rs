@@ -0,0 +1,1377 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use anyhow::Result;
+use std::sync::Arc;
+use rig::{
+    completion::ToolDefinition,
+    tool::Tool,
+};
+use tracing::debug;
+use crate::cache::SearchCache;
+use crate::config::{AgentConfig, SearchConfig};
+use crate::etherscan::{EtherscanClient, HistoryKind};
+use crate::events::EventSink;
+use crate::journal::RunJournal;
+use crate::mcp_client::FoundryMcpClient;
+use crate::dune::DuneClient;
+use crate::search::{distill, summarize_page, SearchOptions, SearchProvider};
+use crate::tenderly::{SimulationRequest, TenderlyClient};
+use crate::tool_results::{AddressValidation, BalanceResult, ContractCode, TokenBalance, TxResult};
+
+// Error types for different tool operations
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    // anyhow::Error isn't a std Error, so it can't be a #[source]
+    #[error("MCP client error: {0}")]
+    McpError(anyhow::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+impl From<anyhow::Error> for ToolError {
+    fn from(err: anyhow::Error) -> Self {
+        ToolError::McpError(err)
+    }
+}
+
+// Validate Address Tool
+#[derive(Deserialize)]
+pub struct ValidateAddressArgs {
+    pub address: String,
+}
+
+#[derive(Clone)]
+pub struct ValidateAddressTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl ValidateAddressTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for ValidateAddressTool {
+    const NAME: &'static str = "validate_address";
+    type Error = ToolError;
+    type Args = ValidateAddressArgs;
+    type Output = AddressValidation;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "validate_address".to_string(),
+            description: "Validate an Ethereum address format and checksum".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "string",
+                        "description": "The Ethereum address to validate"
+                    }
+                },
+                "required": ["address"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.validate_address(&args.address).await?;
+        Ok(result)
+    }
+}
+
+// Send Transaction Tool
+#[derive(Deserialize)]
+pub struct SendTransactionArgs {
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub data: Option<String>,
+    pub gas_limit: Option<u64>,
+    pub gas_price: Option<u128>,
+    pub fee_tier: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+}
+
+#[derive(Clone)]
+pub struct SendTransactionTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl SendTransactionTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for SendTransactionTool {
+    const NAME: &'static str = "send_transaction";
+    type Error = ToolError;
+    type Args = SendTransactionArgs;
+    type Output = TxResult;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "send_transaction".to_string(),
+            description: "Send an Ethereum transaction with specified parameters".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "from": {
+                        "type": "string",
+                        "description": "Sender address"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "Recipient address, empty string to deploy a contract with `data` as init code"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "Amount of ETH to send (in wei)"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "Transaction data (hex encoded)"
+                    },
+                    "gas_limit": {
+                        "type": "number",
+                        "description": "Gas limit for the transaction"
+                    },
+                    "gas_price": {
+                        "type": "number",
+                        "description": "Gas price (in wei)"
+                    },
+                    "fee_tier": {
+                        "type": "string",
+                        "enum": ["slow", "standard", "fast"],
+                        "description": "Fee tier on public networks (optional): fast for the next block and slow for within ~5 minutes, priced from recent blocks and the mempool, standard from the gas oracle. Ignored when gas_price is set"
+                    },
+                    "private": {
+                        "type": "boolean",
+                        "description": "Submit through the network's private RPC (e.g. Flashbots Protect) instead of the public mempool, protecting swaps and large transfers from sandwiching (optional)"
+                    }
+                },
+                "required": ["from", "to", "value"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.send_transaction(
+            &args.from,
+            &args.to,
+            &args.value,
+            args.data.as_deref(),
+            args.gas_limit,
+            args.gas_price,
+            args.fee_tier.as_deref(),
+            args.private,
+        ).await?;
+        Ok(result)
+    }
+}
+
+// Balance Tool
+#[derive(Deserialize)]
+pub struct BalanceArgs {
+    pub address: String,
+    // Explicitly bypass the tool-result cache
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+#[derive(Clone)]
+pub struct BalanceTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl BalanceTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for BalanceTool {
+    const NAME: &'static str = "balance";
+    type Error = ToolError;
+    type Args = BalanceArgs;
+    type Output = BalanceResult;    
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "balance".to_string(),
+            description: "Check the balance of an Ethereum address".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "string",
+                        "description": "The Ethereum address to check balance for"
+                    },
+                    "fresh": {
+                        "type": "boolean",
+                        "description": "Set to true to skip cached results and read fresh on-chain state"
+                    }
+                },
+                "required": ["address"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.balance(&args.address, args.fresh).await?;
+        Ok(result)
+    }
+}
+
+// Get Price Tool
+
+#[derive(Deserialize)]
+pub struct GetPriceArgs {
+    pub asset: String,
+    pub source: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GetPriceTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl GetPriceTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for GetPriceTool {
+    const NAME: &'static str = "get_price";
+    type Error = ToolError;
+    type Args = GetPriceArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_price".to_string(),
+            description: "Get the current USD price of a crypto asset (e.g. ETH, BTC, USDC) from a Chainlink feed or CoinGecko, with the time it was last updated".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "asset": {
+                        "type": "string",
+                        "description": "Asset symbol, e.g. ETH"
+                    },
+                    "source": {
+                        "type": "string",
+                        "enum": ["chainlink", "coingecko"],
+                        "description": "Price source (optional), defaults to Chainlink with CoinGecko as fallback"
+                    }
+                },
+                "required": ["asset"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.get_price(&args.asset, args.source.as_deref()).await?;
+        Ok(result)
+    }
+}
+
+// Gas Oracle Tool
+
+#[derive(Deserialize)]
+pub struct GasOracleArgs {
+    pub source: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GasOracleTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl GasOracleTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for GasOracleTool {
+    const NAME: &'static str = "gas_oracle";
+    type Error = ToolError;
+    type Args = GasOracleArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "gas_oracle".to_string(),
+            description: "Get slow, standard and fast fee recommendations (max fee and priority fee in wei) with expected inclusion times in seconds".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "source": {
+                        "type": "string",
+                        "enum": ["blocknative", "etherscan", "node"],
+                        "description": "Oracle to ask (optional), defaults to the first one configured and the node's fee history otherwise"
+                    }
+                }
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.gas_oracle(args.source.as_deref()).await?;
+        Ok(result)
+    }
+}
+
+// Resolve Token Tool
+
+#[derive(Deserialize)]
+pub struct ResolveTokenArgs {
+    pub symbol: String,
+}
+
+#[derive(Clone)]
+pub struct ResolveTokenTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl ResolveTokenTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for ResolveTokenTool {
+    const NAME: &'static str = "resolve_token";
+    type Error = ToolError;
+    type Args = ResolveTokenArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "resolve_token".to_string(),
+            description: "Resolve an ERC20 token symbol (e.g. USDC) to its checksummed address and decimals on the current chain".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {
+                        "type": "string",
+                        "description": "Token symbol, e.g. USDC"
+                    }
+                },
+                "required": ["symbol"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.resolve_token(&args.symbol).await?;
+        Ok(result)
+    }
+}
+
+// Safe Info Tool
+
+#[derive(Deserialize)]
+pub struct SafeInfoArgs {
+    pub safe: String,
+}
+
+#[derive(Clone)]
+pub struct SafeInfoTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl SafeInfoTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for SafeInfoTool {
+    const NAME: &'static str = "safe_info";
+    type Error = ToolError;
+    type Args = SafeInfoArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "safe_info".to_string(),
+            description: "Read a Safe multisig's owners, confirmation threshold, nonce and version".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "safe": {
+                        "type": "string",
+                        "description": "The Safe address"
+                    }
+                },
+                "required": ["safe"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.safe_info(&args.safe).await?;
+        Ok(result)
+    }
+}
+
+// Sign Permit Tool
+
+#[derive(Deserialize)]
+pub struct SignPermitArgs {
+    pub token: String,
+    pub owner: String,
+    pub spender: String,
+    pub value: String,
+    pub deadline: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct SignPermitTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl SignPermitTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for SignPermitTool {
+    const NAME: &'static str = "sign_permit";
+    type Error = ToolError;
+    type Args = SignPermitArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "sign_permit".to_string(),
+            description: "Sign an EIP-2612 permit so a spender can use the owner's tokens without an approve transaction. Returns v, r, s and the deadline for permit() or a permit-and-swap call".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "token": {
+                        "type": "string",
+                        "description": "Address of the token (resolve symbols with resolve_token)"
+                    },
+                    "owner": {
+                        "type": "string",
+                        "description": "Token holder signing the permit"
+                    },
+                    "spender": {
+                        "type": "string",
+                        "description": "Address allowed to spend the tokens"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "Allowance in the token's smallest unit"
+                    },
+                    "deadline": {
+                        "type": "integer",
+                        "description": "Unix timestamp the permit expires at (optional), defaults to one hour from now"
+                    }
+                },
+                "required": ["token", "owner", "spender", "value"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client
+            .sign_permit(&args.token, &args.owner, &args.spender, &args.value, args.deadline)
+            .await?;
+        Ok(result)
+    }
+}
+
+// Sign In With Ethereum Tool
+
+#[derive(Deserialize, Serialize)]
+pub struct SignInWithEthereumArgs {
+    pub address: String,
+    pub domain: String,
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Vec<String>>,
+}
+
+#[derive(Clone)]
+pub struct SignInWithEthereumTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl SignInWithEthereumTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for SignInWithEthereumTool {
+    const NAME: &'static str = "sign_in_with_ethereum";
+    type Error = ToolError;
+    type Args = SignInWithEthereumArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "sign_in_with_ethereum".to_string(),
+            description: "Build and sign a Sign-In-With-Ethereum (EIP-4361) message so a site logs the account in. Returns the message and signature to hand to the site".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "string",
+                        "description": "Account signing in"
+                    },
+                    "domain": {
+                        "type": "string",
+                        "description": "Host asking for the sign-in without a scheme, e.g. app.example.com"
+                    },
+                    "uri": {
+                        "type": "string",
+                        "description": "Absolute URI signed in to, e.g. https://app.example.com/login"
+                    },
+                    "statement": {
+                        "type": "string",
+                        "description": "One line the user agrees to (optional)"
+                    },
+                    "nonce": {
+                        "type": "string",
+                        "description": "Nonce the site issued (optional), a random one is generated otherwise"
+                    },
+                    "expiration_secs": {
+                        "type": "integer",
+                        "description": "Seconds the sign-in stays valid (optional), defaults to 600"
+                    },
+                    "chain_id": {
+                        "type": "integer",
+                        "description": "Chain id stated in the message (optional), defaults to the connected chain's"
+                    },
+                    "request_id": {
+                        "type": "string",
+                        "description": "Request id the site asked for (optional)"
+                    },
+                    "resources": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "URIs the sign-in grants access to (optional)"
+                    }
+                },
+                "required": ["address", "domain", "uri"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.sign_in_with_ethereum(serde_json::to_value(&args)?).await?;
+        Ok(result)
+    }
+}
+
+// Propose Safe Transaction Tool
+
+#[derive(Deserialize)]
+pub struct ProposeSafeTransactionArgs {
+    pub safe: String,
+    pub to: String,
+    pub value: String,
+    pub data: Option<String>,
+    pub proposer: String,
+    pub nonce: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct ProposeSafeTransactionTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl ProposeSafeTransactionTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for ProposeSafeTransactionTool {
+    const NAME: &'static str = "propose_safe_transaction";
+    type Error = ToolError;
+    type Args = ProposeSafeTransactionArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "propose_safe_transaction".to_string(),
+            description: "Propose a transaction from a Safe multisig to the Safe Transaction Service, signed by the proposer. It is only executed after enough owners confirm it".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "safe": {
+                        "type": "string",
+                        "description": "The Safe address"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "Recipient or contract the Safe calls"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "Amount of ETH to send from the Safe (in wei)"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "Calldata (hex encoded, optional)"
+                    },
+                    "proposer": {
+                        "type": "string",
+                        "description": "Owner or delegate address signing the proposal"
+                    },
+                    "nonce": {
+                        "type": "integer",
+                        "description": "Safe nonce (optional), defaults to the current one"
+                    }
+                },
+                "required": ["safe", "to", "value", "proposer"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client
+            .propose_safe_transaction(&args.safe, &args.to, &args.value, args.data.as_deref(), &args.proposer, args.nonce)
+            .await?;
+        Ok(result)
+    }
+}
+
+// Send User Operation Tool
+
+#[derive(Deserialize)]
+pub struct SendUserOperationArgs {
+    pub account: String,
+    pub owner: String,
+    pub to: String,
+    pub value: String,
+    pub data: Option<String>,
+    #[serde(default)]
+    pub sponsored: bool,
+}
+
+#[derive(Clone)]
+pub struct SendUserOperationTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl SendUserOperationTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for SendUserOperationTool {
+    const NAME: &'static str = "send_user_operation";
+    type Error = ToolError;
+    type Args = SendUserOperationArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "send_user_operation".to_string(),
+            description: "Make a call from an ERC-4337 smart account: builds a UserOperation, signs it as the account's owner and submits it to the bundler, waiting until it is included".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "account": {
+                        "type": "string",
+                        "description": "The smart account address"
+                    },
+                    "owner": {
+                        "type": "string",
+                        "description": "Owner address of the smart account, it signs the UserOperation"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "Recipient or contract the account calls"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "Amount of ETH to send from the account (in wei)"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "Calldata (hex encoded, optional)"
+                    },
+                    "sponsored": {
+                        "type": "boolean",
+                        "description": "Ask the configured paymaster to pay the gas (optional), for gasless or sponsored transactions"
+                    }
+                },
+                "required": ["account", "owner", "to", "value"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client
+            .send_user_operation(&args.account, &args.owner, &args.to, &args.value, args.data.as_deref(), args.sponsored)
+            .await?;
+        Ok(result)
+    }
+}
+
+// Account History Tool
+
+#[derive(Deserialize)]
+pub struct AccountHistoryArgs {
+    pub kind: HistoryKind,
+    pub address: String,
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct AccountHistoryTool {
+    etherscan: Option<Arc<EtherscanClient>>,
+}
+
+impl AccountHistoryTool {
+    pub fn new(etherscan: Option<Arc<EtherscanClient>>) -> Self {
+        Self { etherscan }
+    }
+}
+
+impl Tool for AccountHistoryTool {
+    const NAME: &'static str = "account_history";
+    type Error = ToolError;
+    type Args = AccountHistoryArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "account_history".to_string(),
+            description: "Page through the history of an address on Etherscan, newest first: its transactions, internal transactions, ERC20 transfers or ERC721 transfers".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "kind": {
+                        "type": "string",
+                        "enum": ["transactions", "internal", "erc20", "erc721"],
+                        "description": "Which history to return"
+                    },
+                    "address": {
+                        "type": "string",
+                        "description": "The address to get the history of"
+                    },
+                    "page": {
+                        "type": "integer",
+                        "description": "Page number starting at 1 (optional), ask for the next page while has_more is true"
+                    },
+                    "page_size": {
+                        "type": "integer",
+                        "description": "Items per page, up to 100 (optional)"
+                    }
+                },
+                "required": ["kind", "address"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let etherscan = self.etherscan.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Account history is not configured, no Etherscan API key was given")
+        })?;
+        let result = etherscan
+            .account_history(args.kind, &args.address, args.page.unwrap_or(1), args.page_size)
+            .await?;
+        Ok(result)
+    }
+}
+
+// Dune Query Tool
+
+#[derive(Deserialize)]
+pub struct DuneQueryArgs {
+    pub query_id: u64,
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub fresh: bool,
+    pub limit: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct DuneQueryTool {
+    dune: Option<Arc<DuneClient>>,
+}
+
+impl DuneQueryTool {
+    pub fn new(dune: Option<Arc<DuneClient>>) -> Self {
+        Self { dune }
+    }
+}
+
+impl Tool for DuneQueryTool {
+    const NAME: &'static str = "dune_query";
+    type Error = ToolError;
+    type Args = DuneQueryArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "dune_query".to_string(),
+            description: "Run a saved Dune Analytics query by ID, with optional parameters, and return its result rows".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query_id": {
+                        "type": "integer",
+                        "description": "ID of the saved Dune query"
+                    },
+                    "parameters": {
+                        "type": "object",
+                        "description": "Query parameters by name (optional), e.g. {\"address\": \"0x...\"}"
+                    },
+                    "fresh": {
+                        "type": "boolean",
+                        "description": "Execute the query again instead of using its latest stored result (optional, queries with parameters always run)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum rows to return (optional)"
+                    }
+                },
+                "required": ["query_id"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let dune = self.dune.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Dune queries are disabled, no Dune API key was given")
+        })?;
+        let result = dune.run_query(args.query_id, args.parameters, args.fresh, args.limit).await?;
+        Ok(result)
+    }
+}
+
+// Tenderly Simulation Tool
+
+#[derive(Clone)]
+pub struct TenderlySimulateTool {
+    tenderly: Option<Arc<TenderlyClient>>,
+}
+
+impl TenderlySimulateTool {
+    pub fn new(tenderly: Option<Arc<TenderlyClient>>) -> Self {
+        Self { tenderly }
+    }
+}
+
+impl Tool for TenderlySimulateTool {
+    const NAME: &'static str = "tenderly_simulate";
+    type Error = ToolError;
+    type Args = SimulationRequest;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "tenderly_simulate".to_string(),
+            description: "Simulate a transaction on Tenderly without sending it, returning the decoded call trace, asset changes and revert reason".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "from": {
+                        "type": "string",
+                        "description": "Sender address"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "Recipient or contract address"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "Amount in wei (optional)"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "Hex encoded calldata (optional)"
+                    },
+                    "gas_limit": {
+                        "type": "integer",
+                        "description": "Gas limit (optional)"
+                    }
+                },
+                "required": ["from", "to"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let tenderly = self.tenderly.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Tenderly simulation is not configured, no Tenderly access key was given")
+        })?;
+        let result = tenderly.simulate(&args).await?;
+        Ok(result)
+    }
+}
+
+// Get Contract Code Tool
+
+#[derive(Deserialize)]
+pub struct GetContractCodeArgs {
+    pub address: String,
+    // Explicitly bypass the tool-result cache
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+#[derive(Clone)]
+pub struct GetContractCodeTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl GetContractCodeTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for GetContractCodeTool {
+
+    const NAME: &'static str = "get_contract_code";
+    type Error = ToolError;
+    type Args = GetContractCodeArgs;
+    type Output = ContractCode;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_contract_code".to_string(),
+            description: "Get the contract code of an Ethereum address".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "string",
+                        "description": "The Ethereum address to get the contract code for"
+                    },
+                    "fresh": {
+                        "type": "boolean",
+                        "description": "Set to true to skip cached results and read fresh on-chain state"
+                    }
+                },
+                "required": ["address"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+
+        let result = self.client.get_contract_code(&args.address, args.fresh).await?;
+        Ok(result)
+    }
+}
+
+// ERC20 Balance Tool
+
+#[derive(Deserialize)]
+pub struct Erc20BalanceArgs {
+    pub address: String,
+    pub token_address: String,
+    // Explicitly bypass the tool-result cache
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+
+#[derive(Clone)]
+pub struct Erc20BalanceTool {
+    client: Arc<FoundryMcpClient>,
+}
+
+impl Erc20BalanceTool {
+    pub fn new(client: Arc<FoundryMcpClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl Tool for Erc20BalanceTool {
+    const NAME: &'static str = "erc20_balance";
+    type Error = ToolError;
+    type Args = Erc20BalanceArgs;
+    type Output = TokenBalance;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "erc20_balance".to_string(),
+            description: "Get the balance of an ERC20 token for an address".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "string",
+                        "description": "The address to check the balance of"
+                    },
+                    "token_address": {
+                        "type": "string",
+                        "description": "The address of the ERC20 token"
+                    },
+                    "fresh": {
+                        "type": "boolean",
+                        "description": "Set to true to skip cached results and read fresh on-chain state"
+                    }
+                },
+                "required": ["address", "token_address"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.erc20_balance(&args.address, &args.token_address, args.fresh).await?;
+        Ok(result)
+    }
+}
+// Web Search Tool
+
+#[derive(Deserialize)]
+pub struct WebSearchArgs {
+    pub query: String,
+    pub count: Option<u32>,
+    pub country: Option<String>,
+    pub search_lang: Option<String>,
+    #[serde(default)]
+    pub fetch_top: bool,
+}
+
+#[derive(Clone)]
+pub struct WebSearchTool {
+    provider: Option<Arc<dyn SearchProvider>>,
+    defaults: SearchOptions,
+    max_snippet_chars: usize,
+    page_summary_chars: usize,
+    cache: Option<Arc<SearchCache>>,
+}
+
+impl WebSearchTool {
+
+    pub fn new(provider: Option<Arc<dyn SearchProvider>>) -> Self {
+        let config = SearchConfig::default();
+        Self {
+            provider,
+            defaults: config.defaults,
+            max_snippet_chars: config.max_snippet_chars,
+            page_summary_chars: config.page_summary_chars,
+            cache: None,
+        }
+    }
+
+    // Reuse responses from `cache` for searches that were already made
+    pub fn with_cache(mut self, cache: Arc<SearchCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // Options used when the model doesn't pass its own
+    pub fn with_defaults(mut self, defaults: SearchOptions) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    // Default options and result sizes from the search config
+    pub fn with_config(self, config: &SearchConfig) -> Self {
+        Self {
+            max_snippet_chars: config.max_snippet_chars,
+            page_summary_chars: config.page_summary_chars,
+            ..self.with_defaults(config.defaults.clone())
+        }
+    }
+
+    async fn search(&self, args: WebSearchArgs) -> Result<serde_json::Value> {
+        let provider = self.provider.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Web search is not configured, no search provider was given")
+        })?;
+
+        let options = SearchOptions {
+            count: args.count.unwrap_or(self.defaults.count),
+            country: args.country.or_else(|| self.defaults.country.clone()),
+            search_lang: args.search_lang.or_else(|| self.defaults.search_lang.clone()),
+        };
+        let key = SearchCache::key(provider.name(), &args.query, &options, args.fetch_top);
+        if let Some(response) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            debug!("Search cache hit for {}", args.query);
+            return Ok(response);
+        }
+
+        let results = distill(provider.search(&args.query, &options).await?, self.max_snippet_chars);
+
+        // The top page is best effort, the results are still worth returning without it
+        let top_page = match results.first() {
+            Some(top) if args.fetch_top => Some(
+                summarize_page(&top.url, self.page_summary_chars)
+                    .await
+                    .unwrap_or_else(|e| json!({ "url": top.url, "error": format!("Failed to fetch the page: {}", e) })),
+            ),
+            _ => None,
+        };
+
+        let mut response = json!({
+            "provider": provider.name(),
+            "results": results,
+        });
+        if let Some(top_page) = top_page {
+            response["top_page"] = top_page;
+        }
+        if let Some(cache) = &self.cache {
+            cache.insert(key, response.clone());
+        }
+        Ok(response)
+    }
+}
+
+impl Tool for WebSearchTool {
+
+    const NAME: &'static str = "web_search";
+    type Error = ToolError;
+    type Args = WebSearchArgs;
+    type Output = serde_json::Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "web_search".to_string(),
+            description: "Search the web for information, returns titles, URLs and snippets".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The query to search the web for"
+                    },
+                    "count": {
+                        "type": "integer",
+                        "description": "Number of results to return (optional, up to 20)"
+                    },
+                    "country": {
+                        "type": "string",
+                        "description": "Two letter country code to localize results, e.g. \"us\" (optional)"
+                    },
+                    "search_lang": {
+                        "type": "string",
+                        "description": "Two letter language code of the results, e.g. \"en\" (optional)"
+                    },
+                    "fetch_top": {
+                        "type": "boolean",
+                        "description": "Also fetch the top result's page and return the start of its text (optional), for when the snippets don't answer the question"
+                    }
+                },
+                "required": ["query"]
+            })
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.search(args).await?;
+        Ok(result)
+    }
+}
+
+// MCP Bridge Tool
+
+// foundry-mcp tools with a hand-written wrapper above, or only called by the client itself
+const WRAPPED_TOOLS: &[&str] = &[
+    "validate_address",
+    "send_transaction",
+    "simulate_transaction",
+    "preview_transaction",
+    "balance",
+    "get_contract_code",
+    "erc20_balance",
+    "resolve_ens",
+    "use_network",
+    "resolve_token",
+    "get_price",
+    "gas_oracle",
+    "safe_info",
+    "propose_safe_transaction",
+    "sign_permit",
+    "sign_in_with_ethereum",
+    // Steps of send_user_operation, which the agent-side tool runs in order
+    "build_user_operation",
+    "sign_user_operation",
+    "send_user_operation",
+    // Used by send_transaction in place of sending when `unsigned_transactions` is on
+    "build_unsigned_transaction",
+    // Run snapshots for undo, only the agent itself takes and reverts them
+    "evm_snapshot",
+    "evm_revert",
+];
+
+// Anvil cheatcodes rewriting chain state outside of any transaction, never bridged but wrapped
+// by `CheatcodeTool` so each call is confirmed first
+pub const CHEATCODE_TOOLS: &[&str] = &["set_code", "set_storage_at", "set_nonce", "reset_fork"];
+
+// Bridged tools the defi_agent gets besides its wrappers
+pub const DEFI_TOOLS: &[&str] = &["defi_positions"];
+
+// Any other foundry-mcp tool, described by the server's own definition so new server tools
+// reach the execution agent without a wrapper here
+#[derive(Clone)]
+pub struct McpBridgeTool {
+    client: Arc<FoundryMcpClient>,
+    definition: ToolDefinition,
+}
+
+impl McpBridgeTool {
+    pub fn new(client: Arc<FoundryMcpClient>, tool: &rmcp::model::Tool) -> Self {
+        Self {
+            client,
+            definition: ToolDefinition {
+                name: tool.name.to_string(),
+                description: tool.description.as_deref().unwrap_or_default().to_string(),
+                parameters: serde_json::Value::Object(tool.input_schema.as_ref().clone()),
+            },
+        }
+    }
+}
+
+impl Tool for McpBridgeTool {
+    // Only a placeholder, every bridged tool goes by its server name
+    const NAME: &'static str = "mcp_bridge";
+    type Error = ToolError;
+    type Args = serde_json::Value;
+    type Output = serde_json::Value;
+
+    fn name(&self) -> String {
+        self.definition.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.client.call(&self.definition.name, args).await?;
+        Ok(result)
+    }
+}
+
+// Bridge every listed tool that has no wrapper. Cheatcodes and tools that declare they change
+// state are left out, changing anything has to go through a wrapper with the guard checks.
+pub fn bridge_tools(client: Arc<FoundryMcpClient>, tools: &[rmcp::model::Tool]) -> Vec<McpBridgeTool> {
+    tools
+        .iter()
+        .filter(|tool| !WRAPPED_TOOLS.contains(&tool.name.as_ref()) && !CHEATCODE_TOOLS.contains(&tool.name.as_ref()))
+        .filter(|tool| {
+            let annotations = tool.annotations.as_ref();
+            annotations.and_then(|annotations| annotations.read_only_hint) != Some(false)
+                && annotations.and_then(|annotations| annotations.destructive_hint) != Some(true)
+        })
+        .map(|tool| McpBridgeTool::new(client.clone(), tool))
+        .collect()
+}
+
+// A listed anvil cheatcode, described by the server's own definition like a bridged tool but
+// confirmed per the `[guard] cheatcodes` policy before it runs
+#[derive(Clone)]
+pub struct CheatcodeTool(McpBridgeTool);
+
+impl Tool for CheatcodeTool {
+    // Only a placeholder, every cheatcode goes by its server name
+    const NAME: &'static str = "cheatcode";
+    type Error = ToolError;
+    type Args = serde_json::Value;
+    type Output = serde_json::Value;
+
+    fn name(&self) -> String {
+        self.0.definition.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        self.0.definition.clone()
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let result = self.0.client.cheatcode(&self.0.definition.name, args).await?;
+        Ok(result)
+    }
+}
+
+// The cheatcodes among the listed tools, foundry-mcp only lists them on anvil and Hardhat nodes
+pub fn cheatcode_tools(client: Arc<FoundryMcpClient>, tools: &[rmcp::model::Tool]) -> Vec<CheatcodeTool> {
+    tools
+        .iter()
+        .filter(|tool| CHEATCODE_TOOLS.contains(&tool.name.as_ref()))
+        .map(|tool| CheatcodeTool(McpBridgeTool::new(client.clone(), tool)))
+        .collect()
+}
+
+// Tool collection for managing all available tools
+pub struct McpToolSet {
+    pub validate_address: ValidateAddressTool,
+    pub send_transaction: SendTransactionTool,
+    pub balance: BalanceTool,
+    pub web_search: WebSearchTool,
+    pub erc20_balance: Erc20BalanceTool,
+    pub get_price: GetPriceTool,
+    pub gas_oracle: GasOracleTool,
+}
+
+#[allow(dead_code)]
+impl McpToolSet {
+    pub fn new(client: Arc<FoundryMcpClient>, search_provider: Option<Arc<dyn SearchProvider>>) -> Self {
+        Self {
+            validate_address: ValidateAddressTool::new(client.clone()),
+            send_transaction: SendTransactionTool::new(client.clone()),
+            balance: BalanceTool::new(client.clone()),
+            web_search: WebSearchTool::new(search_provider),
+            erc20_balance: Erc20BalanceTool::new(client.clone()),
+            get_price: GetPriceTool::new(client.clone()),
+            gas_oracle: GasOracleTool::new(client.clone()),
+        }
+    }
+
+    pub fn list_tools(&self) -> Vec<String> {
+        vec![
+            "validate_address".to_string(),
+            "send_transaction".to_string(),
+            "balance".to_string(),
+            "web_search".to_string(),
+            "erc20_balance".to_string(),
+            "get_price".to_string(),
+            "gas_oracle".to_string(),
+        ]
+    }
+
+    pub async fn get_tool_definitions(&self) -> Vec<ToolDefinition> {
+        vec![
+            self.validate_address.definition("".to_string()).await,
+            self.send_transaction.definition("".to_string()).await,
+            self.balance.definition("".to_string()).await,
+            self.web_search.definition("".to_string()).await,
+            self.erc20_balance.definition("".to_string()).await,
+            self.get_price.definition("".to_string()).await,
+            self.gas_oracle.definition("".to_string()).await,
+        ]
+    }
+}
+
+
+// Helper function to create a tool set with a new MCP client
+#[allow(dead_code)]
+pub async fn create_mcp_tool_set(config: &AgentConfig, search_provider: Option<Arc<dyn SearchProvider>>) -> Result<McpToolSet> {
+    let client = FoundryMcpClient::new(config, RunJournal::new(), EventSink::default()).await?;
+    let client = Arc::new(client);
+    let mut tool_set = McpToolSet::new(client, search_provider);
+    tool_set.web_search = tool_set.web_search.with_defaults(config.search.defaults.clone());
+    Ok(tool_set)
+}
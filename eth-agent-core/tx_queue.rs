@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+// Nodes accept a replacement that pays at least 10% more, bump by 12.5% to be safe
+const BUMP_NUMERATOR: u128 = 9;
+const BUMP_DENOMINATOR: u128 = 8;
+
+fn wei(value: &Value, name: &str) -> Option<u128> {
+    match value.get(name)? {
+        Value::String(wei) => wei.parse().ok(),
+        wei => wei.as_u64().map(u128::from),
+    }
+}
+
+fn bump(fee: u128) -> u128 {
+    (fee * BUMP_NUMERATOR).div_ceil(BUMP_DENOMINATOR).max(fee + 1)
+}
+
+// Fees a transaction was sent with, either a legacy gas price or EIP-1559 fees
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fees {
+    pub gas_price: Option<u128>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+}
+
+impl Fees {
+    // From send_transaction's `fees` or a gas oracle tier
+    pub fn from_json(value: &Value) -> Self {
+        Self {
+            gas_price: wei(value, "gas_price"),
+            max_fee_per_gas: wei(value, "max_fee_per_gas"),
+            max_priority_fee_per_gas: wei(value, "max_priority_fee_per_gas"),
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.gas_price.is_some() || (self.max_fee_per_gas.is_some() && self.max_priority_fee_per_gas.is_some())
+    }
+
+    // Fees high enough for the node to let a transaction replace one sent with these
+    pub fn bumped(&self) -> Self {
+        Self {
+            gas_price: self.gas_price.map(bump),
+            max_fee_per_gas: self.max_fee_per_gas.map(bump),
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.map(bump),
+        }
+    }
+
+    // Set these fees on send_transaction arguments, replacing any fee tier
+    pub fn apply(&self, arguments: &mut Value) {
+        let Some(arguments) = arguments.as_object_mut() else {
+            return;
+        };
+        arguments.remove("fee_tier");
+        match (self.gas_price, self.max_fee_per_gas, self.max_priority_fee_per_gas) {
+            (Some(gas_price), _, _) => {
+                arguments.insert("gas_price".to_string(), serde_json::json!(gas_price));
+            }
+            (None, Some(max_fee), Some(max_priority_fee)) => {
+                arguments.remove("gas_price");
+                arguments.insert("max_fee_per_gas".to_string(), serde_json::json!(max_fee));
+                arguments.insert("max_priority_fee_per_gas".to_string(), serde_json::json!(max_priority_fee));
+            }
+            _ => {}
+        }
+    }
+}
+
+// A sent transaction that was not seen confirmed yet
+#[derive(Debug, Clone)]
+pub struct PendingTransaction {
+    pub hash: String,
+    pub nonce: u64,
+    pub fees: Fees,
+}
+
+// Transactions of one sender: the nonce to use next and what is still waiting to be mined
+#[derive(Debug, Default)]
+pub struct SenderQueue {
+    // Read from the node by the server while unknown, e.g. for the first send
+    pub next_nonce: Option<u64>,
+    pub pending: Vec<PendingTransaction>,
+}
+
+impl SenderQueue {
+    pub fn sent(&mut self, hash: String, nonce: u64, fees: Fees) {
+        self.next_nonce = Some(nonce + 1);
+        // A replacement takes the place of the transaction it replaced
+        self.pending.retain(|pending| pending.nonce != nonce);
+        self.pending.push(PendingTransaction { hash, nonce, fees });
+    }
+
+    // Fees of the pending transaction holding `nonce`, which a replacement has to outbid
+    pub fn fees_at(&self, nonce: u64) -> Option<Fees> {
+        self.pending.iter().find(|pending| pending.nonce == nonce).map(|pending| pending.fees.clone())
+    }
+}
+
+// Outgoing transactions per sender. Holding a sender's lock for a whole send serializes its
+// transactions, so concurrent tool calls never race for the same nonce.
+#[derive(Default)]
+pub struct TransactionQueue {
+    senders: Mutex<HashMap<String, Arc<AsyncMutex<SenderQueue>>>>,
+}
+
+impl TransactionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn lock(&self, sender: &str) -> OwnedMutexGuard<SenderQueue> {
+        let queue = self.senders.lock().unwrap().entry(sender.to_lowercase()).or_default().clone();
+        queue.lock_owned().await
+    }
+
+    // Every sender seen so far, to wait for all of their pending transactions
+    pub fn senders(&self) -> Vec<String> {
+        self.senders.lock().unwrap().keys().cloned().collect()
+    }
+
+    // Forget nonces and pending transactions, after cheatcodes rewrote account state behind the queue's back
+    pub fn reset(&self) {
+        self.senders.lock().unwrap().clear();
+    }
+}
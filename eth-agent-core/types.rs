@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A natural language request for the agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPrompt {
+    pub id: String,
+    pub natural_language: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub context: HashMap<String, serde_json::Value>,
+}
+
+impl UserPrompt {
+    pub fn new(natural_language: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            natural_language: natural_language.into(),
+            timestamp: chrono::Utc::now(),
+            context: HashMap::new(),
+        }
+    }
+}
+
+/// Final output of a run. `error_message` is set when the run stopped early with partial results,
+/// `status` tells why. Next to the final answer it carries the whole transcript: the plan that
+/// ran, every step with its tool calls, evaluation and timing, and the hashes of sent transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentResult {
+    pub error_message: Option<String>,
+    pub result: String,
+    pub status: PlanStatus,
+    #[serde(default)]
+    pub plan: Option<AgentPlan>,
+    #[serde(default)]
+    pub steps: Vec<StepRecord>,
+    #[serde(default)]
+    pub transaction_hashes: Vec<String>,
+    /// Concrete values the run produced, taken from tool outputs rather than the answer's prose
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+}
+
+/// A value a step produced, for automation that needs it without parsing the answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Artifact {
+    TransactionHash {
+        step_number: u32,
+        hash: String,
+    },
+    /// `abi` is known when the run inferred the contract's interface after deploying it
+    DeployedContract {
+        step_number: u32,
+        address: String,
+        transaction_hash: Option<String>,
+        abi: Option<serde_json::Value>,
+    },
+    /// `balance` in the smallest unit, wei for ETH
+    Balance {
+        step_number: u32,
+        address: String,
+        token: Option<String>,
+        balance: String,
+        decimals: u8,
+    },
+    /// A signed EIP-2612 permit
+    Signature {
+        step_number: u32,
+        signer: String,
+        signature: String,
+        digest: Option<String>,
+        deadline: Option<u64>,
+    },
+    /// A transaction assembled for an external wallet to sign, see `unsigned_transactions`.
+    /// `transaction` is the JSON-RPC transaction object, `eip681` only set for plain transfers.
+    UnsignedTransaction {
+        step_number: u32,
+        transaction: serde_json::Value,
+        eip681: Option<String>,
+    },
+    /// Analysis or explanation, JSON from a tool or the text of an analysis step
+    Report {
+        step_number: u32,
+        title: String,
+        content: serde_json::Value,
+    },
+}
+
+impl AgentResult {
+    pub fn new(status: PlanStatus, result: String, error_message: Option<String>) -> Self {
+        Self {
+            error_message,
+            result,
+            status,
+            plan: None,
+            steps: vec![],
+            transaction_hashes: vec![],
+            artifacts: vec![],
+        }
+    }
+
+    /// Attach the transcript of the run that produced this result
+    pub fn with_transcript(mut self, run: &RunRecord) -> Self {
+        self.plan = run.plan.clone();
+        self.steps = run.steps.clone();
+        self.transaction_hashes = run.transaction_hashes();
+        self.artifacts = crate::artifacts::extract(&run.steps);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStep {
+    pub step_number: u32,
+    pub agent_name: String,
+    pub agent_prompt: String,
+    // Configured network an ethereum_agent step runs on, foundry-mcp's own network if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    // Chain id an ethereum_agent step must run on. Picks the configured network on that chain
+    // when no network is given, and is checked against the network otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<u64>,
+    // Checked with tool calls after the step, a failed one rejects it with the exact reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub postconditions: Vec<Postcondition>,
+}
+
+/// A machine-checkable claim about chain state after a step. Amounts are decimal strings in the
+/// smallest unit (wei, raw token units), changes are measured from right before the step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Postcondition {
+    // ETH balance grew by at least `amount`
+    BalanceIncreased { address: String, amount: String },
+    // ETH balance shrank by at least `amount`, e.g. a sender paying value plus gas
+    BalanceDecreased { address: String, amount: String },
+    // ERC20 balance grew by at least `amount`
+    TokenBalanceIncreased { address: String, token: String, amount: String },
+    // Code is deployed at `address`
+    HasCode { address: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StepStatus {
+    Planned,
+    Executing,
+    Completed,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentPlanResponse {
+    pub number_of_steps: u32,
+    pub steps: Vec<AgentStep>,
+}
+
+// An ENS name resolved once at the start of a run, every step uses this address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnsResolution {
+    pub name: String,
+    pub address: String,
+}
+
+impl EnsResolution {
+    // Name and address together, e.g. "vitalik.eth (0xd8dA...6045)"
+    pub fn display(&self) -> String {
+        format!("{} ({})", self.name, self.address)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentPlan {
+    pub id: String,
+    pub prompt: UserPrompt,
+    pub steps: Vec<AgentStep>,
+    pub max_steps: u32,
+    pub current_step: u32,
+    #[serde(default)]
+    pub ens_resolutions: Vec<EnsResolution>,
+    // TODO: Add status
+    //pub status: PlanStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlanStatus {
+    Planning,
+    Executing,
+    Completed,
+    Failed(String),
+    MaxStepsReached,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationScoreResponse {
+    pub score: u32,
+    pub reasoning: String,
+}
+
+// Issues the critique found in a plan before it ran, with a fixed plan when it could patch them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanCritiqueResponse {
+    #[serde(default)]
+    pub blocking_issues: Vec<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(default)]
+    pub patched_plan: Option<AgentPlanResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationResult {
+    pub plan_id: String,
+    pub original_prompt: String,
+    pub score: u32,
+    pub reasoning: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Goal {
+    pub id: String,
+    pub description: String,
+    pub success_criteria: Vec<String>,
+    pub constraints: Vec<String>,
+    pub priority: Priority,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deviation {
+    pub id: String,
+    pub goal_id: String,
+    pub description: String,
+    pub severity: DeviationSeverity,
+    pub detected_at: chrono::DateTime<chrono::Utc>,
+    pub correction_attempts: Vec<CorrectionAttempt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeviationSeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrectionAttempt {
+    pub id: String,
+    pub description: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub success: bool,
+    pub error_message: Option<String>,
+} 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub arguments: serde_json::Value,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl ToolCallRecord {
+    // The tool's JSON payload. MCP results are a list of content blocks, it's in the text block
+    pub fn payload(&self) -> Option<serde_json::Value> {
+        let result = self.result.as_ref()?;
+        let blocks = result.get("content").unwrap_or(result).as_array()?;
+        blocks.iter().find_map(|block| serde_json::from_str(block.get("text")?.as_str()?).ok())
+    }
+
+    // The hash of a submitted transaction, if this call sent one
+    pub fn transaction_hash(&self) -> Option<String> {
+        if self.tool != "send_transaction" && self.tool != "send_user_operation" {
+            return None;
+        }
+        self.payload()?.get("transaction_hash")?.as_str().map(|hash| hash.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepEvaluation {
+    pub score: u32,
+    pub reasoning: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub step_number: u32,
+    pub agent_name: String,
+    pub agent_prompt: String,
+    pub status: StepStatus,
+    pub output: Option<String>,
+    pub tool_calls: Vec<ToolCallRecord>,
+    #[serde(default)]
+    pub evaluation: Option<StepEvaluation>,
+    // Set when the step first started and when it completed or failed, retries included
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl StepRecord {
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        Some(self.finished_at? - self.started_at?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: String,
+    pub prompt: UserPrompt,
+    pub plan: Option<AgentPlan>,
+    pub steps: Vec<StepRecord>,
+    pub status: PlanStatus,
+    // Final answer of the run, set once it finishes
+    #[serde(default)]
+    pub result: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    // Gas cost of the plan estimated before it ran, next to what sent transactions cost
+    #[serde(default)]
+    pub cost_estimate: Option<PlanCostEstimate>,
+    #[serde(default)]
+    pub gas_spent_wei: Option<String>,
+}
+
+/// Gas a state-changing step is expected to use. `simulated` when its transaction was fully
+/// known up front, otherwise a typical amount for its kind of action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepCostEstimate {
+    pub step_number: u32,
+    pub action: String,
+    pub gas: u64,
+    pub simulated: bool,
+}
+
+/// Estimated gas cost of a plan's state-changing steps. The totals are missing when the gas
+/// price or ETH price could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanCostEstimate {
+    pub steps: Vec<StepCostEstimate>,
+    pub gas_price_wei: Option<String>,
+    pub total_wei: Option<String>,
+    pub total_usd: Option<f64>,
+}
+
+impl PlanCostEstimate {
+    pub fn total_gas(&self) -> u64 {
+        self.steps.iter().map(|step| step.gas).sum()
+    }
+
+    // One line summary, e.g. "2 transactions, ~63000 gas, ~0.000126 ETH (≈ $0.38)"
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} transaction(s), ~{} gas", self.steps.len(), self.total_gas());
+        if let Some(total_wei) = self.total_wei.as_deref().and_then(|wei| wei.parse::<f64>().ok()) {
+            description += &format!(", ~{:.6} ETH", total_wei / 1e18);
+        }
+        if let Some(total_usd) = self.total_usd {
+            description += &format!(" (≈ ${:.2})", total_usd);
+        }
+        description
+    }
+}
+
+/// ETH balance of an account before and after a what-if run, decimal wei. `network` is the
+/// configured network it was on, `None` for foundry-mcp's own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub network: Option<String>,
+    pub address: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Outcome of a plan executed on throwaway forks, see [`EthAgent::what_if`](crate::EthAgent::what_if).
+/// Nothing of it reached the real networks.
+#[derive(Debug, Clone)]
+pub struct WhatIfReport {
+    pub prompt: UserPrompt,
+    pub result: AgentResult,
+    pub balance_changes: Vec<BalanceChange>,
+    // The plan the fork run finished with, `None` when it needed a partial replan and no single
+    // plan covers the whole run
+    pub plan: Option<AgentPlan>,
+}
+
+impl RunRecord {
+    pub fn transaction_hashes(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.tool_calls.iter())
+            .filter_map(|call| call.transaction_hash())
+            .collect()
+    }
+}
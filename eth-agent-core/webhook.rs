@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use alloy_primitives::hex;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use crate::config::WebhooksConfig;
+use crate::error::AgentError;
+use crate::types::{AgentResult, RunRecord};
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>` when a secret is set
+pub const SIGNATURE_HEADER: &str = "X-Eth-Agent-Signature";
+
+/// Posts a JSON summary of every finished run to the configured webhook URLs, signed with a
+/// shared secret so receivers can check it came from this agent
+pub struct WebhookNotifier {
+    urls: Vec<String>,
+    secret: Option<String>,
+    http: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(secret: Option<String>, config: &WebhooksConfig) -> Self {
+        Self {
+            urls: config.urls.clone(),
+            secret,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Hex HMAC-SHA256 of `body` under `secret`, as sent in [`SIGNATURE_HEADER`]
+    pub fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    // Payload of a run that completed (possibly with partial results) or failed
+    pub fn payload(run: &RunRecord, outcome: &Result<AgentResult, AgentError>) -> Value {
+        let (event, result, error) = match outcome {
+            Ok(result) => ("run.completed", Some(result), result.error_message.clone()),
+            Err(e) => ("run.failed", None, Some(e.to_string())),
+        };
+        json!({
+            "event": event,
+            "run_id": run.id,
+            "prompt": run.prompt.natural_language,
+            "status": run.status,
+            "result": result.map(|result| result.result.clone()),
+            "error": error,
+            "transaction_hashes": run.transaction_hashes(),
+            "artifacts": result.map(|result| result.artifacts.clone()).unwrap_or_default(),
+            "started_at": run.started_at,
+            "finished_at": run.updated_at,
+        })
+    }
+
+    // Deliver to every URL. A webhook that can't be reached only logs a warning, the run's
+    // outcome doesn't depend on it.
+    pub async fn notify(&self, payload: &Value) {
+        let body = payload.to_string();
+        for url in &self.urls {
+            let mut request = self.http.post(url).header("Content-Type", "application/json").body(body.clone());
+            if let Some(secret) = &self.secret {
+                request = request.header(SIGNATURE_HEADER, Self::sign(secret, body.as_bytes()));
+            }
+            match request.send().await.and_then(|response| response.error_for_status()) {
+                Ok(_) => info!("Notified webhook {}", url),
+                Err(e) => warn!("Webhook {} failed: {}", url, e),
+            }
+        }
+    }
+}
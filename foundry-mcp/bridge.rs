@@ -0,0 +1,145 @@
+use alloy_primitives::U256;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+// LI.FI routes through the common bridges (Across, Stargate, the canonical rollup bridges, ...)
+// and returns the source chain transaction of the best route
+const LIFI_QUOTE_URL: &str = "https://li.quest/v1/quote";
+const LIFI_ROUTES_URL: &str = "https://li.quest/v1/advanced/routes";
+
+// Alternatives listed next to the quoted route
+const MAX_ROUTES: usize = 5;
+
+// How LI.FI names a chain's native token
+pub const NATIVE_TOKEN: &str = "0x0000000000000000000000000000000000000000";
+
+pub struct QuoteRequest<'a> {
+    pub from_chain: u64,
+    pub to_chain: u64,
+    pub from_token: &'a str,
+    pub to_token: &'a str,
+    pub amount: &'a str,
+    pub from: &'a str,
+    pub to: &'a str,
+}
+
+// A decimal wei amount from a hex or decimal quantity
+fn wei(value: Option<&Value>) -> Option<String> {
+    U256::from_str(value?.as_str()?).ok().map(|value| value.to_string())
+}
+
+fn lifi_post(url: &str) -> reqwest::RequestBuilder {
+    let post = reqwest::Client::new().post(url);
+    match std::env::var("LIFI_API_KEY") {
+        Ok(key) => post.header("x-lifi-api-key", key),
+        Err(_) => post,
+    }
+}
+
+fn usd_costs<'a>(costs: impl Iterator<Item = &'a Value>) -> f64 {
+    costs.filter_map(|cost| cost.get("amountUSD")?.as_str()?.parse::<f64>().ok()).sum()
+}
+
+// Routes LI.FI would take, cheapest first: the bridges and swaps of each, the amount received, fees
+// and duration. They compare the options, only the quoted route comes with a transaction.
+pub async fn routes(request: &QuoteRequest<'_>) -> Result<Vec<Value>, String> {
+    let response: Value = lifi_post(LIFI_ROUTES_URL)
+        .json(&json!({
+            "fromChainId": request.from_chain,
+            "toChainId": request.to_chain,
+            "fromTokenAddress": request.from_token,
+            "toTokenAddress": request.to_token,
+            "fromAmount": request.amount,
+            "fromAddress": request.from,
+            "toAddress": request.to,
+            "options": { "order": "CHEAPEST" }
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("LI.FI request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid LI.FI response: {}", e))?;
+
+    let routes = response.get("routes").and_then(|routes| routes.as_array()).ok_or("LI.FI returned no routes")?;
+    Ok(routes
+        .iter()
+        .take(MAX_ROUTES)
+        .map(|route| {
+            let steps = route.get("steps").and_then(|steps| steps.as_array()).cloned().unwrap_or_default();
+            let estimates: Vec<&Value> = steps.iter().filter_map(|step| step.get("estimate")).collect();
+            let costs = |kind: &str| -> f64 {
+                estimates
+                    .iter()
+                    .map(|estimate| usd_costs(estimate.get(kind).and_then(|costs| costs.as_array()).into_iter().flatten()))
+                    .sum()
+            };
+            json!({
+                "tools": steps.iter().filter_map(|step| step.get("tool")).collect::<Vec<_>>(),
+                "to_amount": route.get("toAmount"),
+                "to_amount_min": route.get("toAmountMin"),
+                "fees_usd": format!("{:.2}", costs("feeCosts") + costs("gasCosts")),
+                "duration_secs": estimates.iter().filter_map(|estimate| estimate.get("executionDuration")?.as_f64()).sum::<f64>(),
+                "tags": route.get("tags")
+            })
+        })
+        .collect())
+}
+
+// Ask LI.FI for the cheapest route moving `amount` of `from_token` across chains. LIFI_API_KEY
+// raises the rate limit, quotes work without one.
+pub async fn quote(request: &QuoteRequest<'_>) -> Result<Value, String> {
+    let mut get = reqwest::Client::new().get(LIFI_QUOTE_URL).query(&[
+        ("fromChain", request.from_chain.to_string().as_str()),
+        ("toChain", request.to_chain.to_string().as_str()),
+        ("fromToken", request.from_token),
+        ("toToken", request.to_token),
+        ("fromAmount", request.amount),
+        ("fromAddress", request.from),
+        ("toAddress", request.to),
+    ]);
+    if let Ok(key) = std::env::var("LIFI_API_KEY") {
+        get = get.header("x-lifi-api-key", key);
+    }
+    let response: Value = get
+        .send()
+        .await
+        .map_err(|e| format!("LI.FI request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid LI.FI response: {}", e))?;
+    if let Some(message) = response.get("message").and_then(|message| message.as_str()) {
+        return Err(format!("LI.FI found no route: {}", message));
+    }
+
+    let estimate = response.get("estimate").ok_or("LI.FI returned no estimate")?;
+    let transaction = response.get("transactionRequest").ok_or("LI.FI returned no transaction")?;
+    let costs = |kind: &str| estimate.get(kind).and_then(|costs| costs.as_array()).into_iter().flatten();
+    let fees_usd = usd_costs(costs("feeCosts")) + usd_costs(costs("gasCosts"));
+    // Tokens other than the native one have to be approved to this spender first
+    let approval_address = estimate
+        .get("approvalAddress")
+        .and_then(|spender| spender.as_str())
+        .filter(|_| !request.from_token.eq_ignore_ascii_case(NATIVE_TOKEN));
+
+    Ok(json!({
+        "success": true,
+        "bridge": response.get("tool"),
+        "from_chain_id": request.from_chain,
+        "to_chain_id": request.to_chain,
+        "from_token": request.from_token,
+        "to_token": request.to_token,
+        "from_amount": request.amount,
+        "to_amount": estimate.get("toAmount"),
+        "to_amount_min": estimate.get("toAmountMin"),
+        "duration_secs": estimate.get("executionDuration"),
+        "fees_usd": format!("{:.2}", fees_usd),
+        "approval_address": approval_address,
+        "transaction": {
+            "from": request.from,
+            "to": transaction.get("to"),
+            "value": wei(transaction.get("value")).unwrap_or_else(|| "0".to_string()),
+            "data": transaction.get("data")
+        }
+    }))
+}
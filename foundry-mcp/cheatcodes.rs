@@ -0,0 +1,141 @@
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use serde_json::{json, Value};
+
+// Anvil (and Hardhat-compatible) cheatcodes that rewrite fork state directly. They are only
+// listed and only run when the node is a local dev node.
+pub const CHEATCODE_TOOLS: &[&str] = &["set_code", "set_storage_at", "set_nonce", "reset_fork", "evm_snapshot", "evm_revert"];
+
+// Anvil forks keep the chain id of the forked network, so ask the node what it is
+pub async fn supported(provider: &RootProvider<AnyNetwork>) -> Result<(), String> {
+    let client: String = provider
+        .raw_request("web3_clientVersion".into(), ())
+        .await
+        .map_err(|e| format!("Failed to get the node version: {}", e))?;
+    let client = client.to_lowercase();
+    if client.starts_with("anvil") || client.starts_with("hardhatnetwork") {
+        Ok(())
+    } else {
+        Err(format!("Cheatcodes only work on a local anvil node, the connected node is {}", client))
+    }
+}
+
+// Replace the runtime code at `address`, e.g. to put a mock oracle in place of the real one
+pub async fn set_code(provider: &RootProvider<AnyNetwork>, address: Address, code: Bytes) -> Result<Value, String> {
+    let previous = provider.get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    let _: Value = provider
+        .raw_request("anvil_setCode".into(), (address, &code))
+        .await
+        .map_err(|e| format!("anvil_setCode failed: {}", e))?;
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "previous_code_hash": if previous.is_empty() { None } else { Some(keccak256(&previous).to_string()) },
+        "previous_code_size": previous.len(),
+        "code_hash": keccak256(&code).to_string(),
+        "code_size": code.len()
+    }))
+}
+
+// Slot of `mapping[key]` for a mapping declared at `slot`, e.g. an ERC-20 balance
+pub fn mapping_slot(key: B256, slot: B256) -> B256 {
+    keccak256([key.as_slice(), slot.as_slice()].concat())
+}
+
+// Overwrite one storage slot of `address`
+pub async fn set_storage_at(provider: &RootProvider<AnyNetwork>, address: Address, slot: B256, value: B256) -> Result<Value, String> {
+    let previous = provider
+        .get_storage_at(address, U256::from_be_bytes(slot.0))
+        .await
+        .map_err(|e| format!("Failed to read the slot: {}", e))?;
+    let _: Value = provider
+        .raw_request("anvil_setStorageAt".into(), (address, slot, value))
+        .await
+        .map_err(|e| format!("anvil_setStorageAt failed: {}", e))?;
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "slot": slot.to_string(),
+        "previous_value": B256::from(previous).to_string(),
+        "value": value.to_string()
+    }))
+}
+
+// Set the next nonce of `address`, e.g. to unstick an account after dropped transactions
+pub async fn set_nonce(provider: &RootProvider<AnyNetwork>, address: Address, nonce: u64) -> Result<Value, String> {
+    let previous = provider
+        .get_transaction_count(address)
+        .await
+        .map_err(|e| format!("Failed to get the nonce: {}", e))?;
+    let _: Value = provider
+        .raw_request("anvil_setNonce".into(), (address, U256::from(nonce)))
+        .await
+        .map_err(|e| format!("anvil_setNonce failed: {}", e))?;
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "previous_nonce": previous,
+        "nonce": nonce
+    }))
+}
+
+// Throw away all local state: back to genesis (or the original fork block) without parameters,
+// or onto a new fork of `fork_url` at `block_number`
+pub async fn reset(provider: &RootProvider<AnyNetwork>, fork_url: Option<&str>, block_number: Option<u64>) -> Result<Value, String> {
+    let reset: Result<Value, _> = if fork_url.is_none() && block_number.is_none() {
+        provider.raw_request("anvil_reset".into(), ()).await
+    } else {
+        let mut forking = serde_json::Map::new();
+        if let Some(fork_url) = fork_url {
+            forking.insert("jsonRpcUrl".to_string(), json!(fork_url));
+        }
+        if let Some(block_number) = block_number {
+            forking.insert("blockNumber".to_string(), json!(block_number));
+        }
+        provider.raw_request("anvil_reset".into(), (json!({ "forking": forking }),)).await
+    };
+    reset.map_err(|e| format!("anvil_reset failed: {}", e))?;
+
+    let chain_id = provider.get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+    let block_number = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+    Ok(json!({
+        "success": true,
+        "forked_from": fork_url,
+        "chain_id": chain_id,
+        "block_number": block_number
+    }))
+}
+
+// Snapshot the whole chain state, `revert` goes back to it later
+pub async fn snapshot(provider: &RootProvider<AnyNetwork>) -> Result<Value, String> {
+    let id: U256 = provider
+        .raw_request("evm_snapshot".into(), ())
+        .await
+        .map_err(|e| format!("evm_snapshot failed: {}", e))?;
+    let block_number = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+    Ok(json!({
+        "success": true,
+        "snapshot_id": format!("0x{:x}", id),
+        "block_number": block_number
+    }))
+}
+
+// Go back to a snapshot. The node drops it, and every snapshot taken after it, once reverted to.
+pub async fn revert(provider: &RootProvider<AnyNetwork>, snapshot_id: U256) -> Result<Value, String> {
+    let reverted: bool = provider
+        .raw_request("evm_revert".into(), (snapshot_id,))
+        .await
+        .map_err(|e| format!("evm_revert failed: {}", e))?;
+    if !reverted {
+        return Err(format!("Snapshot 0x{:x} is unknown to the node, it was reverted to already or the node restarted", snapshot_id));
+    }
+    let block_number = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+    Ok(json!({
+        "success": true,
+        "snapshot_id": format!("0x{:x}", snapshot_id),
+        "block_number": block_number
+    }))
+}
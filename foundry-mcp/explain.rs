@@ -0,0 +1,278 @@
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolEvent};
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+sol! {
+    #[sol(rpc)]
+    contract Token {
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+
+        function transfer(address to, uint256 amount) external returns (bool);
+        function approve(address spender, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+
+        // ERC721 Transfer has the same signature with the token id indexed as a fourth topic
+        event Transfer(address indexed from, address indexed to, uint256 value);
+        event Approval(address indexed owner, address indexed spender, uint256 value);
+    }
+
+    contract Weth {
+        event Deposit(address indexed dst, uint256 wad);
+        event Withdrawal(address indexed src, uint256 wad);
+    }
+
+    contract UniswapV2Pair {
+        event Swap(address indexed sender, uint256 amount0In, uint256 amount1In, uint256 amount0Out, uint256 amount1Out, address indexed to);
+    }
+
+    contract UniswapV3Pool {
+        event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick);
+    }
+}
+
+// Logs listed one by one, busier transactions only get the summary
+const MAX_LISTED_EVENTS: usize = 50;
+
+fn str_field<'a>(value: &'a Value, name: &str) -> Option<&'a str> {
+    value.get(name)?.as_str()
+}
+
+fn quantity(value: &Value, name: &str) -> U256 {
+    str_field(value, name).and_then(|quantity| U256::from_str(quantity).ok()).unwrap_or_default()
+}
+
+fn address_topic(topic: &B256) -> Address {
+    Address::from_word(*topic)
+}
+
+fn decode_call(input: &[u8]) -> Value {
+    if input.is_empty() {
+        json!({ "function": "ETH transfer" })
+    } else if let Ok(call) = Token::transferCall::abi_decode(input) {
+        json!({ "function": "transfer(address,uint256)", "to": call.to.to_string(), "amount": call.amount.to_string() })
+    } else if let Ok(call) = Token::approveCall::abi_decode(input) {
+        json!({ "function": "approve(address,uint256)", "spender": call.spender.to_string(), "amount": call.amount.to_string() })
+    } else if let Ok(call) = Token::transferFromCall::abi_decode(input) {
+        json!({ "function": "transferFrom(address,address,uint256)", "from": call.from.to_string(), "to": call.to.to_string(), "amount": call.amount.to_string() })
+    } else {
+        json!({ "function": "unknown", "selector": format!("0x{}", hex::encode(&input[..input.len().min(4)])) })
+    }
+}
+
+// One receipt log, decoded when it is a well-known event
+fn decode_log(log: &Value) -> Value {
+    let contract = str_field(log, "address").unwrap_or_default();
+    let topics: Vec<B256> = log
+        .get("topics")
+        .and_then(|topics| topics.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|topic| B256::from_str(topic.as_str()?).ok())
+        .collect();
+    let data = str_field(log, "data").and_then(|data| Bytes::from_str(data).ok()).unwrap_or_default();
+    let word = |index: usize| data.get(index * 32..(index + 1) * 32).map(U256::from_be_slice).unwrap_or_default();
+
+    let topic0 = topics.first().copied().unwrap_or_default();
+    let is = |signature: B256, topic_count: usize| topic0 == signature && topics.len() == topic_count;
+
+    if is(Token::Transfer::SIGNATURE_HASH, 3) {
+        json!({
+            "event": "Transfer",
+            "standard": "erc20",
+            "contract": contract,
+            "from": address_topic(&topics[1]).to_string(),
+            "to": address_topic(&topics[2]).to_string(),
+            "amount": word(0).to_string()
+        })
+    } else if is(Token::Transfer::SIGNATURE_HASH, 4) {
+        json!({
+            "event": "Transfer",
+            "standard": "erc721",
+            "contract": contract,
+            "from": address_topic(&topics[1]).to_string(),
+            "to": address_topic(&topics[2]).to_string(),
+            "token_id": U256::from_be_bytes(topics[3].0).to_string()
+        })
+    } else if is(Token::Approval::SIGNATURE_HASH, 3) {
+        json!({
+            "event": "Approval",
+            "contract": contract,
+            "owner": address_topic(&topics[1]).to_string(),
+            "spender": address_topic(&topics[2]).to_string(),
+            "amount": word(0).to_string()
+        })
+    } else if is(Weth::Deposit::SIGNATURE_HASH, 2) || is(Weth::Withdrawal::SIGNATURE_HASH, 2) {
+        json!({
+            "event": if topic0 == Weth::Deposit::SIGNATURE_HASH { "Deposit" } else { "Withdrawal" },
+            "contract": contract,
+            "account": address_topic(&topics[1]).to_string(),
+            "amount": word(0).to_string()
+        })
+    } else if topic0 == UniswapV2Pair::Swap::SIGNATURE_HASH || topic0 == UniswapV3Pool::Swap::SIGNATURE_HASH {
+        json!({
+            "event": "Swap",
+            "pool": contract
+        })
+    } else {
+        json!({
+            "event": "unknown",
+            "contract": contract,
+            "topic0": topics.first().map(|topic| topic.to_string())
+        })
+    }
+}
+
+// ETH moved by internal calls in a callTracer frame and its children
+fn internal_transfers(frame: &Value, depth: usize, transfers: &mut Vec<Value>) {
+    let value = quantity(frame, "value");
+    if depth > 0 && !value.is_zero() {
+        transfers.push(json!({
+            "from": str_field(frame, "from"),
+            "to": str_field(frame, "to"),
+            "value": value.to_string()
+        }));
+    }
+    for call in frame.get("calls").and_then(|calls| calls.as_array()).into_iter().flatten() {
+        internal_transfers(call, depth + 1, transfers);
+    }
+}
+
+fn action(tx: &Value, call: &Value, events: &[Value]) -> &'static str {
+    let has_event = |name: &str| events.iter().any(|event| event["event"] == name);
+    let has_standard = |standard: &str| events.iter().any(|event| event["standard"] == standard);
+
+    if tx.get("to").is_none_or(|to| to.is_null()) {
+        "contract_deployment"
+    } else if has_event("Swap") {
+        "swap"
+    } else if has_event("Deposit") || has_event("Withdrawal") {
+        "wrap"
+    } else if has_standard("erc721") {
+        "nft_transfer"
+    } else if has_standard("erc20") {
+        "token_transfer"
+    } else if has_event("Approval") || call["function"] == "approve(address,uint256)" {
+        "approval"
+    } else if call["function"] == "ETH transfer" {
+        "eth_transfer"
+    } else {
+        "contract_call"
+    }
+}
+
+// Structured summary of a mined transaction: what kind of action it was, the assets it moved
+// and who was involved, for the model to put into words
+pub async fn explain(provider: &RootProvider<AnyNetwork>, hash: B256) -> Result<Value, String> {
+    let tx: Value = provider
+        .raw_request("eth_getTransactionByHash".into(), (hash,))
+        .await
+        .map_err(|e| format!("Failed to get the transaction: {}", e))?;
+    if tx.is_null() {
+        return Err(format!("Transaction {} not found", hash));
+    }
+    let receipt: Value = provider
+        .raw_request("eth_getTransactionReceipt".into(), (hash,))
+        .await
+        .map_err(|e| format!("Failed to get the receipt: {}", e))?;
+    if receipt.is_null() {
+        return Err(format!("Transaction {} is still pending", hash));
+    }
+    // Traces need a debug-enabled node, without one internal ETH transfers are unknown
+    let trace: Option<Value> = provider
+        .raw_request("debug_traceTransaction".into(), (hash, json!({ "tracer": "callTracer" })))
+        .await
+        .ok();
+
+    let from = str_field(&tx, "from").unwrap_or_default().to_string();
+    let input = str_field(&tx, "input").and_then(|input| Bytes::from_str(input).ok()).unwrap_or_default();
+    let call = decode_call(&input);
+
+    let logs = receipt.get("logs").and_then(|logs| logs.as_array()).cloned().unwrap_or_default();
+    let events: Vec<Value> = logs.iter().map(decode_log).collect();
+
+    let mut assets_moved = vec![];
+    let value = quantity(&tx, "value");
+    if !value.is_zero() {
+        assets_moved.push(json!({
+            "asset": "ETH",
+            "from": from,
+            "to": str_field(&tx, "to"),
+            "amount": value.to_string()
+        }));
+    }
+    let mut internal = vec![];
+    if let Some(trace) = &trace {
+        internal_transfers(trace, 0, &mut internal);
+    }
+    for transfer in &internal {
+        assets_moved.push(json!({
+            "asset": "ETH",
+            "from": transfer["from"],
+            "to": transfer["to"],
+            "amount": transfer["value"],
+            "internal": true
+        }));
+    }
+    for event in events.iter().filter(|event| event["event"] == "Transfer") {
+        let contract = event["contract"].as_str().and_then(|contract| Address::from_str(contract).ok()).unwrap_or_default();
+        let token = Token::new(contract, provider.clone());
+        let mut moved = json!({
+            "asset": event["contract"],
+            "standard": event["standard"],
+            "from": event["from"],
+            "to": event["to"],
+        });
+        if event["standard"] == "erc721" {
+            moved["token_id"] = event["token_id"].clone();
+        } else {
+            moved["amount"] = event["amount"].clone();
+            moved["decimals"] = json!(token.decimals().call().await.ok());
+        }
+        moved["symbol"] = json!(token.symbol().call().await.ok());
+        assets_moved.push(moved);
+    }
+
+    // Everyone the sender dealt with, the zero address only stands for mints and burns
+    let sender = Address::from_str(&from).unwrap_or_default();
+    let mut counterparties = BTreeSet::new();
+    let parties = [str_field(&tx, "to"), str_field(&receipt, "contractAddress")]
+        .into_iter()
+        .chain(assets_moved.iter().flat_map(|moved| [moved["from"].as_str(), moved["to"].as_str()]));
+    for party in parties.flatten() {
+        if let Ok(address) = Address::from_str(party) {
+            counterparties.insert(address);
+        }
+    }
+    counterparties.remove(&sender);
+    counterparties.remove(&Address::ZERO);
+    let counterparties: Vec<String> = counterparties.iter().map(|address| address.to_string()).collect();
+
+    let gas_used = quantity(&receipt, "gasUsed");
+    let gas_price = quantity(&receipt, "effectiveGasPrice");
+    let listed_events: Vec<&Value> = events.iter().take(MAX_LISTED_EVENTS).collect();
+
+    Ok(json!({
+        "success": true,
+        "hash": hash.to_string(),
+        "status": if quantity(&receipt, "status").is_zero() { "reverted" } else { "success" },
+        "block_number": quantity(&receipt, "blockNumber").saturating_to::<u64>(),
+        "from": from,
+        "to": str_field(&tx, "to"),
+        "contract_address": str_field(&receipt, "contractAddress"),
+        "value": value.to_string(),
+        "action": action(&tx, &call, &events),
+        "decoded_call": call,
+        "gas_used": gas_used.saturating_to::<u64>(),
+        "fee": (gas_used * gas_price).to_string(),
+        "assets_moved": assets_moved,
+        "counterparties": counterparties,
+        "events": listed_events,
+        "event_count": events.len(),
+        "traced": trace.is_some()
+    }))
+}
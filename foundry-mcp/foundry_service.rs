@@ -1,33 +1,212 @@
 use alloy::serde::WithOtherFields;
 use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
 use rmcp::{
-    schemars, tool, tool_router, tool_handler,
-    handler::server::{router::tool::ToolRouter, tool::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    ServerHandler,
+    schemars, tool, tool_router,
+    handler::server::{router::tool::ToolRouter, tool::{Parameters, ToolCallContext}},
+    model::{CallToolRequestParam, CallToolResult, ListToolsResult, PaginatedRequestParam, ServerCapabilities, ServerInfo},
+    service::RequestContext,
+    ErrorData, RoleServer, ServerHandler,
 };
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
 use alloy_rpc_types::eth::TransactionRequest;
+use alloy_rpc_types::{BlockId, BlockNumberOrTag};
 use alloy::sol;
+use alloy::sol_types::{decode_revert_reason, SolCall};
 use std::str::FromStr;
 use hex;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
+use std::sync::{Arc, RwLock};
+use tokio::sync::OnceCell;
 use serde_json::json;
 use foundry_cli::{opts::RpcOpts, utils::LoadConfig};
 
+use crate::bridge;
+use crate::cheatcodes;
+use crate::explain;
+use crate::interface;
+use crate::l2_fees::{self, Rollup};
+use crate::mev;
+use crate::multicall;
+use crate::nft;
+use crate::permit::{self, ERC20Permit, Permit};
+use crate::portfolio::{self, Asset};
+use crate::positions;
+use crate::priority_fee::{self, Target};
+use crate::private_tx;
+use crate::risk;
+use crate::safe::{self, Safe};
+use crate::siwe;
+use crate::scan::{self, Progress, Scan};
+use crate::staking;
+use crate::standards;
+use crate::token_list::{self, TokenInfo};
+use crate::twap;
+use crate::unsigned;
+use crate::user_op::{self, EntryPoint, UserOperation};
+use crate::verify;
+
 sol! {
     #[sol(rpc)]
     contract ERC20 {
         function balanceOf(address account) external view returns (uint256);
         function decimals() external view returns (uint8);   // optional
         function symbol() external view returns (string);    // optional
+        function transfer(address to, uint256 amount) external returns (bool);
+        function approve(address spender, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    }
+}
+
+sol! {
+    #[sol(rpc)]
+    contract ENSRegistry {
+        function resolver(bytes32 node) external view returns (address);
+    }
+
+    #[sol(rpc)]
+    contract ENSResolver {
+        function addr(bytes32 node) external view returns (address);
+    }
+}
+
+sol! {
+    #[sol(rpc)]
+    contract AggregatorV3 {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
+    }
+}
+
+// Chainlink USD feeds on mainnet (and mainnet forks) as (symbol, feed address, CoinGecko id)
+const PRICE_FEEDS: &[(&str, &str, &str)] = &[
+    ("ETH", "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419", "ethereum"),
+    ("BTC", "0xF4030086522a5bEEa4988F8cA5B36dbC97BeE88c", "bitcoin"),
+    ("LINK", "0x2c1d072e956AFFC0D435Cb7AC38EF18d24d9127c", "chainlink"),
+    ("USDC", "0x8fFfFfd4AfB6115b954Bd326cbe7B4BA576818f6", "usd-coin"),
+    ("USDT", "0x3E7d1eAB13ad0104d2750B8863b489D65364e32D", "tether"),
+    ("DAI", "0xAed0c38402a5d19df6E4c03F4E2DceD6e29c1ee9", "dai"),
+];
+
+// Format a fixed point integer with `decimals` decimals, e.g. (123456, 2) -> "1234.56"
+fn format_fixed(value: U256, decimals: u8) -> String {
+    let digits = value.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+// How long send_user_operation waits for the bundler to include an operation
+const USER_OP_RECEIPT_TIMEOUT_SECS: u64 = 60;
+
+// How long a permit stays valid when the caller doesn't set a deadline
+const DEFAULT_PERMIT_VALIDITY_SECS: u64 = 3600;
+// How long a Sign-In-With-Ethereum message stays valid when the caller doesn't say
+const DEFAULT_SIWE_VALIDITY_SECS: u64 = 600;
+
+// Averaging window of get_twap, long enough that a single block can't move the price much
+const DEFAULT_TWAP_WINDOW_SECS: u32 = 1800;
+
+// portfolio_history reads every asset at every sample, so the sample count is capped
+const DEFAULT_PORTFOLIO_DAYS: u32 = 30;
+const DEFAULT_PORTFOLIO_SAMPLES: u32 = 8;
+const MAX_PORTFOLIO_SAMPLES: u32 = 50;
+
+// Anvil and other local dev chains, where fees don't matter and no oracle knows the chain
+fn is_local_chain(chain_id: u64) -> bool {
+    matches!(chain_id, 31337 | 1337)
+}
+
+// Fee tiers from slowest to fastest, with the block time multiple used when the oracle has no
+// inclusion estimate of its own
+const FEE_TIERS: &[(&str, u64)] = &[("slow", 5), ("standard", 2), ("fast", 1)];
+const BLOCK_TIME_SECS: u64 = 12;
+
+// Parse a decimal gwei amount, e.g. "12.5", into wei
+fn gwei_to_wei(gwei: &str) -> Option<u128> {
+    let (whole, fraction) = gwei.trim().split_once('.').unwrap_or((gwei.trim(), ""));
+    if fraction.len() > 9 {
+        return gwei_to_wei(&format!("{}.{}", whole, &fraction[..9]));
+    }
+    let whole: u128 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let fraction: u128 = format!("{:0<9}", fraction).parse().ok()?;
+    whole.checked_mul(1_000_000_000)?.checked_add(fraction)
+}
+
+fn fee_tier(max_fee_per_gas: u128, max_priority_fee_per_gas: u128, expected_inclusion_seconds: u64) -> serde_json::Value {
+    json!({
+        "max_fee_per_gas": max_fee_per_gas.to_string(),
+        "max_priority_fee_per_gas": max_priority_fee_per_gas.to_string(),
+        "expected_inclusion_seconds": expected_inclusion_seconds
+    })
+}
+
+// Block tag ("latest", "pending", "earliest", "safe", "finalized"), number (decimal or 0x hex)
+// or block hash, defaulting to the latest block
+fn parse_block(block: Option<&str>) -> Result<BlockId, String> {
+    let Some(block) = block.map(str::trim).filter(|block| !block.is_empty()) else {
+        return Ok(BlockId::latest());
+    };
+    if let Ok(tag) = BlockNumberOrTag::from_str(block) {
+        return Ok(BlockId::Number(tag));
+    }
+    if let Ok(number) = block.parse::<u64>() {
+        return Ok(BlockId::number(number));
+    }
+    B256::from_str(block)
+        .map(BlockId::hash)
+        .map_err(|_| format!("Invalid block: {}, use a tag, a number or a block hash", block))
+}
+
+// Storage slot as a 32 byte key, given as a decimal or 0x hex number
+fn parse_slot(slot: &str) -> Result<B256, String> {
+    U256::from_str(slot.trim())
+        .map(B256::from)
+        .map_err(|e| format!("Invalid storage slot {}: {}", slot, e))
+}
+
+// Same address on mainnet and the public testnets
+const ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+
+// EIP-137 namehash. Names are only lowercased, not fully UTS-46 normalised.
+fn namehash(name: &str) -> B256 {
+    let mut node = B256::ZERO;
+    for label in name.to_lowercase().rsplit('.').filter(|label| !label.is_empty()) {
+        let label_hash = keccak256(label.as_bytes());
+        node = keccak256([node.as_slice(), label_hash.as_slice()].concat());
     }
+    node
 }
 
 
 #[derive(Clone)]
 pub struct FoundryService {
-    foundry_provider: RootProvider<AnyNetwork>,
+    // Network the tools of this session run against, switched with `use_network`
+    foundry_provider: Arc<RwLock<RootProvider<AnyNetwork>>>,
+    // The network from the foundry config, which every session starts on
+    default_provider: RootProvider<AnyNetwork>,
+    // Loaded on the first lookup, remote lists shouldn't slow down the server start
+    tokens: Arc<OnceCell<Vec<TokenInfo>>>,
+    // Addresses of the SCAM_LISTS, loaded on the first risk_score
+    scam_lists: Arc<OnceCell<HashMap<Address, String>>>,
+    // Sent through a private RPC, the node only learns of them once they are mined
+    private_transactions: Arc<RwLock<HashSet<B256>>>,
     tool_router: ToolRouter<Self>,
 }
 
@@ -37,6 +216,12 @@ pub struct BalanceRequest {
     pub address: String,
 }
 
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct AccountsRequest {
+    #[schemars(description = "Addresses to list next to the node's own accounts, e.g. the user's labeled wallets")]
+    pub addresses: Option<Vec<String>>,
+}
+
 #[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 pub struct Erc20BalanceRequest {
     #[schemars(description = "The address of the account to check the balance of")]
@@ -54,7 +239,7 @@ pub struct ValidateAddressRequest {
 pub struct SendTransactionRequest {
     #[schemars(description = "Sender address")]
     pub from: String,
-    #[schemars(description = "Recipient address")]
+    #[schemars(description = "Recipient address, empty to deploy a contract with `data` as its init code")]
     pub to: String,
     #[schemars(description = "Amount in wei")]
     pub value: String,
@@ -64,6 +249,371 @@ pub struct SendTransactionRequest {
     pub gas_limit: Option<u64>,
     #[schemars(description = "Gas price (in wei)")]
     pub gas_price: Option<u128>,
+    #[schemars(description = "\"slow\", \"standard\" or \"fast\" fees from the gas oracle on public networks, defaults to standard. Ignored when gas_price is set")]
+    pub fee_tier: Option<String>,
+    #[schemars(description = "EIP-1559 max fee per gas (in wei), used with max_priority_fee_per_gas instead of fee_tier")]
+    pub max_fee_per_gas: Option<u128>,
+    #[schemars(description = "EIP-1559 max priority fee per gas (in wei)")]
+    pub max_priority_fee_per_gas: Option<u128>,
+    #[schemars(description = "Nonce to send with, the sender's next nonce by default. Reusing a pending transaction's nonce replaces it")]
+    pub nonce: Option<u64>,
+    #[schemars(description = "Private RPC (e.g. Flashbots Protect, MEV Blocker) to submit the node-signed transaction to instead of the public mempool")]
+    pub private_rpc: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ResolveEnsRequest {
+    #[schemars(description = "The ENS name to resolve, e.g. vitalik.eth")]
+    pub name: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct GetPriceRequest {
+    #[schemars(description = "Asset symbol, e.g. ETH, BTC, USDC")]
+    pub asset: String,
+    #[schemars(description = "\"chainlink\" or \"coingecko\", defaults to Chainlink with CoinGecko as fallback")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct GetTwapRequest {
+    #[schemars(description = "Address of the token to price, resolve symbols with resolve_token first")]
+    pub token_a: String,
+    #[schemars(description = "Address of the token the price is quoted in, e.g. USDC or WETH")]
+    pub token_b: String,
+    #[schemars(description = "Pool fee tier in hundredths of a bip (100, 500, 3000, 10000), defaults to the most liquid pool")]
+    pub fee: Option<u32>,
+    #[schemars(description = "Averaging window in seconds, defaults to 1800")]
+    pub window_secs: Option<u32>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct PortfolioHistoryRequest {
+    #[schemars(description = "The account whose holdings are tracked")]
+    pub address: String,
+    #[schemars(description = "ERC20 token addresses to include besides ETH, defaults to WETH, USDC, USDT, DAI and WBTC on mainnet")]
+    pub tokens: Option<Vec<String>>,
+    #[schemars(description = "How many days back to start, defaults to 30")]
+    pub days: Option<u32>,
+    #[schemars(description = "Number of blocks sampled over the period, the latest included, defaults to 8 (at most 50)")]
+    pub samples: Option<u32>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct DefiPositionsRequest {
+    #[schemars(description = "The account whose positions are read")]
+    pub address: String,
+    #[schemars(description = "Protocols to read: aave_v3, compound_v3, uniswap_v3. Defaults to all of them")]
+    pub protocols: Option<Vec<String>>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct StakingTransactionRequest {
+    #[schemars(description = "stake (ETH for stETH or rETH), unstake (request a Lido withdrawal or burn rETH) or claim (a finalized Lido withdrawal)")]
+    pub action: String,
+    #[schemars(description = "lido or rocket_pool")]
+    pub protocol: String,
+    #[schemars(description = "The account staking, who sends the transaction")]
+    pub from: String,
+    #[schemars(description = "Amount in wei: ETH to stake, or stETH / rETH to unstake. Not needed to claim")]
+    pub amount: Option<String>,
+    #[schemars(description = "The Lido withdrawal request to claim, from staking_withdrawals")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct BatchCallEntry {
+    #[schemars(description = "Address called")]
+    pub to: String,
+    #[schemars(description = "Wei sent with the call, defaults to 0")]
+    pub value: Option<String>,
+    #[schemars(description = "Hex calldata, empty for a plain ETH transfer")]
+    pub data: Option<String>,
+    #[schemars(description = "Let the batch go on when this call reverts, defaults to false")]
+    pub allow_failure: Option<bool>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct BatchCallsRequest {
+    #[schemars(description = "The account sending the batch transaction")]
+    pub from: String,
+    #[schemars(description = "Independent calls to make in order, at least two")]
+    pub calls: Vec<BatchCallEntry>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct StakingWithdrawalsRequest {
+    #[schemars(description = "The account that requested the withdrawals")]
+    pub owner: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct BridgeQuoteRequest {
+    #[schemars(description = "Chain id of the destination chain, e.g. 8453 for Base")]
+    pub to_chain_id: u64,
+    #[schemars(description = "Chain id the funds leave from, defaults to the connected chain (required on local chains)")]
+    pub from_chain_id: Option<u64>,
+    #[schemars(description = "Address of the token sent on the source chain, defaults to the native token (ETH)")]
+    pub from_token: Option<String>,
+    #[schemars(description = "Address of the token received on the destination chain, defaults to the native token (ETH)")]
+    pub to_token: Option<String>,
+    #[schemars(description = "Amount to bridge in the source token's smallest unit (wei for ETH)")]
+    pub amount: String,
+    #[schemars(description = "Account sending the funds")]
+    pub from: String,
+    #[schemars(description = "Account receiving the funds on the destination chain, defaults to the sender")]
+    pub to: Option<String>,
+    #[schemars(description = "Also list up to 5 routes to compare (bridges, amount received, fees, duration), defaults to true")]
+    pub compare: Option<bool>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct GasOracleRequest {
+    #[schemars(description = "\"blocknative\", \"etherscan\" or \"node\", defaults to the first one with an API key set and the node's fee history otherwise")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SuggestPriorityFeeRequest {
+    #[schemars(description = "Target inclusion time: next_block, 1m or 5m, defaults to 1m")]
+    pub target: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ResolveTokenRequest {
+    #[schemars(description = "Token symbol, e.g. USDC")]
+    pub symbol: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SafeInfoRequest {
+    #[schemars(description = "The Safe address")]
+    pub safe: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SignPermitRequest {
+    #[schemars(description = "Address of the EIP-2612 token")]
+    pub token: String,
+    #[schemars(description = "Token holder signing the permit")]
+    pub owner: String,
+    #[schemars(description = "Address allowed to spend the tokens, e.g. a router")]
+    pub spender: String,
+    #[schemars(description = "Allowance in the token's smallest unit")]
+    pub value: String,
+    #[schemars(description = "Unix timestamp after which the permit is invalid, defaults to one hour from now")]
+    pub deadline: Option<u64>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ProposeSafeTransactionRequest {
+    #[schemars(description = "The Safe address")]
+    pub safe: String,
+    #[schemars(description = "Recipient or contract the Safe calls")]
+    pub to: String,
+    #[schemars(description = "Amount in wei")]
+    pub value: String,
+    #[schemars(description = "Calldata (hex encoded)")]
+    pub data: Option<String>,
+    #[schemars(description = "Owner or delegate proposing the transaction, it signs the proposal")]
+    pub proposer: String,
+    #[schemars(description = "Safe nonce, defaults to the current on-chain nonce")]
+    pub nonce: Option<u64>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SmartAccountNonceRequest {
+    #[schemars(description = "The ERC-4337 smart account")]
+    pub account: String,
+    #[schemars(description = "Nonce key for parallel nonce lanes, 0 by default")]
+    pub key: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct BuildUserOperationRequest {
+    #[schemars(description = "The ERC-4337 smart account making the call")]
+    pub account: String,
+    #[schemars(description = "Recipient or contract the account calls")]
+    pub to: String,
+    #[schemars(description = "Amount in wei")]
+    pub value: String,
+    #[schemars(description = "Calldata of the call (hex encoded)")]
+    pub data: Option<String>,
+    #[schemars(description = "Account nonce, the EntryPoint's current one by default")]
+    pub nonce: Option<String>,
+    #[schemars(description = "Have the paymaster at PAYMASTER_URL pay the gas instead of the account")]
+    pub sponsored: Option<bool>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SignUserOperationRequest {
+    #[schemars(description = "The UserOperation returned by build_user_operation")]
+    pub user_operation: serde_json::Value,
+    #[schemars(description = "Owner of the smart account, it signs the UserOperation hash")]
+    pub owner: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SendUserOperationRequest {
+    #[schemars(description = "The signed UserOperation returned by sign_user_operation")]
+    pub user_operation: serde_json::Value,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct GetProofRequest {
+    #[schemars(description = "The account to prove")]
+    pub address: String,
+    #[schemars(description = "Storage slots to prove, as decimal or 0x hex numbers")]
+    #[serde(default)]
+    pub slots: Vec<String>,
+    #[schemars(description = "Block number, hash or tag (latest, finalized, ...), defaults to latest")]
+    pub block: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct UseNetworkRequest {
+    #[schemars(description = "RPC URL of the network to switch to, the server's default network if omitted")]
+    pub rpc_url: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ExplainTransactionRequest {
+    #[schemars(description = "Hash of a mined transaction")]
+    pub hash: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct DetectStandardsRequest {
+    #[schemars(description = "The address to classify")]
+    pub address: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct VerifyBytecodeRequest {
+    #[schemars(description = "The contract to check")]
+    pub address: String,
+    #[schemars(description = "Where to get the verified source: sourcify or etherscan. Defaults to Sourcify, then Etherscan when ETHERSCAN_API_KEY is set")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct NftMetadataRequest {
+    #[schemars(description = "The ERC-721 or ERC-1155 collection contract")]
+    pub contract: String,
+    #[schemars(description = "The token id, decimal or 0x-prefixed hex")]
+    pub token_id: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SignInWithEthereumRequest {
+    #[schemars(description = "Account signing in")]
+    pub address: String,
+    #[schemars(description = "Host asking for the sign-in, e.g. app.example.com, without a scheme")]
+    pub domain: String,
+    #[schemars(description = "Absolute URI of the resource signed in to, e.g. https://app.example.com/login")]
+    pub uri: String,
+    #[schemars(description = "One line the user agrees to, e.g. the site's terms (optional)")]
+    pub statement: Option<String>,
+    #[schemars(description = "Nonce the site issued, at least 8 letters and digits; a random one is generated when not set")]
+    pub nonce: Option<String>,
+    #[schemars(description = "Seconds the sign-in stays valid, defaults to 600")]
+    pub expiration_secs: Option<u64>,
+    #[schemars(description = "Chain id stated in the message, defaults to the connected chain's")]
+    pub chain_id: Option<u64>,
+    #[schemars(description = "Request id the site asked to be included (optional)")]
+    pub request_id: Option<String>,
+    #[schemars(description = "URIs the sign-in grants access to (optional)")]
+    pub resources: Option<Vec<String>>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct RiskScoreRequest {
+    #[schemars(description = "The contract or account to assess")]
+    pub address: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct InferInterfaceRequest {
+    #[schemars(description = "The contract without verified source")]
+    pub address: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct EncodeCallRequest {
+    #[schemars(description = "Full function signature, e.g. transfer(address,uint256), or a function name from `abi`")]
+    pub function: String,
+    #[schemars(description = "Arguments in order, as strings (addresses, decimal numbers, true/false, 0x hex bytes, [a,b] arrays)")]
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[schemars(description = "JSON ABI to look `function` up in, e.g. the abi from infer_interface")]
+    pub abi: Option<String>,
+    #[schemars(description = "Contract to call read-only with the encoded data, returning the decoded result")]
+    pub to: Option<String>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ScanEventsRequest {
+    #[schemars(description = "Event signature with indexed markers and names, e.g. Transfer(address indexed from, address indexed to, uint256 indexed tokenId)")]
+    pub event: String,
+    #[schemars(description = "Contract emitting the events, any contract if omitted")]
+    pub address: Option<String>,
+    #[schemars(description = "First block of the range")]
+    pub from_block: u64,
+    #[schemars(description = "Last block of the range, defaults to the latest block")]
+    pub to_block: Option<u64>,
+    #[schemars(description = "Values of indexed parameters to match, by parameter name, e.g. {\"from\": \"0x0000000000000000000000000000000000000000\"} for mints")]
+    #[serde(default)]
+    pub filters: std::collections::BTreeMap<String, String>,
+    #[schemars(description = "Maximum number of events to return, defaults to 1000. Continue from next_from_block when truncated")]
+    pub max_events: Option<usize>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SetCodeRequest {
+    #[schemars(description = "The address whose code to replace")]
+    pub address: String,
+    #[schemars(description = "New runtime bytecode (hex encoded), empty to remove the code")]
+    pub code: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SetStorageAtRequest {
+    #[schemars(description = "The contract whose storage to write")]
+    pub address: String,
+    #[schemars(description = "Storage slot as a decimal or 0x hex number, or the slot the mapping is declared at when mapping_key is set")]
+    pub slot: String,
+    #[schemars(description = "Key (an address or a number) to write mapping[key] of the mapping at `slot`, e.g. the holder for an ERC-20 balance")]
+    pub mapping_key: Option<String>,
+    #[schemars(description = "New 32 byte value as a decimal or 0x hex number")]
+    pub value: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct SetNonceRequest {
+    #[schemars(description = "The account whose nonce to set")]
+    pub address: String,
+    #[schemars(description = "Nonce of the account's next transaction")]
+    pub nonce: u64,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct ResetForkRequest {
+    #[schemars(description = "RPC URL to fork from instead, omit both fields to reset to genesis or the original fork block")]
+    pub fork_url: Option<String>,
+    #[schemars(description = "Block to fork at, defaults to the latest block of the fork")]
+    pub block_number: Option<u64>,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct EvmRevertRequest {
+    #[schemars(description = "Id returned by evm_snapshot")]
+    pub snapshot_id: String,
+}
+
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+pub struct TransactionStatusRequest {
+    #[schemars(description = "Hash of a sent transaction")]
+    pub hash: String,
 }
 
 #[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
@@ -80,11 +630,64 @@ impl FoundryService {
         let provider = foundry_cli::utils::get_provider(&config).unwrap();
         
         Ok(Self {
-            foundry_provider: provider,
+            foundry_provider: Arc::new(RwLock::new(provider.clone())),
+            default_provider: provider,
+            tokens: Arc::new(OnceCell::new()),
+            scam_lists: Arc::new(OnceCell::new()),
+            private_transactions: Arc::new(RwLock::new(HashSet::new())),
             tool_router: Self::tool_router(),
         })
     }
 
+    // A copy of the service for a new session, on the default network whatever other sessions switched to
+    pub fn session(&self) -> Self {
+        Self {
+            foundry_provider: Arc::new(RwLock::new(self.default_provider.clone())),
+            ..self.clone()
+        }
+    }
+
+    fn provider(&self) -> RootProvider<AnyNetwork> {
+        self.foundry_provider.read().unwrap().clone()
+    }
+
+    #[tool(description = "Switch the network every following tool call of this session runs against, by RPC URL, or back to the server's default network")]
+    pub async fn use_network(
+        &self,
+        Parameters(request): Parameters<UseNetworkRequest>,
+    ) -> String {
+        let provider = match request.rpc_url.as_deref() {
+            None => Ok(self.default_provider.clone()),
+            Some(rpc_url) => rpc_url
+                .parse()
+                .map(RootProvider::<AnyNetwork>::new_http)
+                .map_err(|e| format!("Invalid RPC URL {}: {}", rpc_url, e)),
+        };
+
+        // Only switch to networks that answer, a typo shouldn't break every following call
+        let result = match provider {
+            Ok(provider) => match provider.get_chain_id().await {
+                Ok(chain_id) => {
+                    *self.foundry_provider.write().unwrap() = provider;
+                    json!({
+                        "success": true,
+                        "rpc_url": request.rpc_url,
+                        "chain_id": chain_id
+                    })
+                }
+                Err(e) => json!({
+                    "success": false,
+                    "error": format!("Network is not reachable: {}", e)
+                }),
+            },
+            Err(error) => json!({
+                "success": false,
+                "error": error
+            }),
+        };
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
     #[tool(description = "Get the balance of an account in wei")]
     pub async fn balance(
         &self,
@@ -95,7 +698,7 @@ impl FoundryService {
         match Address::from_str(&request.address) {
             Ok(address) => {
                 // FIXME error handling
-                let balance = self.foundry_provider.get_balance(address).await.unwrap();
+                let balance = self.provider().get_balance(address).await.unwrap();
 
                 let result = json!({
                     "success": true,
@@ -117,6 +720,62 @@ impl FoundryService {
         }
     }
 
+    #[tool(description = "List the accounts the node can send from (eth_accounts, e.g. anvil's funded dev accounts) and any given addresses, with each one's ETH balance in wei and its current nonce")]
+    pub async fn accounts(
+        &self,
+        Parameters(request): Parameters<AccountsRequest>,
+    ) -> String {
+        let result = self.read_accounts(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn read_accounts(&self, request: &AccountsRequest) -> Result<serde_json::Value, String> {
+        let provider = self.provider();
+        // Public RPCs manage no accounts and may refuse eth_accounts altogether
+        let mut accounts: Vec<(Address, &str)> = provider
+            .get_accounts()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|address| (address, "node"))
+            .collect();
+        for address in request.addresses.iter().flatten() {
+            let address = Address::from_str(address).map_err(|e| format!("Invalid address {}: {}", address, e))?;
+            if !accounts.iter().any(|(known, _)| *known == address) {
+                accounts.push((address, "requested"));
+            }
+        }
+
+        let mut listed = vec![];
+        for (address, source) in accounts {
+            let balance = provider
+                .get_balance(address)
+                .await
+                .map_err(|e| format!("Failed to get the balance of {}: {}", address, e))?;
+            let nonce = provider
+                .get_transaction_count(address)
+                .await
+                .map_err(|e| format!("Failed to get the nonce of {}: {}", address, e))?;
+            listed.push(json!({
+                "address": address.to_string(),
+                "source": source,
+                "balance": balance.to_string(),
+                "nonce": nonce
+            }));
+        }
+        let chain_id = provider.get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        Ok(json!({
+            "success": true,
+            "chain_id": chain_id,
+            "accounts": listed
+        }))
+    }
+
     #[tool(description = "Validate an Ethereum address and return checksum format")]
     pub async fn validate_address(
         &self,
@@ -145,11 +804,69 @@ impl FoundryService {
         }
     }
 
-    #[tool(description = "Send a transaction to an address")]
-    pub async fn send_transaction(
+    #[tool(description = "Resolve an ENS name (e.g. vitalik.eth) to the address it points to")]
+    pub async fn resolve_ens(
         &self,
-        Parameters(request): Parameters<SendTransactionRequest>,
+        Parameters(request): Parameters<ResolveEnsRequest>,
     ) -> String {
+        let node = namehash(&request.name);
+        let registry = ENSRegistry::new(Address::from_str(ENS_REGISTRY).unwrap(), self.provider());
+
+        let resolver = match registry.resolver(node).call().await {
+            Ok(resolver) if !resolver.is_zero() => resolver,
+            Ok(_) => {
+                let result = json!({
+                    "success": false,
+                    "error": "No resolver set for name",
+                    "name": request.name
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Failed to query ENS registry: {}", e),
+                    "name": request.name
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+
+        let resolver_contract = ENSResolver::new(resolver, self.provider());
+        match resolver_contract.addr(node).call().await {
+            Ok(address) if !address.is_zero() => {
+                let result = json!({
+                    "success": true,
+                    "name": request.name,
+                    "address": address.to_string(),
+                    "resolver": resolver.to_string(),
+                    "message": format!("{} resolves to {}", request.name, address)
+                });
+                serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+            }
+            Ok(_) => {
+                let result = json!({
+                    "success": false,
+                    "error": "Name does not resolve to an address",
+                    "name": request.name
+                });
+                serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+            }
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Failed to query ENS resolver: {}", e),
+                    "name": request.name
+                });
+                serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+            }
+        }
+    }
+
+    // Validate the request fields and build the transaction with the sender's current nonce.
+    // On public networks without an explicit gas price the fees come from the gas oracle, and the
+    // chosen tier is returned alongside. On failure returns the JSON error response for the tool.
+    async fn build_transaction(&self, request: &SendTransactionRequest) -> Result<(TransactionRequest, u64, Option<serde_json::Value>), String> {
         // Validate sender address
         let from_address = match Address::from_str(&request.from) {
             Ok(addr) => addr,
@@ -159,23 +876,23 @@ impl FoundryService {
                     "error": format!("Invalid sender address: {}", e),
                     "from": request.from
                 });
-                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+                return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
             }
         };
 
-        // Validate recipient address
+        // Validate recipient address, an empty recipient deploys a contract
         let to_address = match Address::from_str(&request.to) {
-            Ok(addr) => addr,
+            Ok(addr) => Some(addr),
+            Err(_) if request.to.is_empty() => None,
             Err(e) => {
                 let result = json!({
                     "success": false,
                     "error": format!("Invalid address: {}", e),
                     "to": request.to
                 });
-                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+                return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
             }
         };
-        
 
         let amount = match U256::from_str(&request.value) {
             Ok(amount) => amount,
@@ -185,7 +902,7 @@ impl FoundryService {
                     "error": format!("Invalid amount: {}", e),
                     "value": request.value
                 });
-                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+                return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
             }
         };
 
@@ -199,75 +916,2079 @@ impl FoundryService {
                         "error": format!("Invalid data format: {}", e),
                         "data": data_str
                     });
-                    return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+                    return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
                 }
             }
         } else {
             vec![]
         };
 
-        // Get the current nonce for the sender address
-        let nonce = match self.foundry_provider.get_transaction_count(from_address).await {
-            Ok(nonce) => nonce,
-            Err(e) => {
+        if to_address.is_none() && data.is_empty() {
+            let result = json!({
+                "success": false,
+                "error": "Contract deployment needs the init code in `data`",
+                "to": request.to
+            });
+            return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
+        }
+
+        // Get the current nonce for the sender address, unless the caller assigns it
+        let nonce = match request.nonce {
+            Some(nonce) => nonce,
+            None => match self.provider().get_transaction_count(from_address).await {
+                Ok(nonce) => nonce,
+                Err(e) => {
+                    let result = json!({
+                        "success": false,
+                        "error": format!("Failed to get nonce: {}", e),
+                        "from": request.from
+                    });
+                    return Err(serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string()));
+                }
+            },
+        };
+
+        // Create transaction request
+        let mut tx_request = TransactionRequest::default()
+            .value(amount)
+            .nonce(nonce)
+            .from(from_address);
+
+        tx_request = match to_address {
+            Some(to_address) => tx_request.to(to_address),
+            None => tx_request.into_create(),
+        };
+
+        if !data.is_empty() {
+            tx_request = tx_request.input(data.into());
+        }
+
+        if let Some(gas_limit) = request.gas_limit {
+            tx_request = tx_request.gas_limit(gas_limit);
+        }
+
+        let mut fee = None;
+        if let Some(gas_price) = request.gas_price {
+            tx_request = tx_request.gas_price(gas_price);
+        } else if let (Some(max_fee), Some(max_priority_fee)) = (request.max_fee_per_gas, request.max_priority_fee_per_gas) {
+            tx_request = tx_request.max_fee_per_gas(max_fee).max_priority_fee_per_gas(max_priority_fee);
+        } else if let Some(recommended) = self.recommended_fee(request.fee_tier.as_deref()).await {
+            let wei = |name: &str| recommended.get(name).and_then(|value| value.as_str()).and_then(|value| value.parse::<u128>().ok());
+            if let (Some(max_fee), Some(max_priority_fee)) = (wei("max_fee_per_gas"), wei("max_priority_fee_per_gas")) {
+                tx_request = tx_request.max_fee_per_gas(max_fee).max_priority_fee_per_gas(max_priority_fee);
+                fee = Some(recommended);
+            }
+        }
+
+        Ok((tx_request, nonce, fee))
+    }
+
+    // The oracle's fees for `tier` on public networks, None on local chains or when no oracle answers
+    async fn recommended_fee(&self, tier: Option<&str>) -> Option<serde_json::Value> {
+        let chain_id = self.provider().get_chain_id().await.ok()?;
+        if is_local_chain(chain_id) {
+            return None;
+        }
+
+        let tier = tier.unwrap_or("standard");
+        // Fast and slow transactions are priced from what recent blocks and the mempool pay right now
+        let target = match tier {
+            "fast" => Some(Target::NextBlock),
+            "slow" => Some(Target::FiveMinutes),
+            _ => None,
+        };
+        if let Some(target) = target {
+            match priority_fee::suggest(&self.provider(), target).await {
+                Ok(mut fee) => {
+                    fee["tier"] = json!(tier);
+                    fee["source"] = json!("mempool");
+                    return Some(fee);
+                }
+                Err(e) => tracing::warn!("Mempool fee suggestion unavailable, using the gas oracle: {}", e),
+            }
+        }
+
+        let recommendations = match self.gas_recommendations(None, chain_id).await {
+            Ok(recommendations) => recommendations,
+            Err(e) => {
+                tracing::warn!("Gas oracle unavailable, using the node's gas price: {}", e);
+                return None;
+            }
+        };
+
+        let mut fee = recommendations.pointer(&format!("/tiers/{}", tier))?.clone();
+        fee["tier"] = json!(tier);
+        fee["source"] = recommendations["source"].clone();
+        Some(fee)
+    }
+
+    #[tool(description = "Send a transaction to an address")]
+    pub async fn send_transaction(
+        &self,
+        Parameters(request): Parameters<SendTransactionRequest>,
+    ) -> String {
+        let (tx_request, nonce, _) = match self.build_transaction(&request).await {
+            Ok(tx) => tx,
+            Err(response) => return response,
+        };
+
+        // Log the transaction details for debugging
+        tracing::debug!("Sending transaction: from={}, to={}, value={}, nonce={}", 
+                       request.from, request.to, request.value, nonce);
+
+        // Fees the transaction went out with, a replacement has to outbid them. Unset when the node fills them in.
+        let fees = json!({
+            "gas_price": tx_request.gas_price.map(|fee| fee.to_string()),
+            "max_fee_per_gas": tx_request.max_fee_per_gas.map(|fee| fee.to_string()),
+            "max_priority_fee_per_gas": tx_request.max_priority_fee_per_gas.map(|fee| fee.to_string())
+        });
+
+        // Send the transaction
+        let tx_request = WithOtherFields::new(tx_request);
+        let sent = match &request.private_rpc {
+            Some(rpc_url) => self.send_privately(tx_request, rpc_url).await,
+            None => self
+                .provider()
+                .send_transaction(tx_request)
+                .await
+                .map(|tx_response| *tx_response.tx_hash())
+                .map_err(|e| format!("Failed to send transaction: {}", e)),
+        };
+        let tx_hash = match sent {
+            Ok(tx_hash) => tx_hash,
+            Err(error) => {
                 let result = json!({
                     "success": false,
-                    "error": format!("Failed to get nonce: {}", e),
-                    "from": request.from
+                    "error": error,
+                    "from": request.from,
+                    "to": request.to
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+        
+        tracing::debug!("Transaction sent with hash: {}", tx_hash);
+        
+        let mut result = json!({
+            "success": true,
+            "transaction_hash": tx_hash,
+            "from": request.from,
+            "to": request.to,
+            "value": request.value,
+            "nonce": nonce,
+            "fees": fees,
+            "private": request.private_rpc.is_some(),
+            "message": "Transaction sent successfully"
+        });
+
+        // Deployments land at an address derived from the sender and its nonce
+        if request.to.is_empty() {
+            if let Ok(from_address) = Address::from_str(&request.from) {
+                result["contract_address"] = json!(from_address.create(nonce).to_string());
+            }
+        }
+        
+        return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+    }
+
+    // The node signs as with eth_sendTransaction, but the private RPC broadcasts it
+    async fn send_privately(&self, tx_request: WithOtherFields<TransactionRequest>, rpc_url: &str) -> Result<B256, String> {
+        let signed: serde_json::Value = self
+            .provider()
+            .raw_request("eth_signTransaction".into(), (tx_request,))
+            .await
+            .map_err(|e| format!("Failed to sign the transaction for private submission: {}", e))?;
+        let raw = private_tx::raw_transaction(&signed)?;
+        let tx_hash = private_tx::submit(rpc_url, &raw).await?;
+        self.private_transactions.write().unwrap().insert(tx_hash);
+        Ok(tx_hash)
+    }
+
+    #[tool(description = "Simulate a transaction with eth_call without sending it, returning whether it would succeed and the revert reason if not")]
+    pub async fn simulate_transaction(
+        &self,
+        Parameters(request): Parameters<SendTransactionRequest>,
+    ) -> String {
+        let (tx_request, _, _) = match self.build_transaction(&request).await {
+            Ok(tx) => tx,
+            Err(response) => return response,
+        };
+
+        let tx_request = WithOtherFields::new(tx_request);
+        match self.provider().call(tx_request).await {
+            Ok(output) => {
+                let result = json!({
+                    "success": true,
+                    "would_revert": false,
+                    "from": request.from,
+                    "to": request.to,
+                    "value": request.value,
+                    "output": output,
+                    "message": "Simulation succeeded"
+                });
+                serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+            }
+            Err(e) => {
+                // Prefer the decoded Error(string)/Panic(uint256) reason over the raw RPC message
+                let revert_reason = e
+                    .as_error_resp()
+                    .and_then(|resp| resp.as_revert_data())
+                    .and_then(|data| decode_revert_reason(&data))
+                    .unwrap_or_else(|| e.to_string());
+
+                let result = json!({
+                    "success": false,
+                    "would_revert": true,
+                    "error": format!("Transaction would revert: {}", revert_reason),
+                    "revert_reason": revert_reason,
+                    "from": request.from,
+                    "to": request.to,
+                    "value": request.value
+                });
+                serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+            }
+        }
+    }
+
+    #[tool(description = "Preview a transaction without sending it: decoded calldata, estimated gas cost, simulated balance changes and, on public networks, its MEV exposure (swap slippage tolerance, price impact and value)")]
+    pub async fn preview_transaction(
+        &self,
+        Parameters(request): Parameters<SendTransactionRequest>,
+    ) -> String {
+        let (tx_request, nonce, fee) = match self.build_transaction(&request).await {
+            Ok(tx) => tx,
+            Err(response) => return response,
+        };
+
+        let from_address = tx_request.from.unwrap_or_default();
+        let to_address = tx_request.to.and_then(|to| to.to().copied());
+        let value = tx_request.value.unwrap_or_default();
+        let data = tx_request.input.input().cloned().unwrap_or_default();
+        let unsigned_tx = tx_request.clone();
+        let tx_request = WithOtherFields::new(tx_request);
+
+        let gas = match self.provider().estimate_gas(tx_request).await {
+            Ok(gas) => gas,
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Failed to estimate gas: {}", e),
+                    "from": request.from,
+                    "to": request.to
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+        // With oracle fees the cost is an upper bound, the max fee is only paid if the base fee rises
+        let oracle_max_fee = fee
+            .as_ref()
+            .and_then(|fee| fee.get("max_fee_per_gas")?.as_str()?.parse::<u128>().ok());
+        let gas_price = match (request.gas_price, oracle_max_fee) {
+            (Some(gas_price), _) => gas_price,
+            (None, Some(max_fee)) => max_fee,
+            (None, None) => self.provider().get_gas_price().await.unwrap_or_default(),
+        };
+        let mut gas_cost = U256::from(gas) * U256::from(gas_price);
+
+        // On rollups the L1 data fee comes on top of the execution gas, and is often most of the cost
+        let l2_fees = match self.rollup().await {
+            Some(rollup) => l2_fees::estimate(&self.provider(), rollup, &unsigned_tx, gas, gas_price).await.ok(),
+            None => None,
+        };
+        if let Some(total_fee) = l2_fees.as_ref().and_then(|fees| fees.get("total_fee")?.as_str()?.parse::<U256>().ok()) {
+            gas_cost = total_fee;
+        }
+
+        let sender_balance = self.provider().get_balance(from_address).await.unwrap_or_default();
+
+        let mut balance_changes = vec![
+            json!({
+                "address": from_address.to_string(),
+                "asset": "ETH",
+                "before": sender_balance.to_string(),
+                "change": format!("-{}", value + gas_cost),
+            }),
+        ];
+
+        // Contract deployments have no recipient, the new contract gets the value
+        let Some(to_address) = to_address else {
+            let result = json!({
+                "success": true,
+                "from": request.from,
+                "to": request.to,
+                "value": request.value,
+                "nonce": nonce,
+                "decoded_call": { "function": "contract creation", "contract_address": from_address.create(nonce).to_string() },
+                "estimated_gas": gas,
+                "gas_price": gas_price.to_string(),
+                "estimated_gas_cost": gas_cost.to_string(),
+                "fee": fee,
+                "l2_fees": l2_fees,
+                "balance_changes": balance_changes
+            });
+            return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+        };
+
+        let recipient_balance = self.provider().get_balance(to_address).await.unwrap_or_default();
+        balance_changes.push(json!({
+            "address": to_address.to_string(),
+            "asset": "ETH",
+            "before": recipient_balance.to_string(),
+            "change": format!("+{}", value),
+        }));
+
+        // Decode well-known ERC20 calls so token movements show up too
+        let decoded_call = if let Ok(call) = ERC20::transferCall::abi_decode(&data) {
+            let erc20 = ERC20::new(to_address, self.provider());
+            let sender_tokens = erc20.balanceOf(from_address).call().await.unwrap_or_default();
+            let recipient_tokens = erc20.balanceOf(call.to).call().await.unwrap_or_default();
+            balance_changes.push(json!({
+                "address": from_address.to_string(),
+                "asset": to_address.to_string(),
+                "before": sender_tokens.to_string(),
+                "change": format!("-{}", call.amount),
+            }));
+            balance_changes.push(json!({
+                "address": call.to.to_string(),
+                "asset": to_address.to_string(),
+                "before": recipient_tokens.to_string(),
+                "change": format!("+{}", call.amount),
+            }));
+            json!({ "function": "transfer(address,uint256)", "to": call.to.to_string(), "amount": call.amount.to_string() })
+        } else if let Ok(call) = ERC20::approveCall::abi_decode(&data) {
+            json!({ "function": "approve(address,uint256)", "spender": call.spender.to_string(), "amount": call.amount.to_string() })
+        } else if let Ok(call) = ERC20::transferFromCall::abi_decode(&data) {
+            json!({ "function": "transferFrom(address,address,uint256)", "from": call.from.to_string(), "to": call.to.to_string(), "amount": call.amount.to_string() })
+        } else if data.is_empty() {
+            json!({ "function": "ETH transfer" })
+        } else {
+            json!({ "function": "unknown", "selector": format!("0x{}", hex::encode(&data[..data.len().min(4)])) })
+        };
+
+        // Only the public mempool is watched by sandwich bots, local chains have nothing to fear
+        let mev_exposure = match self.public_chain_id().await {
+            Some(chain_id) => mev::exposure(&self.provider(), chain_id, to_address, value, &data).await.ok(),
+            None => None,
+        };
+
+        let result = json!({
+            "success": true,
+            "from": request.from,
+            "to": request.to,
+            "value": request.value,
+            "nonce": nonce,
+            "decoded_call": decoded_call,
+            "estimated_gas": gas,
+            "gas_price": gas_price.to_string(),
+            "estimated_gas_cost": gas_cost.to_string(),
+            "fee": fee,
+            "l2_fees": l2_fees,
+            "mev_exposure": mev_exposure,
+            "balance_changes": balance_changes
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    // Chain id of the connected network unless it is a local dev node, forks included
+    async fn public_chain_id(&self) -> Option<u64> {
+        let chain_id = self.provider().get_chain_id().await.ok()?;
+        if is_local_chain(chain_id) || cheatcodes::supported(&self.provider()).await.is_ok() {
+            return None;
+        }
+        Some(chain_id)
+    }
+
+    #[tool(description = "Assemble a fully populated unsigned transaction (nonce, gas limit, fees, chain id, calldata) without signing or sending it, for signing in an external wallet. Returns the JSON-RPC transaction object, plus an EIP-681 URI for ETH and ERC20 transfers")]
+    pub async fn build_unsigned_transaction(
+        &self,
+        Parameters(request): Parameters<SendTransactionRequest>,
+    ) -> String {
+        let (mut tx_request, nonce, fee) = match self.build_transaction(&request).await {
+            Ok(tx) => tx,
+            Err(response) => return response,
+        };
+        let failure = |error: String| {
+            let result = json!({
+                "success": false,
+                "error": error,
+                "from": request.from,
+                "to": request.to
+            });
+            serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+        };
+
+        let chain_id = match self.provider().get_chain_id().await {
+            Ok(chain_id) => chain_id,
+            Err(e) => return failure(format!("Failed to get chain id: {}", e)),
+        };
+        tx_request.chain_id = Some(chain_id);
+
+        // The wallet signs exactly what is exported, so nothing may be left for the node to fill in
+        if tx_request.gas.is_none() {
+            match self.provider().estimate_gas(WithOtherFields::new(tx_request.clone())).await {
+                Ok(gas) => tx_request = tx_request.gas_limit(gas),
+                Err(e) => return failure(format!("Failed to estimate gas: {}", e)),
+            }
+        }
+        if tx_request.gas_price.is_none() && tx_request.max_fee_per_gas.is_none() {
+            match self.provider().get_gas_price().await {
+                Ok(gas_price) => tx_request = tx_request.gas_price(gas_price),
+                Err(e) => return failure(format!("Failed to get gas price: {}", e)),
+            }
+        }
+
+        let data = tx_request.input.input().cloned().unwrap_or_default();
+        let to_address = tx_request.to.and_then(|to| to.to().copied());
+        let mut result = json!({
+            "success": true,
+            "from": request.from,
+            "to": request.to,
+            "value": request.value,
+            "nonce": nonce,
+            "unsigned_transaction": unsigned::transaction_json(&tx_request),
+            "eip681": unsigned::eip681(chain_id, to_address, tx_request.value.unwrap_or_default(), &data, tx_request.gas),
+            "fee": fee,
+            "message": "Not sent: sign and broadcast it with an external wallet"
+        });
+
+        if request.to.is_empty() {
+            if let Ok(from_address) = Address::from_str(&request.from) {
+                result["contract_address"] = json!(from_address.create(nonce).to_string());
+            }
+        }
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    // The rollup the node serves, if it is a known one
+    async fn rollup(&self) -> Option<Rollup> {
+        l2_fees::rollup(self.provider().get_chain_id().await.ok()?)
+    }
+
+    #[tool(description = "Estimate the fees of a transaction on Optimism, Base or Arbitrum: L2 execution gas plus the L1 data fee that the node's gas estimate leaves out")]
+    pub async fn estimate_l2_fees(
+        &self,
+        Parameters(request): Parameters<SendTransactionRequest>,
+    ) -> String {
+        let Some(rollup) = self.rollup().await else {
+            let result = json!({
+                "success": false,
+                "error": "The connected chain is not a known L2 (Optimism, Base, Arbitrum), use the preview's gas cost"
+            });
+            return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+        };
+
+        let (tx_request, _, _) = match self.build_transaction(&request).await {
+            Ok(tx) => tx,
+            Err(response) => return response,
+        };
+        let gas = match self.provider().estimate_gas(WithOtherFields::new(tx_request.clone())).await {
+            Ok(gas) => gas,
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Failed to estimate gas: {}", e)
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+        let gas_price = match request.gas_price {
+            Some(gas_price) => gas_price,
+            None => self.provider().get_gas_price().await.unwrap_or_default(),
+        };
+
+        let result = match l2_fees::estimate(&self.provider(), rollup, &tx_request, gas, gas_price).await {
+            Ok(fees) => {
+                let mut result = json!({ "success": true });
+                result.as_object_mut().unwrap().extend(fees.as_object().cloned().unwrap_or_default());
+                result
+            }
+            Err(error) => json!({
+                "success": false,
+                "error": error
+            }),
+        };
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Get slow, standard and fast fee recommendations (EIP-1559 max fee and priority fee in wei) with expected inclusion times, from Blocknative, Etherscan or the node's fee history")]
+    pub async fn gas_oracle(
+        &self,
+        Parameters(request): Parameters<GasOracleRequest>,
+    ) -> String {
+        let result = match self.provider().get_chain_id().await {
+            Ok(chain_id) => self.gas_recommendations(request.source.as_deref(), chain_id).await,
+            Err(e) => Err(format!("Failed to get chain id: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn gas_recommendations(&self, source: Option<&str>, chain_id: u64) -> Result<serde_json::Value, String> {
+        let key = |name: &str| std::env::var(name).ok().filter(|key| !key.is_empty());
+        let blocknative_key = key("BLOCKNATIVE_API_KEY");
+        let etherscan_key = key("ETHERSCAN_API_KEY");
+
+        // Hosted oracles only know public networks, local chains always use the fee history
+        let source = match source {
+            Some(source) => source,
+            None if is_local_chain(chain_id) => "node",
+            None if blocknative_key.is_some() => "blocknative",
+            None if etherscan_key.is_some() => "etherscan",
+            None => "node",
+        };
+
+        let tiers = match source {
+            "blocknative" => {
+                let key = blocknative_key.ok_or("BLOCKNATIVE_API_KEY is not set")?;
+                self.blocknative_fees(&key, chain_id).await?
+            }
+            "etherscan" => {
+                let key = etherscan_key.ok_or("ETHERSCAN_API_KEY is not set")?;
+                self.etherscan_fees(&key, chain_id).await?
+            }
+            "node" => self.node_fees().await?,
+            source => return Err(format!("Unknown gas oracle {}, use blocknative, etherscan or node", source)),
+        };
+
+        Ok(json!({
+            "success": true,
+            "source": source,
+            "chain_id": chain_id,
+            "tiers": tiers
+        }))
+    }
+
+    // Blocknative's estimates come with the confidence of making it into the next block
+    async fn blocknative_fees(&self, key: &str, chain_id: u64) -> Result<serde_json::Value, String> {
+        let response: serde_json::Value = reqwest::Client::new()
+            .get("https://api.blocknative.com/gasprices/blockprices")
+            .header("Authorization", key)
+            .query(&[("chainid", chain_id.to_string())])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Blocknative request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Blocknative response: {}", e))?;
+
+        let estimates = response
+            .pointer("/blockPrices/0/estimatedPrices")
+            .and_then(|estimates| estimates.as_array())
+            .ok_or("Blocknative returned no estimates")?;
+        let estimate = |confidence: u64| {
+            let estimate = estimates.iter().find(|estimate| estimate.get("confidence").and_then(|c| c.as_u64()) == Some(confidence))?;
+            let gwei = |name: &str| estimate.get(name).and_then(|value| value.as_f64()).and_then(|value| gwei_to_wei(&value.to_string()));
+            // 99% confidence is about a block, lower confidence takes proportionally longer
+            let expected = BLOCK_TIME_SECS * 100 / confidence;
+            Some(fee_tier(gwei("maxFeePerGas")?, gwei("maxPriorityFeePerGas")?, expected))
+        };
+
+        Ok(json!({
+            "slow": estimate(70).ok_or("Blocknative returned no 70% estimate")?,
+            "standard": estimate(90).ok_or("Blocknative returned no 90% estimate")?,
+            "fast": estimate(99).ok_or("Blocknative returned no 99% estimate")?
+        }))
+    }
+
+    // Etherscan's gas tracker gives total gas prices, the tip is what's left above the base fee
+    async fn etherscan_fees(&self, key: &str, chain_id: u64) -> Result<serde_json::Value, String> {
+        let http = reqwest::Client::new();
+        let get = |action: &'static str, extra: Vec<(&'static str, String)>| {
+            let mut query = vec![
+                ("chainid", chain_id.to_string()),
+                ("module", "gastracker".to_string()),
+                ("action", action.to_string()),
+                ("apikey", key.to_string()),
+            ];
+            query.extend(extra);
+            let request = http.get("https://api.etherscan.io/v2/api").query(&query);
+            async move {
+                let response: serde_json::Value = request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| format!("Etherscan request failed: {}", e))?
+                    .json()
+                    .await
+                    .map_err(|e| format!("Invalid Etherscan response: {}", e))?;
+                if response.get("status").and_then(|status| status.as_str()) != Some("1") {
+                    return Err(format!("Etherscan gas tracker error: {}", response.get("result").unwrap_or(&json!(null))));
+                }
+                Ok::<_, String>(response["result"].clone())
+            }
+        };
+
+        let oracle = get("gasoracle", vec![]).await?;
+        let gwei = |name: &str| oracle.get(name).and_then(|value| value.as_str()).and_then(gwei_to_wei);
+        let base_fee = gwei("suggestBaseFee").unwrap_or_default();
+
+        let mut tiers = serde_json::Map::new();
+        for ((tier, blocks), field) in FEE_TIERS.iter().zip(["SafeGasPrice", "ProposeGasPrice", "FastGasPrice"]) {
+            let price = gwei(field).ok_or_else(|| format!("Etherscan returned no {}", field))?;
+            // The confirmation time estimate is best effort, fall back to the tier's block multiple
+            let expected = get("gasestimate", vec![("gasprice", price.to_string())])
+                .await
+                .ok()
+                .and_then(|seconds| seconds.as_str()?.parse::<u64>().ok())
+                .unwrap_or(blocks * BLOCK_TIME_SECS);
+            tiers.insert(tier.to_string(), fee_tier(price, price.saturating_sub(base_fee), expected));
+        }
+
+        Ok(serde_json::Value::Object(tiers))
+    }
+
+    // Percentiles of the tips paid over the last blocks, on top of twice the pending base fee
+    async fn node_fees(&self) -> Result<serde_json::Value, String> {
+        let history = self
+            .provider()
+            .get_fee_history(20, BlockNumberOrTag::Latest, &[10.0, 50.0, 90.0])
+            .await
+            .map_err(|e| format!("Failed to get fee history: {}", e))?;
+
+        let base_fee = history.base_fee_per_gas.last().copied().unwrap_or_default();
+        let rewards = history.reward.unwrap_or_default();
+        let tip = |percentile: usize| {
+            let tips: Vec<u128> = rewards.iter().filter_map(|block| block.get(percentile).copied()).collect();
+            if tips.is_empty() {
+                0
+            } else {
+                tips.iter().sum::<u128>() / tips.len() as u128
+            }
+        };
+
+        let mut tiers = serde_json::Map::new();
+        for (percentile, (tier, blocks)) in FEE_TIERS.iter().enumerate() {
+            let tip = tip(percentile);
+            tiers.insert(tier.to_string(), fee_tier(base_fee * 2 + tip, tip, blocks * BLOCK_TIME_SECS));
+        }
+
+        Ok(serde_json::Value::Object(tiers))
+    }
+
+    #[tool(description = "Suggest a priority fee and max fee (in wei) for inclusion in the next block, within 1 minute or within 5 minutes, from the tips paid in recent blocks and by pending transactions")]
+    pub async fn suggest_priority_fee(
+        &self,
+        Parameters(request): Parameters<SuggestPriorityFeeRequest>,
+    ) -> String {
+        let result = match Target::parse(request.target.as_deref().unwrap_or("1m")) {
+            Ok(target) => priority_fee::suggest(&self.provider(), target).await,
+            Err(error) => Err(error),
+        };
+
+        let result = match result {
+            Ok(mut fee) => {
+                fee["success"] = json!(true);
+                fee
+            }
+            Err(error) => json!({
+                "success": false,
+                "error": error
+            }),
+        };
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Resolve a token symbol (e.g. USDC) to its checksummed address and decimals on the current chain, from the bundled and configured token lists")]
+    pub async fn resolve_token(
+        &self,
+        Parameters(request): Parameters<ResolveTokenRequest>,
+    ) -> String {
+        let chain_id = match self.provider().get_chain_id().await {
+            Ok(chain_id) => chain_id,
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Failed to get chain id: {}", e),
+                    "symbol": request.symbol
                 });
                 return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
             }
         };
+        // Local chains are usually mainnet forks, so they use the mainnet addresses
+        let list_chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+
+        let tokens = self.tokens.get_or_init(token_list::load).await;
+        let matches = token_list::resolve(tokens, list_chain_id, &request.symbol);
+        let candidates: Vec<serde_json::Value> = matches
+            .iter()
+            .map(|token| {
+                json!({
+                    "address": token.address.to_string(),
+                    "symbol": token.symbol,
+                    "name": token.name,
+                    "decimals": token.decimals
+                })
+            })
+            .collect();
+
+        let result = match candidates.as_slice() {
+            [] => json!({
+                "success": false,
+                "error": format!("{} is not in the token lists for chain {}, ask for the token address", request.symbol, list_chain_id),
+                "symbol": request.symbol,
+                "chain_id": list_chain_id
+            }),
+            [token] => json!({
+                "success": true,
+                "chain_id": list_chain_id,
+                "token": token
+            }),
+            // Never pick one of several tokens sharing a symbol
+            _ => json!({
+                "success": false,
+                "error": format!("{} is ambiguous on chain {}, ask which token is meant", request.symbol, list_chain_id),
+                "chain_id": list_chain_id,
+                "candidates": candidates
+            }),
+        };
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Sign an EIP-2612 permit letting a spender use the owner's tokens without an approve transaction. Returns v, r, s and the deadline to pass to permit() or a router's permit-and-swap call")]
+    pub async fn sign_permit(
+        &self,
+        Parameters(request): Parameters<SignPermitRequest>,
+    ) -> String {
+        let result = self.permit_signature(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "token": request.token
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn permit_signature(&self, request: &SignPermitRequest) -> Result<serde_json::Value, String> {
+        let token = Address::from_str(&request.token).map_err(|e| format!("Invalid token address: {}", e))?;
+        let owner = Address::from_str(&request.owner).map_err(|e| format!("Invalid owner address: {}", e))?;
+        let spender = Address::from_str(&request.spender).map_err(|e| format!("Invalid spender address: {}", e))?;
+        let value = U256::from_str(&request.value).map_err(|e| format!("Invalid value: {}", e))?;
+        let deadline = request.deadline.unwrap_or_else(|| unix_now() + DEFAULT_PERMIT_VALIDITY_SECS);
+
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        let contract = ERC20Permit::new(token, self.provider());
+        let separator = contract.DOMAIN_SEPARATOR().call().await.map_err(|_| "Token does not support EIP-2612 permits".to_string())?;
+        let name = contract.name().call().await.map_err(|e| format!("Failed to read the token name: {}", e))?;
+        let nonce = contract.nonces(owner).call().await.map_err(|e| format!("Failed to read the permit nonce: {}", e))?;
+        let version = contract.version().call().await.ok();
+        let version = permit::matching_version(&name, permit::candidate_versions(version.as_deref()), chain_id, token, separator)
+            .ok_or_else(|| "Could not reproduce the token's EIP-712 domain, it uses a non-standard permit".to_string())?
+            .to_string();
+
+        let message = Permit {
+            owner,
+            spender,
+            value,
+            nonce,
+            deadline: U256::from(deadline),
+        };
+        let digest = permit::signing_hash(&message, &permit::domain(&name, &version, chain_id, token));
+        let signature = self
+            .provider()
+            .raw_request::<_, String>(
+                "eth_signTypedData_v4".into(),
+                (owner, permit::typed_data(&message, &name, &version, chain_id, token)),
+            )
+            .await
+            .map_err(|e| format!("Failed to sign the permit as {}: {}", owner, e))?;
+        let (v, r, s) = permit::split_signature(&signature)?;
+
+        Ok(json!({
+            "success": true,
+            "token": token.to_string(),
+            "owner": owner.to_string(),
+            "spender": spender.to_string(),
+            "value": value.to_string(),
+            "nonce": nonce.to_string(),
+            "deadline": deadline,
+            "digest": digest.to_string(),
+            "signature": signature,
+            "v": v,
+            "r": r,
+            "s": s
+        }))
+    }
+
+    #[tool(description = "Read a Safe multisig's owners, confirmation threshold, nonce and version")]
+    pub async fn safe_info(
+        &self,
+        Parameters(request): Parameters<SafeInfoRequest>,
+    ) -> String {
+        let safe_address = match Address::from_str(&request.safe) {
+            Ok(address) => address,
+            Err(e) => {
+                let result = json!({
+                    "success": false,
+                    "error": format!("Invalid Safe address: {}", e),
+                    "safe": request.safe
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+
+        let contract = Safe::new(safe_address, self.provider());
+        let (owners, threshold, nonce) = match (contract.getOwners().call().await, contract.getThreshold().call().await, contract.nonce().call().await) {
+            (Ok(owners), Ok(threshold), Ok(nonce)) => (owners, threshold, nonce),
+            _ => {
+                let result = json!({
+                    "success": false,
+                    "error": "Address is not a Safe (getOwners/getThreshold/nonce failed)",
+                    "safe": request.safe
+                });
+                return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+            }
+        };
+        let version = contract.VERSION().call().await.unwrap_or_default();
+
+        let result = json!({
+            "success": true,
+            "safe": safe_address.to_string(),
+            "owners": owners.iter().map(|owner| owner.to_string()).collect::<Vec<_>>(),
+            "threshold": threshold.to_string(),
+            "nonce": nonce.to_string(),
+            "version": version
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Propose a transaction to a Safe multisig through the Safe Transaction Service, signed by the proposer. The other owners still have to confirm and execute it")]
+    pub async fn propose_safe_transaction(
+        &self,
+        Parameters(request): Parameters<ProposeSafeTransactionRequest>,
+    ) -> String {
+        let result = self.propose_to_safe(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "safe": request.safe,
+                "to": request.to
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn propose_to_safe(&self, request: &ProposeSafeTransactionRequest) -> Result<serde_json::Value, String> {
+        let safe_address = Address::from_str(&request.safe).map_err(|e| format!("Invalid Safe address: {}", e))?;
+        let to = Address::from_str(&request.to).map_err(|e| format!("Invalid address: {}", e))?;
+        let proposer = Address::from_str(&request.proposer).map_err(|e| format!("Invalid proposer address: {}", e))?;
+        let value = U256::from_str(&request.value).map_err(|e| format!("Invalid amount: {}", e))?;
+        let data = match &request.data {
+            Some(data) => hex::decode(data.trim_start_matches("0x")).map_err(|e| format!("Invalid data format: {}", e))?,
+            None => vec![],
+        };
+
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        let service_url = safe::transaction_service_url(chain_id)
+            .ok_or_else(|| format!("No Safe Transaction Service known for chain {}, set SAFE_TX_SERVICE_URL", chain_id))?;
+
+        let contract = Safe::new(safe_address, self.provider());
+        let threshold = contract.getThreshold().call().await.map_err(|_| "Address is not a Safe".to_string())?;
+        let nonce = match request.nonce {
+            Some(nonce) => U256::from(nonce),
+            None => contract.nonce().call().await.map_err(|e| format!("Failed to read the Safe nonce: {}", e))?,
+        };
+        let proposer_is_owner = contract.isOwner(proposer).call().await.unwrap_or(false);
+
+        let tx = safe::safe_tx(to, value, data.into(), nonce);
+        let safe_tx_hash = safe::safe_tx_hash(&tx, safe_address, chain_id);
+
+        // A local proposer key signs directly, otherwise the node holding the proposer's account does
+        let signature = match safe::sign_locally(safe_tx_hash, proposer).await? {
+            Some(signature) => signature,
+            None => self
+                .provider()
+                .raw_request::<_, String>(
+                    "eth_signTypedData_v4".into(),
+                    (proposer, safe::typed_data(&tx, safe_address, chain_id)),
+                )
+                .await
+                .map_err(|e| format!("Failed to sign the Safe transaction as {}: {}", proposer, e))?,
+        };
+
+        let body = json!({
+            "to": to.to_string(),
+            "value": value.to_string(),
+            "data": if tx.data.is_empty() { serde_json::Value::Null } else { json!(tx.data.to_string()) },
+            "operation": 0,
+            "safeTxGas": "0",
+            "baseGas": "0",
+            "gasPrice": "0",
+            "gasToken": Address::ZERO.to_string(),
+            "refundReceiver": Address::ZERO.to_string(),
+            "nonce": nonce.to_string(),
+            "contractTransactionHash": safe_tx_hash.to_string(),
+            "sender": proposer.to_string(),
+            "signature": signature,
+            "origin": "eth-agent"
+        });
+
+        let mut post = reqwest::Client::new()
+            .post(format!("{}/api/v1/safes/{}/multisig-transactions/", service_url, safe_address))
+            .json(&body);
+        if let Ok(key) = std::env::var("SAFE_API_KEY") {
+            post = post.bearer_auth(key);
+        }
+        let response = post.send().await.map_err(|e| format!("Safe Transaction Service request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            return Err(format!("Safe Transaction Service rejected the proposal ({}): {}", status, detail));
+        }
+
+        Ok(json!({
+            "success": true,
+            "safe": safe_address.to_string(),
+            "to": to.to_string(),
+            "value": value.to_string(),
+            "nonce": nonce.to_string(),
+            "safe_tx_hash": safe_tx_hash.to_string(),
+            "proposer": proposer.to_string(),
+            "proposer_is_owner": proposer_is_owner,
+            "confirmations_required": threshold.to_string(),
+            "message": "Proposed, the transaction is executed once enough owners have confirmed it"
+        }))
+    }
+
+    #[tool(description = "Read the EntryPoint nonce of an ERC-4337 smart account, the nonce its next UserOperation needs")]
+    pub async fn smart_account_nonce(
+        &self,
+        Parameters(request): Parameters<SmartAccountNonceRequest>,
+    ) -> String {
+        let result = self.account_nonce(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "account": request.account
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn account_nonce(&self, request: &SmartAccountNonceRequest) -> Result<serde_json::Value, String> {
+        let account = Address::from_str(&request.account).map_err(|e| format!("Invalid account address: {}", e))?;
+        let key = match &request.key {
+            Some(key) => U256::from_str(key).map_err(|e| format!("Invalid nonce key: {}", e))?,
+            None => U256::ZERO,
+        };
+        let entry_point = user_op::entry_point()?;
+        let nonce = EntryPoint::new(entry_point, self.provider())
+            .getNonce(account, key.to())
+            .call()
+            .await
+            .map_err(|e| format!("Failed to read the nonce from EntryPoint {}: {}", entry_point, e))?;
+
+        Ok(json!({
+            "success": true,
+            "account": account.to_string(),
+            "entry_point": entry_point.to_string(),
+            "nonce": nonce.to_string()
+        }))
+    }
+
+    #[tool(description = "Build an unsigned ERC-4337 UserOperation making a smart account call `to` with `value` and `data`, with gas limits estimated by the bundler and fees from the node. With `sponsored` a paymaster pays the gas, its terms are returned as `sponsorship`. Sign it with sign_user_operation")]
+    pub async fn build_user_operation(
+        &self,
+        Parameters(request): Parameters<BuildUserOperationRequest>,
+    ) -> String {
+        let result = self.user_operation(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "account": request.account,
+                "to": request.to
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn user_operation(&self, request: &BuildUserOperationRequest) -> Result<serde_json::Value, String> {
+        let account = Address::from_str(&request.account).map_err(|e| format!("Invalid account address: {}", e))?;
+        let to = Address::from_str(&request.to).map_err(|e| format!("Invalid address: {}", e))?;
+        let value = U256::from_str(&request.value).map_err(|e| format!("Invalid amount: {}", e))?;
+        let data = match &request.data {
+            Some(data) => hex::decode(data.trim_start_matches("0x")).map_err(|e| format!("Invalid data format: {}", e))?,
+            None => vec![],
+        };
+        let entry_point = user_op::entry_point()?;
+        let nonce = match &request.nonce {
+            Some(nonce) => U256::from_str(nonce).map_err(|e| format!("Invalid nonce: {}", e))?,
+            None => EntryPoint::new(entry_point, self.provider())
+                .getNonce(account, Default::default())
+                .call()
+                .await
+                .map_err(|e| format!("Failed to read the nonce from EntryPoint {}: {}", entry_point, e))?,
+        };
+        let fees = self.provider().estimate_eip1559_fees().await.map_err(|e| format!("Failed to estimate fees: {}", e))?;
+
+        let call_data = user_op::executeCall { dest: to, value, func: data.into() }.abi_encode();
+        let mut user_operation = UserOperation {
+            sender: account,
+            nonce,
+            factory: None,
+            factory_data: None,
+            call_data: call_data.into(),
+            call_gas_limit: U256::ZERO,
+            verification_gas_limit: U256::ZERO,
+            pre_verification_gas: U256::ZERO,
+            max_fee_per_gas: U256::from(fees.max_fee_per_gas),
+            max_priority_fee_per_gas: U256::from(fees.max_priority_fee_per_gas),
+            paymaster: None,
+            paymaster_verification_gas_limit: None,
+            paymaster_post_op_gas_limit: None,
+            paymaster_data: None,
+            signature: Bytes::from_str(user_op::DUMMY_SIGNATURE).unwrap_or_default(),
+        };
+
+        // The bundler simulates validation and the call, a call that would revert fails here
+        let estimate = user_op::bundler_request(
+            "eth_estimateUserOperationGas",
+            json!([user_operation, entry_point.to_string()]),
+        )
+        .await?;
+        user_operation.call_gas_limit = user_op::quantity(&estimate, "callGasLimit")?;
+        user_operation.verification_gas_limit = user_op::quantity(&estimate, "verificationGasLimit")?;
+        user_operation.pre_verification_gas = user_op::quantity(&estimate, "preVerificationGas")?;
+        let sponsorship = match request.sponsored {
+            Some(true) => Some(user_op::sponsor(&mut user_operation, entry_point).await?),
+            _ => None,
+        };
+        user_operation.signature = Bytes::new();
+
+        let user_op_hash = EntryPoint::new(entry_point, self.provider())
+            .getUserOpHash(user_operation.packed())
+            .call()
+            .await
+            .map_err(|e| format!("Failed to hash the UserOperation: {}", e))?;
+
+        Ok(json!({
+            "success": true,
+            "account": account.to_string(),
+            "to": to.to_string(),
+            "value": value.to_string(),
+            "entry_point": entry_point.to_string(),
+            "user_operation": user_operation,
+            "user_op_hash": user_op_hash.to_string(),
+            "max_gas": user_operation.max_gas().to_string(),
+            "max_gas_cost": user_operation.max_gas_cost().to_string(),
+            "gas_paid_by": if sponsorship.is_some() { "paymaster" } else { "account" },
+            "sponsorship": sponsorship,
+            "message": "Not signed yet, sign it with sign_user_operation"
+        }))
+    }
+
+    #[tool(description = "Sign an ERC-4337 UserOperation as the smart account's owner, returning it with the signature to pass to send_user_operation")]
+    pub async fn sign_user_operation(
+        &self,
+        Parameters(request): Parameters<SignUserOperationRequest>,
+    ) -> String {
+        let result = self.signed_user_operation(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "owner": request.owner
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn signed_user_operation(&self, request: &SignUserOperationRequest) -> Result<serde_json::Value, String> {
+        let mut user_operation: UserOperation =
+            serde_json::from_value(request.user_operation.clone()).map_err(|e| format!("Invalid UserOperation: {}", e))?;
+        let owner = Address::from_str(&request.owner).map_err(|e| format!("Invalid owner address: {}", e))?;
+        let entry_point = user_op::entry_point()?;
+
+        user_operation.signature = Bytes::new();
+        let user_op_hash = EntryPoint::new(entry_point, self.provider())
+            .getUserOpHash(user_operation.packed())
+            .call()
+            .await
+            .map_err(|e| format!("Failed to hash the UserOperation: {}", e))?;
+
+        // A local owner key signs directly, otherwise the node holding the owner's account does
+        let signature = match user_op::sign_locally(user_op_hash, owner).await? {
+            Some(signature) => signature,
+            None => self
+                .provider()
+                .raw_request::<_, String>("eth_sign".into(), (owner, user_op_hash))
+                .await
+                .map_err(|e| format!("Failed to sign the UserOperation as {}: {}", owner, e))?,
+        };
+        user_operation.signature = Bytes::from_str(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+
+        Ok(json!({
+            "success": true,
+            "owner": owner.to_string(),
+            "user_operation": user_operation,
+            "user_op_hash": user_op_hash.to_string(),
+            "signature": signature
+        }))
+    }
+
+    #[tool(description = "Submit a signed ERC-4337 UserOperation to the bundler and wait for it to be included, returning the bundle transaction hash and whether the call succeeded")]
+    pub async fn send_user_operation(
+        &self,
+        Parameters(request): Parameters<SendUserOperationRequest>,
+    ) -> String {
+        let result = self.submitted_user_operation(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn submitted_user_operation(&self, request: &SendUserOperationRequest) -> Result<serde_json::Value, String> {
+        let user_operation: UserOperation =
+            serde_json::from_value(request.user_operation.clone()).map_err(|e| format!("Invalid UserOperation: {}", e))?;
+        if user_operation.signature.is_empty() {
+            return Err("The UserOperation is not signed, sign it with sign_user_operation first".to_string());
+        }
+        let entry_point = user_op::entry_point()?;
+        let hash = user_op::bundler_request("eth_sendUserOperation", json!([user_operation, entry_point.to_string()])).await?;
+        let user_op_hash = hash.as_str().unwrap_or_default().to_string();
+
+        // Bundlers batch operations into their next bundle, usually within a few blocks
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(USER_OP_RECEIPT_TIMEOUT_SECS);
+        loop {
+            let receipt = user_op::bundler_request("eth_getUserOperationReceipt", json!([user_op_hash])).await?;
+            if !receipt.is_null() {
+                let included = receipt.get("success").and_then(|success| success.as_bool()).unwrap_or(false);
+                let transaction_hash = receipt
+                    .get("receipt")
+                    .and_then(|receipt| receipt.get("transactionHash"))
+                    .cloned()
+                    .unwrap_or_default();
+                return Ok(json!({
+                    "success": included,
+                    "status": if included { "included" } else { "reverted" },
+                    "account": user_operation.sender.to_string(),
+                    "user_op_hash": user_op_hash,
+                    "transaction_hash": transaction_hash,
+                    "actual_gas_cost": receipt.get("actualGasCost").cloned().unwrap_or_default(),
+                    "error": if included { serde_json::Value::Null } else { json!("The UserOperation was included but its call reverted") }
+                }));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(json!({
+                    "success": true,
+                    "status": "pending",
+                    "account": user_operation.sender.to_string(),
+                    "user_op_hash": user_op_hash,
+                    "message": format!("Submitted, not included within {}s", USER_OP_RECEIPT_TIMEOUT_SECS)
+                }));
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        }
+    }
+
+    #[tool(description = "Get the spot USD price of an asset from a Chainlink feed or CoinGecko, with the time it was last updated")]
+    pub async fn get_price(
+        &self,
+        Parameters(request): Parameters<GetPriceRequest>,
+    ) -> String {
+        let asset = request.asset.trim().to_uppercase();
+        let feed = PRICE_FEEDS.iter().find(|(symbol, _, _)| *symbol == asset);
+
+        let result = match request.source.as_deref() {
+            Some("coingecko") => self.coingecko_price(&asset, feed.map(|(_, _, id)| *id)).await,
+            Some("chainlink") => match feed {
+                Some((_, feed, _)) => self.chainlink_price(&asset, feed).await,
+                None => Err(format!("No Chainlink feed known for {}", asset)),
+            },
+            Some(source) => Err(format!("Unknown price source {}, use chainlink or coingecko", source)),
+            // Feeds only exist on mainnet and its forks, fall back to CoinGecko elsewhere
+            None => match feed {
+                Some((_, feed, id)) => match self.chainlink_price(&asset, feed).await {
+                    Ok(price) => Ok(price),
+                    Err(_) => self.coingecko_price(&asset, Some(id)).await,
+                },
+                None => self.coingecko_price(&asset, None).await,
+            },
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "asset": asset
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn chainlink_price(&self, asset: &str, feed: &str) -> Result<serde_json::Value, String> {
+        let aggregator = AggregatorV3::new(Address::from_str(feed).unwrap(), self.provider());
+
+        let decimals = aggregator.decimals().call().await.map_err(|e| format!("Failed to read Chainlink feed: {}", e))?;
+        let round = aggregator.latestRoundData().call().await.map_err(|e| format!("Failed to read Chainlink feed: {}", e))?;
+        if round.answer.is_negative() {
+            return Err(format!("Chainlink feed returned a negative price for {}", asset));
+        }
+
+        let updated_at = round.updatedAt.to::<u64>();
+        Ok(json!({
+            "success": true,
+            "asset": asset,
+            "price_usd": format_fixed(round.answer.into_raw(), decimals),
+            "source": "chainlink",
+            "feed": feed,
+            "updated_at": updated_at,
+            "age_seconds": unix_now().saturating_sub(updated_at)
+        }))
+    }
+
+    async fn coingecko_price(&self, asset: &str, coingecko_id: Option<&str>) -> Result<serde_json::Value, String> {
+        let id = coingecko_id.map(|id| id.to_string()).unwrap_or_else(|| asset.to_lowercase());
+
+        let response: serde_json::Value = reqwest::Client::new()
+            .get("https://api.coingecko.com/api/v3/simple/price")
+            .query(&[("ids", id.as_str()), ("vs_currencies", "usd"), ("include_last_updated_at", "true")])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("CoinGecko request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid CoinGecko response: {}", e))?;
+
+        let (Some(price), updated_at) = (
+            response.pointer(&format!("/{}/usd", id)).and_then(|price| price.as_f64()),
+            response.pointer(&format!("/{}/last_updated_at", id)).and_then(|updated_at| updated_at.as_u64()),
+        ) else {
+            return Err(format!("CoinGecko has no USD price for {} (id {})", asset, id));
+        };
+
+        let updated_at = updated_at.unwrap_or_else(unix_now);
+        Ok(json!({
+            "success": true,
+            "asset": asset,
+            "price_usd": price.to_string(),
+            "source": "coingecko",
+            "coingecko_id": id,
+            "updated_at": updated_at,
+            "age_seconds": unix_now().saturating_sub(updated_at)
+        }))
+    }
+
+    #[tool(description = "Sample an account's ETH and ERC20 balances at blocks spread over the last days, each priced in USD by the Chainlink feed at that block (mainnet and forks), for holdings-over-time and P&L reports. Needs an archive node for old blocks")]
+    pub async fn portfolio_history(
+        &self,
+        Parameters(request): Parameters<PortfolioHistoryRequest>,
+    ) -> String {
+        let result = self.holdings_over_time(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn holdings_over_time(&self, request: &PortfolioHistoryRequest) -> Result<serde_json::Value, String> {
+        let account = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let days = request.days.unwrap_or(DEFAULT_PORTFOLIO_DAYS).max(1);
+        let samples = request.samples.unwrap_or(DEFAULT_PORTFOLIO_SAMPLES).clamp(2, MAX_PORTFOLIO_SAMPLES);
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        // Forks keep mainnet's tokens and price feeds
+        let price_chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+
+        let tokens = match &request.tokens {
+            Some(tokens) => tokens
+                .iter()
+                .map(|token| Address::from_str(token).map_err(|e| format!("Invalid token address {}: {}", token, e)))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => portfolio::default_tokens(price_chain_id),
+        };
+        let mut assets = vec![Asset {
+            symbol: "ETH".to_string(),
+            token: None,
+            decimals: 18,
+            feed: None,
+        }];
+        for token in tokens {
+            assets.push(portfolio::token_asset(&self.provider(), token).await?);
+        }
+        // Wrapped assets are priced like what they wrap
+        if price_chain_id == 1 {
+            for asset in &mut assets {
+                let symbol = match asset.symbol.to_uppercase().as_str() {
+                    "WETH" => "ETH".to_string(),
+                    "WBTC" => "BTC".to_string(),
+                    symbol => symbol.to_string(),
+                };
+                asset.feed = PRICE_FEEDS
+                    .iter()
+                    .find(|(feed_symbol, _, _)| *feed_symbol == symbol)
+                    .and_then(|(_, feed, _)| Address::from_str(feed).ok());
+            }
+        }
+
+        let mut history = vec![];
+        for block in portfolio::sample_blocks(&self.provider(), days, samples).await? {
+            history.push(portfolio::sample(&self.provider(), block, account, &assets).await?);
+        }
+        Ok(json!({
+            "success": true,
+            "address": account.to_string(),
+            "chain_id": chain_id,
+            "days": days,
+            "samples": history
+        }))
+    }
+
+    #[tool(description = "Read an account's DeFi positions: Aave V3 and Compound V3 supplied and borrowed assets with their health factor and liquidation risk (none, safe, watch, at_risk, liquidatable), and open Uniswap V3 LP positions with their amounts and whether they are in range")]
+    pub async fn defi_positions(
+        &self,
+        Parameters(request): Parameters<DefiPositionsRequest>,
+    ) -> String {
+        let result = self.read_positions(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn read_positions(&self, request: &DefiPositionsRequest) -> Result<serde_json::Value, String> {
+        let account = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let protocols: Vec<&str> = match &request.protocols {
+            Some(protocols) => protocols.iter().map(String::as_str).collect(),
+            None => positions::PROTOCOLS.to_vec(),
+        };
+        if let Some(unknown) = protocols.iter().find(|protocol| !positions::PROTOCOLS.contains(protocol)) {
+            return Err(format!("Unknown protocol {}, use one of {}", unknown, positions::PROTOCOLS.join(", ")));
+        }
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        // Forks keep mainnet's deployments
+        let chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+        Ok(positions::read(&self.provider(), chain_id, account, &protocols).await)
+    }
+
+    #[tool(description = "Batch independent calls from one sender into a single Multicall3 aggregate3Value transaction, e.g. ETH payouts to many recipients. The calls run with Multicall3 as msg.sender, so token transfers, approvals and other calls acting for the sender are refused. Nothing is sent, pass the returned transaction to send_transaction")]
+    pub async fn batch_calls(
+        &self,
+        Parameters(request): Parameters<BatchCallsRequest>,
+    ) -> String {
+        let result = self.build_batch(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "from": request.from
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn build_batch(&self, request: &BatchCallsRequest) -> Result<serde_json::Value, String> {
+        let from = Address::from_str(&request.from).map_err(|e| format!("Invalid from address: {}", e))?;
+        let calls = request
+            .calls
+            .iter()
+            .map(|call| multicall::BatchCall::parse(&call.to, call.value.as_deref(), call.data.as_deref(), call.allow_failure.unwrap_or(false)))
+            .collect::<Result<Vec<_>, _>>()?;
+        multicall::batch(&self.provider(), from, &calls).await
+    }
+
+    #[tool(description = "Build the transaction of a staking action on mainnet (and forks): stake ETH with Lido (stETH) or Rocket Pool (rETH), unstake (a Lido withdrawal request, after approving the stETH to approval_address, or an rETH burn) or claim a finalized Lido withdrawal. Nothing is sent, pass the returned transaction to send_transaction")]
+    pub async fn staking_transaction(
+        &self,
+        Parameters(request): Parameters<StakingTransactionRequest>,
+    ) -> String {
+        let result = self.build_staking_transaction(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "action": request.action,
+                "protocol": request.protocol
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn build_staking_transaction(&self, request: &StakingTransactionRequest) -> Result<serde_json::Value, String> {
+        let from = Address::from_str(&request.from).map_err(|e| format!("Invalid from address: {}", e))?;
+        self.check_staking_chain().await?;
+        let amount = || {
+            let amount = request.amount.as_deref().ok_or_else(|| format!("{} needs an amount", request.action))?;
+            U256::from_str(amount).map_err(|e| format!("Invalid amount {}: {}", amount, e))
+        };
+        match request.action.as_str() {
+            "stake" => staking::stake(&self.provider(), &request.protocol, from, amount()?).await,
+            "unstake" => staking::unstake(&self.provider(), &request.protocol, from, amount()?).await,
+            "claim" if request.protocol == "lido" => {
+                let request_id = request.request_id.as_deref().ok_or("claim needs a request_id")?;
+                let request_id = U256::from_str(request_id).map_err(|e| format!("Invalid request_id: {}", e))?;
+                Ok(staking::claim(from, request_id))
+            }
+            "claim" => Err("Only Lido withdrawals are claimed, rETH burns pay out right away".to_string()),
+            action => Err(format!("Unknown staking action {}, use stake, unstake or claim", action)),
+        }
+    }
+
+    #[tool(description = "List an account's Lido withdrawal requests with their stETH amount and status: pending (waiting to be finalized), claimable or claimed")]
+    pub async fn staking_withdrawals(
+        &self,
+        Parameters(request): Parameters<StakingWithdrawalsRequest>,
+    ) -> String {
+        let result = self.list_withdrawals(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "owner": request.owner
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn list_withdrawals(&self, request: &StakingWithdrawalsRequest) -> Result<serde_json::Value, String> {
+        let owner = Address::from_str(&request.owner).map_err(|e| format!("Invalid owner address: {}", e))?;
+        self.check_staking_chain().await?;
+        staking::withdrawal_status(&self.provider(), owner).await
+    }
+
+    // Staking contracts are only known on mainnet, forks keep them
+    async fn check_staking_chain(&self) -> Result<(), String> {
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        if chain_id != 1 && !is_local_chain(chain_id) {
+            return Err(format!("Staking is only supported on mainnet and its forks, not chain {}", chain_id));
+        }
+        Ok(())
+    }
+
+    #[tool(description = "Quote moving funds to another chain through a bridge (via LI.FI): the bridge, the amount received, fees and duration, and the transaction to send on the source chain with send_transaction, plus the other routes to compare. Tokens other than ETH need an approve of approval_address first")]
+    pub async fn bridge_quote(
+        &self,
+        Parameters(request): Parameters<BridgeQuoteRequest>,
+    ) -> String {
+        let result = self.quote_bridge(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn quote_bridge(&self, request: &BridgeQuoteRequest) -> Result<serde_json::Value, String> {
+        let from_chain = match request.from_chain_id {
+            Some(chain_id) => chain_id,
+            None => {
+                let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+                // Bridges only run between public chains, a fork's parent chain is unknown here
+                if is_local_chain(chain_id) {
+                    return Err(format!("Chain {} is a local chain, give from_chain_id", chain_id));
+                }
+                chain_id
+            }
+        };
+        if from_chain == request.to_chain_id {
+            return Err(format!("Both ends are chain {}, nothing to bridge", from_chain));
+        }
 
-        // Create transaction request
-        let mut tx_request = TransactionRequest::default()
-            .to(to_address)
-            .value(amount)
-            .nonce(nonce)
-            .from(from_address);
-
-        if !data.is_empty() {
-            tx_request = tx_request.input(data.into());
+        let quote_request = bridge::QuoteRequest {
+            from_chain,
+            to_chain: request.to_chain_id,
+            from_token: request.from_token.as_deref().unwrap_or(bridge::NATIVE_TOKEN),
+            to_token: request.to_token.as_deref().unwrap_or(bridge::NATIVE_TOKEN),
+            amount: &request.amount,
+            from: &request.from,
+            to: request.to.as_deref().unwrap_or(&request.from),
+        };
+        let mut quote = bridge::quote(&quote_request).await?;
+        // The alternatives only inform the choice, the quote stands without them
+        if request.compare.unwrap_or(true) {
+            match bridge::routes(&quote_request).await {
+                Ok(routes) => quote["routes"] = json!(routes),
+                Err(e) => quote["routes_error"] = json!(e),
+            }
         }
+        Ok(quote)
+    }
 
-        if let Some(gas_limit) = request.gas_limit {
-            tx_request = tx_request.gas_limit(gas_limit);
+    #[tool(description = "Get the time-weighted average price of a token pair from a Uniswap V3 pool on the connected chain. Works on forks and offline, where get_price's external sources don't reflect the chain's state")]
+    pub async fn get_twap(
+        &self,
+        Parameters(request): Parameters<GetTwapRequest>,
+    ) -> String {
+        let result = self.pool_twap(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn pool_twap(&self, request: &GetTwapRequest) -> Result<serde_json::Value, String> {
+        let token_a = Address::from_str(&request.token_a).map_err(|e| format!("Invalid token address {}: {}", request.token_a, e))?;
+        let token_b = Address::from_str(&request.token_b).map_err(|e| format!("Invalid token address {}: {}", request.token_b, e))?;
+        let window_secs = request.window_secs.unwrap_or(DEFAULT_TWAP_WINDOW_SECS);
+        if window_secs == 0 {
+            return Err("window_secs must be at least 1".to_string());
         }
 
-        if let Some(gas_price) = request.gas_price {
-            tx_request = tx_request.gas_price(gas_price);
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        // Local chains are usually mainnet forks, so they use the mainnet deployment
+        let chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+
+        let decimals_a = ERC20::new(token_a, self.provider()).decimals().call().await
+            .map_err(|e| format!("Failed to read the decimals of {}: {}", token_a, e))?;
+        let decimals_b = ERC20::new(token_b, self.provider()).decimals().call().await
+            .map_err(|e| format!("Failed to read the decimals of {}: {}", token_b, e))?;
+
+        twap::twap(&self.provider(), chain_id, (token_a, decimals_a), (token_b, decimals_b), request.fee, window_secs).await
+    }
+
+    #[tool(description = "Get the EIP-1186 Merkle proof (eth_getProof) of an account and some of its storage slots at a block, for light clients and bridges verifying state against a state root")]
+    pub async fn get_proof(
+        &self,
+        Parameters(request): Parameters<GetProofRequest>,
+    ) -> String {
+        let result = match self.account_proof(&request).await {
+            Ok(proof) => json!({
+                "success": true,
+                "block": request.block.as_deref().unwrap_or("latest"),
+                "proof": proof
+            }),
+            Err(error) => json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            }),
+        };
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn account_proof(&self, request: &GetProofRequest) -> Result<serde_json::Value, String> {
+        let address = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let slots = request.slots.iter().map(|slot| parse_slot(slot)).collect::<Result<Vec<_>, _>>()?;
+        let block = parse_block(request.block.as_deref())?;
+
+        // Nodes without archive state can only prove recent blocks
+        let proof = self.provider()
+            .get_proof(address, slots)
+            .block_id(block)
+            .await
+            .map_err(|e| format!("Failed to get proof: {}", e))?;
+        serde_json::to_value(proof).map_err(|e| format!("Failed to serialize proof: {}", e))
+    }
+
+    #[tool(description = "Explain what a mined transaction did: status, action type (swap, token transfer, approval, ...), decoded call and events, assets moved including internal ETH transfers, and counterparties")]
+    pub async fn explain_transaction(
+        &self,
+        Parameters(request): Parameters<ExplainTransactionRequest>,
+    ) -> String {
+        let result = match B256::from_str(&request.hash) {
+            Ok(hash) => explain::explain(&self.provider(), hash).await,
+            Err(e) => Err(format!("Invalid transaction hash: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "hash": request.hash
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Classify an address by the standards it implements (ERC-20, ERC-721, ERC-1155, ERC-4626, EIP-2612, Safe, proxies, plain accounts) using ERC-165 and getter probes, with the tools that fit it. Call it before using token or NFT tools on an unknown contract")]
+    pub async fn detect_standards(
+        &self,
+        Parameters(request): Parameters<DetectStandardsRequest>,
+    ) -> String {
+        let result = match Address::from_str(&request.address) {
+            Ok(address) => standards::detect(&self.provider(), address).await,
+            Err(e) => Err(format!("Invalid address: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Check that a contract's deployed bytecode matches its verified source: fetches the source from Sourcify or Etherscan, recompiles it with forge using the recorded compiler settings and diffs the runtime bytecode. A mismatch means the verified source can't be trusted")]
+    pub async fn verify_bytecode(
+        &self,
+        Parameters(request): Parameters<VerifyBytecodeRequest>,
+    ) -> String {
+        let result = self.bytecode_diff(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn bytecode_diff(&self, request: &VerifyBytecodeRequest) -> Result<serde_json::Value, String> {
+        let address = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let deployed = self.provider().get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+        if deployed.is_empty() {
+            return Err("No contract code found at address".to_string());
         }
 
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        // Forks keep mainnet's code, so look up mainnet's verified source
+        let chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+        let etherscan_key = std::env::var("ETHERSCAN_API_KEY").ok().filter(|key| !key.is_empty());
 
-        // Log the transaction details for debugging
-        tracing::debug!("Sending transaction: from={}, to={}, value={}, nonce={}", 
-                       request.from, request.to, request.value, nonce);
+        let source = match request.source.as_deref() {
+            Some("sourcify") => verify::sourcify_source(chain_id, address).await?,
+            Some("etherscan") => {
+                let key = etherscan_key.ok_or("ETHERSCAN_API_KEY is not set")?;
+                verify::etherscan_source(chain_id, address, &key).await?
+            }
+            Some(source) => return Err(format!("Unknown source {}, use sourcify or etherscan", source)),
+            None => match (verify::sourcify_source(chain_id, address).await, etherscan_key) {
+                (Ok(source), _) => source,
+                (Err(_), Some(key)) => verify::etherscan_source(chain_id, address, &key).await?,
+                (Err(e), None) => return Err(e),
+            },
+        };
 
-        // Send the transaction
-        let tx_request = WithOtherFields::new(tx_request);
-        let tx_response = self.foundry_provider.send_transaction(tx_request).await.map_err(|e| {
-            let result = json!({
+        let root = std::env::temp_dir().join(format!("foundry-mcp-verify-{}-{}", address, std::process::id()));
+        let artifact = verify::compile(&source, &root).await;
+        let _ = std::fs::remove_dir_all(&root);
+
+        let mut result = verify::diff(&deployed, &artifact?);
+        result["success"] = json!(true);
+        result["address"] = json!(address.to_string());
+        result["source"] = json!(source.origin);
+        result["contract"] = json!(format!("{}:{}", source.path, source.name));
+        result["compiler_version"] = json!(source.compiler_version);
+        Ok(result)
+    }
+
+    #[tool(description = "Describe an NFT: reads tokenURI (ERC-721) or uri (ERC-1155), fetches the metadata through IPFS_GATEWAY for ipfs:// URIs (or decodes inline data: URIs) and returns its name, description, traits and image URL")]
+    pub async fn nft_metadata(
+        &self,
+        Parameters(request): Parameters<NftMetadataRequest>,
+    ) -> String {
+        let result = self.read_nft_metadata(&request).await.unwrap_or_else(|error| {
+            json!({
                 "success": false,
-                "error": format!("Failed to send transaction: {}", e),
-                "from": request.from,
-                "to": request.to
-            });
-            return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
-        }).unwrap();
-        
-        tracing::debug!("Transaction sent with hash: {}", tx_response.tx_hash());
-        
-        let result = json!({
+                "error": error,
+                "contract": request.contract,
+                "token_id": request.token_id
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn read_nft_metadata(&self, request: &NftMetadataRequest) -> Result<serde_json::Value, String> {
+        let contract = Address::from_str(&request.contract).map_err(|e| format!("Invalid contract address: {}", e))?;
+        let token_id = U256::from_str(&request.token_id).map_err(|e| format!("Invalid token id: {}", e))?;
+        nft::metadata(&self.provider(), contract, token_id).await
+    }
+
+    #[tool(description = "Build and sign a Sign-In-With-Ethereum (EIP-4361) message logging a site in as the account. Returns the message, its signature, the nonce and the expiration time to hand to the site")]
+    pub async fn sign_in_with_ethereum(
+        &self,
+        Parameters(request): Parameters<SignInWithEthereumRequest>,
+    ) -> String {
+        let result = self.siwe_signature(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address,
+                "domain": request.domain
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn siwe_signature(&self, request: &SignInWithEthereumRequest) -> Result<serde_json::Value, String> {
+        let address = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        siwe::check_domain(&request.domain)?;
+        siwe::check_uri(&request.uri)?;
+        if let Some(statement) = &request.statement {
+            siwe::check_statement(statement)?;
+        }
+        let nonce = request.nonce.clone().unwrap_or_else(siwe::new_nonce);
+        siwe::check_nonce(&nonce)?;
+        let chain_id = match request.chain_id {
+            Some(chain_id) => chain_id,
+            None => self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?,
+        };
+
+        let issued_at = chrono::Utc::now();
+        let expiration_secs = request.expiration_secs.unwrap_or(DEFAULT_SIWE_VALIDITY_SECS);
+        let expiration_time = issued_at + chrono::Duration::seconds(expiration_secs as i64);
+        let message = siwe::SiweMessage {
+            domain: request.domain.clone(),
+            address,
+            statement: request.statement.clone(),
+            uri: request.uri.clone(),
+            chain_id,
+            nonce: nonce.clone(),
+            issued_at,
+            expiration_time: Some(expiration_time),
+            not_before: None,
+            request_id: request.request_id.clone(),
+            resources: request.resources.clone().unwrap_or_default(),
+        }
+        .render();
+
+        let signature = match siwe::sign_locally(&message, address).await? {
+            Some(signature) => signature,
+            None => self
+                .provider()
+                .raw_request::<_, String>("personal_sign".into(), (format!("0x{}", hex::encode(&message)), address))
+                .await
+                .map_err(|e| format!("Failed to sign the message as {}: {}", address, e))?,
+        };
+
+        Ok(json!({
             "success": true,
-            "transaction_hash": tx_response.tx_hash(),
-            "from": request.from,
-            "to": request.to,
-            "value": request.value,
+            "address": address.to_checksum(None),
+            "domain": request.domain,
+            "chain_id": chain_id,
             "nonce": nonce,
-            "message": "Transaction sent successfully"
+            "issued_at": siwe::timestamp(&issued_at),
+            "expiration_time": siwe::timestamp(&expiration_time),
+            "message": message,
+            "signature": signature
+        }))
+    }
+
+    #[tool(description = "Score the risk of interacting with an address from 0 to 100 before the first transaction to it: source verification, deployment age, who can upgrade or own the contract, holder concentration of tokens and the scam lists in SCAM_LISTS. Returns the score, a level (low, medium, high, critical) and the reasons behind it")]
+    pub async fn risk_score(
+        &self,
+        Parameters(request): Parameters<RiskScoreRequest>,
+    ) -> String {
+        let result = self.assess_risk(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
         });
-        
-        return serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string());
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn assess_risk(&self, request: &RiskScoreRequest) -> Result<serde_json::Value, String> {
+        let address = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let chain_id = self.provider().get_chain_id().await.map_err(|e| format!("Failed to get chain id: {}", e))?;
+        // Forks keep mainnet's code, so look the contract up on mainnet
+        let chain_id = if is_local_chain(chain_id) { 1 } else { chain_id };
+        let scam_lists = self.scam_lists.get_or_init(risk::load_scam_lists).await;
+        risk::assess(&self.provider(), chain_id, address, scam_lists, unix_now()).await
+    }
+
+    #[tool(description = "Infer a best-effort ABI for a contract without verified source, from the selectors in its bytecode named via the OpenChain and 4byte signature databases, with no-argument functions probed for views and the first storage slots decoded. Pass the abi to encode_call")]
+    pub async fn infer_interface(
+        &self,
+        Parameters(request): Parameters<InferInterfaceRequest>,
+    ) -> String {
+        let result = match Address::from_str(&request.address) {
+            Ok(address) => interface::infer(&self.provider(), address).await,
+            Err(e) => Err(format!("Invalid address: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "ABI-encode a function call into transaction data for send_transaction, and with `to` set, call it read-only and decode the result")]
+    pub async fn encode_call(
+        &self,
+        Parameters(request): Parameters<EncodeCallRequest>,
+    ) -> String {
+        let result = self.encoded_call(&request).await.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "function": request.function
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn encoded_call(&self, request: &EncodeCallRequest) -> Result<serde_json::Value, String> {
+        let function = interface::function(request.abi.as_deref(), &request.function)?;
+        let data = interface::encode(&function, &request.args)?;
+        let mut result = json!({
+            "success": true,
+            "function": function.signature(),
+            "data": data.to_string()
+        });
+
+        if let Some(to) = &request.to {
+            let to = Address::from_str(to).map_err(|e| format!("Invalid address: {}", e))?;
+            let tx_request = WithOtherFields::new(TransactionRequest::default().to(to).input(data.into()));
+            let output = self.provider().call(tx_request).await.map_err(|e| format!("Call reverted: {}", e))?;
+            result["output"] = json!(output.to_string());
+            // Inferred ABIs only know the outputs of probed getters
+            if let Ok(values) = function.abi_decode_output(&output) {
+                result["decoded"] = serde_json::Value::Array(values.iter().map(interface::to_json).collect());
+            }
+            if !interface::is_view(&function) {
+                result["note"] = json!("The function may change state, this call did not send a transaction");
+            }
+        }
+        Ok(result)
+    }
+
+    #[tool(description = "Find every event matching a signature (and optionally a contract and indexed values) over a block range of any size, fetched in chunks with progress updates, deduplicated across reorgs and decoded")]
+    pub async fn scan_events(
+        &self,
+        Parameters(request): Parameters<ScanEventsRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> String {
+        let progress = Progress {
+            token: context.meta.get_progress_token(),
+            peer: context.peer,
+        };
+        let result = match self.event_scan(&request) {
+            Ok(event_scan) => scan::scan(&self.provider(), &event_scan, &progress).await,
+            Err(error) => Err(error),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "event": request.event
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    fn event_scan(&self, request: &ScanEventsRequest) -> Result<Scan, String> {
+        let event = scan::parse_event(&request.event)?;
+        let address = match &request.address {
+            Some(address) => Some(Address::from_str(address).map_err(|e| format!("Invalid address: {}", e))?),
+            None => None,
+        };
+        Ok(Scan {
+            topics: scan::topics(&event, &request.filters)?,
+            event,
+            address,
+            from_block: request.from_block,
+            to_block: request.to_block.unwrap_or(u64::MAX),
+            max_events: request.max_events.unwrap_or(scan::DEFAULT_MAX_EVENTS).max(1),
+        })
+    }
+
+    #[tool(description = "Replace the runtime bytecode at an address on a local anvil fork (anvil_setCode), e.g. to swap an oracle for a mock when testing an integration")]
+    pub async fn set_code(
+        &self,
+        Parameters(request): Parameters<SetCodeRequest>,
+    ) -> String {
+        let result = match (Address::from_str(&request.address), Bytes::from_str(&request.code)) {
+            (Ok(address), Ok(code)) => match cheatcodes::supported(&self.provider()).await {
+                Ok(()) => cheatcodes::set_code(&self.provider(), address, code).await,
+                Err(error) => Err(error),
+            },
+            (Err(e), _) => Err(format!("Invalid address: {}", e)),
+            (_, Err(e)) => Err(format!("Invalid code: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Overwrite a storage slot of a contract on a local anvil fork (anvil_setStorageAt), e.g. to give an account a token balance for testing. Set mapping_key to write mapping[mapping_key] of the mapping declared at slot")]
+    pub async fn set_storage_at(
+        &self,
+        Parameters(request): Parameters<SetStorageAtRequest>,
+    ) -> String {
+        let result = match self.storage_write(&request) {
+            Ok((address, slot, value)) => match cheatcodes::supported(&self.provider()).await {
+                Ok(()) => cheatcodes::set_storage_at(&self.provider(), address, slot, value).await,
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    fn storage_write(&self, request: &SetStorageAtRequest) -> Result<(Address, B256, B256), String> {
+        let address = Address::from_str(&request.address).map_err(|e| format!("Invalid address: {}", e))?;
+        let slot = parse_slot(&request.slot)?;
+        let slot = match &request.mapping_key {
+            // Addresses are left-padded like any other value type
+            Some(key) => match Address::from_str(key) {
+                Ok(key) => cheatcodes::mapping_slot(key.into_word(), slot),
+                Err(_) => cheatcodes::mapping_slot(parse_slot(key)?, slot),
+            },
+            None => slot,
+        };
+        let value = parse_slot(&request.value).map_err(|_| format!("Invalid value {}, use a decimal or 0x hex number", request.value))?;
+        Ok((address, slot, value))
+    }
+
+    #[tool(description = "Set the nonce of an account's next transaction on a local anvil node (anvil_setNonce), e.g. to unstick it after experiments")]
+    pub async fn set_nonce(
+        &self,
+        Parameters(request): Parameters<SetNonceRequest>,
+    ) -> String {
+        let result = match Address::from_str(&request.address) {
+            Ok(address) => match cheatcodes::supported(&self.provider()).await {
+                Ok(()) => cheatcodes::set_nonce(&self.provider(), address, request.nonce).await,
+                Err(error) => Err(error),
+            },
+            Err(e) => Err(format!("Invalid address: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "address": request.address
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Reset a local anvil node (anvil_reset), dropping every change made to it: back to genesis or the original fork block, or onto a new fork of fork_url at block_number")]
+    pub async fn reset_fork(
+        &self,
+        Parameters(request): Parameters<ResetForkRequest>,
+    ) -> String {
+        let result = match cheatcodes::supported(&self.provider()).await {
+            Ok(()) => cheatcodes::reset(&self.provider(), request.fork_url.as_deref(), request.block_number).await,
+            Err(error) => Err(error),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Snapshot the whole state of a local anvil node (evm_snapshot), for evm_revert to go back to")]
+    pub async fn evm_snapshot(&self) -> String {
+        let result = match cheatcodes::supported(&self.provider()).await {
+            Ok(()) => cheatcodes::snapshot(&self.provider()).await,
+            Err(error) => Err(error),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Revert a local anvil node to a snapshot taken with evm_snapshot (evm_revert), undoing everything since")]
+    pub async fn evm_revert(
+        &self,
+        Parameters(request): Parameters<EvmRevertRequest>,
+    ) -> String {
+        let result = match U256::from_str(&request.snapshot_id) {
+            Ok(snapshot_id) => match cheatcodes::supported(&self.provider()).await {
+                Ok(()) => cheatcodes::revert(&self.provider(), snapshot_id).await,
+                Err(error) => Err(error),
+            },
+            Err(e) => Err(format!("Invalid snapshot id: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "snapshot_id": request.snapshot_id
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    #[tool(description = "Get the status of a sent transaction: pending, confirmed or failed (reverted) with its block, or not_found when the node doesn't know it")]
+    pub async fn transaction_status(
+        &self,
+        Parameters(request): Parameters<TransactionStatusRequest>,
+    ) -> String {
+        let result = match B256::from_str(&request.hash) {
+            Ok(hash) => self.transaction_status_of(hash).await,
+            Err(e) => Err(format!("Invalid transaction hash: {}", e)),
+        };
+
+        let result = result.unwrap_or_else(|error| {
+            json!({
+                "success": false,
+                "error": error,
+                "hash": request.hash
+            })
+        });
+        serde_json::to_string(&result).unwrap_or_else(|_| "Error serializing response".to_string())
+    }
+
+    async fn transaction_status_of(&self, hash: B256) -> Result<serde_json::Value, String> {
+        let receipt: serde_json::Value = self.provider()
+            .raw_request("eth_getTransactionReceipt".into(), (hash,))
+            .await
+            .map_err(|e| format!("Failed to get the receipt: {}", e))?;
+        if !receipt.is_null() {
+            let quantity = |name: &str| {
+                receipt.get(name).and_then(|value| value.as_str()).and_then(|value| U256::from_str(value).ok()).unwrap_or_default()
+            };
+            return Ok(json!({
+                "success": true,
+                "hash": hash.to_string(),
+                "status": if quantity("status").is_zero() { "failed" } else { "confirmed" },
+                "block_number": quantity("blockNumber").saturating_to::<u64>()
+            }));
+        }
+
+        // No receipt yet: still in the mempool, or dropped (replaced, evicted) if the node doesn't know it
+        let tx: serde_json::Value = self.provider()
+            .raw_request("eth_getTransactionByHash".into(), (hash,))
+            .await
+            .map_err(|e| format!("Failed to get the transaction: {}", e))?;
+        // Private transactions stay invisible to the node until a builder includes them
+        let private = self.private_transactions.read().unwrap().contains(&hash);
+        Ok(json!({
+            "success": true,
+            "hash": hash.to_string(),
+            "status": if tx.is_null() && !private { "not_found" } else { "pending" },
+            "private": private
+        }))
     }
 
     #[tool(description = "Check the contract code at an address")]
@@ -275,7 +2996,7 @@ impl FoundryService {
         &self,
         Parameters(request): Parameters<GetContractCodeRequest>,
     ) -> String {
-        let code = self.foundry_provider.get_code_at(Address::from_str(&request.address).unwrap()).await.unwrap();
+        let code = self.provider().get_code_at(Address::from_str(&request.address).unwrap()).await.unwrap();
         if code.is_empty() {
             let result = json!({
                 "success": false,
@@ -302,7 +3023,7 @@ impl FoundryService {
         let token_address = Address::from_str(&request.token_address).unwrap();
         let account_address = Address::from_str(&request.address).unwrap();
 
-        let erc20 = ERC20::new(token_address, self.foundry_provider.clone());
+        let erc20 = ERC20::new(token_address, self.provider());
 
         // FIXME: error handling
         let balance = erc20.balanceOf(account_address).call().await.unwrap().to_string();
@@ -321,7 +3042,6 @@ impl FoundryService {
     }
 }
 
-#[tool_handler]
 impl ServerHandler for FoundryService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
@@ -330,4 +3050,25 @@ impl ServerHandler for FoundryService {
             ..Default::default()
         }
     }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        self.tool_router.call(ToolCallContext::new(self, request, context)).await
+    }
+
+    // Cheatcodes are only offered while the session is connected to an anvil node
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        let mut tools = self.tool_router.list_all();
+        if cheatcodes::supported(&self.provider()).await.is_err() {
+            tools.retain(|tool| !cheatcodes::CHEATCODE_TOOLS.contains(&tool.name.as_ref()));
+        }
+        Ok(ListToolsResult { tools, next_cursor: None })
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,255 @@
+use alloy::dyn_abi::{DynSolType, DynSolValue, JsonAbiExt};
+use alloy::json_abi::{Function, JsonAbi, StateMutability};
+use alloy::serde::WithOtherFields;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use alloy_rpc_types::eth::TransactionRequest;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::standards;
+
+const OPENCHAIN_URL: &str = "https://api.openchain.xyz/signature-database/v1/lookup";
+const FOURBYTE_URL: &str = "https://www.4byte.directory/api/v1/signatures/";
+
+// Storage slots read from the start of the layout, where simple contracts keep owners and config
+const PROBED_SLOTS: u64 = 10;
+
+const PUSH1: u8 = 0x60;
+const PUSH4: u8 = 0x63;
+const PUSH32: u8 = 0x7f;
+const EQ: u8 = 0x14;
+const GT: u8 = 0x11;
+const LT: u8 = 0x10;
+const DUP2: u8 = 0x81;
+
+// Function selectors compared against in the dispatcher: `PUSH4 selector EQ`, `PUSH4 selector
+// DUP2 EQ` (via-IR) and the `GT`/`LT` splits of large binary-search dispatchers
+pub fn selectors(code: &[u8]) -> Vec<[u8; 4]> {
+    let mut found = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if opcode == PUSH4 && pc + 5 < code.len() {
+            let selector: [u8; 4] = code[pc + 1..pc + 5].try_into().unwrap_or_default();
+            let next = &code[pc + 5..];
+            let compared = matches!(next.first(), Some(&EQ) | Some(&GT) | Some(&LT))
+                || (next.first() == Some(&DUP2) && next.get(1) == Some(&EQ));
+            if compared && selector != [0; 4] && selector != [0xff; 4] && !found.contains(&selector) {
+                found.push(selector);
+            }
+        }
+        // Skip push data, it's not code
+        pc += if (PUSH1..=PUSH32).contains(&opcode) { (opcode - PUSH1) as usize + 2 } else { 1 };
+    }
+    found
+}
+
+// Text signatures of the selectors, from the OpenChain database with 4byte.directory for misses.
+// Colliding signatures are common, the one that was registered first is usually the real one.
+pub async fn lookup(selectors: &[[u8; 4]]) -> BTreeMap<[u8; 4], String> {
+    let http = reqwest::Client::new();
+    let mut signatures = BTreeMap::new();
+    let hex_selectors: Vec<String> = selectors.iter().map(|selector| format!("0x{}", hex::encode(selector))).collect();
+
+    let query = [("function", hex_selectors.join(",")), ("filter", "true".to_string())];
+    let response: Option<Value> = match http.get(OPENCHAIN_URL).query(&query).send().await {
+        Ok(response) => response.json().await.ok(),
+        Err(_) => None,
+    };
+    for (selector, hex_selector) in selectors.iter().zip(&hex_selectors) {
+        let signature = response
+            .as_ref()
+            .and_then(|response| response.pointer(&format!("/result/function/{}/0/name", hex_selector)))
+            .and_then(|name| name.as_str());
+        if let Some(signature) = signature {
+            signatures.insert(*selector, signature.to_string());
+        }
+    }
+
+    for (selector, hex_selector) in selectors.iter().zip(&hex_selectors) {
+        if signatures.contains_key(selector) {
+            continue;
+        }
+        let query = [("hex_signature", hex_selector.as_str()), ("ordering", "created_at")];
+        let Ok(response) = http.get(FOURBYTE_URL).query(&query).send().await else {
+            continue;
+        };
+        let signature = response.json::<Value>().await.ok().and_then(|response| {
+            response.pointer("/results/0/text_signature").and_then(|name| name.as_str()).map(|name| name.to_string())
+        });
+        if let Some(signature) = signature {
+            signatures.insert(*selector, signature);
+        }
+    }
+    signatures
+}
+
+// Best guess at the type of a 32 byte word
+fn word_type(word: &[u8]) -> &'static str {
+    let value = U256::from_be_slice(word);
+    if value <= U256::from(1) {
+        "bool"
+    } else if word[..12].iter().all(|byte| *byte == 0) && word[12..16].iter().any(|byte| *byte != 0) {
+        "address"
+    } else {
+        "uint256"
+    }
+}
+
+// Output type of a getter from what it returned
+fn output_type(data: &[u8]) -> Option<&'static str> {
+    match data.len() {
+        0 => None,
+        32 => Some(word_type(data)),
+        // A dynamic value starts with its offset, 0x20 for a single string or bytes
+        len if len >= 64 && U256::from_be_slice(&data[..32]) == U256::from(32) => Some("string"),
+        _ => None,
+    }
+}
+
+// Call a getter taking no arguments, to tell views from state-changing functions and guess what it returns
+async fn probe_getter(provider: &RootProvider<AnyNetwork>, address: Address, selector: [u8; 4]) -> Option<Bytes> {
+    let tx = TransactionRequest::default().to(address).input(Bytes::from(selector.to_vec()).into());
+    provider.call(WithOtherFields::new(tx)).await.ok()
+}
+
+// Non-empty storage slots at the start of the layout, with a guess at what they hold
+async fn probe_storage(provider: &RootProvider<AnyNetwork>, address: Address) -> Vec<Value> {
+    let mut slots = vec![];
+    for slot in 0..PROBED_SLOTS {
+        let Ok(value) = provider.get_storage_at(address, U256::from(slot)).await else {
+            continue;
+        };
+        if value.is_zero() {
+            continue;
+        }
+        let word = B256::from(value);
+        // Short strings are stored in place with twice their length in the last byte
+        let length = word[31] as usize / 2;
+        let short_string = word[31] % 2 == 0
+            && (1..32).contains(&length)
+            && word[length..31].iter().all(|byte| *byte == 0)
+            && std::str::from_utf8(&word[..length]).is_ok_and(|text| text.chars().all(|c| !c.is_control()));
+        let (guess, decoded) = if short_string {
+            ("string", json!(String::from_utf8_lossy(&word[..length])))
+        } else {
+            match word_type(&word[..]) {
+                "address" => ("address", json!(Address::from_word(word).to_string())),
+                guess => (guess, json!(value.to_string())),
+            }
+        };
+        slots.push(json!({ "slot": slot, "value": word.to_string(), "type": guess, "decoded": decoded }));
+    }
+    slots
+}
+
+// Best-effort ABI of a contract without verified source: the selectors its dispatcher compares
+// against, named from public signature databases, with no-argument functions called to find views
+pub async fn infer(provider: &RootProvider<AnyNetwork>, address: Address) -> Result<Value, String> {
+    let mut code = provider.get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    if code.is_empty() {
+        return Err("No contract code found at address".to_string());
+    }
+    // A proxy's own dispatcher only forwards calls, the interface is the implementation's
+    let proxy = standards::proxy(provider, address, &code).await;
+    let implementation = proxy
+        .as_ref()
+        .and_then(|proxy| proxy["implementation"].as_str())
+        .and_then(|implementation| Address::from_str(implementation).ok());
+    if let Some(implementation) = implementation {
+        code = provider.get_code_at(implementation).await.map_err(|e| format!("Failed to get the implementation code: {}", e))?;
+    }
+
+    let selectors = selectors(&code);
+    let signatures = lookup(&selectors).await;
+
+    let mut abi = vec![];
+    let mut unresolved = vec![];
+    for selector in &selectors {
+        let Some(function) = signatures.get(selector).and_then(|signature| Function::parse(signature).ok()) else {
+            unresolved.push(format!("0x{}", hex::encode(selector)));
+            continue;
+        };
+        let mut entry = serde_json::to_value(&function).unwrap_or_default();
+        // Probed through the address itself so proxies answer with their own state
+        let returned = if function.inputs.is_empty() { probe_getter(provider, address, *selector).await } else { None };
+        match returned {
+            Some(data) => {
+                entry["stateMutability"] = json!("view");
+                entry["outputs"] = match output_type(&data) {
+                    Some(output) => json!([{ "name": "", "type": output, "internalType": output }]),
+                    None => json!([]),
+                };
+            }
+            None => entry["stateMutability"] = json!("nonpayable"),
+        }
+        abi.push(entry);
+    }
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "proxy": proxy,
+        "abi": abi,
+        "unresolved_selectors": unresolved,
+        "storage": probe_storage(provider, address).await,
+        "note": "Inferred from bytecode: names come from public signature databases and output types are guesses"
+    }))
+}
+
+// The function to encode: a full signature like "transfer(address,uint256)", or a name looked up in `abi`
+pub fn function(abi: Option<&str>, function: &str) -> Result<Function, String> {
+    if function.contains('(') {
+        return Function::parse(function).map_err(|e| format!("Invalid function signature {}: {}", function, e));
+    }
+    let abi = abi.ok_or("Pass a full function signature, or an ABI to look the name up in")?;
+    // Accept both a bare ABI array and an infer_interface response
+    let abi: Value = serde_json::from_str(abi).map_err(|e| format!("Invalid ABI: {}", e))?;
+    let abi: JsonAbi = serde_json::from_value(abi.get("abi").cloned().unwrap_or(abi)).map_err(|e| format!("Invalid ABI: {}", e))?;
+    let overloads = abi.function(function).ok_or_else(|| format!("No function {} in the ABI", function))?;
+    match overloads.as_slice() {
+        [function] => Ok(function.clone()),
+        _ => Err(format!("{} is overloaded, pass its full signature", function)),
+    }
+}
+
+pub fn encode(function: &Function, args: &[String]) -> Result<Bytes, String> {
+    if args.len() != function.inputs.len() {
+        return Err(format!("{} takes {} arguments, got {}", function.signature(), function.inputs.len(), args.len()));
+    }
+    let values = function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(input, arg)| {
+            let ty = DynSolType::parse(&input.selector_type()).map_err(|e| format!("Unsupported type {}: {}", input.ty, e))?;
+            ty.coerce_str(arg).map_err(|e| format!("Invalid {} argument {}: {}", input.ty, arg, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    function.abi_encode_input(&values).map(Bytes::from).map_err(|e| format!("Failed to encode the call: {}", e))
+}
+
+// Decoded return values as JSON, numbers as strings so they survive large values
+pub fn to_json(value: &DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(value) => json!(value),
+        DynSolValue::Int(value, _) => json!(value.to_string()),
+        DynSolValue::Uint(value, _) => json!(value.to_string()),
+        DynSolValue::Address(value) => json!(value.to_string()),
+        DynSolValue::String(value) => json!(value),
+        DynSolValue::Bytes(value) => json!(format!("0x{}", hex::encode(value))),
+        DynSolValue::FixedBytes(value, size) => json!(format!("0x{}", hex::encode(&value[..*size]))),
+        DynSolValue::Function(value) => json!(value.to_string()),
+        // Arrays, tuples and structs
+        sequence => {
+            let values = sequence.as_fixed_seq().or_else(|| sequence.as_array()).unwrap_or_default();
+            Value::Array(values.iter().map(to_json).collect())
+        }
+    }
+}
+
+pub fn is_view(function: &Function) -> bool {
+    matches!(function.state_mutability, StateMutability::View | StateMutability::Pure)
+}
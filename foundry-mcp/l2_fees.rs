@@ -0,0 +1,108 @@
+use alloy::sol;
+use alloy_primitives::{Address, U256};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use alloy_rpc_types::eth::TransactionRequest;
+use serde_json::json;
+use std::str::FromStr;
+
+sol! {
+    // OP Stack predeploy pricing the L1 data of a transaction
+    #[sol(rpc)]
+    contract GasPriceOracle {
+        function getL1Fee(bytes memory data) external view returns (uint256);
+        function getL1FeeUpperBound(uint256 unsignedTxSize) external view returns (uint256);
+    }
+
+    // Arbitrum precompile splitting a gas estimate into its L2 and L1 parts
+    #[sol(rpc)]
+    contract NodeInterface {
+        function gasEstimateComponents(address to, bool contractCreation, bytes calldata data) external payable returns (uint64 gasEstimate, uint64 gasEstimateForL1, uint256 baseFee, uint256 l1BaseFeeEstimate);
+    }
+}
+
+const GAS_PRICE_ORACLE: &str = "0x420000000000000000000000000000000000000F";
+const NODE_INTERFACE: &str = "0x00000000000000000000000000000000000000C8";
+
+// Envelope of a signed EIP-1559 transaction around its calldata (nonce, fees, gas, to, value,
+// signature), the oracle prices the whole serialized transaction
+const TX_ENVELOPE_BYTES: usize = 68;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollup {
+    OpStack,
+    Arbitrum,
+}
+
+impl Rollup {
+    fn name(self) -> &'static str {
+        match self {
+            Rollup::OpStack => "op-stack",
+            Rollup::Arbitrum => "arbitrum",
+        }
+    }
+}
+
+// Rollups whose fees have an L1 component the node's gas estimate doesn't show
+pub fn rollup(chain_id: u64) -> Option<Rollup> {
+    match chain_id {
+        // Optimism, Base and their Sepolia testnets
+        10 | 8453 | 11155420 | 84532 => Some(Rollup::OpStack),
+        // Arbitrum One, Nova and Arbitrum Sepolia
+        42161 | 42170 | 421614 => Some(Rollup::Arbitrum),
+        _ => None,
+    }
+}
+
+// Fees of a transaction on a rollup. `gas` and `gas_price` are the node's L2 estimate, which
+// OP Stack chains top up with the L1 data fee and Arbitrum replaces with its own split.
+pub async fn estimate(
+    provider: &RootProvider<AnyNetwork>,
+    rollup: Rollup,
+    tx: &TransactionRequest,
+    gas: u64,
+    gas_price: u128,
+) -> Result<serde_json::Value, String> {
+    let to = tx.to.and_then(|to| to.to().copied());
+    let data = tx.input.input().cloned().unwrap_or_default();
+
+    let (l2_gas, l2_gas_price, l1_data_fee) = match rollup {
+        Rollup::OpStack => {
+            let oracle = GasPriceOracle::new(Address::from_str(GAS_PRICE_ORACLE).unwrap(), provider.clone());
+            // Chains before the Fjord upgrade only have getL1Fee
+            let l1_fee = match oracle.getL1FeeUpperBound(U256::from(data.len() + TX_ENVELOPE_BYTES)).call().await {
+                Ok(fee) => fee,
+                Err(_) => oracle
+                    .getL1Fee(data)
+                    .call()
+                    .await
+                    .map_err(|e| format!("Failed to read the L1 fee from the gas price oracle: {}", e))?,
+            };
+            (gas, gas_price, l1_fee)
+        }
+        Rollup::Arbitrum => {
+            let node = NodeInterface::new(Address::from_str(NODE_INTERFACE).unwrap(), provider.clone());
+            let components = node
+                .gasEstimateComponents(to.unwrap_or_default(), to.is_none(), data)
+                .from(tx.from.unwrap_or_default())
+                .value(tx.value.unwrap_or_default())
+                .call()
+                .await
+                .map_err(|e| format!("Failed to estimate Arbitrum gas components: {}", e))?;
+            // The L1 part is charged as extra L2 gas at the L2 base fee
+            let base_fee = u128::try_from(components.baseFee).unwrap_or(u128::MAX);
+            let l2_gas = components.gasEstimate.saturating_sub(components.gasEstimateForL1);
+            let l1_fee = U256::from(components.gasEstimateForL1) * components.baseFee;
+            (l2_gas, base_fee, l1_fee)
+        }
+    };
+
+    let l2_execution_fee = U256::from(l2_gas) * U256::from(l2_gas_price);
+    Ok(json!({
+        "rollup": rollup.name(),
+        "l2_gas": l2_gas,
+        "l2_gas_price": l2_gas_price.to_string(),
+        "l2_execution_fee": l2_execution_fee.to_string(),
+        "l1_data_fee": l1_data_fee.to_string(),
+        "total_fee": (l2_execution_fee + l1_data_fee).to_string()
+    }))
+}
@@ -1,11 +1,64 @@
 use std::error::Error;
+use std::net::SocketAddr;
+mod bridge;
+mod cheatcodes;
+mod explain;
 mod foundry_service;
+mod interface;
+mod l2_fees;
+mod mev;
+mod multicall;
+mod nft;
+mod permit;
+mod portfolio;
+mod positions;
+mod priority_fee;
+mod private_tx;
+mod risk;
+mod safe;
+mod scan;
+mod siwe;
+mod staking;
+mod standards;
+mod token_list;
+mod twap;
+mod unsigned;
+mod user_op;
+mod verify;
 use foundry_service::FoundryService;
-use rmcp::{ServiceExt, transport::stdio};
+use rmcp::{
+    ServiceExt,
+    transport::{
+        sse_server::SseServer,
+        stdio,
+        streamable_http_server::{session::local::LocalSessionManager, StreamableHttpService},
+    },
+};
 use tracing_subscriber;
 
+// How the server is reached, picked from the command line:
+//   foundry-mcp                     stdio, spawned by the agent
+//   foundry-mcp --http 0.0.0.0:8080 streamable HTTP at /mcp, shared by several agents
+//   foundry-mcp --sse 0.0.0.0:8080  HTTP with server-sent events at /sse
+enum Transport {
+    Stdio,
+    Http(SocketAddr),
+    Sse(SocketAddr),
+}
+
+fn parse_args() -> Result<Transport, Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => Ok(Transport::Stdio),
+        [flag, addr] if flag == "--http" => Ok(Transport::Http(addr.parse()?)),
+        [flag, addr] if flag == "--sse" => Ok(Transport::Sse(addr.parse()?)),
+        _ => Err("usage: foundry-mcp [--http <addr> | --sse <addr>]".into()),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let transport = parse_args()?;
     let foundry_service = FoundryService::new().await?;
     //let io = (tokio::io::stdin(), tokio::io::stdout());
 
@@ -19,12 +72,40 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     tracing::info!("Starting MCP server");
 
-    // Create an instance of our counter router
-    let service = foundry_service.serve(stdio()).await.inspect_err(|e| {
-        tracing::error!("serving error: {:?}", e);
-    })?;
+    match transport {
+        Transport::Stdio => {
+            // Create an instance of our counter router
+            let service = foundry_service.serve(stdio()).await.inspect_err(|e| {
+                tracing::error!("serving error: {:?}", e);
+            })?;
+
+            service.waiting().await?;
+        }
+        Transport::Http(addr) => {
+            // Every session gets its own copy of the service, starting on the default network
+            let service = StreamableHttpService::new(
+                move || Ok(foundry_service.session()),
+                LocalSessionManager::default().into(),
+                Default::default(),
+            );
+            let router = axum::Router::new().nest_service("/mcp", service);
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::warn!("Serving MCP over streamable HTTP at http://{}/mcp", addr);
+
+            axum::serve(listener, router)
+                .with_graceful_shutdown(async {
+                    let _ = tokio::signal::ctrl_c().await;
+                })
+                .await?;
+        }
+        Transport::Sse(addr) => {
+            let cancel = SseServer::serve(addr).await?.with_service(move || foundry_service.session());
+            tracing::warn!("Serving MCP over SSE at http://{}/sse", addr);
 
-    service.waiting().await?;
+            tokio::signal::ctrl_c().await?;
+            cancel.cancel();
+        }
+    }
     Ok(())
 
-} 
\ No newline at end of file
+}
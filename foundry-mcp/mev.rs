@@ -0,0 +1,212 @@
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy_primitives::{aliases::U24, Address, U256};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use serde_json::json;
+use std::str::FromStr;
+
+sol! {
+    // Uniswap V2 routers and their forks (SushiSwap, PancakeSwap, ...) share this interface
+    #[sol(rpc)]
+    contract UniswapV2Router {
+        function swapExactETHForTokens(uint256 amountOutMin, address[] calldata path, address to, uint256 deadline) external payable returns (uint256[] memory amounts);
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] calldata path, address to, uint256 deadline) external returns (uint256[] memory amounts);
+        function swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] calldata path, address to, uint256 deadline) external returns (uint256[] memory amounts);
+        function getAmountsOut(uint256 amountIn, address[] calldata path) external view returns (uint256[] memory amounts);
+        function WETH() external view returns (address);
+    }
+
+    // Uniswap SwapRouter02
+    contract SwapRouter02 {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut);
+    }
+
+    #[sol(rpc)]
+    contract QuoterV2 {
+        struct QuoteExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint256 amountIn;
+            uint24 fee;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function quoteExactInputSingle(QuoteExactInputSingleParams memory params) external returns (uint256 amountOut, uint160 sqrtPriceX96After, uint32 initializedTicksCrossed, uint256 gasEstimate);
+    }
+}
+
+// Share of the trade the price impact is measured against: a quote this much smaller moves the
+// pool price by next to nothing
+const MARGINAL_DIVISOR: u64 = 1000;
+
+// Uniswap V3 QuoterV2 of a chain, forks use their parent chain's
+fn quoter(chain_id: u64) -> Option<Address> {
+    let address = match chain_id {
+        1 | 10 | 137 | 42161 => "0x61fFE014bA17989E743c5F6cB21bF9697530B21e",
+        8453 => "0x3d4e44Eb1374240CE5F1B871ab261CD16335B76a",
+        11155111 => "0xEd1f6473345F45b75F8179591dd5bA1888cf2FB3",
+        _ => return None,
+    };
+    Address::from_str(address).ok()
+}
+
+// Canonical WETH of a chain, to price V3 swaps in ETH
+fn weth(chain_id: u64) -> Option<Address> {
+    let address = match chain_id {
+        1 => "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+        10 | 8453 => "0x4200000000000000000000000000000000000006",
+        42161 => "0x82aF49447D8a07e3bd95FD0d56f35241523fBab1",
+        11155111 => "0xfFf9976782d46CC05630D1f6eBAB18b2324d6B14",
+        _ => return None,
+    };
+    Address::from_str(address).ok()
+}
+
+// How a swap is quoted again: along a V2 router's path, or through a V3 pool's fee tier
+enum Route {
+    V2 { router: Address, path: Vec<Address> },
+    V3 { token_in: Address, token_out: Address, fee: U24 },
+}
+
+struct Swap {
+    protocol: &'static str,
+    route: Route,
+    amount_in: U256,
+    min_amount_out: U256,
+}
+
+fn decode_swap(to: Address, value: U256, data: &[u8]) -> Option<Swap> {
+    let v2 = |path: Vec<Address>, amount_in, min_amount_out| Swap {
+        protocol: "uniswap_v2",
+        route: Route::V2 { router: to, path },
+        amount_in,
+        min_amount_out,
+    };
+    if let Ok(call) = UniswapV2Router::swapExactETHForTokensCall::abi_decode(data) {
+        return Some(v2(call.path, value, call.amountOutMin));
+    }
+    if let Ok(call) = UniswapV2Router::swapExactTokensForTokensCall::abi_decode(data) {
+        return Some(v2(call.path, call.amountIn, call.amountOutMin));
+    }
+    if let Ok(call) = UniswapV2Router::swapExactTokensForETHCall::abi_decode(data) {
+        return Some(v2(call.path, call.amountIn, call.amountOutMin));
+    }
+    if let Ok(call) = SwapRouter02::exactInputSingleCall::abi_decode(data) {
+        let params = call.params;
+        return Some(Swap {
+            protocol: "uniswap_v3",
+            route: Route::V3 { token_in: params.tokenIn, token_out: params.tokenOut, fee: params.fee },
+            amount_in: params.amountIn,
+            min_amount_out: params.amountOutMinimum,
+        });
+    }
+    None
+}
+
+// What `amount_in` of the input token buys along the swap's route right now
+async fn quote(provider: &RootProvider<AnyNetwork>, chain_id: u64, route: &Route, amount_in: U256) -> Result<U256, String> {
+    match route {
+        Route::V2 { router, path } => {
+            let amounts = UniswapV2Router::new(*router, provider)
+                .getAmountsOut(amount_in, path.clone())
+                .call()
+                .await
+                .map_err(|e| format!("Failed to quote the swap: {}", e))?;
+            amounts.last().copied().ok_or_else(|| "The router quoted nothing".to_string())
+        }
+        Route::V3 { token_in, token_out, fee } => {
+            let quoter = quoter(chain_id).ok_or_else(|| format!("No Uniswap V3 quoter known on chain {}", chain_id))?;
+            let params = QuoterV2::QuoteExactInputSingleParams {
+                tokenIn: *token_in,
+                tokenOut: *token_out,
+                amountIn: amount_in,
+                fee: *fee,
+                sqrtPriceLimitX96: Default::default(),
+            };
+            let quote = QuoterV2::new(quoter, provider)
+                .quoteExactInputSingle(params)
+                .call()
+                .await
+                .map_err(|e| format!("Failed to quote the swap: {}", e))?;
+            Ok(quote.amountOut)
+        }
+    }
+}
+
+// The swap's input in wei of ETH, `None` when it can't be priced
+async fn value_in_eth(provider: &RootProvider<AnyNetwork>, chain_id: u64, swap: &Swap) -> Option<U256> {
+    let (weth, token_in) = match &swap.route {
+        Route::V2 { router, path } => (UniswapV2Router::new(*router, provider).WETH().call().await.ok()?, *path.first()?),
+        Route::V3 { token_in, .. } => (weth(chain_id)?, *token_in),
+    };
+    if token_in == weth {
+        return Some(swap.amount_in);
+    }
+    let route = match &swap.route {
+        Route::V2 { router, .. } => Route::V2 { router: *router, path: vec![token_in, weth] },
+        Route::V3 { fee, .. } => Route::V3 { token_in, token_out: weth, fee: *fee },
+    };
+    quote(provider, chain_id, &route, swap.amount_in).await.ok()
+}
+
+// `part` short of `whole` in basis points, 0 when it isn't short
+fn shortfall_bps(part: U256, whole: U256) -> u64 {
+    if whole.is_zero() || part >= whole {
+        return 0;
+    }
+    ((whole - part) * U256::from(10_000) / whole).saturating_to()
+}
+
+// How much a sandwich could take from a transaction sent to the public mempool: for swaps the
+// slippage tolerance (what a front-runner may push the price by), the price impact (how shallow
+// the pool is for the trade) and the size of the trade in ETH. Other transactions only report
+// their value, nothing can be extracted from them by reordering.
+pub async fn exposure(
+    provider: &RootProvider<AnyNetwork>,
+    chain_id: u64,
+    to: Address,
+    value: U256,
+    data: &[u8],
+) -> Result<serde_json::Value, String> {
+    let Some(swap) = decode_swap(to, value, data) else {
+        return Ok(json!({ "kind": if data.is_empty() { "transfer" } else { "call" }, "value_wei": value.to_string() }));
+    };
+
+    let expected = quote(provider, chain_id, &swap.route, swap.amount_in).await?;
+    // The rate of a tiny trade is the pool price before the swap moves it
+    let marginal_in = swap.amount_in / U256::from(MARGINAL_DIVISOR);
+    let price_impact_bps = if marginal_in.is_zero() {
+        None
+    } else {
+        quote(provider, chain_id, &swap.route, marginal_in)
+            .await
+            .ok()
+            .map(|marginal_out| shortfall_bps(expected, marginal_out * U256::from(MARGINAL_DIVISOR)))
+    };
+    let slippage_bps = shortfall_bps(swap.min_amount_out, expected);
+    let value_wei = value_in_eth(provider, chain_id, &swap).await;
+    // A sandwich can push the price up to the slippage tolerance, taking that share of the trade
+    let max_extractable_wei = value_wei.map(|value| value * U256::from(slippage_bps) / U256::from(10_000));
+
+    Ok(json!({
+        "kind": "swap",
+        "protocol": swap.protocol,
+        "amount_in": swap.amount_in.to_string(),
+        "expected_amount_out": expected.to_string(),
+        "min_amount_out": swap.min_amount_out.to_string(),
+        "slippage_bps": slippage_bps,
+        "price_impact_bps": price_impact_bps,
+        "value_wei": value_wei.map(|value| value.to_string()),
+        "max_extractable_wei": max_extractable_wei.map(|value| value.to_string())
+    }))
+}
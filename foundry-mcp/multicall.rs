@@ -0,0 +1,222 @@
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+sol! {
+    contract Multicall3 {
+        struct Call3Value {
+            address target;
+            bool allowFailure;
+            uint256 value;
+            bytes callData;
+        }
+
+        struct Call3Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3Value(Call3Value[] calldata calls) external payable returns (Call3Result[] memory returnData);
+    }
+}
+
+// Same address on every chain it is deployed to
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+// Batches bigger than this are better split, one failing call reverts them all
+pub const MAX_BATCH_CALLS: usize = 50;
+
+// Gas of a transaction's own intrinsic cost, saved for every call after the first
+const TRANSACTION_BASE_GAS: u64 = 21_000;
+
+// Calls that act on behalf of msg.sender, which inside a batch is Multicall3 and not the sender:
+// token transfers and approvals would move or approve Multicall3's (empty) balance, WETH deposits
+// would credit it
+const SENDER_BOUND_SELECTORS: &[(&str, &str)] = &[
+    ("a9059cbb", "transfer"),
+    ("23b872dd", "transferFrom"),
+    ("095ea7b3", "approve"),
+    ("a22cb465", "setApprovalForAll"),
+    ("42842e0e", "safeTransferFrom"),
+    ("b88d4fde", "safeTransferFrom"),
+    ("f242432a", "safeTransferFrom"),
+    ("2eb2c2d6", "safeBatchTransferFrom"),
+    ("d505accf", "permit"),
+    ("d0e30db0", "deposit"),
+    ("2e1a7d4d", "withdraw"),
+];
+
+// One call of a batch as the caller gives it
+pub struct BatchCall {
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub allow_failure: bool,
+}
+
+impl BatchCall {
+    pub fn parse(to: &str, value: Option<&str>, data: Option<&str>, allow_failure: bool) -> Result<Self, String> {
+        let to = Address::from_str(to).map_err(|e| format!("Invalid to address {}: {}", to, e))?;
+        let value = match value {
+            Some(value) => U256::from_str(value).map_err(|e| format!("Invalid value {}: {}", value, e))?,
+            None => U256::ZERO,
+        };
+        let data = match data {
+            Some(data) => Bytes::from_str(data).map_err(|e| format!("Invalid data for {}: {}", to, e))?,
+            None => Bytes::new(),
+        };
+        Ok(Self {
+            to,
+            value,
+            data,
+            allow_failure,
+        })
+    }
+
+    // The function that would run as Multicall3 instead of as the sender, if any
+    fn sender_bound(&self) -> Option<&'static str> {
+        let selector = hex::encode(self.data.get(..4)?);
+        SENDER_BOUND_SELECTORS
+            .iter()
+            .find(|(bound, _)| *bound == selector)
+            .map(|(_, name)| *name)
+    }
+}
+
+// Calls that can't go in one batch from `from`
+fn check_calls(from: Address, calls: &[BatchCall]) -> Result<(), String> {
+    if calls.len() < 2 {
+        return Err("A batch takes at least two calls, send a single call with send_transaction".to_string());
+    }
+    if calls.len() > MAX_BATCH_CALLS {
+        return Err(format!("A batch takes at most {} calls, split the {} calls into several", MAX_BATCH_CALLS, calls.len()));
+    }
+    if let Some((index, name)) = calls.iter().enumerate().find_map(|(index, call)| Some((index, call.sender_bound()?))) {
+        return Err(format!(
+            "Call {} ({} on {}) acts for msg.sender, which in a batch is Multicall3 and not {}; send it on its own",
+            index + 1,
+            name,
+            calls[index].to,
+            from
+        ));
+    }
+    Ok(())
+}
+
+// The batch's total value and `aggregate3Value` calldata
+fn encode(calls: &[BatchCall]) -> Result<(U256, Vec<u8>), String> {
+    let total = calls.iter().try_fold(U256::ZERO, |total, call| total.checked_add(call.value)).ok_or("The values overflow")?;
+    let data = Multicall3::aggregate3ValueCall {
+        calls: calls
+            .iter()
+            .map(|call| Multicall3::Call3Value {
+                target: call.to,
+                allowFailure: call.allow_failure,
+                value: call.value,
+                callData: call.data.clone(),
+            })
+            .collect(),
+    }
+    .abi_encode();
+    Ok((total, data))
+}
+
+// One `aggregate3Value` transaction from `from` making `calls` in order. The transaction's value is
+// the sum of the calls' values; without allow_failure a failing call reverts the whole batch.
+pub async fn batch(provider: &RootProvider<AnyNetwork>, from: Address, calls: &[BatchCall]) -> Result<Value, String> {
+    check_calls(from, calls)?;
+
+    let multicall = Address::from_str(MULTICALL3).expect("valid address");
+    let code = provider.get_code_at(multicall).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    if code.is_empty() {
+        return Err(format!("Multicall3 is not deployed at {} on this chain", MULTICALL3));
+    }
+
+    let (total, data) = encode(calls)?;
+    let summary: Vec<Value> = calls
+        .iter()
+        .map(|call| {
+            json!({
+                "to": call.to.to_string(),
+                "value": call.value.to_string(),
+                "selector": call.data.get(..4).map(|selector| format!("0x{}", hex::encode(selector))),
+                "allow_failure": call.allow_failure
+            })
+        })
+        .collect();
+    Ok(json!({
+        "success": true,
+        "calls": summary,
+        "total_value": total.to_string(),
+        "base_gas_saved": TRANSACTION_BASE_GAS * (calls.len() as u64 - 1),
+        "message": "The calls run with Multicall3 as msg.sender. Send the transaction with send_transaction",
+        "transaction": {
+            "from": from.to_string(),
+            "to": MULTICALL3,
+            "value": total.to_string(),
+            "data": format!("0x{}", hex::encode(data))
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROM: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    const TARGET: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+    fn call(value: &str, data: &str, allow_failure: bool) -> BatchCall {
+        BatchCall::parse(TARGET, Some(value), Some(data), allow_failure).unwrap()
+    }
+
+    #[test]
+    fn encodes_calls_in_order_and_sums_values() {
+        let calls = [call("1000", "0x12345678", false), call("2000", "0x", true), call("0", "0xdeadbeef00", false)];
+
+        let (total, data) = encode(&calls).unwrap();
+
+        assert_eq!(total, U256::from(3000));
+        assert_eq!(data[..4], Multicall3::aggregate3ValueCall::SELECTOR);
+        let decoded = Multicall3::aggregate3ValueCall::abi_decode(&data).unwrap();
+        assert_eq!(decoded.calls.len(), 3);
+        for (decoded, call) in decoded.calls.iter().zip(&calls) {
+            assert_eq!(decoded.target, call.to);
+            assert_eq!(decoded.value, call.value);
+            assert_eq!(decoded.callData, call.data);
+            assert_eq!(decoded.allowFailure, call.allow_failure);
+        }
+    }
+
+    #[test]
+    fn overflowing_values_are_refused() {
+        let calls = [call(&U256::MAX.to_string(), "0x", false), call("1", "0x", false)];
+
+        assert_eq!(encode(&calls).unwrap_err(), "The values overflow");
+    }
+
+    #[test]
+    fn calls_acting_for_the_sender_are_refused() {
+        let from = Address::from_str(FROM).unwrap();
+        let transfer = format!("0xa9059cbb{}", "00".repeat(64));
+        let calls = [call("0", "0x12345678", false), call("0", &transfer, false)];
+
+        let error = check_calls(from, &calls).unwrap_err();
+
+        assert!(error.starts_with("Call 2 (transfer on"), "{}", error);
+        assert!(check_calls(from, &calls[..1]).is_err());
+        assert!(check_calls(from, &[call("1", "0x", false), call("2", "0x12345678", true)]).is_ok());
+    }
+
+    #[test]
+    fn missing_value_and_data_default_to_empty() {
+        let call = BatchCall::parse(TARGET, None, None, false).unwrap();
+
+        assert_eq!(call.value, U256::ZERO);
+        assert!(call.data.is_empty());
+        assert!(BatchCall::parse(TARGET, Some("ten"), None, false).is_err());
+    }
+}
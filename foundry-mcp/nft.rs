@@ -0,0 +1,142 @@
+use alloy::sol;
+use alloy_primitives::{Address, U256};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use base64::Engine;
+use serde_json::{json, Value};
+
+sol! {
+    #[sol(rpc)]
+    contract MetadataNft {
+        function tokenURI(uint256 tokenId) external view returns (string);
+        function uri(uint256 id) external view returns (string);
+        function name() external view returns (string);
+    }
+}
+
+const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+const ARWEAVE_GATEWAY: &str = "https://arweave.net/";
+// Metadata documents are small, anything bigger is not metadata
+const MAX_METADATA_BYTES: usize = 1024 * 1024;
+
+// IPFS_GATEWAY, e.g. a pinning service's dedicated gateway, with a trailing slash
+fn ipfs_gateway() -> String {
+    let gateway = std::env::var("IPFS_GATEWAY").ok().filter(|gateway| !gateway.is_empty());
+    let gateway = gateway.unwrap_or_else(|| DEFAULT_IPFS_GATEWAY.to_string());
+    if gateway.ends_with('/') {
+        gateway
+    } else {
+        format!("{}/", gateway)
+    }
+}
+
+// An HTTP(S) URL for ipfs:// and ar:// URIs, others are kept as they are
+pub fn resolve_uri(uri: &str) -> String {
+    if let Some(path) = uri.strip_prefix("ipfs://") {
+        format!("{}{}", ipfs_gateway(), path.trim_start_matches("ipfs/"))
+    } else if let Some(path) = uri.strip_prefix("ar://") {
+        format!("{}{}", ARWEAVE_GATEWAY, path)
+    } else {
+        uri.to_string()
+    }
+}
+
+// ERC-1155 URIs may hold an `{id}` placeholder for the token id as 64 lowercase hex digits
+fn substitute_id(uri: &str, token_id: U256) -> String {
+    uri.replace("{id}", &format!("{:064x}", token_id))
+}
+
+// The token URI from ERC-721 `tokenURI`, or ERC-1155 `uri` when there is none
+async fn token_uri(provider: &RootProvider<AnyNetwork>, contract: Address, token_id: U256) -> Result<(String, &'static str), String> {
+    let nft = MetadataNft::new(contract, provider);
+    if let Ok(uri) = nft.tokenURI(token_id).call().await {
+        return Ok((uri, "ERC-721"));
+    }
+    match nft.uri(token_id).call().await {
+        Ok(uri) => Ok((substitute_id(&uri, token_id), "ERC-1155")),
+        Err(e) => Err(format!("{} has no tokenURI or uri for token {}: {}", contract, token_id, e)),
+    }
+}
+
+// The metadata document behind a URI: inline `data:` JSON (base64 or plain) or fetched over HTTP
+async fn fetch_metadata(uri: &str) -> Result<Value, String> {
+    let content = if let Some(data) = uri.strip_prefix("data:") {
+        let (media_type, payload) = data.split_once(',').ok_or("Malformed data URI")?;
+        if media_type.ends_with(";base64") {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(payload)
+                .map_err(|e| format!("Invalid base64 metadata: {}", e))?;
+            String::from_utf8(bytes).map_err(|e| format!("Metadata is not UTF-8: {}", e))?
+        } else {
+            payload.to_string()
+        }
+    } else {
+        let response = reqwest::get(resolve_uri(uri))
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch the metadata: {}", e))?;
+        let bytes = response.bytes().await.map_err(|e| format!("Failed to fetch the metadata: {}", e))?;
+        if bytes.len() > MAX_METADATA_BYTES {
+            return Err(format!("Metadata is {} bytes, too large to be metadata", bytes.len()));
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    serde_json::from_str(&content).map_err(|e| format!("Metadata is not JSON: {}", e))
+}
+
+// Traits as `{trait_type, value}`, from the usual `attributes` array or an older `properties` map
+fn traits(metadata: &Value) -> Vec<Value> {
+    if let Some(attributes) = metadata.get("attributes").and_then(Value::as_array) {
+        return attributes
+            .iter()
+            .map(|attribute| {
+                json!({
+                    "trait_type": attribute.get("trait_type").cloned().unwrap_or(Value::Null),
+                    "value": attribute.get("value").cloned().unwrap_or_else(|| attribute.clone())
+                })
+            })
+            .collect();
+    }
+    match metadata.get("properties").and_then(Value::as_object) {
+        Some(properties) => properties
+            .iter()
+            .map(|(name, value)| json!({ "trait_type": name, "value": value.get("description").unwrap_or(value) }))
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn text(metadata: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| metadata.get(*key)?.as_str()).map(str::to_string)
+}
+
+// Name, description, traits and media of one token, with image URLs made fetchable. Inline SVG
+// images are summarized, their data URI would flood the result.
+pub async fn metadata(provider: &RootProvider<AnyNetwork>, contract: Address, token_id: U256) -> Result<Value, String> {
+    let (uri, standard) = token_uri(provider, contract, token_id).await?;
+    let collection = MetadataNft::new(contract, provider).name().call().await.ok();
+    let metadata = fetch_metadata(&uri).await?;
+
+    let image = text(&metadata, &["image", "image_url", "image_data"]).map(|image| {
+        if image.starts_with("data:") || image.starts_with("<svg") {
+            format!("inline image ({} bytes)", image.len())
+        } else {
+            resolve_uri(&image)
+        }
+    });
+    Ok(json!({
+        "success": true,
+        "contract": contract.to_string(),
+        "token_id": token_id.to_string(),
+        "standard": standard,
+        "collection": collection,
+        // Inline metadata is already decoded below
+        "token_uri": if uri.starts_with("data:") { "inline data URI".to_string() } else { uri.clone() },
+        "metadata_url": (!uri.starts_with("data:")).then(|| resolve_uri(&uri)),
+        "name": text(&metadata, &["name", "title"]),
+        "description": text(&metadata, &["description"]),
+        "image": image,
+        "animation_url": text(&metadata, &["animation_url"]).map(|url| resolve_uri(&url)),
+        "external_url": text(&metadata, &["external_url"]),
+        "traits": traits(&metadata)
+    }))
+}
@@ -0,0 +1,182 @@
+use alloy::sol;
+use alloy::sol_types::{Eip712Domain, SolStruct};
+use alloy_primitives::{Address, B256, U256};
+use serde_json::json;
+use std::borrow::Cow;
+
+sol! {
+    #[sol(rpc)]
+    contract ERC20Permit {
+        function name() external view returns (string);
+        function nonces(address owner) external view returns (uint256);
+        function DOMAIN_SEPARATOR() external view returns (bytes32);
+        function version() external view returns (string);   // optional
+    }
+
+    // EIP-2612 message the owner signs
+    struct Permit {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 nonce;
+        uint256 deadline;
+    }
+}
+
+// Versions tried when the token has no version() getter, most tokens use "1" and USDC uses "2"
+const FALLBACK_VERSIONS: [&str; 2] = ["1", "2"];
+
+pub fn domain(name: &str, version: &str, chain_id: u64, token: Address) -> Eip712Domain {
+    Eip712Domain {
+        name: Some(Cow::Owned(name.to_string())),
+        version: Some(Cow::Owned(version.to_string())),
+        chain_id: Some(U256::from(chain_id)),
+        verifying_contract: Some(token),
+        salt: None,
+    }
+}
+
+// The token's EIP-712 version, checked against its DOMAIN_SEPARATOR so a signature is never
+// made for a domain the token would reject
+pub fn matching_version<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    chain_id: u64,
+    token: Address,
+    separator: B256,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .find(|version| domain(name, version, chain_id, token).separator() == separator)
+}
+
+pub fn candidate_versions(version: Option<&str>) -> Vec<&str> {
+    version.into_iter().chain(FALLBACK_VERSIONS).collect()
+}
+
+pub fn signing_hash(permit: &Permit, domain: &Eip712Domain) -> B256 {
+    permit.eip712_signing_hash(domain)
+}
+
+// The same message in eth_signTypedData_v4 form, for the node holding the owner's key
+pub fn typed_data(permit: &Permit, name: &str, version: &str, chain_id: u64, token: Address) -> serde_json::Value {
+    json!({
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Permit": [
+                { "name": "owner", "type": "address" },
+                { "name": "spender", "type": "address" },
+                { "name": "value", "type": "uint256" },
+                { "name": "nonce", "type": "uint256" },
+                { "name": "deadline", "type": "uint256" }
+            ]
+        },
+        "primaryType": "Permit",
+        "domain": {
+            "name": name,
+            "version": version,
+            "chainId": chain_id,
+            "verifyingContract": token.to_string()
+        },
+        "message": {
+            "owner": permit.owner.to_string(),
+            "spender": permit.spender.to_string(),
+            "value": permit.value.to_string(),
+            "nonce": permit.nonce.to_string(),
+            "deadline": permit.deadline.to_string()
+        }
+    })
+}
+
+// Split a 65 byte signature into the v, r and s arguments of permit()
+pub fn split_signature(signature: &str) -> Result<(u8, String, String), String> {
+    let bytes = hex::decode(signature.trim_start_matches("0x")).map_err(|e| format!("Invalid signature: {}", e))?;
+    if bytes.len() != 65 {
+        return Err(format!("Invalid signature length {}", bytes.len()));
+    }
+    // Some signers return the recovery id as 0/1 instead of 27/28
+    let v = if bytes[64] < 27 { bytes[64] + 27 } else { bytes[64] };
+    Ok((v, format!("0x{}", hex::encode(&bytes[..32])), format!("0x{}", hex::encode(&bytes[32..64]))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::signers::{local::PrivateKeySigner, SignerSync};
+    use alloy_primitives::Signature;
+    use std::str::FromStr;
+
+    // Anvil's first account
+    const OWNER_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const TOKEN: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+    const SPENDER: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+    fn permit(owner: Address) -> Permit {
+        Permit {
+            owner,
+            spender: Address::from_str(SPENDER).unwrap(),
+            value: U256::from(1_000_000),
+            nonce: U256::from(3),
+            deadline: U256::from(1_900_000_000u64),
+        }
+    }
+
+    #[test]
+    fn finds_the_version_matching_the_domain_separator() {
+        let token = Address::from_str(TOKEN).unwrap();
+        let separator = domain("USD Coin", "2", 1, token).separator();
+
+        assert_eq!(matching_version("USD Coin", candidate_versions(None), 1, token, separator), Some("2"));
+        assert_eq!(candidate_versions(Some("3")), vec!["3", "1", "2"]);
+        assert_eq!(matching_version("USD Coin", candidate_versions(Some("3")), 1, token, separator), Some("2"));
+        // Another chain has another separator
+        assert_eq!(matching_version("USD Coin", candidate_versions(None), 10, token, separator), None);
+    }
+
+    #[test]
+    fn split_signature_recovers_the_owner() {
+        let signer = PrivateKeySigner::from_str(OWNER_KEY).unwrap();
+        let token = Address::from_str(TOKEN).unwrap();
+        let hash = signing_hash(&permit(signer.address()), &domain("USD Coin", "2", 1, token));
+        let signature = signer.sign_hash_sync(&hash).unwrap();
+
+        let (v, r, s) = split_signature(&format!("0x{}", hex::encode(signature.as_bytes()))).unwrap();
+
+        assert!(v == 27 || v == 28);
+        let signature = Signature::new(U256::from_str(&r).unwrap(), U256::from_str(&s).unwrap(), v == 28);
+        assert_eq!(signature.recover_address_from_prehash(&hash).unwrap(), signer.address());
+    }
+
+    #[test]
+    fn split_signature_normalizes_the_recovery_id() {
+        let signature = format!("0x{}{}01", "11".repeat(32), "22".repeat(32));
+
+        let (v, r, s) = split_signature(&signature).unwrap();
+
+        assert_eq!(v, 28);
+        assert_eq!(r, format!("0x{}", "11".repeat(32)));
+        assert_eq!(s, format!("0x{}", "22".repeat(32)));
+        assert!(split_signature("0x1234").is_err());
+    }
+
+    #[test]
+    fn typed_data_carries_the_signed_message() {
+        let owner = Address::from_str(SPENDER).unwrap();
+        let token = Address::from_str(TOKEN).unwrap();
+
+        let typed_data = typed_data(&permit(owner), "USD Coin", "2", 1, token);
+
+        assert_eq!(typed_data["primaryType"], "Permit");
+        assert_eq!(typed_data["domain"]["version"], "2");
+        assert_eq!(typed_data["domain"]["verifyingContract"], TOKEN);
+        assert_eq!(typed_data["message"]["owner"], SPENDER);
+        assert_eq!(typed_data["message"]["value"], "1000000");
+        assert_eq!(typed_data["message"]["nonce"], "3");
+        assert_eq!(typed_data["message"]["deadline"], "1900000000");
+    }
+}
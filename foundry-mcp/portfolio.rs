@@ -0,0 +1,138 @@
+use alloy::sol;
+use alloy_primitives::{utils::format_units, Address, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+sol! {
+    #[sol(rpc)]
+    contract HeldToken {
+        function balanceOf(address account) external view returns (uint256);
+        function decimals() external view returns (uint8);
+        function symbol() external view returns (string);
+    }
+
+    #[sol(rpc)]
+    contract PriceFeed {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
+    }
+}
+
+// Tokens sampled on mainnet (and forks) when the caller names none
+pub const DEFAULT_MAINNET_TOKENS: &[&str] = &[
+    "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", // WETH
+    "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", // USDC
+    "0xdAC17F958D2ee523a2206206994597C13D831ec7", // USDT
+    "0x6B175474E89094C44Da98b954EedeAC495271d0F", // DAI
+    "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599", // WBTC
+];
+
+// Blocks looked back over to measure the chain's average block time
+const BLOCK_TIME_WINDOW: u64 = 10_000;
+
+// What is sampled at every block: the native balance (`token` None) or an ERC20 balance, priced
+// by a Chainlink USD feed when there is one
+pub struct Asset {
+    pub symbol: String,
+    pub token: Option<Address>,
+    pub decimals: u8,
+    pub feed: Option<Address>,
+}
+
+// An ERC20 with its symbol and decimals as of now
+pub async fn token_asset(provider: &RootProvider<AnyNetwork>, token: Address) -> Result<Asset, String> {
+    let contract = HeldToken::new(token, provider);
+    let decimals = contract.decimals().call().await.map_err(|e| format!("Failed to read the decimals of {}: {}", token, e))?;
+    let symbol = contract.symbol().call().await.unwrap_or_else(|_| token.to_string());
+    Ok(Asset {
+        symbol,
+        token: Some(token),
+        decimals,
+        feed: None,
+    })
+}
+
+async fn timestamp(provider: &RootProvider<AnyNetwork>, block: u64) -> Result<u64, String> {
+    provider
+        .get_block_by_number(BlockNumberOrTag::Number(block))
+        .await
+        .map_err(|e| format!("Failed to get block {}: {}", block, e))?
+        .map(|block| block.header.timestamp)
+        .ok_or_else(|| format!("Block {} not found", block))
+}
+
+// `samples` blocks spread evenly over the last `days`, the latest one last. Block numbers are
+// found from the average block time, so they land close to, not exactly on, the even times.
+pub async fn sample_blocks(provider: &RootProvider<AnyNetwork>, days: u32, samples: u32) -> Result<Vec<u64>, String> {
+    let latest = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+    let reference = latest.saturating_sub(BLOCK_TIME_WINDOW);
+    let block_time = if reference < latest {
+        let elapsed = timestamp(provider, latest).await?.saturating_sub(timestamp(provider, reference).await?);
+        (elapsed as f64 / (latest - reference) as f64).max(0.1)
+    } else {
+        12.0
+    };
+
+    let span = ((days as f64 * 86_400.0) / block_time) as u64;
+    let start = latest.saturating_sub(span);
+    let samples = samples.max(2) as u64;
+    let mut blocks: Vec<u64> = (0..samples).map(|i| start + (latest - start) * i / (samples - 1)).collect();
+    blocks.dedup();
+    Ok(blocks)
+}
+
+async fn balance_at(provider: &RootProvider<AnyNetwork>, asset: &Asset, account: Address, block: BlockId) -> Result<U256, String> {
+    match asset.token {
+        None => provider
+            .get_balance(account)
+            .block_id(block)
+            .await
+            .map_err(|e| format!("Failed to read the balance at {:?}, historical state needs an archive node: {}", block, e)),
+        // A token deployed after the block had no balances yet
+        Some(token) => Ok(HeldToken::new(token, provider).balanceOf(account).call().block(block).await.unwrap_or_default()),
+    }
+}
+
+async fn price_at(provider: &RootProvider<AnyNetwork>, feed: Address, block: BlockId) -> Option<String> {
+    let feed = PriceFeed::new(feed, provider);
+    let decimals = feed.decimals().call().block(block).await.ok()?;
+    let round = feed.latestRoundData().call().block(block).await.ok()?;
+    if round.answer.is_negative() {
+        return None;
+    }
+    format_units(round.answer.into_raw(), decimals).ok()
+}
+
+// Balances of `account` at `block` with the USD price of each asset at that block
+pub async fn sample(provider: &RootProvider<AnyNetwork>, block: u64, account: Address, assets: &[Asset]) -> Result<Value, String> {
+    let at = BlockId::number(block);
+    let mut holdings = vec![];
+    for asset in assets {
+        let balance = balance_at(provider, asset, account, at).await?;
+        let price_usd = match asset.feed {
+            Some(feed) => price_at(provider, feed, at).await,
+            None => None,
+        };
+        holdings.push(json!({
+            "asset": asset.symbol,
+            "token": asset.token.map(|token| token.to_string()),
+            "balance": format_units(balance, asset.decimals).map_err(|e| e.to_string())?,
+            "price_usd": price_usd
+        }));
+    }
+    Ok(json!({
+        "block": block,
+        "timestamp": timestamp(provider, block).await?,
+        "holdings": holdings
+    }))
+}
+
+// Default tokens of a chain, forks use mainnet's
+pub fn default_tokens(chain_id: u64) -> Vec<Address> {
+    match chain_id {
+        1 => DEFAULT_MAINNET_TOKENS.iter().filter_map(|token| Address::from_str(token).ok()).collect(),
+        _ => vec![],
+    }
+}
@@ -0,0 +1,370 @@
+use alloy::sol;
+use alloy_primitives::{utils::format_units, Address, U256};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+use crate::portfolio::HeldToken;
+use crate::twap::{self, UniswapV3Factory};
+
+sol! {
+    #[sol(rpc)]
+    contract AavePool {
+        struct ReserveData {
+            uint256 configuration;
+            uint128 liquidityIndex;
+            uint128 currentLiquidityRate;
+            uint128 variableBorrowIndex;
+            uint128 currentVariableBorrowRate;
+            uint128 currentStableBorrowRate;
+            uint40 lastUpdateTimestamp;
+            uint16 id;
+            address aTokenAddress;
+            address stableDebtTokenAddress;
+            address variableDebtTokenAddress;
+            address interestRateStrategyAddress;
+            uint128 accruedToTreasury;
+            uint128 unbacked;
+            uint128 isolationModeTotalDebt;
+        }
+
+        function getUserAccountData(address user) external view returns (uint256 totalCollateralBase, uint256 totalDebtBase, uint256 availableBorrowsBase, uint256 currentLiquidationThreshold, uint256 ltv, uint256 healthFactor);
+        function getReservesList() external view returns (address[] memory);
+        function getReserveData(address asset) external view returns (ReserveData memory);
+    }
+
+    #[sol(rpc)]
+    contract Comet {
+        struct AssetInfo {
+            uint8 offset;
+            address asset;
+            address priceFeed;
+            uint64 scale;
+            uint64 borrowCollateralFactor;
+            uint64 liquidateCollateralFactor;
+            uint64 liquidationFactor;
+            uint128 supplyCap;
+        }
+
+        function baseToken() external view returns (address);
+        function baseTokenPriceFeed() external view returns (address);
+        function balanceOf(address account) external view returns (uint256);
+        function borrowBalanceOf(address account) external view returns (uint256);
+        function numAssets() external view returns (uint8);
+        function getAssetInfo(uint8 i) external view returns (AssetInfo memory);
+        function collateralBalanceOf(address account, address asset) external view returns (uint128);
+        function getPrice(address priceFeed) external view returns (uint256);
+        function isLiquidatable(address account) external view returns (bool);
+    }
+
+    #[sol(rpc)]
+    contract PositionManager {
+        function balanceOf(address owner) external view returns (uint256);
+        function tokenOfOwnerByIndex(address owner, uint256 index) external view returns (uint256);
+        function positions(uint256 tokenId) external view returns (uint96 nonce, address operator, address token0, address token1, uint24 fee, int24 tickLower, int24 tickUpper, uint128 liquidity, uint256 feeGrowthInside0LastX128, uint256 feeGrowthInside1LastX128, uint128 tokensOwed0, uint128 tokensOwed1);
+    }
+
+    #[sol(rpc)]
+    contract PositionPool {
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked);
+    }
+}
+
+// Protocols read by `read`, in the order of the result
+pub const PROTOCOLS: &[&str] = &["aave_v3", "compound_v3", "uniswap_v3"];
+
+// Health factor under which a lending position is flagged, 1.0 is liquidation
+const AT_RISK_HEALTH_FACTOR: f64 = 1.1;
+const WATCH_HEALTH_FACTOR: f64 = 1.5;
+// LP positions read per account, the newest ones are skipped past this
+const MAX_LP_POSITIONS: u64 = 25;
+
+// Aave V3 Pool of a chain, forks use their parent chain's
+fn aave_pool(chain_id: u64) -> Option<Address> {
+    let address = match chain_id {
+        1 => "0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2",
+        10 | 137 | 42161 => "0x794a61358D6845594F94dc1DB02A252b5b4814aD",
+        8453 => "0xA238Dd80C259a72e81d7e4664a9801593F98d1c5",
+        _ => return None,
+    };
+    Address::from_str(address).ok()
+}
+
+// Compound V3 (Comet) markets of a chain
+fn comet_markets(chain_id: u64) -> Vec<Address> {
+    let markets: &[&str] = match chain_id {
+        1 => &[
+            "0xc3d688B66703497DAA19211EEdff47f25384cdc3", // USDC
+            "0xA17581A9E3356d9A858b789D68B4d866e593aE94", // WETH
+            "0x3Afdc9BCA9213A35503b077a6072F3D0d5AB0840", // USDT
+        ],
+        8453 => &["0xb125E6687d4313864e53df431d5425969c15Eb2F"],
+        42161 => &["0x9c4ec768c28520B50860ea7a15bd7213a9fF58bf"],
+        _ => &[],
+    };
+    markets.iter().filter_map(|market| Address::from_str(market).ok()).collect()
+}
+
+// Uniswap V3 NonfungiblePositionManager of a chain
+fn position_manager(chain_id: u64) -> Option<Address> {
+    let address = match chain_id {
+        1 | 10 | 137 | 42161 => "0xC36442b4a4522E871399CD717aBDD847Ab11FE88",
+        8453 => "0x03a520b32C04BF3bEEf7BEb72E919cf822Ed34f1",
+        _ => return None,
+    };
+    Address::from_str(address).ok()
+}
+
+fn units(value: U256, decimals: u8) -> f64 {
+    format_units(value, decimals).ok().and_then(|value| value.parse().ok()).unwrap_or_default()
+}
+
+// How close a lending position is to liquidation, `None` without debt
+pub fn liquidation_risk(health_factor: Option<f64>) -> &'static str {
+    match health_factor {
+        None => "none",
+        Some(health) if health < 1.0 => "liquidatable",
+        Some(health) if health < AT_RISK_HEALTH_FACTOR => "at_risk",
+        Some(health) if health < WATCH_HEALTH_FACTOR => "watch",
+        Some(_) => "safe",
+    }
+}
+
+async fn token_meta(provider: &RootProvider<AnyNetwork>, token: Address) -> (String, u8) {
+    let contract = HeldToken::new(token, provider);
+    let symbol = contract.symbol().call().await.unwrap_or_else(|_| token.to_string());
+    let decimals = contract.decimals().call().await.unwrap_or(18);
+    (symbol, decimals)
+}
+
+// Supplied and borrowed reserves with the account totals. Aave values its base currency in USD
+// with 8 decimals; a health factor of uint256 max means no debt.
+async fn aave(provider: &RootProvider<AnyNetwork>, pool: Address, account: Address) -> Result<Value, String> {
+    let pool = AavePool::new(pool, provider);
+    let data = pool
+        .getUserAccountData(account)
+        .call()
+        .await
+        .map_err(|e| format!("Failed to read the Aave account data: {}", e))?;
+    let has_debt = !data.totalDebtBase.is_zero();
+    let health_factor = has_debt.then(|| units(data.healthFactor, 18));
+
+    let mut supplied = vec![];
+    let mut borrowed = vec![];
+    if !data.totalCollateralBase.is_zero() || has_debt {
+        let reserves = pool.getReservesList().call().await.map_err(|e| format!("Failed to list the Aave reserves: {}", e))?;
+        for reserve in reserves {
+            let Ok(reserve_data) = pool.getReserveData(reserve).call().await else {
+                continue;
+            };
+            let balance = |token: Address| async move {
+                HeldToken::new(token, provider).balanceOf(account).call().await.unwrap_or_default()
+            };
+            let a_token = balance(reserve_data.aTokenAddress).await;
+            let debt = balance(reserve_data.variableDebtTokenAddress).await;
+            if a_token.is_zero() && debt.is_zero() {
+                continue;
+            }
+            let (symbol, decimals) = token_meta(provider, reserve).await;
+            if !a_token.is_zero() {
+                supplied.push(json!({ "asset": symbol, "token": reserve.to_string(), "amount": units(a_token, decimals) }));
+            }
+            if !debt.is_zero() {
+                borrowed.push(json!({ "asset": symbol, "token": reserve.to_string(), "amount": units(debt, decimals) }));
+            }
+        }
+    }
+
+    Ok(json!({
+        "protocol": "aave_v3",
+        "collateral_usd": units(data.totalCollateralBase, 8),
+        "debt_usd": units(data.totalDebtBase, 8),
+        "available_borrows_usd": units(data.availableBorrowsBase, 8),
+        "liquidation_threshold_pct": units(data.currentLiquidationThreshold, 2),
+        "ltv_pct": units(data.ltv, 2),
+        "health_factor": health_factor,
+        "liquidation_risk": liquidation_risk(health_factor),
+        "supplied": supplied,
+        "borrowed": borrowed
+    }))
+}
+
+// One Comet market: the base asset supplied or borrowed and the collateral posted. The health
+// factor is the collateral value weighted by the liquidation factors over the debt value, priced
+// by the market's own feeds (8 decimals).
+async fn compound(provider: &RootProvider<AnyNetwork>, market: Address, account: Address) -> Result<Option<Value>, String> {
+    let comet = Comet::new(market, provider);
+    let read_error = |e: alloy::contract::Error| format!("Failed to read Compound market {}: {}", market, e);
+    let supplied = comet.balanceOf(account).call().await.map_err(read_error)?;
+    let borrowed = comet.borrowBalanceOf(account).call().await.map_err(read_error)?;
+
+    let mut collateral = vec![];
+    let mut liquidation_limit_usd = 0.0;
+    for i in 0..comet.numAssets().call().await.map_err(read_error)? {
+        let info = comet.getAssetInfo(i).call().await.map_err(read_error)?;
+        let balance = comet.collateralBalanceOf(account, info.asset).call().await.map_err(read_error)?;
+        if balance == 0 {
+            continue;
+        }
+        let amount = balance as f64 / info.scale as f64;
+        let price = units(comet.getPrice(info.priceFeed).call().await.map_err(read_error)?, 8);
+        liquidation_limit_usd += amount * price * info.liquidateCollateralFactor as f64 / 1e18;
+        let (symbol, _) = token_meta(provider, info.asset).await;
+        collateral.push(json!({ "asset": symbol, "token": info.asset.to_string(), "amount": amount, "value_usd": amount * price }));
+    }
+    if supplied.is_zero() && borrowed.is_zero() && collateral.is_empty() {
+        return Ok(None);
+    }
+
+    let base = comet.baseToken().call().await.map_err(read_error)?;
+    let (symbol, decimals) = token_meta(provider, base).await;
+    let base_feed = comet.baseTokenPriceFeed().call().await.map_err(read_error)?;
+    let base_price = units(comet.getPrice(base_feed).call().await.map_err(read_error)?, 8);
+    let debt_usd = units(borrowed, decimals) * base_price;
+    let health_factor = (!borrowed.is_zero()).then(|| liquidation_limit_usd / debt_usd);
+    let liquidatable = comet.isLiquidatable(account).call().await.unwrap_or_default();
+
+    Ok(Some(json!({
+        "protocol": "compound_v3",
+        "market": market.to_string(),
+        "base_asset": symbol,
+        "supplied": units(supplied, decimals),
+        "borrowed": units(borrowed, decimals),
+        "debt_usd": debt_usd,
+        "collateral": collateral,
+        "liquidation_limit_usd": liquidation_limit_usd,
+        "health_factor": health_factor,
+        "liquidation_risk": if liquidatable { "liquidatable" } else { liquidation_risk(health_factor) }
+    })))
+}
+
+// Token amounts of a position with `liquidity` between two ticks at the pool's current tick, in
+// raw units (the usual Uniswap V3 formulas, in floating point)
+fn lp_amounts(liquidity: u128, tick: i32, tick_lower: i32, tick_upper: i32) -> (f64, f64) {
+    let sqrt_price = |tick: i32| 1.0001_f64.powf(tick as f64 / 2.0);
+    let (lower, upper, current) = (sqrt_price(tick_lower), sqrt_price(tick_upper), sqrt_price(tick));
+    let liquidity = liquidity as f64;
+    if tick < tick_lower {
+        (liquidity * (1.0 / lower - 1.0 / upper), 0.0)
+    } else if tick >= tick_upper {
+        (0.0, liquidity * (upper - lower))
+    } else {
+        (liquidity * (1.0 / current - 1.0 / upper), liquidity * (current - lower))
+    }
+}
+
+// Open Uniswap V3 LP positions (NFTs) of the account with their amounts and whether the pool
+// price is inside their range, i.e. whether they earn fees
+async fn uniswap(provider: &RootProvider<AnyNetwork>, chain_id: u64, manager: Address, account: Address) -> Result<Vec<Value>, String> {
+    let manager = PositionManager::new(manager, provider);
+    let count = manager
+        .balanceOf(account)
+        .call()
+        .await
+        .map_err(|e| format!("Failed to read the Uniswap V3 positions: {}", e))?;
+    let factory = twap::factory(chain_id).map(|factory| UniswapV3Factory::new(factory, provider));
+
+    let mut positions = vec![];
+    for index in 0..count.saturating_to::<u64>().min(MAX_LP_POSITIONS) {
+        let Ok(token_id) = manager.tokenOfOwnerByIndex(account, U256::from(index)).call().await else {
+            continue;
+        };
+        let Ok(position) = manager.positions(token_id).call().await else {
+            continue;
+        };
+        if position.liquidity == 0 && position.tokensOwed0 == 0 && position.tokensOwed1 == 0 {
+            continue;
+        }
+        let tick_lower = i32::try_from(position.tickLower).unwrap_or_default();
+        let tick_upper = i32::try_from(position.tickUpper).unwrap_or_default();
+        let pool = match &factory {
+            Some(factory) => factory.getPool(position.token0, position.token1, position.fee).call().await.ok(),
+            None => None,
+        };
+        let tick = match pool {
+            Some(pool) => {
+                let slot0 = PositionPool::new(pool, provider).slot0().call().await.ok();
+                slot0.and_then(|slot0| i32::try_from(slot0.tick).ok())
+            }
+            None => None,
+        };
+        let (symbol0, decimals0) = token_meta(provider, position.token0).await;
+        let (symbol1, decimals1) = token_meta(provider, position.token1).await;
+        let amounts = tick.map(|tick| lp_amounts(position.liquidity, tick, tick_lower, tick_upper));
+
+        positions.push(json!({
+            "protocol": "uniswap_v3",
+            "token_id": token_id.to_string(),
+            "token0": symbol0,
+            "token1": symbol1,
+            "fee": position.fee.to::<u32>(),
+            "tick_lower": tick_lower,
+            "tick_upper": tick_upper,
+            "current_tick": tick,
+            "in_range": tick.map(|tick| tick_lower <= tick && tick < tick_upper),
+            "amount0": amounts.map(|(amount0, _)| amount0 / 10f64.powi(decimals0.into())),
+            "amount1": amounts.map(|(_, amount1)| amount1 / 10f64.powi(decimals1.into())),
+            "uncollected_fees0": units(U256::from(position.tokensOwed0), decimals0),
+            "uncollected_fees1": units(U256::from(position.tokensOwed1), decimals1)
+        }));
+    }
+    Ok(positions)
+}
+
+// Lending and LP positions of `account` in `protocols`, read from the deployments on `chain_id`.
+// A protocol without a deployment there is listed as unsupported, one that fails to read carries
+// its error, so the others are still reported.
+pub async fn read(provider: &RootProvider<AnyNetwork>, chain_id: u64, account: Address, protocols: &[&str]) -> Value {
+    let mut lending = vec![];
+    let mut liquidity = vec![];
+    let mut unsupported = vec![];
+    let mut errors = vec![];
+
+    if protocols.contains(&"aave_v3") {
+        match aave_pool(chain_id) {
+            Some(pool) => match aave(provider, pool, account).await {
+                Ok(position) if position["supplied"] != json!([]) || position["borrowed"] != json!([]) => lending.push(position),
+                Ok(_) => {}
+                Err(e) => errors.push(json!({ "protocol": "aave_v3", "error": e })),
+            },
+            None => unsupported.push("aave_v3"),
+        }
+    }
+    if protocols.contains(&"compound_v3") {
+        let markets = comet_markets(chain_id);
+        if markets.is_empty() {
+            unsupported.push("compound_v3");
+        }
+        for market in markets {
+            match compound(provider, market, account).await {
+                Ok(position) => lending.extend(position),
+                Err(e) => errors.push(json!({ "protocol": "compound_v3", "error": e })),
+            }
+        }
+    }
+    if protocols.contains(&"uniswap_v3") {
+        match position_manager(chain_id) {
+            Some(manager) => match uniswap(provider, chain_id, manager, account).await {
+                Ok(positions) => liquidity = positions,
+                Err(e) => errors.push(json!({ "protocol": "uniswap_v3", "error": e })),
+            },
+            None => unsupported.push("uniswap_v3"),
+        }
+    }
+
+    let lowest_health_factor = lending
+        .iter()
+        .filter_map(|position| position["health_factor"].as_f64())
+        .min_by(f64::total_cmp);
+    let liquidatable = lending.iter().any(|position| position["liquidation_risk"] == "liquidatable");
+    json!({
+        "success": true,
+        "address": account.to_string(),
+        "lending": lending,
+        "liquidity": liquidity,
+        "lowest_health_factor": lowest_health_factor,
+        "liquidation_risk": if liquidatable { "liquidatable" } else { liquidation_risk(lowest_health_factor) },
+        "unsupported": unsupported,
+        "errors": errors
+    })
+}
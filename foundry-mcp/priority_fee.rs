@@ -0,0 +1,140 @@
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use serde_json::{json, Value};
+
+// Recent blocks whose included transactions show what tips currently get in
+const SAMPLED_BLOCKS: u64 = 10;
+const BLOCK_TIME_SECS: u64 = 12;
+
+// How soon the transaction should be included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    NextBlock,
+    OneMinute,
+    FiveMinutes,
+}
+
+impl Target {
+    pub fn parse(target: &str) -> Result<Self, String> {
+        match target {
+            "next_block" => Ok(Target::NextBlock),
+            "1m" => Ok(Target::OneMinute),
+            "5m" => Ok(Target::FiveMinutes),
+            target => Err(format!("Unknown target {}, use next_block, 1m or 5m", target)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Target::NextBlock => "next_block",
+            Target::OneMinute => "1m",
+            Target::FiveMinutes => "5m",
+        }
+    }
+
+    fn blocks(&self) -> u32 {
+        match self {
+            Target::NextBlock => 1,
+            Target::OneMinute => 5,
+            Target::FiveMinutes => 25,
+        }
+    }
+
+    // Percentile of the sampled tips to beat: the next block needs to outbid most of the mempool,
+    // waiting a few minutes only needs to beat the cheapest transactions that still get in
+    fn percentile(&self) -> usize {
+        match self {
+            Target::NextBlock => 90,
+            Target::OneMinute => 50,
+            Target::FiveMinutes => 20,
+        }
+    }
+}
+
+fn quantity(value: &Value, name: &str) -> Option<u128> {
+    u128::from_str_radix(value.get(name)?.as_str()?.trim_start_matches("0x"), 16).ok()
+}
+
+// Tip each transaction of a block pays on top of `base_fee`
+fn tips(block: &Value, base_fee: u128) -> Vec<u128> {
+    block
+        .get("transactions")
+        .and_then(|transactions| transactions.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|tx| match (quantity(tx, "maxPriorityFeePerGas"), quantity(tx, "maxFeePerGas")) {
+            (Some(priority_fee), Some(max_fee)) => Some(priority_fee.min(max_fee.saturating_sub(base_fee))),
+            // Legacy transactions tip whatever their gas price leaves above the base fee
+            _ => quantity(tx, "gasPrice").map(|gas_price| gas_price.saturating_sub(base_fee)),
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[u128], percentile: usize) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    sorted[(sorted.len() - 1) * percentile / 100]
+}
+
+// EIP-1559 base fee of the block after `block`, which moves by up to 1/8 towards the gas target
+fn next_base_fee(block: &Value) -> u128 {
+    let base_fee = quantity(block, "baseFeePerGas").unwrap_or_default();
+    let gas_used = quantity(block, "gasUsed").unwrap_or_default();
+    let gas_target = quantity(block, "gasLimit").unwrap_or_default() / 2;
+    if gas_target == 0 || gas_used == gas_target {
+        return base_fee;
+    }
+    let delta = base_fee * gas_used.abs_diff(gas_target) / gas_target / 8;
+    if gas_used > gas_target {
+        base_fee + delta.max(1)
+    } else {
+        base_fee - delta
+    }
+}
+
+// Priority fee and max fee for inclusion within `target`, from the tips paid in the last blocks and
+// by the pending transactions the node knows about
+pub async fn suggest(provider: &RootProvider<AnyNetwork>, target: Target) -> Result<Value, String> {
+    let latest = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+
+    let mut sampled = vec![];
+    let mut next_base_fee_per_gas = None;
+    for number in latest.saturating_sub(SAMPLED_BLOCKS - 1)..=latest {
+        let block: Value = provider
+            .raw_request("eth_getBlockByNumber".into(), (format!("0x{:x}", number), true))
+            .await
+            .map_err(|e| format!("Failed to get block {}: {}", number, e))?;
+        if block.is_null() {
+            continue;
+        }
+        sampled.extend(tips(&block, quantity(&block, "baseFeePerGas").unwrap_or_default()));
+        if number == latest {
+            next_base_fee_per_gas = Some(next_base_fee(&block));
+        }
+    }
+    let base_fee = next_base_fee_per_gas.ok_or("Latest block not found")?;
+    let sampled_transactions = sampled.len();
+
+    // Not every node exposes a pending block, recent blocks are enough without one
+    let pending: Option<Value> = provider.raw_request("eth_getBlockByNumber".into(), ("pending", true)).await.ok();
+    let pending_tips = pending.as_ref().map(|pending| tips(pending, base_fee)).unwrap_or_default();
+    let pending_transactions = pending_tips.len();
+    sampled.extend(pending_tips);
+    sampled.sort_unstable();
+
+    let priority_fee = percentile(&sampled, target.percentile());
+    // Room for the base fee rising 12.5% a block until inclusion, capped at the usual doubling
+    let headroom = 1.125_f64.powi(target.blocks() as i32).min(2.0);
+    let max_fee = (base_fee as f64 * headroom) as u128 + priority_fee;
+
+    Ok(json!({
+        "target": target.name(),
+        "max_fee_per_gas": max_fee.to_string(),
+        "max_priority_fee_per_gas": priority_fee.to_string(),
+        "expected_inclusion_seconds": u64::from(target.blocks()) * BLOCK_TIME_SECS,
+        "base_fee_per_gas": base_fee.to_string(),
+        "sampled_blocks": SAMPLED_BLOCKS.min(latest + 1),
+        "sampled_transactions": sampled_transactions,
+        "pending_transactions": pending_transactions
+    }))
+}
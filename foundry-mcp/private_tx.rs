@@ -0,0 +1,34 @@
+use alloy_primitives::{keccak256, Bytes, B256};
+use serde_json::json;
+use std::str::FromStr;
+
+// The raw transaction from an eth_signTransaction response: anvil returns the encoded
+// transaction itself, geth an object with it under `raw`
+pub fn raw_transaction(signed: &serde_json::Value) -> Result<Bytes, String> {
+    let raw = signed
+        .as_str()
+        .or_else(|| signed.get("raw")?.as_str())
+        .ok_or_else(|| "The node returned no signed transaction".to_string())?;
+    Bytes::from_str(raw).map_err(|e| format!("Invalid signed transaction: {}", e))
+}
+
+// Submit a signed transaction to a private RPC such as Flashbots Protect or MEV Blocker, which
+// forwards it to builders without it ever entering the public mempool
+pub async fn submit(rpc_url: &str, raw: &Bytes) -> Result<B256, String> {
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(rpc_url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_sendRawTransaction", "params": [raw] }))
+        .send()
+        .await
+        .map_err(|e| format!("Private RPC request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Private RPC returned invalid JSON: {}", e))?;
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(|message| message.as_str()).unwrap_or("unknown error");
+        return Err(format!("Private RPC rejected the transaction: {}", message));
+    }
+
+    // The hash is the transaction's own, whatever the RPC echoes back
+    Ok(keccak256(raw))
+}
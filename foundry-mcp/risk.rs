@@ -0,0 +1,299 @@
+use alloy::sol;
+use alloy_primitives::Address;
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::standards;
+use crate::verify;
+
+sol! {
+    #[sol(rpc)]
+    contract Owned {
+        function owner() external view returns (address);
+    }
+}
+
+const ETHERSCAN_URL: &str = "https://api.etherscan.io/v2/api";
+const ETHPLORER_URL: &str = "https://api.ethplorer.io";
+
+// Contracts younger than this are a common shape of rug pulls and drainers
+const NEW_CONTRACT_DAYS: u64 = 7;
+const YOUNG_CONTRACT_DAYS: u64 = 30;
+// Share of the supply held by the top 10 holders that lets a few wallets move the price
+const CONCENTRATED_SUPPLY_PCT: f64 = 80.0;
+
+// Every address of the lists in SCAM_LISTS, a comma separated list of file paths and URLs with a
+// JSON array of addresses or one address per line, mapped to the list naming it. Lists that fail
+// to load are skipped with a warning.
+pub async fn load_scam_lists() -> HashMap<Address, String> {
+    let mut listed = HashMap::new();
+    let sources = std::env::var("SCAM_LISTS").unwrap_or_default();
+    for source in sources.split(',').map(str::trim).filter(|source| !source.is_empty()) {
+        match fetch(source).await {
+            Ok(addresses) => listed.extend(addresses.into_iter().map(|address| (address, source.to_string()))),
+            Err(e) => tracing::warn!("Skipping scam list {}: {}", source, e),
+        }
+    }
+    listed
+}
+
+async fn fetch(source: &str) -> Result<Vec<Address>, String> {
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        reqwest::get(source)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        tokio::fs::read_to_string(source).await.map_err(|e| e.to_string())?
+    };
+
+    let entries: Vec<String> = match serde_json::from_str(&content) {
+        Ok(entries) => entries,
+        Err(_) => content.lines().map(|line| line.trim().to_string()).collect(),
+    };
+    Ok(entries.iter().filter_map(|entry| Address::from_str(entry).ok()).collect())
+}
+
+// First block with code at `address`: from Etherscan with a key, otherwise by bisecting the
+// chain's history, which needs an archive node
+async fn creation_block(provider: &RootProvider<AnyNetwork>, chain_id: u64, address: Address) -> Option<u64> {
+    if let Some(key) = std::env::var("ETHERSCAN_API_KEY").ok().filter(|key| !key.is_empty()) {
+        let response: Option<Value> = async {
+            reqwest::Client::new()
+                .get(ETHERSCAN_URL)
+                .query(&[
+                    ("chainid", chain_id.to_string()),
+                    ("module", "contract".to_string()),
+                    ("action", "getcontractcreation".to_string()),
+                    ("contractaddresses", address.to_string()),
+                    ("apikey", key),
+                ])
+                .send()
+                .await
+                .ok()?
+                .json()
+                .await
+                .ok()
+        }
+        .await;
+        let block = response
+            .as_ref()
+            .and_then(|response| response.pointer("/result/0/blockNumber"))
+            .and_then(|block| block.as_str())
+            .and_then(|block| block.parse().ok());
+        if block.is_some() {
+            return block;
+        }
+    }
+
+    let (mut low, mut high) = (0, provider.get_block_number().await.ok()?);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let code = provider.get_code_at(address).block_id(BlockId::number(middle)).await.ok()?;
+        if code.is_empty() {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Some(low)
+}
+
+async fn deployment(provider: &RootProvider<AnyNetwork>, chain_id: u64, address: Address, now: u64) -> Value {
+    let Some(block) = creation_block(provider, chain_id, address).await else {
+        return json!({ "known": false });
+    };
+    let timestamp = provider
+        .get_block_by_number(BlockNumberOrTag::Number(block))
+        .await
+        .ok()
+        .flatten()
+        .map(|block| block.header.timestamp);
+    json!({
+        "known": timestamp.is_some(),
+        "block": block,
+        "timestamp": timestamp,
+        "age_days": timestamp.map(|timestamp| now.saturating_sub(timestamp) / 86_400)
+    })
+}
+
+// Verified on Sourcify or, with a key, on Etherscan. Forks keep mainnet's code, so local chains
+// are looked up on mainnet.
+async fn verification(chain_id: u64, address: Address) -> Value {
+    if verify::sourcify_source(chain_id, address).await.is_ok() {
+        return json!({ "verified": true, "source": "sourcify" });
+    }
+    match std::env::var("ETHERSCAN_API_KEY").ok().filter(|key| !key.is_empty()) {
+        Some(key) => match verify::etherscan_source(chain_id, address, &key).await {
+            // Verified Vyper sources are fetched, they just can't be recompiled
+            Ok(_) => json!({ "verified": true, "source": "etherscan" }),
+            Err(e) if e.contains("Vyper") => json!({ "verified": true, "source": "etherscan" }),
+            Err(e) if e.contains("not verified") => json!({ "verified": false, "source": "etherscan" }),
+            Err(e) => json!({ "verified": null, "error": e }),
+        },
+        None => json!({ "verified": false, "source": "sourcify" }),
+    }
+}
+
+// Who can change the contract: the proxy kind and admin, and an Ownable owner
+async fn control(provider: &RootProvider<AnyNetwork>, address: Address) -> Result<Value, String> {
+    let code = provider.get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    let proxy = standards::proxy(provider, address, &code).await;
+    let admin = standards::proxy_admin(provider, address).await;
+    let is_contract =
+        |account: Address| async move { provider.get_code_at(account).await.map(|code| !code.is_empty()).ok() };
+    let admin_is_contract = match admin {
+        Some(admin) => is_contract(admin).await,
+        None => None,
+    };
+    let owner = Owned::new(address, provider).owner().call().await.ok().filter(|owner| !owner.is_zero());
+    let owner_is_contract = match owner {
+        Some(owner) => is_contract(owner).await,
+        None => None,
+    };
+    Ok(json!({
+        "proxy": proxy,
+        "admin": admin.map(|admin| admin.to_string()),
+        "admin_is_contract": admin_is_contract,
+        "owner": owner.map(|owner| owner.to_string()),
+        "owner_is_contract": owner_is_contract
+    }))
+}
+
+// Share of a mainnet token's supply held by its 10 largest holders, from Ethplorer
+// (ETHPLORER_API_KEY, the public "freekey" otherwise)
+async fn holder_concentration(chain_id: u64, address: Address) -> Option<f64> {
+    if chain_id != 1 {
+        return None;
+    }
+    let key = std::env::var("ETHPLORER_API_KEY").unwrap_or_else(|_| "freekey".to_string());
+    let response: Value = reqwest::Client::new()
+        .get(format!("{}/getTopTokenHolders/{}", ETHPLORER_URL, address))
+        .query(&[("apiKey", key.as_str()), ("limit", "10")])
+        .send()
+        .await
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    let holders = response.get("holders")?.as_array()?;
+    Some(holders.iter().filter_map(|holder| holder.get("share")?.as_f64()).sum())
+}
+
+// Score how risky a first interaction with `address` is, from 0 (nothing found) to 100 (listed as
+// a scam), with the reasons behind it. `chain_id` is the chain to look the contract up on.
+pub async fn assess(
+    provider: &RootProvider<AnyNetwork>,
+    chain_id: u64,
+    address: Address,
+    scam_lists: &HashMap<Address, String>,
+    now: u64,
+) -> Result<Value, String> {
+    let listed = scam_lists.get(&address);
+    let code = provider.get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    if code.is_empty() {
+        let (score, reasons) = match listed {
+            Some(list) => (100, vec![format!("listed as a scam address by {}", list)]),
+            None => (0, vec![]),
+        };
+        return Ok(json!({
+            "success": true,
+            "address": address.to_string(),
+            "is_contract": false,
+            "score": score,
+            "level": level(score),
+            "reasons": reasons,
+            "scam_list": { "checked": !scam_lists.is_empty(), "listed": listed.is_some(), "list": listed }
+        }));
+    }
+
+    let verification = verification(chain_id, address).await;
+    let deployment = deployment(provider, chain_id, address, now).await;
+    let control = control(provider, address).await?;
+    let top_holders_pct = holder_concentration(chain_id, address).await;
+
+    let mut score: u64 = 0;
+    let mut reasons = vec![];
+    if let Some(list) = listed {
+        score += 100;
+        reasons.push(format!("listed as a scam address by {}", list));
+    }
+    match verification.get("verified").and_then(Value::as_bool) {
+        Some(true) => {}
+        Some(false) => {
+            score += 30;
+            reasons.push("source code is not verified".to_string());
+        }
+        None => {
+            score += 10;
+            reasons.push("verification status is unknown".to_string());
+        }
+    }
+    match deployment.get("age_days").and_then(Value::as_u64) {
+        Some(days) if days < NEW_CONTRACT_DAYS => {
+            score += 25;
+            reasons.push(format!("deployed {} day(s) ago", days));
+        }
+        Some(days) if days < YOUNG_CONTRACT_DAYS => {
+            score += 10;
+            reasons.push(format!("deployed {} days ago", days));
+        }
+        Some(_) => {}
+        None => {
+            score += 5;
+            reasons.push("deployment age is unknown".to_string());
+        }
+    }
+    if !control["proxy"].is_null() {
+        // An account can swap the code behind the proxy at any time, a contract admin is usually a
+        // multisig or timelock
+        match control["admin_is_contract"].as_bool() {
+            Some(false) => {
+                score += 20;
+                reasons.push("upgradeable by a single account".to_string());
+            }
+            _ => {
+                score += 10;
+                reasons.push("upgradeable proxy".to_string());
+            }
+        }
+    }
+    if control["owner_is_contract"].as_bool() == Some(false) {
+        score += 5;
+        reasons.push("owned by a single account".to_string());
+    }
+    if let Some(share) = top_holders_pct.filter(|share| *share > CONCENTRATED_SUPPLY_PCT) {
+        score += 15;
+        reasons.push(format!("top 10 holders own {:.1}% of the supply", share));
+    }
+    let score = score.min(100);
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "is_contract": true,
+        "score": score,
+        "level": level(score),
+        "reasons": reasons,
+        "verification": verification,
+        "deployment": deployment,
+        "control": control,
+        "holders": { "top10_share_pct": top_holders_pct },
+        "scam_list": { "checked": !scam_lists.is_empty(), "listed": listed.is_some(), "list": listed }
+    }))
+}
+
+fn level(score: u64) -> &'static str {
+    match score {
+        0..=39 => "low",
+        40..=69 => "medium",
+        70..=99 => "high",
+        _ => "critical",
+    }
+}
@@ -0,0 +1,129 @@
+use alloy::signers::{local::PrivateKeySigner, Signer};
+use alloy::sol;
+use alloy::sol_types::{eip712_domain, SolStruct};
+use alloy_primitives::{Address, Bytes, B256, U256};
+use serde_json::json;
+use std::str::FromStr;
+
+sol! {
+    #[sol(rpc)]
+    contract Safe {
+        function getOwners() external view returns (address[] memory);
+        function getThreshold() external view returns (uint256);
+        function nonce() external view returns (uint256);
+        function VERSION() external view returns (string memory);
+        function isOwner(address owner) external view returns (bool);
+    }
+
+    // EIP-712 message owners sign, Safe 1.3 and later
+    struct SafeTx {
+        address to;
+        uint256 value;
+        bytes data;
+        uint8 operation;
+        uint256 safeTxGas;
+        uint256 baseGas;
+        uint256 gasPrice;
+        address gasToken;
+        address refundReceiver;
+        uint256 nonce;
+    }
+}
+
+// Safe Transaction Service per chain, SAFE_TX_SERVICE_URL overrides it (e.g. for a local service)
+pub fn transaction_service_url(chain_id: u64) -> Option<String> {
+    if let Ok(url) = std::env::var("SAFE_TX_SERVICE_URL") {
+        if !url.is_empty() {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+    }
+
+    let network = match chain_id {
+        1 => "mainnet",
+        10 => "optimism",
+        100 => "gnosis-chain",
+        137 => "polygon",
+        8453 => "base",
+        42161 => "arbitrum",
+        11155111 => "sepolia",
+        _ => return None,
+    };
+    Some(format!("https://safe-transaction-{}.safe.global", network))
+}
+
+// A call from the Safe with no gas refund, the only kind the agent proposes. Delegate calls run
+// arbitrary code in the Safe's context and are never built here.
+pub fn safe_tx(to: Address, value: U256, data: Bytes, nonce: U256) -> SafeTx {
+    SafeTx {
+        to,
+        value,
+        data,
+        operation: 0,
+        safeTxGas: U256::ZERO,
+        baseGas: U256::ZERO,
+        gasPrice: U256::ZERO,
+        gasToken: Address::ZERO,
+        refundReceiver: Address::ZERO,
+        nonce,
+    }
+}
+
+pub fn safe_tx_hash(tx: &SafeTx, safe: Address, chain_id: u64) -> B256 {
+    let domain = eip712_domain! {
+        chain_id: chain_id,
+        verifying_contract: safe,
+    };
+    tx.eip712_signing_hash(&domain)
+}
+
+// The same message in eth_signTypedData_v4 form, for nodes holding the proposer's key
+pub fn typed_data(tx: &SafeTx, safe: Address, chain_id: u64) -> serde_json::Value {
+    json!({
+        "types": {
+            "EIP712Domain": [
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "SafeTx": [
+                { "name": "to", "type": "address" },
+                { "name": "value", "type": "uint256" },
+                { "name": "data", "type": "bytes" },
+                { "name": "operation", "type": "uint8" },
+                { "name": "safeTxGas", "type": "uint256" },
+                { "name": "baseGas", "type": "uint256" },
+                { "name": "gasPrice", "type": "uint256" },
+                { "name": "gasToken", "type": "address" },
+                { "name": "refundReceiver", "type": "address" },
+                { "name": "nonce", "type": "uint256" }
+            ]
+        },
+        "primaryType": "SafeTx",
+        "domain": { "chainId": chain_id, "verifyingContract": safe.to_string() },
+        "message": {
+            "to": tx.to.to_string(),
+            "value": tx.value.to_string(),
+            "data": tx.data.to_string(),
+            "operation": tx.operation,
+            "safeTxGas": "0",
+            "baseGas": "0",
+            "gasPrice": "0",
+            "gasToken": Address::ZERO.to_string(),
+            "refundReceiver": Address::ZERO.to_string(),
+            "nonce": tx.nonce.to_string()
+        }
+    })
+}
+
+// Sign with SAFE_PROPOSER_PRIVATE_KEY when it belongs to `proposer`. None means the node has to sign.
+pub async fn sign_locally(hash: B256, proposer: Address) -> Result<Option<String>, String> {
+    let Ok(key) = std::env::var("SAFE_PROPOSER_PRIVATE_KEY") else {
+        return Ok(None);
+    };
+    let signer = PrivateKeySigner::from_str(key.trim()).map_err(|e| format!("Invalid SAFE_PROPOSER_PRIVATE_KEY: {}", e))?;
+    if signer.address() != proposer {
+        return Ok(None);
+    }
+
+    let signature = signer.sign_hash(&hash).await.map_err(|e| format!("Failed to sign the Safe transaction: {}", e))?;
+    Ok(Some(format!("0x{}", hex::encode(signature.as_bytes()))))
+}
@@ -0,0 +1,199 @@
+use alloy::dyn_abi::{DynSolType, EventExt};
+use alloy::json_abi::Event;
+use alloy_primitives::{keccak256, Address, B256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use alloy_rpc_types::{Filter, Log};
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+use rmcp::service::{Peer, RoleServer};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::interface;
+
+// Blocks per eth_getLogs request to start with, halved whenever the node refuses a range
+const INITIAL_CHUNK: u64 = 2000;
+// Blocks near the head that can still be reorged away, scanned again at the end
+const REORG_DEPTH: u64 = 12;
+pub const DEFAULT_MAX_EVENTS: usize = 1000;
+
+pub struct Scan {
+    pub event: Event,
+    pub address: Option<Address>,
+    // Topic filters of the indexed parameters, in order, `None` for any value
+    pub topics: Vec<Option<B256>>,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub max_events: usize,
+}
+
+// Sends the scan's progress to the client when it asked for it
+pub struct Progress {
+    pub peer: Peer<RoleServer>,
+    pub token: Option<ProgressToken>,
+}
+
+impl Progress {
+    async fn report(&self, done: u64, total: u64, message: String) {
+        let Some(token) = self.token.clone() else {
+            return;
+        };
+        let _ = self
+            .peer
+            .notify_progress(ProgressNotificationParam {
+                progress_token: token,
+                progress: u32::try_from(done).unwrap_or(u32::MAX).into(),
+                total: Some(u32::try_from(total).unwrap_or(u32::MAX).into()),
+                message: Some(message),
+            })
+            .await;
+    }
+}
+
+// "Transfer(address indexed from, address indexed to, uint256 value)", with or without `event`
+pub fn parse_event(signature: &str) -> Result<Event, String> {
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("event ").unwrap_or(signature);
+    Event::parse(signature).map_err(|e| format!("Invalid event signature {}: {}", signature, e))
+}
+
+// Topic of an indexed parameter's value: the value itself for static types, its hash for
+// strings, bytes, arrays and structs
+pub fn topics(event: &Event, filters: &BTreeMap<String, String>) -> Result<Vec<Option<B256>>, String> {
+    let indexed: Vec<_> = event.inputs.iter().filter(|input| input.indexed).collect();
+    if let Some(name) = filters.keys().find(|name| !indexed.iter().any(|input| &input.name == *name)) {
+        return Err(format!("{} is not an indexed parameter of {}", name, event.name));
+    }
+
+    indexed
+        .iter()
+        .map(|input| {
+            let Some(value) = filters.get(&input.name) else {
+                return Ok(None);
+            };
+            let ty = DynSolType::parse(&input.selector_type()).map_err(|e| format!("Unsupported type {}: {}", input.ty, e))?;
+            let value = ty.coerce_str(value).map_err(|e| format!("Invalid {} filter {}: {}", input.name, value, e))?;
+            Ok(Some(match value.as_word() {
+                Some(word) => word,
+                None => keccak256(value.abi_encode_packed()),
+            }))
+        })
+        .collect()
+}
+
+fn filter(scan: &Scan, from: u64, to: u64) -> Filter {
+    let mut filter = Filter::new().from_block(from).to_block(to);
+    if let Some(address) = scan.address {
+        filter = filter.address(address);
+    }
+    if !scan.event.anonymous {
+        filter = filter.event_signature(scan.event.selector());
+    }
+    for (position, topic) in scan.topics.iter().enumerate() {
+        if let Some(topic) = topic {
+            filter = match position {
+                0 => filter.topic1(*topic),
+                1 => filter.topic2(*topic),
+                _ => filter.topic3(*topic),
+            };
+        }
+    }
+    filter
+}
+
+// Logs of `from..=to`, in smaller chunks while the node refuses a range as too large or too busy
+async fn logs(provider: &RootProvider<AnyNetwork>, scan: &Scan, from: u64, to: u64, chunk: &mut u64) -> Result<Vec<Log>, String> {
+    let mut found = vec![];
+    let mut start = from;
+    while start <= to {
+        let end = to.min(start + *chunk - 1);
+        match provider.get_logs(&filter(scan, start, end)).await {
+            Ok(logs) => {
+                found.extend(logs);
+                start = end + 1;
+            }
+            Err(e) if *chunk == 1 => return Err(format!("Failed to get logs of block {}: {}", start, e)),
+            Err(_) => *chunk = (*chunk / 2).max(1),
+        }
+    }
+    Ok(found)
+}
+
+fn decode(event: &Event, log: &Log) -> Value {
+    let args = match event.decode_log(&log.inner.data) {
+        Ok(decoded) => {
+            let mut indexed = decoded.indexed.iter();
+            let mut body = decoded.body.iter();
+            let mut args = serde_json::Map::new();
+            for (position, input) in event.inputs.iter().enumerate() {
+                let value = if input.indexed { indexed.next() } else { body.next() };
+                let name = if input.name.is_empty() { format!("arg{}", position) } else { input.name.clone() };
+                args.insert(name, value.map(interface::to_json).unwrap_or_default());
+            }
+            Value::Object(args)
+        }
+        // Same topic0 with a different layout, e.g. ERC-721 vs ERC-20 Transfer
+        Err(_) => Value::Null,
+    };
+    json!({
+        "block_number": log.block_number,
+        "transaction_hash": log.transaction_hash.map(|hash| hash.to_string()),
+        "log_index": log.log_index,
+        "address": log.inner.address.to_string(),
+        "args": args
+    })
+}
+
+// Every matching event of the range, requested in chunks with progress after each one. Blocks
+// close to the head are scanned again at the end so events of reorged blocks are replaced.
+pub async fn scan(provider: &RootProvider<AnyNetwork>, scan: &Scan, progress: &Progress) -> Result<Value, String> {
+    let head = provider.get_block_number().await.map_err(|e| format!("Failed to get the block number: {}", e))?;
+    let to_block = scan.to_block.min(head);
+    if scan.from_block > to_block {
+        return Err(format!("from_block {} is after to_block {}", scan.from_block, to_block));
+    }
+    let total = to_block - scan.from_block + 1;
+
+    let mut found: Vec<Log> = vec![];
+    let mut chunk = INITIAL_CHUNK;
+    let mut next = scan.from_block;
+    while next <= to_block && found.len() < scan.max_events {
+        let end = to_block.min(next + chunk - 1);
+        found.extend(logs(provider, scan, next, end, &mut chunk).await?);
+        next = end + 1;
+        let message = format!("Scanned blocks {}-{}, {} events so far", scan.from_block, end, found.len());
+        progress.report(next - scan.from_block, total, message).await;
+    }
+
+    let unsafe_from = head.saturating_sub(REORG_DEPTH).max(scan.from_block);
+    if unsafe_from < next {
+        let rescanned = logs(provider, scan, unsafe_from, next - 1, &mut chunk).await?;
+        found.retain(|log| log.block_number.is_some_and(|number| number < unsafe_from));
+        found.extend(rescanned);
+    }
+
+    // Reorgs can also hand back the same log twice, or flag one as removed
+    let mut seen = HashSet::new();
+    found.retain(|log| !log.removed && seen.insert((log.transaction_hash, log.log_index)));
+    found.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // Stop at a block boundary so resuming from next_from_block neither repeats nor skips events,
+    // unless a single block holds more than the limit
+    let mut next_from_block = (next <= to_block).then_some(next);
+    if found.len() > scan.max_events {
+        let cut = found[scan.max_events].block_number;
+        next_from_block = cut;
+        let whole_blocks = found.iter().take_while(|log| log.block_number < cut).count();
+        found.truncate(if whole_blocks > 0 { whole_blocks } else { scan.max_events });
+    }
+
+    Ok(json!({
+        "success": true,
+        "event": scan.event.signature(),
+        "from_block": scan.from_block,
+        "to_block": to_block,
+        "count": found.len(),
+        "truncated": next_from_block.is_some(),
+        "next_from_block": next_from_block,
+        "events": found.iter().map(|log| decode(&scan.event, log)).collect::<Vec<_>>()
+    }))
+}
@@ -0,0 +1,220 @@
+use alloy::signers::{local::PrivateKeySigner, Signer};
+use alloy_primitives::Address;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::str::FromStr;
+
+// EIP-4361 nonces are at least 8 alphanumeric characters
+const MIN_NONCE_LENGTH: usize = 8;
+
+// The fields of an EIP-4361 message, rendered in the order the spec fixes
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: Address,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+pub fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl SiweMessage {
+    pub fn render(&self) -> String {
+        let mut message = format!(
+            "{} wants you to sign in with your Ethereum account:\n{}\n\n",
+            self.domain,
+            self.address.to_checksum(None)
+        );
+        if let Some(statement) = &self.statement {
+            message.push_str(&format!("{}\n", statement));
+        }
+        message.push_str(&format!(
+            "\nURI: {}\nVersion: 1\nChain ID: {}\nNonce: {}\nIssued At: {}",
+            self.uri,
+            self.chain_id,
+            self.nonce,
+            timestamp(&self.issued_at)
+        ));
+        if let Some(expiration_time) = &self.expiration_time {
+            message.push_str(&format!("\nExpiration Time: {}", timestamp(expiration_time)));
+        }
+        if let Some(not_before) = &self.not_before {
+            message.push_str(&format!("\nNot Before: {}", timestamp(not_before)));
+        }
+        if let Some(request_id) = &self.request_id {
+            message.push_str(&format!("\nRequest ID: {}", request_id));
+        }
+        if !self.resources.is_empty() {
+            message.push_str("\nResources:");
+            for resource in &self.resources {
+                message.push_str(&format!("\n- {}", resource));
+            }
+        }
+        message
+    }
+}
+
+// A fresh random nonce, 32 hex characters
+pub fn new_nonce() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+// The domain is an RFC 3986 authority: a host with an optional port, no scheme or path
+pub fn check_domain(domain: &str) -> Result<(), String> {
+    if domain.is_empty() || domain.contains("://") || domain.contains(['/', ' ', '\n']) {
+        return Err(format!("Invalid domain {}, use the host the user signs in to, e.g. example.com", domain));
+    }
+    Ok(())
+}
+
+pub fn check_uri(uri: &str) -> Result<(), String> {
+    match uri.split_once(':') {
+        Some((scheme, rest)) if !scheme.is_empty() && !rest.is_empty() && !uri.contains(char::is_whitespace) => Ok(()),
+        _ => Err(format!("Invalid URI {}, use an absolute URI like https://example.com/login", uri)),
+    }
+}
+
+pub fn check_nonce(nonce: &str) -> Result<(), String> {
+    if nonce.len() < MIN_NONCE_LENGTH || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid nonce {}, it takes at least {} letters and digits", nonce, MIN_NONCE_LENGTH));
+    }
+    Ok(())
+}
+
+// Statements are a single line of text, the message format has no escaping
+pub fn check_statement(statement: &str) -> Result<(), String> {
+    if statement.contains('\n') {
+        return Err("The statement must fit on one line".to_string());
+    }
+    Ok(())
+}
+
+// Sign `message` (EIP-191) with SIWE_SIGNER_PRIVATE_KEY when that key is `address`'s, `None` means
+// the node has to sign it
+pub async fn sign_locally(message: &str, address: Address) -> Result<Option<String>, String> {
+    let Ok(key) = std::env::var("SIWE_SIGNER_PRIVATE_KEY") else {
+        return Ok(None);
+    };
+    let signer = PrivateKeySigner::from_str(key.trim()).map_err(|e| format!("Invalid SIWE_SIGNER_PRIVATE_KEY: {}", e))?;
+    if signer.address() != address {
+        return Ok(None);
+    }
+
+    let signature = signer
+        .sign_message(message.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to sign the message: {}", e))?;
+    Ok(Some(format!("0x{}", hex::encode(signature.as_bytes()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::Signature;
+
+    // Anvil's first account
+    const SIGNER_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn message(statement: Option<&str>, resources: Vec<String>) -> SiweMessage {
+        SiweMessage {
+            domain: "service.invalid".to_string(),
+            address: Address::from_str("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2").unwrap(),
+            statement: statement.map(str::to_string),
+            uri: "https://service.invalid/login".to_string(),
+            chain_id: 1,
+            nonce: "32891756".to_string(),
+            issued_at: DateTime::parse_from_rfc3339("2021-09-30T16:25:24Z").unwrap().with_timezone(&Utc),
+            expiration_time: None,
+            not_before: None,
+            request_id: None,
+            resources,
+        }
+    }
+
+    #[test]
+    fn renders_the_eip_4361_example() {
+        let message = message(
+            Some("I accept the ServiceOrg Terms of Service: https://service.invalid/tos"),
+            vec![
+                "ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/".to_string(),
+                "https://example.com/my-web2-claim.json".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            message.render(),
+            "service.invalid wants you to sign in with your Ethereum account:\n\
+             0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\
+             \n\
+             I accept the ServiceOrg Terms of Service: https://service.invalid/tos\n\
+             \n\
+             URI: https://service.invalid/login\n\
+             Version: 1\n\
+             Chain ID: 1\n\
+             Nonce: 32891756\n\
+             Issued At: 2021-09-30T16:25:24Z\n\
+             Resources:\n\
+             - ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/\n\
+             - https://example.com/my-web2-claim.json"
+        );
+    }
+
+    #[test]
+    fn renders_optional_fields_in_order() {
+        let mut message = message(None, vec![]);
+        message.expiration_time = Some(message.issued_at + chrono::Duration::hours(1));
+        message.not_before = Some(message.issued_at);
+        message.request_id = Some("login-1".to_string());
+
+        assert_eq!(
+            message.render(),
+            "service.invalid wants you to sign in with your Ethereum account:\n\
+             0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\
+             \n\
+             \n\
+             URI: https://service.invalid/login\n\
+             Version: 1\n\
+             Chain ID: 1\n\
+             Nonce: 32891756\n\
+             Issued At: 2021-09-30T16:25:24Z\n\
+             Expiration Time: 2021-09-30T17:25:24Z\n\
+             Not Before: 2021-09-30T16:25:24Z\n\
+             Request ID: login-1"
+        );
+    }
+
+    #[test]
+    fn checks_fields_that_would_break_the_format() {
+        assert!(check_domain("example.com:8443").is_ok());
+        assert!(check_domain("https://example.com").is_err());
+        assert!(check_domain("example.com/login").is_err());
+        assert!(check_uri("https://example.com/login").is_ok());
+        assert!(check_uri("example.com").is_err());
+        assert!(check_nonce(&new_nonce()).is_ok());
+        assert!(check_nonce("1234567").is_err());
+        assert!(check_nonce("abc-defgh").is_err());
+        assert!(check_statement("Sign in\nURI: https://evil.invalid").is_err());
+    }
+
+    #[tokio::test]
+    async fn signs_locally_for_the_signer_address_only() {
+        std::env::set_var("SIWE_SIGNER_PRIVATE_KEY", SIGNER_KEY);
+        let signer = PrivateKeySigner::from_str(SIGNER_KEY).unwrap();
+        let rendered = message(None, vec![]).render();
+
+        let signature = sign_locally(&rendered, signer.address()).await.unwrap().unwrap();
+        let other = sign_locally(&rendered, Address::ZERO).await.unwrap();
+
+        let signature = hex::decode(signature.trim_start_matches("0x")).unwrap();
+        let signature = Signature::try_from(signature.as_slice()).unwrap();
+        assert_eq!(signature.recover_address_from_msg(&rendered).unwrap(), signer.address());
+        assert_eq!(other, None);
+    }
+}
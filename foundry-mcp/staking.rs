@@ -0,0 +1,246 @@
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy_primitives::{keccak256, Address, U256};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+sol! {
+    #[sol(rpc)]
+    contract Lido {
+        function submit(address referral) external payable returns (uint256);
+        function isStakingPaused() external view returns (bool);
+        function getCurrentStakeLimit() external view returns (uint256);
+    }
+
+    #[sol(rpc)]
+    contract WithdrawalQueue {
+        struct WithdrawalRequestStatus {
+            uint256 amountOfStETH;
+            uint256 amountOfShares;
+            address owner;
+            uint256 timestamp;
+            bool isFinalized;
+            bool isClaimed;
+        }
+
+        function requestWithdrawals(uint256[] amounts, address owner) external returns (uint256[] requestIds);
+        function claimWithdrawal(uint256 requestId) external;
+        function getWithdrawalRequests(address owner) external view returns (uint256[] requestIds);
+        function getWithdrawalStatus(uint256[] requestIds) external view returns (WithdrawalRequestStatus[] statuses);
+        function getLastFinalizedRequestId() external view returns (uint256);
+    }
+
+    #[sol(rpc)]
+    contract RocketStorage {
+        function getAddress(bytes32 key) external view returns (address);
+    }
+
+    #[sol(rpc)]
+    contract RocketDepositPool {
+        function deposit() external payable;
+        function getMaximumDepositAmount() external view returns (uint256);
+    }
+
+    #[sol(rpc)]
+    contract RocketTokenRETH {
+        function burn(uint256 rethAmount) external;
+        function getRethValue(uint256 ethAmount) external view returns (uint256);
+        function getEthValue(uint256 rethAmount) external view returns (uint256);
+        function getTotalCollateral() external view returns (uint256);
+    }
+}
+
+pub const PROTOCOLS: &[&str] = &["lido", "rocket_pool"];
+
+// Mainnet deployments, forks keep them
+const LIDO_STETH: &str = "0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84";
+const LIDO_WITHDRAWAL_QUEUE: &str = "0x889edC2eDab5f40e902b864aD4d7AdE8E412F9B1";
+const ROCKET_STORAGE: &str = "0x1d8f8f00cfa6758d7bE78336684788Fb0ee0Fa46";
+
+// Lido takes withdrawal requests of 100 wei up to 1000 stETH each
+const LIDO_MIN_WITHDRAWAL: u128 = 100;
+const LIDO_MAX_WITHDRAWAL: u128 = 1000 * 10u128.pow(18);
+
+fn address(address: &str) -> Address {
+    Address::from_str(address).expect("valid address")
+}
+
+// Rocket Pool contracts move on upgrades, RocketStorage knows the current ones
+async fn rocket_contract(provider: &RootProvider<AnyNetwork>, name: &str) -> Result<Address, String> {
+    let key = keccak256(format!("contract.address{}", name));
+    let contract = RocketStorage::new(address(ROCKET_STORAGE), provider)
+        .getAddress(key)
+        .call()
+        .await
+        .map_err(|e| format!("Failed to look up Rocket Pool's {}: {}", name, e))?;
+    if contract.is_zero() {
+        return Err(format!("Rocket Pool has no {} on this chain", name));
+    }
+    Ok(contract)
+}
+
+fn transaction(from: Address, to: Address, value: U256, data: Vec<u8>) -> Value {
+    json!({
+        "from": from.to_string(),
+        "to": to.to_string(),
+        "value": value.to_string(),
+        "data": format!("0x{}", hex::encode(data))
+    })
+}
+
+// Stake `amount` wei of ETH: Lido mints stETH 1:1, Rocket Pool mints rETH at its exchange rate
+pub async fn stake(provider: &RootProvider<AnyNetwork>, protocol: &str, from: Address, amount: U256) -> Result<Value, String> {
+    match protocol {
+        "lido" => {
+            let lido = Lido::new(address(LIDO_STETH), provider);
+            if lido.isStakingPaused().call().await.map_err(|e| format!("Failed to read Lido: {}", e))? {
+                return Err("Lido staking is paused".to_string());
+            }
+            let limit = lido.getCurrentStakeLimit().call().await.map_err(|e| format!("Failed to read Lido: {}", e))?;
+            if amount > limit {
+                return Err(format!("Lido accepts at most {} wei right now", limit));
+            }
+            let data = Lido::submitCall { referral: Address::ZERO }.abi_encode();
+            Ok(json!({
+                "success": true,
+                "protocol": "lido",
+                "action": "stake",
+                "amount": amount.to_string(),
+                "receive_token": LIDO_STETH,
+                "receive_symbol": "stETH",
+                "expected_amount": amount.to_string(),
+                "transaction": transaction(from, address(LIDO_STETH), amount, data)
+            }))
+        }
+        "rocket_pool" => {
+            let pool = rocket_contract(provider, "rocketDepositPool").await?;
+            let reth = rocket_contract(provider, "rocketTokenRETH").await?;
+            let maximum = RocketDepositPool::new(pool, provider)
+                .getMaximumDepositAmount()
+                .call()
+                .await
+                .map_err(|e| format!("Failed to read the Rocket Pool deposit pool: {}", e))?;
+            if amount > maximum {
+                return Err(format!("The Rocket Pool deposit pool accepts at most {} wei right now", maximum));
+            }
+            let expected = RocketTokenRETH::new(reth, provider)
+                .getRethValue(amount)
+                .call()
+                .await
+                .map_err(|e| format!("Failed to read the rETH exchange rate: {}", e))?;
+            Ok(json!({
+                "success": true,
+                "protocol": "rocket_pool",
+                "action": "stake",
+                "amount": amount.to_string(),
+                "receive_token": reth.to_string(),
+                "receive_symbol": "rETH",
+                // Before the deposit fee, which Rocket Pool takes from the minted rETH
+                "expected_amount": expected.to_string(),
+                "transaction": transaction(from, pool, amount, RocketDepositPool::depositCall {}.abi_encode())
+            }))
+        }
+        _ => Err(format!("Unknown staking protocol {}, use one of {}", protocol, PROTOCOLS.join(", "))),
+    }
+}
+
+// Unstake `amount` of the staked token: Lido queues a withdrawal request (an NFT claimed once it is
+// finalized, after an approval of the stETH to the queue), rETH is burnt for ETH right away when
+// Rocket Pool holds enough of it
+pub async fn unstake(provider: &RootProvider<AnyNetwork>, protocol: &str, from: Address, amount: U256) -> Result<Value, String> {
+    match protocol {
+        "lido" => {
+            if amount < U256::from(LIDO_MIN_WITHDRAWAL) || amount > U256::from(LIDO_MAX_WITHDRAWAL) {
+                return Err("Lido withdrawal requests take between 100 wei and 1000 stETH, split larger amounts".to_string());
+            }
+            let data = WithdrawalQueue::requestWithdrawalsCall {
+                amounts: vec![amount],
+                owner: from,
+            }
+            .abi_encode();
+            Ok(json!({
+                "success": true,
+                "protocol": "lido",
+                "action": "unstake",
+                "amount": amount.to_string(),
+                "approval_token": LIDO_STETH,
+                "approval_address": LIDO_WITHDRAWAL_QUEUE,
+                "message": "Approve the stETH to approval_address first. Claim the ETH with a claim once staking_withdrawals reports the request claimable, usually within days",
+                "transaction": transaction(from, address(LIDO_WITHDRAWAL_QUEUE), U256::ZERO, data)
+            }))
+        }
+        "rocket_pool" => {
+            let reth = rocket_contract(provider, "rocketTokenRETH").await?;
+            let token = RocketTokenRETH::new(reth, provider);
+            let read_error = |e: alloy::contract::Error| format!("Failed to read rETH: {}", e);
+            let expected = token.getEthValue(amount).call().await.map_err(read_error)?;
+            let collateral = token.getTotalCollateral().call().await.map_err(read_error)?;
+            if expected > collateral {
+                return Err(format!(
+                    "Rocket Pool only holds {} wei to pay out burnt rETH, swap the rETH on a DEX instead",
+                    collateral
+                ));
+            }
+            Ok(json!({
+                "success": true,
+                "protocol": "rocket_pool",
+                "action": "unstake",
+                "amount": amount.to_string(),
+                "expected_eth": expected.to_string(),
+                "transaction": transaction(from, reth, U256::ZERO, RocketTokenRETH::burnCall { rethAmount: amount }.abi_encode())
+            }))
+        }
+        _ => Err(format!("Unknown staking protocol {}, use one of {}", protocol, PROTOCOLS.join(", "))),
+    }
+}
+
+// Claim a finalized Lido withdrawal request
+pub fn claim(from: Address, request_id: U256) -> Value {
+    let data = WithdrawalQueue::claimWithdrawalCall { requestId: request_id }.abi_encode();
+    json!({
+        "success": true,
+        "protocol": "lido",
+        "action": "claim",
+        "request_id": request_id.to_string(),
+        "transaction": transaction(from, address(LIDO_WITHDRAWAL_QUEUE), U256::ZERO, data)
+    })
+}
+
+// Lido withdrawal requests of `owner`: pending ones wait for the oracle to finalize them, finalized
+// ones can be claimed once
+pub async fn withdrawal_status(provider: &RootProvider<AnyNetwork>, owner: Address) -> Result<Value, String> {
+    let queue = WithdrawalQueue::new(address(LIDO_WITHDRAWAL_QUEUE), provider);
+    let read_error = |e: alloy::contract::Error| format!("Failed to read the Lido withdrawal queue: {}", e);
+    let ids = queue.getWithdrawalRequests(owner).call().await.map_err(read_error)?;
+    let statuses = if ids.is_empty() {
+        vec![]
+    } else {
+        queue.getWithdrawalStatus(ids.clone()).call().await.map_err(read_error)?
+    };
+
+    let requests: Vec<Value> = ids
+        .iter()
+        .zip(statuses)
+        .map(|(id, status)| {
+            let state = match (status.isFinalized, status.isClaimed) {
+                (_, true) => "claimed",
+                (true, false) => "claimable",
+                (false, _) => "pending",
+            };
+            json!({
+                "request_id": id.to_string(),
+                "amount_steth": status.amountOfStETH.to_string(),
+                "requested_at": status.timestamp.to::<u64>(),
+                "status": state
+            })
+        })
+        .collect();
+    Ok(json!({
+        "success": true,
+        "protocol": "lido",
+        "owner": owner.to_string(),
+        "requests": requests,
+        "last_finalized_request_id": queue.getLastFinalizedRequestId().call().await.ok().map(|id| id.to_string())
+    }))
+}
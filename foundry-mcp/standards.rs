@@ -0,0 +1,192 @@
+use alloy::sol;
+use alloy_primitives::{b256, Address, Bytes, FixedBytes, B256, U256};
+use alloy_provider::{network::AnyNetwork, Provider, RootProvider};
+use serde_json::{json, Value};
+
+use crate::permit::ERC20Permit;
+
+sol! {
+    #[sol(rpc)]
+    contract Probe {
+        function supportsInterface(bytes4 interfaceId) external view returns (bool);
+
+        function totalSupply() external view returns (uint256);
+        function decimals() external view returns (uint8);
+        function balanceOf(address owner) external view returns (uint256);
+        function allowance(address owner, address spender) external view returns (uint256);
+
+        function asset() external view returns (address);
+        function totalAssets() external view returns (uint256);
+
+        function getOwners() external view returns (address[]);
+        function getThreshold() external view returns (uint256);
+
+        // Beacon proxies point at a beacon, the beacon knows the implementation
+        function implementation() external view returns (address);
+    }
+}
+
+// ERC-165 interface ids
+const ERC165: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+const INVALID_INTERFACE: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const INTERFACES: [(&str, [u8; 4]); 7] = [
+    ("erc721", [0x80, 0xac, 0x58, 0xcd]),
+    ("erc721_metadata", [0x5b, 0x5e, 0x13, 0x9f]),
+    ("erc721_enumerable", [0x78, 0x0e, 0x9d, 0x63]),
+    ("erc1155", [0xd9, 0xb6, 0x7a, 0x26]),
+    ("erc1155_metadata_uri", [0x0e, 0x89, 0x34, 0x1c]),
+    ("erc2981", [0x2a, 0x55, 0x20, 0x5a]),
+    ("access_control", [0x79, 0x65, 0xdb, 0x0b]),
+];
+
+// EIP-1967 slots: keccak256("eip1967.proxy.implementation") - 1 and keccak256("eip1967.proxy.beacon") - 1
+const IMPLEMENTATION_SLOT: B256 = b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+const BEACON_SLOT: B256 = b256!("a3f0ad74e5423aebfd80d3ef4346578335a9a72aeee59ff6cb3582b35133d50");
+// keccak256("eip1967.proxy.admin") - 1, who can upgrade a transparent proxy
+const ADMIN_SLOT: B256 = b256!("b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103");
+// EIP-1822 (UUPS before EIP-1967): keccak256("PROXIABLE")
+const PROXIABLE_SLOT: B256 = b256!("c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7");
+
+// EIP-1167 minimal proxy runtime code around the 20 byte implementation address
+const MINIMAL_PROXY_PREFIX: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const MINIMAL_PROXY_SUFFIX: [u8; 15] = [0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3];
+// EIP-7702 delegation designator of an EOA running a contract's code
+const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+async fn slot_address(provider: &RootProvider<AnyNetwork>, address: Address, slot: B256) -> Option<Address> {
+    let value = provider.get_storage_at(address, U256::from_be_bytes(slot.0)).await.ok()?;
+    Some(Address::from_word(B256::from(value))).filter(|address| !address.is_zero())
+}
+
+// Proxy kind and implementation, from the code itself or the standard storage slots
+pub async fn proxy(provider: &RootProvider<AnyNetwork>, address: Address, code: &Bytes) -> Option<Value> {
+    if code.len() == 45 && code.starts_with(&MINIMAL_PROXY_PREFIX) && code.ends_with(&MINIMAL_PROXY_SUFFIX) {
+        let implementation = Address::from_slice(&code[10..30]);
+        return Some(json!({ "kind": "eip1167", "implementation": implementation.to_string() }));
+    }
+    if let Some(implementation) = slot_address(provider, address, IMPLEMENTATION_SLOT).await {
+        return Some(json!({ "kind": "eip1967", "implementation": implementation.to_string() }));
+    }
+    if let Some(beacon) = slot_address(provider, address, BEACON_SLOT).await {
+        let implementation = Probe::new(beacon, provider.clone()).implementation().call().await.ok();
+        return Some(json!({
+            "kind": "eip1967_beacon",
+            "beacon": beacon.to_string(),
+            "implementation": implementation.map(|implementation| implementation.to_string())
+        }));
+    }
+    if let Some(implementation) = slot_address(provider, address, PROXIABLE_SLOT).await {
+        return Some(json!({ "kind": "eip1822", "implementation": implementation.to_string() }));
+    }
+    None
+}
+
+// Admin of an EIP-1967 transparent proxy, UUPS proxies upgrade through the implementation instead
+pub async fn proxy_admin(provider: &RootProvider<AnyNetwork>, address: Address) -> Option<Address> {
+    slot_address(provider, address, ADMIN_SLOT).await
+}
+
+// Tools that fit each detected standard, so the planner doesn't have to guess
+fn suggested_tools(standards: &[&str]) -> Vec<&'static str> {
+    let mut tools = vec![];
+    for standard in standards {
+        let fitting: &[&'static str] = match *standard {
+            "erc20" => &["erc20_balance", "resolve_token", "get_price"],
+            "erc2612" => &["sign_permit"],
+            "safe" => &["safe_info", "propose_safe_transaction"],
+            "eoa" => &["balance", "account_history"],
+            _ => &[],
+        };
+        for tool in fitting {
+            if !tools.contains(tool) {
+                tools.push(*tool);
+            }
+        }
+    }
+    tools
+}
+
+// Classify the code at `address` by probing ERC-165 and calling the getters each standard
+// requires. Calls go through proxies, so a proxy is classified by its implementation's standards.
+pub async fn detect(provider: &RootProvider<AnyNetwork>, address: Address) -> Result<Value, String> {
+    let code = provider.get_code_at(address).await.map_err(|e| format!("Failed to get the code: {}", e))?;
+    if code.is_empty() {
+        return Ok(json!({
+            "success": true,
+            "address": address.to_string(),
+            "is_contract": false,
+            "standards": ["eoa"],
+            "suggested_tools": suggested_tools(&["eoa"])
+        }));
+    }
+    if code.starts_with(&DELEGATION_PREFIX) && code.len() == 23 {
+        return Ok(json!({
+            "success": true,
+            "address": address.to_string(),
+            "is_contract": false,
+            "standards": ["eoa", "eip7702"],
+            "delegate": Address::from_slice(&code[3..]).to_string(),
+            "suggested_tools": suggested_tools(&["eoa"])
+        }));
+    }
+
+    let probe = Probe::new(address, provider.clone());
+    let mut standards = vec![];
+    let mut interfaces = serde_json::Map::new();
+    let mut vault_asset = None;
+
+    // A contract implements ERC-165 when it accepts its id and rejects 0xffffffff
+    let supports = |id: [u8; 4]| {
+        let probe = &probe;
+        async move { probe.supportsInterface(FixedBytes::new(id)).call().await.unwrap_or(false) }
+    };
+    let erc165 = supports(ERC165).await && !supports(INVALID_INTERFACE).await;
+    if erc165 {
+        standards.push("erc165");
+        for (name, id) in INTERFACES {
+            let supported = supports(id).await;
+            interfaces.insert(name.to_string(), json!(supported));
+            if supported && matches!(name, "erc721" | "erc1155" | "erc2981") {
+                standards.push(name);
+            }
+        }
+    }
+
+    // ERC-721 shares balanceOf and sometimes totalSupply with ERC-20, but has no decimals or allowance
+    let erc20 = !standards.contains(&"erc721")
+        && probe.totalSupply().call().await.is_ok()
+        && probe.decimals().call().await.is_ok()
+        && probe.balanceOf(Address::ZERO).call().await.is_ok()
+        && probe.allowance(Address::ZERO, Address::ZERO).call().await.is_ok();
+    if erc20 {
+        standards.push("erc20");
+        if ERC20Permit::new(address, provider.clone()).DOMAIN_SEPARATOR().call().await.is_ok() {
+            standards.push("erc2612");
+        }
+        if let (Ok(asset), Ok(_)) = (probe.asset().call().await, probe.totalAssets().call().await) {
+            standards.push("erc4626");
+            vault_asset = Some(asset.to_string());
+        }
+    }
+
+    if probe.getOwners().call().await.is_ok() && probe.getThreshold().call().await.is_ok() {
+        standards.push("safe");
+    }
+
+    let proxy = proxy(provider, address, &code).await;
+    if proxy.is_some() {
+        standards.push("proxy");
+    }
+
+    Ok(json!({
+        "success": true,
+        "address": address.to_string(),
+        "is_contract": true,
+        "standards": standards,
+        "interfaces": interfaces,
+        "proxy": proxy,
+        "vault_asset": vault_asset,
+        "code_size": code.len(),
+        "suggested_tools": suggested_tools(&standards)
+    }))
+}
@@ -0,0 +1,69 @@
+use alloy_primitives::Address;
+use serde::Deserialize;
+
+// Bundled list in the Uniswap token list format, covering the chains the agent is used on
+const BUNDLED_LIST: &str = include_str!("tokenlists/default.tokenlist.json");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    pub chain_id: u64,
+    pub address: Address,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenList {
+    tokens: Vec<TokenInfo>,
+}
+
+// The bundled list plus every list in TOKEN_LISTS, a comma separated list of file paths and URLs.
+// Lists that fail to load are skipped with a warning, so a broken source never hides the bundled tokens.
+pub async fn load() -> Vec<TokenInfo> {
+    let mut tokens = serde_json::from_str::<TokenList>(BUNDLED_LIST)
+        .map(|list| list.tokens)
+        .expect("bundled token list is valid");
+
+    let sources = std::env::var("TOKEN_LISTS").unwrap_or_default();
+    for source in sources.split(',').map(str::trim).filter(|source| !source.is_empty()) {
+        match fetch(source).await {
+            Ok(list) => tokens.extend(list.tokens),
+            Err(e) => tracing::warn!("Skipping token list {}: {}", source, e),
+        }
+    }
+
+    tokens
+}
+
+async fn fetch(source: &str) -> Result<TokenList, String> {
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        reqwest::get(source)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        tokio::fs::read_to_string(source).await.map_err(|e| e.to_string())?
+    };
+
+    serde_json::from_str(&content).map_err(|e| format!("not a token list: {}", e))
+}
+
+// Tokens on `chain_id` whose symbol matches, ignoring case. The same token can appear in
+// several lists, so duplicate addresses are dropped.
+pub fn resolve<'a>(tokens: &'a [TokenInfo], chain_id: u64, symbol: &str) -> Vec<&'a TokenInfo> {
+    let mut matches: Vec<&TokenInfo> = vec![];
+    for token in tokens {
+        if token.chain_id == chain_id
+            && token.symbol.eq_ignore_ascii_case(symbol.trim())
+            && !matches.iter().any(|known| known.address == token.address)
+        {
+            matches.push(token);
+        }
+    }
+    matches
+}
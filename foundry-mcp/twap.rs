@@ -0,0 +1,115 @@
+use alloy::sol;
+use alloy_primitives::{aliases::U24, Address};
+use alloy_provider::{network::AnyNetwork, RootProvider};
+use serde_json::json;
+use std::str::FromStr;
+
+sol! {
+    #[sol(rpc)]
+    contract UniswapV3Factory {
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool);
+    }
+
+    #[sol(rpc)]
+    contract UniswapV3Pool {
+        function observe(uint32[] calldata secondsAgos) external view returns (int56[] memory tickCumulatives, uint160[] memory secondsPerLiquidityCumulativeX128s);
+        function liquidity() external view returns (uint128);
+        function token0() external view returns (address);
+    }
+}
+
+// Fee tiers in hundredths of a bip, tried in turn when the caller doesn't pick one
+const FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+// Uniswap V3 factory of a chain, forks use their parent chain's
+pub fn factory(chain_id: u64) -> Option<Address> {
+    let address = match chain_id {
+        // Mainnet, Optimism, Polygon and Arbitrum share the original deployment
+        1 | 10 | 137 | 42161 => "0x1F98431c8aD98523631AE4a59f267346ea31F984",
+        8453 => "0x33128a8fC17869897dcE68Ed026d694621f6FDfD",
+        11155111 => "0x0227628f3F023bb0B980b67D528571c95c6DaC1c",
+        _ => return None,
+    };
+    Address::from_str(address).ok()
+}
+
+// Pool of the pair for `fee`, or the most liquid of the standard fee tiers
+async fn find_pool(
+    provider: &RootProvider<AnyNetwork>,
+    factory: Address,
+    token_a: Address,
+    token_b: Address,
+    fee: Option<u32>,
+) -> Result<(Address, u32), String> {
+    let fees = match fee {
+        Some(fee) => vec![fee],
+        None => FEE_TIERS.to_vec(),
+    };
+
+    let factory = UniswapV3Factory::new(factory, provider.clone());
+    let mut best: Option<(Address, u32, u128)> = None;
+    for fee in fees {
+        let pool = factory
+            .getPool(token_a, token_b, U24::from(fee))
+            .call()
+            .await
+            .map_err(|e| format!("Failed to look up the Uniswap V3 pool: {}", e))?;
+        if pool.is_zero() {
+            continue;
+        }
+        let liquidity = UniswapV3Pool::new(pool, provider.clone()).liquidity().call().await.unwrap_or_default();
+        if best.is_none_or(|(_, _, best_liquidity)| liquidity > best_liquidity) {
+            best = Some((pool, fee, liquidity));
+        }
+    }
+
+    best.map(|(pool, fee, _)| (pool, fee))
+        .ok_or_else(|| "No Uniswap V3 pool for this pair".to_string())
+}
+
+// Time-weighted average price of `token_a` in `token_b` over the last `window_secs`, from the
+// pool's tick accumulator. `chain_id` is the chain whose deployment to use.
+pub async fn twap(
+    provider: &RootProvider<AnyNetwork>,
+    chain_id: u64,
+    (token_a, decimals_a): (Address, u8),
+    (token_b, decimals_b): (Address, u8),
+    fee: Option<u32>,
+    window_secs: u32,
+) -> Result<serde_json::Value, String> {
+    let factory = factory(chain_id).ok_or_else(|| format!("No known Uniswap V3 deployment on chain {}", chain_id))?;
+    let (pool_address, fee) = find_pool(provider, factory, token_a, token_b, fee).await?;
+    let pool = UniswapV3Pool::new(pool_address, provider.clone());
+
+    let observations = pool
+        .observe(vec![window_secs, 0])
+        .call()
+        .await
+        // Pools only keep as many observations as their cardinality allows
+        .map_err(|e| format!("Pool has no observations over the last {}s, try a shorter window: {}", window_secs, e))?;
+    let [start, end] = observations.tickCumulatives.as_slice() else {
+        return Err("Unexpected observe response".to_string());
+    };
+    let start = i64::try_from(*start).map_err(|e| e.to_string())?;
+    let end = i64::try_from(*end).map_err(|e| e.to_string())?;
+    // Rounded towards negative infinity, like Uniswap's OracleLibrary
+    let tick = (end - start).div_euclid(i64::from(window_secs));
+
+    // Price of token0 in token1, in whole units
+    let token0 = pool.token0().call().await.map_err(|e| format!("Failed to read the pool's token0: {}", e))?;
+    let (decimals0, decimals1) = if token0 == token_a { (decimals_a, decimals_b) } else { (decimals_b, decimals_a) };
+    let price0 = 1.0001_f64.powf(tick as f64) * 10f64.powi(i32::from(decimals0) - i32::from(decimals1));
+    let price = if token0 == token_a { price0 } else { 1.0 / price0 };
+
+    Ok(json!({
+        "success": true,
+        "base": token_a.to_string(),
+        "quote": token_b.to_string(),
+        "price": format!("{}", price),
+        "pool": pool_address.to_string(),
+        "fee": fee,
+        "tick": tick,
+        "window_secs": window_secs,
+        "source": "uniswap-v3"
+    }))
+}
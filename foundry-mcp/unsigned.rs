@@ -0,0 +1,47 @@
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy_primitives::{Address, U256};
+use alloy_rpc_types::eth::TransactionRequest;
+use serde_json::{json, Value};
+
+sol! {
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+// JSON-RPC transaction object with hex quantities, the shape wallets take for eth_signTransaction
+pub fn transaction_json(tx: &TransactionRequest) -> Value {
+    let mut transaction = json!({
+        "chainId": tx.chain_id.map(|chain_id| format!("{:#x}", chain_id)),
+        "from": tx.from.map(|from| from.to_string()),
+        "to": tx.to.and_then(|to| to.to().copied()).map(|to| to.to_string()),
+        "value": format!("{:#x}", tx.value.unwrap_or_default()),
+        "data": format!("0x{}", hex::encode(tx.input.input().cloned().unwrap_or_default())),
+        "nonce": tx.nonce.map(|nonce| format!("{:#x}", nonce)),
+        "gas": tx.gas.map(|gas| format!("{:#x}", gas)),
+    });
+    match (tx.gas_price, tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(gas_price), _, _) => {
+            transaction["type"] = json!("0x0");
+            transaction["gasPrice"] = json!(format!("{:#x}", gas_price));
+        }
+        (None, Some(max_fee), Some(max_priority_fee)) => {
+            transaction["type"] = json!("0x2");
+            transaction["maxFeePerGas"] = json!(format!("{:#x}", max_fee));
+            transaction["maxPriorityFeePerGas"] = json!(format!("{:#x}", max_priority_fee));
+        }
+        _ => {}
+    }
+    transaction
+}
+
+// EIP-681 payment request for wallets that scan or open `ethereum:` links. It only has a form
+// for ETH and ERC20 transfers, other calls and deployments are exported as JSON alone.
+pub fn eip681(chain_id: u64, to: Option<Address>, value: U256, data: &[u8], gas: Option<u64>) -> Option<String> {
+    let to = to?;
+    let gas = gas.map(|gas| format!("&gasLimit={}", gas)).unwrap_or_default();
+    if data.is_empty() {
+        return Some(format!("ethereum:{}@{}?value={}{}", to, chain_id, value, gas));
+    }
+    let call = transferCall::abi_decode(data).ok().filter(|_| value.is_zero())?;
+    Some(format!("ethereum:{}@{}/transfer?address={}&uint256={}{}", to, chain_id, call.to, call.amount, gas))
+}
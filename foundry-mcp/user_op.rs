@@ -0,0 +1,232 @@
+use alloy::signers::{local::PrivateKeySigner, Signer};
+use alloy::sol;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+
+// ERC-4337 EntryPoint v0.7, the same address on every chain
+const ENTRY_POINT_V07: &str = "0x0000000071727De22E5E9d8BAf0edAc6f37da032";
+
+// Stands in for the owner's signature while the bundler estimates gas: the right length and
+// shape for an ECDSA check, so validation costs what it will with the real one
+pub const DUMMY_SIGNATURE: &str = "0xfffffffffffffffffffffffffffffff0000000000000000000000000000000007aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa1c";
+
+sol! {
+    #[sol(rpc)]
+    contract EntryPoint {
+        struct PackedUserOperation {
+            address sender;
+            uint256 nonce;
+            bytes initCode;
+            bytes callData;
+            bytes32 accountGasLimits;
+            uint256 preVerificationGas;
+            bytes32 gasFees;
+            bytes paymasterAndData;
+            bytes signature;
+        }
+
+        function getNonce(address sender, uint192 key) external view returns (uint256 nonce);
+        function getUserOpHash(PackedUserOperation calldata userOp) external view returns (bytes32);
+    }
+
+    // The call interface of SimpleAccount, LightAccount and most accounts derived from them
+    function execute(address dest, uint256 value, bytes calldata func);
+}
+
+// ENTRY_POINT_ADDRESS overrides the v0.7 EntryPoint, e.g. for a local deployment
+pub fn entry_point() -> Result<Address, String> {
+    let address = std::env::var("ENTRY_POINT_ADDRESS").unwrap_or_else(|_| ENTRY_POINT_V07.to_string());
+    Address::from_str(&address).map_err(|e| format!("Invalid ENTRY_POINT_ADDRESS: {}", e))
+}
+
+// A UserOperation in the unpacked v0.7 form bundlers take over JSON-RPC, quantities as hex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factory: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factory_data: Option<Bytes>,
+    pub call_data: Bytes,
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    // Set when a paymaster pays the gas instead of the account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paymaster: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paymaster_verification_gas_limit: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paymaster_post_op_gas_limit: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paymaster_data: Option<Bytes>,
+    pub signature: Bytes,
+}
+
+// Two 128-bit values in one word, the high one first
+fn pack_u128s(high: U256, low: U256) -> B256 {
+    let mut word = [0u8; 32];
+    word[..16].copy_from_slice(&high.to_be_bytes::<32>()[16..]);
+    word[16..].copy_from_slice(&low.to_be_bytes::<32>()[16..]);
+    B256::from(word)
+}
+
+impl UserOperation {
+    // The on-chain form the EntryPoint hashes and executes
+    pub fn packed(&self) -> EntryPoint::PackedUserOperation {
+        let mut init_code = vec![];
+        if let Some(factory) = self.factory {
+            init_code.extend_from_slice(factory.as_slice());
+            init_code.extend_from_slice(self.factory_data.as_deref().unwrap_or_default());
+        }
+        // paymaster, its verification and post-op gas limits as 16 bytes each, then its data
+        let mut paymaster_and_data = vec![];
+        if let Some(paymaster) = self.paymaster {
+            paymaster_and_data.extend_from_slice(paymaster.as_slice());
+            let limits = pack_u128s(
+                self.paymaster_verification_gas_limit.unwrap_or_default(),
+                self.paymaster_post_op_gas_limit.unwrap_or_default(),
+            );
+            paymaster_and_data.extend_from_slice(limits.as_slice());
+            paymaster_and_data.extend_from_slice(self.paymaster_data.as_deref().unwrap_or_default());
+        }
+        EntryPoint::PackedUserOperation {
+            sender: self.sender,
+            nonce: self.nonce,
+            initCode: init_code.into(),
+            callData: self.call_data.clone(),
+            accountGasLimits: pack_u128s(self.verification_gas_limit, self.call_gas_limit),
+            preVerificationGas: self.pre_verification_gas,
+            gasFees: pack_u128s(self.max_priority_fee_per_gas, self.max_fee_per_gas),
+            paymasterAndData: paymaster_and_data.into(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    // Most gas the operation can use, whoever pays it
+    pub fn max_gas(&self) -> U256 {
+        self.call_gas_limit
+            + self.verification_gas_limit
+            + self.pre_verification_gas
+            + self.paymaster_verification_gas_limit.unwrap_or_default()
+            + self.paymaster_post_op_gas_limit.unwrap_or_default()
+    }
+
+    pub fn max_gas_cost(&self) -> U256 {
+        self.max_gas() * self.max_fee_per_gas
+    }
+}
+
+// One JSON-RPC call to the service at the URL in `url_variable`
+async fn rpc_request(url_variable: &str, service: &str, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    let url = std::env::var(url_variable)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| format!("No {} configured, set {}", service, url_variable))?;
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", service, e))?
+        .json()
+        .await
+        .map_err(|e| format!("{} returned invalid JSON: {}", service, e))?;
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(|message| message.as_str()).unwrap_or("unknown error");
+        return Err(format!("{} rejected {}: {}", service, method, message));
+    }
+    Ok(response.get("result").cloned().unwrap_or_default())
+}
+
+pub async fn bundler_request(method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    rpc_request("BUNDLER_URL", "bundler", method, params).await
+}
+
+// What a paymaster agreed to pay for, shown before the operation is signed
+#[derive(Debug, Clone, Serialize)]
+pub struct Sponsorship {
+    pub paymaster: Address,
+    pub policy: Option<String>,
+    // Up to this much gas is paid by the paymaster, the account pays none of it
+    pub max_sponsored_cost: String,
+    pub valid_until: Option<u64>,
+    pub valid_after: Option<u64>,
+}
+
+// Ask the paymaster at PAYMASTER_URL to sponsor `user_operation` with pm_sponsorUserOperation,
+// under the PAYMASTER_POLICY_ID sponsorship policy when one is set. Fills in the paymaster
+// fields and the gas limits the paymaster's own estimate settled on.
+pub async fn sponsor(user_operation: &mut UserOperation, entry_point: Address) -> Result<Sponsorship, String> {
+    let policy = std::env::var("PAYMASTER_POLICY_ID").ok().filter(|policy| !policy.is_empty());
+    let mut params = vec![json!(user_operation), json!(entry_point.to_string())];
+    if let Some(policy) = &policy {
+        params.push(json!({ "sponsorshipPolicyId": policy }));
+    }
+    let result = rpc_request("PAYMASTER_URL", "paymaster", "pm_sponsorUserOperation", json!(params)).await?;
+
+    let paymaster = result
+        .get("paymaster")
+        .and_then(|paymaster| paymaster.as_str())
+        .and_then(|paymaster| Address::from_str(paymaster).ok())
+        .ok_or_else(|| "The paymaster declined to sponsor the UserOperation".to_string())?;
+    user_operation.paymaster = Some(paymaster);
+    user_operation.paymaster_verification_gas_limit = Some(quantity(&result, "paymasterVerificationGasLimit")?);
+    user_operation.paymaster_post_op_gas_limit = Some(quantity(&result, "paymasterPostOpGasLimit")?);
+    user_operation.paymaster_data = result
+        .get("paymasterData")
+        .and_then(|data| data.as_str())
+        .and_then(|data| Bytes::from_str(data).ok());
+    // Paymasters re-estimate with their own data in place, their limits are the ones they signed
+    for (name, limit) in [
+        ("callGasLimit", &mut user_operation.call_gas_limit),
+        ("verificationGasLimit", &mut user_operation.verification_gas_limit),
+        ("preVerificationGas", &mut user_operation.pre_verification_gas),
+    ] {
+        if let Ok(value) = quantity(&result, name) {
+            *limit = value;
+        }
+    }
+
+    let timestamp = |name: &str| quantity(&result, name).ok().and_then(|value| u64::try_from(value).ok());
+    Ok(Sponsorship {
+        paymaster,
+        policy,
+        max_sponsored_cost: user_operation.max_gas_cost().to_string(),
+        valid_until: timestamp("validUntil"),
+        valid_after: timestamp("validAfter"),
+    })
+}
+
+// A hex quantity from a bundler or paymaster response, e.g. a gas estimate
+pub fn quantity(value: &serde_json::Value, name: &str) -> Result<U256, String> {
+    value
+        .get(name)
+        .and_then(|quantity| quantity.as_str())
+        .and_then(|quantity| U256::from_str(quantity).ok())
+        .ok_or_else(|| format!("Response has no {}", name))
+}
+
+// Sign with USER_OP_SIGNER_PRIVATE_KEY when it belongs to `owner`, as an EIP-191 message over the
+// UserOperation hash like SimpleAccount checks it. None means the node has to sign.
+pub async fn sign_locally(hash: B256, owner: Address) -> Result<Option<String>, String> {
+    let Ok(key) = std::env::var("USER_OP_SIGNER_PRIVATE_KEY") else {
+        return Ok(None);
+    };
+    let signer = PrivateKeySigner::from_str(key.trim()).map_err(|e| format!("Invalid USER_OP_SIGNER_PRIVATE_KEY: {}", e))?;
+    if signer.address() != owner {
+        return Ok(None);
+    }
+
+    let signature = signer
+        .sign_message(hash.as_slice())
+        .await
+        .map_err(|e| format!("Failed to sign the UserOperation: {}", e))?;
+    Ok(Some(format!("0x{}", hex::encode(signature.as_bytes()))))
+}
@@ -0,0 +1,289 @@
+use alloy_primitives::{Address, Bytes};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SOURCIFY_URL: &str = "https://sourcify.dev/server";
+const ETHERSCAN_URL: &str = "https://api.etherscan.io/v2/api";
+
+// Big projects with via-IR can take a while, but a stuck solc download shouldn't hang the tool
+const BUILD_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Verified source in standard JSON form, with the contract to compare and the compiler it was built with
+pub struct VerifiedSource {
+    pub origin: &'static str,
+    pub input: Value,
+    pub path: String,
+    pub name: String,
+    pub compiler_version: String,
+}
+
+pub async fn sourcify_source(chain_id: u64, address: Address) -> Result<VerifiedSource, String> {
+    let response: Value = reqwest::Client::new()
+        .get(format!("{}/v2/contract/{}/{}", SOURCIFY_URL, chain_id, address))
+        .query(&[("fields", "stdJsonInput,compilation")])
+        .send()
+        .await
+        .map_err(|e| format!("Sourcify request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Sourcify response: {}", e))?;
+    if response.get("match").is_none_or(|matched| matched.is_null()) {
+        return Err("Contract is not verified on Sourcify".to_string());
+    }
+
+    let compilation = response.get("compilation").ok_or("Sourcify returned no compilation")?;
+    let (path, name) = compilation
+        .get("fullyQualifiedName")
+        .and_then(|name| name.as_str())
+        .and_then(|name| name.rsplit_once(':'))
+        .ok_or("Sourcify returned no contract name")?;
+    Ok(VerifiedSource {
+        origin: "sourcify",
+        input: response.get("stdJsonInput").cloned().ok_or("Sourcify returned no sources")?,
+        path: path.to_string(),
+        name: name.to_string(),
+        compiler_version: compilation.get("compilerVersion").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+    })
+}
+
+pub async fn etherscan_source(chain_id: u64, address: Address, key: &str) -> Result<VerifiedSource, String> {
+    let response: Value = reqwest::Client::new()
+        .get(ETHERSCAN_URL)
+        .query(&[
+            ("chainid", chain_id.to_string()),
+            ("module", "contract".to_string()),
+            ("action", "getsourcecode".to_string()),
+            ("address", address.to_string()),
+            ("apikey", key.to_string()),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Etherscan request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Etherscan response: {}", e))?;
+
+    let contract = response.pointer("/result/0").filter(|contract| contract.is_object()).ok_or_else(|| {
+        format!("Etherscan error: {}", response.get("result").and_then(|r| r.as_str()).unwrap_or("no result"))
+    })?;
+    let field = |name: &str| contract.get(name).and_then(|value| value.as_str()).unwrap_or_default();
+    let name = field("ContractName");
+    if field("SourceCode").is_empty() {
+        return Err("Contract is not verified on Etherscan".to_string());
+    }
+    if field("CompilerVersion").starts_with("vyper") {
+        return Err("Vyper contracts can't be recompiled with forge".to_string());
+    }
+    let input = etherscan_input(field("SourceCode"), name, field)?;
+    let path = contract_path(&input, name).ok_or_else(|| format!("Contract {} not found in the verified sources", name))?;
+
+    Ok(VerifiedSource {
+        origin: "etherscan",
+        input,
+        path,
+        name: name.to_string(),
+        compiler_version: field("CompilerVersion").trim_start_matches('v').to_string(),
+    })
+}
+
+// Etherscan stores either a whole standard JSON input wrapped in an extra pair of braces, a JSON
+// map of sources, or a single flattened file, with the compiler settings in separate fields
+fn etherscan_input<'a>(source: &str, name: &str, field: impl Fn(&str) -> &'a str) -> Result<Value, String> {
+    if let Some(input) = source.strip_prefix("{{").and_then(|source| source.strip_suffix("}}")) {
+        return serde_json::from_str(&format!("{{{}}}", input)).map_err(|e| format!("Invalid standard JSON input: {}", e));
+    }
+    let sources = if source.starts_with('{') {
+        serde_json::from_str(source).map_err(|e| format!("Invalid source map: {}", e))?
+    } else {
+        json!({ format!("{}.sol", name): { "content": source } })
+    };
+
+    let mut settings = json!({
+        "optimizer": {
+            "enabled": field("OptimizationUsed") == "1",
+            "runs": field("Runs").parse::<u64>().unwrap_or(200)
+        }
+    });
+    let evm_version = field("EVMVersion");
+    if !evm_version.is_empty() && !evm_version.eq_ignore_ascii_case("default") {
+        settings["evmVersion"] = json!(evm_version.to_lowercase());
+    }
+    Ok(json!({ "language": "Solidity", "sources": sources, "settings": settings }))
+}
+
+// File declaring `contract <name>`, Etherscan only records the bare name
+fn contract_path(input: &Value, name: &str) -> Option<String> {
+    let declaration = format!("contract {} ", name);
+    let sources = input.get("sources")?.as_object()?;
+    sources
+        .iter()
+        .find(|(_, source)| source.get("content").and_then(|c| c.as_str()).is_some_and(|c| c.contains(&declaration)))
+        .map(|(path, _)| path.clone())
+}
+
+// foundry.toml reproducing the recorded compiler settings
+fn foundry_config(source: &VerifiedSource) -> toml::Table {
+    let settings = source.input.get("settings").cloned().unwrap_or_default();
+    // "0.8.19+commit.7dd6d404" -> "0.8.19"
+    let version = source.compiler_version.split('+').next().unwrap_or_default();
+
+    let mut profile = toml::Table::new();
+    profile.insert("src".into(), ".".into());
+    profile.insert("out".into(), "out".into());
+    profile.insert("libs".into(), toml::Value::Array(vec![]));
+    profile.insert("auto_detect_remappings".into(), false.into());
+    profile.insert("solc_version".into(), version.into());
+    profile.insert("optimizer".into(), settings.pointer("/optimizer/enabled").and_then(|v| v.as_bool()).unwrap_or(false).into());
+    if let Some(runs) = settings.pointer("/optimizer/runs").and_then(|v| v.as_i64()) {
+        profile.insert("optimizer_runs".into(), runs.into());
+    }
+    if let Some(evm_version) = settings.get("evmVersion").and_then(|v| v.as_str()) {
+        profile.insert("evm_version".into(), evm_version.into());
+    }
+    if settings.get("viaIR").and_then(|v| v.as_bool()) == Some(true) {
+        profile.insert("via_ir".into(), true.into());
+    }
+    if let Some(bytecode_hash) = settings.pointer("/metadata/bytecodeHash").and_then(|v| v.as_str()) {
+        profile.insert("bytecode_hash".into(), bytecode_hash.into());
+    }
+    if let Some(remappings) = settings.get("remappings").and_then(|v| v.as_array()) {
+        let remappings = remappings.iter().filter_map(|r| r.as_str()).map(toml::Value::from).collect();
+        profile.insert("remappings".into(), toml::Value::Array(remappings));
+    }
+
+    let mut profiles = toml::Table::new();
+    profiles.insert("default".into(), toml::Value::Table(profile));
+    let mut config = toml::Table::new();
+    config.insert("profile".into(), toml::Value::Table(profiles));
+    config
+}
+
+// Write the sources into a scratch project, build it with forge and return the contract's artifact
+pub async fn compile(source: &VerifiedSource, root: &Path) -> Result<Value, String> {
+    let sources = source.input.get("sources").and_then(|s| s.as_object()).ok_or("Verified source has no files")?;
+    for (path, file) in sources {
+        // Paths come from a third party, never let one escape the scratch project
+        if Path::new(path).components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+            return Err(format!("Refusing to write source outside the project: {}", path));
+        }
+        let content = file.get("content").and_then(|c| c.as_str()).ok_or_else(|| format!("No content for {}", path))?;
+        let target = root.join(path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&target, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    std::fs::write(root.join("foundry.toml"), foundry_config(source).to_string())
+        .map_err(|e| format!("Failed to write foundry.toml: {}", e))?;
+
+    let forge = std::env::var("FORGE_BIN").unwrap_or_else(|_| "forge".to_string());
+    let build = tokio::process::Command::new(&forge)
+        .args(["build", "--root"])
+        .arg(root)
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(BUILD_TIMEOUT, build)
+        .await
+        .map_err(|_| format!("forge build timed out after {}s", BUILD_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run {}: {}", forge, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let log = if stderr.trim().is_empty() { stdout } else { stderr };
+        return Err(format!("forge build failed: {}", log.chars().take(2000).collect::<String>()));
+    }
+
+    let file_name = Path::new(&source.path).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let artifact: PathBuf = root.join("out").join(file_name).join(format!("{}.json", source.name));
+    let artifact = std::fs::read_to_string(&artifact).map_err(|e| format!("No artifact for {}: {}", source.name, e))?;
+    serde_json::from_str(&artifact).map_err(|e| format!("Invalid artifact: {}", e))
+}
+
+// Byte ranges of a bytecode section's references, e.g. immutables or library links
+fn ranges(references: Option<&Value>) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut collect = |entries: &Value| {
+        for entry in entries.as_array().into_iter().flatten() {
+            if let (Some(start), Some(length)) = (entry["start"].as_u64(), entry["length"].as_u64()) {
+                ranges.push((start as usize, length as usize));
+            }
+        }
+    };
+    for (_, value) in references.and_then(|r| r.as_object()).into_iter().flatten() {
+        // linkReferences nest one level deeper: file -> library -> ranges
+        match value {
+            Value::Object(libraries) => libraries.values().for_each(&mut collect),
+            entries => collect(entries),
+        }
+    }
+    ranges
+}
+
+fn mask(code: &mut [u8], ranges: &[(usize, usize)]) {
+    for &(start, length) in ranges {
+        if let Some(bytes) = code.get_mut(start..start + length) {
+            bytes.fill(0);
+        }
+    }
+}
+
+// Code without the trailing CBOR metadata, whose length is stored in the last two bytes
+fn without_metadata(code: &[u8]) -> &[u8] {
+    let Some(length) = code.len().checked_sub(2).map(|end| u16::from_be_bytes([code[end], code[end + 1]]) as usize) else {
+        return code;
+    };
+    match code.len().checked_sub(length + 2) {
+        // CBOR maps of the metadata's 1 to 3 entries start with 0xa1..0xa3
+        Some(start) if (0xa1..=0xa3).contains(&code[start]) => &code[..start],
+        _ => code,
+    }
+}
+
+// Compare the deployed runtime code with the compiled one. Immutables are filled in at deployment
+// and libraries at link time, so those ranges are zeroed on both sides first.
+pub fn diff(deployed: &Bytes, artifact: &Value) -> Value {
+    let bytecode = &artifact["deployedBytecode"];
+    // Unlinked libraries show up as 40 character "__$...$__" placeholders in the hex
+    let mut object = bytecode["object"].as_str().unwrap_or_default().trim_start_matches("0x").to_string();
+    while let Some(start) = object.find("__$") {
+        object.replace_range(start..(start + 40).min(object.len()), &"0".repeat(40));
+    }
+    let mut compiled = hex::decode(&object).unwrap_or_default();
+    let mut deployed = deployed.to_vec();
+
+    let immutables = ranges(bytecode.get("immutableReferences"));
+    let libraries = ranges(bytecode.get("linkReferences"));
+    for code in [&mut compiled, &mut deployed] {
+        mask(code, &immutables);
+        mask(code, &libraries);
+    }
+
+    let (result, first_difference) = if compiled == deployed {
+        ("exact", None)
+    } else {
+        let (compiled, deployed) = (without_metadata(&compiled), without_metadata(&deployed));
+        if compiled == deployed {
+            ("metadata", None)
+        } else {
+            let offset = compiled.iter().zip(deployed).position(|(a, b)| a != b).unwrap_or(compiled.len().min(deployed.len()));
+            ("mismatch", Some(offset))
+        }
+    };
+
+    json!({
+        "match": result,
+        "verdict": match result {
+            "exact" => "Deployed code matches the verified source exactly",
+            // Comments, file paths or whitespace changed, the code itself is the same
+            "metadata" => "Deployed code matches the verified source apart from its metadata hash",
+            _ => "Deployed code DOES NOT match the verified source",
+        },
+        "first_difference": first_difference,
+        "deployed_size": deployed.len(),
+        "compiled_size": compiled.len(),
+        "immutables_masked": immutables.len(),
+        "library_links_masked": libraries.len()
+    })
+}
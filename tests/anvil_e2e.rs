@@ -0,0 +1,222 @@
+// End-to-end tests: the real foundry-mcp binary over stdio against a local anvil, driven by
+// scripted plans. Skipped when anvil (or forge, for the token test) is not installed.
+
+use alloy::node_bindings::{Anvil, AnvilInstance};
+use alloy::primitives::{address, keccak256, utils::parse_ether, Address, Bytes, B256, U256};
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use eth_agent_core::testing::*;
+use eth_agent_core::{EthAgent, McpConnector, UserPrompt};
+use serde_json::json;
+
+sol! {
+    #[sol(rpc)]
+    contract TestToken {
+        function balanceOf(address account) external view returns (uint256);
+        function transfer(address to, uint256 amount) external returns (bool);
+    }
+}
+
+// Default anvil accounts, unlocked so foundry-mcp can send from them
+const ALICE: Address = address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+const BOB: Address = address!("70997970C51812dc3A010C7d01b50e0d17dc79C8");
+
+// Init code for a contract whose runtime code returns 42 for any call
+const ANSWER_INIT_CODE: &str = "0x600a600c600039600a6000f3602a60005260206000f3";
+const ANSWER_RUNTIME_CODE: &str = "0x602a60005260206000f3";
+
+fn start_anvil() -> Option<AnvilInstance> {
+    match Anvil::new().try_spawn() {
+        Ok(anvil) => Some(anvil),
+        Err(e) => {
+            eprintln!("skipping, anvil is not available: {}", e);
+            None
+        }
+    }
+}
+
+fn foundry_mcp(anvil: &AnvilInstance) -> McpConnector {
+    let endpoint = anvil.endpoint();
+    process_connector(move || {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_foundry-mcp"));
+        command.env("FOUNDRY_ETH_RPC_URL", &endpoint);
+        command
+    })
+}
+
+fn agent(client: ScriptedClient, anvil: &AnvilInstance) -> EthAgent<ScriptedClient> {
+    EthAgent::new(client, None, test_config())
+        .unwrap()
+        .with_mcp_connector(foundry_mcp(anvil))
+}
+
+// Compile the fixture token with forge, returning its init code
+fn token_init_code() -> Option<String> {
+    let output = std::process::Command::new("forge")
+        .args(["inspect", "tests/fixtures/TestToken.sol:TestToken", "bytecode"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        Ok(output) => {
+            eprintln!("skipping, forge could not compile the token: {}", String::from_utf8_lossy(&output.stderr));
+            None
+        }
+        Err(e) => {
+            eprintln!("skipping, forge is not available: {}", e);
+            None
+        }
+    }
+}
+
+#[tokio::test]
+async fn transfers_eth() {
+    let Some(anvil) = start_anvil() else { return };
+    let provider = ProviderBuilder::new().connect_http(anvil.endpoint_url());
+    let bob_before = provider.get_balance(BOB).await.unwrap();
+    let one_eth = parse_ether("1").unwrap();
+
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Send 1 ETH from Alice to Bob")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({
+            "from": ALICE.to_string(),
+            "to": BOB.to_string(),
+            "value": one_eth.to_string(),
+        })))
+        .reply(EXECUTOR, ScriptedReply::text("Sent 1 ETH to Bob"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let mut agent = agent(client, &anvil);
+
+    agent.run(UserPrompt::new("Send 1 ETH from Alice to Bob")).await.unwrap();
+
+    assert_eq!(provider.get_balance(BOB).await.unwrap(), bob_before + one_eth);
+    assert_eq!(agent.last_run().unwrap().transaction_hashes().len(), 1);
+}
+
+#[tokio::test]
+async fn deploys_a_contract() {
+    let Some(anvil) = start_anvil() else { return };
+    let provider = ProviderBuilder::new().connect_http(anvil.endpoint_url());
+    let contract_address = ALICE.create(provider.get_transaction_count(ALICE).await.unwrap());
+
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Deploy the contract from Alice")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({
+            "from": ALICE.to_string(),
+            "to": "",
+            "value": "0",
+            "data": ANSWER_INIT_CODE,
+        })))
+        .reply(EXECUTOR, ScriptedReply::text(format!("Deployed at {}", contract_address)))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let mut agent = agent(client, &anvil);
+
+    agent.run(UserPrompt::new("Deploy the contract from Alice")).await.unwrap();
+
+    let code = provider.get_code_at(contract_address).await.unwrap();
+    assert_eq!(code, ANSWER_RUNTIME_CODE.parse::<Bytes>().unwrap());
+}
+
+#[tokio::test]
+async fn swaps_contract_code_on_the_fork() {
+    let Some(anvil) = start_anvil() else { return };
+    let provider = ProviderBuilder::new().connect_http(anvil.endpoint_url());
+    let oracle = address!("5f4eC3Df9cbd43714FE2740f5E3616155c5b8419");
+
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Replace the oracle with a mock returning 42")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("set_code", json!({
+            "address": oracle.to_string(),
+            "code": ANSWER_RUNTIME_CODE,
+        })))
+        .reply(EXECUTOR, ScriptedReply::text("The oracle now returns 42"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let mut agent = agent(client, &anvil);
+
+    agent.run(UserPrompt::new("Mock the ETH/USD oracle with one returning 42")).await.unwrap();
+
+    let code = provider.get_code_at(oracle).await.unwrap();
+    assert_eq!(code, ANSWER_RUNTIME_CODE.parse::<Bytes>().unwrap());
+}
+
+#[tokio::test]
+async fn writes_contract_storage_on_the_fork() {
+    let Some(anvil) = start_anvil() else { return };
+    let provider = ProviderBuilder::new().connect_http(anvil.endpoint_url());
+    let token = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[("ethereum_agent", "Give Alice a balance of 1000 in slot 9 of the token")])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("set_storage_at", json!({
+            "address": token.to_string(),
+            "slot": "9",
+            "mapping_key": ALICE.to_string(),
+            "value": "1000",
+        })))
+        .reply(EXECUTOR, ScriptedReply::text("Alice now has 1000"))
+        .reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")));
+    let mut agent = agent(client, &anvil);
+
+    agent.run(UserPrompt::new("Give Alice 1000 of the token")).await.unwrap();
+
+    // balances[alice] of a mapping declared at slot 9
+    let slot = keccak256([ALICE.into_word().as_slice(), B256::with_last_byte(9).as_slice()].concat());
+    let value = provider.get_storage_at(token, U256::from_be_bytes(slot.0)).await.unwrap();
+    assert_eq!(value, U256::from(1000));
+}
+
+#[tokio::test]
+async fn deploys_and_transfers_an_erc20() {
+    let Some(anvil) = start_anvil() else { return };
+    let Some(init_code) = token_init_code() else { return };
+    let provider = ProviderBuilder::new().connect_http(anvil.endpoint_url());
+    let token = ALICE.create(provider.get_transaction_count(ALICE).await.unwrap());
+    let amount = parse_ether("100").unwrap();
+
+    let client = ScriptedClient::new()
+        .reply(PLANNER, ScriptedReply::text(plan_json(&[
+            ("ethereum_agent", "Deploy the test token from Alice"),
+            ("ethereum_agent", "Transfer 100 TT from Alice to Bob"),
+            ("ethereum_agent", "Check the TT balance of Bob"),
+        ])))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({
+            "from": ALICE.to_string(),
+            "to": "",
+            "value": "0",
+            "data": init_code,
+        })))
+        .reply(EXECUTOR, ScriptedReply::text(format!("Deployed TT at {}", token)))
+        .reply(EXECUTOR, ScriptedReply::tool_call("send_transaction", json!({
+            "from": ALICE.to_string(),
+            "to": token.to_string(),
+            "value": "0",
+            "data": Bytes::from(TestToken::transferCall { to: BOB, amount }.abi_encode()).to_string(),
+        })))
+        .reply(EXECUTOR, ScriptedReply::text("Transferred 100 TT to Bob"))
+        .reply(EXECUTOR, ScriptedReply::tool_call("erc20_balance", json!({
+            "address": BOB.to_string(),
+            "token_address": token.to_string(),
+        })))
+        .reply(EXECUTOR, ScriptedReply::text("Bob has 100 TT"));
+    let client = (0..3).fold(client, |client, _| {
+        client.reply(EVALUATOR, ScriptedReply::text(evaluation_json(90, "Correct")))
+    });
+    let mut agent = agent(client, &anvil);
+
+    let result = agent.run(UserPrompt::new("Deploy the test token, send 100 TT to Bob and check his balance")).await.unwrap();
+
+    assert_eq!(result.result, "Bob has 100 TT");
+    let token = TestToken::new(token, provider);
+    assert_eq!(token.balanceOf(BOB).call().await.unwrap(), amount);
+    assert_eq!(token.balanceOf(ALICE).call().await.unwrap(), parse_ether("999900").unwrap());
+
+    let run = agent.last_run().unwrap();
+    assert_eq!(run.transaction_hashes().len(), 2);
+
+    // The last step read the balance through the real erc20_balance tool
+    let balance_call = &run.steps[2].tool_calls[0];
+    assert_eq!(balance_call.tool, "erc20_balance");
+    assert!(balance_call.result.as_ref().unwrap().to_string().contains(&amount.to_string()));
+}